@@ -0,0 +1,634 @@
+//! Audio waveform sync for dual-system sound.
+//!
+//! Finds where recordings sit relative to a reference by cross-correlating
+//! downsampled amplitude envelopes with an FFT, then refines each lag to the
+//! sample by correlating the waveforms directly around the coarse match.
+//! Long overlaps are measured again window by window to detect clock drift
+//! between the recorders. Recordings made at another rate than the reference
+//! are resampled to its rate first.
+
+use crate::error::{AiError, AiResult};
+use crate::stem_separation::AudioBuffer;
+use proedit_core::RationalTime;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// Configuration for audio sync.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncConfig {
+    /// Envelope rate in Hz for the coarse FFT search.
+    pub envelope_rate: u32,
+    /// Largest offset searched in seconds (0 = unlimited).
+    pub max_offset_secs: f64,
+    /// Length of the waveform excerpt used for sample-accurate refinement.
+    pub refine_secs: f64,
+    /// Window length in seconds for drift measurement. Overlaps shorter
+    /// than three windows are not checked for drift.
+    pub drift_window_secs: f64,
+    /// Largest offset change between drift windows searched, in seconds.
+    pub max_drift_secs: f64,
+}
+
+impl Default for SyncConfig {
+    fn default() -> Self {
+        Self {
+            envelope_rate: 1000,
+            max_offset_secs: 0.0,
+            refine_secs: 0.5,
+            drift_window_secs: 30.0,
+            max_drift_secs: 0.05,
+        }
+    }
+}
+
+/// Offset of one recording measured at one point of the overlap.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DriftPoint {
+    /// Position on the reference in seconds.
+    pub time_secs: f64,
+    /// Offset in samples measured there.
+    pub offset_samples: i64,
+}
+
+/// Clock drift between two recordings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DriftEstimate {
+    /// Change of the offset per second of reference, in parts per million.
+    /// Positive when the other recorder's clock runs slow.
+    pub ppm: f64,
+    /// The per-window measurements the estimate was fitted to.
+    pub points: Vec<DriftPoint>,
+}
+
+/// Where a recording sits relative to the reference.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncResult {
+    /// Position of the recording's first sample on the reference's
+    /// timeline. Negative when the recording started before the reference.
+    pub offset: RationalTime,
+    /// `offset` in samples at `sample_rate`.
+    pub offset_samples: i64,
+    /// Sample rate of the reference, which offsets are counted at.
+    pub sample_rate: u32,
+    /// How distinct the match is (0.0 to 1.0).
+    pub confidence: f32,
+    /// Clock drift, when the overlap was long enough to measure it.
+    pub drift: Option<DriftEstimate>,
+}
+
+impl SyncResult {
+    /// The result for the reference itself.
+    pub fn reference(sample_rate: u32) -> Self {
+        Self {
+            offset: RationalTime::ZERO,
+            offset_samples: 0,
+            sample_rate,
+            confidence: 1.0,
+            drift: None,
+        }
+    }
+
+    /// Timeline starts for the (reference, recording) clip pair, with the
+    /// earlier of the two at zero.
+    pub fn clip_starts(&self) -> (RationalTime, RationalTime) {
+        if self.offset >= RationalTime::ZERO {
+            (RationalTime::ZERO, self.offset)
+        } else {
+            (-self.offset, RationalTime::ZERO)
+        }
+    }
+}
+
+/// Syncs recordings of the same event against each other.
+pub struct AudioSync {
+    config: SyncConfig,
+}
+
+impl AudioSync {
+    /// Create a new audio sync.
+    pub fn new(config: SyncConfig) -> Self {
+        Self { config }
+    }
+
+    /// Get the configuration.
+    pub fn config(&self) -> &SyncConfig {
+        &self.config
+    }
+
+    /// Find where `other` sits relative to `reference`.
+    ///
+    /// `other` is resampled to the reference's rate if the two differ.
+    pub fn sync(&self, reference: &AudioBuffer, other: &AudioBuffer) -> AiResult<SyncResult> {
+        validate(reference)?;
+        validate(other)?;
+        let rate = reference.sample_rate;
+        let factor = (rate / self.config.envelope_rate.max(1)).max(1) as usize;
+        let a = mono(reference);
+        let mut b = mono(other);
+        if other.sample_rate != rate {
+            b = resample(&b, other.sample_rate, rate);
+        }
+        let ea = envelope(&a, factor);
+        let eb = envelope(&b, factor);
+
+        let max_lag = if self.config.max_offset_secs > 0.0 {
+            (self.config.max_offset_secs * rate as f64 / factor as f64).ceil() as i64
+        } else {
+            i64::MAX
+        };
+        let exclusion = (self.config.envelope_rate as usize / 20).max(2);
+        let (coarse, confidence) = coarse_lag(&ea, &eb, max_lag, exclusion);
+
+        let refine_len = (self.config.refine_secs * rate as f64) as usize;
+        let center = coarse * factor as i64;
+        let offset_samples = refine_lag(
+            &a,
+            &b,
+            center,
+            2 * factor as i64,
+            overlap(a.len(), b.len(), center),
+            refine_len,
+        );
+        let drift = self.measure_drift(&a, &b, &ea, &eb, offset_samples, factor, rate);
+
+        Ok(SyncResult {
+            offset: RationalTime::new(offset_samples, rate as i64),
+            offset_samples,
+            sample_rate: rate,
+            confidence,
+            drift,
+        })
+    }
+
+    /// Sync every buffer against the first. The first result is the
+    /// reference itself.
+    pub fn sync_all(&self, buffers: &[AudioBuffer]) -> AiResult<Vec<SyncResult>> {
+        let Some((reference, others)) = buffers.split_first() else {
+            return Ok(Vec::new());
+        };
+        validate(reference)?;
+        let mut results = vec![SyncResult::reference(reference.sample_rate)];
+        for other in others {
+            results.push(self.sync(reference, other)?);
+        }
+        Ok(results)
+    }
+
+    /// Re-measure the offset window by window across the overlap and fit
+    /// a line through the measurements.
+    #[allow(clippy::too_many_arguments)]
+    fn measure_drift(
+        &self,
+        a: &[f32],
+        b: &[f32],
+        ea: &[f32],
+        eb: &[f32],
+        lag: i64,
+        factor: usize,
+        rate: u32,
+    ) -> Option<DriftEstimate> {
+        let window = (self.config.drift_window_secs * rate as f64) as usize;
+        let span = overlap(a.len(), b.len(), lag);
+        if window == 0 || span.len() < 3 * window {
+            return None;
+        }
+        let search = (self.config.max_drift_secs * rate as f64 / factor as f64).ceil() as i64;
+        let refine_len = (self.config.refine_secs * rate as f64) as usize;
+
+        let mut points = Vec::new();
+        for w in 0..span.len() / window {
+            let range = span.start + w * window..span.start + (w + 1) * window;
+            let env_range = range.start / factor..(range.end / factor).min(eb.len());
+            let env_center = lag.div_euclid(factor as i64);
+            let local = local_lag(ea, eb, env_range, env_center, search);
+            let offset = refine_lag(
+                a,
+                b,
+                local * factor as i64,
+                2 * factor as i64,
+                range.clone(),
+                refine_len,
+            );
+            let middle = (range.start + range.end) as f64 / 2.0;
+            points.push(DriftPoint {
+                time_secs: (middle + offset as f64) / rate as f64,
+                offset_samples: offset,
+            });
+        }
+
+        // Least-squares slope of offset (seconds) against reference time.
+        let n = points.len() as f64;
+        let mean_t = points.iter().map(|p| p.time_secs).sum::<f64>() / n;
+        let mean_o = points
+            .iter()
+            .map(|p| p.offset_samples as f64 / rate as f64)
+            .sum::<f64>()
+            / n;
+        let (mut num, mut den) = (0.0, 0.0);
+        for p in &points {
+            let dt = p.time_secs - mean_t;
+            num += dt * (p.offset_samples as f64 / rate as f64 - mean_o);
+            den += dt * dt;
+        }
+        let ppm = if den > 0.0 { num / den * 1e6 } else { 0.0 };
+        Some(DriftEstimate { ppm, points })
+    }
+}
+
+impl Default for AudioSync {
+    fn default() -> Self {
+        Self::new(SyncConfig::default())
+    }
+}
+
+fn validate(buffer: &AudioBuffer) -> AiResult<()> {
+    if buffer.channels == 0 {
+        return Err(AiError::PreprocessError("Zero channels".into()));
+    }
+    if buffer.sample_rate == 0 {
+        return Err(AiError::PreprocessError("Zero sample rate".into()));
+    }
+    if buffer.frame_count() == 0 {
+        return Err(AiError::PreprocessError("Empty audio buffer".into()));
+    }
+    Ok(())
+}
+
+/// Average the channels of an interleaved buffer.
+fn mono(buffer: &AudioBuffer) -> Vec<f32> {
+    let channels = buffer.channels as usize;
+    buffer
+        .samples
+        .chunks_exact(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect()
+}
+
+/// Linearly interpolate `samples` from `from` Hz to `to` Hz. Downsampled
+/// audio is not low-passed first; the envelopes and the refinement only
+/// need the bands both rates carry to line up.
+fn resample(samples: &[f32], from: u32, to: u32) -> Vec<f32> {
+    let step = from as f64 / to as f64;
+    let len = ((samples.len() as f64 / step) as usize).max(1);
+    (0..len)
+        .map(|i| {
+            let pos = i as f64 * step;
+            let index = (pos as usize).min(samples.len() - 1);
+            let frac = (pos - index as f64) as f32;
+            let next = samples.get(index + 1).copied().unwrap_or(samples[index]);
+            samples[index] + (next - samples[index]) * frac
+        })
+        .collect()
+}
+
+/// RMS per block of `factor` samples, with the mean removed.
+fn envelope(samples: &[f32], factor: usize) -> Vec<f32> {
+    let mut env: Vec<f32> = samples
+        .chunks(factor)
+        .map(|block| (block.iter().map(|s| s * s).sum::<f32>() / block.len() as f32).sqrt())
+        .collect();
+    let mean = env.iter().sum::<f32>() / env.len().max(1) as f32;
+    for value in &mut env {
+        *value -= mean;
+    }
+    env
+}
+
+/// Range of `other` indices that overlap `reference` when `other` starts
+/// at `lag` on the reference.
+fn overlap(reference_len: usize, other_len: usize, lag: i64) -> Range<usize> {
+    let start = (-lag).clamp(0, other_len as i64) as usize;
+    let end = (reference_len as i64 - lag).clamp(start as i64, other_len as i64) as usize;
+    start..end
+}
+
+/// Full cross-correlation of the envelopes via FFT. Returns the lag of the
+/// highest peak within `max_lag` and how far it stands above the best peak
+/// more than `exclusion` blocks away.
+fn coarse_lag(ea: &[f32], eb: &[f32], max_lag: i64, exclusion: usize) -> (i64, f32) {
+    let n = (ea.len() + eb.len()).next_power_of_two();
+    let mut fa = vec![Complex::default(); n];
+    let mut fb = vec![Complex::default(); n];
+    for (dst, &v) in fa.iter_mut().zip(ea) {
+        dst.re = v as f64;
+    }
+    for (dst, &v) in fb.iter_mut().zip(eb) {
+        dst.re = v as f64;
+    }
+    fft(&mut fa, false);
+    fft(&mut fb, false);
+    for (x, y) in fa.iter_mut().zip(&fb) {
+        *x = x.mul(y.conj());
+    }
+    fft(&mut fa, true);
+
+    // corr[k] = sum a[i + k] * b[i]; negative lags wrap to the end.
+    let lo = -(eb.len() as i64 - 1).max(0).min(max_lag);
+    let hi = (ea.len() as i64 - 1).min(max_lag);
+    let corr = |k: i64| fa[k.rem_euclid(n as i64) as usize].re;
+
+    let mut best = 0;
+    let mut peak = f64::MIN;
+    for k in lo..=hi {
+        if corr(k) > peak {
+            peak = corr(k);
+            best = k;
+        }
+    }
+    let second = (lo..=hi)
+        .filter(|k| k.abs_diff(best) > exclusion as u64)
+        .map(corr)
+        .fold(0.0_f64, f64::max);
+    let confidence = if peak > 0.0 {
+        ((peak - second) / peak).clamp(0.0, 1.0) as f32
+    } else {
+        0.0
+    };
+    (best, confidence)
+}
+
+/// Direct envelope correlation of `eb[range]` over lags within `search`
+/// blocks of `center`.
+fn local_lag(ea: &[f32], eb: &[f32], range: Range<usize>, center: i64, search: i64) -> i64 {
+    let mut best = center;
+    let mut peak = f32::MIN;
+    for lag in center - search..=center + search {
+        let sum: f32 = eb[range.clone()]
+            .iter()
+            .enumerate()
+            .filter_map(|(i, &v)| {
+                let j = (range.start + i) as i64 + lag;
+                (0..ea.len() as i64)
+                    .contains(&j)
+                    .then(|| v * ea[j as usize])
+            })
+            .sum();
+        if sum > peak {
+            peak = sum;
+            best = lag;
+        }
+    }
+    best
+}
+
+/// Sample-accurate lag within `radius` of `center`, correlating the
+/// loudest `len`-sample excerpt of `b[range]` against the reference.
+fn refine_lag(
+    a: &[f32],
+    b: &[f32],
+    center: i64,
+    radius: i64,
+    range: Range<usize>,
+    len: usize,
+) -> i64 {
+    let len = len.min(range.len());
+    if len == 0 {
+        return center;
+    }
+    // Pick the loudest of a few evenly spaced excerpts.
+    let candidates = (range.len() / len).clamp(1, 8);
+    let step = if candidates > 1 {
+        (range.len() - len) / (candidates - 1)
+    } else {
+        0
+    };
+    let start = (0..candidates)
+        .map(|c| range.start + c * step)
+        .max_by(|&x, &y| {
+            let energy = |s: usize| b[s..s + len].iter().map(|v| v * v).sum::<f32>();
+            energy(x).total_cmp(&energy(y))
+        })
+        .unwrap_or(range.start);
+    let excerpt = &b[start..start + len];
+
+    let mut best = center;
+    let mut peak = f64::MIN;
+    for lag in center - radius..=center + radius {
+        let (mut dot, mut energy) = (0.0_f64, 0.0_f64);
+        for (i, &v) in excerpt.iter().enumerate() {
+            let j = (start + i) as i64 + lag;
+            if let Some(&r) = usize::try_from(j).ok().and_then(|j| a.get(j)) {
+                dot += v as f64 * r as f64;
+                energy += r as f64 * r as f64;
+            }
+        }
+        let score = if energy > 0.0 {
+            dot / energy.sqrt()
+        } else {
+            0.0
+        };
+        if score > peak {
+            peak = score;
+            best = lag;
+        }
+    }
+    best
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Complex {
+    re: f64,
+    im: f64,
+}
+
+impl Complex {
+    fn mul(self, o: Self) -> Self {
+        Self {
+            re: self.re * o.re - self.im * o.im,
+            im: self.re * o.im + self.im * o.re,
+        }
+    }
+
+    fn conj(self) -> Self {
+        Self {
+            re: self.re,
+            im: -self.im,
+        }
+    }
+}
+
+/// In-place iterative radix-2 FFT. `data.len()` must be a power of two.
+fn fft(data: &mut [Complex], inverse: bool) {
+    let n = data.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            data.swap(i, j);
+        }
+    }
+
+    let sign = if inverse { 1.0 } else { -1.0 };
+    let mut len = 2;
+    while len <= n {
+        let angle = sign * 2.0 * std::f64::consts::PI / len as f64;
+        let step = Complex {
+            re: angle.cos(),
+            im: angle.sin(),
+        };
+        for chunk in data.chunks_exact_mut(len) {
+            let (lo, hi) = chunk.split_at_mut(len / 2);
+            let mut w = Complex { re: 1.0, im: 0.0 };
+            for (u, v) in lo.iter_mut().zip(hi.iter_mut()) {
+                let t = v.mul(w);
+                *v = Complex {
+                    re: u.re - t.re,
+                    im: u.im - t.im,
+                };
+                u.re += t.re;
+                u.im += t.im;
+                w = w.mul(step);
+            }
+        }
+        len <<= 1;
+    }
+
+    if inverse {
+        for x in data.iter_mut() {
+            x.re /= n as f64;
+            x.im /= n as f64;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Noise with a randomly stepping amplitude, like speech bursts.
+    fn bursty_noise(frames: usize, rate: u32, seed: u64) -> Vec<f32> {
+        let mut state = seed;
+        let mut next = move || {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            ((state >> 33) as f32 / (1u64 << 31) as f32) * 2.0 - 1.0
+        };
+        let block = rate as usize / 20;
+        let mut gain = 0.0;
+        (0..frames)
+            .map(|i| {
+                if i % block == 0 {
+                    gain = next().abs();
+                }
+                next() * gain
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_finds_positive_and_negative_offsets() {
+        let rate = 8000;
+        let source = bursty_noise(rate as usize * 20, rate, 1);
+        let reference = AudioBuffer::from_samples(source[..rate as usize * 12].to_vec(), rate, 1);
+
+        // Starts 3.5 s into the reference, stereo with a little noise.
+        let late: Vec<f32> = source[28000..rate as usize * 16]
+            .iter()
+            .zip(bursty_noise(rate as usize * 13, rate, 9))
+            .flat_map(|(s, n)| [s + n * 0.05, s - n * 0.05])
+            .collect();
+        let late = AudioBuffer::from_samples(late, rate, 2);
+        let sync = AudioSync::default();
+        let result = sync.sync(&reference, &late).unwrap();
+        assert_eq!(result.offset_samples, 28000);
+        assert_eq!(result.offset, RationalTime::new(7, 2));
+        assert!(result.confidence > 0.5, "{}", result.confidence);
+        assert_eq!(result.clip_starts(), (RationalTime::ZERO, result.offset));
+
+        // The reference seen from the late recording starts earlier.
+        let result = sync.sync(&late, &reference).unwrap();
+        assert_eq!(result.offset_samples, -28000);
+        assert_eq!(
+            result.clip_starts(),
+            (RationalTime::new(7, 2), RationalTime::ZERO)
+        );
+        assert!(result.drift.is_none());
+    }
+
+    #[test]
+    fn test_sync_all_and_unrelated_confidence() {
+        let rate = 8000;
+        let source = bursty_noise(rate as usize * 10, rate, 2);
+        let buffers = [
+            AudioBuffer::from_samples(source.clone(), rate, 1),
+            AudioBuffer::from_samples(source[12345..].to_vec(), rate, 1),
+            AudioBuffer::from_samples(source[..50000].to_vec(), rate, 1),
+        ];
+        let results = AudioSync::default().sync_all(&buffers).unwrap();
+        let offsets: Vec<i64> = results.iter().map(|r| r.offset_samples).collect();
+        assert_eq!(offsets, [0, 12345, 0]);
+
+        let unrelated =
+            AudioBuffer::from_samples(bursty_noise(rate as usize * 10, rate, 3), rate, 1);
+        let result = AudioSync::default().sync(&buffers[0], &unrelated).unwrap();
+        assert!(result.confidence < 0.3, "{}", result.confidence);
+    }
+
+    #[test]
+    fn test_syncs_recordings_at_different_rates() {
+        let rate = 8000;
+        let source = bursty_noise(rate as usize * 12, rate, 5);
+        let reference = AudioBuffer::from_samples(source[..rate as usize * 10].to_vec(), rate, 1);
+        // The same event recorded at twice the rate, starting 2.5 s in.
+        let fast =
+            AudioBuffer::from_samples(resample(&source[20000..], rate, 2 * rate), 2 * rate, 1);
+
+        let sync = AudioSync::default();
+        let result = sync.sync(&reference, &fast).unwrap();
+        assert_eq!(result.sample_rate, rate);
+        assert_eq!(result.offset_samples, 20000);
+        assert_eq!(result.offset, RationalTime::new(5, 2));
+
+        let result = sync.sync(&fast, &reference).unwrap();
+        assert_eq!(result.sample_rate, 2 * rate);
+        assert_eq!(result.offset_samples, -40000);
+
+        assert!(sync.sync(&reference, &AudioBuffer::new(rate, 1)).is_err());
+    }
+
+    #[test]
+    fn test_detects_drift() {
+        // The second recorder's clock runs 100 ppm fast.
+        let rate = 8000;
+        let source = bursty_noise(rate as usize * 130, rate, 4);
+        let reference = AudioBuffer::from_samples(source[..rate as usize * 125].to_vec(), rate, 1);
+        let ratio = 1.0 / (1.0 + 100e-6);
+        let start = 4000.0;
+        let fast: Vec<f32> = (0..rate as usize * 120)
+            .map(|i| {
+                let pos = start + i as f64 * ratio;
+                let (index, frac) = (pos as usize, (pos - pos.floor()) as f32);
+                source[index] * (1.0 - frac) + source[index + 1] * frac
+            })
+            .collect();
+        let fast = AudioBuffer::from_samples(fast, rate, 1);
+
+        let result = AudioSync::default().sync(&reference, &fast).unwrap();
+        assert!((result.offset_samples - 4000).abs() <= 100);
+        let drift = result.drift.unwrap();
+        assert_eq!(drift.points.len(), 4);
+        assert!((drift.ppm + 100.0).abs() < 15.0, "{}", drift.ppm);
+    }
+
+    #[test]
+    fn test_fft_round_trip() {
+        let mut data: Vec<Complex> = (0..16)
+            .map(|i| Complex {
+                re: i as f64,
+                im: 0.0,
+            })
+            .collect();
+        fft(&mut data, false);
+        assert!((data[0].re - 120.0).abs() < 1e-9);
+        fft(&mut data, true);
+        for (i, x) in data.iter().enumerate() {
+            assert!((x.re - i as f64).abs() < 1e-9 && x.im.abs() < 1e-9);
+        }
+    }
+}
@@ -8,6 +8,7 @@
 //! - AI upscaling (Real-ESRGAN via ONNX, requires `onnx` feature)
 //! - Auto-rotoscoping / intelligent masking (SAM 2)
 //! - Audio stem separation (Demucs v4)
+//! - Dual-system sound sync (FFT envelope cross-correlation with drift detection)
 //! - Speaker diarization
 //! - Visual content indexing (CLIP embeddings) and natural language search
 //! - Audio classification and quality detection
@@ -20,6 +21,7 @@
 
 pub mod analysis_store;
 pub mod audio_classify;
+pub mod audio_sync;
 pub mod auto_color;
 pub mod auto_edit;
 pub mod content_index;
@@ -1,14 +1,49 @@
-//! CPU frame compositor — builds a render graph per frame and composites layers.
+//! CPU frame compositor — builds a render graph per frame and runs it.
 //!
-//! Uses `RenderGraph` from `proedit-gpu` for dependency ordering, but executes
-//! compositing on the CPU for portability. GPU path can be added later using
-//! `GpuContext`, `BlitPipeline`, and `TexturePool`.
+//! Uses `RenderGraph` from `proedit-gpu` for the layer structure and
+//! executes it with a `GraphExecutor` on the `CpuBackend`, so layers that
+//! did not change since the previous frame come from the node cache. A GPU
+//! backend can be plugged into the same executor later.
+//!
+//! Layers are composited in the sequence's linear working space, in a float
+//! format; frames are encoded to sRGB only when they leave for display.
+//!
+//! Effects become `Effect` nodes in the layer chain: a clip's own effects
+//! and then its track's follow the clip's source, and an adjustment layer's
+//! effects take the composite of everything beneath it as their input.
+//!
+//! Generator clips are sources too: their frames are synthesized by a
+//! `GeneratorRenderer` at the clip's local time rather than decoded.
+//!
+//! During a clip's transition its lane shows a `Transition` node blending
+//! the outgoing clip's layer into the incoming one's, rendered by the
+//! `TransitionRegistry` implementation of that name in the working space.
 
 #![allow(dead_code)]
 
-use proedit_core::{FrameBuffer, PixelFormat};
+use proedit_color::WorkingSpace;
+use proedit_core::{
+    ColorSpace, FrameBuffer, FrameRate, ParamValue, ParamValues, PixelFormat, ProEditError,
+    RationalTime, Result,
+};
+use proedit_effects::transition::TransitionRegistry;
+use proedit_effects::EffectsRegistry;
+use proedit_generators::GeneratorRenderer;
 use proedit_gpu::render_graph::{NodeId, NodeOp, RenderGraph};
-use proedit_ui::timeline::TimelineClip;
+use proedit_gpu::{CpuBackend, GraphExecutor};
+use proedit_text::FontLibrary;
+use proedit_timeline::{EffectInstance, Sequence};
+use proedit_ui::timeline::{ClipKind, ClipTransition, TimelineClip};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+/// Source id of the black frame shown when nothing is visible.
+const BLACK_FRAME: u64 = 0;
+
+/// Node cache budget (about 16 HD half-float frames).
+const CACHE_BYTES: usize = 16 * 1920 * 1080 * 8;
 
 /// A composited output frame.
 pub struct CompositeFrame {
@@ -16,9 +51,16 @@ pub struct CompositeFrame {
 }
 
 /// Configuration for the compositor.
+#[derive(Debug, Clone, Copy)]
 pub struct CompositorConfig {
     pub width: u32,
     pub height: u32,
+    /// Primaries of the linear space layers are composited in.
+    pub working_space: ColorSpace,
+    /// Pixel format of working frames (`Rgba16F` or `Rgba32F`).
+    pub working_format: PixelFormat,
+    /// Frame rate playhead positions are counted in.
+    pub frame_rate: FrameRate,
 }
 
 impl Default for CompositorConfig {
@@ -26,109 +68,537 @@ impl Default for CompositorConfig {
         Self {
             width: 1920,
             height: 1080,
+            working_space: ColorSpace::LinearSrgb,
+            working_format: PixelFormat::Rgba16F,
+            frame_rate: FrameRate::FPS_24,
+        }
+    }
+}
+
+impl CompositorConfig {
+    /// Size, working space and frame rate of `sequence`.
+    pub fn for_sequence(sequence: &Sequence) -> Self {
+        Self {
+            width: sequence.width,
+            height: sequence.height,
+            working_space: sequence.working_space,
+            frame_rate: sequence.frame_rate,
+            ..Self::default()
         }
     }
+
+    fn working(&self) -> Result<WorkingSpace> {
+        let space = self
+            .working_space
+            .try_into()
+            .map_err(|e: proedit_color::ColorError| {
+                ProEditError::InvalidParameter(e.to_string())
+            })?;
+        WorkingSpace::new(space, self.working_format)
+    }
+}
+
+/// Transition used for names the registry doesn't know.
+const FALLBACK_TRANSITION: &str = "Cross Dissolve";
+
+/// What a lane shows at a playhead: one clip, or two in a transition.
+#[derive(Debug, Clone, Copy)]
+enum Layer<'a> {
+    Clip(&'a TimelineClip),
+    Transition {
+        from: &'a TimelineClip,
+        to: &'a TimelineClip,
+        transition: &'a ClipTransition,
+        progress: f32,
+    },
+}
+
+impl<'a> Layer<'a> {
+    fn track(&self) -> usize {
+        match self {
+            Layer::Clip(clip) => clip.track,
+            Layer::Transition { to, .. } => to.track,
+        }
+    }
+
+    /// The clips whose pictures the layer shows.
+    fn clips(&self) -> Vec<&'a TimelineClip> {
+        match *self {
+            Layer::Clip(clip) => vec![clip],
+            Layer::Transition { from, to, .. } => vec![from, to],
+        }
+    }
+}
+
+/// `clip`'s transition, if `playhead_frame` is in it and the clip it comes
+/// from ends where it begins on the same lane.
+fn transition_into<'a>(
+    clips: &'a [TimelineClip],
+    clip: &'a TimelineClip,
+    playhead_frame: f32,
+) -> Option<Layer<'a>> {
+    let transition = clip.transition.as_ref()?;
+    let begin = clip.start - transition.dur;
+    if clip.clip_type == ClipKind::Adjustment || !(begin..clip.start).contains(&playhead_frame) {
+        return None;
+    }
+    let from = clips.iter().find(|c| {
+        c.track == clip.track
+            && c.clip_type != ClipKind::Adjustment
+            && (c.start + c.dur - begin).abs() < 0.5
+    })?;
+    Some(Layer::Transition {
+        from,
+        to: clip,
+        transition,
+        progress: (playhead_frame - begin) / transition.dur,
+    })
+}
+
+/// Layers visible at `playhead_frame`, back to front (higher track = further back).
+fn visible_layers(clips: &[TimelineClip], playhead_frame: f32) -> Vec<Layer<'_>> {
+    let mut visible: Vec<Layer> = clips
+        .iter()
+        .filter_map(|c| {
+            if playhead_frame >= c.start && playhead_frame < c.start + c.dur {
+                Some(Layer::Clip(c))
+            } else {
+                transition_into(clips, c, playhead_frame)
+            }
+        })
+        .collect();
+    visible.sort_by_key(|layer| std::cmp::Reverse(layer.track()));
+    visible
+}
+
+/// Time into a generator clip at `playhead_frame`, held at its first frame
+/// while it is transitioned in.
+fn generator_time(clip: &TimelineClip, playhead_frame: f32, rate: FrameRate) -> RationalTime {
+    RationalTime::from_frames((playhead_frame - clip.start).floor().max(0.0) as i64, rate)
+}
+
+/// Source id for a clip's picture at `playhead_frame`: changes whenever its
+/// content does, so an animated generator gets one per frame.
+fn source_id(clip: &TimelineClip, playhead_frame: f32, rate: FrameRate) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    (clip.id, clip.color.to_array()).hash(&mut hasher);
+    if let Some(generator) = &clip.generator {
+        format!("{generator:?}").hash(&mut hasher);
+        if generator.is_animated() {
+            generator_time(clip, playhead_frame, rate).hash(&mut hasher);
+        }
+    }
+    hasher.finish().max(BLACK_FRAME + 1)
+}
+
+/// Effects of `clip` and, unless it is an adjustment layer, of its track.
+fn clip_effects<'a>(
+    clip: &'a TimelineClip,
+    track_effects: &'a [Vec<EffectInstance>],
+) -> impl Iterator<Item = &'a EffectInstance> {
+    let track = match clip.clip_type {
+        ClipKind::Adjustment => &[][..],
+        _ => track_effects.get(clip.track).map_or(&[][..], Vec::as_slice),
+    };
+    clip.effects
+        .iter()
+        .chain(track)
+        .filter(|effect| effect.enabled)
+}
+
+/// Backend effect name for an effect instance: its registry name plus a
+/// hash of its parameters, so cached results follow parameter changes.
+fn effect_key(effect: &EffectInstance) -> String {
+    let mut hasher = DefaultHasher::new();
+    hash_params(&effect.params, &mut hasher);
+    format!("{}#{:016x}", effect.name, hasher.finish())
+}
+
+/// Backend transition name for a transition, keyed like `effect_key` on
+/// its parameters and easing.
+fn transition_key(transition: &ClipTransition) -> String {
+    let mut hasher = DefaultHasher::new();
+    hash_params(&transition.params, &mut hasher);
+    format!("{:?}", transition.easing).hash(&mut hasher);
+    format!("{}#{:016x}", transition.name, hasher.finish())
+}
+
+/// Hash parameter values in name order.
+fn hash_params(params: &ParamValues, hasher: &mut DefaultHasher) {
+    let mut params: Vec<_> = params.iter().collect();
+    params.sort_by(|a, b| a.0.cmp(b.0));
+    for (name, value) in params {
+        name.hash(hasher);
+        match value {
+            ParamValue::Float(v) => (0u8, v.to_bits()).hash(hasher),
+            ParamValue::Int(v) => (1u8, v).hash(hasher),
+            ParamValue::Bool(v) => (2u8, v).hash(hasher),
+            ParamValue::Color(c) => (3u8, c.map(f32::to_bits)).hash(hasher),
+            ParamValue::Vec2(v) => (4u8, v.map(f32::to_bits)).hash(hasher),
+            ParamValue::Choice(option) => (5u8, option).hash(hasher),
+        }
+    }
+}
+
+/// Chain `effects` after `input`, returning the last node.
+fn add_effects<'a>(
+    graph: &mut RenderGraph,
+    input: NodeId,
+    effects: impl IntoIterator<Item = &'a EffectInstance>,
+    size: (u32, u32),
+) -> NodeId {
+    effects.into_iter().fold(input, |node, effect| {
+        graph.add_node(
+            NodeOp::Effect {
+                effect_name: effect_key(effect),
+            },
+            vec![node],
+            size,
+        )
+    })
+}
+
+/// A clip's source followed by its own and its track's effects.
+fn add_clip(
+    graph: &mut RenderGraph,
+    clip: &TimelineClip,
+    track_effects: &[Vec<EffectInstance>],
+    playhead_frame: f32,
+    config: &CompositorConfig,
+) -> NodeId {
+    let size = (config.width, config.height);
+    let source = graph.add_node(
+        NodeOp::Source {
+            frame_id: source_id(clip, playhead_frame, config.frame_rate),
+        },
+        vec![],
+        size,
+    );
+    add_effects(graph, source, clip_effects(clip, track_effects), size)
 }
 
 /// Build a render graph for the given playhead position.
 ///
-/// Determines which clips are visible at `playhead_frame`, creates source
-/// nodes for each, and chains them through a composite node to the output.
+/// Determines which clips are visible at `playhead_frame` and chains them,
+/// back to front, through composite nodes to the output. Each clip's source
+/// (solid colour or generator) is followed by its own and its track's
+/// effects; an adjustment layer adds no source but applies its effects to
+/// the composite so far (black if nothing is beneath it). A lane in a
+/// transition blends the two clips' layers through a `Transition` node.
 pub fn build_render_graph(
     clips: &[TimelineClip],
+    track_effects: &[Vec<EffectInstance>],
     playhead_frame: f32,
     config: &CompositorConfig,
 ) -> (RenderGraph, NodeId) {
     let mut graph = RenderGraph::new();
     let size = (config.width, config.height);
+    let black = |graph: &mut RenderGraph| {
+        graph.add_node(
+            NodeOp::Source {
+                frame_id: BLACK_FRAME,
+            },
+            vec![],
+            size,
+        )
+    };
 
-    // Find clips visible at the current playhead, sorted by track (back to front)
-    let mut visible: Vec<&TimelineClip> = clips
-        .iter()
-        .filter(|c| playhead_frame >= c.start && playhead_frame < c.start + c.dur)
-        .collect();
-    visible.sort_by_key(|c| std::cmp::Reverse(c.track));
+    // Chain layers: bottom layer first, each subsequent layer composited on top
+    let mut current: Option<NodeId> = None;
+    for layer in visible_layers(clips, playhead_frame) {
+        let layer = match layer {
+            Layer::Clip(clip) if clip.clip_type == ClipKind::Adjustment => {
+                let below = current.unwrap_or_else(|| black(&mut graph));
+                let effects = clip_effects(clip, track_effects);
+                current = Some(add_effects(&mut graph, below, effects, size));
+                continue;
+            }
+            Layer::Clip(clip) => add_clip(&mut graph, clip, track_effects, playhead_frame, config),
+            Layer::Transition {
+                from,
+                to,
+                transition,
+                progress,
+            } => {
+                let inputs = vec![
+                    add_clip(&mut graph, from, track_effects, playhead_frame, config),
+                    add_clip(&mut graph, to, track_effects, playhead_frame, config),
+                ];
+                graph.add_node(
+                    NodeOp::Transition {
+                        transition_name: transition_key(transition),
+                        progress,
+                    },
+                    inputs,
+                    size,
+                )
+            }
+        };
+        current = Some(match current {
+            Some(below) => graph.add_node(
+                NodeOp::Composite {
+                    blend_mode: 0, // Normal
+                    opacity: 1.0,
+                },
+                vec![below, layer],
+                size,
+            ),
+            None => layer,
+        });
+    }
+
+    let current = current.unwrap_or_else(|| black(&mut graph));
+    let out = graph.add_node(NodeOp::Output, vec![current], size);
+    (graph, out)
+}
+
+/// A solid working-space frame of an sRGB colour.
+fn solid_frame(
+    working: &WorkingSpace,
+    width: u32,
+    height: u32,
+    rgba: [u8; 4],
+) -> Result<FrameBuffer> {
+    let srgb = rgba.map(|v| v as f32 / 255.0);
+    let color = working.color_to_working(srgb, proedit_color::ColorSpace::SRGB);
+    let pixels = vec![color; width as usize * height as usize];
+    FrameBuffer::from_rgba_f32(width, height, working.format(), &pixels)
+}
 
-    if visible.is_empty() {
-        // Black frame
-        let src = graph.add_node(NodeOp::Source { frame_id: 0 }, vec![], size);
-        let out = graph.add_node(NodeOp::Output, vec![src], size);
-        return (graph, out);
+/// Composites timeline frames, keeping sources and cached layers between
+/// frames.
+pub struct Compositor {
+    config: CompositorConfig,
+    working: WorkingSpace,
+    executor: GraphExecutor<CpuBackend>,
+    /// Source ids registered with the backend.
+    sources: HashSet<u64>,
+    /// Effect implementations, looked up by name.
+    registry: Arc<EffectsRegistry>,
+    /// Effect keys registered with the backend, kept only while a visible
+    /// clip uses them.
+    effects: HashSet<String>,
+    /// Transition implementations, looked up by name.
+    transitions: Arc<TransitionRegistry>,
+    /// Transition names registered with the backend.
+    registered_transitions: HashSet<String>,
+    /// Generator clip renderer, created with the system fonts when first
+    /// needed.
+    generators: Option<GeneratorRenderer>,
+}
+
+impl Compositor {
+    /// Create a compositor producing frames of `config`'s size. Fails if
+    /// the working space or format can't be used for compositing.
+    pub fn new(config: CompositorConfig) -> Result<Self> {
+        let working = config.working()?;
+        let mut backend = CpuBackend::with_format(working.format());
+        backend.set_source(
+            BLACK_FRAME,
+            solid_frame(&working, config.width, config.height, [0, 0, 0, 255])?,
+        );
+        Ok(Self {
+            config,
+            working,
+            executor: GraphExecutor::new(backend, CACHE_BYTES),
+            sources: HashSet::from([BLACK_FRAME]),
+            registry: Arc::new(EffectsRegistry::new()),
+            effects: HashSet::new(),
+            transitions: Arc::new(TransitionRegistry::new()),
+            registered_transitions: HashSet::new(),
+            generators: None,
+        })
     }
 
-    // Create source nodes for each visible clip
-    let source_nodes: Vec<NodeId> = visible
-        .iter()
-        .enumerate()
-        .map(|(i, _clip)| graph.add_node(NodeOp::Source { frame_id: i as u64 }, vec![], size))
-        .collect();
+    /// Set titles and countdowns in `fonts` instead of the system fonts.
+    pub fn set_fonts(&mut self, fonts: FontLibrary) {
+        self.generators = Some(GeneratorRenderer::new(fonts));
+    }
+
+    /// A working space frame of `clip`'s picture at `playhead_frame`: its
+    /// generator's output, or else its colour.
+    fn clip_frame(&mut self, clip: &TimelineClip, playhead_frame: f32) -> Result<FrameBuffer> {
+        let (w, h) = (self.config.width, self.config.height);
+        let Some(generator) = &clip.generator else {
+            let color = clip.color.to_srgba_unmultiplied();
+            return solid_frame(&self.working, w, h, color);
+        };
+        let time = generator_time(clip, playhead_frame, self.config.frame_rate);
+        let frame = self
+            .generators
+            .get_or_insert_with(|| GeneratorRenderer::new(FontLibrary::with_system_fonts()))
+            .render(generator, time, w, h)?;
+        self.working
+            .to_working(&frame, proedit_color::ColorSpace::SRGB)
+    }
+
+    /// The working space frames are composited in.
+    pub fn working(&self) -> &WorkingSpace {
+        &self.working
+    }
 
-    // Chain composites: bottom layer first, each subsequent layer composited on top
-    let mut current = source_nodes[0];
-    for &layer in &source_nodes[1..] {
-        current = graph.add_node(
-            NodeOp::Composite {
-                blend_mode: 0, // Normal
-                opacity: 1.0,
+    /// The executor, e.g. to register effects or read its statistics.
+    pub fn executor_mut(&mut self) -> &mut GraphExecutor<CpuBackend> {
+        &mut self.executor
+    }
+
+    /// Register the backend implementation of `effect`, unless it already
+    /// is, and return its key. Fails if the effect is not in the registry.
+    fn register_effect(&mut self, effect: &EffectInstance) -> Result<String> {
+        let key = effect_key(effect);
+        if self.effects.contains(&key) {
+            return Ok(key);
+        }
+        if self.registry.find(&effect.name).is_none() {
+            return Err(ProEditError::InvalidParameter(format!(
+                "Unknown effect '{}'",
+                effect.name
+            )));
+        }
+        let registry = Arc::clone(&self.registry);
+        let name = effect.name.clone();
+        let params = effect.params.clone();
+        self.executor
+            .backend_mut()
+            .register_effect(key.clone(), move |frame: &FrameBuffer| {
+                let effect = registry.find(&name).ok_or_else(|| {
+                    ProEditError::InvalidParameter(format!("Unknown effect '{name}'"))
+                })?;
+                effect.apply_cpu(frame, &params)
+            });
+        self.effects.insert(key.clone());
+        Ok(key)
+    }
+
+    /// Register the backend implementation of `transition` under its
+    /// `transition_key`, unless it already is. Names the registry doesn't
+    /// know fall back to a cross dissolve, as audio falls back to an
+    /// equal-power crossfade. Progress is eased by the transition's curve.
+    fn register_transition(&mut self, transition: &ClipTransition) {
+        let key = transition_key(transition);
+        if !self.registered_transitions.insert(key.clone()) {
+            return;
+        }
+        let registry = Arc::clone(&self.transitions);
+        let lookup = transition.name.clone();
+        let params = transition.params.clone();
+        let easing = transition.easing;
+        self.executor.backend_mut().register_transition(
+            key,
+            move |a: &FrameBuffer, b: &FrameBuffer, progress: f32| {
+                let transition = registry
+                    .find(&lookup)
+                    .or_else(|| registry.find(FALLBACK_TRANSITION))
+                    .ok_or_else(|| {
+                        ProEditError::InvalidParameter(format!("Unknown transition '{lookup}'"))
+                    })?;
+                let progress = easing.apply(progress as f64) as f32;
+                transition.apply_cpu(a, b, progress, &params)
             },
-            vec![current, layer],
-            size,
         );
     }
 
-    let out = graph.add_node(NodeOp::Output, vec![current], size);
-    (graph, out)
+    /// Composite all visible clips at the given playhead into a working
+    /// space frame (straight alpha), for export or further processing.
+    /// `track_effects` holds the effect stack of each track, by index.
+    ///
+    /// Each "source" node produces a generator clip's synthesized frame or
+    /// a solid-color frame from the clip's color (placeholder for decoded
+    /// video frames).
+    pub fn render_working(
+        &mut self,
+        clips: &[TimelineClip],
+        track_effects: &[Vec<EffectInstance>],
+        playhead_frame: f32,
+    ) -> Result<Arc<FrameBuffer>> {
+        let rate = self.config.frame_rate;
+        let layers = visible_layers(clips, playhead_frame);
+        for layer in &layers {
+            if let Layer::Transition { transition, .. } = layer {
+                self.register_transition(transition);
+            }
+        }
+        let visible: Vec<&TimelineClip> = layers.iter().flat_map(Layer::clips).collect();
+        let mut used_effects = HashSet::new();
+        for clip in &visible {
+            for effect in clip_effects(clip, track_effects) {
+                used_effects.insert(self.register_effect(effect)?);
+            }
+        }
+        // Each parameter change registers a new key; drop the stale ones.
+        for key in self.effects.difference(&used_effects) {
+            self.executor.backend_mut().unregister_effect(key);
+        }
+        self.effects.retain(|key| used_effects.contains(key));
+
+        let used: HashSet<u64> = visible
+            .iter()
+            .filter(|c| c.clip_type != ClipKind::Adjustment)
+            .map(|c| source_id(c, playhead_frame, rate))
+            .chain([BLACK_FRAME])
+            .collect();
+
+        for id in self.sources.difference(&used) {
+            self.executor.backend_mut().remove_source(*id);
+        }
+        self.sources.retain(|id| used.contains(id));
+        for clip in visible
+            .iter()
+            .filter(|c| c.clip_type != ClipKind::Adjustment)
+        {
+            let id = source_id(clip, playhead_frame, rate);
+            if !self.sources.contains(&id) {
+                let frame = self.clip_frame(clip, playhead_frame)?;
+                self.executor.backend_mut().set_source(id, frame);
+                self.sources.insert(id);
+            }
+        }
+
+        let (graph, out) = build_render_graph(clips, track_effects, playhead_frame, &self.config);
+        self.executor.execute(&graph, out)
+    }
+
+    /// Composite all visible clips at the given playhead into a single
+    /// opaque sRGB RGBA8 frame over black, for display.
+    pub fn composite(
+        &mut self,
+        clips: &[TimelineClip],
+        track_effects: &[Vec<EffectInstance>],
+        playhead_frame: f32,
+    ) -> Result<CompositeFrame> {
+        let output = self.render_working(clips, track_effects, playhead_frame)?;
+
+        // Flatten over black in linear light, then encode.
+        let mut pixels = output.read_rgba_f32()?;
+        for [r, g, b, a] in &mut pixels {
+            *r *= *a;
+            *g *= *a;
+            *b *= *a;
+            *a = 1.0;
+        }
+        let flat = FrameBuffer::from_rgba_f32(output.width, output.height, output.format, &pixels)?;
+        let buffer =
+            self.working
+                .to_output(&flat, proedit_color::ColorSpace::SRGB, PixelFormat::Rgba8)?;
+        Ok(CompositeFrame { buffer })
+    }
 }
 
 /// Composite all visible clips at the given playhead into a single RGBA8 frame.
 ///
-/// This is the CPU fallback path. Each "source" node produces a solid-color
-/// frame from the clip's color (placeholder for decoded video frames).
+/// This is the CPU fallback path; see [`Compositor::composite`].
 pub fn composite_frame(
     clips: &[TimelineClip],
+    track_effects: &[Vec<EffectInstance>],
     playhead_frame: f32,
     config: &CompositorConfig,
 ) -> CompositeFrame {
-    let w = config.width;
-    let h = config.height;
-
-    // Collect visible clips sorted by track (back to front: higher track = further back)
-    let mut visible: Vec<&TimelineClip> = clips
-        .iter()
-        .filter(|c| playhead_frame >= c.start && playhead_frame < c.start + c.dur)
-        .collect();
-    visible.sort_by_key(|c| std::cmp::Reverse(c.track));
-
-    // Start with black background
-    let mut output = FrameBuffer::new(w, h, PixelFormat::Rgba8);
-
-    if visible.is_empty() {
-        return CompositeFrame { buffer: output };
-    }
-
-    // Simple over-compositing: paint each layer on top
-    for clip in &visible {
-        let [r, g, b, a] = clip.color.to_srgba_unmultiplied();
-        let alpha = a as f32 / 255.0;
-        let plane = output.primary_plane_mut();
-
-        for y in 0..h {
-            let row = plane.row_mut(y);
-            for x in 0..w as usize {
-                let idx = x * 4;
-                // Alpha-over compositing
-                let dst_r = row[idx] as f32;
-                let dst_g = row[idx + 1] as f32;
-                let dst_b = row[idx + 2] as f32;
-                row[idx] = (r as f32 * alpha + dst_r * (1.0 - alpha)) as u8;
-                row[idx + 1] = (g as f32 * alpha + dst_g * (1.0 - alpha)) as u8;
-                row[idx + 2] = (b as f32 * alpha + dst_b * (1.0 - alpha)) as u8;
-                row[idx + 3] = 255;
-            }
-        }
-    }
-
-    CompositeFrame { buffer: output }
+    let (w, h) = (config.width, config.height);
+    Compositor::new(*config)
+        .and_then(|mut compositor| compositor.composite(clips, track_effects, playhead_frame))
+        .unwrap_or_else(|_| CompositeFrame {
+            buffer: FrameBuffer::new(w, h, PixelFormat::Rgba8),
+        })
 }
 
 /// Render a single solid-color RGBA8 frame (utility for export pipeline).
@@ -140,7 +610,9 @@ pub fn render_black_frame(width: u32, height: u32) -> Vec<u8> {
 mod tests {
     use super::*;
     use egui::Color32;
-    use proedit_ui::timeline::ClipKind;
+    use proedit_core::EasingCurve;
+    use proedit_timeline::{BarsGenerator, Generator, NoiseGenerator, SolidGenerator};
+    use proedit_ui::timeline::{ClipKind, ClipTransition};
 
     fn make_clip(id: usize, start: f32, dur: f32, track: usize, color: Color32) -> TimelineClip {
         TimelineClip {
@@ -151,12 +623,15 @@ mod tests {
             dur,
             track,
             clip_type: ClipKind::Video,
+            effects: Vec::new(),
+            generator: None,
+            transition: None,
         }
     }
 
     #[test]
     fn test_build_graph_empty() {
-        let (graph, _out) = build_render_graph(&[], 0.0, &CompositorConfig::default());
+        let (graph, _out) = build_render_graph(&[], &[], 0.0, &CompositorConfig::default());
         // Should have source + output = 2 nodes
         assert_eq!(graph.node_count(), 2);
     }
@@ -164,7 +639,7 @@ mod tests {
     #[test]
     fn test_build_graph_single_clip() {
         let clips = vec![make_clip(1, 0.0, 100.0, 0, Color32::RED)];
-        let (graph, _out) = build_render_graph(&clips, 50.0, &CompositorConfig::default());
+        let (graph, _out) = build_render_graph(&clips, &[], 50.0, &CompositorConfig::default());
         // source + output = 2
         assert_eq!(graph.node_count(), 2);
     }
@@ -175,7 +650,7 @@ mod tests {
             make_clip(1, 0.0, 100.0, 0, Color32::RED),
             make_clip(2, 0.0, 100.0, 1, Color32::BLUE),
         ];
-        let (graph, _out) = build_render_graph(&clips, 50.0, &CompositorConfig::default());
+        let (graph, _out) = build_render_graph(&clips, &[], 50.0, &CompositorConfig::default());
         // 2 sources + 1 composite + output = 4
         assert_eq!(graph.node_count(), 4);
         assert!(graph.topological_sort().is_some());
@@ -184,14 +659,252 @@ mod tests {
     #[test]
     fn test_build_graph_clip_not_visible() {
         let clips = vec![make_clip(1, 100.0, 50.0, 0, Color32::RED)];
-        let (graph, _out) = build_render_graph(&clips, 0.0, &CompositorConfig::default());
+        let (graph, _out) = build_render_graph(&clips, &[], 0.0, &CompositorConfig::default());
         // Clip not visible at frame 0, so just black source + output
         assert_eq!(graph.node_count(), 2);
     }
 
+    fn adjustment(id: usize, start: f32, dur: f32, track: usize) -> TimelineClip {
+        TimelineClip {
+            clip_type: ClipKind::Adjustment,
+            effects: vec![EffectInstance::new("Vignette")],
+            ..make_clip(id, start, dur, track, Color32::TRANSPARENT)
+        }
+    }
+
+    /// The single input of the only node matching `op`.
+    fn input_of(graph: &RenderGraph, op: impl Fn(&NodeOp) -> bool) -> NodeOp {
+        let nodes: Vec<_> = (0..graph.node_count() as u32)
+            .filter_map(|id| graph.node(NodeId(id)))
+            .filter(|n| op(&n.op))
+            .collect();
+        assert_eq!(nodes.len(), 1);
+        graph.node(nodes[0].inputs[0]).unwrap().op.clone()
+    }
+
+    #[test]
+    fn test_build_graph_adjustment_layer_takes_tracks_below() {
+        let clips = vec![
+            make_clip(1, 0.0, 100.0, 2, Color32::RED),
+            adjustment(2, 0.0, 100.0, 1),
+            make_clip(3, 0.0, 100.0, 0, Color32::BLUE),
+        ];
+        let (graph, _out) = build_render_graph(&clips, &[], 50.0, &CompositorConfig::default());
+        // 2 sources + effect + composite + output
+        assert_eq!(graph.node_count(), 5);
+        // The vignette sees only track 2; track 0 is composited over it.
+        assert!(matches!(
+            input_of(&graph, |op| matches!(op, NodeOp::Effect { .. })),
+            NodeOp::Source { .. }
+        ));
+        assert!(matches!(
+            input_of(&graph, |op| matches!(op, NodeOp::Composite { .. })),
+            NodeOp::Effect { .. }
+        ));
+
+        // Over nothing, an adjustment layer applies to black.
+        let (graph, _out) = build_render_graph(
+            &[adjustment(1, 0.0, 10.0, 0)],
+            &[],
+            5.0,
+            &CompositorConfig::default(),
+        );
+        assert_eq!(graph.node_count(), 3);
+    }
+
+    #[test]
+    fn test_build_graph_track_effects_follow_each_clip() {
+        let mut clip = make_clip(1, 0.0, 100.0, 1, Color32::RED);
+        clip.effects = vec![EffectInstance::new("Film Grain")];
+        let clips = vec![clip, make_clip(2, 0.0, 100.0, 2, Color32::BLUE)];
+        let mut track_effects = vec![Vec::new(); 3];
+        let mut disabled = EffectInstance::new("Vignette");
+        disabled.enabled = false;
+        track_effects[1] = vec![EffectInstance::new("Gaussian Blur"), disabled];
+        let (graph, _out) =
+            build_render_graph(&clips, &track_effects, 50.0, &CompositorConfig::default());
+        // 2 sources + grain + blur + composite + output; the disabled
+        // vignette is left out.
+        assert_eq!(graph.node_count(), 6);
+        let blur_input = input_of(
+            &graph,
+            |op| matches!(op, NodeOp::Effect { effect_name } if effect_name.starts_with("Gaussian Blur#")),
+        );
+        assert!(
+            matches!(blur_input, NodeOp::Effect { effect_name } if effect_name.starts_with("Film Grain#"))
+        );
+    }
+
+    fn transition_pair(name: &str) -> Vec<TimelineClip> {
+        let mut incoming = make_clip(2, 72.0, 48.0, 0, Color32::WHITE);
+        incoming.transition = Some(ClipTransition {
+            name: name.into(),
+            dur: 24.0,
+            params: ParamValues::new(),
+            easing: EasingCurve::Linear,
+        });
+        vec![make_clip(1, 0.0, 48.0, 0, Color32::BLACK), incoming]
+    }
+
+    #[test]
+    fn test_build_graph_transition_blends_neighbours() {
+        let clips = transition_pair("Wipe");
+        let config = CompositorConfig::default();
+        let (graph, out) = build_render_graph(&clips, &[], 60.0, &config);
+        // 2 sources + transition + output
+        assert_eq!(graph.node_count(), 4);
+        let node = graph.node(graph.node(out).unwrap().inputs[0]).unwrap();
+        assert!(matches!(
+            &node.op,
+            NodeOp::Transition { transition_name, progress }
+                if transition_name.starts_with("Wipe#") && *progress == 0.5
+        ));
+        assert_eq!(node.inputs.len(), 2);
+
+        // Outside the transition each clip plays alone.
+        for frame in [40.0, 80.0] {
+            let (graph, _out) = build_render_graph(&clips, &[], frame, &config);
+            assert_eq!(graph.node_count(), 2);
+        }
+        // Without an outgoing clip there is nothing to blend from.
+        let (graph, _out) = build_render_graph(&clips[1..], &[], 60.0, &config);
+        assert_eq!(graph.node_count(), 2);
+    }
+
+    #[test]
+    fn test_transition_renders_in_linear_light() {
+        let mut compositor = Compositor::new(CompositorConfig {
+            width: 2,
+            height: 2,
+            ..CompositorConfig::default()
+        })
+        .unwrap();
+        // Half way from black to white is half the light: sRGB 188.
+        for name in ["Cross Dissolve", "Not A Transition"] {
+            let frame = compositor
+                .composite(&transition_pair(name), &[], 60.0)
+                .unwrap();
+            assert_eq!(
+                &frame.buffer.primary_plane().row(0)[..4],
+                &[188, 188, 188, 255],
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_transition_params_and_easing_reach_render() {
+        let mut compositor = Compositor::new(CompositorConfig {
+            width: 2,
+            height: 2,
+            ..CompositorConfig::default()
+        })
+        .unwrap();
+        let linear = transition_pair("Cross Dissolve");
+        let mut held = linear.clone();
+        held[1].transition.as_mut().unwrap().easing = EasingCurve::Hold;
+        let key = |clips: &[TimelineClip]| transition_key(clips[1].transition.as_ref().unwrap());
+        assert_ne!(key(&linear), key(&held));
+        let mut wide = linear.clone();
+        wide[1]
+            .transition
+            .as_mut()
+            .unwrap()
+            .params
+            .insert("softness".into(), ParamValue::Float(1.0));
+        assert_ne!(key(&linear), key(&wide));
+
+        // Held progress stays on the outgoing (black) clip until the end.
+        let frame = compositor.composite(&held, &[], 60.0).unwrap();
+        assert_eq!(&frame.buffer.primary_plane().row(0)[..4], &[0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_effect_key_follows_params() {
+        let vignette = EffectInstance::new("Vignette");
+        let strong = vignette
+            .clone()
+            .with_param("intensity", ParamValue::Float(1.0));
+        assert_eq!(effect_key(&vignette), effect_key(&vignette.clone()));
+        assert_ne!(effect_key(&vignette), effect_key(&strong));
+        assert!(effect_key(&strong).starts_with("Vignette#"));
+    }
+
+    #[test]
+    fn test_adjustment_layer_renders_over_composite() {
+        let config = CompositorConfig {
+            width: 8,
+            height: 8,
+            ..CompositorConfig::default()
+        };
+        let mut compositor = Compositor::new(config).unwrap();
+        let mut vignette = adjustment(2, 0.0, 10.0, 0);
+        vignette.effects[0] = EffectInstance::new("Vignette")
+            .with_param("intensity", ParamValue::Float(1.0))
+            .with_param("radius", ParamValue::Float(0.2));
+        let clips = vec![make_clip(1, 0.0, 100.0, 1, Color32::WHITE), vignette];
+
+        let graded = compositor.composite(&clips, &[], 5.0).unwrap();
+        let plane = graded.buffer.primary_plane();
+        assert!(plane.row(0)[0] < plane.row(4)[16], "corner not darkened");
+
+        // After the adjustment layer ends the clip is untouched.
+        let plain = compositor.composite(&clips, &[], 20.0).unwrap();
+        assert_eq!(
+            &plain.buffer.primary_plane().row(0)[..4],
+            &[255, 255, 255, 255]
+        );
+
+        // Track effects darken the same way.
+        let mut track_effects = vec![Vec::new(); 2];
+        track_effects[1] = clips[1].effects.clone();
+        let tracked = compositor
+            .composite(&clips[..1], &track_effects, 20.0)
+            .unwrap();
+        assert_eq!(tracked.buffer.primary_plane().data, plane.data);
+    }
+
+    #[test]
+    fn test_parameter_drag_keeps_one_effect_registered() {
+        let mut compositor = Compositor::new(CompositorConfig {
+            width: 4,
+            height: 4,
+            ..CompositorConfig::default()
+        })
+        .unwrap();
+        let mut vignette = adjustment(2, 0.0, 10.0, 0);
+        for step in 0..10 {
+            vignette.effects[0] = EffectInstance::new("Vignette")
+                .with_param("intensity", ParamValue::Float(step as f32 / 10.0));
+            let clips = vec![
+                make_clip(1, 0.0, 100.0, 1, Color32::WHITE),
+                vignette.clone(),
+            ];
+            compositor.composite(&clips, &[], 5.0).unwrap();
+            assert_eq!(compositor.effects.len(), 1);
+        }
+
+        // Once no visible clip uses it, it is dropped.
+        compositor.composite(&[], &[], 5.0).unwrap();
+        assert!(compositor.effects.is_empty());
+    }
+
+    #[test]
+    fn test_unknown_effect_is_an_error() {
+        let mut compositor = Compositor::new(CompositorConfig {
+            width: 2,
+            height: 2,
+            ..CompositorConfig::default()
+        })
+        .unwrap();
+        let mut clip = adjustment(1, 0.0, 10.0, 0);
+        clip.effects = vec![EffectInstance::new("No Such Effect")];
+        assert!(compositor.composite(&[clip], &[], 0.0).is_err());
+    }
+
     #[test]
     fn test_composite_empty() {
-        let result = composite_frame(&[], 0.0, &CompositorConfig::default());
+        let result = composite_frame(&[], &[], 0.0, &CompositorConfig::default());
         // Should be a black frame
         let plane = result.buffer.primary_plane();
         let row = plane.row(0);
@@ -206,8 +919,9 @@ mod tests {
         let config = CompositorConfig {
             width: 4,
             height: 4,
+            ..CompositorConfig::default()
         };
-        let result = composite_frame(&clips, 50.0, &config);
+        let result = composite_frame(&clips, &[], 50.0, &config);
         let plane = result.buffer.primary_plane();
         let row = plane.row(0);
         assert_eq!(row[0], 200);
@@ -237,15 +951,176 @@ mod tests {
         let config = CompositorConfig {
             width: 2,
             height: 2,
+            ..CompositorConfig::default()
         };
-        let result = composite_frame(&clips, 50.0, &config);
+        let result = composite_frame(&clips, &[], 50.0, &config);
         let plane = result.buffer.primary_plane();
         let row = plane.row(0);
-        // Blue (track 1, front) at ~50% over Red (track 2, back)
-        // R: 255 * (1 - 0.502) + 0 * 0.502 ≈ 127
-        // B: 0 * (1 - 0.502) + 255 * 0.502 ≈ 128
-        assert!(row[0] > 100 && row[0] < 150, "R = {}", row[0]);
-        assert!(row[2] > 100 && row[2] < 150, "B = {}", row[2]);
+        // Blue (track 1, front) at ~50% over Red (track 2, back), mixed in
+        // linear light: half the light of each is sRGB 187-188, not 128.
+        assert!(row[0] > 180 && row[0] < 195, "R = {}", row[0]);
+        assert!(row[2] > 180 && row[2] < 195, "B = {}", row[2]);
+    }
+
+    #[test]
+    fn test_half_dissolve_of_black_and_white_is_linear() {
+        let clips = vec![
+            make_clip(1, 0.0, 100.0, 2, Color32::BLACK),
+            make_clip(
+                2,
+                0.0,
+                100.0,
+                1,
+                Color32::from_rgba_unmultiplied(255, 255, 255, 128),
+            ),
+        ];
+        let config = CompositorConfig {
+            width: 2,
+            height: 2,
+            working_format: PixelFormat::Rgba32F,
+            ..CompositorConfig::default()
+        };
+        let mut compositor = Compositor::new(config).unwrap();
+        let working = compositor.render_working(&clips, &[], 0.0).unwrap();
+        let px = working.read_rgba_f32().unwrap()[0];
+        assert!((px[0] - 128.0 / 255.0).abs() < 1e-5, "{px:?}");
+
+        // Encoded for display, 50% of white's light is sRGB 188.
+        let display = compositor.composite(&clips, &[], 0.0).unwrap();
+        assert_eq!(
+            &display.buffer.primary_plane().row(0)[..4],
+            &[188, 188, 188, 255]
+        );
+    }
+
+    #[test]
+    fn test_config_follows_sequence_working_space() {
+        let mut sequence = Sequence {
+            working_space: ColorSpace::AcesCg,
+            ..Sequence::default()
+        };
+        let config = CompositorConfig::for_sequence(&sequence);
+        assert_eq!(
+            (config.width, config.height),
+            (sequence.width, sequence.height)
+        );
+        let compositor = Compositor::new(config).unwrap();
+        assert_eq!(
+            compositor.working().space(),
+            proedit_color::ColorSpace::ACEScg
+        );
+
+        sequence.working_space = ColorSpace::Aces2065;
+        assert!(Compositor::new(CompositorConfig::for_sequence(&sequence)).is_err());
+    }
+
+    #[test]
+    fn test_compositor_reuses_unchanged_layers() {
+        let config = CompositorConfig {
+            width: 4,
+            height: 4,
+            ..CompositorConfig::default()
+        };
+        let mut compositor = Compositor::new(config).unwrap();
+        let mut clips = vec![
+            make_clip(1, 0.0, 100.0, 2, Color32::RED),
+            make_clip(
+                2,
+                0.0,
+                100.0,
+                1,
+                Color32::from_rgba_unmultiplied(0, 0, 255, 128),
+            ),
+        ];
+        let first = compositor.composite(&clips, &[], 10.0).unwrap();
+        let second = compositor.composite(&clips, &[], 11.0).unwrap();
+        assert_eq!(compositor.executor_mut().stats().cache_hits, 1);
+        assert_eq!(
+            first.buffer.primary_plane().data,
+            second.buffer.primary_plane().data
+        );
+
+        // A changed layer is rendered again.
+        clips[1].color = Color32::GREEN;
+        let third = compositor.composite(&clips, &[], 12.0).unwrap();
+        assert_eq!(compositor.executor_mut().stats().cache_hits, 0);
+        assert_eq!(&third.buffer.primary_plane().row(0)[..4], &[0, 255, 0, 255]);
+    }
+
+    fn generator(id: usize, start: f32, dur: f32, generator: Generator) -> TimelineClip {
+        TimelineClip {
+            clip_type: ClipKind::Gfx,
+            generator: Some(Box::new(generator)),
+            ..make_clip(id, start, dur, 0, Color32::GRAY)
+        }
+    }
+
+    #[test]
+    fn test_generator_clip_is_a_source() {
+        let bars = generator(1, 0.0, 100.0, Generator::Bars(BarsGenerator::default()));
+        let (graph, out) = build_render_graph(
+            std::slice::from_ref(&bars),
+            &[],
+            10.0,
+            &CompositorConfig::default(),
+        );
+        assert_eq!(graph.node_count(), 2);
+        assert!(matches!(
+            graph.node(graph.node(out).unwrap().inputs[0]).unwrap().op,
+            NodeOp::Source { frame_id } if frame_id != BLACK_FRAME
+        ));
+
+        // Still pictures keep one source; animated ones change every frame.
+        let rate = FrameRate::FPS_24;
+        assert_eq!(source_id(&bars, 10.0, rate), source_id(&bars, 11.0, rate));
+        let noise = generator(2, 0.0, 100.0, Generator::Noise(NoiseGenerator::new(1)));
+        assert_eq!(source_id(&noise, 10.0, rate), source_id(&noise, 11.0, rate));
+        let mut evolving = NoiseGenerator::new(1);
+        evolving
+            .evolution
+            .set(RationalTime::ZERO, 0.0, EasingCurve::Linear);
+        evolving
+            .evolution
+            .set(RationalTime::new(1, 1), 1.0, EasingCurve::Linear);
+        let evolving = generator(3, 0.0, 100.0, Generator::Noise(evolving));
+        assert_ne!(
+            source_id(&evolving, 10.0, rate),
+            source_id(&evolving, 11.0, rate)
+        );
+        assert_ne!(
+            source_id(&noise, 10.0, rate),
+            source_id(&evolving, 10.0, rate)
+        );
+    }
+
+    #[test]
+    fn test_generator_clip_renders_from_clip_start() {
+        let config = CompositorConfig {
+            width: 4,
+            height: 4,
+            ..CompositorConfig::default()
+        };
+        let mut compositor = Compositor::new(config).unwrap();
+        let mut fade = SolidGenerator::new([0.0, 0.0, 0.0, 1.0]);
+        fade.color.set(
+            RationalTime::ZERO,
+            [0.0, 0.0, 0.0, 1.0],
+            EasingCurve::Linear,
+        );
+        fade.color.set(
+            RationalTime::new(1, 1),
+            [1.0, 1.0, 1.0, 1.0],
+            EasingCurve::Linear,
+        );
+        let clips = vec![generator(1, 100.0, 48.0, Generator::Solid(fade))];
+
+        let start = compositor.composite(&clips, &[], 100.0).unwrap();
+        assert_eq!(&start.buffer.primary_plane().row(0)[..4], &[0, 0, 0, 255]);
+        let end = compositor.composite(&clips, &[], 124.0).unwrap();
+        assert_eq!(
+            &end.buffer.primary_plane().row(0)[..4],
+            &[255, 255, 255, 255]
+        );
     }
 
     #[test]
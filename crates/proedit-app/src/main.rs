@@ -5,25 +5,43 @@
 
 mod ai_bridge;
 mod compositor;
+mod project_io;
+mod sequence_view;
 
 use anyhow::Result;
 use eframe::egui;
-use proedit_core::{FrameBuffer, FrameRate};
-use proedit_media::VideoDecoder;
-use proedit_timeline::{Project, ProjectFile, Sequence};
-use proedit_ui::timeline::{TimelineAction, TimelineClip};
+use proedit_audio::{
+    Bus, ChannelLayout, CpalInput, CpalOutput, NullOutput, PcmSource, PeakCache, RecordSettings,
+    RecordState, Recorder, Route, SourceLoader, WaveformSample,
+};
+use proedit_core::{FrameBuffer, FrameRate, RationalTime, TimeRange};
+use proedit_media::{ComplianceReport, ExportJob, LoudnessTarget, VideoDecoder};
+use proedit_timeline::{
+    resolve_media_path, Clip, ClipRef, EditCommand, Generator, OperationLog, Project, Sequence,
+    TextGenerator,
+};
+use proedit_ui::timeline::{ClipKind, TimelineAction};
 use proedit_ui::{
     show_audio_mixer, show_color_wheels, show_command_palette, show_effects_panel,
-    show_export_dialog, show_inspector, show_media_browser, show_timeline, show_top_bar,
-    show_viewer, AudioMixerState, ColorWheelsState, CommandPaletteState, CommandRegistry,
-    CurveEditorState, EffectsPanelState, ExportDialogAction, ExportDialogState, InspectorState,
-    LeftTab, MediaBrowserAction, MediaBrowserState, Page, Theme, TimelineState, TopBarAction,
-    TopBarState, ViewerState,
+    show_export_dialog, show_inspector, show_load_report, show_media_browser, show_timeline,
+    show_top_bar, show_viewer, AudioMixerAction, AudioMixerState, ColorWheelsState,
+    CommandPaletteState, CommandRegistry, CurveEditorState, EffectsPanelState, ExportDialogAction,
+    ExportDialogState, InspectorState, LeftTab, LoadReportState, MediaBrowserAction,
+    MediaBrowserState, Page, Theme, TimelineState, TopBarAction, TopBarState, ViewerState,
 };
+use sequence_view::SequenceView;
+use std::collections::HashMap;
 use std::path::PathBuf;
-use tracing::{info, Level};
+use std::sync::Arc;
+use tracing::{info, warn, Level};
 use tracing_subscriber::FmtSubscriber;
 
+/// Rate audio is decoded at when building waveform peak files.
+const PEAK_SAMPLE_RATE: u32 = 48000;
+
+/// Length of a voice-over pass punched in at or past the end of the cut.
+const OPEN_PUNCH_SECONDS: i64 = 60;
+
 fn main() -> Result<()> {
     let subscriber = FmtSubscriber::builder()
         .with_max_level(Level::INFO)
@@ -70,12 +88,22 @@ struct ProEditApp {
 
     // Audio
     audio_engine: Option<proedit_audio::AudioEngine>,
-
-    // Undo/redo
-    undo_snapshots: Vec<Vec<TimelineClip>>,
-    redo_snapshots: Vec<Vec<TimelineClip>>,
+    peak_cache: Option<PeakCache>,
+    /// Voice-over recorder, opened on the first recording.
+    recorder: Option<Recorder>,
+    /// Imported file paths by media name, for looking up clip sources.
+    media_paths: HashMap<String, PathBuf>,
+    /// Timeline zoom the cached clip waveforms were drawn at.
+    waveform_zoom: f32,
+
+    // Editing: the timeline panel shows the active sequence, whose
+    // history records every edit
+    view: SequenceView,
     dirty: bool,
     project_path: Option<PathBuf>,
+    /// Edits (including undos and redos) not yet appended to the project's
+    /// operation log, for merging with other editors' logs
+    oplog: OperationLog,
 
     // Command system
     command_registry: CommandRegistry,
@@ -92,6 +120,7 @@ struct ProEditApp {
     audio_mixer: AudioMixerState,
     curve_editor: CurveEditorState,
     export_dialog: ExportDialogState,
+    load_report: LoadReportState,
 }
 
 impl ProEditApp {
@@ -117,11 +146,17 @@ impl ProEditApp {
             }
         });
 
-        let mut project = Project::new("New Project");
-        project.add_sequence(Sequence::default());
+        let project = new_project();
 
         let audio_engine = match proedit_audio::AudioEngine::new() {
-            Ok(engine) => {
+            Ok(mut engine) => {
+                if let Err(e) = engine.open_output(Box::new(CpalOutput::new(None))) {
+                    // Keep the engine's clock running without a device.
+                    warn!("No audio output device ({}), using null output", e);
+                    if let Err(e) = engine.open_output(Box::new(NullOutput::new())) {
+                        warn!("Null audio output failed: {}", e);
+                    }
+                }
                 info!("Audio engine initialized");
                 Some(engine)
             }
@@ -131,6 +166,20 @@ impl ProEditApp {
             }
         };
 
+        let peak_cache = match PeakCache::new(
+            std::env::temp_dir().join("proedit").join("peaks"),
+            Arc::new(|path: &str| {
+                let samples = proedit_media::decode_audio(path, PEAK_SAMPLE_RATE, 2)?;
+                Ok(Arc::new(PcmSource::new(PEAK_SAMPLE_RATE, 2, samples)))
+            }),
+        ) {
+            Ok(cache) => Some(cache),
+            Err(e) => {
+                warn!("Waveform peak cache unavailable: {}", e);
+                None
+            }
+        };
+
         let mut app = Self {
             ai_engine: Some(ai_bridge::init_ai_engine()),
             project,
@@ -142,10 +191,14 @@ impl ProEditApp {
             start_time: std::time::Instant::now(),
             frame_number: 0,
             audio_engine,
-            undo_snapshots: Vec::new(),
-            redo_snapshots: Vec::new(),
+            peak_cache,
+            recorder: None,
+            media_paths: HashMap::new(),
+            waveform_zoom: 0.0,
+            view: SequenceView::default(),
             dirty: false,
             project_path: None,
+            oplog: OperationLog::new(),
             command_registry: CommandRegistry::new(),
             top_bar: TopBarState::default(),
             timeline: TimelineState::default(),
@@ -158,75 +211,56 @@ impl ProEditApp {
             audio_mixer: AudioMixerState::default(),
             curve_editor: CurveEditorState::default(),
             export_dialog: ExportDialogState::default(),
+            load_report: LoadReportState::default(),
         };
+        if let Some(engine) = &app.audio_engine {
+            let mut mixer = engine.mixer.lock();
+            for name in &app.audio_mixer.buses {
+                mixer.add_bus(Bus::new(name.clone()));
+            }
+        }
         // Load demo content so the app looks populated on first launch
         if app.decoder.is_none() {
             app.load_demo_content();
         }
+        app.load_media_from_project();
+        app.show_active_sequence();
         app
     }
 
     /// Populate timeline and media browser with sample content for demo purposes.
     fn load_demo_content(&mut self) {
         use proedit_ui::media_browser::{MediaItem, MediaKind};
-        use proedit_ui::timeline::ClipKind;
 
-        self.timeline.clips = vec![
-            TimelineClip {
-                id: 1,
-                name: "Interview A.mp4".into(),
-                color: Theme::accent(),
-                start: 0.0,
-                dur: 120.0,
-                track: 2,
-                clip_type: ClipKind::Video,
-            },
-            TimelineClip {
-                id: 2,
-                name: "B-Roll Forest.mp4".into(),
-                color: Theme::cyan(),
-                start: 60.0,
-                dur: 90.0,
-                track: 1,
-                clip_type: ClipKind::Video,
-            },
-            TimelineClip {
-                id: 3,
-                name: "Title Card".into(),
-                color: Theme::purple(),
-                start: 10.0,
-                dur: 40.0,
-                track: 0,
-                clip_type: ClipKind::Gfx,
-            },
-            TimelineClip {
-                id: 4,
-                name: "BG Music.wav".into(),
-                color: Theme::green(),
-                start: 0.0,
-                dur: 200.0,
-                track: 3,
-                clip_type: ClipKind::Audio,
-            },
-            TimelineClip {
-                id: 5,
-                name: "VO Take 3.wav".into(),
-                color: Theme::pink(),
-                start: 20.0,
-                dur: 80.0,
-                track: 4,
-                clip_type: ClipKind::Audio,
-            },
-            TimelineClip {
-                id: 6,
-                name: "SFX Whoosh".into(),
-                color: Theme::amber(),
-                start: 58.0,
-                dur: 12.0,
-                track: 5,
-                clip_type: ClipKind::Audio,
-            },
+        let Some(sequence) = self.project.active_sequence_mut() else {
+            return;
+        };
+        let rate = sequence.frame_rate;
+        let frames = |n: i64| RationalTime::from_frames(n, rate);
+        let demo = [
+            ("Interview A.mp4", 0, 120, 2),
+            ("B-Roll Forest.mp4", 60, 90, 1),
+            ("Title Card", 10, 40, 0),
+            ("BG Music.wav", 0, 200, 3),
+            ("VO Take 3.wav", 20, 80, 4),
+            ("SFX Whoosh", 58, 12, 5),
         ];
+        for (name, start, dur, lane) in demo {
+            let clip = if name == "Title Card" {
+                let title = TextGenerator::new("Title Card", 96.0);
+                Clip::generated(name, Generator::Text(title), frames(dur))
+            } else {
+                let mut clip = Clip::new(name, ClipRef::new(name, frames(dur)));
+                clip.duration = frames(dur);
+                clip
+            };
+            let track = if lane < 3 {
+                &mut sequence.video_tracks[2 - lane]
+            } else {
+                &mut sequence.audio_tracks[lane - 3]
+            };
+            track.overwrite(frames(start), clip);
+        }
 
         self.media_browser.items = vec![
             MediaItem {
@@ -298,42 +332,306 @@ impl ProEditApp {
     }
 
     fn frame_rate(&self) -> FrameRate {
-        self.decoder
-            .as_ref()
-            .map(|d| d.frame_rate())
-            .unwrap_or(self.project.frame_rate)
+        self.decoder.as_ref().map(|d| d.frame_rate()).unwrap_or(
+            self.project
+                .active_sequence()
+                .map_or(self.project.frame_rate, |s| s.frame_rate),
+        )
+    }
+
+    /// Decode the audio engine's sources from the project's media, with
+    /// relative source paths resolved against the project's folder.
+    fn load_media_from_project(&self) {
+        let Some(engine) = self.audio_engine.as_ref() else {
+            return;
+        };
+        engine.set_source_loader(Arc::new(ProjectAudioLoader {
+            root: self.project.media_root.clone(),
+            sample_rate: engine.sample_rate(),
+        }));
+    }
+
+    /// Start or stop the audio engine to match `self.playing`, starting
+    /// from the active sequence at the playhead.
+    fn sync_audio(&mut self) {
+        let rate = self.frame_rate();
+        let Some(engine) = self.audio_engine.as_mut() else {
+            return;
+        };
+        if self.playing {
+            if let Some(sequence) = self.project.active_sequence() {
+                engine.set_sequence(sequence.clone());
+            }
+            engine.seek(RationalTime::from_seconds_f64(
+                self.timeline.playhead as f64 / rate.to_fps_f64(),
+            ));
+            engine.play();
+        } else {
+            engine.stop();
+        }
+    }
+
+    /// Frame being heard, compensated for output latency, when audio is
+    /// playing at normal speed through an output.
+    fn audio_clock_frame(&self) -> Option<f32> {
+        let engine = self.audio_engine.as_ref()?;
+        if self.speed != 1.0 || !engine.is_playing() || engine.output().is_none() {
+            return None;
+        }
+        let rate = self.frame_rate();
+        Some(engine.audible_position().to_frames(rate) as f32)
+    }
+
+    /// Measure the active sequence's mix against a loudness spec.
+    fn check_loudness(&mut self, target: LoudnessTarget) -> Option<ComplianceReport> {
+        let engine = self.audio_engine.as_ref()?;
+        let sequence = self.project.active_sequence()?;
+        engine.set_sequence(sequence.clone());
+        let measured = engine.measure_loudness(None)?;
+        let report = ComplianceReport {
+            target,
+            integrated: measured.integrated,
+            loudness_range: measured.loudness_range,
+            true_peak: measured.true_peak,
+        };
+        info!("{report}");
+        Some(report)
+    }
+
+    /// Apply a change from the audio mixer panel: routing goes to the
+    /// engine, normalization is an edit of the active sequence.
+    fn apply_mixer_action(&mut self, action: AudioMixerAction) {
+        if let AudioMixerAction::NormalizeMaster { target } = action {
+            self.normalize_master(target);
+            return;
+        }
+        let Some(engine) = self.audio_engine.as_ref() else {
+            return;
+        };
+        let mut mixer = engine.mixer.lock();
+        match action {
+            AudioMixerAction::SetLayout(index) => {
+                mixer.set_layout(ChannelLayout::ALL[index]);
+            }
+            AudioMixerAction::SetRoute { track, bus } => {
+                while mixer.channel_count() <= track {
+                    mixer.add_channel();
+                }
+                if let Some(channel) = mixer.channel_mut(track) {
+                    channel.output = bus.map_or(Route::Master, Route::Bus);
+                }
+            }
+            AudioMixerAction::NormalizeMaster { .. } => {}
+        }
+    }
+
+    /// Set the active sequence's master gain so its mix measures `target`
+    /// LUFS, as an undoable edit.
+    fn normalize_master(&mut self, target: f64) {
+        let (Some(engine), Some(sequence)) =
+            (self.audio_engine.as_ref(), self.project.active_sequence())
+        else {
+            return;
+        };
+        engine.set_sequence(sequence.clone());
+        let Some(edit) = engine.normalize_master(None, target) else {
+            return;
+        };
+        self.execute_edit("Normalize Master", edit);
+        if let (Some(engine), Some(sequence)) =
+            (self.audio_engine.as_ref(), self.project.active_sequence())
+        {
+            engine.set_sequence(sequence.clone());
+        }
+    }
+
+    /// Render the active sequence's mix (and stems, if requested) for an
+    /// export job, returning the job with the mix attached as its audio.
+    fn render_export_audio(&mut self, job: ExportJob) -> ExportJob {
+        let Some(engine) = self.audio_engine.as_ref() else {
+            return job;
+        };
+        if let Some(sequence) = self.project.active_sequence() {
+            engine.set_sequence(sequence.clone());
+        }
+        let layout = ChannelLayout::from_channel_count(job.format.audio_channels as usize)
+            .unwrap_or_default();
+
+        if job.stems {
+            match engine.export_stems(None, layout, |bus| job.stem_path(&bus.name)) {
+                Ok(paths) => info!("Wrote {} audio stems", paths.len()),
+                Err(e) => warn!("Stem export failed: {}", e),
+            }
+        }
+        let mix = job.mix_path();
+        match engine.export_mix(&mix, None, layout) {
+            Ok(()) => {
+                info!("Wrote {} mix to {:?}", layout.name(), mix);
+                job.with_audio_input(mix)
+            }
+            Err(e) => {
+                warn!("Audio mix export failed: {}", e);
+                job
+            }
+        }
     }
 
     // ── Undo/Redo ────────────────────────────────────────────
 
-    fn push_undo(&mut self) {
-        self.undo_snapshots.push(self.timeline.clips.clone());
-        self.redo_snapshots.clear();
+    /// Apply an edit to the active sequence and record it in its history
+    /// and the operation log.
+    fn execute_edit(&mut self, label: impl Into<String>, command: EditCommand) {
+        let Some(sequence) = self.project.active_sequence_mut() else {
+            return;
+        };
+        let applied = self
+            .oplog
+            .record(author(), sequence, command)
+            .command
+            .clone();
+        sequence.history.push_labeled(label, applied);
+        // A finished gesture is its own step.
+        sequence.history.seal();
         self.dirty = true;
-        // Limit history depth
-        if self.undo_snapshots.len() > 200 {
-            self.undo_snapshots.remove(0);
+        self.sync_timeline();
+        // Show the edited values.
+        self.inspector.clip = None;
+    }
+
+    /// Record the inspector's values for clip `id` as an undoable edit.
+    ///
+    /// Consecutive changes to one clip (a slider drag) merge into one step.
+    fn apply_inspector(&mut self, id: usize) {
+        let Some(props) = &self.inspector.clip else {
+            return;
+        };
+        let speed = f64::from(props.speed) / 100.0;
+        let Some(sequence) = self.project.active_sequence_mut() else {
+            return;
+        };
+        // Transform properties are not part of the clip model.
+        if self
+            .view
+            .clip(sequence, id)
+            .map_or(true, |clip| (clip.speed - speed).abs() < 1e-6)
+        {
+            return;
+        }
+        if let Some(command) = self.view.update(sequence, id, |clip| clip.speed = speed) {
+            let applied = self
+                .oplog
+                .record(author(), sequence, command)
+                .command
+                .clone();
+            sequence.history.push(applied);
+            self.dirty = true;
+            self.sync_timeline();
         }
     }
 
     fn undo(&mut self) {
-        if let Some(snapshot) = self.undo_snapshots.pop() {
-            self.redo_snapshots.push(self.timeline.clips.clone());
-            self.timeline.clips = snapshot;
+        let Some(sequence) = self.project.active_sequence_mut() else {
+            return;
+        };
+        let label = sequence.history.undo_label().map(str::to_owned);
+        // Undoing is a new operation for everyone else.
+        if let Some(inverse) = sequence.history.undo() {
+            self.oplog.record(author(), sequence, inverse);
             self.dirty = true;
-            info!("Undo");
+            self.sync_timeline();
+            self.inspector.clip = None;
+            info!("Undo {}", label.unwrap_or_default());
         }
     }
 
     fn redo(&mut self) {
-        if let Some(snapshot) = self.redo_snapshots.pop() {
-            self.undo_snapshots.push(self.timeline.clips.clone());
-            self.timeline.clips = snapshot;
+        let Some(sequence) = self.project.active_sequence_mut() else {
+            return;
+        };
+        let label = sequence.history.redo_label().map(str::to_owned);
+        if let Some(command) = sequence.history.redo() {
+            self.oplog.record(author(), sequence, command);
             self.dirty = true;
-            info!("Redo");
+            self.sync_timeline();
+            self.inspector.clip = None;
+            info!("Redo {}", label.unwrap_or_default());
+        }
+    }
+
+    // ── Sequences ────────────────────────────────────────────
+
+    /// Rebuild the timeline panel from the active sequence.
+    fn sync_timeline(&mut self) {
+        if let Some(sequence) = self.project.active_sequence() {
+            self.view.sync(sequence, &mut self.timeline);
+        }
+    }
+
+    /// Save the playhead into the active sequence.
+    fn store_playhead(&mut self) {
+        if let Some(sequence) = self.project.active_sequence_mut() {
+            sequence.playhead =
+                sequence_view::from_frames(self.timeline.playhead, sequence.frame_rate);
         }
     }
 
+    /// Show the active sequence: its clips, tracks and playhead.
+    fn show_active_sequence(&mut self) {
+        self.playing = false;
+        self.sync_audio();
+        for sequence in &mut self.project.sequences {
+            SequenceView::ensure_lanes(sequence);
+        }
+        self.timeline.selected_clip = None;
+        self.timeline.selection.clear();
+        self.timeline.waveform_cache.clear();
+        if let Some(sequence) = self.project.active_sequence() {
+            self.timeline.playhead =
+                sequence_view::to_frames(sequence.playhead, sequence.frame_rate);
+        }
+        self.sync_timeline();
+    }
+
+    /// Make `id` the active sequence, keeping the current one's playhead.
+    fn switch_sequence(&mut self, id: uuid::Uuid) {
+        self.store_playhead();
+        if let Err(e) = self.project.set_active_sequence(id) {
+            warn!("Cannot switch sequence: {}", e);
+            return;
+        }
+        self.show_active_sequence();
+        info!(
+            "Switched to {}",
+            self.project
+                .active_sequence()
+                .map_or("", |s| s.name.as_str())
+        );
+    }
+
+    /// Activate the sequence after the active one, wrapping around.
+    fn next_sequence(&mut self) {
+        let sequences = &self.project.sequences;
+        let active = self.project.active_sequence().map(|s| s.id);
+        let index = sequences.iter().position(|s| Some(s.id) == active);
+        let next = index.map_or(0, |i| (i + 1) % sequences.len());
+        if let Some(id) = sequences.get(next).map(|s| s.id) {
+            self.switch_sequence(id);
+        }
+    }
+
+    /// Add an empty sequence with the active one's settings and switch to it.
+    fn new_sequence(&mut self) {
+        self.store_playhead();
+        let settings = self
+            .project
+            .active_sequence()
+            .map_or_else(|| Sequence::default().settings(), Sequence::settings);
+        let name = format!("Sequence {}", self.project.sequences.len() + 1);
+        self.project.create_sequence(name, settings);
+        self.dirty = true;
+        self.show_active_sequence();
+    }
+
     // ── Save/Load ──────────────────────────────────────────
 
     fn save_project(&mut self) {
@@ -346,10 +644,11 @@ impl ProEditApp {
                 .save_file()
         };
         if let Some(path) = path {
-            let file = ProjectFile::new(self.project.clone());
-            match file.save_to_file(&path) {
+            self.store_playhead();
+            match project_io::save(&self.project, &self.oplog, &path) {
                 Ok(()) => {
                     self.project_path = Some(path);
+                    self.oplog = OperationLog::new();
                     self.dirty = false;
                     info!("Project saved");
                 }
@@ -364,13 +663,28 @@ impl ProEditApp {
             .add_filter("ProEdit Project", &["pep"])
             .pick_file();
         if let Some(path) = path {
-            match ProjectFile::load_from_file(&path) {
-                Ok(file) => {
-                    self.project = file.project;
+            match project_io::open(&path) {
+                Ok((project, findings)) => {
+                    for diagnostic in &findings.repaired {
+                        info!("Project repaired: {}", diagnostic);
+                    }
+                    for diagnostic in &findings.remaining {
+                        warn!("Project validation: {}", diagnostic);
+                    }
+                    // Repairs exist only in memory until the project is saved.
+                    let dirty = !findings.repaired.is_empty();
+                    self.load_report.report(
+                        project.name.clone(),
+                        findings.repaired,
+                        findings.remaining,
+                    );
+                    self.project = project;
                     self.project_path = Some(path);
-                    self.dirty = false;
-                    self.undo_snapshots.clear();
-                    self.redo_snapshots.clear();
+                    self.oplog = OperationLog::new();
+                    self.dirty = dirty;
+                    self.view.reset();
+                    self.load_media_from_project();
+                    self.show_active_sequence();
                     info!("Project loaded");
                 }
                 Err(e) => eprintln!("Load failed: {}", e),
@@ -387,14 +701,12 @@ impl ProEditApp {
             "Save Project" => self.save_project(),
             "Open Project" => self.load_project(),
             "Import Media" => self.import_media(),
-            "Razor at Playhead" | "Split at Playhead" => {
-                self.push_undo();
-                self.razor_at_playhead();
-            }
-            "Ripple Delete" | "Delete" => {
-                self.push_undo();
-                self.delete_selected_clip();
-            }
+            "Razor at Playhead" | "Split at Playhead" => self.razor_at_playhead(),
+            "New Sequence" => self.new_sequence(),
+            "Next Sequence" => self.next_sequence(),
+            "Delete" => self.delete_selected_clip(),
+            "Ripple Delete" => self.ripple_delete_selected_clip(),
+            "Record Voice-Over" => self.toggle_recording(),
             "Add Marker" => {
                 self.timeline.markers.push(proedit_ui::timeline::Marker {
                     frame: self.timeline.playhead,
@@ -415,13 +727,7 @@ impl ProEditApp {
             }
             "Play/Pause" => {
                 self.playing = !self.playing;
-                if let Some(ref mut engine) = self.audio_engine {
-                    if self.playing {
-                        engine.play();
-                    } else {
-                        engine.stop();
-                    }
-                }
+                self.sync_audio();
             }
             "Zoom In" => {
                 self.timeline.zoom = (self.timeline.zoom + 0.2).min(3.0);
@@ -439,13 +745,13 @@ impl ProEditApp {
                 info!("Fullscreen toggle requested");
             }
             "New Project" => {
-                self.project = Project::new("New Project");
-                self.project.add_sequence(Sequence::default());
-                self.timeline.clips.clear();
-                self.undo_snapshots.clear();
-                self.redo_snapshots.clear();
+                self.project = new_project();
+                self.view.reset();
+                self.load_media_from_project();
+                self.show_active_sequence();
                 self.dirty = false;
                 self.project_path = None;
+                self.oplog = OperationLog::new();
                 info!("New project created");
             }
             "Detect Scenes" | "Scene Detect" => {
@@ -483,13 +789,7 @@ impl ProEditApp {
             // Space — toggle play/pause
             if inp.key_pressed(egui::Key::Space) {
                 self.playing = !self.playing;
-                if let Some(ref mut engine) = self.audio_engine {
-                    if self.playing {
-                        engine.play();
-                    } else {
-                        engine.stop();
-                    }
-                }
+                self.sync_audio();
             }
             // J — play reverse (speed -= 1)
             if inp.key_pressed(egui::Key::J) {
@@ -546,13 +846,15 @@ impl ProEditApp {
             }
             // C — razor at playhead (split selected clip)
             if inp.key_pressed(egui::Key::C) {
-                self.push_undo();
                 self.razor_at_playhead();
             }
-            // Delete/Backspace — delete selected clip
+            // Delete/Backspace — delete selected clip, ⇧ to ripple
             if inp.key_pressed(egui::Key::Delete) || inp.key_pressed(egui::Key::Backspace) {
-                self.push_undo();
-                self.delete_selected_clip();
+                if inp.modifiers.shift {
+                    self.ripple_delete_selected_clip();
+                } else {
+                    self.delete_selected_clip();
+                }
             }
             // G — toggle curve editor
             if inp.key_pressed(egui::Key::G) {
@@ -616,36 +918,124 @@ impl ProEditApp {
             return;
         };
         let playhead = self.timeline.playhead;
+        let Some(sequence) = self.project.active_sequence() else {
+            return;
+        };
+        // Only splits if the playhead is within the clip bounds
+        let Some(command) = self.view.split(sequence, selected_id, playhead) else {
+            return;
+        };
+        let name = self
+            .view
+            .clip(sequence, selected_id)
+            .map(|c| c.name.clone())
+            .unwrap_or_default();
+        self.execute_edit(format!("Razor {}", name), command);
+        info!("Razor split clip {} at frame {}", selected_id, playhead);
+    }
 
-        let clip_idx = self.timeline.clips.iter().position(|c| c.id == selected_id);
-        let Some(idx) = clip_idx else { return };
+    // ── Voice-over ───────────────────────────────────────────
 
-        let clip = &self.timeline.clips[idx];
-        // Only split if playhead is within the clip bounds
-        if playhead <= clip.start || playhead >= clip.start + clip.dur {
+    /// Start a voice-over pass, or end the one under way.
+    ///
+    /// The pass punches in over the selected audio clip, or on the first
+    /// audio track from the playhead to the end of the cut.
+    fn toggle_recording(&mut self) {
+        if self.recorder.as_ref().is_some_and(Recorder::is_recording) {
+            self.finish_recording();
             return;
         }
-
-        let split_offset = playhead - clip.start;
-        let next_id = self.timeline.clips.iter().map(|c| c.id).max().unwrap_or(0) + 1;
-
-        // Create the right half
-        let right_half = proedit_ui::timeline::TimelineClip {
-            id: next_id,
-            name: format!("{} (split)", clip.name),
-            color: clip.color,
-            start: playhead,
-            dur: clip.dur - split_offset,
-            track: clip.track,
-            clip_type: clip.clip_type,
+        let Some(sequence) = self.project.active_sequence() else {
+            return;
         };
+        if sequence.audio_tracks.is_empty() {
+            warn!("Recording needs an audio track");
+            return;
+        }
+        let selected = self
+            .timeline
+            .selected_clip
+            .and_then(|id| self.view.audio_range(sequence, id));
+        let (track, punch) = selected.unwrap_or_else(|| {
+            let start = sequence_view::from_frames(self.timeline.playhead, sequence.frame_rate);
+            let end = sequence.duration();
+            let duration = if end > start {
+                end - start
+            } else {
+                RationalTime::new(OPEN_PUNCH_SECONDS, 1)
+            };
+            (0, TimeRange::new(start, duration))
+        });
+        let media_dir = self
+            .project_path
+            .as_ref()
+            .and_then(|path| path.parent())
+            .map_or_else(
+                || std::env::temp_dir().join("proedit").join("Recordings"),
+                |dir| dir.join("Recordings"),
+            );
+        let mut settings = RecordSettings::new(track, punch, media_dir);
+        if let Some(stats) = self.audio_engine.as_ref().and_then(|e| e.output_stats()) {
+            settings.latency = stats.latency;
+        }
+        let start = settings.start_time();
 
-        // Trim the left half
-        self.timeline.clips[idx].dur = split_offset;
+        let recorder = self
+            .recorder
+            .get_or_insert_with(|| Recorder::new(Box::new(CpalInput::new(None))));
+        if let Err(e) = recorder.start(settings) {
+            warn!("Recording failed to start: {}", e);
+            return;
+        }
+        self.speed = 1.0;
+        self.timeline.playhead = sequence_view::to_frames(start, self.frame_rate());
+        self.playing = true;
+        self.sync_audio();
+    }
 
-        // Insert right half after the original
-        self.timeline.clips.insert(idx + 1, right_half);
-        info!("Razor split clip {} at frame {}", selected_id, playhead);
+    /// Write the input captured since the last frame, ending the pass at
+    /// its punch-out point.
+    fn poll_recording(&mut self) {
+        let Some(recorder) = self.recorder.as_mut().filter(|r| r.is_recording()) else {
+            return;
+        };
+        match recorder.poll() {
+            Ok(RecordState::Done) => self.finish_recording(),
+            Ok(_) => {}
+            Err(e) => {
+                warn!("Recording failed: {}", e);
+                self.finish_recording();
+            }
+        }
+    }
+
+    /// Stop the pass and playback and put the take on its track.
+    fn finish_recording(&mut self) {
+        let Some(recorder) = self.recorder.as_mut() else {
+            return;
+        };
+        let take = recorder.stop();
+        self.playing = false;
+        self.sync_audio();
+        let take = match take {
+            Ok(Some(take)) => take,
+            Ok(None) => {
+                info!("Nothing recorded inside the punch range");
+                return;
+            }
+            Err(e) => {
+                warn!("Recording failed: {}", e);
+                return;
+            }
+        };
+        let Some(sequence) = self.project.active_sequence() else {
+            return;
+        };
+        let media_root = self.project.media_root.as_deref();
+        match proedit_audio::place_take(sequence, &take, media_root) {
+            Ok(command) => self.execute_edit(format!("Record {}", take.name), command),
+            Err(e) => warn!("Could not place {}: {}", take.name, e),
+        }
     }
 
     /// Delete the currently selected clip from the timeline.
@@ -653,11 +1043,68 @@ impl ProEditApp {
         let Some(selected_id) = self.timeline.selected_clip else {
             return;
         };
-        self.timeline.clips.retain(|c| c.id != selected_id);
+        let Some(sequence) = self.project.active_sequence() else {
+            return;
+        };
+        let Some(command) = self.view.lift(sequence, selected_id) else {
+            return;
+        };
+        let name = self
+            .view
+            .clip(sequence, selected_id)
+            .map(|c| c.name.clone())
+            .unwrap_or_default();
+        self.execute_edit(format!("Delete {}", name), command);
         self.timeline.selected_clip = None;
         info!("Deleted clip {}", selected_id);
     }
 
+    /// Delete the selected clip and close the gap it leaves.
+    fn ripple_delete_selected_clip(&mut self) {
+        let Some(selected_id) = self.timeline.selected_clip else {
+            return;
+        };
+        let Some(sequence) = self.project.active_sequence() else {
+            return;
+        };
+        let Some(command) = self.view.ripple_delete(sequence, selected_id) else {
+            return;
+        };
+        let name = self
+            .view
+            .clip(sequence, selected_id)
+            .map(|c| c.name.clone())
+            .unwrap_or_default();
+        self.execute_edit(format!("Ripple Delete {}", name), command);
+        self.timeline.selected_clip = None;
+        info!("Ripple deleted clip {}", selected_id);
+    }
+
+    /// Commit a drag or trim the timeline panel has shown for clip `id`.
+    fn place_clip(&mut self, id: usize, verb: &str) {
+        let Some(sequence) = self.project.active_sequence() else {
+            return;
+        };
+        let command = self
+            .timeline
+            .clips
+            .iter()
+            .find(|c| c.id == id)
+            .and_then(|placed| self.view.place(sequence, placed));
+        match command {
+            Some(command) => {
+                let name = self
+                    .view
+                    .clip(sequence, id)
+                    .map(|c| c.name.clone())
+                    .unwrap_or_default();
+                self.execute_edit(format!("{} {}", verb, name), command);
+            }
+            // Put the panel back in step with the sequence.
+            None => self.sync_timeline(),
+        }
+    }
+
     /// Sync the inspector panel to the currently selected timeline clip.
     fn sync_inspector(&mut self) {
         match self.timeline.selected_clip {
@@ -669,7 +1116,7 @@ impl ProEditApp {
                         .inspector
                         .clip
                         .as_ref()
-                        .map_or(true, |ic| ic.name != clip.name);
+                        .map_or(true, |ic| ic.clip_id != Some(clip.id));
                     if needs_update {
                         let clip_type = match clip.clip_type {
                             proedit_ui::timeline::ClipKind::Video => {
@@ -681,14 +1128,25 @@ impl ProEditApp {
                             proedit_ui::timeline::ClipKind::Gfx => {
                                 proedit_ui::inspector::ClipType::Gfx
                             }
+                            proedit_ui::timeline::ClipKind::Adjustment => {
+                                proedit_ui::inspector::ClipType::Adjustment
+                            }
                         };
-                        self.inspector.clip = Some(proedit_ui::InspectorClip::new(
+                        let mut inspected = proedit_ui::InspectorClip::new(
                             Some(clip.id),
                             clip.name.clone(),
                             clip.color,
                             clip_type,
                             clip.dur,
-                        ));
+                        );
+                        let model = self
+                            .project
+                            .active_sequence()
+                            .and_then(|s| self.view.clip(s, id));
+                        if let Some(model) = model {
+                            inspected.speed = (model.speed * 100.0) as f32;
+                        }
+                        self.inspector.clip = Some(inspected);
                     }
                 } else {
                     self.inspector.clip = None;
@@ -750,16 +1208,57 @@ impl ProEditApp {
             self.media_browser
                 .items
                 .push(proedit_ui::media_browser::MediaItem {
-                    name,
+                    name: name.clone(),
                     kind,
                     duration: duration_str,
                     size,
                     color,
                 });
+            if kind == proedit_ui::media_browser::MediaKind::Audio {
+                if let Some(cache) = &self.peak_cache {
+                    cache.request(&path);
+                }
+            }
+            self.media_paths.insert(name.clone(), path.clone());
             info!("Imported: {:?}", path);
         }
     }
 
+    /// Fill the timeline's waveform cache for audio clips whose peak
+    /// files are ready, redrawing them all when the zoom changes.
+    fn refresh_waveforms(&mut self) {
+        let Some(cache) = &self.peak_cache else {
+            return;
+        };
+        if self.waveform_zoom != self.timeline.zoom {
+            self.waveform_zoom = self.timeline.zoom;
+            self.timeline.waveform_cache.clear();
+        }
+        let zoom = self.timeline.zoom as f64;
+        let fps = self.timeline.fps.max(1.0) as f64;
+        for clip in &self.timeline.clips {
+            if clip.clip_type != ClipKind::Audio
+                || self.timeline.waveform_cache.contains_key(&clip.id)
+            {
+                continue;
+            }
+            let Some((path, peaks)) = self
+                .media_paths
+                .get(&clip.name)
+                .and_then(|path| Some((path, cache.get(path)?)))
+            else {
+                continue;
+            };
+            let samples_per_pixel = peaks.sample_rate() as f64 / fps / zoom;
+            let mut pixels =
+                vec![WaveformSample::default(); (clip.dur as f64 * zoom).ceil() as usize];
+            cache.peaks(path, 0, 0.0, samples_per_pixel, &mut pixels);
+            self.timeline
+                .waveform_cache
+                .insert(clip.id, pixels.iter().map(|p| [p.min, p.max]).collect());
+        }
+    }
+
     // ── Page Switching ──────────────────────────────────────────
 
     fn apply_page_layout(&mut self, page: Page) {
@@ -813,11 +1312,12 @@ impl ProEditApp {
 impl eframe::App for ProEditApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         let time = self.start_time.elapsed().as_secs_f64();
-
         // ── Sync fps to UI components ────────────────────────────
         let fps = self.frame_rate().to_fps_f64() as f32;
         self.timeline.fps = fps;
         self.viewer.fps = fps;
+        self.refresh_waveforms();
+        self.poll_recording();
 
         // ── Playback ───────────────────────────────────────────
         if self.playing {
@@ -825,7 +1325,14 @@ impl eframe::App for ProEditApp {
                 1.0 / (self.frame_rate().to_fps_f64() * self.speed.abs() as f64),
             );
 
-            if self.last_frame_time.elapsed() >= frame_duration {
+            if let Some(frame) = self.audio_clock_frame() {
+                // Audio is the master clock: show the frame being heard.
+                if frame > self.timeline.playhead {
+                    self.decode_next_frame();
+                    self.timeline.playhead = frame;
+                    self.last_frame_time = std::time::Instant::now();
+                }
+            } else if self.last_frame_time.elapsed() >= frame_duration {
                 self.decode_next_frame();
                 self.timeline.playhead += self.speed;
                 self.last_frame_time = std::time::Instant::now();
@@ -896,14 +1403,9 @@ impl eframe::App for ProEditApp {
         // Handle timeline actions
         for action in timeline_actions {
             match action {
-                TimelineAction::TrimClip { .. } => {
-                    self.push_undo();
-                }
-                TimelineAction::DragClip { .. } => {
-                    self.push_undo();
-                }
+                TimelineAction::TrimClip { clip_id, .. } => self.place_clip(clip_id, "Trim"),
+                TimelineAction::DragClip { clip_id, .. } => self.place_clip(clip_id, "Move"),
                 TimelineAction::SplitClip { clip_id, offset } => {
-                    self.push_undo();
                     self.timeline.selected_clip = Some(clip_id);
                     self.razor_at_playhead();
                     let _ = (clip_id, offset);
@@ -949,8 +1451,8 @@ impl eframe::App for ProEditApp {
 
             for action in inspector_actions {
                 match action {
-                    proedit_ui::InspectorAction::PropertyChanged { .. } => {
-                        self.push_undo();
+                    proedit_ui::InspectorAction::PropertyChanged { clip_id } => {
+                        self.apply_inspector(clip_id);
                     }
                 }
             }
@@ -965,13 +1467,7 @@ impl eframe::App for ProEditApp {
                     match action {
                         proedit_ui::viewer::ViewerAction::TogglePlay => {
                             self.playing = !self.playing;
-                            if let Some(ref mut engine) = self.audio_engine {
-                                if self.playing {
-                                    engine.play();
-                                } else {
-                                    engine.stop();
-                                }
-                            }
+                            self.sync_audio();
                         }
                         proedit_ui::viewer::ViewerAction::SetSpeed(s) => {
                             self.speed = s;
@@ -1000,7 +1496,12 @@ impl eframe::App for ProEditApp {
             show_color_wheels(ctx, &mut self.color_wheels, time);
         }
         if self.top_bar.audio_mixer_open {
-            show_audio_mixer(ctx, &mut self.audio_mixer);
+            if let Some(sequence) = self.project.active_sequence() {
+                self.audio_mixer.master_gain_db = sequence.master_gain_db;
+            }
+            for action in show_audio_mixer(ctx, &mut self.audio_mixer) {
+                self.apply_mixer_action(action);
+            }
         }
 
         // ── Export dialog ─────────────────────────────────────
@@ -1010,11 +1511,18 @@ impl eframe::App for ProEditApp {
                 ExportDialogAction::StartExport {
                     format,
                     output_path,
+                    loudness_target,
+                    stems,
                 } => {
                     info!(
                         "Export requested: {:?} -> {:?}",
                         format.video_codec, output_path
                     );
+                    self.export_dialog.compliance =
+                        loudness_target.and_then(|target| self.check_loudness(target));
+                    let job = ExportJob::new(output_path, format).with_stems(stems);
+                    let job = self.render_export_audio(job);
+                    info!("Export command: ffmpeg {}", job.ffmpeg_args().join(" "));
                 }
                 ExportDialogAction::Cancel => {
                     info!("Export cancelled by user");
@@ -1033,6 +1541,8 @@ impl eframe::App for ProEditApp {
             }
         }
 
+        show_load_report(ctx, &mut self.load_report);
+
         // ── Command palette (must be last — topmost layer) ─────
         show_command_palette(ctx, &mut self.command_palette);
 
@@ -1042,3 +1552,43 @@ impl eframe::App for ProEditApp {
         }
     }
 }
+
+/// Author recorded with this session's operations: the login name.
+fn author() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".into())
+}
+
+/// An empty project with one sequence.
+fn new_project() -> Project {
+    let mut project = Project::new("New Project");
+    project.add_sequence(Sequence::default());
+    project
+}
+
+/// Decodes clip audio from the project's media in stereo at the engine's
+/// rate, seeking to the ranges the engine asks for.
+struct ProjectAudioLoader {
+    root: Option<PathBuf>,
+    sample_rate: u32,
+}
+
+impl ProjectAudioLoader {
+    fn path(&self, path: &str) -> PathBuf {
+        resolve_media_path(self.root.as_deref(), path)
+    }
+}
+
+impl SourceLoader for ProjectAudioLoader {
+    fn load(&self, path: &str) -> proedit_core::Result<Arc<PcmSource>> {
+        let samples = proedit_media::decode_audio(self.path(path), self.sample_rate, 2)?;
+        Ok(Arc::new(PcmSource::new(self.sample_rate, 2, samples)))
+    }
+
+    fn load_range(&self, path: &str, range: TimeRange) -> proedit_core::Result<Arc<PcmSource>> {
+        let samples =
+            proedit_media::decode_audio_range(self.path(path), self.sample_rate, 2, range)?;
+        Ok(Arc::new(PcmSource::new(self.sample_rate, 2, samples)))
+    }
+}
@@ -0,0 +1,146 @@
+//! Saving and opening projects together with their edit history.
+//!
+//! Each sequence's undo history is written to a sidecar next to the project
+//! file, so undo keeps working after a project is reopened. Operations
+//! recorded since the last save are appended to the project's operation log
+//! (`<project file>.oplog`), which other editors' logs can be rebased onto.
+
+use proedit_core::Result;
+use proedit_timeline::{Diagnostic, HistoryFile, OperationLog, Project, ProjectFile};
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// Write `project` and its history to `path`, and append `operations` to
+/// its operation log.
+pub fn save(project: &Project, operations: &OperationLog, path: &Path) -> Result<()> {
+    ProjectFile::new(project.clone()).save_to_file(path)?;
+    HistoryFile::save_for_project(project, path)?;
+    let log_path = oplog_path(path);
+    for operation in operations.operations() {
+        OperationLog::append_to_file(&log_path, operation)?;
+    }
+    Ok(())
+}
+
+/// Operation log path for a project file (`<project file>.oplog`).
+pub fn oplog_path(project_path: &Path) -> PathBuf {
+    let mut name = project_path.as_os_str().to_owned();
+    name.push(".oplog");
+    PathBuf::from(name)
+}
+
+/// What validation found in a project while opening it.
+#[derive(Debug, Clone, Default)]
+pub struct LoadFindings {
+    /// Issues `Project::repair` fixed.
+    pub repaired: Vec<Diagnostic>,
+    /// Issues left for the user (repair does not guess at intent).
+    pub remaining: Vec<Diagnostic>,
+}
+
+/// Read the project at `path` with its history, repairing what can be
+/// repaired. Returns the project and what was repaired and what is left.
+pub fn open(path: &Path) -> Result<(Project, LoadFindings)> {
+    let file = ProjectFile::load_from_file(path)?;
+    let mut project = file.project;
+    let repaired = project.repair();
+    let remaining = project.validate();
+    // A stale or foreign history only costs the undo steps; a repaired
+    // sequence no longer matches its history, so it starts a fresh one.
+    if let Err(e) = HistoryFile::load_into_project(&mut project, path) {
+        warn!("Ignoring saved history: {}", e);
+    }
+    Ok((
+        project,
+        LoadFindings {
+            repaired,
+            remaining,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sequence_view::SequenceView;
+    use proedit_core::RationalTime;
+    use proedit_timeline::{Clip, ClipRef, Sequence};
+    use proedit_ui::TimelineState;
+
+    #[test]
+    fn test_history_survives_save_and_reload() {
+        let mut project = Project::new("Round Trip");
+        let mut sequence = Sequence::default();
+        SequenceView::ensure_lanes(&mut sequence);
+        let mut clip = Clip::new("a", ClipRef::new("a.mp4", RationalTime::new(20, 1)));
+        clip.duration = RationalTime::new(5, 1);
+        sequence.video_tracks[0].append_clip(clip);
+        project.add_sequence(sequence);
+        let base = project.clone();
+
+        // A drag and an inspector edit, made the way the app makes them.
+        let mut view = SequenceView::default();
+        let mut timeline = TimelineState::default();
+        let mut log = OperationLog::new();
+        let sequence = project.active_sequence_mut().unwrap();
+        view.sync(sequence, &mut timeline);
+        let mut dragged = timeline.clips[0].clone();
+        dragged.start = 48.0;
+        let command = view.place(sequence, &dragged).unwrap();
+        let applied = log.record("editor", sequence, command).command.clone();
+        sequence.history.push(applied);
+        sequence.history.seal();
+        let command = view
+            .update(sequence, dragged.id, |c| c.speed = 2.0)
+            .unwrap();
+        let applied = log.record("editor", sequence, command).command.clone();
+        sequence.history.push(applied);
+
+        let dir = std::env::temp_dir().join(format!("proedit-project-io-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("round_trip.pep");
+        save(&project, &log, &path).unwrap();
+        let (mut reopened, findings) = open(&path).unwrap();
+        let saved_log = OperationLog::load_from_file(&oplog_path(&path)).unwrap();
+        std::fs::remove_dir_all(&dir).ok();
+        assert!(findings.repaired.is_empty() && findings.remaining.is_empty());
+
+        // The operation log replays the same edits onto the original.
+        assert_eq!(saved_log.len(), 2);
+        let mut replayed = base;
+        saved_log.replay(&mut replayed).unwrap();
+        let track = &replayed.active_sequence().unwrap().video_tracks[0];
+        assert_eq!(track.clip_at(1).unwrap().speed, 2.0);
+
+        let sequence = reopened.active_sequence_mut().unwrap();
+        assert_eq!(sequence.history.undo_count(), 2);
+        assert_eq!(sequence.history.undo_label(), Some("Edit a"));
+        assert!(sequence.undo());
+        assert_eq!(sequence.video_tracks[0].clip_at(1).unwrap().speed, 1.0);
+        assert!(sequence.undo());
+        let track = &sequence.video_tracks[0];
+        assert_eq!(track.items.len(), 1);
+        assert_eq!(track.clip_at(0).unwrap().name, "a");
+        assert!(!sequence.undo());
+    }
+
+    #[test]
+    fn test_open_repairs_and_reports() {
+        let mut project = Project::new("Broken");
+        let mut sequence = Sequence::default();
+        sequence.video_tracks[0].append_gap(RationalTime::new(-1, 1));
+        project.add_sequence(sequence);
+
+        let dir =
+            std::env::temp_dir().join(format!("proedit-project-repair-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("broken.pep");
+        ProjectFile::new(project).save_to_file(&path).unwrap();
+        let (reopened, findings) = open(&path).unwrap();
+        std::fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(findings.repaired.len(), 1);
+        assert!(findings.remaining.is_empty());
+        assert!(reopened.validate().is_empty());
+    }
+}
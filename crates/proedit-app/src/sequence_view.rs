@@ -0,0 +1,485 @@
+//! The timeline panel's view of the active sequence.
+//!
+//! The panel draws and edits `TimelineClip`s, timed in frames, on six fixed
+//! lanes (V3, V2, V1, A1, A2, A3). `SequenceView` builds them from a
+//! `Sequence` and turns finished gestures back into `EditCommand`s, so every
+//! edit lands in the sequence's own history, which is saved with the project.
+//!
+//! A transition item on a video track rides along on the clip after it, so
+//! the compositor can blend into that clip from the one before.
+
+use proedit_core::{FrameRate, RationalTime, TimeRange};
+use proedit_timeline::{Clip, EditCommand, Sequence, Track, TrackItem};
+use proedit_ui::timeline::{ClipKind, ClipTransition, TimelineClip, TRACK_COUNT};
+use proedit_ui::{Theme, TimelineState};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Lanes 0..VIDEO_LANES are video tracks (topmost first); the rest are audio.
+const VIDEO_LANES: usize = 3;
+
+/// Maps panel clip IDs to sequence items and back.
+///
+/// Panel IDs stay stable across rebuilds so selection and waveform caches
+/// survive edits.
+#[derive(Debug, Default)]
+pub struct SequenceView {
+    items: HashMap<usize, Uuid>,
+    ids: HashMap<Uuid, usize>,
+    next_id: usize,
+}
+
+impl SequenceView {
+    /// Add empty tracks until the sequence has one for every panel lane.
+    pub fn ensure_lanes(sequence: &mut Sequence) {
+        while sequence.video_tracks.len() < VIDEO_LANES {
+            let name = format!("V{}", sequence.video_tracks.len() + 1);
+            sequence.video_tracks.push(Track::new_video(name));
+        }
+        while sequence.audio_tracks.len() < TRACK_COUNT - VIDEO_LANES {
+            let name = format!("A{}", sequence.audio_tracks.len() + 1);
+            sequence.audio_tracks.push(Track::new_audio(name));
+        }
+    }
+
+    /// Forget every ID, e.g. when another project is opened.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Rebuild the panel's clips and track states from `sequence`.
+    pub fn sync(&mut self, sequence: &Sequence, timeline: &mut TimelineState) {
+        let rate = sequence.frame_rate;
+        timeline.clips.clear();
+        for lane in 0..TRACK_COUNT {
+            let Some(track) = lane_track(sequence, lane) else {
+                continue;
+            };
+            timeline.track_muted[lane] = track.muted;
+            timeline.track_locked[lane] = track.locked;
+            let mut start = RationalTime::ZERO;
+            let mut transition = None;
+            for item in &track.items {
+                match item {
+                    TrackItem::Transition {
+                        transition_name,
+                        duration,
+                        params,
+                        easing,
+                    } if lane < VIDEO_LANES => {
+                        transition = Some(ClipTransition {
+                            name: transition_name.clone(),
+                            dur: to_frames(*duration, rate),
+                            params: params.clone(),
+                            easing: *easing,
+                        });
+                    }
+                    TrackItem::Clip(clip) => {
+                        let clip_type = if clip.is_generated() {
+                            ClipKind::Gfx
+                        } else if lane >= VIDEO_LANES {
+                            ClipKind::Audio
+                        } else {
+                            ClipKind::Video
+                        };
+                        timeline.clips.push(TimelineClip {
+                            id: self.id_for(clip.id),
+                            name: clip.name.clone(),
+                            color: kind_color(clip_type),
+                            start: to_frames(start, rate),
+                            dur: to_frames(clip.duration, rate),
+                            track: lane,
+                            clip_type,
+                            effects: Vec::new(),
+                            generator: clip.generator.clone().map(Box::new),
+                            transition: transition.take(),
+                        });
+                    }
+                    _ => transition = None,
+                }
+                start = start + item.duration();
+            }
+        }
+
+        let live: Vec<usize> = timeline.clips.iter().map(|c| c.id).collect();
+        self.items.retain(|id, _| live.contains(id));
+        self.ids.retain(|_, id| live.contains(id));
+        timeline.selection.retain(|id| live.contains(id));
+        if timeline.selected_clip.is_some_and(|id| !live.contains(&id)) {
+            timeline.selected_clip = None;
+        }
+        timeline.waveform_cache.retain(|id, _| live.contains(id));
+    }
+
+    /// The sequence clip behind panel clip `id`.
+    pub fn clip<'a>(&self, sequence: &'a Sequence, id: usize) -> Option<&'a Clip> {
+        let (track, index) = self.locate(sequence, id)?;
+        track.clip_at(index)
+    }
+
+    /// Move and/or trim a clip to where the panel now shows it. Returns
+    /// `None` if nothing changed.
+    ///
+    /// The clip is lifted from its track (leaving a gap) and overwritten at
+    /// the new position; trimming its head also advances its source in-point.
+    pub fn place(&self, sequence: &Sequence, placed: &TimelineClip) -> Option<EditCommand> {
+        let rate = sequence.frame_rate;
+        let (track, index) = self.locate(sequence, placed.id)?;
+        let mut clip = track.clip_at(index)?.clone();
+        let old_start = track.item_start_time(index);
+        let start = from_frames(placed.start.max(0.0), rate);
+        let duration = from_frames(placed.dur, rate);
+        let dst_track = lane_track(sequence, placed.track)?;
+        let unchanged = start == old_start && duration == clip.duration;
+        if duration <= RationalTime::ZERO || (unchanged && dst_track.id == track.id) {
+            return None;
+        }
+        if duration != clip.duration && start != old_start {
+            let shift = clip.source_offset(start - old_start);
+            clip.source_in = (clip.source_in + shift).max(RationalTime::ZERO);
+        }
+        clip.duration = duration;
+
+        let mut source = track.clone();
+        lift_item(&mut source, index);
+        if dst_track.id == track.id {
+            source.overwrite(start, clip);
+            source.consolidate_gaps();
+            return Some(replace(track, source));
+        }
+        let mut destination = dst_track.clone();
+        destination.overwrite(start, clip);
+        destination.consolidate_gaps();
+        Some(EditCommand::Batch(vec![
+            replace(track, source),
+            replace(dst_track, destination),
+        ]))
+    }
+
+    /// Edit the properties of panel clip `id`.
+    pub fn update(
+        &self,
+        sequence: &Sequence,
+        id: usize,
+        edit: impl FnOnce(&mut Clip),
+    ) -> Option<EditCommand> {
+        let (track, index) = self.locate(sequence, id)?;
+        let before = track.clip_at(index)?.clone();
+        let mut after = before.clone();
+        edit(&mut after);
+        Some(EditCommand::UpdateClip {
+            track_id: track.id,
+            clip_index: index,
+            before: Box::new(before),
+            after: Box::new(after),
+        })
+    }
+
+    /// Remove a clip, leaving a gap in its place.
+    pub fn lift(&self, sequence: &Sequence, id: usize) -> Option<EditCommand> {
+        let (track, index) = self.locate(sequence, id)?;
+        let mut lifted = track.clone();
+        lift_item(&mut lifted, index);
+        Some(replace(track, lifted))
+    }
+
+    /// Remove a clip and close the gap, pulling later items on its track
+    /// earlier. Transitions into or out of the clip go with it, as they
+    /// would be left with nothing on one side.
+    pub fn ripple_delete(&self, sequence: &Sequence, id: usize) -> Option<EditCommand> {
+        let (track, index) = self.locate(sequence, id)?;
+        let mut rippled = track.clone();
+        let is_transition =
+            |item: Option<&TrackItem>| matches!(item, Some(TrackItem::Transition { .. }));
+        if is_transition(rippled.items.get(index + 1)) {
+            rippled.items.remove(index + 1);
+        }
+        rippled.items.remove(index);
+        if index > 0 && is_transition(rippled.items.get(index - 1)) {
+            rippled.items.remove(index - 1);
+        }
+        trim_trailing_gaps(&mut rippled);
+        Some(replace(track, rippled))
+    }
+
+    /// Split a clip at timeline frame `frame`.
+    pub fn split(&self, sequence: &Sequence, id: usize, frame: f32) -> Option<EditCommand> {
+        let (track, index) = self.locate(sequence, id)?;
+        let clip = track.clip_at(index)?;
+        let offset = from_frames(frame, sequence.frame_rate) - track.item_start_time(index);
+        if offset <= RationalTime::ZERO || offset >= clip.duration {
+            return None;
+        }
+        Some(EditCommand::SplitClip {
+            track_id: track.id,
+            clip_index: index,
+            offset,
+            original: None,
+        })
+    }
+
+    /// Audio track index and timeline range of panel clip `id`, if it is
+    /// on an audio track.
+    pub fn audio_range(&self, sequence: &Sequence, id: usize) -> Option<(usize, TimeRange)> {
+        let item = *self.items.get(&id)?;
+        sequence
+            .audio_tracks
+            .iter()
+            .enumerate()
+            .find_map(|(number, track)| {
+                let (index, clip) = track.find_clip(item)?;
+                let start = track.item_start_time(index);
+                Some((number, TimeRange::new(start, clip.duration)))
+            })
+    }
+
+    fn id_for(&mut self, item: Uuid) -> usize {
+        if let Some(&id) = self.ids.get(&item) {
+            return id;
+        }
+        self.next_id += 1;
+        self.ids.insert(item, self.next_id);
+        self.items.insert(self.next_id, item);
+        self.next_id
+    }
+
+    /// Track and item index of panel clip `id`.
+    fn locate<'a>(&self, sequence: &'a Sequence, id: usize) -> Option<(&'a Track, usize)> {
+        let item = *self.items.get(&id)?;
+        sequence
+            .video_tracks
+            .iter()
+            .chain(&sequence.audio_tracks)
+            .find_map(|track| track.find_clip(item).map(|(index, _)| (track, index)))
+    }
+}
+
+/// The sequence track shown on panel lane `lane`.
+fn lane_track(sequence: &Sequence, lane: usize) -> Option<&Track> {
+    if lane < VIDEO_LANES {
+        sequence.video_tracks.get(VIDEO_LANES - 1 - lane)
+    } else {
+        sequence.audio_tracks.get(lane - VIDEO_LANES)
+    }
+}
+
+/// Replace item `index` with a gap, dropping gaps left at the end.
+fn lift_item(track: &mut Track, index: usize) {
+    let duration = track.items[index].duration();
+    track.items[index] = TrackItem::Gap { duration };
+    trim_trailing_gaps(track);
+}
+
+/// Merge adjacent gaps and drop those at the end of `track`.
+fn trim_trailing_gaps(track: &mut Track) {
+    track.consolidate_gaps();
+    while matches!(track.items.last(), Some(TrackItem::Gap { .. })) {
+        track.items.pop();
+    }
+}
+
+fn replace(track: &Track, edited: Track) -> EditCommand {
+    EditCommand::ReplaceItems {
+        track_id: track.id,
+        before: track.items.clone(),
+        after: edited.items,
+    }
+}
+
+fn kind_color(kind: ClipKind) -> egui::Color32 {
+    match kind {
+        ClipKind::Video => Theme::accent(),
+        ClipKind::Audio => Theme::green(),
+        ClipKind::Gfx => Theme::purple(),
+        ClipKind::Adjustment => Theme::amber(),
+    }
+}
+
+/// Timeline frames (as the panel counts them) in `time`.
+pub fn to_frames(time: RationalTime, rate: FrameRate) -> f32 {
+    (time.to_seconds_f64() * rate.to_fps_f64()) as f32
+}
+
+/// Time of panel frame `frames`, rounded to a whole frame.
+pub fn from_frames(frames: f32, rate: FrameRate) -> RationalTime {
+    RationalTime::from_frames(frames.round() as i64, rate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proedit_core::{EasingCurve, ParamValues};
+    use proedit_timeline::ClipRef;
+
+    fn sequence_with_clip() -> Sequence {
+        let mut sequence = Sequence::default();
+        SequenceView::ensure_lanes(&mut sequence);
+        let mut clip = Clip::new("a", ClipRef::new("a.mp4", RationalTime::new(20, 1)));
+        clip.duration = RationalTime::new(5, 1);
+        sequence.video_tracks[0].append_gap(RationalTime::new(1, 1));
+        sequence.video_tracks[0].append_clip(clip);
+        sequence
+    }
+
+    #[test]
+    fn test_sync_lays_out_lanes() {
+        let sequence = sequence_with_clip();
+        let mut view = SequenceView::default();
+        let mut timeline = TimelineState::default();
+        view.sync(&sequence, &mut timeline);
+
+        assert_eq!(timeline.clips.len(), 1);
+        let clip = &timeline.clips[0];
+        // V1 is the lowest video lane.
+        assert_eq!(clip.track, 2);
+        assert_eq!(clip.start, 24.0);
+        assert_eq!(clip.dur, 120.0);
+        assert_eq!(clip.clip_type, ClipKind::Video);
+    }
+
+    #[test]
+    fn test_sync_hands_transitions_to_the_next_clip() {
+        let mut sequence = sequence_with_clip();
+        let mut clip = Clip::new("b", ClipRef::new("b.mp4", RationalTime::new(20, 1)));
+        clip.duration = RationalTime::new(5, 1);
+        sequence.video_tracks[0].append_clip(clip);
+        sequence.video_tracks[0].insert_transition(1, "Wipe", RationalTime::new(1, 1));
+        let mut view = SequenceView::default();
+        let mut timeline = TimelineState::default();
+        view.sync(&sequence, &mut timeline);
+
+        assert_eq!(timeline.clips.len(), 2);
+        assert_eq!(timeline.clips[0].transition, None);
+        let incoming = &timeline.clips[1];
+        assert_eq!(incoming.start, 168.0);
+        assert_eq!(
+            incoming.transition,
+            Some(ClipTransition {
+                name: "Wipe".into(),
+                dur: 24.0,
+                params: ParamValues::new(),
+                easing: EasingCurve::Linear,
+            })
+        );
+    }
+
+    #[test]
+    fn test_drag_to_other_lane_is_undoable() {
+        let mut sequence = sequence_with_clip();
+        let mut view = SequenceView::default();
+        let mut timeline = TimelineState::default();
+        view.sync(&sequence, &mut timeline);
+        let id = timeline.clips[0].id;
+
+        let mut dragged = timeline.clips[0].clone();
+        dragged.start = 48.0;
+        dragged.track = 1;
+        sequence.execute(view.place(&sequence, &dragged).unwrap());
+        view.sync(&sequence, &mut timeline);
+        assert_eq!(sequence.video_tracks[0].clip_count(), 0);
+        assert_eq!(
+            sequence.video_tracks[1].item_start_time(1),
+            RationalTime::new(2, 1)
+        );
+        assert_eq!(timeline.clips[0].id, id);
+        assert_eq!(timeline.clips[0].track, 1);
+
+        assert!(sequence.undo());
+        view.sync(&sequence, &mut timeline);
+        assert_eq!(timeline.clips[0].track, 2);
+        assert_eq!(timeline.clips[0].start, 24.0);
+    }
+
+    #[test]
+    fn test_head_trim_advances_source() {
+        let mut sequence = sequence_with_clip();
+        let mut view = SequenceView::default();
+        let mut timeline = TimelineState::default();
+        view.sync(&sequence, &mut timeline);
+
+        let mut trimmed = timeline.clips[0].clone();
+        trimmed.start += 24.0;
+        trimmed.dur -= 24.0;
+        sequence.execute(view.place(&sequence, &trimmed).unwrap());
+
+        let clip = sequence.video_tracks[0].clip_at(1).unwrap();
+        assert_eq!(clip.source_in, RationalTime::new(1, 1));
+        assert_eq!(clip.duration, RationalTime::new(4, 1));
+        assert_eq!(
+            sequence.video_tracks[0].item_start_time(1),
+            RationalTime::new(2, 1)
+        );
+    }
+
+    #[test]
+    fn test_split_and_lift() {
+        let mut sequence = sequence_with_clip();
+        let mut view = SequenceView::default();
+        let mut timeline = TimelineState::default();
+        view.sync(&sequence, &mut timeline);
+        let id = timeline.clips[0].id;
+
+        assert!(view.split(&sequence, id, 24.0).is_none());
+        sequence.execute(view.split(&sequence, id, 48.0).unwrap());
+        view.sync(&sequence, &mut timeline);
+        assert_eq!(timeline.clips.len(), 2);
+
+        sequence.execute(view.lift(&sequence, id).unwrap());
+        view.sync(&sequence, &mut timeline);
+        assert_eq!(timeline.clips.len(), 1);
+        assert_eq!(timeline.clips[0].start, 48.0);
+    }
+
+    #[test]
+    fn test_ripple_delete_closes_the_gap() {
+        let mut sequence = sequence_with_clip();
+        let mut view = SequenceView::default();
+        let mut timeline = TimelineState::default();
+        view.sync(&sequence, &mut timeline);
+        let id = timeline.clips[0].id;
+        sequence.execute(view.split(&sequence, id, 48.0).unwrap());
+        view.sync(&sequence, &mut timeline);
+
+        sequence.execute(view.ripple_delete(&sequence, id).unwrap());
+        view.sync(&sequence, &mut timeline);
+        assert_eq!(timeline.clips.len(), 1);
+        // The right half moves up to where the deleted clip started.
+        assert_eq!(timeline.clips[0].start, 24.0);
+        assert_eq!(timeline.clips[0].dur, 96.0);
+
+        assert!(sequence.undo());
+        view.sync(&sequence, &mut timeline);
+        assert_eq!(timeline.clips.len(), 2);
+    }
+
+    #[test]
+    fn test_ripple_delete_takes_neighbouring_transitions() {
+        let mut sequence = sequence_with_clip();
+        let mut view = SequenceView::default();
+        let mut timeline = TimelineState::default();
+        view.sync(&sequence, &mut timeline);
+        let id = timeline.clips[0].id;
+        sequence.execute(view.split(&sequence, id, 72.0).unwrap());
+        view.sync(&sequence, &mut timeline);
+        let middle = timeline.clips[1].id;
+        sequence.execute(view.split(&sequence, middle, 96.0).unwrap());
+        let track = &mut sequence.video_tracks[0];
+        track.insert_transition(2, "Wipe", RationalTime::new(1, 2));
+        track.insert_transition(1, "Dissolve", RationalTime::new(1, 2));
+        assert!(sequence.validate().is_empty());
+        view.sync(&sequence, &mut timeline);
+
+        sequence.execute(view.ripple_delete(&sequence, middle).unwrap());
+        let track = &sequence.video_tracks[0];
+        assert_eq!(track.clip_count(), 2);
+        assert!(!track
+            .items
+            .iter()
+            .any(|item| matches!(item, TrackItem::Transition { .. })));
+        assert!(sequence.validate().is_empty());
+
+        // One undo brings back the clip and both transitions.
+        assert!(sequence.undo());
+        assert_eq!(sequence.video_tracks[0].items.len(), 6);
+    }
+}
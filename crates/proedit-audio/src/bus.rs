@@ -0,0 +1,376 @@
+//! Channel layouts, surround panning and submix buses.
+//!
+//! Every bus and the master share the mixer's `ChannelLayout`. Tracks
+//! route to the master or to one submix bus (`Route`); buses sum into
+//! the master. Channel order follows WAVE_FORMAT_EXTENSIBLE, so a
+//! rendered block can be written to a multichannel WAV as is.
+
+use serde::{Deserialize, Serialize};
+use std::f32::consts::{FRAC_1_SQRT_2, FRAC_PI_2};
+
+/// A loudspeaker of a channel layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Speaker {
+    FrontLeft,
+    FrontRight,
+    Center,
+    Lfe,
+    /// 5.1 surround (±110°).
+    SurroundLeft,
+    SurroundRight,
+    /// 7.1 side surround (±90°).
+    SideLeft,
+    SideRight,
+    /// 7.1 rear surround (±150°).
+    RearLeft,
+    RearRight,
+}
+
+impl Speaker {
+    /// Short label for meters and the routing matrix.
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::FrontLeft => "L",
+            Self::FrontRight => "R",
+            Self::Center => "C",
+            Self::Lfe => "LFE",
+            Self::SurroundLeft => "Ls",
+            Self::SurroundRight => "Rs",
+            Self::SideLeft => "Lss",
+            Self::SideRight => "Rss",
+            Self::RearLeft => "Lrs",
+            Self::RearRight => "Rrs",
+        }
+    }
+
+    /// Horizontal angle in degrees (0 = front, positive = right) per
+    /// ITU-R BS.775 / BS.2051. `None` for the LFE, which has no position.
+    pub fn azimuth(self) -> Option<f32> {
+        match self {
+            Self::FrontLeft => Some(-30.0),
+            Self::FrontRight => Some(30.0),
+            Self::Center => Some(0.0),
+            Self::Lfe => None,
+            Self::SurroundLeft => Some(-110.0),
+            Self::SurroundRight => Some(110.0),
+            Self::SideLeft => Some(-90.0),
+            Self::SideRight => Some(90.0),
+            Self::RearLeft => Some(-150.0),
+            Self::RearRight => Some(150.0),
+        }
+    }
+
+    /// BS.1770 channel weight: +1.5 dB for surrounds between 60° and
+    /// 120°, excluded for the LFE.
+    pub fn loudness_weight(self) -> f64 {
+        match self.azimuth() {
+            None => 0.0,
+            Some(az) if (60.0..=120.0).contains(&az.abs()) => 1.41,
+            Some(_) => 1.0,
+        }
+    }
+
+    /// WAVE_FORMAT_EXTENSIBLE speaker position bit.
+    fn mask(self) -> u32 {
+        match self {
+            Self::FrontLeft => 0x1,
+            Self::FrontRight => 0x2,
+            Self::Center => 0x4,
+            Self::Lfe => 0x8,
+            Self::SurroundLeft | Self::RearLeft => 0x10,
+            Self::SurroundRight | Self::RearRight => 0x20,
+            Self::SideLeft => 0x200,
+            Self::SideRight => 0x400,
+        }
+    }
+}
+
+/// Output channel layout of the mixer's buses.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChannelLayout {
+    #[default]
+    Stereo,
+    /// L R C LFE Ls Rs.
+    Surround51,
+    /// L R C LFE Lrs Rrs Lss Rss.
+    Surround71,
+}
+
+impl ChannelLayout {
+    /// All layouts, in menu order.
+    pub const ALL: [ChannelLayout; 3] = [Self::Stereo, Self::Surround51, Self::Surround71];
+
+    /// Display name.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Stereo => "Stereo",
+            Self::Surround51 => "5.1",
+            Self::Surround71 => "7.1",
+        }
+    }
+
+    /// Speakers in interleaved channel order.
+    pub fn speakers(self) -> &'static [Speaker] {
+        use Speaker::*;
+        match self {
+            Self::Stereo => &[FrontLeft, FrontRight],
+            Self::Surround51 => &[
+                FrontLeft,
+                FrontRight,
+                Center,
+                Lfe,
+                SurroundLeft,
+                SurroundRight,
+            ],
+            Self::Surround71 => &[
+                FrontLeft, FrontRight, Center, Lfe, RearLeft, RearRight, SideLeft, SideRight,
+            ],
+        }
+    }
+
+    /// Number of interleaved channels.
+    pub fn channel_count(self) -> usize {
+        self.speakers().len()
+    }
+
+    /// The layout with `channels` channels, if there is one.
+    pub fn from_channel_count(channels: usize) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|layout| layout.channel_count() == channels)
+    }
+
+    /// Whether the layout has more than two channels.
+    pub fn is_surround(self) -> bool {
+        self != Self::Stereo
+    }
+
+    /// WAVE_FORMAT_EXTENSIBLE channel mask.
+    pub fn channel_mask(self) -> u32 {
+        self.speakers().iter().fold(0, |mask, s| mask | s.mask())
+    }
+
+    /// Per-speaker gains for a mono source at `(x, y)`: `x` from -1
+    /// (left) to 1 (right), `y` from -1 (rear) to 1 (front).
+    ///
+    /// The edge of the square runs around the speaker ring (front edge
+    /// L..R, sides to the surrounds, rear edge behind the listener) and
+    /// the source is panned between the two nearest speakers at constant
+    /// power. Towards the center of the room it spreads evenly over all
+    /// speakers. The LFE gets nothing. Stereo ignores `y` and uses the
+    /// sine/cosine pan law.
+    pub fn pan_gains(self, x: f32, y: f32, gains: &mut [f32]) {
+        let speakers = self.speakers();
+        let gains = &mut gains[..speakers.len()];
+        gains.fill(0.0);
+        let x = x.clamp(-1.0, 1.0);
+        let y = y.clamp(-1.0, 1.0);
+
+        if !self.is_surround() {
+            let angle = (x + 1.0) * 0.25 * std::f32::consts::PI;
+            gains[0] = angle.cos();
+            gains[1] = angle.sin();
+            return;
+        }
+
+        let radius = x.abs().max(y.abs());
+        if radius > 0.0 {
+            ring_gains(speakers, square_azimuth(x / radius, y / radius), gains);
+        }
+        let positioned = speakers.iter().filter(|s| s.azimuth().is_some()).count();
+        let spread = (1.0 - radius * radius) / positioned as f32;
+        for (gain, speaker) in gains.iter_mut().zip(speakers) {
+            if speaker.azimuth().is_some() {
+                *gain = (radius * radius * *gain * *gain + spread).sqrt();
+            }
+        }
+    }
+
+    /// Fold interleaved frames of this layout down to interleaved stereo
+    /// (ITU-R BS.775: centre and surrounds at -3 dB, LFE dropped).
+    ///
+    /// `output` must hold two samples per input frame.
+    pub fn downmix_stereo(self, input: &[f32], output: &mut [f32]) {
+        let speakers = self.speakers();
+        for (frame, out) in input
+            .chunks_exact(speakers.len())
+            .zip(output.chunks_exact_mut(2))
+        {
+            let (mut left, mut right) = (0.0, 0.0);
+            for (sample, speaker) in frame.iter().zip(speakers) {
+                match speaker {
+                    Speaker::FrontLeft => left += sample,
+                    Speaker::FrontRight => right += sample,
+                    Speaker::Lfe => {}
+                    Speaker::Center => {
+                        left += sample * FRAC_1_SQRT_2;
+                        right += sample * FRAC_1_SQRT_2;
+                    }
+                    Speaker::SurroundLeft | Speaker::SideLeft | Speaker::RearLeft => {
+                        left += sample * FRAC_1_SQRT_2
+                    }
+                    Speaker::SurroundRight | Speaker::SideRight | Speaker::RearRight => {
+                        right += sample * FRAC_1_SQRT_2
+                    }
+                }
+            }
+            out[0] = left;
+            out[1] = right;
+        }
+    }
+}
+
+/// Azimuth of a point on the edge of the pan square (max(|x|, |y|) = 1).
+fn square_azimuth(x: f32, y: f32) -> f32 {
+    if y >= x.abs() {
+        // Front edge: between the front speakers.
+        x * 30.0
+    } else if x.abs() > y.abs() {
+        // Side edges: from the front speakers back to the surrounds.
+        x.signum() * (30.0 + (1.0 - y) * 40.0)
+    } else {
+        // Rear edge: from the surrounds to directly behind.
+        let side = if x < 0.0 { -1.0 } else { 1.0 };
+        side * (180.0 - x.abs() * 70.0)
+    }
+}
+
+/// Constant-power pan between the two ring speakers around `azimuth`.
+fn ring_gains(speakers: &[Speaker], azimuth: f32, gains: &mut [f32]) {
+    let mut ring = [(0usize, 0.0f32); 8];
+    let mut count = 0;
+    for (index, speaker) in speakers.iter().enumerate() {
+        if let Some(az) = speaker.azimuth() {
+            ring[count] = (index, az);
+            count += 1;
+        }
+    }
+    let ring = &mut ring[..count];
+    ring.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+    for i in 0..count {
+        let (a_index, a) = ring[i];
+        let (b_index, mut b) = ring[(i + 1) % count];
+        if b <= a {
+            b += 360.0;
+        }
+        let mut t = azimuth;
+        if t < a {
+            t += 360.0;
+        }
+        if t < b {
+            let angle = (t - a) / (b - a) * FRAC_PI_2;
+            gains[a_index] = angle.cos();
+            gains[b_index] = angle.sin();
+            return;
+        }
+    }
+}
+
+/// Where a mixer channel's signal goes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Route {
+    #[default]
+    Master,
+    /// Submix bus by index.
+    Bus(usize),
+}
+
+/// A submix bus (e.g. a dialogue, music or effects stem).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bus {
+    /// Name, used for stem file names.
+    pub name: String,
+    /// Volume (0.0 to 1.0).
+    pub volume: f32,
+    /// Whether the bus is muted (also silences its stem).
+    pub muted: bool,
+}
+
+impl Bus {
+    /// Create a unity-gain bus.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            volume: 1.0,
+            muted: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gains(layout: ChannelLayout, x: f32, y: f32) -> Vec<f32> {
+        let mut gains = vec![0.0; layout.channel_count()];
+        layout.pan_gains(x, y, &mut gains);
+        gains
+    }
+
+    fn power(gains: &[f32]) -> f32 {
+        gains.iter().map(|g| g * g).sum()
+    }
+
+    #[test]
+    fn test_layout_channels_and_mask() {
+        assert_eq!(ChannelLayout::Stereo.channel_count(), 2);
+        assert_eq!(ChannelLayout::Surround51.channel_mask(), 0x3F);
+        assert_eq!(ChannelLayout::Surround71.channel_mask(), 0x63F);
+        assert_eq!(
+            ChannelLayout::from_channel_count(8),
+            Some(ChannelLayout::Surround71)
+        );
+        assert_eq!(ChannelLayout::from_channel_count(3), None);
+    }
+
+    #[test]
+    fn test_surround_pan_hits_speakers() {
+        let layout = ChannelLayout::Surround51;
+        let close = |a: &[f32], b: &[f32]| a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-5);
+        assert!(close(
+            &gains(layout, 0.0, 1.0),
+            &[0.0, 0.0, 1.0, 0.0, 0.0, 0.0]
+        ));
+        assert!(close(
+            &gains(layout, -1.0, 1.0),
+            &[1.0, 0.0, 0.0, 0.0, 0.0, 0.0]
+        ));
+        assert!(close(
+            &gains(layout, 1.0, -1.0),
+            &[0.0, 0.0, 0.0, 0.0, 0.0, 1.0]
+        ));
+
+        let side = ChannelLayout::Surround71;
+        let g = gains(side, -1.0, -0.5);
+        assert!((g[6] - 1.0).abs() < 1e-5, "{g:?}");
+    }
+
+    #[test]
+    fn test_surround_pan_is_constant_power() {
+        for layout in ChannelLayout::ALL {
+            for (x, y) in [
+                (0.0, 0.0),
+                (0.3, 0.9),
+                (-0.7, -0.2),
+                (0.0, -1.0),
+                (1.0, 0.5),
+            ] {
+                let g = gains(layout, x, y);
+                assert!((power(&g) - 1.0).abs() < 1e-4, "{layout:?} {x} {y}");
+                if layout.is_surround() {
+                    assert_eq!(g[3], 0.0);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_downmix_stereo() {
+        let frame = [1.0, 0.5, 1.0, 1.0, 1.0, 0.0];
+        let mut out = [0.0; 2];
+        ChannelLayout::Surround51.downmix_stereo(&frame, &mut out);
+        assert!((out[0] - (1.0 + 2.0 * FRAC_1_SQRT_2)).abs() < 1e-6);
+        assert!((out[1] - (0.5 + FRAC_1_SQRT_2)).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,217 @@
+//! Feed-forward compressor with soft knee and external sidechain.
+
+use crate::effect::{AudioEffect, AudioParam};
+use crate::effects::{db_to_gain, gain_to_db, EffectSettings, Envelope};
+use serde::{Deserialize, Serialize};
+
+/// Compressor parameters.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CompressorParams {
+    pub threshold_db: f32,
+    pub ratio: f32,
+    pub attack_ms: f32,
+    pub release_ms: f32,
+    pub knee_db: f32,
+    pub makeup_db: f32,
+}
+
+impl Default for CompressorParams {
+    fn default() -> Self {
+        Self {
+            threshold_db: -18.0,
+            ratio: 4.0,
+            attack_ms: 10.0,
+            release_ms: 100.0,
+            knee_db: 6.0,
+            makeup_db: 0.0,
+        }
+    }
+}
+
+/// Gain change in dB of a compressor curve for a detector level.
+pub(crate) fn compression_db(level_db: f32, threshold_db: f32, ratio: f32, knee_db: f32) -> f32 {
+    let over = level_db - threshold_db;
+    let slope = 1.0 / ratio - 1.0;
+    if knee_db > 0.0 && 2.0 * over.abs() <= knee_db {
+        let x = over + knee_db / 2.0;
+        slope * x * x / (2.0 * knee_db)
+    } else if over > 0.0 {
+        slope * over
+    } else {
+        0.0
+    }
+}
+
+/// Compressor keyed by its input or, when routed, a sidechain signal.
+/// Both channels share one detector so the stereo image stays put.
+pub struct Compressor {
+    params: CompressorParams,
+    envelope: Envelope,
+    sample_rate: u32,
+}
+
+impl Default for Compressor {
+    fn default() -> Self {
+        Self::new(CompressorParams::default())
+    }
+}
+
+impl Compressor {
+    pub fn new(params: CompressorParams) -> Self {
+        let mut compressor = Self {
+            params,
+            envelope: Envelope::default(),
+            sample_rate: 48000,
+        };
+        compressor.prepare(48000);
+        compressor
+    }
+
+    fn update_times(&mut self) {
+        self.envelope.set_times(
+            self.params.attack_ms,
+            self.params.release_ms,
+            self.sample_rate,
+        );
+    }
+}
+
+impl AudioEffect for Compressor {
+    fn name(&self) -> &str {
+        "Compressor"
+    }
+
+    fn params(&self) -> Vec<AudioParam> {
+        vec![
+            AudioParam::new("threshold_db", "Threshold", -18.0, -60.0, 0.0),
+            AudioParam::new("ratio", "Ratio", 4.0, 1.0, 20.0),
+            AudioParam::new("attack_ms", "Attack", 10.0, 0.1, 200.0),
+            AudioParam::new("release_ms", "Release", 100.0, 5.0, 2000.0),
+            AudioParam::new("knee_db", "Knee", 6.0, 0.0, 24.0),
+            AudioParam::new("makeup_db", "Makeup Gain", 0.0, -12.0, 24.0),
+        ]
+    }
+
+    fn param(&self, name: &str) -> Option<f32> {
+        let p = &self.params;
+        match name {
+            "threshold_db" => Some(p.threshold_db),
+            "ratio" => Some(p.ratio),
+            "attack_ms" => Some(p.attack_ms),
+            "release_ms" => Some(p.release_ms),
+            "knee_db" => Some(p.knee_db),
+            "makeup_db" => Some(p.makeup_db),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        let p = &mut self.params;
+        match name {
+            "threshold_db" => p.threshold_db = value.clamp(-60.0, 0.0),
+            "ratio" => p.ratio = value.clamp(1.0, 20.0),
+            "attack_ms" => p.attack_ms = value.clamp(0.1, 200.0),
+            "release_ms" => p.release_ms = value.clamp(5.0, 2000.0),
+            "knee_db" => p.knee_db = value.clamp(0.0, 24.0),
+            "makeup_db" => p.makeup_db = value.clamp(-12.0, 24.0),
+            _ => return false,
+        }
+        self.update_times();
+        true
+    }
+
+    fn prepare(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate;
+        self.update_times();
+        self.reset();
+    }
+
+    fn reset(&mut self) {
+        self.envelope.reset();
+    }
+
+    fn process(&mut self, buffer: &mut [f32], sidechain: Option<&[f32]>) {
+        let p = &self.params;
+        for (i, frame) in buffer.chunks_exact_mut(2).enumerate() {
+            let key = match sidechain {
+                Some(key) => key[i * 2].abs().max(key[i * 2 + 1].abs()),
+                None => frame[0].abs().max(frame[1].abs()),
+            };
+            let level = self.envelope.next(key);
+            let change = compression_db(gain_to_db(level), p.threshold_db, p.ratio, p.knee_db);
+            let gain = db_to_gain(change + p.makeup_db);
+            frame[0] *= gain;
+            frame[1] *= gain;
+        }
+    }
+
+    fn settings(&self) -> EffectSettings {
+        EffectSettings::Compressor(self.params.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effects::test_signals::*;
+
+    fn hard_knee(threshold_db: f32, ratio: f32) -> Compressor {
+        let mut c = Compressor::new(CompressorParams {
+            threshold_db,
+            ratio,
+            attack_ms: 1.0,
+            release_ms: 200.0,
+            knee_db: 0.0,
+            makeup_db: 0.0,
+        });
+        c.prepare(RATE);
+        c
+    }
+
+    #[test]
+    fn test_static_curve() {
+        assert_eq!(compression_db(-30.0, -20.0, 4.0, 0.0), 0.0);
+        assert_eq!(compression_db(0.0, -20.0, 4.0, 0.0), -15.0);
+        // Soft knee is continuous at its edges.
+        let edge = compression_db(-17.0, -20.0, 4.0, 6.0);
+        assert!((edge - compression_db(-17.0, -20.0, 4.0, 0.0)).abs() < 1e-4);
+        assert!(compression_db(-20.0, -20.0, 4.0, 6.0) < 0.0);
+    }
+
+    #[test]
+    fn test_reduces_loud_sine() {
+        // 0 dBFS into -20 dB threshold at 4:1 settles at -15 dBFS.
+        let mut c = hard_knee(-20.0, 4.0);
+        let mut signal = sine(1000.0, 1.0, 0.5);
+        process_blocks(&mut c, &mut signal, None);
+        assert!((settled_peak_db(&signal) + 15.0).abs() < 0.5);
+    }
+
+    #[test]
+    fn test_leaves_quiet_signal() {
+        let mut c = hard_knee(-20.0, 4.0);
+        let mut signal = sine(1000.0, 0.05, 0.5);
+        let input = settled_peak_db(&signal);
+        process_blocks(&mut c, &mut signal, None);
+        assert!((settled_peak_db(&signal) - input).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_sidechain_ducks_input() {
+        let mut c = hard_knee(-30.0, 10.0);
+        let music = sine(220.0, 0.1, 0.5);
+
+        // Loud voice on the key ducks the quiet music.
+        let voice = sine(1000.0, 1.0, 0.5);
+        let mut ducked = music.clone();
+        process_blocks(&mut c, &mut ducked, Some(&voice));
+        assert!(settled_peak_db(&ducked) < settled_peak_db(&music) - 20.0);
+
+        // Silent key leaves it untouched.
+        c.reset();
+        let silence = vec![0.0; music.len()];
+        let mut open = music.clone();
+        process_blocks(&mut c, &mut open, Some(&silence));
+        assert_eq!(open, music);
+    }
+}
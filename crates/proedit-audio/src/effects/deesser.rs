@@ -0,0 +1,167 @@
+//! Broadband de-esser keyed by a high-passed detector.
+
+use crate::effect::{AudioEffect, AudioParam};
+use crate::effects::compressor::compression_db;
+use crate::effects::{db_to_gain, gain_to_db, Biquad, EffectSettings, Envelope, EqBandKind};
+use serde::{Deserialize, Serialize};
+
+/// De-esser parameters.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeEsserParams {
+    /// Detector high-pass frequency in Hz.
+    pub frequency: f32,
+    pub threshold_db: f32,
+    pub ratio: f32,
+    /// Maximum gain reduction (positive dB).
+    pub max_reduction_db: f32,
+}
+
+impl Default for DeEsserParams {
+    fn default() -> Self {
+        Self {
+            frequency: 6000.0,
+            threshold_db: -30.0,
+            ratio: 4.0,
+            max_reduction_db: 12.0,
+        }
+    }
+}
+
+/// De-esser: compresses the whole signal when energy above `frequency`
+/// exceeds the threshold. Gain is applied broadband so the band split
+/// cannot introduce phase artifacts.
+pub struct DeEsser {
+    params: DeEsserParams,
+    detector_filter: Biquad,
+    envelope: Envelope,
+    sample_rate: u32,
+}
+
+impl Default for DeEsser {
+    fn default() -> Self {
+        Self::new(DeEsserParams::default())
+    }
+}
+
+impl DeEsser {
+    pub fn new(params: DeEsserParams) -> Self {
+        let mut deesser = Self {
+            params,
+            detector_filter: Biquad::default(),
+            envelope: Envelope::default(),
+            sample_rate: 48000,
+        };
+        deesser.prepare(48000);
+        deesser
+    }
+
+    fn update_filter(&mut self) {
+        self.detector_filter.set(
+            EqBandKind::HighPass,
+            self.params.frequency,
+            0.0,
+            0.707,
+            self.sample_rate,
+        );
+    }
+}
+
+impl AudioEffect for DeEsser {
+    fn name(&self) -> &str {
+        "De-Esser"
+    }
+
+    fn params(&self) -> Vec<AudioParam> {
+        vec![
+            AudioParam::new("frequency", "Frequency", 6000.0, 2000.0, 16000.0),
+            AudioParam::new("threshold_db", "Threshold", -30.0, -60.0, 0.0),
+            AudioParam::new("ratio", "Ratio", 4.0, 1.0, 20.0),
+            AudioParam::new("max_reduction_db", "Max Reduction", 12.0, 0.0, 40.0),
+        ]
+    }
+
+    fn param(&self, name: &str) -> Option<f32> {
+        let p = &self.params;
+        match name {
+            "frequency" => Some(p.frequency),
+            "threshold_db" => Some(p.threshold_db),
+            "ratio" => Some(p.ratio),
+            "max_reduction_db" => Some(p.max_reduction_db),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        let p = &mut self.params;
+        match name {
+            "frequency" => p.frequency = value.clamp(2000.0, 16000.0),
+            "threshold_db" => p.threshold_db = value.clamp(-60.0, 0.0),
+            "ratio" => p.ratio = value.clamp(1.0, 20.0),
+            "max_reduction_db" => p.max_reduction_db = value.clamp(0.0, 40.0),
+            _ => return false,
+        }
+        self.update_filter();
+        true
+    }
+
+    fn prepare(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate;
+        // Sibilance is short: fast attack, quick release.
+        self.envelope.set_times(0.5, 40.0, sample_rate);
+        self.update_filter();
+        self.reset();
+    }
+
+    fn reset(&mut self) {
+        self.detector_filter.reset();
+        self.envelope.reset();
+    }
+
+    fn process(&mut self, buffer: &mut [f32], sidechain: Option<&[f32]>) {
+        let p = &self.params;
+        for (i, frame) in buffer.chunks_exact_mut(2).enumerate() {
+            let (l, r) = match sidechain {
+                Some(key) => (key[i * 2], key[i * 2 + 1]),
+                None => (frame[0], frame[1]),
+            };
+            let hl = self.detector_filter.tick(0, l);
+            let hr = self.detector_filter.tick(1, r);
+            let level = self.envelope.next(hl.abs().max(hr.abs()));
+            let change = compression_db(gain_to_db(level), p.threshold_db, p.ratio, 0.0)
+                .max(-p.max_reduction_db);
+            let gain = db_to_gain(change);
+            frame[0] *= gain;
+            frame[1] *= gain;
+        }
+    }
+
+    fn settings(&self) -> EffectSettings {
+        EffectSettings::DeEsser(self.params.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effects::test_signals::*;
+
+    fn reduction(freq: f32) -> f32 {
+        let mut d = DeEsser::default();
+        d.prepare(RATE);
+        let mut signal = sine(freq, 0.5, 0.3);
+        let input = settled_peak_db(&signal);
+        process_blocks(&mut d, &mut signal, None);
+        input - settled_peak_db(&signal)
+    }
+
+    #[test]
+    fn test_reduces_sibilance() {
+        // -6 dBFS at 8 kHz is 24 dB over threshold: capped at 12 dB.
+        assert!((reduction(8000.0) - 12.0).abs() < 0.5);
+    }
+
+    #[test]
+    fn test_leaves_low_frequencies() {
+        assert!(reduction(200.0) < 0.1);
+    }
+}
@@ -0,0 +1,397 @@
+//! Parametric EQ built from RBJ-cookbook biquads.
+
+use crate::effect::{AudioEffect, AudioParam};
+use crate::effects::EffectSettings;
+use serde::{Deserialize, Serialize};
+
+/// Filter shape of an EQ band.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EqBandKind {
+    Peak,
+    LowShelf,
+    HighShelf,
+    LowPass,
+    HighPass,
+    Notch,
+}
+
+/// Biquad filter with per-channel state for interleaved stereo
+/// (transposed direct form II).
+#[derive(Debug, Clone, Copy)]
+pub struct Biquad {
+    b0: f64,
+    b1: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+    state: [[f64; 2]; 2],
+}
+
+impl Default for Biquad {
+    fn default() -> Self {
+        Self {
+            b0: 1.0,
+            b1: 0.0,
+            b2: 0.0,
+            a1: 0.0,
+            a2: 0.0,
+            state: [[0.0; 2]; 2],
+        }
+    }
+}
+
+impl Biquad {
+    /// Compute coefficients for a filter shape. State is kept so that
+    /// parameters can change while audio is running.
+    pub fn set(
+        &mut self,
+        kind: EqBandKind,
+        frequency: f32,
+        gain_db: f32,
+        q: f32,
+        sample_rate: u32,
+    ) {
+        let nyquist = sample_rate as f64 / 2.0;
+        let freq = (frequency as f64).clamp(1.0, nyquist * 0.999);
+        let w0 = 2.0 * std::f64::consts::PI * freq / sample_rate as f64;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2.0 * (q as f64).max(0.01));
+        let a = 10f64.powf(gain_db as f64 / 40.0);
+        let sqrt_a_alpha = 2.0 * a.sqrt() * alpha;
+
+        let (b0, b1, b2, a0, a1, a2) = match kind {
+            EqBandKind::Peak => (
+                1.0 + alpha * a,
+                -2.0 * cos,
+                1.0 - alpha * a,
+                1.0 + alpha / a,
+                -2.0 * cos,
+                1.0 - alpha / a,
+            ),
+            EqBandKind::LowShelf => (
+                a * ((a + 1.0) - (a - 1.0) * cos + sqrt_a_alpha),
+                2.0 * a * ((a - 1.0) - (a + 1.0) * cos),
+                a * ((a + 1.0) - (a - 1.0) * cos - sqrt_a_alpha),
+                (a + 1.0) + (a - 1.0) * cos + sqrt_a_alpha,
+                -2.0 * ((a - 1.0) + (a + 1.0) * cos),
+                (a + 1.0) + (a - 1.0) * cos - sqrt_a_alpha,
+            ),
+            EqBandKind::HighShelf => (
+                a * ((a + 1.0) + (a - 1.0) * cos + sqrt_a_alpha),
+                -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+                a * ((a + 1.0) + (a - 1.0) * cos - sqrt_a_alpha),
+                (a + 1.0) - (a - 1.0) * cos + sqrt_a_alpha,
+                2.0 * ((a - 1.0) - (a + 1.0) * cos),
+                (a + 1.0) - (a - 1.0) * cos - sqrt_a_alpha,
+            ),
+            EqBandKind::LowPass => (
+                (1.0 - cos) / 2.0,
+                1.0 - cos,
+                (1.0 - cos) / 2.0,
+                1.0 + alpha,
+                -2.0 * cos,
+                1.0 - alpha,
+            ),
+            EqBandKind::HighPass => (
+                (1.0 + cos) / 2.0,
+                -(1.0 + cos),
+                (1.0 + cos) / 2.0,
+                1.0 + alpha,
+                -2.0 * cos,
+                1.0 - alpha,
+            ),
+            EqBandKind::Notch => (1.0, -2.0 * cos, 1.0, 1.0 + alpha, -2.0 * cos, 1.0 - alpha),
+        };
+
+        self.b0 = b0 / a0;
+        self.b1 = b1 / a0;
+        self.b2 = b2 / a0;
+        self.a1 = a1 / a0;
+        self.a2 = a2 / a0;
+    }
+
+    /// Filter one sample of channel `ch` (0 or 1).
+    #[inline]
+    pub fn tick(&mut self, ch: usize, input: f32) -> f32 {
+        let x = input as f64;
+        let [z1, z2] = &mut self.state[ch];
+        let y = self.b0 * x + *z1;
+        *z1 = self.b1 * x - self.a1 * y + *z2;
+        *z2 = self.b2 * x - self.a2 * y;
+        y as f32
+    }
+
+    /// Filter an interleaved stereo buffer in place.
+    pub fn process(&mut self, buffer: &mut [f32]) {
+        for frame in buffer.chunks_exact_mut(2) {
+            frame[0] = self.tick(0, frame[0]);
+            frame[1] = self.tick(1, frame[1]);
+        }
+    }
+
+    /// Clear filter memory.
+    pub fn reset(&mut self) {
+        self.state = [[0.0; 2]; 2];
+    }
+}
+
+/// One EQ band.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EqBand {
+    pub kind: EqBandKind,
+    /// Center / corner frequency in Hz.
+    pub frequency: f32,
+    /// Gain in dB (peak and shelf bands).
+    pub gain_db: f32,
+    pub q: f32,
+    pub enabled: bool,
+}
+
+impl EqBand {
+    pub fn new(kind: EqBandKind, frequency: f32, gain_db: f32, q: f32) -> Self {
+        Self {
+            kind,
+            frequency,
+            gain_db,
+            q,
+            enabled: true,
+        }
+    }
+}
+
+/// Parametric EQ parameters.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EqParams {
+    pub bands: Vec<EqBand>,
+}
+
+impl Default for EqParams {
+    fn default() -> Self {
+        Self {
+            bands: vec![
+                EqBand::new(EqBandKind::LowShelf, 100.0, 0.0, 0.707),
+                EqBand::new(EqBandKind::Peak, 500.0, 0.0, 1.0),
+                EqBand::new(EqBandKind::Peak, 2500.0, 0.0, 1.0),
+                EqBand::new(EqBandKind::HighShelf, 8000.0, 0.0, 0.707),
+            ],
+        }
+    }
+}
+
+/// Multi-band parametric EQ.
+///
+/// Parameters are addressed as `band<N>.frequency`, `band<N>.gain` and
+/// `band<N>.q` with 1-based band numbers.
+pub struct ParametricEq {
+    params: EqParams,
+    filters: Vec<Biquad>,
+    sample_rate: u32,
+}
+
+impl Default for ParametricEq {
+    fn default() -> Self {
+        Self::new(EqParams::default())
+    }
+}
+
+impl ParametricEq {
+    pub fn new(params: EqParams) -> Self {
+        let mut eq = Self {
+            filters: vec![Biquad::default(); params.bands.len()],
+            params,
+            sample_rate: 48000,
+        };
+        eq.prepare(48000);
+        eq
+    }
+
+    /// Parameters (use `set_param` to change them).
+    pub fn bands(&self) -> &[EqBand] {
+        &self.params.bands
+    }
+
+    fn update_band(&mut self, index: usize) {
+        let band = &self.params.bands[index];
+        self.filters[index].set(
+            band.kind,
+            band.frequency,
+            band.gain_db,
+            band.q,
+            self.sample_rate,
+        );
+    }
+
+    /// Split `band<N>.<field>` into a band index and field name.
+    fn parse(name: &str) -> Option<(usize, &str)> {
+        let (band, field) = name.strip_prefix("band")?.split_once('.')?;
+        let index = band.parse::<usize>().ok()?.checked_sub(1)?;
+        Some((index, field))
+    }
+}
+
+impl AudioEffect for ParametricEq {
+    fn name(&self) -> &str {
+        "Parametric EQ"
+    }
+
+    fn params(&self) -> Vec<AudioParam> {
+        (1..=self.params.bands.len())
+            .flat_map(|n| {
+                let band = &self.params.bands[n - 1];
+                [
+                    AudioParam::new(
+                        format!("band{n}.frequency"),
+                        format!("Band {n} Frequency"),
+                        band.frequency,
+                        20.0,
+                        20000.0,
+                    ),
+                    AudioParam::new(
+                        format!("band{n}.gain"),
+                        format!("Band {n} Gain"),
+                        0.0,
+                        -24.0,
+                        24.0,
+                    ),
+                    AudioParam::new(
+                        format!("band{n}.q"),
+                        format!("Band {n} Q"),
+                        band.q,
+                        0.1,
+                        18.0,
+                    ),
+                ]
+            })
+            .collect()
+    }
+
+    fn param(&self, name: &str) -> Option<f32> {
+        let (index, field) = Self::parse(name)?;
+        let band = self.params.bands.get(index)?;
+        match field {
+            "frequency" => Some(band.frequency),
+            "gain" => Some(band.gain_db),
+            "q" => Some(band.q),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        let Some((index, field)) = Self::parse(name) else {
+            return false;
+        };
+        let Some(band) = self.params.bands.get_mut(index) else {
+            return false;
+        };
+        match field {
+            "frequency" => band.frequency = value.clamp(20.0, 20000.0),
+            "gain" => band.gain_db = value.clamp(-24.0, 24.0),
+            "q" => band.q = value.clamp(0.1, 18.0),
+            _ => return false,
+        }
+        self.update_band(index);
+        true
+    }
+
+    fn prepare(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate;
+        self.filters = vec![Biquad::default(); self.params.bands.len()];
+        for i in 0..self.filters.len() {
+            self.update_band(i);
+        }
+    }
+
+    fn reset(&mut self) {
+        for filter in &mut self.filters {
+            filter.reset();
+        }
+    }
+
+    fn process(&mut self, buffer: &mut [f32], _sidechain: Option<&[f32]>) {
+        for (band, filter) in self.params.bands.iter().zip(&mut self.filters) {
+            if band.enabled {
+                filter.process(buffer);
+            }
+        }
+    }
+
+    fn settings(&self) -> EffectSettings {
+        EffectSettings::Eq(self.params.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effects::test_signals::*;
+
+    fn single_band(kind: EqBandKind, frequency: f32, gain_db: f32, q: f32) -> ParametricEq {
+        let mut eq = ParametricEq::new(EqParams {
+            bands: vec![EqBand::new(kind, frequency, gain_db, q)],
+        });
+        eq.prepare(RATE);
+        eq
+    }
+
+    fn gain_at(eq: &mut ParametricEq, freq: f32) -> f32 {
+        eq.reset();
+        let mut signal = sine(freq, 0.25, 0.2);
+        let input = settled_rms_db(&signal);
+        process_blocks(eq, &mut signal, None);
+        settled_rms_db(&signal) - input
+    }
+
+    #[test]
+    fn test_flat_eq_is_transparent() {
+        let mut eq = ParametricEq::default();
+        for freq in [50.0, 1000.0, 12000.0] {
+            assert!(gain_at(&mut eq, freq).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn test_peak_boost() {
+        let mut eq = single_band(EqBandKind::Peak, 1000.0, 6.0, 1.0);
+        assert!((gain_at(&mut eq, 1000.0) - 6.0).abs() < 0.1);
+        assert!(gain_at(&mut eq, 50.0).abs() < 0.3);
+        assert!(gain_at(&mut eq, 15000.0).abs() < 0.3);
+    }
+
+    #[test]
+    fn test_shelves() {
+        let mut low = single_band(EqBandKind::LowShelf, 200.0, -9.0, 0.707);
+        assert!((gain_at(&mut low, 30.0) + 9.0).abs() < 0.3);
+        assert!(gain_at(&mut low, 5000.0).abs() < 0.2);
+
+        let mut high = single_band(EqBandKind::HighShelf, 4000.0, 6.0, 0.707);
+        assert!((gain_at(&mut high, 16000.0) - 6.0).abs() < 0.3);
+        assert!(gain_at(&mut high, 100.0).abs() < 0.2);
+    }
+
+    #[test]
+    fn test_pass_filters() {
+        // 12 dB/octave: ~3.3 octaves below the corner is around -40 dB.
+        let mut hp = single_band(EqBandKind::HighPass, 1000.0, 0.0, 0.707);
+        assert!(gain_at(&mut hp, 100.0) < -35.0);
+        assert!((gain_at(&mut hp, 1000.0) + 3.0).abs() < 0.2);
+        assert!(gain_at(&mut hp, 10000.0).abs() < 0.2);
+
+        let mut lp = single_band(EqBandKind::LowPass, 1000.0, 0.0, 0.707);
+        assert!(gain_at(&mut lp, 10000.0) < -35.0);
+        assert!(gain_at(&mut lp, 100.0).abs() < 0.2);
+
+        let mut notch = single_band(EqBandKind::Notch, 1000.0, 0.0, 2.0);
+        assert!(gain_at(&mut notch, 1000.0) < -40.0);
+    }
+
+    #[test]
+    fn test_param_addressing() {
+        let mut eq = ParametricEq::default();
+        assert_eq!(eq.params().len(), 12);
+        assert!(eq.set_param("band3.gain", 30.0));
+        assert_eq!(eq.param("band3.gain"), Some(24.0));
+        assert!(!eq.set_param("band9.gain", 1.0));
+        assert!(!eq.set_param("band0.gain", 1.0));
+        assert!(!eq.set_param("gain", 1.0));
+    }
+}
@@ -0,0 +1,224 @@
+//! Noise gate with hysteresis and hold.
+
+use crate::effect::{AudioEffect, AudioParam};
+use crate::effects::{db_to_gain, time_coeff, EffectSettings, Envelope};
+use serde::{Deserialize, Serialize};
+
+/// The gate closes this far below the open threshold, so signals hovering
+/// around the threshold do not chatter.
+const HYSTERESIS_DB: f32 = 4.0;
+
+/// Noise gate parameters.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GateParams {
+    /// Level above which the gate opens.
+    pub threshold_db: f32,
+    /// Attenuation while closed (negative dB).
+    pub range_db: f32,
+    pub attack_ms: f32,
+    /// Time the gate stays open after the signal drops.
+    pub hold_ms: f32,
+    pub release_ms: f32,
+}
+
+impl Default for GateParams {
+    fn default() -> Self {
+        Self {
+            threshold_db: -45.0,
+            range_db: -60.0,
+            attack_ms: 1.0,
+            hold_ms: 50.0,
+            release_ms: 150.0,
+        }
+    }
+}
+
+/// Noise gate.
+pub struct NoiseGate {
+    params: GateParams,
+    detector: Envelope,
+    attack: f32,
+    release: f32,
+    hold_samples: usize,
+    open: bool,
+    hold_left: usize,
+    gain: f32,
+    sample_rate: u32,
+}
+
+impl Default for NoiseGate {
+    fn default() -> Self {
+        Self::new(GateParams::default())
+    }
+}
+
+impl NoiseGate {
+    pub fn new(params: GateParams) -> Self {
+        let mut gate = Self {
+            params,
+            detector: Envelope::default(),
+            attack: 0.0,
+            release: 0.0,
+            hold_samples: 0,
+            open: false,
+            hold_left: 0,
+            gain: 0.0,
+            sample_rate: 48000,
+        };
+        gate.prepare(48000);
+        gate
+    }
+
+    fn update_times(&mut self) {
+        let sr = self.sample_rate;
+        // Fast peak detector; the audible timing comes from attack/release.
+        self.detector.set_times(0.1, 10.0, sr);
+        self.attack = time_coeff(self.params.attack_ms, sr);
+        self.release = time_coeff(self.params.release_ms, sr);
+        self.hold_samples = (self.params.hold_ms * 0.001 * sr as f32) as usize;
+    }
+}
+
+impl AudioEffect for NoiseGate {
+    fn name(&self) -> &str {
+        "Noise Gate"
+    }
+
+    fn params(&self) -> Vec<AudioParam> {
+        vec![
+            AudioParam::new("threshold_db", "Threshold", -45.0, -90.0, 0.0),
+            AudioParam::new("range_db", "Range", -60.0, -90.0, 0.0),
+            AudioParam::new("attack_ms", "Attack", 1.0, 0.05, 100.0),
+            AudioParam::new("hold_ms", "Hold", 50.0, 0.0, 1000.0),
+            AudioParam::new("release_ms", "Release", 150.0, 5.0, 2000.0),
+        ]
+    }
+
+    fn param(&self, name: &str) -> Option<f32> {
+        let p = &self.params;
+        match name {
+            "threshold_db" => Some(p.threshold_db),
+            "range_db" => Some(p.range_db),
+            "attack_ms" => Some(p.attack_ms),
+            "hold_ms" => Some(p.hold_ms),
+            "release_ms" => Some(p.release_ms),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        let p = &mut self.params;
+        match name {
+            "threshold_db" => p.threshold_db = value.clamp(-90.0, 0.0),
+            "range_db" => p.range_db = value.clamp(-90.0, 0.0),
+            "attack_ms" => p.attack_ms = value.clamp(0.05, 100.0),
+            "hold_ms" => p.hold_ms = value.clamp(0.0, 1000.0),
+            "release_ms" => p.release_ms = value.clamp(5.0, 2000.0),
+            _ => return false,
+        }
+        self.update_times();
+        true
+    }
+
+    fn prepare(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate;
+        self.update_times();
+        self.reset();
+    }
+
+    fn reset(&mut self) {
+        self.detector.reset();
+        self.open = false;
+        self.hold_left = 0;
+        self.gain = db_to_gain(self.params.range_db);
+    }
+
+    fn process(&mut self, buffer: &mut [f32], sidechain: Option<&[f32]>) {
+        let open_level = db_to_gain(self.params.threshold_db);
+        let close_level = db_to_gain(self.params.threshold_db - HYSTERESIS_DB);
+        let floor = db_to_gain(self.params.range_db);
+
+        for (i, frame) in buffer.chunks_exact_mut(2).enumerate() {
+            let key = match sidechain {
+                Some(key) => key[i * 2].abs().max(key[i * 2 + 1].abs()),
+                None => frame[0].abs().max(frame[1].abs()),
+            };
+            let level = self.detector.next(key);
+
+            if level >= open_level {
+                self.open = true;
+                self.hold_left = self.hold_samples;
+            } else if self.open && level < close_level {
+                if self.hold_left > 0 {
+                    self.hold_left -= 1;
+                } else {
+                    self.open = false;
+                }
+            }
+
+            let (target, coeff) = if self.open {
+                (1.0, self.attack)
+            } else {
+                (floor, self.release)
+            };
+            self.gain = target + coeff * (self.gain - target);
+            frame[0] *= self.gain;
+            frame[1] *= self.gain;
+        }
+    }
+
+    fn settings(&self) -> EffectSettings {
+        EffectSettings::Gate(self.params.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effects::gain_to_db;
+    use crate::effects::test_signals::*;
+
+    fn gate() -> NoiseGate {
+        let mut g = NoiseGate::new(GateParams {
+            threshold_db: -40.0,
+            range_db: -60.0,
+            attack_ms: 1.0,
+            hold_ms: 20.0,
+            release_ms: 20.0,
+        });
+        g.prepare(RATE);
+        g
+    }
+
+    #[test]
+    fn test_passes_signal_above_threshold() {
+        let mut g = gate();
+        let mut signal = sine(440.0, 0.1, 0.5);
+        process_blocks(&mut g, &mut signal, None);
+        assert!((settled_peak_db(&signal) - gain_to_db(0.1)).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_attenuates_noise_by_range() {
+        let mut g = gate();
+        let mut noise = sine(440.0, 0.001, 0.5); // -60 dBFS
+        process_blocks(&mut g, &mut noise, None);
+        assert!((settled_peak_db(&noise) - (-120.0)).abs() < 0.5);
+    }
+
+    #[test]
+    fn test_closes_after_hold_and_release() {
+        let mut g = gate();
+        let mut signal = sine(440.0, 0.1, 0.2);
+        signal.extend(sine(440.0, 0.001, 0.3));
+        process_blocks(&mut g, &mut signal, None);
+
+        // Still open during the hold time right after the drop...
+        let drop = (0.2 * RATE as f32) as usize * 2;
+        let early = &signal[drop..drop + 480];
+        assert!(gain_to_db(early.iter().fold(0.0f32, |m, s| m.max(s.abs()))) > -61.0);
+        // ...and fully closed well after it.
+        let tail = &signal[signal.len() - 4800..];
+        assert!(gain_to_db(tail.iter().fold(0.0f32, |m, s| m.max(s.abs()))) < -115.0);
+    }
+}
@@ -0,0 +1,442 @@
+//! Look-ahead brickwall limiter with true-peak detection.
+
+use crate::effect::{AudioEffect, AudioParam};
+use crate::effects::{db_to_gain, time_coeff, EffectSettings};
+use serde::{Deserialize, Serialize};
+
+/// Oversampling factor for true-peak detection (ITU-R BS.1770 uses 4x).
+const OVERSAMPLE: usize = 4;
+/// Interpolation filter taps per phase.
+const TAPS: usize = 12;
+/// Input samples the detector lags behind its newest input.
+const DETECTOR_DELAY: usize = TAPS / 2;
+/// Longest supported look-ahead.
+const MAX_LOOKAHEAD_MS: f32 = 5.0;
+
+/// Streaming 4x-oversampled true-peak detector for interleaved stereo.
+///
+/// Each call to [`push`](Self::push) returns the peak of the interval that
+/// starts [`DELAY`](Self::DELAY) frames before the frame just pushed.
+#[derive(Debug, Clone)]
+pub struct TruePeakDetector {
+    coeffs: [[f32; TAPS]; OVERSAMPLE],
+    history: [[f32; TAPS]; 2],
+    pos: usize,
+}
+
+impl Default for TruePeakDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TruePeakDetector {
+    /// Detector latency in frames.
+    pub const DELAY: usize = DETECTOR_DELAY;
+
+    pub fn new() -> Self {
+        // Hann-windowed sinc interpolator, one phase per oversampled point,
+        // each normalized to unity DC gain.
+        let mut coeffs = [[0.0f32; TAPS]; OVERSAMPLE];
+        for (phase, row) in coeffs.iter_mut().enumerate() {
+            let mut taps = [0.0f64; TAPS];
+            for (j, tap) in taps.iter_mut().enumerate() {
+                // Coefficient j weights the sample TAPS - 1 - j frames back;
+                // `d` is the distance from that sample to the output point.
+                let d = (TAPS - 1 - j) as f64 - DETECTOR_DELAY as f64
+                    + phase as f64 / OVERSAMPLE as f64;
+                let sinc = if d == 0.0 {
+                    1.0
+                } else {
+                    (std::f64::consts::PI * d).sin() / (std::f64::consts::PI * d)
+                };
+                let window = 0.5
+                    * (1.0
+                        - (2.0 * std::f64::consts::PI * (j + 1) as f64 / (TAPS + 1) as f64).cos());
+                *tap = sinc * window;
+            }
+            let sum: f64 = taps.iter().sum();
+            for (c, t) in row.iter_mut().zip(taps) {
+                *c = (t / sum) as f32;
+            }
+        }
+        Self {
+            coeffs,
+            history: [[0.0; TAPS]; 2],
+            pos: 0,
+        }
+    }
+
+    /// Push one stereo frame; returns the interval's absolute peak.
+    #[inline]
+    pub fn push(&mut self, left: f32, right: f32) -> f32 {
+        self.pos = (self.pos + 1) % TAPS;
+        self.history[0][self.pos] = left;
+        self.history[1][self.pos] = right;
+
+        let mut peak = 0.0f32;
+        for history in &self.history {
+            for phase in &self.coeffs {
+                let mut acc = 0.0f32;
+                for (j, c) in phase.iter().enumerate() {
+                    // Oldest sample first.
+                    let idx = (self.pos + 1 + j) % TAPS;
+                    acc += history[idx] * c;
+                }
+                peak = peak.max(acc.abs());
+            }
+        }
+        peak
+    }
+
+    /// Clear the history.
+    pub fn reset(&mut self) {
+        self.history = [[0.0; TAPS]; 2];
+        self.pos = 0;
+    }
+}
+
+/// Limiter parameters.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LimiterParams {
+    /// Maximum true-peak output level (dBTP).
+    pub ceiling_db: f32,
+    pub release_ms: f32,
+    pub lookahead_ms: f32,
+}
+
+impl Default for LimiterParams {
+    fn default() -> Self {
+        Self {
+            ceiling_db: -1.0,
+            release_ms: 50.0,
+            lookahead_ms: 1.5,
+        }
+    }
+}
+
+/// Brickwall limiter that keeps true peaks under the ceiling.
+///
+/// The required gain for each sample is spread backwards over the
+/// look-ahead window (sliding minimum followed by a moving average of the
+/// same length), so the gain is already down when the peak arrives and
+/// never exceeds what the peak needs. Audio is delayed to match.
+pub struct TruePeakLimiter {
+    params: LimiterParams,
+    detector: TruePeakDetector,
+    previous_peak: f32,
+    /// Look-ahead window length in samples.
+    window: usize,
+    release: f32,
+    released_gain: f32,
+    /// Ring of required gains (sliding minimum input).
+    required: Vec<f32>,
+    /// Ring of min-filtered gains (moving average input).
+    smoothed: Vec<f32>,
+    gain_pos: usize,
+    /// Audio delay line, interleaved stereo.
+    delay: Vec<f32>,
+    delay_pos: usize,
+    sample_rate: u32,
+}
+
+impl Default for TruePeakLimiter {
+    fn default() -> Self {
+        Self::new(LimiterParams::default())
+    }
+}
+
+impl TruePeakLimiter {
+    pub fn new(params: LimiterParams) -> Self {
+        let mut limiter = Self {
+            params,
+            detector: TruePeakDetector::new(),
+            previous_peak: 0.0,
+            window: 1,
+            release: 0.0,
+            released_gain: 1.0,
+            required: Vec::new(),
+            smoothed: Vec::new(),
+            gain_pos: 0,
+            delay: Vec::new(),
+            delay_pos: 0,
+            sample_rate: 48000,
+        };
+        limiter.prepare(48000);
+        limiter
+    }
+
+    fn update_times(&mut self) {
+        let sr = self.sample_rate as f32;
+        self.window = ((self.params.lookahead_ms * 0.001 * sr).round() as usize)
+            .clamp(1, self.required.len());
+        self.release = time_coeff(self.params.release_ms, self.sample_rate);
+    }
+
+    /// Frames from input to output.
+    fn delay_frames(&self) -> usize {
+        DETECTOR_DELAY + self.window - 1
+    }
+
+    /// Carry the state over from a look-ahead window of `old_window`
+    /// samples to the current one. Buffered audio keeps playing: the
+    /// oldest frames are dropped when the delay shrinks, and silence is
+    /// added in front when it grows. The gain holds at the deepest
+    /// reduction still pending, so no buffered peak gets through.
+    fn resize_window(&mut self, old_window: usize) {
+        let old_len = DETECTOR_DELAY + old_window;
+        let new_len = self.delay_frames() + 1;
+        // Oldest first; the frame in slot 0 has already been played.
+        self.delay[..old_len * 2].rotate_left(self.delay_pos * 2);
+        if new_len < old_len {
+            self.delay
+                .copy_within((old_len - new_len) * 2..old_len * 2, 0);
+        } else {
+            let added = (new_len - old_len) * 2;
+            self.delay.copy_within(..old_len * 2, added);
+            self.delay[..added].fill(0.0);
+        }
+        self.delay_pos = 0;
+
+        let pending = self.required[..old_window]
+            .iter()
+            .chain(&self.smoothed[..old_window])
+            .fold(1.0f32, |m, g| m.min(*g));
+        self.required.fill(pending);
+        self.smoothed.fill(pending);
+        self.released_gain = self.released_gain.min(pending);
+        self.gain_pos = 0;
+    }
+}
+
+impl AudioEffect for TruePeakLimiter {
+    fn name(&self) -> &str {
+        "True Peak Limiter"
+    }
+
+    fn params(&self) -> Vec<AudioParam> {
+        vec![
+            AudioParam::new("ceiling_db", "Ceiling", -1.0, -24.0, 0.0),
+            AudioParam::new("release_ms", "Release", 50.0, 1.0, 1000.0),
+            AudioParam::new("lookahead_ms", "Look-ahead", 1.5, 0.1, MAX_LOOKAHEAD_MS),
+        ]
+    }
+
+    fn param(&self, name: &str) -> Option<f32> {
+        match name {
+            "ceiling_db" => Some(self.params.ceiling_db),
+            "release_ms" => Some(self.params.release_ms),
+            "lookahead_ms" => Some(self.params.lookahead_ms),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        let window = self.window;
+        match name {
+            "ceiling_db" => self.params.ceiling_db = value.clamp(-24.0, 0.0),
+            "release_ms" => self.params.release_ms = value.clamp(1.0, 1000.0),
+            "lookahead_ms" => self.params.lookahead_ms = value.clamp(0.1, MAX_LOOKAHEAD_MS),
+            _ => return false,
+        }
+        self.update_times();
+        if self.window != window {
+            self.resize_window(window);
+        }
+        true
+    }
+
+    fn prepare(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate;
+        let max_window = (MAX_LOOKAHEAD_MS * 0.001 * sample_rate as f32).ceil() as usize + 1;
+        self.required = vec![1.0; max_window];
+        self.smoothed = vec![1.0; max_window];
+        self.delay = vec![0.0; (DETECTOR_DELAY + max_window) * 2];
+        self.update_times();
+        self.reset();
+    }
+
+    fn reset(&mut self) {
+        self.detector.reset();
+        self.previous_peak = 0.0;
+        self.released_gain = 1.0;
+        self.required.fill(1.0);
+        self.smoothed.fill(1.0);
+        self.gain_pos = 0;
+        self.delay.fill(0.0);
+        self.delay_pos = 0;
+    }
+
+    fn latency(&self) -> usize {
+        self.delay_frames()
+    }
+
+    fn process(&mut self, buffer: &mut [f32], _sidechain: Option<&[f32]>) {
+        let ceiling = db_to_gain(self.params.ceiling_db);
+        let window = self.window;
+        let delay_len = self.delay_frames() + 1;
+
+        for frame in buffer.chunks_exact_mut(2) {
+            // Peak around the sample the detector is currently centred on.
+            let interval_peak = self.detector.push(frame[0], frame[1]);
+            let peak = interval_peak.max(self.previous_peak);
+            self.previous_peak = interval_peak;
+            let required = if peak > ceiling { ceiling / peak } else { 1.0 };
+
+            // Sliding minimum over the window, then release smoothing
+            // (which only ever slows the gain's recovery).
+            self.required[self.gain_pos] = required;
+            let minimum = self.required[..window]
+                .iter()
+                .fold(1.0f32, |m, g| m.min(*g));
+            self.released_gain = if minimum < self.released_gain {
+                minimum
+            } else {
+                minimum + self.release * (self.released_gain - minimum)
+            };
+
+            // Moving average over the same window.
+            self.smoothed[self.gain_pos] = self.released_gain;
+            let gain = self.smoothed[..window].iter().sum::<f32>() / window as f32;
+            self.gain_pos = (self.gain_pos + 1) % window;
+
+            // Delay the audio to line up with the gain.
+            let write = self.delay_pos * 2;
+            let read = ((self.delay_pos + 1) % delay_len) * 2;
+            self.delay[write] = frame[0];
+            self.delay[write + 1] = frame[1];
+            frame[0] = self.delay[read] * gain;
+            frame[1] = self.delay[read + 1] * gain;
+            self.delay_pos = (self.delay_pos + 1) % delay_len;
+        }
+    }
+
+    fn settings(&self) -> EffectSettings {
+        EffectSettings::Limiter(self.params.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effects::gain_to_db;
+    use crate::effects::test_signals::*;
+
+    /// Reference true peak: 16x band-limited interpolation of one channel
+    /// with a long windowed sinc.
+    fn reference_true_peak(interleaved: &[f32]) -> f32 {
+        let x: Vec<f32> = interleaved.iter().step_by(2).copied().collect();
+        let half = 64i64;
+        let mut peak = 0.0f64;
+        for n in half as usize..x.len() - half as usize {
+            for k in 0..16 {
+                let t = n as f64 + k as f64 / 16.0;
+                let mut acc = 0.0f64;
+                for m in (n as i64 - half + 1)..=(n as i64 + half) {
+                    let d = t - m as f64;
+                    let sinc = if d == 0.0 {
+                        1.0
+                    } else {
+                        (std::f64::consts::PI * d).sin() / (std::f64::consts::PI * d)
+                    };
+                    let w = 0.5 * (1.0 + (std::f64::consts::PI * d / half as f64).cos());
+                    acc += x[m as usize] as f64 * sinc * w;
+                }
+                peak = peak.max(acc.abs());
+            }
+        }
+        peak as f32
+    }
+
+    /// fs/4 sine at 45° phase: samples peak at 0.707 of the true peak.
+    fn intersample_sine(amplitude: f32, frames: usize) -> Vec<f32> {
+        (0..frames)
+            .flat_map(|n| {
+                let s = amplitude
+                    * (std::f32::consts::FRAC_PI_2 * n as f32 + std::f32::consts::FRAC_PI_4).sin();
+                [s, s]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_detector_finds_intersample_peaks() {
+        let signal = intersample_sine(1.0, 256);
+        let mut detector = TruePeakDetector::new();
+        let peak = signal
+            .chunks_exact(2)
+            .map(|f| detector.push(f[0], f[1]))
+            .fold(0.0f32, f32::max);
+        let sample_peak = signal.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+        assert!(sample_peak < 0.71);
+        assert!((gain_to_db(peak)).abs() < 0.2, "peak {peak}");
+    }
+
+    #[test]
+    fn test_limits_true_peak_below_ceiling() {
+        let mut limiter = TruePeakLimiter::default();
+        limiter.prepare(RATE);
+        let mut signal = intersample_sine(1.0, 9600);
+        process_blocks(&mut limiter, &mut signal, None);
+
+        let true_peak = gain_to_db(reference_true_peak(&signal[2000..]));
+        assert!(true_peak <= -1.0 + 0.1, "true peak {true_peak} dBTP");
+        // Not over-limited either.
+        assert!(true_peak > -2.0);
+    }
+
+    #[test]
+    fn test_loud_transient_never_exceeds_ceiling() {
+        let mut limiter = TruePeakLimiter::default();
+        limiter.prepare(RATE);
+        let mut signal = vec![0.0f32; 4800];
+        signal.extend(sine(1000.0, 4.0, 0.1));
+        process_blocks(&mut limiter, &mut signal, None);
+        let ceiling = db_to_gain(-1.0);
+        assert!(signal.iter().all(|s| s.abs() <= ceiling * 1.001));
+    }
+
+    #[test]
+    fn test_quiet_signal_is_only_delayed() {
+        let mut limiter = TruePeakLimiter::default();
+        limiter.prepare(RATE);
+        let input = sine(1000.0, 0.25, 0.05);
+        let mut output = input.clone();
+        process_blocks(&mut limiter, &mut output, None);
+
+        let latency = limiter.latency();
+        assert_eq!(latency, DETECTOR_DELAY + 72 - 1);
+        let shifted = &output[latency * 2..];
+        for (o, i) in shifted.iter().zip(&input) {
+            assert!((o - i).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_lookahead_change_keeps_buffered_audio() {
+        let mut limiter = TruePeakLimiter::default();
+        limiter.prepare(RATE);
+        let input = sine(1000.0, 0.25, 0.05);
+        let (head, tail) = input.split_at(2400);
+        let mut output = head.to_vec();
+        process_blocks(&mut limiter, &mut output, None);
+
+        // Same window: nothing changes.
+        limiter.set_param("lookahead_ms", 1.5);
+        assert_eq!(limiter.latency(), DETECTOR_DELAY + 71);
+
+        // A shorter window drops the oldest buffered frames and carries on
+        // from the rest, without a gap.
+        limiter.set_param("lookahead_ms", 1.0);
+        let latency = limiter.latency();
+        assert_eq!(latency, DETECTOR_DELAY + 47);
+        let mut rest = tail.to_vec();
+        process_blocks(&mut limiter, &mut rest, None);
+        for (n, o) in rest.iter().enumerate() {
+            assert!(
+                (o - input[2400 + n - latency * 2]).abs() < 1e-6,
+                "sample {n}"
+            );
+        }
+    }
+}
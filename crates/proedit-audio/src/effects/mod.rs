@@ -0,0 +1,138 @@
+//! Built-in audio insert effects.
+
+mod compressor;
+mod deesser;
+mod eq;
+mod gate;
+mod limiter;
+
+pub use compressor::{Compressor, CompressorParams};
+pub use deesser::{DeEsser, DeEsserParams};
+pub use eq::{Biquad, EqBand, EqBandKind, EqParams, ParametricEq};
+pub use gate::{GateParams, NoiseGate};
+pub use limiter::{LimiterParams, TruePeakDetector, TruePeakLimiter};
+
+use crate::effect::AudioEffect;
+use serde::{Deserialize, Serialize};
+
+/// Convert decibels to linear gain.
+#[inline]
+pub fn db_to_gain(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+/// Convert linear gain to decibels (floored at -200 dB).
+#[inline]
+pub fn gain_to_db(gain: f32) -> f32 {
+    20.0 * gain.max(1e-10).log10()
+}
+
+/// One-pole smoothing coefficient for a time constant in milliseconds.
+#[inline]
+pub(crate) fn time_coeff(ms: f32, sample_rate: u32) -> f32 {
+    if ms <= 0.0 {
+        0.0
+    } else {
+        (-1.0 / (ms * 0.001 * sample_rate as f32)).exp()
+    }
+}
+
+/// Peak envelope follower with separate attack and release.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Envelope {
+    attack: f32,
+    release: f32,
+    value: f32,
+}
+
+impl Envelope {
+    pub(crate) fn set_times(&mut self, attack_ms: f32, release_ms: f32, sample_rate: u32) {
+        self.attack = time_coeff(attack_ms, sample_rate);
+        self.release = time_coeff(release_ms, sample_rate);
+    }
+
+    #[inline]
+    pub(crate) fn next(&mut self, input: f32) -> f32 {
+        let coeff = if input > self.value {
+            self.attack
+        } else {
+            self.release
+        };
+        self.value = input + coeff * (self.value - input);
+        self.value
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.value = 0.0;
+    }
+}
+
+/// Serializable parameters of a built-in effect.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum EffectSettings {
+    Eq(EqParams),
+    Compressor(CompressorParams),
+    Limiter(LimiterParams),
+    Gate(GateParams),
+    DeEsser(DeEsserParams),
+}
+
+impl EffectSettings {
+    /// Build the effect, prepared for `sample_rate`.
+    pub fn build(&self, sample_rate: u32) -> Box<dyn AudioEffect> {
+        let mut effect: Box<dyn AudioEffect> = match self {
+            Self::Eq(p) => Box::new(ParametricEq::new(p.clone())),
+            Self::Compressor(p) => Box::new(Compressor::new(p.clone())),
+            Self::Limiter(p) => Box::new(TruePeakLimiter::new(p.clone())),
+            Self::Gate(p) => Box::new(NoiseGate::new(p.clone())),
+            Self::DeEsser(p) => Box::new(DeEsser::new(p.clone())),
+        };
+        effect.prepare(sample_rate);
+        effect
+    }
+}
+
+/// Reference signals and measurements for effect tests.
+#[cfg(test)]
+pub(crate) mod test_signals {
+    pub const RATE: u32 = 48000;
+
+    /// Interleaved stereo sine (same on both channels).
+    pub fn sine(freq: f32, amplitude: f32, seconds: f32) -> Vec<f32> {
+        let frames = (seconds * RATE as f32) as usize;
+        (0..frames)
+            .flat_map(|n| {
+                let s =
+                    amplitude * (2.0 * std::f32::consts::PI * freq * n as f32 / RATE as f32).sin();
+                [s, s]
+            })
+            .collect()
+    }
+
+    /// Peak level in dB of the second half of a buffer (after settling).
+    pub fn settled_peak_db(buffer: &[f32]) -> f32 {
+        let tail = &buffer[buffer.len() / 2..];
+        super::gain_to_db(tail.iter().fold(0.0f32, |m, s| m.max(s.abs())))
+    }
+
+    /// RMS level in dB of the second half of a buffer.
+    pub fn settled_rms_db(buffer: &[f32]) -> f32 {
+        let tail = &buffer[buffer.len() / 2..];
+        let ms = tail.iter().map(|s| s * s).sum::<f32>() / tail.len() as f32;
+        super::gain_to_db(ms.sqrt())
+    }
+
+    /// Process a buffer in fixed-size blocks like the mixer does.
+    pub fn process_blocks(
+        effect: &mut dyn crate::effect::AudioEffect,
+        buffer: &mut [f32],
+        sidechain: Option<&[f32]>,
+    ) {
+        const BLOCK: usize = 1024;
+        for (i, block) in buffer.chunks_mut(BLOCK).enumerate() {
+            let key = sidechain.map(|k| &k[i * BLOCK..i * BLOCK + block.len()]);
+            effect.process(block, key);
+        }
+    }
+}
@@ -0,0 +1,540 @@
+//! Audio input backends.
+//!
+//! An `InputBackend` captures interleaved audio into an `InputSink`:
+//! `CpalInput` records from a sound device, `SyntheticInput` generates a
+//! tone (or plays a buffer once) in real time for headless runs and tests.
+//! The sink hands whole frames to the consumer through a ring buffer,
+//! counts overruns and holds per-channel peaks for input metering.
+
+use crate::ring_buffer::RingBuffer;
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SampleFormat, SizedSample};
+use proedit_core::{ProEditError, Result};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Channels metered by an `InputSink`; further channels are captured but
+/// not metered.
+pub const MAX_METERED_CHANNELS: usize = 8;
+
+/// Counters shared between a sink and its consumer.
+#[derive(Debug, Default)]
+struct InputCounters {
+    overruns: AtomicU64,
+    frames_captured: AtomicU64,
+    /// Peak magnitude per channel since the last `take_peaks` (f32 bits).
+    peaks: [AtomicU32; MAX_METERED_CHANNELS],
+}
+
+/// Input statistics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct InputStats {
+    /// Callbacks whose audio did not fit the buffer and was dropped.
+    pub overruns: u64,
+    /// Frames delivered by the backend (including dropped ones).
+    pub frames_captured: u64,
+}
+
+/// The consumer side of an input stream: a ring buffer of interleaved
+/// frames plus counters. Cloning shares the same stream.
+#[derive(Clone)]
+pub struct InputSink {
+    buffer: Arc<RingBuffer>,
+    counters: Arc<InputCounters>,
+}
+
+impl InputSink {
+    /// Create a sink buffering up to `capacity` samples.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            buffer: Arc::new(RingBuffer::new(capacity)),
+            counters: Arc::default(),
+        }
+    }
+
+    /// Capture interleaved `samples` with `channels` channels. Frames that
+    /// do not fit are dropped and counted as an overrun.
+    pub fn push(&self, samples: &[f32], channels: usize) {
+        let channels = channels.max(1);
+        for (c, peak) in self
+            .counters
+            .peaks
+            .iter()
+            .enumerate()
+            .take(channels.min(MAX_METERED_CHANNELS))
+        {
+            let level = samples
+                .iter()
+                .skip(c)
+                .step_by(channels)
+                .fold(0.0_f32, |m, s| m.max(s.abs()));
+            peak.fetch_max(level.to_bits(), Ordering::Relaxed);
+        }
+
+        let fits = self.buffer.available_write() / channels * channels;
+        let count = samples.len().min(fits);
+        self.buffer.write(&samples[..count]);
+        if count < samples.len() {
+            self.counters.overruns.fetch_add(1, Ordering::Relaxed);
+        }
+        self.counters
+            .frames_captured
+            .fetch_add((samples.len() / channels) as u64, Ordering::Relaxed);
+    }
+
+    /// Read captured samples; returns how many were read.
+    pub fn read(&self, output: &mut [f32]) -> usize {
+        self.buffer.read(output)
+    }
+
+    /// Samples waiting to be read.
+    pub fn available(&self) -> usize {
+        self.buffer.available_read()
+    }
+
+    /// Discard everything buffered.
+    pub fn clear(&self) {
+        self.buffer.clear();
+    }
+
+    /// Peak level per channel since the last call (linear), resetting
+    /// them.
+    pub fn take_peaks(&self, channels: usize) -> Vec<f32> {
+        self.counters
+            .peaks
+            .iter()
+            .take(channels.min(MAX_METERED_CHANNELS))
+            .map(|peak| f32::from_bits(peak.swap(0, Ordering::Relaxed)))
+            .collect()
+    }
+
+    /// Overruns and frames captured.
+    pub fn stats(&self) -> InputStats {
+        InputStats {
+            overruns: self.counters.overruns.load(Ordering::Relaxed),
+            frames_captured: self.counters.frames_captured.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// A source of captured audio.
+pub trait InputBackend: Send {
+    /// Backend name for logs and UI.
+    fn name(&self) -> &str;
+
+    /// Start capturing into `sink`.
+    fn start(&mut self, sink: InputSink) -> Result<()>;
+
+    /// Stop capturing and release the source.
+    fn stop(&mut self) -> Result<()>;
+
+    /// Whether the source is running.
+    fn is_running(&self) -> bool;
+
+    /// Capture sample rate and channel count, once started.
+    fn format(&self) -> Option<(u32, u16)>;
+
+    /// Measured time from audio reaching the device to it arriving in the
+    /// sink; zero until the first buffer is captured, or if unknown.
+    fn latency(&self) -> Duration {
+        Duration::ZERO
+    }
+}
+
+// ── cpal ────────────────────────────────────────────────────────
+
+/// An input device reported by cpal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputDevice {
+    pub name: String,
+    /// Whether this is the host's default input.
+    pub is_default: bool,
+    /// Default sample rate.
+    pub sample_rate: u32,
+    /// Default channel count.
+    pub channels: u16,
+}
+
+/// Captures from a cpal input device in its default format.
+///
+/// Like `CpalOutput`, the stream lives on its own thread for as long as
+/// the backend runs.
+pub struct CpalInput {
+    device_name: Option<String>,
+    format: Option<(u32, u16)>,
+    running: Option<(crossbeam_channel::Sender<()>, JoinHandle<()>)>,
+    /// Latency measured by the stream callback, in nanoseconds.
+    latency_ns: Arc<AtomicU64>,
+}
+
+impl CpalInput {
+    /// Capture from the named device, or the host default when `None`.
+    pub fn new(device_name: Option<String>) -> Self {
+        Self {
+            device_name,
+            format: None,
+            running: None,
+            latency_ns: Arc::default(),
+        }
+    }
+
+    /// List the host's input devices.
+    pub fn devices() -> Result<Vec<InputDevice>> {
+        let host = cpal::default_host();
+        let default_name = host.default_input_device().and_then(|d| d.name().ok());
+        let devices = host
+            .input_devices()
+            .map_err(|e| ProEditError::Audio(format!("Failed to list input devices: {e}")))?;
+        Ok(devices
+            .filter_map(|device| {
+                let name = device.name().ok()?;
+                let config = device.default_input_config().ok()?;
+                Some(InputDevice {
+                    is_default: default_name.as_deref() == Some(name.as_str()),
+                    name,
+                    sample_rate: config.sample_rate().0,
+                    channels: config.channels(),
+                })
+            })
+            .collect())
+    }
+
+    fn find_device(&self) -> Result<cpal::Device> {
+        let host = cpal::default_host();
+        match &self.device_name {
+            None => host
+                .default_input_device()
+                .ok_or_else(|| ProEditError::Audio("No default input device".into())),
+            Some(name) => host
+                .input_devices()
+                .map_err(|e| ProEditError::Audio(format!("Failed to list input devices: {e}")))?
+                .find(|d| d.name().ok().as_deref() == Some(name.as_str()))
+                .ok_or_else(|| ProEditError::NotFound(format!("Input device {name}"))),
+        }
+    }
+}
+
+fn build_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    sink: InputSink,
+    latency_ns: Arc<AtomicU64>,
+) -> Result<cpal::Stream>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    let channels = config.channels.max(1) as usize;
+    let mut scratch = Vec::<f32>::with_capacity(8192);
+    device
+        .build_input_stream(
+            config,
+            move |data: &[T], info: &cpal::InputCallbackInfo| {
+                // The first frame of this buffer was captured at `capture`.
+                let timestamp = info.timestamp();
+                let device_delay = timestamp
+                    .callback
+                    .duration_since(&timestamp.capture)
+                    .unwrap_or_default();
+                latency_ns.store(device_delay.as_nanos() as u64, Ordering::Relaxed);
+                scratch.clear();
+                scratch.extend(data.iter().map(|s| s.to_sample::<f32>()));
+                sink.push(&scratch, channels);
+            },
+            |e| warn!("Audio input error: {}", e),
+            None,
+        )
+        .map_err(|e| ProEditError::Audio(format!("Failed to open input stream: {e}")))
+}
+
+impl InputBackend for CpalInput {
+    fn name(&self) -> &str {
+        self.device_name.as_deref().unwrap_or("Default Input")
+    }
+
+    fn start(&mut self, sink: InputSink) -> Result<()> {
+        self.stop()?;
+        let device = self.find_device()?;
+        let supported = device
+            .default_input_config()
+            .map_err(|e| ProEditError::Audio(format!("No usable input config: {e}")))?;
+        let format = supported.sample_format();
+        let config = supported.config();
+
+        let (ready_tx, ready_rx) = crossbeam_channel::bounded::<Result<()>>(1);
+        let (stop_tx, stop_rx) = crossbeam_channel::bounded::<()>(1);
+        let stream_config = config.clone();
+        let latency_ns = Arc::clone(&self.latency_ns);
+        latency_ns.store(0, Ordering::Relaxed);
+        let thread = std::thread::Builder::new()
+            .name("audio-input".into())
+            .spawn(move || {
+                let stream = match format {
+                    SampleFormat::F32 => {
+                        build_stream::<f32>(&device, &stream_config, sink, latency_ns)
+                    }
+                    SampleFormat::I16 => {
+                        build_stream::<i16>(&device, &stream_config, sink, latency_ns)
+                    }
+                    SampleFormat::U16 => {
+                        build_stream::<u16>(&device, &stream_config, sink, latency_ns)
+                    }
+                    SampleFormat::I32 => {
+                        build_stream::<i32>(&device, &stream_config, sink, latency_ns)
+                    }
+                    other => Err(ProEditError::Audio(format!(
+                        "Unsupported input sample format {other:?}"
+                    ))),
+                }
+                .and_then(|stream| {
+                    stream
+                        .play()
+                        .map_err(|e| ProEditError::Audio(format!("Failed to start input: {e}")))?;
+                    Ok(stream)
+                });
+                match stream {
+                    Ok(stream) => {
+                        let _ = ready_tx.send(Ok(()));
+                        let _ = stop_rx.recv();
+                        drop(stream);
+                    }
+                    Err(e) => {
+                        let _ = ready_tx.send(Err(e));
+                    }
+                }
+            })
+            .map_err(|e| ProEditError::Audio(format!("Failed to start input thread: {e}")))?;
+
+        let ready = ready_rx
+            .recv()
+            .unwrap_or_else(|_| Err(ProEditError::Audio("Input thread exited".into())));
+        if let Err(e) = ready {
+            let _ = thread.join();
+            return Err(e);
+        }
+        info!(
+            "Audio input on {} at {} Hz, {} channels",
+            self.name(),
+            config.sample_rate.0,
+            config.channels
+        );
+        self.format = Some((config.sample_rate.0, config.channels));
+        self.running = Some((stop_tx, thread));
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        if let Some((stop, thread)) = self.running.take() {
+            let _ = stop.send(());
+            thread
+                .join()
+                .map_err(|_| ProEditError::Audio("Input thread panicked".into()))?;
+        }
+        self.format = None;
+        Ok(())
+    }
+
+    fn is_running(&self) -> bool {
+        self.running.is_some()
+    }
+
+    fn format(&self) -> Option<(u32, u16)> {
+        self.format
+    }
+
+    fn latency(&self) -> Duration {
+        Duration::from_nanos(self.latency_ns.load(Ordering::Relaxed))
+    }
+}
+
+impl Drop for CpalInput {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
+
+// ── Synthetic ───────────────────────────────────────────────────
+
+/// What a `SyntheticInput` produces.
+#[derive(Debug, Clone)]
+enum Signal {
+    /// A sine tone on every channel.
+    Tone { frequency: f32, amplitude: f32 },
+    /// Interleaved samples played once, then silence.
+    Samples(Arc<Vec<f32>>),
+}
+
+/// A device-less input delivering audio in real time, every 10 ms, for
+/// headless runs and tests.
+pub struct SyntheticInput {
+    sample_rate: u32,
+    channels: u16,
+    signal: Signal,
+    period: Duration,
+    format: Option<(u32, u16)>,
+    running: Option<(Arc<AtomicBool>, JoinHandle<()>)>,
+}
+
+impl SyntheticInput {
+    /// A sine tone at `frequency` Hz and `amplitude` (linear).
+    pub fn tone(sample_rate: u32, channels: u16, frequency: f32, amplitude: f32) -> Self {
+        Self::with_signal(
+            sample_rate,
+            channels,
+            Signal::Tone {
+                frequency,
+                amplitude,
+            },
+        )
+    }
+
+    /// Interleaved `samples`, played once and followed by silence.
+    pub fn from_samples(sample_rate: u32, channels: u16, samples: Vec<f32>) -> Self {
+        Self::with_signal(sample_rate, channels, Signal::Samples(Arc::new(samples)))
+    }
+
+    fn with_signal(sample_rate: u32, channels: u16, signal: Signal) -> Self {
+        Self {
+            sample_rate,
+            channels: channels.max(1),
+            signal,
+            period: Duration::from_millis(10),
+            format: None,
+            running: None,
+        }
+    }
+}
+
+impl InputBackend for SyntheticInput {
+    fn name(&self) -> &str {
+        "Synthetic Input"
+    }
+
+    fn start(&mut self, sink: InputSink) -> Result<()> {
+        self.stop()?;
+        let channels = self.channels as usize;
+        let rate = self.sample_rate;
+        let period = self.period;
+        let period_frames = ((rate as f64 * period.as_secs_f64()).round() as usize).max(1);
+        let signal = self.signal.clone();
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = Arc::clone(&stop);
+        let thread = std::thread::Builder::new()
+            .name("audio-synthetic-input".into())
+            .spawn(move || {
+                let mut block = vec![0.0f32; period_frames * channels];
+                let mut frame = 0usize;
+                let mut deadline = Instant::now();
+                while !thread_stop.load(Ordering::Acquire) {
+                    deadline += period;
+                    if let Some(wait) = deadline.checked_duration_since(Instant::now()) {
+                        std::thread::sleep(wait);
+                    }
+                    for (n, out) in block.chunks_exact_mut(channels).enumerate() {
+                        let index = frame + n;
+                        match &signal {
+                            Signal::Tone {
+                                frequency,
+                                amplitude,
+                            } => {
+                                let t = index as f64 / rate as f64;
+                                let value = (std::f64::consts::TAU * *frequency as f64 * t).sin()
+                                    as f32
+                                    * amplitude;
+                                out.fill(value);
+                            }
+                            Signal::Samples(samples) => {
+                                let base = index * channels;
+                                for (c, s) in out.iter_mut().enumerate() {
+                                    *s = samples.get(base + c).copied().unwrap_or(0.0);
+                                }
+                            }
+                        }
+                    }
+                    frame += period_frames;
+                    sink.push(&block, channels);
+                }
+            })
+            .map_err(|e| ProEditError::Audio(format!("Failed to start input thread: {e}")))?;
+
+        self.format = Some((rate, self.channels));
+        self.running = Some((stop, thread));
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        self.format = None;
+        if let Some((stop, thread)) = self.running.take() {
+            stop.store(true, Ordering::Release);
+            thread
+                .join()
+                .map_err(|_| ProEditError::Audio("Input thread panicked".into()))?;
+        }
+        Ok(())
+    }
+
+    fn is_running(&self) -> bool {
+        self.running.is_some()
+    }
+
+    fn format(&self) -> Option<(u32, u16)> {
+        self.format
+    }
+}
+
+impl Drop for SyntheticInput {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sink_meters_and_drops_whole_frames() {
+        let sink = InputSink::new(7);
+        sink.push(&[0.5, -0.25, -0.75, 0.1], 2);
+        assert_eq!(sink.take_peaks(2), [0.75, 0.25]);
+        assert_eq!(sink.take_peaks(2), [0.0, 0.0]);
+
+        // Room for one more stereo frame of three.
+        sink.push(&[0.1, 0.2, 0.3, 0.4, 0.5, 0.6], 2);
+        assert_eq!(sink.available(), 6);
+        assert_eq!(
+            sink.stats(),
+            InputStats {
+                overruns: 1,
+                frames_captured: 5,
+            }
+        );
+        let mut out = [0.0; 6];
+        assert_eq!(sink.read(&mut out), 6);
+        assert_eq!(out, [0.5, -0.25, -0.75, 0.1, 0.1, 0.2]);
+    }
+
+    #[test]
+    fn test_synthetic_input_delivers_in_real_time() {
+        let sink = InputSink::new(48000);
+        let mut input = SyntheticInput::from_samples(8000, 1, vec![0.25; 400]);
+        input.start(sink.clone()).unwrap();
+        assert_eq!(input.format(), Some((8000, 1)));
+        while sink.stats().frames_captured < 800 {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        input.stop().unwrap();
+        assert!(!input.is_running());
+
+        let mut out = vec![0.0; sink.available()];
+        sink.read(&mut out);
+        assert!(out.len() >= 800);
+        assert!(out[..400].iter().all(|s| *s == 0.25));
+        assert!(out[400..].iter().all(|s| *s == 0.0));
+        assert_eq!(sink.take_peaks(1), [0.25]);
+    }
+}
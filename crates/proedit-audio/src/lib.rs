@@ -4,61 +4,514 @@
 //!
 //! Architecture:
 //! - `RingBuffer`: Lock-free SPSC buffer between mixer thread and audio callback
-//! - `Mixer`: Combines multiple channels with volume/pan/solo/mute
+//! - `Mixer`: Combines multiple channels with volume/pan/solo/mute and
+//!   insert latency compensation; `InsertSender` hands it new chains
+//! - `Bus`/`ChannelLayout`: Submix buses and stereo/5.1/7.1 master layouts
+//! - `EffectChain`: Per-channel and master insert effects (EQ, dynamics)
+//! - `LoudnessMeter`: BS.1770 / EBU R128 loudness and true-peak metering
+//! - `time_stretch`/`pitch_shift`: WSOLA speed and pitch changes per clip
+//! - `TimelineRenderer`: Renders a sequence's audio tracks through the mixer
+//! - `bounce_mix`/`bounce_stems`: Multichannel mix and per-bus stem WAVs
 //! - `Waveform`: Pre-computed waveform data for UI display
-//! - `AudioEngine`: Top-level orchestrator
+//! - `PeakCache`: Mipmapped, memory-mapped waveform peak files per asset
+//! - `OutputBackend`: Device (cpal) and null/file sinks draining the ring buffer
+//! - `InputBackend`: Device (cpal) and synthetic capture with input metering
+//! - `Recorder`: Voice-over takes punched into a timeline range with pre-roll
+//! - `AudioEngine`: Top-level orchestrator with the real-time render thread
 
+pub mod bounce;
+pub mod bus;
+pub mod effect;
+pub mod effects;
+pub mod input;
+pub mod loudness;
 pub mod mixer;
+pub mod output;
+pub mod peaks;
+pub mod record;
+pub mod render;
 pub mod ring_buffer;
+pub mod stretch;
+mod wav;
 pub mod waveform;
 
-pub use mixer::{Mixer, MixerChannel};
+pub use bounce::{bounce_mix, bounce_stems};
+pub use bus::{Bus, ChannelLayout, Route, Speaker};
+pub use effect::{AudioEffect, AudioParam, EffectChain, EffectSlot, EffectSlotSettings};
+pub use effects::EffectSettings;
+pub use input::{CpalInput, InputBackend, InputDevice, InputSink, InputStats, SyntheticInput};
+pub use loudness::{
+    measure_clip, measure_range, normalize_clip, normalize_master, LoudnessMeter, LoudnessReport,
+};
+pub use mixer::{InsertSender, Mixer, MixerChannel};
+pub use output::{CpalOutput, NullOutput, OutputBackend, OutputDevice, OutputSource, OutputStats};
+pub use peaks::{peak_file_path, PeakCache, PeakFile, SourceStamp, PEAK_LEVELS};
+pub use record::{place_take, RecordSettings, RecordState, Recorder, Take};
+pub use render::{
+    sample_to_time, time_to_sample, MemorySourceLoader, PcmSource, SourceLoader, TimelineRenderer,
+    BLOCK_FRAMES,
+};
 pub use ring_buffer::RingBuffer;
+pub use stretch::{pitch_shift, time_stretch, Stretcher};
 pub use waveform::{Waveform, WaveformSample};
 
-use proedit_core::Result;
+use parking_lot::Mutex;
+use proedit_core::{ProEditError, RationalTime, Result, TimeRange};
+use proedit_timeline::{EditCommand, Sequence};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
 use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 use tracing::info;
 
+/// Audio the prepare thread keeps loaded ahead of the playhead.
+const PREPARE_AHEAD_SECS: i64 = 15;
+
+/// How often the prepare thread moves that window along while playing.
+const PREPARE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// What the render thread draws from.
+struct RenderState {
+    renderer: TimelineRenderer,
+    sequence: Option<Arc<Sequence>>,
+    /// One block in the mixer's layout.
+    block: Vec<f32>,
+    /// Stereo fold-down of a surround block.
+    monitor: Vec<f32>,
+}
+
+/// State shared between the engine and its render thread.
+///
+/// Lock order: `state` before `mixer`.
+struct RenderShared {
+    state: Mutex<RenderState>,
+    mixer: Arc<Mutex<Mixer>>,
+    /// The mixer's output ring, reachable without the mixer lock.
+    output: Arc<RingBuffer>,
+    /// Output sample index of the next block to render.
+    position: AtomicI64,
+    /// Odd while `position` and `output` are changed together; playhead
+    /// readers retry until it is even and unchanged.
+    epoch: AtomicU64,
+    playing: Arc<AtomicBool>,
+    /// Set while buffered output waits to be discarded by the audio
+    /// callback; nothing is rendered until it has been.
+    flush: Arc<AtomicBool>,
+    shutdown: AtomicBool,
+    /// Sequences handed to the prepare thread, and prepared so far.
+    requested: AtomicU64,
+    prepared: AtomicU64,
+    /// Set by `play` and `seek`: nothing is rendered until the audio at
+    /// the playhead has been prepared, so playback doesn't start silent.
+    priming: AtomicBool,
+}
+
+impl RenderShared {
+    /// Render blocks until the output buffer is full. A surround mix is
+    /// folded down to stereo for the output.
+    fn fill(&self) {
+        let mut state = self.state.lock();
+        let RenderState {
+            renderer,
+            sequence,
+            block,
+            monitor,
+        } = &mut *state;
+        let Some(sequence) = sequence.as_deref() else {
+            return;
+        };
+        if self.priming.load(Ordering::Acquire) {
+            if !self.is_prepared() {
+                return;
+            }
+            self.priming.store(false, Ordering::Release);
+        }
+
+        let mut mixer = self.mixer.lock();
+        let layout = mixer.layout();
+        block.resize(BLOCK_FRAMES * layout.channel_count(), 0.0);
+        let output = &self.output;
+        while self.playing.load(Ordering::Acquire)
+            && !self.flush.load(Ordering::Acquire)
+            && output.available_write() >= monitor.len()
+        {
+            let start = self.position.load(Ordering::Acquire);
+            renderer.render_block(sequence, &mut mixer, start, BLOCK_FRAMES, block);
+            self.update(|| {
+                if layout.is_surround() {
+                    layout.downmix_stereo(block, monitor);
+                    output.write(monitor);
+                } else {
+                    output.write(block);
+                }
+                self.position
+                    .store(start + BLOCK_FRAMES as i64, Ordering::Release);
+            });
+        }
+    }
+
+    /// Whether the prepare thread has served every request so far.
+    fn is_prepared(&self) -> bool {
+        self.prepared.load(Ordering::Acquire) >= self.requested.load(Ordering::Acquire)
+    }
+
+    /// Change `position` and `output` together, as one step for
+    /// [`playhead`](Self::playhead).
+    fn update<R>(&self, change: impl FnOnce() -> R) -> R {
+        self.epoch.fetch_add(1, Ordering::AcqRel);
+        let result = change();
+        self.epoch.fetch_add(1, Ordering::Release);
+        result
+    }
+
+    /// Output sample index of the next frame the audio callback will read.
+    /// Lock-free, so it can be polled from the UI thread while rendering.
+    fn playhead(&self) -> i64 {
+        loop {
+            let epoch = self.epoch.load(Ordering::Acquire);
+            if epoch % 2 == 0 {
+                let rendered = self.position.load(Ordering::Acquire);
+                let buffered = self.buffered_frames();
+                if self.epoch.load(Ordering::Acquire) == epoch {
+                    return rendered - buffered;
+                }
+            }
+            std::hint::spin_loop();
+        }
+    }
+
+    /// Stereo frames rendered but not yet read by the audio callback; none
+    /// while a flush is pending, as that audio will never be heard.
+    fn buffered_frames(&self) -> i64 {
+        if self.flush.load(Ordering::Acquire) {
+            return 0;
+        }
+        (self.output.available_read() / 2) as i64
+    }
+}
+
 /// Audio engine state.
+///
+/// A render thread reads the current sequence's audio tracks at the
+/// playhead and keeps the mixer's output ring buffer filled ahead of the
+/// audio callback while playing. A prepare thread decodes and stretches
+/// the audio ahead of the playhead, following it while playing and after
+/// seeks or sequence changes; what it has not loaded yet plays as silence.
+/// Insert chains changed on the sequence are built on the caller's thread
+/// and handed to the mixer, so they never wait on the render thread.
 pub struct AudioEngine {
     sample_rate: u32,
     channels: u16,
-    playing: bool,
     /// The mixer for combining audio tracks.
-    pub mixer: Mixer,
+    pub mixer: Arc<Mutex<Mixer>>,
+    /// Hands the sequence's insert chains to the mixer.
+    inserts: Mutex<InsertSender>,
+    shared: Arc<RenderShared>,
+    thread: Option<JoinHandle<()>>,
+    prepare: Option<(Sender<u64>, JoinHandle<()>)>,
+    output: Option<Box<dyn OutputBackend>>,
 }
 
 impl AudioEngine {
-    /// Create a new audio engine.
+    /// Create a new audio engine and start its render thread.
     pub fn new() -> Result<Self> {
         info!("Initializing audio engine");
+        let mut engine = Self::without_thread();
+        let shared = Arc::clone(&engine.shared);
+        let sample_rate = engine.sample_rate;
+        let thread = std::thread::Builder::new()
+            .name("audio-render".into())
+            .spawn(move || render_loop(shared, sample_rate))
+            .map_err(|e| ProEditError::Audio(format!("Failed to start render thread: {e}")))?;
+        engine.thread = Some(thread);
+
+        let shared = Arc::clone(&engine.shared);
+        let (sender, requests) = mpsc::channel();
+        let thread = std::thread::Builder::new()
+            .name("audio-prepare".into())
+            .spawn(move || prepare_loop(shared, requests))
+            .map_err(|e| ProEditError::Audio(format!("Failed to start prepare thread: {e}")))?;
+        engine.prepare = Some((sender, thread));
+        engine.shared.state.lock().renderer.set_deferred(true);
+        Ok(engine)
+    }
+
+    fn without_thread() -> Self {
+        let sample_rate = 48000;
         // Buffer size: ~100ms at 48kHz stereo
-        let buffer_samples = 48000 / 10 * 2;
-        Ok(Self {
-            sample_rate: 48000,
+        let buffer_samples = sample_rate as usize / 10 * 2;
+        let mut mixer = Mixer::new(3, buffer_samples);
+        mixer.meter = Some(LoudnessMeter::new(sample_rate));
+        let inserts = Mutex::new(mixer.insert_sender());
+        let mixer = Arc::new(Mutex::new(mixer));
+        let shared = Arc::new(RenderShared {
+            state: Mutex::new(RenderState {
+                renderer: TimelineRenderer::new(sample_rate, Arc::new(MemorySourceLoader::new())),
+                sequence: None,
+                block: vec![0.0; BLOCK_FRAMES * 2],
+                monitor: vec![0.0; BLOCK_FRAMES * 2],
+            }),
+            mixer: Arc::clone(&mixer),
+            output: Arc::clone(&mixer.lock().output_buffer),
+            position: AtomicI64::new(0),
+            epoch: AtomicU64::new(0),
+            playing: Arc::new(AtomicBool::new(false)),
+            flush: Arc::new(AtomicBool::new(false)),
+            shutdown: AtomicBool::new(false),
+            requested: AtomicU64::new(0),
+            prepared: AtomicU64::new(0),
+            priming: AtomicBool::new(false),
+        });
+        Self {
+            sample_rate,
             channels: 2,
-            playing: false,
-            mixer: Mixer::new(3, buffer_samples),
-        })
+            mixer,
+            inserts,
+            shared,
+            thread: None,
+            prepare: None,
+            output: None,
+        }
+    }
+
+    /// Set the sequence whose audio tracks are played. Its sources are
+    /// loaded on the prepare thread; see [`wait_prepared`](Self::wait_prepared).
+    pub fn set_sequence(&self, sequence: Sequence) {
+        self.inserts.lock().sync(&sequence);
+        let mut state = self.shared.state.lock();
+        state.renderer.release_unused(&sequence);
+        state.sequence = Some(Arc::new(sequence));
+        drop(state);
+        self.request_prepare();
+    }
+
+    /// Set the loader used to decode clip sources.
+    pub fn set_source_loader(&self, loader: Arc<dyn SourceLoader>) {
+        self.shared.state.lock().renderer.set_loader(loader);
+        self.request_prepare();
+    }
+
+    /// Whether the prepare thread has loaded the current sequence's audio
+    /// at the playhead.
+    pub fn is_prepared(&self) -> bool {
+        self.shared.is_prepared()
+    }
+
+    /// Block until the current sequence's audio is loaded or `timeout`
+    /// passes; returns whether it is loaded.
+    pub fn wait_prepared(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        while !self.is_prepared() {
+            if Instant::now() >= deadline {
+                return false;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        true
+    }
+
+    /// Have the prepare thread load the current sequence's audio at the
+    /// playhead.
+    fn request_prepare(&self) {
+        if let Some((sender, _)) = &self.prepare {
+            let request = self.shared.requested.fetch_add(1, Ordering::AcqRel) + 1;
+            let _ = sender.send(request);
+        }
+    }
+
+    /// Measure the loudness of the current sequence's mix over `range`
+    /// (the whole sequence when `None`).
+    ///
+    /// Renders offline with a copy of the mixer, so playback is not
+    /// disturbed. Returns `None` when no sequence is set.
+    pub fn measure_loudness(&self, range: Option<TimeRange>) -> Option<LoudnessReport> {
+        let (mut renderer, sequence, mut mixer) = self.offline()?;
+        let range = range.unwrap_or_else(|| sequence.time_range());
+        Some(measure_range(&mut renderer, &sequence, &mut mixer, range))
+    }
+
+    /// The edit that brings the current sequence's mix over `range` (the
+    /// whole sequence when `None`) to `target` LUFS through its master
+    /// gain, measured like [`measure_loudness`](Self::measure_loudness).
+    pub fn normalize_master(&self, range: Option<TimeRange>, target: f64) -> Option<EditCommand> {
+        let (mut renderer, sequence, mut mixer) = self.offline()?;
+        let range = range.unwrap_or_else(|| sequence.time_range());
+        Some(normalize_master(
+            &mut renderer,
+            &sequence,
+            &mut mixer,
+            range,
+            target,
+        ))
+    }
+
+    /// Write the current sequence's mix over `range` (the whole sequence
+    /// when `None`) to a WAV file in `layout`, rendering offline.
+    pub fn export_mix(
+        &self,
+        path: &Path,
+        range: Option<TimeRange>,
+        layout: ChannelLayout,
+    ) -> Result<()> {
+        let (mut renderer, sequence, mut mixer) = self
+            .offline()
+            .ok_or_else(|| ProEditError::NotFound("No sequence to export".into()))?;
+        mixer.set_layout(layout);
+        let range = range.unwrap_or_else(|| sequence.time_range());
+        bounce_mix(&mut renderer, &sequence, &mut mixer, range, path)
+    }
+
+    /// Write one stem per submix bus to `path_for(bus)`, like
+    /// [`export_mix`](Self::export_mix). Returns the written paths.
+    pub fn export_stems(
+        &self,
+        range: Option<TimeRange>,
+        layout: ChannelLayout,
+        path_for: impl Fn(&Bus) -> PathBuf,
+    ) -> Result<Vec<PathBuf>> {
+        let (mut renderer, sequence, mut mixer) = self
+            .offline()
+            .ok_or_else(|| ProEditError::NotFound("No sequence to export".into()))?;
+        mixer.set_layout(layout);
+        let range = range.unwrap_or_else(|| sequence.time_range());
+        bounce_stems(&mut renderer, &sequence, &mut mixer, range, path_for)
+    }
+
+    /// A renderer, the current sequence and a copy of the mixer for
+    /// offline work that must not disturb playback.
+    fn offline(&self) -> Option<(TimelineRenderer, Arc<Sequence>, Mixer)> {
+        let (sequence, loader) = {
+            let state = self.shared.state.lock();
+            (state.sequence.clone()?, state.renderer.loader())
+        };
+        let mixer = self.mixer.lock().offline_copy();
+        let renderer = TimelineRenderer::new(self.sample_rate, loader);
+        Some((renderer, sequence, mixer))
+    }
+
+    /// The engine's output stream, for driving an `OutputBackend`.
+    pub fn output_source(&self) -> OutputSource {
+        OutputSource::new(
+            self.output_buffer(),
+            self.sample_rate,
+            Arc::clone(&self.shared.playing),
+            Arc::clone(&self.shared.flush),
+        )
+    }
+
+    /// Start `backend` on the engine's output, replacing any open one.
+    pub fn open_output(&mut self, mut backend: Box<dyn OutputBackend>) -> Result<()> {
+        self.close_output()?;
+        backend.start(self.output_source())?;
+        info!("Audio output opened: {}", backend.name());
+        self.output = Some(backend);
+        Ok(())
+    }
+
+    /// Stop and release the output backend.
+    pub fn close_output(&mut self) -> Result<()> {
+        let result = match self.output.take() {
+            Some(mut backend) => backend.stop(),
+            None => Ok(()),
+        };
+        // No callback is left to carry out a pending flush.
+        if self.shared.flush.swap(false, Ordering::AcqRel) {
+            self.shared.output.clear();
+        }
+        result
+    }
+
+    /// The open output backend.
+    pub fn output(&self) -> Option<&dyn OutputBackend> {
+        self.output.as_deref()
+    }
+
+    /// Underruns, frames played and latency of the open output.
+    pub fn output_stats(&self) -> Option<OutputStats> {
+        self.output.as_ref().map(|o| o.stats())
+    }
+
+    /// The time being heard right now: the playhead minus the measured
+    /// output latency. Video should present this time to stay in sync.
+    pub fn audible_position(&self) -> RationalTime {
+        let latency = self
+            .output_stats()
+            .map_or(Duration::ZERO, |stats| stats.latency);
+        let latency = time_to_sample(
+            RationalTime::new(latency.as_micros() as i64, 1_000_000),
+            self.sample_rate,
+        );
+        let position = time_to_sample(self.position(), self.sample_rate);
+        sample_to_time((position - latency).max(0), self.sample_rate)
     }
 
     /// Start audio playback.
+    ///
+    /// Rendering waits for the audio at the playhead to be prepared, so the
+    /// first block heard is the sequence rather than silence.
     pub fn play(&mut self) {
-        self.playing = true;
+        self.shared.priming.store(true, Ordering::Release);
+        self.shared.playing.store(true, Ordering::Release);
+        if let Some(thread) = &self.thread {
+            thread.thread().unpark();
+        }
         info!("Audio playback started");
     }
 
     /// Stop audio playback.
+    ///
+    /// Audio that was rendered but not yet played is discarded, so playback
+    /// resumes from the last audible sample.
     pub fn stop(&mut self) {
-        self.playing = false;
-        self.mixer.output_buffer.clear();
+        self.shared.playing.store(false, Ordering::Release);
+        let _state = self.shared.state.lock();
+        self.shared.update(|| {
+            let buffered = self.shared.buffered_frames();
+            self.shared.position.fetch_sub(buffered, Ordering::AcqRel);
+            self.discard_output();
+        });
         info!("Audio playback stopped");
     }
 
+    /// Move the playhead, snapping to the nearest output sample.
+    ///
+    /// Buffered audio is discarded so the next block starts exactly at
+    /// `time`, keeping audio aligned with the video clock. The audio there
+    /// is loaded on the prepare thread, and playback holds until it is.
+    pub fn seek(&self, time: RationalTime) {
+        let _state = self.shared.state.lock();
+        self.shared.update(|| {
+            self.discard_output();
+            self.shared
+                .position
+                .store(time_to_sample(time, self.sample_rate), Ordering::Release);
+        });
+        self.request_prepare();
+        self.shared.priming.store(true, Ordering::Release);
+    }
+
+    /// Current playhead: the time of the next sample the audio callback
+    /// will read. Doesn't wait on the render thread.
+    pub fn position(&self) -> RationalTime {
+        sample_to_time(self.shared.playhead(), self.sample_rate)
+    }
+
+    /// Discard rendered audio that has not been played. The ring's read
+    /// side belongs to the audio callback, so with an output open the
+    /// callback is asked to drain it; otherwise it is cleared here.
+    fn discard_output(&self) {
+        if self.output.is_some() {
+            self.shared.flush.store(true, Ordering::Release);
+        } else {
+            self.shared.output.clear();
+        }
+    }
+
     /// Check if audio is playing.
     pub fn is_playing(&self) -> bool {
-        self.playing
+        self.shared.playing.load(Ordering::Acquire)
     }
 
     /// Get sample rate.
@@ -73,17 +526,73 @@ impl AudioEngine {
 
     /// Get the output ring buffer for the audio callback.
     pub fn output_buffer(&self) -> Arc<RingBuffer> {
-        Arc::clone(&self.mixer.output_buffer)
+        Arc::clone(&self.mixer.lock().output_buffer)
     }
 }
 
 impl Default for AudioEngine {
     fn default() -> Self {
-        Self::new().unwrap_or(Self {
-            sample_rate: 48000,
-            channels: 2,
-            playing: false,
-            mixer: Mixer::new(3, 9600),
-        })
+        Self::new().unwrap_or_else(|_| Self::without_thread())
+    }
+}
+
+impl Drop for AudioEngine {
+    fn drop(&mut self) {
+        let _ = self.close_output();
+        self.shared.shutdown.store(true, Ordering::Release);
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            let _ = thread.join();
+        }
+        if let Some((sender, thread)) = self.prepare.take() {
+            drop(sender);
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Render thread body: top up the output buffer every half block.
+fn render_loop(shared: Arc<RenderShared>, sample_rate: u32) {
+    let interval = Duration::from_secs_f64(BLOCK_FRAMES as f64 / sample_rate as f64 / 2.0);
+    while !shared.shutdown.load(Ordering::Acquire) {
+        if shared.playing.load(Ordering::Acquire) {
+            shared.fill();
+        }
+        std::thread::park_timeout(interval);
+    }
+}
+
+/// Prepare thread body: for each request, and every `PREPARE_INTERVAL`
+/// while playing, load the audio of the current sequence ahead of the
+/// playhead without holding the render state, then hand it to the render
+/// thread's renderer. Requests that queued up meanwhile are served at once.
+fn prepare_loop(shared: Arc<RenderShared>, requests: mpsc::Receiver<u64>) {
+    loop {
+        let request = match requests.recv_timeout(PREPARE_INTERVAL) {
+            Ok(request) => Some(request),
+            Err(RecvTimeoutError::Timeout) if shared.playing.load(Ordering::Acquire) => None,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        let request = requests.try_iter().last().or(request);
+        let (mut preparer, sequence) = {
+            let state = shared.state.lock();
+            (state.renderer.preparer(), state.sequence.clone())
+        };
+        if let Some(sequence) = &sequence {
+            let position = shared.position.load(Ordering::Acquire);
+            let start = sample_to_time(position, preparer.sample_rate());
+            let range = TimeRange::new(start, RationalTime::new(PREPARE_AHEAD_SECS, 1));
+            preparer.prepare(sequence, range);
+        }
+        let mut state = shared.state.lock();
+        state.renderer.adopt(preparer);
+        if let Some(current) = state.sequence.clone() {
+            state.renderer.release_unused(&current);
+        }
+        drop(state);
+        if let Some(request) = request {
+            shared.prepared.store(request, Ordering::Release);
+        }
     }
 }
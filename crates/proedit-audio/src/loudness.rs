@@ -0,0 +1,574 @@
+//! Loudness metering per ITU-R BS.1770-4 and EBU R128.
+//!
+//! `LoudnessMeter` K-weights interleaved stereo or surround (with the
+//! BS.1770 channel weights), accumulates mean square energy in 100 ms
+//! steps and derives momentary (400 ms), short-term (3 s) and gated
+//! integrated loudness, loudness range (EBU Tech 3342) and 4x-oversampled
+//! true peak. Gating blocks are kept in loudness histograms, so the meter
+//! never allocates while processing. The mixer can run one live on its
+//! output; `measure_range` and `measure_clip` run one over an offline
+//! render.
+
+use crate::bus::ChannelLayout;
+use crate::effects::TruePeakDetector;
+use crate::mixer::Mixer;
+use crate::render::TimelineRenderer;
+use proedit_core::TimeRange;
+use proedit_timeline::{Clip, EditCommand, Sequence, Track};
+use std::collections::VecDeque;
+
+/// Absolute gate for integrated loudness and loudness range (LUFS).
+const ABSOLUTE_GATE: f64 = -70.0;
+/// Relative gate for integrated loudness (LU below the ungated level).
+const INTEGRATED_RELATIVE_GATE: f64 = -10.0;
+/// Relative gate for loudness range.
+const RANGE_RELATIVE_GATE: f64 = -20.0;
+/// Sub-blocks per momentary window (400 ms).
+const MOMENTARY_STEPS: usize = 4;
+/// Sub-blocks per short-term window (3 s).
+const SHORT_TERM_STEPS: usize = 30;
+/// Width of a histogram bin (LU).
+const HISTOGRAM_STEP: f64 = 0.02;
+/// Loudness of the top histogram bin; louder blocks share it (LUFS).
+const HISTOGRAM_TOP: f64 = 10.0;
+
+/// Loudness in LUFS of a mean square energy (-inf for silence).
+fn energy_to_lufs(energy: f64) -> f64 {
+    if energy <= 0.0 {
+        f64::NEG_INFINITY
+    } else {
+        -0.691 + 10.0 * energy.log10()
+    }
+}
+
+/// Gating blocks above the absolute gate, binned by loudness.
+///
+/// Each bin keeps its block count and summed energy, so gated means stay
+/// exact up to the bin holding the gate and percentiles are read to
+/// `HISTOGRAM_STEP`. Its size is fixed when the meter is built.
+#[derive(Debug, Clone)]
+struct BlockHistogram {
+    counts: Vec<u64>,
+    energies: Vec<f64>,
+}
+
+impl BlockHistogram {
+    fn new() -> Self {
+        let bins = ((HISTOGRAM_TOP - ABSOLUTE_GATE) / HISTOGRAM_STEP).round() as usize + 1;
+        Self {
+            counts: vec![0; bins],
+            energies: vec![0.0; bins],
+        }
+    }
+
+    /// Bin of a loudness above the absolute gate.
+    fn bin(&self, lufs: f64) -> usize {
+        (((lufs - ABSOLUTE_GATE) / HISTOGRAM_STEP).max(0.0) as usize).min(self.counts.len() - 1)
+    }
+
+    /// Loudness at the middle of `bin`.
+    fn level(bin: usize) -> f64 {
+        ABSOLUTE_GATE + (bin as f64 + 0.5) * HISTOGRAM_STEP
+    }
+
+    fn clear(&mut self) {
+        self.counts.fill(0);
+        self.energies.fill(0.0);
+    }
+
+    /// Record a block's mean square; blocks at or below the absolute gate
+    /// are dropped.
+    fn add(&mut self, energy: f64) {
+        let lufs = energy_to_lufs(energy);
+        if lufs > ABSOLUTE_GATE {
+            let bin = self.bin(lufs);
+            self.counts[bin] += 1;
+            self.energies[bin] += energy;
+        }
+    }
+
+    /// First bin at or above `gate` (LUFS).
+    fn first_bin(&self, gate: f64) -> usize {
+        if gate > ABSOLUTE_GATE {
+            self.bin(gate)
+        } else {
+            0
+        }
+    }
+
+    /// Mean energy and count of the blocks from the bin of `gate` up.
+    fn gated_mean(&self, gate: f64) -> Option<(f64, u64)> {
+        let from = self.first_bin(gate);
+        let count: u64 = self.counts[from..].iter().sum();
+        let sum: f64 = self.energies[from..].iter().sum();
+        (count > 0).then(|| (sum / count as f64, count))
+    }
+
+    /// Loudness of the block at `rank` (from 0) among the blocks from the
+    /// bin of `gate` up, quietest first.
+    fn level_at(&self, gate: f64, rank: u64) -> f64 {
+        let from = self.first_bin(gate);
+        let mut seen = 0;
+        for (bin, count) in self.counts.iter().enumerate().skip(from) {
+            seen += count;
+            if seen > rank {
+                return Self::level(bin);
+            }
+        }
+        Self::level(self.counts.len() - 1)
+    }
+}
+
+/// One second-order section in direct form I.
+#[derive(Debug, Clone, Copy, Default)]
+struct Section {
+    b: [f64; 3],
+    a: [f64; 2],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Section {
+    #[inline]
+    fn tick(&mut self, input: f64) -> f64 {
+        let [x1, x2] = self.x;
+        let [y1, y2] = self.y;
+        let out =
+            self.b[0] * input + self.b[1] * x1 + self.b[2] * x2 - self.a[0] * y1 - self.a[1] * y2;
+        self.x = [input, x1];
+        self.y = [out, y1];
+        out
+    }
+}
+
+/// The BS.1770 K-weighting filter (head shelf + RLB high-pass) for one
+/// channel, designed for any sample rate so that it matches the published
+/// 48 kHz coefficients.
+#[derive(Debug, Clone, Copy)]
+struct KWeighting {
+    shelf: Section,
+    highpass: Section,
+}
+
+impl KWeighting {
+    fn new(sample_rate: u32) -> Self {
+        let rate = sample_rate as f64;
+
+        let f0 = 1681.974450955533;
+        let gain_db = 3.999843853973347;
+        let q = 0.7071752369554196;
+        let k = (std::f64::consts::PI * f0 / rate).tan();
+        let vh = 10f64.powf(gain_db / 20.0);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1.0 + k / q + k * k;
+        let shelf = Section {
+            b: [
+                (vh + vb * k / q + k * k) / a0,
+                2.0 * (k * k - vh) / a0,
+                (vh - vb * k / q + k * k) / a0,
+            ],
+            a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+            ..Default::default()
+        };
+
+        let f0 = 38.13547087602444;
+        let q = 0.5003270373238773;
+        let k = (std::f64::consts::PI * f0 / rate).tan();
+        let a0 = 1.0 + k / q + k * k;
+        let highpass = Section {
+            b: [1.0, -2.0, 1.0],
+            a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+            ..Default::default()
+        };
+
+        Self { shelf, highpass }
+    }
+
+    #[inline]
+    fn tick(&mut self, input: f32) -> f64 {
+        let shelved = self.shelf.tick(input as f64);
+        self.highpass.tick(shelved)
+    }
+}
+
+/// Summary of a loudness measurement.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoudnessReport {
+    /// Gated integrated loudness (LUFS).
+    pub integrated: f64,
+    /// Loudness range (LU).
+    pub loudness_range: f64,
+    /// Maximum true peak (dBTP).
+    pub true_peak: f64,
+    /// Highest momentary loudness (LUFS).
+    pub max_momentary: f64,
+    /// Highest short-term loudness (LUFS).
+    pub max_short_term: f64,
+}
+
+impl LoudnessReport {
+    /// Gain in dB that brings the integrated loudness to `target` LUFS
+    /// (0 for silence).
+    pub fn gain_to_target(&self, target: f64) -> f64 {
+        if self.integrated.is_finite() {
+            target - self.integrated
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Streaming BS.1770 loudness meter for interleaved stereo or surround.
+///
+/// All memory is allocated when the meter is built, so it can run on the
+/// render thread for any length of program.
+#[derive(Debug, Clone)]
+pub struct LoudnessMeter {
+    sample_rate: u32,
+    layout: ChannelLayout,
+    /// K-weighting filter and BS.1770 weight per channel.
+    filters: Vec<KWeighting>,
+    weights: Vec<f64>,
+    /// One detector per channel pair.
+    true_peaks: Vec<TruePeakDetector>,
+    /// Frames per 100 ms step.
+    step_frames: usize,
+    /// Energy sum and frame count of the step in progress.
+    step_sum: f64,
+    step_count: usize,
+    /// Energy sums of the last `SHORT_TERM_STEPS` steps, newest last.
+    steps: VecDeque<f64>,
+    /// Every 400 ms gating block (75% overlap).
+    momentary_blocks: BlockHistogram,
+    /// Every 3 s short-term block, for loudness range.
+    short_term_blocks: BlockHistogram,
+    peak: f32,
+    max_momentary: f64,
+    max_short_term: f64,
+}
+
+impl LoudnessMeter {
+    /// Create a stereo meter for `sample_rate`.
+    pub fn new(sample_rate: u32) -> Self {
+        Self::with_layout(sample_rate, ChannelLayout::Stereo)
+    }
+
+    /// Create a meter for interleaved audio in `layout`.
+    pub fn with_layout(sample_rate: u32, layout: ChannelLayout) -> Self {
+        let speakers = layout.speakers();
+        Self {
+            sample_rate,
+            layout,
+            filters: vec![KWeighting::new(sample_rate); speakers.len()],
+            weights: speakers.iter().map(|s| s.loudness_weight()).collect(),
+            true_peaks: vec![TruePeakDetector::new(); speakers.len().div_ceil(2)],
+            step_frames: (sample_rate as usize / 10).max(1),
+            step_sum: 0.0,
+            step_count: 0,
+            steps: VecDeque::with_capacity(SHORT_TERM_STEPS),
+            momentary_blocks: BlockHistogram::new(),
+            short_term_blocks: BlockHistogram::new(),
+            peak: 0.0,
+            max_momentary: f64::NEG_INFINITY,
+            max_short_term: f64::NEG_INFINITY,
+        }
+    }
+
+    /// Sample rate the meter was built for.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Channel layout the meter reads.
+    pub fn layout(&self) -> ChannelLayout {
+        self.layout
+    }
+
+    /// Clear all measurements, keeping the allocated history.
+    pub fn reset(&mut self) {
+        self.filters.fill(KWeighting::new(self.sample_rate));
+        self.true_peaks.fill(TruePeakDetector::new());
+        self.step_sum = 0.0;
+        self.step_count = 0;
+        self.steps.clear();
+        self.momentary_blocks.clear();
+        self.short_term_blocks.clear();
+        self.peak = 0.0;
+        self.max_momentary = f64::NEG_INFINITY;
+        self.max_short_term = f64::NEG_INFINITY;
+    }
+
+    /// Feed interleaved samples in the meter's layout.
+    pub fn process(&mut self, buffer: &[f32]) {
+        for frame in buffer.chunks_exact(self.filters.len()) {
+            let mut energy = 0.0;
+            for ((sample, filter), weight) in frame.iter().zip(&mut self.filters).zip(&self.weights)
+            {
+                let y = filter.tick(*sample);
+                energy += weight * y * y;
+            }
+            self.step_sum += energy;
+            for (pair, detector) in frame.chunks(2).zip(&mut self.true_peaks) {
+                let right = pair.get(1).copied().unwrap_or(0.0);
+                self.peak = self.peak.max(detector.push(pair[0], right));
+            }
+            self.step_count += 1;
+            if self.step_count == self.step_frames {
+                self.finish_step();
+            }
+        }
+    }
+
+    fn finish_step(&mut self) {
+        if self.steps.len() == SHORT_TERM_STEPS {
+            self.steps.pop_front();
+        }
+        self.steps.push_back(self.step_sum);
+        self.step_sum = 0.0;
+        self.step_count = 0;
+
+        if let Some(energy) = self.window_energy(MOMENTARY_STEPS) {
+            self.momentary_blocks.add(energy);
+            self.max_momentary = self.max_momentary.max(energy_to_lufs(energy));
+        }
+        if let Some(energy) = self.window_energy(SHORT_TERM_STEPS) {
+            self.short_term_blocks.add(energy);
+            self.max_short_term = self.max_short_term.max(energy_to_lufs(energy));
+        }
+    }
+
+    /// Mean square over the last `steps` steps, once that many exist.
+    fn window_energy(&self, steps: usize) -> Option<f64> {
+        (self.steps.len() >= steps).then(|| {
+            let sum: f64 = self.steps.iter().rev().take(steps).sum();
+            sum / (steps * self.step_frames) as f64
+        })
+    }
+
+    /// Momentary loudness over the last 400 ms (LUFS).
+    pub fn momentary(&self) -> f64 {
+        self.window_energy(MOMENTARY_STEPS)
+            .map_or(f64::NEG_INFINITY, energy_to_lufs)
+    }
+
+    /// Short-term loudness over the last 3 s (LUFS).
+    pub fn short_term(&self) -> f64 {
+        self.window_energy(SHORT_TERM_STEPS)
+            .map_or(f64::NEG_INFINITY, energy_to_lufs)
+    }
+
+    /// Gated integrated loudness of everything processed so far (LUFS).
+    pub fn integrated(&self) -> f64 {
+        let Some((ungated, _)) = self.momentary_blocks.gated_mean(ABSOLUTE_GATE) else {
+            return f64::NEG_INFINITY;
+        };
+        let relative = energy_to_lufs(ungated) + INTEGRATED_RELATIVE_GATE;
+        self.momentary_blocks
+            .gated_mean(relative)
+            .map_or(f64::NEG_INFINITY, |(energy, _)| energy_to_lufs(energy))
+    }
+
+    /// Loudness range per EBU Tech 3342 (LU): the spread between the 10th
+    /// and 95th percentile of gated short-term loudness.
+    pub fn loudness_range(&self) -> f64 {
+        let Some((ungated, _)) = self.short_term_blocks.gated_mean(ABSOLUTE_GATE) else {
+            return 0.0;
+        };
+        let relative = energy_to_lufs(ungated) + RANGE_RELATIVE_GATE;
+        let Some((_, count)) = self.short_term_blocks.gated_mean(relative) else {
+            return 0.0;
+        };
+        let percentile = |p: f64| {
+            let rank = ((count - 1) as f64 * p).round() as u64;
+            self.short_term_blocks.level_at(relative, rank)
+        };
+        percentile(0.95) - percentile(0.10)
+    }
+
+    /// Maximum true peak so far (dBTP).
+    pub fn true_peak(&self) -> f64 {
+        if self.peak > 0.0 {
+            20.0 * (self.peak as f64).log10()
+        } else {
+            f64::NEG_INFINITY
+        }
+    }
+
+    /// All measurements so far.
+    pub fn report(&self) -> LoudnessReport {
+        LoudnessReport {
+            integrated: self.integrated(),
+            loudness_range: self.loudness_range(),
+            true_peak: self.true_peak(),
+            max_momentary: self.max_momentary,
+            max_short_term: self.max_short_term,
+        }
+    }
+}
+
+/// Measure a range of a sequence's mix offline.
+///
+/// Renders through `mixer` block by block, so memory stays constant for
+/// long programs.
+pub fn measure_range(
+    renderer: &mut TimelineRenderer,
+    sequence: &Sequence,
+    mixer: &mut Mixer,
+    range: TimeRange,
+) -> LoudnessReport {
+    let mut meter = LoudnessMeter::with_layout(renderer.sample_rate(), mixer.layout());
+    renderer.render_range_with(sequence, mixer, range, |block| meter.process(block));
+    meter.report()
+}
+
+/// Measure one clip on its own (with its gain, fades and automation).
+pub fn measure_clip(renderer: &mut TimelineRenderer, clip: &Clip) -> LoudnessReport {
+    let mut meter = LoudnessMeter::new(renderer.sample_rate());
+    meter.process(&renderer.render_clip(clip));
+    meter.report()
+}
+
+/// The edit that sets the gain of the clip at `clip_index` on `track` so
+/// it measures `target` LUFS (no change for a silent clip). `None` if the
+/// item there is not a clip.
+pub fn normalize_clip(
+    renderer: &mut TimelineRenderer,
+    track: &Track,
+    clip_index: usize,
+    target: f64,
+) -> Option<EditCommand> {
+    let before = track.clip_at(clip_index)?;
+    let change = measure_clip(renderer, before).gain_to_target(target);
+    let mut after = before.clone();
+    after.audio.gain_db += change as f32;
+    Some(EditCommand::UpdateClip {
+        track_id: track.id,
+        clip_index,
+        before: Box::new(before.clone()),
+        after: Box::new(after),
+    })
+}
+
+/// The edit that sets the sequence's master gain so the mix of `range`
+/// measures `target` LUFS (no change for a silent mix).
+pub fn normalize_master(
+    renderer: &mut TimelineRenderer,
+    sequence: &Sequence,
+    mixer: &mut Mixer,
+    range: TimeRange,
+    target: f64,
+) -> EditCommand {
+    let change = measure_range(renderer, sequence, mixer, range).gain_to_target(target);
+    EditCommand::SetMasterGain {
+        before: sequence.master_gain_db,
+        after: sequence.master_gain_db + change as f32,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 48000;
+
+    /// Interleaved stereo sine at `dbfs` peak on both channels.
+    fn sine(freq: f64, dbfs: f64, seconds: f64) -> Vec<f32> {
+        let amplitude = 10f64.powf(dbfs / 20.0);
+        let frames = (seconds * RATE as f64) as usize;
+        (0..frames)
+            .flat_map(|n| {
+                let s = (amplitude
+                    * (2.0 * std::f64::consts::PI * freq * n as f64 / RATE as f64).sin())
+                    as f32;
+                [s, s]
+            })
+            .collect()
+    }
+
+    fn measure(signal: &[f32]) -> LoudnessMeter {
+        let mut meter = LoudnessMeter::new(RATE);
+        meter.process(signal);
+        meter
+    }
+
+    #[test]
+    fn test_reference_tone() {
+        // EBU Tech 3341: a 1 kHz sine at -23 dBFS on both channels of a
+        // stereo signal measures -23 LUFS.
+        let meter = measure(&sine(1000.0, -23.0, 20.0));
+        assert!((meter.integrated() + 23.0).abs() < 0.1);
+        assert!((meter.momentary() + 23.0).abs() < 0.1);
+        assert!((meter.short_term() + 23.0).abs() < 0.1);
+        assert!(meter.loudness_range() < 0.1);
+    }
+
+    #[test]
+    fn test_k_weighting_at_44100() {
+        let mut meter = LoudnessMeter::new(44100);
+        let amplitude = 10f64.powf(-23.0 / 20.0);
+        let signal: Vec<f32> = (0..44100 * 10)
+            .flat_map(|n| {
+                let s = (amplitude
+                    * (2.0 * std::f64::consts::PI * 1000.0 * n as f64 / 44100.0).sin())
+                    as f32;
+                [s, s]
+            })
+            .collect();
+        meter.process(&signal);
+        assert!((meter.integrated() + 23.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_gating_ignores_silence() {
+        let mut signal = sine(1000.0, -23.0, 10.0);
+        signal.extend(vec![0.0; RATE as usize * 2 * 10]);
+        let meter = measure(&signal);
+        assert!((meter.integrated() + 23.0).abs() < 0.1);
+        assert_eq!(measure(&vec![0.0; 48000]).integrated(), f64::NEG_INFINITY);
+    }
+
+    #[test]
+    fn test_loudness_range() {
+        // EBU Tech 3342 case 1: 20 s at -20 LUFS then 20 s at -30 LUFS has
+        // an LRA of 10 LU.
+        let mut signal = sine(1000.0, -20.0, 20.0);
+        signal.extend(sine(1000.0, -30.0, 20.0));
+        let lra = measure(&signal).loudness_range();
+        assert!((lra - 10.0).abs() < 0.2, "LRA {lra}");
+    }
+
+    #[test]
+    fn test_reset_starts_a_new_measurement() {
+        let mut meter = measure(&sine(1000.0, -14.0, 10.0));
+        meter.reset();
+        meter.process(&sine(1000.0, -30.0, 10.0));
+        assert!((meter.integrated() + 30.0).abs() < 0.1);
+        assert!((meter.true_peak() + 30.0).abs() < 0.3);
+        assert!(meter.loudness_range() < 0.1);
+    }
+
+    #[test]
+    fn test_true_peak() {
+        // A quarter-rate sine sampled at 45 degrees peaks between samples.
+        let amplitude = 0.5f32;
+        let signal: Vec<f32> = (0..48000)
+            .flat_map(|n| {
+                let phase = std::f32::consts::FRAC_PI_2 * n as f32 + std::f32::consts::FRAC_PI_4;
+                let s = amplitude * phase.sin();
+                [s, s]
+            })
+            .collect();
+        let sample_peak = signal.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+        let meter = measure(&signal);
+        assert!(sample_peak < 0.36);
+        assert!((meter.true_peak() - 20.0 * 0.5f64.log10()).abs() < 0.3);
+    }
+
+    #[test]
+    fn test_gain_to_target() {
+        let report = measure(&sine(1000.0, -30.0, 5.0)).report();
+        assert!((report.gain_to_target(-23.0) - 7.0).abs() < 0.1);
+        let silent = measure(&[0.0; 960]).report();
+        assert_eq!(silent.gain_to_target(-23.0), 0.0);
+    }
+}
@@ -1,8 +1,23 @@
-//! Audio mixer — mixes multiple tracks into a stereo output buffer.
-
+//! Audio mixer — mixes multiple tracks through submix buses into a
+//! stereo or surround master.
+
+use crate::bus::{Bus, ChannelLayout, Route};
+use crate::effect::EffectChain;
+use crate::effects::db_to_gain;
+use crate::loudness::LoudnessMeter;
+use crate::render::sample_to_time;
 use crate::ring_buffer::RingBuffer;
+use crossbeam_channel::{Receiver, Sender};
+use proedit_core::RationalTime;
+use proedit_timeline::{Sequence, TrackAutomation};
 use std::sync::Arc;
 
+/// Longest insert latency difference the mixer compensates (ms).
+const MAX_COMPENSATION_MS: usize = 50;
+
+/// Replaced chains that can wait to be dropped off the render thread.
+const RETIRED_CAPACITY: usize = 64;
+
 /// Per-track mixer channel configuration.
 #[derive(Debug, Clone)]
 pub struct MixerChannel {
@@ -10,10 +25,19 @@ pub struct MixerChannel {
     pub volume: f32,
     /// Pan (-1.0 = full left, 0.0 = center, 1.0 = full right).
     pub pan: f32,
+    /// Surround front/back position (1.0 = front, -1.0 = rear); only
+    /// used with a surround layout.
+    pub pan_depth: f32,
+    /// LFE send (0.0 to 1.0); only used with a surround layout.
+    pub lfe: f32,
     /// Whether this channel is muted.
     pub muted: bool,
     /// Whether this channel is soloed.
     pub solo: bool,
+    /// Volume (dB, scales `volume`) and pan (replaces `pan`) automation.
+    pub automation: TrackAutomation,
+    /// Destination bus.
+    pub output: Route,
 }
 
 impl Default for MixerChannel {
@@ -21,8 +45,12 @@ impl Default for MixerChannel {
         Self {
             volume: 1.0,
             pan: 0.0,
+            pan_depth: 1.0,
+            lfe: 0.0,
             muted: false,
             solo: false,
+            automation: TrackAutomation::default(),
+            output: Route::Master,
         }
     }
 }
@@ -33,40 +61,244 @@ impl MixerChannel {
         if self.muted {
             return (0.0, 0.0);
         }
-        // Constant-power panning: use sin/cos curve
-        let angle = (self.pan + 1.0) * 0.25 * std::f32::consts::PI;
-        let left = self.volume * angle.cos();
-        let right = self.volume * angle.sin();
-        (left, right)
+        pan_gain(self.volume, self.pan)
+    }
+
+    /// Left/right gain with automation evaluated at sequence time `time`.
+    pub fn automated_gain(&self, time: RationalTime) -> (f32, f32) {
+        if self.muted {
+            return (0.0, 0.0);
+        }
+        let (volume, pan) = self.automated_volume_pan(time);
+        pan_gain(volume, pan)
+    }
+
+    /// Per-speaker gains of the (mono-folded) channel in a surround
+    /// `layout`, with automation evaluated at `time`.
+    pub fn surround_gains(&self, layout: ChannelLayout, time: RationalTime, gains: &mut [f32]) {
+        let (volume, pan) = self.automated_volume_pan(time);
+        layout.pan_gains(pan, self.pan_depth, gains);
+        for (gain, speaker) in gains.iter_mut().zip(layout.speakers()) {
+            *gain = if self.muted {
+                0.0
+            } else if speaker.azimuth().is_none() {
+                volume * self.lfe
+            } else {
+                volume * *gain
+            };
+        }
+    }
+
+    fn automated_volume_pan(&self, time: RationalTime) -> (f32, f32) {
+        let volume = match &self.automation.volume {
+            Some(track) => self.volume * db_to_gain(track.evaluate(time) as f32),
+            None => self.volume,
+        };
+        let pan = match &self.automation.pan {
+            Some(track) => (track.evaluate(time) as f32).clamp(-1.0, 1.0),
+            None => self.pan,
+        };
+        (volume, pan)
+    }
+}
+
+/// Stereo delay that holds a channel back by the difference between its
+/// insert latency and the slowest channel's. Sized when the mixer is built
+/// or its sample rate changes, so delaying never allocates.
+#[derive(Debug, Clone)]
+struct DelayLine {
+    buffer: Vec<f32>,
+    pos: usize,
+}
+
+impl DelayLine {
+    fn new(sample_rate: u32) -> Self {
+        let frames = (sample_rate as usize * MAX_COMPENSATION_MS / 1000).max(1);
+        Self {
+            buffer: vec![0.0; frames * 2],
+            pos: 0,
+        }
+    }
+
+    fn clear(&mut self) {
+        self.buffer.fill(0.0);
+    }
+
+    /// Delay interleaved stereo `block` in place by `delay` frames (at
+    /// most the line's length).
+    fn process(&mut self, block: &mut [f32], delay: usize) {
+        let frames = self.buffer.len() / 2;
+        let delay = delay.min(frames - 1);
+        for frame in block.chunks_exact_mut(2) {
+            let write = self.pos * 2;
+            self.buffer[write..write + 2].copy_from_slice(frame);
+            let read = (self.pos + frames - delay) % frames * 2;
+            frame.copy_from_slice(&self.buffer[read..read + 2]);
+            self.pos = (self.pos + 1) % frames;
+        }
+    }
+}
+
+/// An insert chain built off the render thread, with the settings it was
+/// built from (`channel` is `None` for the master).
+struct InsertUpdate {
+    channel: Option<usize>,
+    saved: Vec<serde_json::Value>,
+    chain: EffectChain,
+}
+
+/// A chain the mixer replaced, with its settings, to be dropped by the
+/// sender.
+type Retired = (EffectChain, Vec<serde_json::Value>);
+
+/// Hands insert chains to a [`Mixer`] without locking it.
+///
+/// Chains are built on the caller's thread from the settings saved on a
+/// sequence and swapped in by the mixer at the start of its next block;
+/// the chains they replace come back here to be dropped.
+pub struct InsertSender {
+    updates: Sender<InsertUpdate>,
+    retired: Receiver<Retired>,
+    sample_rate: u32,
+    /// Settings last sent per channel, and for the master.
+    sent: Vec<Vec<serde_json::Value>>,
+    sent_master: Vec<serde_json::Value>,
+}
+
+impl InsertSender {
+    /// Send chains for the audio tracks (track `i` on channel `i`) and
+    /// master of `sequence` whose inserts changed since the last call.
+    pub fn sync(&mut self, sequence: &Sequence) {
+        self.retired.try_iter().for_each(drop);
+        if self.sent.len() < sequence.audio_tracks.len() {
+            self.sent.resize(sequence.audio_tracks.len(), Vec::new());
+        }
+        for (index, track) in sequence.audio_tracks.iter().enumerate() {
+            if self.sent[index] != track.inserts {
+                self.send(Some(index), &track.inserts);
+                self.sent[index].clone_from(&track.inserts);
+            }
+        }
+        if self.sent_master != sequence.master_inserts {
+            self.send(None, &sequence.master_inserts);
+            self.sent_master.clone_from(&sequence.master_inserts);
+        }
+    }
+
+    fn send(&self, channel: Option<usize>, saved: &[serde_json::Value]) {
+        let _ = self.updates.send(InsertUpdate {
+            channel,
+            saved: saved.to_vec(),
+            chain: EffectChain::from_saved(saved, self.sample_rate),
+        });
     }
 }
 
-/// Audio mixer that combines multiple channels into stereo output.
+/// Constant-power panning: use sin/cos curve
+fn pan_gain(volume: f32, pan: f32) -> (f32, f32) {
+    let angle = (pan + 1.0) * 0.25 * std::f32::consts::PI;
+    (volume * angle.cos(), volume * angle.sin())
+}
+
+/// Audio mixer that combines multiple channels into a stereo or surround
+/// master.
+///
+/// Channels route to the master or to a submix bus; buses sum into the
+/// master. Each channel and the master have an insert `EffectChain`.
+/// Channel inserts run before volume and pan; master inserts run after
+/// the master volume. Inserts are stereo, so with a surround layout the
+/// master inserts are bypassed and channels are folded to mono before
+/// panning. Channels whose inserts add less latency than the slowest
+/// chain are delayed to line up with it. The optional loudness meter reads
+/// the final output.
+///
+/// Chains can be handed over from another thread through an
+/// [`InsertSender`], so changing inserts never waits on a block being
+/// mixed.
 pub struct Mixer {
     /// Per-track channels.
     channels: Vec<MixerChannel>,
+    /// Insert chain per channel (same order as `channels`).
+    inserts: Vec<EffectChain>,
+    /// Timeline insert settings each channel chain was last built from.
+    saved_inserts: Vec<Vec<serde_json::Value>>,
+    /// Timeline insert settings the master chain was last built from.
+    saved_master_inserts: Vec<serde_json::Value>,
+    /// Latency compensation per channel (same order as `channels`).
+    compensation: Vec<DelayLine>,
+    /// Chains handed over by `InsertSender`s, and the ones they replaced.
+    updates: Receiver<InsertUpdate>,
+    retired: Sender<Retired>,
+    /// The other ends, cloned into each `InsertSender`.
+    update_sender: Sender<InsertUpdate>,
+    retired_receiver: Receiver<Retired>,
+    /// Submix buses.
+    buses: Vec<Bus>,
+    /// Post-fader signal of each bus for the last block.
+    bus_buffers: Vec<Vec<f32>>,
+    /// Samples of the last block in each bus buffer.
+    bus_len: usize,
+    /// Layout of the buses and the master.
+    layout: ChannelLayout,
+    /// Insert chain on the master bus.
+    pub master_inserts: EffectChain,
     /// Master volume.
     pub master_volume: f32,
+    /// The sequence's master gain, linear.
+    master_gain: f32,
     /// Master limiter enabled.
     pub limiter_enabled: bool,
     /// Limiter threshold in linear amplitude.
     pub limiter_threshold: f32,
+    /// Loudness meter on the final output, when metering is enabled.
+    pub meter: Option<LoudnessMeter>,
     /// Output ring buffer for the audio callback.
     pub output_buffer: Arc<RingBuffer>,
     /// Scratch buffer for mixing.
     scratch: Vec<f32>,
+    /// Scratch buffer for one channel's inserts.
+    channel_scratch: Vec<f32>,
+    /// Stereo fold-down of a surround block for the ring buffer.
+    monitor_scratch: Vec<f32>,
+    sample_rate: u32,
+    /// Sample index of the next block (drives automation).
+    position: i64,
 }
 
 impl Mixer {
     /// Create a new mixer with the given number of channels.
     pub fn new(num_channels: usize, buffer_size: usize) -> Self {
+        let sample_rate = 48000;
+        let (update_sender, updates) = crossbeam_channel::unbounded();
+        let (retired, retired_receiver) = crossbeam_channel::bounded(RETIRED_CAPACITY);
         Self {
             channels: (0..num_channels).map(|_| MixerChannel::default()).collect(),
+            inserts: (0..num_channels)
+                .map(|_| EffectChain::new(sample_rate))
+                .collect(),
+            saved_inserts: vec![Vec::new(); num_channels],
+            saved_master_inserts: Vec::new(),
+            compensation: vec![DelayLine::new(sample_rate); num_channels],
+            updates,
+            retired,
+            update_sender,
+            retired_receiver,
+            buses: Vec::new(),
+            bus_buffers: Vec::new(),
+            bus_len: 0,
+            layout: ChannelLayout::Stereo,
+            master_inserts: EffectChain::new(sample_rate),
             master_volume: 1.0,
+            master_gain: 1.0,
             limiter_enabled: false,
             limiter_threshold: 0.95,
+            meter: None,
             output_buffer: Arc::new(RingBuffer::new(buffer_size)),
             scratch: vec![0.0; 4096],
+            channel_scratch: vec![0.0; 4096],
+            monitor_scratch: Vec::new(),
+            sample_rate,
+            position: 0,
         }
     }
 
@@ -80,6 +312,16 @@ impl Mixer {
         self.channels.get_mut(index)
     }
 
+    /// Insert chain of a channel.
+    pub fn inserts(&self, index: usize) -> Option<&EffectChain> {
+        self.inserts.get(index)
+    }
+
+    /// Mutable insert chain of a channel.
+    pub fn inserts_mut(&mut self, index: usize) -> Option<&mut EffectChain> {
+        self.inserts.get_mut(index)
+    }
+
     /// Number of mixer channels.
     pub fn channel_count(&self) -> usize {
         self.channels.len()
@@ -89,9 +331,209 @@ impl Mixer {
     pub fn add_channel(&mut self) -> usize {
         let idx = self.channels.len();
         self.channels.push(MixerChannel::default());
+        self.inserts.push(EffectChain::new(self.sample_rate));
+        self.saved_inserts.push(Vec::new());
+        self.compensation.push(DelayLine::new(self.sample_rate));
         idx
     }
 
+    /// Submix buses.
+    pub fn buses(&self) -> &[Bus] {
+        &self.buses
+    }
+
+    /// Mutable bus settings.
+    pub fn bus_mut(&mut self, index: usize) -> Option<&mut Bus> {
+        self.buses.get_mut(index)
+    }
+
+    /// Add a submix bus.
+    pub fn add_bus(&mut self, bus: Bus) -> usize {
+        self.buses.push(bus);
+        self.bus_buffers.push(Vec::new());
+        self.buses.len() - 1
+    }
+
+    /// Remove a bus. Channels routed to it go to the master; routes to
+    /// later buses are renumbered.
+    pub fn remove_bus(&mut self, index: usize) -> Option<Bus> {
+        if index >= self.buses.len() {
+            return None;
+        }
+        for channel in &mut self.channels {
+            channel.output = match channel.output {
+                Route::Bus(i) if i == index => Route::Master,
+                Route::Bus(i) if i > index => Route::Bus(i - 1),
+                route => route,
+            };
+        }
+        self.bus_buffers.remove(index);
+        Some(self.buses.remove(index))
+    }
+
+    /// Post-fader output of a bus for the last mixed block, interleaved in
+    /// the mixer's layout (used to render stems).
+    pub fn bus_output(&self, index: usize) -> Option<&[f32]> {
+        self.bus_buffers
+            .get(index)
+            .map(|buffer| &buffer[..self.bus_len.min(buffer.len())])
+    }
+
+    /// Layout of the buses and master.
+    pub fn layout(&self) -> ChannelLayout {
+        self.layout
+    }
+
+    /// Change the bus and master layout. `mix_into` then writes
+    /// `layout.channel_count()` interleaved channels per frame.
+    pub fn set_layout(&mut self, layout: ChannelLayout) {
+        self.layout = layout;
+        if self.meter.is_some() {
+            self.meter = Some(LoudnessMeter::with_layout(self.sample_rate, layout));
+        }
+    }
+
+    /// Sample rate the effects are prepared for.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Prepare all insert effects for a new sample rate.
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate;
+        for chain in &mut self.inserts {
+            chain.set_sample_rate(sample_rate);
+        }
+        self.master_inserts.set_sample_rate(sample_rate);
+        self.compensation = vec![DelayLine::new(sample_rate); self.channels.len()];
+        if self.meter.is_some() {
+            self.meter = Some(LoudnessMeter::with_layout(sample_rate, self.layout));
+        }
+    }
+
+    /// Copy of the channel settings, inserts (with fresh state) and master
+    /// bus, for rendering offline without disturbing playback. The copy
+    /// has its own output buffer and no meter.
+    pub fn offline_copy(&self) -> Self {
+        let mut copy = Self::new(0, 4096);
+        copy.sample_rate = self.sample_rate;
+        copy.channels = self.channels.clone();
+        copy.inserts = self
+            .inserts
+            .iter()
+            .map(|chain| EffectChain::from_settings(&chain.settings(), self.sample_rate))
+            .collect();
+        copy.saved_inserts = self.saved_inserts.clone();
+        copy.compensation = vec![DelayLine::new(self.sample_rate); self.channels.len()];
+        copy.master_inserts =
+            EffectChain::from_settings(&self.master_inserts.settings(), self.sample_rate);
+        copy.saved_master_inserts = self.saved_master_inserts.clone();
+        copy.buses = self.buses.clone();
+        copy.bus_buffers = vec![Vec::new(); self.buses.len()];
+        copy.layout = self.layout;
+        copy.master_volume = self.master_volume;
+        copy.master_gain = self.master_gain;
+        copy.limiter_enabled = self.limiter_enabled;
+        copy.limiter_threshold = self.limiter_threshold;
+        copy
+    }
+
+    /// Set the sample index of the next block. A jump (seek) resets effect
+    /// state so filter tails from the old position are not heard.
+    pub fn set_position(&mut self, position: i64) {
+        if position != self.position {
+            for chain in &mut self.inserts {
+                chain.reset();
+            }
+            for delay in &mut self.compensation {
+                delay.clear();
+            }
+            self.master_inserts.reset();
+            self.position = position;
+        }
+    }
+
+    /// Replace a channel's automation if it differs (no allocation when
+    /// unchanged, so this can run every block).
+    pub fn set_automation(&mut self, index: usize, automation: &TrackAutomation) {
+        if let Some(channel) = self.channels.get_mut(index) {
+            if channel.automation != *automation {
+                channel.automation.clone_from(automation);
+            }
+        }
+    }
+
+    /// Rebuild a channel's inserts from the settings saved on its timeline
+    /// track when they differ from the last ones applied (no allocation
+    /// when unchanged, so this can run every block).
+    pub fn set_inserts(&mut self, index: usize, saved: &[serde_json::Value]) {
+        if let (Some(chain), Some(applied)) = (
+            self.inserts.get_mut(index),
+            self.saved_inserts.get_mut(index),
+        ) {
+            if applied.as_slice() != saved {
+                *chain = EffectChain::from_saved(saved, self.sample_rate);
+                *applied = saved.to_vec();
+            }
+        }
+    }
+
+    /// Rebuild the master inserts from the settings saved on the sequence
+    /// when they differ from the last ones applied.
+    pub fn set_master_inserts(&mut self, saved: &[serde_json::Value]) {
+        if self.saved_master_inserts.as_slice() != saved {
+            self.master_inserts = EffectChain::from_saved(saved, self.sample_rate);
+            self.saved_master_inserts = saved.to_vec();
+        }
+    }
+
+    /// A sender that hands this mixer insert chains built on another
+    /// thread, starting from the settings applied so far.
+    pub fn insert_sender(&self) -> InsertSender {
+        InsertSender {
+            updates: self.update_sender.clone(),
+            retired: self.retired_receiver.clone(),
+            sample_rate: self.sample_rate,
+            sent: self.saved_inserts.clone(),
+            sent_master: self.saved_master_inserts.clone(),
+        }
+    }
+
+    /// Swap in the chains handed over by `InsertSender`s. The replaced
+    /// chains go back to be dropped off this thread.
+    pub(crate) fn receive_inserts(&mut self) {
+        while let Ok(InsertUpdate {
+            channel,
+            saved,
+            mut chain,
+        }) = self.updates.try_recv()
+        {
+            let (slot, applied) = match channel {
+                Some(index) => match (
+                    self.inserts.get_mut(index),
+                    self.saved_inserts.get_mut(index),
+                ) {
+                    (Some(slot), Some(applied)) => (slot, applied),
+                    _ => continue,
+                },
+                None => (&mut self.master_inserts, &mut self.saved_master_inserts),
+            };
+            if chain.sample_rate() != self.sample_rate {
+                chain.set_sample_rate(self.sample_rate);
+            }
+            let old = (
+                std::mem::replace(slot, chain),
+                std::mem::replace(applied, saved),
+            );
+            let _ = self.retired.try_send(old);
+        }
+    }
+
+    /// Apply the master gain saved on the sequence (dB).
+    pub fn set_master_gain_db(&mut self, gain_db: f32) {
+        self.master_gain = 10f32.powf(gain_db / 20.0);
+    }
+
     /// Check if any channel is soloed.
     fn any_solo(&self) -> bool {
         self.channels.iter().any(|c| c.solo)
@@ -101,24 +543,65 @@ impl Mixer {
     /// the scratch buffer and write to the output ring buffer.
     ///
     /// `sources` is a slice of interleaved stereo f32 buffers (one per channel).
-    /// Each buffer must have exactly `frame_count * 2` samples.
-    pub fn mix(&mut self, sources: &[&[f32]], frame_count: usize) {
-        let output_len = frame_count * 2;
+    /// Each buffer must have exactly `frame_count * 2` samples. The ring
+    /// buffer is always stereo: a surround mix is folded down for
+    /// monitoring.
+    pub fn mix<S: AsRef<[f32]>>(&mut self, sources: &[S], frame_count: usize) {
+        let output_len = frame_count * self.layout.channel_count();
         if self.scratch.len() < output_len {
             self.scratch.resize(output_len, 0.0);
         }
+        let mut scratch = std::mem::take(&mut self.scratch);
+        self.mix_into(sources, frame_count, &mut scratch[..output_len]);
+        if self.layout.is_surround() {
+            self.monitor_scratch.resize(frame_count * 2, 0.0);
+            self.layout
+                .downmix_stereo(&scratch[..output_len], &mut self.monitor_scratch);
+            self.output_buffer.write(&self.monitor_scratch);
+        } else {
+            self.output_buffer.write(&scratch[..output_len]);
+        }
+        self.scratch = scratch;
+    }
 
-        // Zero the scratch buffer
-        for s in self.scratch[..output_len].iter_mut() {
-            *s = 0.0;
+    /// Mix like [`mix`](Self::mix), but into `output` instead of the ring
+    /// buffer (used for offline rendering).
+    ///
+    /// `output` is interleaved in the mixer's layout and must hold at
+    /// least `frame_count * layout.channel_count()` samples. Does not
+    /// allocate unless the block is larger than any before it.
+    pub fn mix_into<S: AsRef<[f32]>>(
+        &mut self,
+        sources: &[S],
+        frame_count: usize,
+        output: &mut [f32],
+    ) {
+        let channels = self.layout.channel_count();
+        let len = frame_count * channels;
+        let stereo_len = frame_count * 2;
+        let output = &mut output[..len];
+        output.fill(0.0);
+        if self.channel_scratch.len() < stereo_len {
+            self.channel_scratch.resize(stereo_len, 0.0);
         }
+        for buffer in &mut self.bus_buffers {
+            buffer.resize(len, 0.0);
+            buffer.fill(0.0);
+        }
+        self.bus_len = len;
 
         let has_solo = self.any_solo();
+        let max_latency = self.inserts.iter().map(EffectChain::latency).max();
+        let max_latency = max_latency.unwrap_or(0);
+        let time = sample_to_time(self.position, self.sample_rate);
+        let end_time = sample_to_time(self.position + frame_count as i64, self.sample_rate);
 
         for (ch_idx, source) in sources.iter().enumerate() {
-            let channel = match self.channels.get(ch_idx) {
-                Some(c) => c,
-                None => continue,
+            let source = source.as_ref();
+            let (Some(channel), Some(inserts)) =
+                (self.channels.get(ch_idx), self.inserts.get_mut(ch_idx))
+            else {
+                continue;
             };
 
             // If any track is soloed, only play soloed tracks
@@ -126,36 +609,98 @@ impl Mixer {
                 continue;
             }
 
-            let (gain_l, gain_r) = channel.stereo_gain();
+            // Automation is evaluated per block and ramped across it.
+            let mut start_gains = [0.0f32; 8];
+            let mut end_gains = [0.0f32; 8];
+            if self.layout.is_surround() {
+                channel.surround_gains(self.layout, time, &mut start_gains);
+                channel.surround_gains(self.layout, end_time, &mut end_gains);
+            } else {
+                (start_gains[0], start_gains[1]) = channel.automated_gain(time);
+                (end_gains[0], end_gains[1]) = channel.automated_gain(end_time);
+            }
+            if start_gains.iter().chain(&end_gains).all(|g| *g == 0.0) {
+                continue;
+            }
+            let mut steps = [0.0f32; 8];
+            for (step, (start, end)) in steps.iter_mut().zip(start_gains.iter().zip(&end_gains)) {
+                *step = (end - start) / frame_count as f32;
+            }
 
-            for frame in 0..frame_count {
-                let src_idx = frame * 2;
-                if src_idx + 1 >= source.len() {
-                    break;
+            let buffer = &mut self.channel_scratch[..stereo_len];
+            let copied = source.len().min(stereo_len) & !1;
+            buffer[..copied].copy_from_slice(&source[..copied]);
+            buffer[copied..].fill(0.0);
+            if !inserts.is_empty() {
+                inserts.process(buffer, sources, time);
+            }
+            if max_latency > 0 {
+                if let Some(delay) = self.compensation.get_mut(ch_idx) {
+                    delay.process(buffer, max_latency - inserts.latency());
                 }
-                let sl = source[src_idx];
-                let sr = source[src_idx + 1];
+            }
 
-                self.scratch[src_idx] += sl * gain_l;
-                self.scratch[src_idx + 1] += sr * gain_r;
+            let dest = match channel.output {
+                Route::Bus(index) if index < self.bus_buffers.len() => {
+                    &mut self.bus_buffers[index][..len]
+                }
+                _ => &mut *output,
+            };
+            if self.layout.is_surround() {
+                for (n, (out, frame)) in dest
+                    .chunks_exact_mut(channels)
+                    .zip(buffer.chunks_exact(2))
+                    .enumerate()
+                {
+                    let mono = (frame[0] + frame[1]) * 0.5;
+                    for (k, sample) in out.iter_mut().enumerate() {
+                        *sample += mono * (start_gains[k] + steps[k] * n as f32);
+                    }
+                }
+            } else {
+                for (n, (out, frame)) in dest
+                    .chunks_exact_mut(2)
+                    .zip(buffer.chunks_exact(2))
+                    .enumerate()
+                {
+                    out[0] += frame[0] * (start_gains[0] + steps[0] * n as f32);
+                    out[1] += frame[1] * (start_gains[1] + steps[1] * n as f32);
+                }
+            }
+        }
+
+        // Buses sum into the master post-fader.
+        for (bus, buffer) in self.buses.iter().zip(&mut self.bus_buffers) {
+            let gain = if bus.muted { 0.0 } else { bus.volume };
+            for (sample, out) in buffer.iter_mut().zip(output.iter_mut()) {
+                *sample *= gain;
+                *out += *sample;
             }
         }
 
         // Apply master volume
-        for s in self.scratch[..output_len].iter_mut() {
-            *s *= self.master_volume;
+        let master = self.master_volume * self.master_gain;
+        for s in output.iter_mut() {
+            *s *= master;
+        }
+
+        if !self.master_inserts.is_empty() && !self.layout.is_surround() {
+            self.master_inserts.process(output, &[] as &[&[f32]], time);
         }
 
         // Apply limiter (simple hard clamp)
         if self.limiter_enabled {
             let threshold = self.limiter_threshold;
-            for s in self.scratch[..output_len].iter_mut() {
+            for s in output.iter_mut() {
                 *s = s.clamp(-threshold, threshold);
             }
         }
 
-        // Write to output ring buffer
-        self.output_buffer.write(&self.scratch[..output_len]);
+        if let Some(meter) = &mut self.meter {
+            meter.process(output);
+        }
+
+        self.position += frame_count as i64;
     }
 }
 
@@ -248,4 +793,187 @@ mod tests {
             assert!(s.abs() <= 0.81); // within threshold + rounding
         }
     }
+
+    #[test]
+    fn test_channel_and_master_inserts() {
+        use crate::effects::{LimiterParams, ParametricEq, TruePeakLimiter};
+        use crate::AudioEffect;
+
+        let mut mixer = Mixer::new(2, 4096);
+        let mut eq = ParametricEq::default();
+        eq.set_param("band1.gain", -24.0);
+        mixer.inserts_mut(0).unwrap().push(Box::new(eq));
+        mixer
+            .master_inserts
+            .push(Box::new(TruePeakLimiter::new(LimiterParams {
+                ceiling_db: -6.0,
+                ..Default::default()
+            })));
+
+        // Loud low-frequency content on the EQ'd channel is cut; the
+        // limiter holds the sum under its ceiling.
+        let frames = 4800;
+        let low: Vec<f32> = (0..frames)
+            .flat_map(|n| {
+                let s = (2.0 * std::f32::consts::PI * 40.0 * n as f32 / 48000.0).sin();
+                [s, s]
+            })
+            .collect();
+        let mut out = vec![0.0f32; frames * 2];
+        mixer.mix_into(&[&low, &low], frames, &mut out);
+        let ceiling = 10f32.powf(-6.0 / 20.0);
+        assert!(out.iter().all(|s| s.abs() <= ceiling * 1.001));
+        assert_eq!(mixer.position, frames as i64);
+    }
+
+    #[test]
+    fn test_inserts_follow_saved_settings() {
+        use crate::effects::{EqParams, ParametricEq};
+        use crate::AudioEffect;
+
+        let mut chain = EffectChain::new(48000);
+        let mut eq = ParametricEq::new(EqParams::default());
+        eq.set_param("band1.gain", -6.0);
+        chain.push(Box::new(eq));
+        let saved = chain.saved();
+
+        let mut mixer = Mixer::new(1, 4096);
+        mixer.set_inserts(0, &saved);
+        mixer.set_master_inserts(&saved);
+        assert_eq!(mixer.inserts(0).unwrap().len(), 1);
+        assert_eq!(mixer.master_inserts.len(), 1);
+        assert_eq!(mixer.inserts(0).unwrap().saved(), saved);
+
+        // Unchanged settings keep the running chain; clearing removes it.
+        mixer.inserts_mut(0).unwrap().slots_mut()[0].bypass = true;
+        mixer.set_inserts(0, &saved);
+        assert!(mixer.inserts(0).unwrap().slots()[0].bypass);
+        mixer.set_inserts(0, &[]);
+        mixer.set_master_inserts(&[]);
+        assert!(mixer.inserts(0).unwrap().is_empty());
+        assert!(mixer.master_inserts.is_empty());
+    }
+
+    #[test]
+    fn test_insert_latency_is_compensated() {
+        use crate::effects::TruePeakLimiter;
+
+        // An impulse hard left through a look-ahead limiter and one hard
+        // right without inserts come out together.
+        let mut mixer = Mixer::new(2, 4096);
+        mixer.channel_mut(0).unwrap().pan = -1.0;
+        mixer.channel_mut(1).unwrap().pan = 1.0;
+        mixer
+            .inserts_mut(0)
+            .unwrap()
+            .push(Box::new(TruePeakLimiter::default()));
+        let latency = mixer.inserts(0).unwrap().latency();
+        assert!(latency > 0);
+
+        let frames = 1024;
+        let mut impulse = vec![0.0f32; frames * 2];
+        impulse[0] = 0.1;
+        impulse[1] = 0.1;
+        let mut out = vec![0.0f32; frames * 2];
+        mixer.mix_into(&[&impulse, &impulse], frames, &mut out);
+        let peak = |channel: usize| {
+            (0..frames)
+                .max_by(|a, b| out[a * 2 + channel].total_cmp(&out[b * 2 + channel]))
+                .unwrap()
+        };
+        assert_eq!(peak(0), latency);
+        assert_eq!(peak(1), latency);
+    }
+
+    #[test]
+    fn test_insert_sender_hands_chains_over() {
+        use crate::effects::{EqParams, ParametricEq};
+
+        let mut chain = EffectChain::new(48000);
+        chain.push(Box::new(ParametricEq::new(EqParams::default())));
+        let mut sequence = Sequence::default();
+        sequence.audio_tracks[0].inserts = chain.saved();
+        sequence.master_inserts = chain.saved();
+
+        let mut mixer = Mixer::new(1, 4096);
+        let mut sender = mixer.insert_sender();
+        sender.sync(&sequence);
+        assert!(mixer.inserts(0).unwrap().is_empty());
+        mixer.receive_inserts();
+        assert_eq!(mixer.inserts(0).unwrap().len(), 1);
+        assert_eq!(mixer.master_inserts.len(), 1);
+
+        // The handed-over chain is the one that runs: the saved settings
+        // match, so it is not rebuilt.
+        mixer.inserts_mut(0).unwrap().slots_mut()[0].bypass = true;
+        mixer.set_inserts(0, &sequence.audio_tracks[0].inserts);
+        assert!(mixer.inserts(0).unwrap().slots()[0].bypass);
+
+        // Replaced chains come back to be dropped; unchanged settings are
+        // not sent again.
+        assert_eq!(sender.retired.len(), 2);
+        sender.sync(&sequence);
+        assert!(sender.retired.is_empty());
+        assert!(mixer.updates.is_empty());
+    }
+
+    #[test]
+    fn test_track_automation() {
+        use proedit_core::{EasingCurve, KeyframeTrack};
+
+        let mut mixer = Mixer::new(1, 4096);
+        let mut volume = KeyframeTrack::new("volume");
+        volume.set(RationalTime::ZERO, 0.0, EasingCurve::Linear);
+        volume.set(RationalTime::new(1, 100), -120.0, EasingCurve::Hold);
+        let automation = TrackAutomation {
+            volume: Some(volume),
+            pan: Some(KeyframeTrack::constant("pan", -1.0)),
+        };
+        mixer.set_automation(0, &automation);
+        assert_eq!(mixer.channel(0).unwrap().automation, automation);
+
+        // Hard left, fading to silence over the first 480 samples.
+        let frames = 960;
+        let ones = vec![1.0f32; frames * 2];
+        let mut out = vec![0.0f32; frames * 2];
+        mixer.mix_into(&[&ones], 480, &mut out[..960]);
+        mixer.mix_into(&[&ones], 480, &mut out[960..]);
+        assert!((out[0] - 1.0).abs() < 1e-6);
+        assert!(out.iter().skip(1).step_by(2).all(|r| r.abs() < 1e-6));
+        assert!(out[400] < out[200] && out[200] < out[0]);
+        assert!(out[960..].iter().all(|s| s.abs() < 1e-5));
+    }
+
+    #[test]
+    fn test_bus_routing_and_surround_monitor() {
+        let mut mixer = Mixer::new(2, 4096);
+        mixer.set_layout(ChannelLayout::Surround51);
+        let music = mixer.add_bus(Bus::new("Music"));
+        let effects = mixer.add_bus(Bus::new("Effects"));
+        mixer.channel_mut(0).unwrap().output = Route::Bus(music);
+        mixer.channel_mut(1).unwrap().output = Route::Bus(effects);
+        mixer.channel_mut(1).unwrap().lfe = 0.5;
+        mixer.bus_mut(music).unwrap().muted = true;
+
+        // Both channels centered: the muted music bus is silent, the
+        // effects bus feeds C and LFE.
+        let ones = vec![1.0f32; 8];
+        let mut out = vec![0.0f32; 4 * 6];
+        mixer.mix_into(&[&ones, &ones], 4, &mut out);
+        assert_eq!(&out[..6], &[0.0, 0.0, 1.0, 0.5, 0.0, 0.0]);
+        assert!(mixer.bus_output(music).unwrap().iter().all(|s| *s == 0.0));
+        assert_eq!(mixer.bus_output(effects).unwrap(), &out[..]);
+
+        // The ring buffer gets the stereo fold-down.
+        mixer.mix(&[&ones, &ones], 4);
+        let mut monitor = vec![0.0f32; 8];
+        assert_eq!(mixer.output_buffer.read(&mut monitor), 8);
+        assert!((monitor[0] - std::f32::consts::FRAC_1_SQRT_2).abs() < 1e-6);
+
+        // Removing a bus sends its channels to the master and renumbers.
+        mixer.remove_bus(music);
+        assert_eq!(mixer.channel(0).unwrap().output, Route::Master);
+        assert_eq!(mixer.channel(1).unwrap().output, Route::Bus(0));
+        assert_eq!(mixer.buses()[0].name, "Effects");
+    }
 }
@@ -0,0 +1,710 @@
+//! Audio output backends.
+//!
+//! An `OutputBackend` drains the engine's output ring buffer into a sink:
+//! `CpalOutput` plays through a sound device, `NullOutput` consumes audio
+//! in real time without a device (optionally recording it to a WAV file)
+//! for headless runs. Both convert the engine's stereo stream to the
+//! sink's sample rate and channel count through an `OutputFeed`, count
+//! underruns and report their output latency so the video clock can
+//! compensate for it.
+
+use crate::ring_buffer::RingBuffer;
+use crate::wav::WavWriter;
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SampleFormat, SizedSample};
+use proedit_core::{ProEditError, Result};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// The engine side of an output stream: interleaved stereo at the
+/// engine's sample rate.
+#[derive(Clone)]
+pub struct OutputSource {
+    buffer: Arc<RingBuffer>,
+    sample_rate: u32,
+    playing: Arc<AtomicBool>,
+    flush: Arc<AtomicBool>,
+}
+
+impl OutputSource {
+    /// Create a source reading `buffer`. `playing` tells the feed whether
+    /// an empty buffer is an underrun or just a stopped engine; setting
+    /// `flush` asks the feed to discard everything buffered.
+    pub fn new(
+        buffer: Arc<RingBuffer>,
+        sample_rate: u32,
+        playing: Arc<AtomicBool>,
+        flush: Arc<AtomicBool>,
+    ) -> Self {
+        Self {
+            buffer,
+            sample_rate,
+            playing,
+            flush,
+        }
+    }
+
+    /// Sample rate of the engine stream.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Read one stereo frame, if available.
+    #[inline]
+    fn read_frame(&self) -> Option<[f32; 2]> {
+        let mut frame = [0.0; 2];
+        (self.buffer.read(&mut frame) == 2).then_some(frame)
+    }
+}
+
+/// Counters shared between a backend and its audio callback.
+#[derive(Debug, Default)]
+struct OutputCounters {
+    underruns: AtomicU64,
+    frames_played: AtomicU64,
+    latency_ns: AtomicU64,
+}
+
+impl OutputCounters {
+    fn set_latency(&self, latency: Duration) {
+        self.latency_ns
+            .store(latency.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    fn stats(&self) -> OutputStats {
+        OutputStats {
+            underruns: self.underruns.load(Ordering::Relaxed),
+            frames_played: self.frames_played.load(Ordering::Relaxed),
+            latency: Duration::from_nanos(self.latency_ns.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// Output statistics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct OutputStats {
+    /// Callbacks that ran out of audio while the engine was playing.
+    pub underruns: u64,
+    /// Frames delivered to the sink (at the sink's rate).
+    pub frames_played: u64,
+    /// Measured time from a sample leaving the ring buffer to it being
+    /// heard.
+    pub latency: Duration,
+}
+
+/// Pulls engine audio for a sink, converting sample rate (linear
+/// interpolation) and channel count (mono downmix, extra channels silent).
+pub struct OutputFeed {
+    source: OutputSource,
+    channels: usize,
+    /// Engine frames per sink frame.
+    step: f64,
+    /// Position between `prev` and `next` (0..1).
+    frac: f64,
+    prev: [f32; 2],
+    next: [f32; 2],
+    counters: Arc<OutputCounters>,
+}
+
+impl OutputFeed {
+    fn new(
+        source: OutputSource,
+        sample_rate: u32,
+        channels: u16,
+        counters: Arc<OutputCounters>,
+    ) -> Self {
+        Self {
+            step: source.sample_rate as f64 / sample_rate as f64,
+            source,
+            channels: channels.max(1) as usize,
+            // Loads both `prev` and `next` on the first frame.
+            frac: 2.0,
+            prev: [0.0; 2],
+            next: [0.0; 2],
+            counters,
+        }
+    }
+
+    /// Fill one interleaved sink buffer; missing audio becomes silence.
+    pub fn fill(&mut self, output: &mut [f32]) {
+        // Only the consumer may move the read position, so a flush asked
+        // for by the engine happens here. The flag is cleared after the
+        // ring is, so the renderer never writes into the discarded span.
+        if self.source.flush.load(Ordering::Acquire) {
+            self.source.buffer.clear();
+            self.source.flush.store(false, Ordering::Release);
+            self.frac = 2.0;
+            self.prev = [0.0; 2];
+            self.next = [0.0; 2];
+        }
+        let mut starved = false;
+        let mut frames = 0;
+        for out in output.chunks_exact_mut(self.channels) {
+            let [l, r] = if self.step == 1.0 {
+                self.source.read_frame().unwrap_or_else(|| {
+                    starved = true;
+                    [0.0; 2]
+                })
+            } else {
+                while self.frac >= 1.0 {
+                    self.prev = self.next;
+                    self.next = self.source.read_frame().unwrap_or_else(|| {
+                        starved = true;
+                        [0.0; 2]
+                    });
+                    self.frac -= 1.0;
+                }
+                let t = self.frac as f32;
+                self.frac += self.step;
+                [
+                    self.prev[0] + (self.next[0] - self.prev[0]) * t,
+                    self.prev[1] + (self.next[1] - self.prev[1]) * t,
+                ]
+            };
+            match out {
+                [mono] => *mono = (l + r) * 0.5,
+                [left, right, rest @ ..] => {
+                    *left = l;
+                    *right = r;
+                    rest.fill(0.0);
+                }
+                [] => {}
+            }
+            frames += 1;
+        }
+        if starved && self.source.playing.load(Ordering::Acquire) {
+            self.counters.underruns.fetch_add(1, Ordering::Relaxed);
+        }
+        self.counters
+            .frames_played
+            .fetch_add(frames, Ordering::Relaxed);
+    }
+}
+
+/// A sink that plays the engine's output.
+pub trait OutputBackend: Send {
+    /// Backend name for logs and UI.
+    fn name(&self) -> &str;
+
+    /// Start draining `source`.
+    fn start(&mut self, source: OutputSource) -> Result<()>;
+
+    /// Stop draining and release the sink.
+    fn stop(&mut self) -> Result<()>;
+
+    /// Whether the sink is running.
+    fn is_running(&self) -> bool;
+
+    /// Sink sample rate and channel count, once started.
+    fn format(&self) -> Option<(u32, u16)>;
+
+    /// Underruns, frames played and measured latency.
+    fn stats(&self) -> OutputStats;
+}
+
+// ── cpal ────────────────────────────────────────────────────────
+
+/// An output device reported by cpal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputDevice {
+    pub name: String,
+    /// Whether this is the host's default output.
+    pub is_default: bool,
+    /// Default sample rate.
+    pub sample_rate: u32,
+    /// Default channel count.
+    pub channels: u16,
+}
+
+/// Plays through a cpal output device.
+///
+/// cpal streams cannot move between threads on every platform, so the
+/// stream lives on its own thread for as long as the backend runs.
+pub struct CpalOutput {
+    device_name: Option<String>,
+    counters: Arc<OutputCounters>,
+    format: Option<(u32, u16)>,
+    running: Option<(crossbeam_channel::Sender<()>, JoinHandle<()>)>,
+}
+
+impl CpalOutput {
+    /// Output to the named device, or the host default when `None`.
+    pub fn new(device_name: Option<String>) -> Self {
+        Self {
+            device_name,
+            counters: Arc::default(),
+            format: None,
+            running: None,
+        }
+    }
+
+    /// List the host's output devices.
+    pub fn devices() -> Result<Vec<OutputDevice>> {
+        let host = cpal::default_host();
+        let default_name = host.default_output_device().and_then(|d| d.name().ok());
+        let devices = host
+            .output_devices()
+            .map_err(|e| ProEditError::Audio(format!("Failed to list output devices: {e}")))?;
+        Ok(devices
+            .filter_map(|device| {
+                let name = device.name().ok()?;
+                let config = device.default_output_config().ok()?;
+                Some(OutputDevice {
+                    is_default: default_name.as_deref() == Some(name.as_str()),
+                    name,
+                    sample_rate: config.sample_rate().0,
+                    channels: config.channels(),
+                })
+            })
+            .collect())
+    }
+
+    fn find_device(&self) -> Result<cpal::Device> {
+        let host = cpal::default_host();
+        match &self.device_name {
+            None => host
+                .default_output_device()
+                .ok_or_else(|| ProEditError::Audio("No default output device".into())),
+            Some(name) => host
+                .output_devices()
+                .map_err(|e| ProEditError::Audio(format!("Failed to list output devices: {e}")))?
+                .find(|d| d.name().ok().as_deref() == Some(name.as_str()))
+                .ok_or_else(|| ProEditError::NotFound(format!("Output device {name}"))),
+        }
+    }
+}
+
+/// Pick a stream config: stereo f32 at the engine rate if the device
+/// supports it, then any format at the engine rate, then the device
+/// default (which the feed resamples to).
+fn choose_config(device: &cpal::Device, sample_rate: u32) -> Result<cpal::SupportedStreamConfig> {
+    let rate = cpal::SampleRate(sample_rate);
+    let supported: Vec<_> = device
+        .supported_output_configs()
+        .map(|configs| configs.collect())
+        .unwrap_or_default();
+    let at_rate = |range: &&cpal::SupportedStreamConfigRange| {
+        range.min_sample_rate() <= rate && rate <= range.max_sample_rate()
+    };
+    let preferred = supported
+        .iter()
+        .filter(at_rate)
+        .find(|r| r.channels() == 2 && r.sample_format() == SampleFormat::F32)
+        .or_else(|| supported.iter().filter(at_rate).find(|r| r.channels() == 2))
+        .or_else(|| supported.iter().find(at_rate));
+    match preferred {
+        Some(range) => Ok(range.with_sample_rate(rate)),
+        None => device
+            .default_output_config()
+            .map_err(|e| ProEditError::Audio(format!("No usable output config: {e}"))),
+    }
+}
+
+/// Sink frames converted per pass in the device callback. The scratch
+/// buffer is allocated once; larger callbacks are filled in several passes.
+const CALLBACK_FRAMES: usize = 4096;
+
+fn build_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    mut feed: OutputFeed,
+    counters: Arc<OutputCounters>,
+) -> Result<cpal::Stream>
+where
+    T: SizedSample + FromSample<f32>,
+{
+    let channels = config.channels.max(1) as usize;
+    let rate = config.sample_rate.0 as f64;
+    let frames = match config.buffer_size {
+        cpal::BufferSize::Fixed(n) => (n as usize).max(CALLBACK_FRAMES),
+        cpal::BufferSize::Default => CALLBACK_FRAMES,
+    };
+    let mut scratch = vec![0.0f32; frames * channels];
+    device
+        .build_output_stream(
+            config,
+            move |data: &mut [T], info: &cpal::OutputCallbackInfo| {
+                for chunk in data.chunks_mut(scratch.len()) {
+                    let scratch = &mut scratch[..chunk.len()];
+                    feed.fill(scratch);
+                    for (out, sample) in chunk.iter_mut().zip(scratch.iter()) {
+                        *out = T::from_sample(*sample);
+                    }
+                }
+                // The first frame of this buffer is heard at `playback`; the
+                // rest of the buffer adds its own length.
+                let timestamp = info.timestamp();
+                let device_delay = timestamp
+                    .playback
+                    .duration_since(&timestamp.callback)
+                    .unwrap_or_default();
+                let buffered = Duration::from_secs_f64((data.len() / channels) as f64 / rate);
+                counters.set_latency(device_delay + buffered);
+            },
+            |e| warn!("Audio output error: {}", e),
+            None,
+        )
+        .map_err(|e| ProEditError::Audio(format!("Failed to open output stream: {e}")))
+}
+
+impl OutputBackend for CpalOutput {
+    fn name(&self) -> &str {
+        self.device_name.as_deref().unwrap_or("Default Output")
+    }
+
+    fn start(&mut self, source: OutputSource) -> Result<()> {
+        self.stop()?;
+        let device = self.find_device()?;
+        let supported = choose_config(&device, source.sample_rate())?;
+        let format = supported.sample_format();
+        let config = supported.config();
+        let counters = Arc::clone(&self.counters);
+
+        let (ready_tx, ready_rx) = crossbeam_channel::bounded::<Result<()>>(1);
+        let (stop_tx, stop_rx) = crossbeam_channel::bounded::<()>(1);
+        let stream_config = config.clone();
+        let thread = std::thread::Builder::new()
+            .name("audio-output".into())
+            .spawn(move || {
+                let feed = OutputFeed::new(
+                    source,
+                    stream_config.sample_rate.0,
+                    stream_config.channels,
+                    Arc::clone(&counters),
+                );
+                let stream = match format {
+                    SampleFormat::F32 => {
+                        build_stream::<f32>(&device, &stream_config, feed, counters)
+                    }
+                    SampleFormat::I16 => {
+                        build_stream::<i16>(&device, &stream_config, feed, counters)
+                    }
+                    SampleFormat::U16 => {
+                        build_stream::<u16>(&device, &stream_config, feed, counters)
+                    }
+                    SampleFormat::I32 => {
+                        build_stream::<i32>(&device, &stream_config, feed, counters)
+                    }
+                    other => Err(ProEditError::Audio(format!(
+                        "Unsupported output sample format {other:?}"
+                    ))),
+                }
+                .and_then(|stream| {
+                    stream
+                        .play()
+                        .map_err(|e| ProEditError::Audio(format!("Failed to start output: {e}")))?;
+                    Ok(stream)
+                });
+                match stream {
+                    Ok(stream) => {
+                        let _ = ready_tx.send(Ok(()));
+                        let _ = stop_rx.recv();
+                        drop(stream);
+                    }
+                    Err(e) => {
+                        let _ = ready_tx.send(Err(e));
+                    }
+                }
+            })
+            .map_err(|e| ProEditError::Audio(format!("Failed to start output thread: {e}")))?;
+
+        let ready = ready_rx
+            .recv()
+            .unwrap_or_else(|_| Err(ProEditError::Audio("Output thread exited".into())));
+        if let Err(e) = ready {
+            let _ = thread.join();
+            return Err(e);
+        }
+        info!(
+            "Audio output on {} at {} Hz, {} channels",
+            self.name(),
+            config.sample_rate.0,
+            config.channels
+        );
+        self.format = Some((config.sample_rate.0, config.channels));
+        self.running = Some((stop_tx, thread));
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        if let Some((stop, thread)) = self.running.take() {
+            let _ = stop.send(());
+            thread
+                .join()
+                .map_err(|_| ProEditError::Audio("Output thread panicked".into()))?;
+        }
+        self.format = None;
+        Ok(())
+    }
+
+    fn is_running(&self) -> bool {
+        self.running.is_some()
+    }
+
+    fn format(&self) -> Option<(u32, u16)> {
+        self.format
+    }
+
+    fn stats(&self) -> OutputStats {
+        self.counters.stats()
+    }
+}
+
+impl Drop for CpalOutput {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
+
+// ── Null / file sink ────────────────────────────────────────────
+
+/// Consumes audio in real time without a device, for headless runs and
+/// tests. Can record what it consumes to a 32-bit float WAV file.
+pub struct NullOutput {
+    sample_rate: Option<u32>,
+    channels: u16,
+    period: Duration,
+    path: Option<PathBuf>,
+    counters: Arc<OutputCounters>,
+    format: Option<(u32, u16)>,
+    running: Option<(Arc<AtomicBool>, JoinHandle<Result<()>>)>,
+}
+
+impl Default for NullOutput {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NullOutput {
+    /// Stereo sink at the engine's sample rate, consuming every 10 ms.
+    pub fn new() -> Self {
+        Self {
+            sample_rate: None,
+            channels: 2,
+            period: Duration::from_millis(10),
+            path: None,
+            counters: Arc::default(),
+            format: None,
+            running: None,
+        }
+    }
+
+    /// Act like a device with this sample rate and channel count.
+    pub fn with_format(mut self, sample_rate: u32, channels: u16) -> Self {
+        self.sample_rate = Some(sample_rate);
+        self.channels = channels.max(1);
+        self
+    }
+
+    /// Record everything consumed to a WAV file.
+    pub fn with_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+}
+
+impl OutputBackend for NullOutput {
+    fn name(&self) -> &str {
+        if self.path.is_some() {
+            "File Output"
+        } else {
+            "Null Output"
+        }
+    }
+
+    fn start(&mut self, source: OutputSource) -> Result<()> {
+        self.stop()?;
+        let sample_rate = self.sample_rate.unwrap_or(source.sample_rate());
+        let channels = self.channels;
+        let period = self.period;
+        let period_frames = ((sample_rate as f64 * period.as_secs_f64()).round() as usize).max(1);
+        let mut writer = match &self.path {
+            Some(path) => Some(WavWriter::create(path, sample_rate, channels, 0)?),
+            None => None,
+        };
+
+        let mut feed = OutputFeed::new(source, sample_rate, channels, Arc::clone(&self.counters));
+        // One period is always queued ahead of "playback".
+        self.counters.set_latency(period);
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = Arc::clone(&stop);
+        let thread = std::thread::Builder::new()
+            .name("audio-null-output".into())
+            .spawn(move || {
+                let mut block = vec![0.0f32; period_frames * channels as usize];
+                let mut deadline = Instant::now();
+                while !thread_stop.load(Ordering::Acquire) {
+                    feed.fill(&mut block);
+                    if let Some(writer) = &mut writer {
+                        writer.write(&block)?;
+                    }
+                    deadline += period;
+                    if let Some(wait) = deadline.checked_duration_since(Instant::now()) {
+                        std::thread::sleep(wait);
+                    }
+                }
+                match writer {
+                    Some(writer) => writer.finish(),
+                    None => Ok(()),
+                }
+            })
+            .map_err(|e| ProEditError::Audio(format!("Failed to start output thread: {e}")))?;
+
+        self.format = Some((sample_rate, channels));
+        self.running = Some((stop, thread));
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        self.format = None;
+        match self.running.take() {
+            Some((stop, thread)) => {
+                stop.store(true, Ordering::Release);
+                thread
+                    .join()
+                    .map_err(|_| ProEditError::Audio("Output thread panicked".into()))?
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn is_running(&self) -> bool {
+        self.running.is_some()
+    }
+
+    fn format(&self) -> Option<(u32, u16)> {
+        self.format
+    }
+
+    fn stats(&self) -> OutputStats {
+        self.counters.stats()
+    }
+}
+
+impl Drop for NullOutput {
+    fn drop(&mut self) {
+        if let Err(e) = self.stop() {
+            warn!("Null output: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(samples: &[f32], sample_rate: u32, playing: bool) -> OutputSource {
+        let buffer = Arc::new(RingBuffer::new(samples.len().max(16)));
+        buffer.write(samples);
+        OutputSource::new(
+            buffer,
+            sample_rate,
+            Arc::new(AtomicBool::new(playing)),
+            Arc::default(),
+        )
+    }
+
+    fn feed(source: OutputSource, sample_rate: u32, channels: u16) -> OutputFeed {
+        OutputFeed::new(source, sample_rate, channels, Arc::default())
+    }
+
+    #[test]
+    fn test_feed_passes_through_at_engine_rate() {
+        let samples = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+        let mut f = feed(source(&samples, 48000, true), 48000, 2);
+        let mut out = [0.0; 6];
+        f.fill(&mut out);
+        assert_eq!(out, samples);
+        assert_eq!(f.counters.stats().underruns, 0);
+        assert_eq!(f.counters.stats().frames_played, 3);
+    }
+
+    #[test]
+    fn test_feed_channel_mapping() {
+        let mut f = feed(source(&[0.2, 0.6], 48000, true), 48000, 1);
+        let mut mono = [0.0; 1];
+        f.fill(&mut mono);
+        assert!((mono[0] - 0.4).abs() < 1e-6);
+
+        let mut f = feed(source(&[0.2, 0.6], 48000, true), 48000, 4);
+        let mut quad = [1.0; 4];
+        f.fill(&mut quad);
+        assert_eq!(quad, [0.2, 0.6, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_feed_resamples() {
+        // Engine ramp at 48 kHz played on a 96 kHz sink: every other
+        // output frame is interpolated halfway.
+        let ramp: Vec<f32> = (0..8).flat_map(|i| [i as f32, -(i as f32)]).collect();
+        let mut f = feed(source(&ramp, 48000, true), 96000, 2);
+        let mut out = [0.0; 12];
+        f.fill(&mut out);
+        let left: Vec<f32> = out.iter().step_by(2).copied().collect();
+        assert_eq!(left, [0.0, 0.5, 1.0, 1.5, 2.0, 2.5]);
+        assert_eq!(out[7], -1.5);
+    }
+
+    #[test]
+    fn test_underruns_only_count_while_playing() {
+        let mut f = feed(source(&[0.5, 0.5], 48000, true), 48000, 2);
+        let mut out = [0.0; 8];
+        f.fill(&mut out);
+        assert_eq!(&out[..4], &[0.5, 0.5, 0.0, 0.0]);
+        assert_eq!(f.counters.stats().underruns, 1);
+
+        let mut f = feed(source(&[], 48000, false), 48000, 2);
+        f.fill(&mut out);
+        assert_eq!(f.counters.stats().underruns, 0);
+    }
+
+    #[test]
+    fn test_flush_discards_buffered_audio() {
+        let src = source(&[0.5; 8], 48000, true);
+        let flush = Arc::clone(&src.flush);
+        let buffer = Arc::clone(&src.buffer);
+        let mut f = feed(src, 48000, 2);
+        flush.store(true, Ordering::Release);
+        let mut out = [1.0; 4];
+        f.fill(&mut out);
+        assert_eq!(out, [0.0; 4]);
+        assert!(!flush.load(Ordering::Acquire));
+
+        buffer.write(&[0.25, 0.75]);
+        f.fill(&mut out[..2]);
+        assert_eq!(&out[..2], &[0.25, 0.75]);
+    }
+
+    #[test]
+    fn test_null_output_records_wav() {
+        let path =
+            std::env::temp_dir().join(format!("proedit-null-output-{}.wav", std::process::id()));
+        let samples: Vec<f32> = (0..4800).map(|i| (i % 100) as f32 / 100.0).collect();
+        let mut output = NullOutput::new().with_format(48000, 2).with_file(&path);
+        output.start(source(&samples, 48000, false)).unwrap();
+        assert_eq!(output.format(), Some((48000, 2)));
+        assert_eq!(output.stats().latency, Duration::from_millis(10));
+        while output.stats().frames_played < 2400 {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        output.stop().unwrap();
+        assert!(!output.is_running());
+
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(&bytes[..4], b"RIFF");
+        assert_eq!(u16::from_le_bytes([bytes[20], bytes[21]]), 3);
+        let data_len = u32::from_le_bytes(bytes[40..44].try_into().unwrap()) as usize;
+        assert_eq!(data_len, bytes.len() - 44);
+        let recorded: Vec<f32> = bytes[44..]
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+            .collect();
+        assert_eq!(&recorded[..samples.len()], &samples[..]);
+    }
+}
@@ -0,0 +1,791 @@
+//! Multi-resolution waveform peak files.
+//!
+//! A peak file holds min/max pairs per channel at several reductions
+//! (`PEAK_LEVELS` samples per peak), so the timeline can draw an
+//! hour-long asset at any zoom without touching its audio. `PeakCache`
+//! builds one file per asset on a background thread, streaming the source
+//! in chunks rather than decoding it whole, memory-maps it for reading and
+//! rebuilds it when the source file's size or modification time changes.
+//! Zoomed in past the finest level, `PeakCache::peaks` reads the samples
+//! under the view instead.
+//!
+//! Layout (little-endian): a 48-byte header (`MAGIC`, channels, sample
+//! rate, frame count, source stamp, level count), one 16-byte entry per
+//! level (samples per peak, peak count), then each level's peaks with
+//! channels interleaved as `i16` min/max pairs.
+
+use crate::render::{sample_to_time, PcmSource, SourceLoader};
+use crate::waveform::{Waveform, WaveformSample};
+use crossbeam_channel::Sender;
+use memmap2::Mmap;
+use parking_lot::Mutex;
+use proedit_core::{ProEditError, RationalTime, Result, TimeRange};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::UNIX_EPOCH;
+use tracing::{info, warn};
+
+/// Samples per peak of each level, finest first. Each level is a 4x
+/// reduction of the one before.
+pub const PEAK_LEVELS: [u32; 4] = [256, 1024, 4096, 16384];
+
+const MAGIC: &[u8; 8] = b"PEDPEAK1";
+const HEADER_BYTES: usize = 48;
+const LEVEL_ENTRY_BYTES: usize = 16;
+/// Bytes per channel per peak (`i16` min and max).
+const PEAK_BYTES: usize = 4;
+/// Source audio decoded per step when building a peak file.
+const BUILD_CHUNK_SECONDS: i64 = 30;
+
+/// Size and modification time of a source file, recorded in its peak
+/// file to detect changes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SourceStamp {
+    /// File size in bytes.
+    pub len: u64,
+    /// Modification time in nanoseconds since the Unix epoch.
+    pub modified: u64,
+}
+
+impl SourceStamp {
+    /// Stamp of the file at `path` (zero if it cannot be read).
+    pub fn of(path: &Path) -> Self {
+        let Ok(meta) = std::fs::metadata(path) else {
+            return Self::default();
+        };
+        let modified = meta
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |d| d.as_nanos() as u64);
+        Self {
+            len: meta.len(),
+            modified,
+        }
+    }
+}
+
+/// One resolution of a peak file.
+#[derive(Debug, Clone, Copy)]
+struct Level {
+    samples_per_peak: u32,
+    count: u64,
+    /// Byte offset of the level's first peak.
+    offset: usize,
+}
+
+/// A memory-mapped peak file.
+pub struct PeakFile {
+    map: Mmap,
+    channels: u16,
+    sample_rate: u32,
+    frames: u64,
+    stamp: SourceStamp,
+    levels: Vec<Level>,
+}
+
+impl PeakFile {
+    /// Build all levels for `source` and write them to `path`.
+    pub fn write(path: &Path, source: &PcmSource, stamp: SourceStamp) -> Result<()> {
+        let mut builder = PeakBuilder::new(source.channels.max(1) as usize);
+        builder.push(&source.samples);
+        write_file(path, builder, source.sample_rate, stamp)
+    }
+
+    /// Build all levels for the media at `source`, reading it through
+    /// `loader` a chunk at a time, and write them to `path`. Only the
+    /// chunk being read and the peaks are held in memory, so `loader`
+    /// should override `SourceLoader::load_range` to seek.
+    pub fn build(
+        path: &Path,
+        loader: &dyn SourceLoader,
+        source: &str,
+        stamp: SourceStamp,
+    ) -> Result<()> {
+        let chunk_length = RationalTime::new(BUILD_CHUNK_SECONDS, 1);
+        let mut built: Option<(PeakBuilder, u32)> = None;
+        for index in 0.. {
+            let range = TimeRange::new(
+                RationalTime::new(index * BUILD_CHUNK_SECONDS, 1),
+                chunk_length,
+            );
+            let chunk = loader.load_range(source, range)?;
+            let channels = chunk.channels.max(1) as usize;
+            let (builder, sample_rate) =
+                built.get_or_insert_with(|| (PeakBuilder::new(channels), chunk.sample_rate));
+            if channels != builder.channels || chunk.sample_rate != *sample_rate {
+                return Err(ProEditError::Audio(format!(
+                    "{source} changed format while building peaks"
+                )));
+            }
+            builder.push(&chunk.samples);
+            // A short chunk is the end of the media.
+            if (chunk.frame_count() as i64) < BUILD_CHUNK_SECONDS * *sample_rate as i64 {
+                break;
+            }
+        }
+        let (builder, sample_rate) = built.expect("at least one chunk is read");
+        write_file(path, builder, sample_rate, stamp)
+    }
+
+    /// Memory-map and validate the peak file at `path`.
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = File::open(path)?;
+        // SAFETY: peak files are only ever replaced by rename, never
+        // modified in place, so the mapped pages stay valid and unchanged
+        // for the lifetime of the map.
+        #[allow(unsafe_code)]
+        let map = unsafe { Mmap::map(&file)? };
+
+        let invalid = || ProEditError::Audio(format!("Invalid peak file: {}", path.display()));
+        if map.len() < HEADER_BYTES || &map[..8] != MAGIC {
+            return Err(invalid());
+        }
+        let u16_at = |i: usize| u16::from_le_bytes([map[i], map[i + 1]]);
+        let u32_at = |i: usize| u32::from_le_bytes(map[i..i + 4].try_into().unwrap());
+        let u64_at = |i: usize| u64::from_le_bytes(map[i..i + 8].try_into().unwrap());
+
+        let channels = u16_at(8);
+        let level_count = u32_at(40) as usize;
+        if channels == 0 || level_count == 0 || level_count > PEAK_LEVELS.len() {
+            return Err(invalid());
+        }
+        let mut offset = HEADER_BYTES + level_count * LEVEL_ENTRY_BYTES;
+        if map.len() < offset {
+            return Err(invalid());
+        }
+        let mut levels = Vec::with_capacity(level_count);
+        for i in 0..level_count {
+            let entry = HEADER_BYTES + i * LEVEL_ENTRY_BYTES;
+            let level = Level {
+                samples_per_peak: u32_at(entry),
+                count: u64_at(entry + 8),
+                offset,
+            };
+            if level.samples_per_peak == 0 {
+                return Err(invalid());
+            }
+            // A corrupt count must not wrap the offset past the checks.
+            offset = usize::try_from(level.count)
+                .ok()
+                .and_then(|count| count.checked_mul(channels as usize * PEAK_BYTES))
+                .and_then(|bytes| offset.checked_add(bytes))
+                .ok_or_else(invalid)?;
+            levels.push(level);
+        }
+        if map.len() < offset {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            channels,
+            sample_rate: u32_at(12),
+            frames: u64_at(16),
+            stamp: SourceStamp {
+                len: u64_at(24),
+                modified: u64_at(32),
+            },
+            levels,
+            map,
+        })
+    }
+
+    /// Number of channels.
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    /// Sample rate of the analysed audio.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Length of the analysed audio in frames.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Stamp of the source the file was built from.
+    pub fn stamp(&self) -> SourceStamp {
+        self.stamp
+    }
+
+    /// Fill `out` with one min/max pair per pixel for `channel`, starting
+    /// at frame `start` with `samples_per_pixel` frames per pixel.
+    ///
+    /// Reads the coarsest level that is at least as fine as the zoom and
+    /// combines the peaks under each pixel. Pixels past the end are zero.
+    /// Below `PEAK_LEVELS[0]` samples per pixel neighbouring pixels share
+    /// a peak; `PeakCache::peaks` reads the samples there instead.
+    pub fn peaks(
+        &self,
+        channel: usize,
+        start: f64,
+        samples_per_pixel: f64,
+        out: &mut [WaveformSample],
+    ) {
+        out.fill(WaveformSample::default());
+        if channel >= self.channels as usize || samples_per_pixel <= 0.0 {
+            return;
+        }
+        let level = self
+            .levels
+            .iter()
+            .rev()
+            .find(|l| l.samples_per_peak as f64 <= samples_per_pixel)
+            .unwrap_or(&self.levels[0]);
+        let spp = level.samples_per_peak as f64;
+
+        for (i, pixel) in out.iter_mut().enumerate() {
+            let from = start + i as f64 * samples_per_pixel;
+            let to = from + samples_per_pixel;
+            let first = (from / spp).floor().max(0.0) as u64;
+            let last = ((to / spp).ceil() as u64).max(first + 1).min(level.count);
+            if first >= last {
+                continue;
+            }
+            let mut min = f32::MAX;
+            let mut max = f32::MIN;
+            for index in first..last {
+                let peak = self.peak(level, index, channel);
+                min = min.min(peak.min);
+                max = max.max(peak.max);
+            }
+            *pixel = WaveformSample { min, max };
+        }
+    }
+
+    /// The whole channel as a `Waveform` at `samples_per_pixel`.
+    pub fn waveform(&self, channel: usize, samples_per_pixel: usize) -> Waveform {
+        let samples_per_pixel = samples_per_pixel.max(1);
+        let pixels = (self.frames as usize).div_ceil(samples_per_pixel);
+        let mut data = vec![WaveformSample::default(); pixels];
+        self.peaks(channel, 0.0, samples_per_pixel as f64, &mut data);
+        Waveform {
+            samples_per_pixel,
+            data,
+            sample_rate: self.sample_rate,
+        }
+    }
+
+    fn peak(&self, level: &Level, index: u64, channel: usize) -> WaveformSample {
+        let at = level.offset + (index as usize * self.channels as usize + channel) * PEAK_BYTES;
+        let min = i16::from_le_bytes([self.map[at], self.map[at + 1]]);
+        let max = i16::from_le_bytes([self.map[at + 2], self.map[at + 3]]);
+        WaveformSample {
+            min: min as f32 / i16::MAX as f32,
+            max: max as f32 / i16::MAX as f32,
+        }
+    }
+}
+
+/// Quantize a sample to `i16`, rounding with `round`.
+fn quantize(v: f32, round: fn(f32) -> f32) -> i16 {
+    round(v.clamp(-1.0, 1.0) * i16::MAX as f32) as i16
+}
+
+/// Finest-level peaks of interleaved audio fed in order, a chunk at a
+/// time. Quantization rounds outwards so peaks are never drawn smaller
+/// than they are.
+struct PeakBuilder {
+    channels: usize,
+    /// Finished finest-level peaks, channels interleaved.
+    finest: Vec<[i16; 2]>,
+    /// Min and max per channel of the peak being filled.
+    current: Vec<[f32; 2]>,
+    /// Frames in the peak being filled.
+    filled: usize,
+    frames: u64,
+}
+
+impl PeakBuilder {
+    fn new(channels: usize) -> Self {
+        Self {
+            channels,
+            finest: Vec::new(),
+            current: vec![[f32::MAX, f32::MIN]; channels],
+            filled: 0,
+            frames: 0,
+        }
+    }
+
+    fn push(&mut self, samples: &[f32]) {
+        for frame in samples.chunks_exact(self.channels) {
+            for (peak, s) in self.current.iter_mut().zip(frame) {
+                *peak = [peak[0].min(*s), peak[1].max(*s)];
+            }
+            self.filled += 1;
+            if self.filled == PEAK_LEVELS[0] as usize {
+                self.end_peak();
+            }
+        }
+        self.frames += (samples.len() / self.channels) as u64;
+    }
+
+    fn end_peak(&mut self) {
+        for peak in &mut self.current {
+            let [min, max] = std::mem::replace(peak, [f32::MAX, f32::MIN]);
+            self.finest
+                .push([quantize(min, f32::floor), quantize(max, f32::ceil)]);
+        }
+        self.filled = 0;
+    }
+
+    /// Min/max pairs of every level, channels interleaved.
+    fn finish(mut self) -> Vec<Vec<[i16; 2]>> {
+        if self.filled > 0 {
+            self.end_peak();
+        }
+        let channels = self.channels;
+        let mut levels = vec![self.finest];
+        for pair in PEAK_LEVELS.windows(2) {
+            let factor = (pair[1] / pair[0]) as usize;
+            let previous = levels.last().unwrap();
+            let mut level = Vec::with_capacity(previous.len() / factor + channels);
+            for group in previous.chunks(factor * channels) {
+                for channel in 0..channels {
+                    let mut peak = [i16::MAX, i16::MIN];
+                    for [min, max] in group.iter().skip(channel).step_by(channels) {
+                        peak = [peak[0].min(*min), peak[1].max(*max)];
+                    }
+                    level.push(peak);
+                }
+            }
+            levels.push(level);
+        }
+        levels
+    }
+}
+
+/// Write the levels of `builder` to `path`.
+///
+/// Writes to a temporary file and renames it into place, so readers never
+/// map a partly written file.
+fn write_file(
+    path: &Path,
+    builder: PeakBuilder,
+    sample_rate: u32,
+    stamp: SourceStamp,
+) -> Result<()> {
+    let channels = builder.channels;
+    let frames = builder.frames;
+    let levels = builder.finish();
+
+    let tmp = path.with_extension("peaks.tmp");
+    let mut file = BufWriter::new(File::create(&tmp)?);
+    file.write_all(MAGIC)?;
+    file.write_all(&(channels as u16).to_le_bytes())?;
+    file.write_all(&0u16.to_le_bytes())?;
+    file.write_all(&sample_rate.to_le_bytes())?;
+    file.write_all(&frames.to_le_bytes())?;
+    file.write_all(&stamp.len.to_le_bytes())?;
+    file.write_all(&stamp.modified.to_le_bytes())?;
+    file.write_all(&(levels.len() as u32).to_le_bytes())?;
+    file.write_all(&0u32.to_le_bytes())?;
+    for (level, spp) in levels.iter().zip(PEAK_LEVELS) {
+        file.write_all(&spp.to_le_bytes())?;
+        file.write_all(&0u32.to_le_bytes())?;
+        file.write_all(&((level.len() / channels) as u64).to_le_bytes())?;
+    }
+    for level in &levels {
+        for [min, max] in level {
+            file.write_all(&min.to_le_bytes())?;
+            file.write_all(&max.to_le_bytes())?;
+        }
+    }
+    file.flush()?;
+    drop(file);
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// Min/max of `channel` of `source` per pixel, starting `start` frames
+/// into it with `samples_per_pixel` frames per pixel. Pixels past the end
+/// are zero.
+fn sample_peaks(
+    source: &PcmSource,
+    channel: usize,
+    start: f64,
+    samples_per_pixel: f64,
+    out: &mut [WaveformSample],
+) {
+    let channels = source.channels.max(1) as usize;
+    let frames = source.frame_count();
+    for (i, pixel) in out.iter_mut().enumerate() {
+        let from = start + i as f64 * samples_per_pixel;
+        let first = from.floor().max(0.0) as usize;
+        let last = ((from + samples_per_pixel).ceil() as usize)
+            .max(first + 1)
+            .min(frames);
+        *pixel = if first < last {
+            let mut peak = WaveformSample {
+                min: f32::MAX,
+                max: f32::MIN,
+            };
+            for frame in first..last {
+                let s = source.samples[frame * channels + channel];
+                peak = WaveformSample {
+                    min: peak.min.min(s),
+                    max: peak.max.max(s),
+                };
+            }
+            peak
+        } else {
+            WaveformSample::default()
+        };
+    }
+}
+
+/// Where the peak file of `source` lives in `dir`: a stable 64-bit FNV-1a
+/// hash of the source path.
+pub fn peak_file_path(dir: &Path, source: &Path) -> PathBuf {
+    let hash = source
+        .to_string_lossy()
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325u64, |h, b| {
+            (h ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+        });
+    dir.join(format!("{hash:016x}.peaks"))
+}
+
+/// Build state shared with the worker thread.
+#[derive(Default)]
+struct PeakJobs {
+    pending: HashSet<PathBuf>,
+    /// Sources that failed to decode, with the stamp they failed at.
+    failed: HashMap<PathBuf, SourceStamp>,
+}
+
+/// Peak files for a set of assets, built in the background.
+pub struct PeakCache {
+    dir: PathBuf,
+    loader: Arc<dyn SourceLoader>,
+    loaded: Mutex<HashMap<PathBuf, Arc<PeakFile>>>,
+    jobs: Arc<Mutex<PeakJobs>>,
+    sender: Option<Sender<PathBuf>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl PeakCache {
+    /// Create a cache storing peak files in `dir`, decoding sources with
+    /// `loader` on a "peak-builder" thread.
+    pub fn new(dir: impl Into<PathBuf>, loader: Arc<dyn SourceLoader>) -> Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        let jobs = Arc::new(Mutex::new(PeakJobs::default()));
+        let (sender, receiver) = crossbeam_channel::unbounded::<PathBuf>();
+
+        let worker = {
+            let dir = dir.clone();
+            let jobs = Arc::clone(&jobs);
+            let loader = Arc::clone(&loader);
+            std::thread::Builder::new()
+                .name("peak-builder".into())
+                .spawn(move || {
+                    for source in receiver {
+                        let stamp = SourceStamp::of(&source);
+                        let built = PeakFile::build(
+                            &peak_file_path(&dir, &source),
+                            loader.as_ref(),
+                            &source.to_string_lossy(),
+                            stamp,
+                        );
+                        let mut jobs = jobs.lock();
+                        jobs.pending.remove(&source);
+                        match built {
+                            Ok(()) => info!("Built waveform peaks for {:?}", source),
+                            Err(e) => {
+                                warn!("Waveform peaks for {:?} failed: {}", source, e);
+                                jobs.failed.insert(source, stamp);
+                            }
+                        }
+                    }
+                })
+                .map_err(|e| ProEditError::Audio(format!("Failed to start peak builder: {e}")))?
+        };
+
+        Ok(Self {
+            dir,
+            loader,
+            loaded: Mutex::new(HashMap::new()),
+            jobs,
+            sender: Some(sender),
+            worker: Some(worker),
+        })
+    }
+
+    /// Directory holding the peak files.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The up-to-date peak file of `source`, if it is built.
+    ///
+    /// Otherwise queues a build (once per source version) and returns
+    /// `None`; call again later. A source whose size or modification time
+    /// changed is rebuilt.
+    pub fn get(&self, source: &Path) -> Option<Arc<PeakFile>> {
+        let stamp = SourceStamp::of(source);
+        {
+            let mut loaded = self.loaded.lock();
+            match loaded.get(source) {
+                Some(file) if file.stamp() == stamp => return Some(Arc::clone(file)),
+                Some(_) => {
+                    loaded.remove(source);
+                }
+                None => {}
+            }
+        }
+        {
+            let jobs = self.jobs.lock();
+            if jobs.pending.contains(source) || jobs.failed.get(source) == Some(&stamp) {
+                return None;
+            }
+        }
+
+        match PeakFile::open(&peak_file_path(&self.dir, source)) {
+            Ok(file) if file.stamp() == stamp => {
+                let file = Arc::new(file);
+                self.loaded
+                    .lock()
+                    .insert(source.to_path_buf(), Arc::clone(&file));
+                Some(file)
+            }
+            _ => {
+                self.request(source);
+                None
+            }
+        }
+    }
+
+    /// Fill `out` like [`PeakFile::peaks`] from `source`'s peak file.
+    ///
+    /// Finer than the finest level (`PEAK_LEVELS[0]` samples per pixel)
+    /// the peaks would repeat, so the samples under `out` are read from
+    /// the source instead. Returns false, leaving `out` untouched, if the
+    /// peak file is not built yet (see [`get`](Self::get)).
+    pub fn peaks(
+        &self,
+        source: &Path,
+        channel: usize,
+        start: f64,
+        samples_per_pixel: f64,
+        out: &mut [WaveformSample],
+    ) -> bool {
+        let Some(file) = self.get(source) else {
+            return false;
+        };
+        if samples_per_pixel >= PEAK_LEVELS[0] as f64 || channel >= file.channels() as usize {
+            file.peaks(channel, start, samples_per_pixel, out);
+            return true;
+        }
+        let first = start.floor().max(0.0) as i64;
+        let frames = (out.len() as f64 * samples_per_pixel).ceil() as i64 + 1;
+        let rate = file.sample_rate();
+        let range = TimeRange::new(sample_to_time(first, rate), sample_to_time(frames, rate));
+        match self.loader.load_range(&source.to_string_lossy(), range) {
+            Ok(pcm) => sample_peaks(&pcm, channel, start - first as f64, samples_per_pixel, out),
+            Err(e) => {
+                warn!("Reading samples of {:?} failed: {}", source, e);
+                file.peaks(channel, start, samples_per_pixel, out);
+            }
+        }
+        true
+    }
+
+    /// Queue a build of `source`'s peak file unless one is pending.
+    pub fn request(&self, source: &Path) {
+        let mut jobs = self.jobs.lock();
+        if jobs.pending.insert(source.to_path_buf()) {
+            jobs.failed.remove(source);
+            if let Some(sender) = &self.sender {
+                let _ = sender.send(source.to_path_buf());
+            }
+        }
+    }
+
+    /// Whether a build of `source` is queued or running.
+    pub fn is_pending(&self, source: &Path) -> bool {
+        self.jobs.lock().pending.contains(source)
+    }
+}
+
+impl Drop for PeakCache {
+    fn drop(&mut self) {
+        // Closing the channel ends the worker after its current build.
+        self.sender.take();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::MemorySourceLoader;
+    use std::time::{Duration, Instant};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("proedit-{name}-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Stereo source: left is a ramp from -1 to 1, right is silent.
+    fn ramp(frames: usize) -> PcmSource {
+        let samples = (0..frames)
+            .flat_map(|i| [i as f32 / frames as f32 * 2.0 - 1.0, 0.0])
+            .collect();
+        PcmSource::new(48000, 2, samples)
+    }
+
+    #[test]
+    fn test_levels_match_direct_computation() {
+        let dir = temp_dir("peaks-levels");
+        let path = dir.join("ramp.peaks");
+        let source = ramp(100_000);
+        PeakFile::write(
+            &path,
+            &source,
+            SourceStamp {
+                len: 1,
+                modified: 2,
+            },
+        )
+        .unwrap();
+        let file = PeakFile::open(&path).unwrap();
+        assert_eq!(file.channels(), 2);
+        assert_eq!(file.frames(), 100_000);
+        assert_eq!(
+            file.stamp(),
+            SourceStamp {
+                len: 1,
+                modified: 2
+            }
+        );
+        assert_eq!(file.levels.len(), PEAK_LEVELS.len());
+        assert_eq!(file.levels[0].count, 100_000u64.div_ceil(256));
+
+        let left: Vec<f32> = source.samples.iter().step_by(2).copied().collect();
+        let tolerance = 1.0 / i16::MAX as f32;
+        // Any zoom, fine or coarse, matches min/max of the raw samples
+        // where pixels line up with peaks.
+        for spp in [256, 1024, 3000, 4096, 20000] {
+            let direct = Waveform::compute(&left, spp, 48000);
+            let cached = file.waveform(0, spp);
+            assert_eq!(cached.data.len(), direct.data.len());
+            for (c, d) in cached.data.iter().zip(&direct.data) {
+                assert!(c.min <= d.min + tolerance && c.max >= d.max - tolerance);
+                let reach = 2.0 * 16384.0 / 100_000.0 + tolerance;
+                assert!(d.min - c.min <= reach && c.max - d.max <= reach, "{spp}");
+            }
+        }
+        let right = file.waveform(1, 1024);
+        assert!(right.data.iter().all(|p| p.min == 0.0 && p.max == 0.0));
+
+        // A window into the middle at a fine zoom.
+        let mut window = [WaveformSample::default(); 4];
+        file.peaks(0, 51_200.0, 256.0, &mut window);
+        assert!((window[0].min - left[51_200]).abs() <= tolerance);
+        assert!((window[3].max - left[52_223]).abs() <= tolerance);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_streamed_build_matches_whole_source() {
+        let dir = temp_dir("peaks-streamed");
+        // 70 s at 1 kHz: three chunks, the peaks straddling their edges.
+        let mut source = ramp(70_000);
+        source.sample_rate = 1000;
+        let loader = MemorySourceLoader::new();
+        loader.insert("long.wav", source.clone());
+
+        let whole = dir.join("whole.peaks");
+        let streamed = dir.join("streamed.peaks");
+        PeakFile::write(&whole, &source, SourceStamp::default()).unwrap();
+        PeakFile::build(&streamed, &loader, "long.wav", SourceStamp::default()).unwrap();
+        assert_eq!(
+            std::fs::read(&whole).unwrap(),
+            std::fs::read(&streamed).unwrap()
+        );
+        assert!(
+            PeakFile::build(&streamed, &loader, "missing.wav", SourceStamp::default()).is_err()
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_open_rejects_bad_files() {
+        let dir = temp_dir("peaks-bad");
+        let path = dir.join("bad.peaks");
+        std::fs::write(&path, b"not a peak file at all, really not one").unwrap();
+        assert!(PeakFile::open(&path).is_err());
+
+        PeakFile::write(&path, &ramp(10_000), SourceStamp::default()).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &bytes[..bytes.len() - 1]).unwrap();
+        assert!(PeakFile::open(&path).is_err());
+
+        // Peak counts so large the level sizes overflow.
+        for count in [u64::MAX, u64::MAX / 8] {
+            let mut patched = bytes.clone();
+            let entry = HEADER_BYTES + 8;
+            patched[entry..entry + 8].copy_from_slice(&count.to_le_bytes());
+            std::fs::write(&path, &patched).unwrap();
+            assert!(PeakFile::open(&path).is_err());
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_cache_builds_in_background_and_invalidates() {
+        let dir = temp_dir("peaks-cache");
+        let source = dir.join("take.wav");
+        std::fs::write(&source, b"v1").unwrap();
+        let loader = Arc::new(MemorySourceLoader::new());
+        loader.insert(source.to_string_lossy(), ramp(10_000));
+        let cache = PeakCache::new(dir.join("cache"), loader.clone()).unwrap();
+
+        let wait = |cache: &PeakCache| {
+            let deadline = Instant::now() + Duration::from_secs(5);
+            loop {
+                if let Some(file) = cache.get(&source) {
+                    return file;
+                }
+                assert!(Instant::now() < deadline, "peak build timed out");
+                std::thread::sleep(Duration::from_millis(5));
+            }
+        };
+        assert!(cache.get(&source).is_none());
+        let first = wait(&cache);
+        assert_eq!(first.frames(), 10_000);
+        assert!(Arc::ptr_eq(&first, &cache.get(&source).unwrap()));
+
+        // Zoomed in past the finest level, each pixel gets its own samples.
+        let mut pixels = [WaveformSample::default(); 8];
+        assert!(cache.peaks(&source, 0, 5000.0, 4.0, &mut pixels));
+        let samples = &ramp(10_000).samples;
+        for (i, pixel) in pixels.iter().enumerate() {
+            let frame = 5000 + i * 4;
+            assert_eq!(pixel.min, samples[frame * 2]);
+            assert_eq!(pixel.max, samples[(frame + 3) * 2]);
+        }
+
+        // Changing the source rebuilds its peaks.
+        loader.insert(source.to_string_lossy(), ramp(20_000));
+        std::fs::write(&source, b"version 2").unwrap();
+        assert!(cache.get(&source).is_none());
+        assert_eq!(wait(&cache).frames(), 20_000);
+
+        // A fresh cache reuses the file on disk.
+        drop(cache);
+        let reopened = PeakCache::new(dir.join("cache"), loader).unwrap();
+        assert_eq!(reopened.get(&source).unwrap().frames(), 20_000);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,567 @@
+//! Voice-over recording into the timeline.
+//!
+//! A `Recorder` captures from an `InputBackend` while the cut plays from
+//! the pre-roll point. Only input that lands inside the punch range (after
+//! compensating the output latency and the input's own latency) is
+//! written, as a float WAV named `"<name> Take N.wav"` in the media folder.
+//! `place_take` then builds the edit putting the take on a track: over a
+//! clip that already covers the same range at normal speed it is stacked
+//! as the new active take, otherwise it overwrites the range.
+
+use crate::input::{InputBackend, InputSink, InputStats};
+use crate::render::{sample_to_time, time_to_sample};
+use crate::wav::WavWriter;
+use proedit_core::{ProEditError, RationalTime, Result, TimeRange};
+use proedit_timeline::{Clip, ClipRef, EditCommand, Sequence, TrackItem};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::info;
+
+/// Samples the input sink buffers between polls (about 10 s of stereo
+/// at 48 kHz).
+const INPUT_BUFFER_SAMPLES: usize = 1 << 20;
+
+/// What to record and where.
+#[derive(Debug, Clone)]
+pub struct RecordSettings {
+    /// Index of the audio track the take goes on.
+    pub track: usize,
+    /// Timeline range to punch in over.
+    pub punch: TimeRange,
+    /// Playback before the punch-in point.
+    pub pre_roll: RationalTime,
+    /// Folder the take files are written to.
+    pub media_dir: PathBuf,
+    /// Take file and clip name prefix.
+    pub name: String,
+    /// Delay between the timeline being sent for playback and it being
+    /// heard (the output latency), compensated when punching. The input's
+    /// latency, as reported by its backend, is added to it.
+    pub latency: Duration,
+}
+
+impl RecordSettings {
+    /// Record over `punch` on `track` into `media_dir`, with two seconds
+    /// of pre-roll.
+    pub fn new(track: usize, punch: TimeRange, media_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            track,
+            punch,
+            pre_roll: RationalTime::new(2, 1),
+            media_dir: media_dir.into(),
+            name: "VO".into(),
+            latency: Duration::ZERO,
+        }
+    }
+
+    /// Where playback starts: the punch-in point minus the pre-roll.
+    pub fn start_time(&self) -> RationalTime {
+        (self.punch.start - self.pre_roll).max(RationalTime::ZERO)
+    }
+}
+
+/// Progress of a recording pass.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordState {
+    /// Playing toward the punch-in point.
+    PreRoll,
+    /// Writing the take.
+    Recording,
+    /// Past the punch-out point; the take is complete.
+    Done,
+}
+
+/// A recorded take.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Take {
+    /// The WAV file.
+    pub path: PathBuf,
+    /// Index of the audio track it was recorded for.
+    pub track: usize,
+    /// Timeline range the take covers.
+    pub range: TimeRange,
+    /// Take number within the folder.
+    pub number: u32,
+    /// Clip name, e.g. "VO Take 3".
+    pub name: String,
+}
+
+/// One recording pass.
+struct Session {
+    settings: RecordSettings,
+    sample_rate: u32,
+    channels: usize,
+    /// Timeline sample of the next input frame, latency compensated.
+    next: i64,
+    /// Whether the input latency is still to be taken off `next`; it is
+    /// known once the first input arrives.
+    input_latency_pending: bool,
+    punch_in: i64,
+    punch_out: i64,
+    writer: Option<WavWriter>,
+    written: i64,
+    take: Take,
+}
+
+impl Session {
+    fn state(&self) -> RecordState {
+        if self.next < self.punch_in {
+            RecordState::PreRoll
+        } else if self.next < self.punch_out {
+            RecordState::Recording
+        } else {
+            RecordState::Done
+        }
+    }
+
+    /// Write the part of `samples` (starting at `next`) inside the punch.
+    fn write(&mut self, samples: &[f32]) -> Result<()> {
+        let frames = (samples.len() / self.channels) as i64;
+        let first = (self.punch_in - self.next).clamp(0, frames);
+        let last = (self.punch_out - self.next).clamp(0, frames);
+        if let Some(writer) = self.writer.as_mut().filter(|_| last > first) {
+            writer
+                .write(&samples[first as usize * self.channels..last as usize * self.channels])?;
+            self.written += last - first;
+        }
+        self.next += frames;
+        Ok(())
+    }
+
+    /// Close the file; `None` if nothing inside the punch was captured.
+    fn finish(mut self) -> Result<Option<Take>> {
+        let Some(writer) = self.writer.take() else {
+            return Ok(None);
+        };
+        writer.finish()?;
+        if self.written == 0 {
+            std::fs::remove_file(&self.take.path)?;
+            return Ok(None);
+        }
+        if self.written < self.punch_out - self.punch_in {
+            let duration = sample_to_time(self.written, self.sample_rate);
+            self.take.range = TimeRange::new(self.take.range.start, duration);
+        }
+        info!("Recorded {}", self.take.path.display());
+        Ok(Some(self.take))
+    }
+}
+
+/// Records takes from an input backend.
+pub struct Recorder {
+    input: Box<dyn InputBackend>,
+    sink: InputSink,
+    session: Option<Session>,
+    /// A take completed by `poll`, returned by the next `stop`.
+    finished: Option<Take>,
+    scratch: Vec<f32>,
+}
+
+impl Recorder {
+    /// Create a recorder capturing from `input`.
+    pub fn new(input: Box<dyn InputBackend>) -> Self {
+        Self {
+            input,
+            sink: InputSink::new(INPUT_BUFFER_SAMPLES),
+            session: None,
+            finished: None,
+            scratch: vec![0.0; 8192],
+        }
+    }
+
+    /// The input backend.
+    pub fn input(&self) -> &dyn InputBackend {
+        self.input.as_ref()
+    }
+
+    /// Start the input for metering without recording.
+    pub fn monitor(&mut self) -> Result<()> {
+        if !self.input.is_running() {
+            self.input.start(self.sink.clone())?;
+        }
+        Ok(())
+    }
+
+    /// Begin a pass. Playback should start at `settings.start_time()` at
+    /// the same moment; the take file is created right away.
+    pub fn start(&mut self, settings: RecordSettings) -> Result<()> {
+        if self.session.is_some() {
+            return Err(ProEditError::Audio("Already recording".into()));
+        }
+        self.monitor()?;
+        let (sample_rate, channels) = self
+            .input
+            .format()
+            .ok_or_else(|| ProEditError::Audio("Input has no format".into()))?;
+        self.sink.clear();
+        self.finished = None;
+
+        std::fs::create_dir_all(&settings.media_dir)?;
+        let (number, path) = next_take(&settings.media_dir, &settings.name);
+        let writer = WavWriter::create(&path, sample_rate, channels, 0)?;
+        let session = Session {
+            next: time_to_sample(
+                settings.start_time() - to_time(settings.latency),
+                sample_rate,
+            ),
+            input_latency_pending: true,
+            punch_in: time_to_sample(settings.punch.start, sample_rate),
+            punch_out: time_to_sample(settings.punch.end(), sample_rate),
+            sample_rate,
+            channels: channels.max(1) as usize,
+            writer: Some(writer),
+            written: 0,
+            take: Take {
+                name: format!("{} Take {number}", settings.name),
+                path,
+                track: settings.track,
+                range: settings.punch,
+                number,
+            },
+            settings,
+        };
+        info!(
+            "Recording {} on track {}",
+            session.take.name, session.settings.track
+        );
+        self.session = Some(session);
+        Ok(())
+    }
+
+    /// Write the input captured since the last call. Reaching the
+    /// punch-out point completes the take; `stop` returns it.
+    pub fn poll(&mut self) -> Result<RecordState> {
+        let Some(session) = self.session.as_mut() else {
+            // Monitoring only: keep the buffer from filling up.
+            self.sink.clear();
+            return Ok(RecordState::Done);
+        };
+        if session.input_latency_pending && self.sink.available() > 0 {
+            session.next -= time_to_sample(to_time(self.input.latency()), session.sample_rate);
+            session.input_latency_pending = false;
+        }
+        let chunk = self.scratch.len() / session.channels * session.channels;
+        while session.state() != RecordState::Done && self.sink.available() >= session.channels {
+            let wanted = (self.sink.available() / session.channels * session.channels).min(chunk);
+            let read = self.sink.read(&mut self.scratch[..wanted]);
+            session.write(&self.scratch[..read])?;
+        }
+        let state = session.state();
+        if state == RecordState::Done {
+            self.finished = self
+                .session
+                .take()
+                .map(Session::finish)
+                .transpose()?
+                .flatten();
+        }
+        Ok(state)
+    }
+
+    /// Peak input level per channel (linear) since the last call.
+    pub fn levels(&self) -> Vec<f32> {
+        let channels = self.input.format().map_or(0, |(_, c)| c as usize);
+        self.sink.take_peaks(channels)
+    }
+
+    /// Timeline time of the input being captured, or `None` when not
+    /// recording.
+    pub fn position(&self) -> Option<RationalTime> {
+        self.session
+            .as_ref()
+            .map(|s| sample_to_time(s.next, s.sample_rate))
+    }
+
+    /// Whether a pass is under way.
+    pub fn is_recording(&self) -> bool {
+        self.session.is_some()
+    }
+
+    /// The pass in progress.
+    pub fn settings(&self) -> Option<&RecordSettings> {
+        self.session.as_ref().map(|s| &s.settings)
+    }
+
+    /// End the pass and stop the input, writing what was captured up to
+    /// now. Returns the take, or `None` if nothing inside the punch range
+    /// was recorded.
+    pub fn stop(&mut self) -> Result<Option<Take>> {
+        if self.session.is_some() {
+            self.poll()?;
+        }
+        self.input.stop()?;
+        let take = match self.session.take() {
+            Some(session) => session.finish()?,
+            None => self.finished.take(),
+        };
+        self.sink.clear();
+        Ok(take)
+    }
+
+    /// Input overruns and frames captured.
+    pub fn stats(&self) -> InputStats {
+        self.sink.stats()
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
+
+fn to_time(duration: Duration) -> RationalTime {
+    RationalTime::new(duration.as_micros() as i64, 1_000_000)
+}
+
+/// The first free take number in `dir` and its path.
+fn next_take(dir: &Path, name: &str) -> (u32, PathBuf) {
+    (1..)
+        .map(|n| (n, dir.join(format!("{name} Take {n}.wav"))))
+        .find(|(_, path)| !path.exists())
+        .expect("take numbers exhausted")
+}
+
+/// The edit putting `take` on its audio track of `sequence`. Execute it
+/// on the sequence so the take can be undone.
+///
+/// A clip spanning exactly the take's range at normal speed gets it stacked
+/// as its new active take, keeping the earlier ones as alternates;
+/// otherwise the take overwrites the range as a new clip. Takes under
+/// `media_root` (the project's folder) are referenced relative to it, like
+/// the rest of the project's media.
+pub fn place_take(
+    sequence: &Sequence,
+    take: &Take,
+    media_root: Option<&Path>,
+) -> Result<EditCommand> {
+    let track = sequence
+        .audio_tracks
+        .get(take.track)
+        .ok_or_else(|| ProEditError::NotFound(format!("Audio track {}", take.track)))?;
+    let path = media_root
+        .and_then(|root| take.path.strip_prefix(root).ok())
+        .unwrap_or(&take.path);
+    let source = ClipRef::new(path.to_string_lossy(), take.range.duration);
+
+    let mut placed = track.clone();
+    let mut start = RationalTime::ZERO;
+    let mut stacked = false;
+    for item in &mut placed.items {
+        let duration = item.duration();
+        if let TrackItem::Clip(clip) = item {
+            // Takes hold no speed of their own, so a retimed clip is
+            // replaced rather than playing the take at its speed.
+            if start == take.range.start && duration == take.range.duration && clip.speed == 1.0 {
+                clip.push_take(source.clone(), RationalTime::ZERO);
+                stacked = true;
+                break;
+            }
+        }
+        start = start + duration;
+    }
+    if !stacked {
+        placed.overwrite(take.range.start, Clip::new(&take.name, source));
+    }
+    Ok(EditCommand::ReplaceItems {
+        track_id: track.id,
+        before: track.items.clone(),
+        after: placed.items,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::SyntheticInput;
+    use proedit_timeline::Track;
+
+    const RATE: u32 = 8000;
+
+    fn media_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("proedit-record-{name}"));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    /// Read back the samples of a WAV written by `WavWriter`.
+    fn read_samples(path: &Path) -> Vec<f32> {
+        std::fs::read(path).unwrap()[44..]
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+            .collect()
+    }
+
+    /// A synthetic input reporting a fixed capture latency.
+    struct Delayed {
+        input: SyntheticInput,
+        latency: Duration,
+    }
+
+    impl InputBackend for Delayed {
+        fn name(&self) -> &str {
+            self.input.name()
+        }
+
+        fn start(&mut self, sink: InputSink) -> Result<()> {
+            self.input.start(sink)
+        }
+
+        fn stop(&mut self) -> Result<()> {
+            self.input.stop()
+        }
+
+        fn is_running(&self) -> bool {
+            self.input.is_running()
+        }
+
+        fn format(&self) -> Option<(u32, u16)> {
+            self.input.format()
+        }
+
+        fn latency(&self) -> Duration {
+            self.latency
+        }
+    }
+
+    fn record(
+        input: impl InputBackend + 'static,
+        settings: RecordSettings,
+    ) -> (Option<Take>, Vec<f32>) {
+        let mut recorder = Recorder::new(Box::new(input));
+        recorder.start(settings).unwrap();
+        let mut levels = vec![0.0f32];
+        while recorder.poll().unwrap() != RecordState::Done {
+            for (l, p) in levels.iter_mut().zip(recorder.levels()) {
+                *l = l.max(p);
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        (recorder.stop().unwrap(), levels)
+    }
+
+    #[test]
+    fn test_punch_with_pre_roll_and_latency() {
+        let dir = media_dir("punch");
+        // Input frame n holds n / RATE, so the file shows what was kept.
+        let ramp: Vec<f32> = (0..RATE as usize).map(|n| n as f32 / RATE as f32).collect();
+        let punch = TimeRange::new(RationalTime::new(3, 10), RationalTime::new(1, 10));
+        let mut settings = RecordSettings::new(0, punch, &dir);
+        settings.pre_roll = RationalTime::new(2, 10);
+        settings.latency = Duration::from_millis(30);
+        assert_eq!(settings.start_time(), RationalTime::new(1, 10));
+
+        let input = Delayed {
+            input: SyntheticInput::from_samples(RATE, 1, ramp),
+            latency: Duration::from_millis(20),
+        };
+        let (take, levels) = record(input, settings);
+        let take = take.unwrap();
+        assert_eq!(take.number, 1);
+        assert_eq!(take.name, "VO Take 1");
+        assert_eq!(take.range, punch);
+        assert!(levels[0] > 0.0);
+
+        // 0.2 s of pre-roll plus 0.03 s of output and 0.02 s of input
+        // latency before the punch-in.
+        let samples = read_samples(&take.path);
+        assert_eq!(samples.len(), 800);
+        assert_eq!(samples[0], 2000.0 / RATE as f32);
+        assert_eq!(samples[799], 2799.0 / RATE as f32);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_takes_stack_as_alternates() {
+        let dir = media_dir("takes");
+        let punch = TimeRange::new(RationalTime::new(1, 10), RationalTime::new(1, 10));
+        let mut seq = Sequence::default();
+        seq.audio_tracks[0].append_clip(Clip::new(
+            "bed",
+            ClipRef::new("bed.wav", RationalTime::new(1, 2)),
+        ));
+
+        let mut settings = RecordSettings::new(0, punch, &dir);
+        settings.pre_roll = RationalTime::ZERO;
+        for expected in 1..=2 {
+            let input = SyntheticInput::tone(RATE, 2, 440.0, 0.5);
+            let (take, _) = record(input, settings.clone());
+            let take = take.unwrap();
+            assert_eq!(take.number, expected);
+            assert!(take.path.exists());
+            seq.execute(place_take(&seq, &take, None).unwrap());
+            seq.history.seal();
+        }
+
+        // Bed split around one clip holding both takes.
+        let track: &Track = &seq.audio_tracks[0];
+        assert_eq!(track.clip_count(), 3);
+        assert_eq!(track.item_start_time(1), punch.start);
+        let clip = track.clip_at(1).unwrap();
+        assert_eq!(clip.duration, punch.duration);
+        assert!(clip.source.path.ends_with("VO Take 2.wav"));
+        assert_eq!(clip.takes.len(), 1);
+        assert!(clip.takes[0].source.path.ends_with("VO Take 1.wav"));
+        assert_eq!(track.duration(), RationalTime::new(1, 2));
+
+        // Each take is one undo step.
+        assert!(seq.undo());
+        let clip = seq.audio_tracks[0].clip_at(1).unwrap();
+        assert!(clip.source.path.ends_with("VO Take 1.wav"));
+        assert!(clip.takes.is_empty());
+        assert!(seq.undo());
+        assert_eq!(seq.audio_tracks[0].clip_count(), 1);
+
+        let take = Take {
+            path: dir.join("VO Take 1.wav"),
+            track: 5,
+            range: punch,
+            number: 1,
+            name: "VO Take 1".into(),
+        };
+        assert!(place_take(&seq, &take, None).is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_take_placed_relative_to_media_root_at_normal_speed() {
+        let root = std::env::temp_dir().join("proedit-record-root");
+        let punch = TimeRange::new(RationalTime::ZERO, RationalTime::new(1, 2));
+        let mut seq = Sequence::default();
+        let mut bed = Clip::new("bed", ClipRef::new("bed.wav", RationalTime::new(1, 1)));
+        bed.speed = 2.0;
+        bed.duration = punch.duration;
+        seq.audio_tracks[0].append_clip(bed);
+        let take = Take {
+            path: root.join("Recordings").join("VO Take 1.wav"),
+            track: 0,
+            range: punch,
+            number: 1,
+            name: "VO Take 1".into(),
+        };
+
+        seq.execute(place_take(&seq, &take, Some(&root)).unwrap());
+        let clip = seq.audio_tracks[0].clip_at(0).unwrap();
+        assert_eq!(
+            Path::new(&clip.source.path),
+            Path::new("Recordings").join("VO Take 1.wav")
+        );
+        // The retimed clip is replaced, not stacked at its speed.
+        assert_eq!(clip.speed, 1.0);
+        assert!(clip.takes.is_empty());
+    }
+
+    #[test]
+    fn test_stop_before_punch_in_keeps_nothing() {
+        let dir = media_dir("early");
+        let punch = TimeRange::new(RationalTime::new(10, 1), RationalTime::new(1, 1));
+        let mut recorder = Recorder::new(Box::new(SyntheticInput::tone(RATE, 1, 440.0, 0.5)));
+        recorder.start(RecordSettings::new(0, punch, &dir)).unwrap();
+        std::thread::sleep(Duration::from_millis(30));
+        assert_eq!(recorder.poll().unwrap(), RecordState::PreRoll);
+        assert!(recorder.position().unwrap() >= RationalTime::new(8, 1));
+        assert_eq!(recorder.stop().unwrap(), None);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
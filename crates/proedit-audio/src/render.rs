@@ -0,0 +1,1439 @@
+//! Timeline audio rendering.
+//!
+//! Turns a `Sequence`'s audio tracks into mixed stereo blocks. Each clip's
+//! source range is resampled to the output rate (honoring clip speed) and
+//! shaped by the clip's gain, fades and volume automation. Clips that keep
+//! their pitch or shift it read a time-stretched copy of their source,
+//! shared by every clip of that source at the same ratio, so trims do not
+//! stretch again. Bars generator clips play their reference
+//! tone, synthesized once per clip; other generators are silent. A transition
+//! crossfades the clips on either side, playing their media past the edit
+//! point; disabled clips, muted tracks and gaps render as silence. The
+//! per-track buffers are then combined by the `Mixer`, which applies track
+//! volume and pan automation.
+//!
+//! The same `TimelineRenderer` feeds the real-time render thread in
+//! `AudioEngine` and offline rendering through `render_range`. The render
+//! thread's renderer defers loading: sources it has not been handed yet
+//! play as silence, and a copy made with `preparer` decodes and stretches
+//! them on another thread before `adopt` hands them back.
+//!
+//! Sources are never decoded whole. They load in chunks of a few seconds
+//! as clips read them, and are stretched chunk by chunk, each carrying on
+//! the stretch of the one before: `prepare` loads the chunks a time range
+//! reads and drops the others, and offline rendering drops chunks it has
+//! moved past.
+
+use crate::effects::db_to_gain;
+use crate::mixer::Mixer;
+use crate::stretch::{Stretcher, STRETCH_LAG_SECS};
+use parking_lot::RwLock;
+use proedit_core::{KeyframeTrack, ProEditError, RationalTime, Result, TimeRange};
+use proedit_timeline::{
+    BarsGenerator, Clip, Fade, FadeCurve, Generator, Sequence, Track, TrackItem,
+};
+use std::collections::{HashMap, HashSet};
+use std::f64::consts::TAU;
+use std::sync::Arc;
+use tracing::warn;
+use uuid::Uuid;
+
+/// Frames rendered per block by the render thread.
+pub const BLOCK_FRAMES: usize = 512;
+
+/// Seconds of source audio decoded at a time.
+const CHUNK_SECS: i64 = 10;
+
+/// Tone synthesized past the end of a bars clip's range, so transitions
+/// can play past the edit point.
+const TONE_MARGIN_SECS: f64 = 5.0;
+
+/// Convert a timeline time to a sample index at `sample_rate`
+/// (rounded to the nearest sample).
+pub fn time_to_sample(time: RationalTime, sample_rate: u32) -> i64 {
+    let num = time.numer() as i128 * sample_rate as i128;
+    let den = time.denom() as i128;
+    (2 * num + den).div_euclid(2 * den) as i64
+}
+
+/// Convert a sample index at `sample_rate` to a timeline time.
+pub fn sample_to_time(sample: i64, sample_rate: u32) -> RationalTime {
+    RationalTime::new(sample, sample_rate as i64)
+}
+
+/// Decoded PCM audio for one media file.
+#[derive(Debug, Clone)]
+pub struct PcmSource {
+    /// Sample rate of `samples`.
+    pub sample_rate: u32,
+    /// Number of interleaved channels.
+    pub channels: u16,
+    /// Interleaved f32 samples.
+    pub samples: Vec<f32>,
+}
+
+impl PcmSource {
+    /// Create a source from interleaved samples.
+    pub fn new(sample_rate: u32, channels: u16, samples: Vec<f32>) -> Self {
+        Self {
+            sample_rate,
+            channels,
+            samples,
+        }
+    }
+
+    /// Number of sample frames.
+    pub fn frame_count(&self) -> usize {
+        if self.channels == 0 {
+            0
+        } else {
+            self.samples.len() / self.channels as usize
+        }
+    }
+
+    /// Stereo sample pair at `index` (mono is duplicated, extra channels
+    /// beyond the first two are ignored).
+    #[inline]
+    pub fn frame(&self, index: usize) -> (f32, f32) {
+        let channels = self.channels as usize;
+        let base = index * channels;
+        if channels == 1 {
+            (self.samples[base], self.samples[base])
+        } else {
+            (self.samples[base], self.samples[base + 1])
+        }
+    }
+
+    /// The frames within `range` (source time from frame zero), clamped to
+    /// the frames there are.
+    pub fn slice(&self, range: TimeRange) -> PcmSource {
+        let frames = self.frame_count() as i64;
+        let first = time_to_sample(range.start, self.sample_rate).clamp(0, frames);
+        let last = time_to_sample(range.end(), self.sample_rate).clamp(first, frames);
+        let channels = self.channels as usize;
+        let samples = self.samples[first as usize * channels..last as usize * channels].to_vec();
+        PcmSource::new(self.sample_rate, self.channels, samples)
+    }
+}
+
+/// Provides decoded audio for clip source paths.
+pub trait SourceLoader: Send + Sync {
+    /// Load (decode) the audio of the media file at `path`.
+    fn load(&self, path: &str) -> Result<Arc<PcmSource>>;
+
+    /// Load the audio of `path` within `range` (source time). The result
+    /// starts at `range.start` and is shorter than the range, possibly
+    /// empty, where the media ends.
+    ///
+    /// The default loads the whole file and copies the range out; loaders
+    /// that can seek should override it.
+    fn load_range(&self, path: &str, range: TimeRange) -> Result<Arc<PcmSource>> {
+        Ok(Arc::new(self.load(path)?.slice(range)))
+    }
+}
+
+impl<F> SourceLoader for F
+where
+    F: Fn(&str) -> Result<Arc<PcmSource>> + Send + Sync,
+{
+    fn load(&self, path: &str) -> Result<Arc<PcmSource>> {
+        self(path)
+    }
+}
+
+/// Source loader backed by in-memory buffers (generated audio, tests).
+#[derive(Default)]
+pub struct MemorySourceLoader {
+    sources: RwLock<HashMap<String, Arc<PcmSource>>>,
+}
+
+impl MemorySourceLoader {
+    /// Create an empty loader.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register audio for a source path.
+    pub fn insert(&self, path: impl Into<String>, source: PcmSource) {
+        self.sources.write().insert(path.into(), Arc::new(source));
+    }
+}
+
+impl SourceLoader for MemorySourceLoader {
+    fn load(&self, path: &str) -> Result<Arc<PcmSource>> {
+        self.sources
+            .read()
+            .get(path)
+            .cloned()
+            .ok_or_else(|| ProEditError::NotFound(format!("No audio for {}", path)))
+    }
+}
+
+/// Loaded source chunks, stretched chunks and tones.
+struct SourceCache {
+    loader: Arc<dyn SourceLoader>,
+    /// Chunks of each source by stretch factor (bits; 1.0 for the source
+    /// as decoded) and chunk index.
+    chunks: HashMap<String, HashMap<(u64, usize), Chunk>>,
+    /// Sources that failed to load, so they are only attempted (and
+    /// reported) once.
+    failed: HashSet<String>,
+    /// Rate tones are synthesized at (the output rate).
+    sample_rate: u32,
+    /// Tones of bars generator clips, by clip.
+    tones: HashMap<Uuid, Tone>,
+    /// Play audio that is not loaded yet as silence instead of decoding it.
+    deferred: bool,
+}
+
+/// Source chunk `index`: `CHUNK_SECS` of the source from
+/// `index * CHUNK_SECS` seconds (fewer at the end of the media), or what
+/// stretching it added to the stretch of the chunks before.
+#[derive(Clone)]
+struct Chunk {
+    audio: ChunkAudio,
+    /// Read since the last eviction.
+    read: bool,
+    /// Stretch state to carry on with the next chunk.
+    next: Option<Box<Stretcher>>,
+}
+
+/// A chunk's frames and where they start in the (stretched) source.
+#[derive(Clone)]
+struct ChunkAudio {
+    pcm: Arc<PcmSource>,
+    start: usize,
+    /// The media ends in this chunk.
+    last: bool,
+}
+
+impl ChunkAudio {
+    fn end(&self) -> usize {
+        self.start + self.pcm.frame_count()
+    }
+}
+
+/// Index of the chunk holding source time `seconds`.
+fn chunk_index(seconds: f64) -> usize {
+    (seconds.max(0.0) / CHUNK_SECS as f64) as usize
+}
+
+/// The source time chunk `index` covers.
+fn chunk_range(index: usize) -> TimeRange {
+    let start = RationalTime::new(index as i64 * CHUNK_SECS, 1);
+    TimeRange::new(start, RationalTime::new(CHUNK_SECS, 1))
+}
+
+/// How much a clip's source is stretched: the pitch ratio over the tempo,
+/// or 1 when the clip plays it as is.
+fn stretch_factor(clip: &Clip) -> f64 {
+    let (tempo, pitch) = clip
+        .audio
+        .stretch
+        .ratios(clip.speed, clip.audio.pitch_semitones);
+    let factor = pitch / tempo;
+    if !factor.is_finite() || factor <= 0.0 || (factor - 1.0).abs() < 1e-9 {
+        1.0
+    } else {
+        factor
+    }
+}
+
+/// Frames in a full chunk at `sample_rate`.
+fn chunk_frames(sample_rate: u32) -> usize {
+    CHUNK_SECS as usize * sample_rate as usize
+}
+
+/// A bars clip's tone, from source time zero.
+#[derive(Clone)]
+struct Tone {
+    bars: BarsGenerator,
+    pcm: Arc<PcmSource>,
+}
+
+/// Audio a clip reads from: up to two consecutive chunks of its source
+/// (stretched by `scale`), or its tone. Source frame `f` is at frame
+/// `f * scale` of the chunks.
+struct ClipSource {
+    head: ChunkAudio,
+    tail: Option<ChunkAudio>,
+    scale: f64,
+}
+
+impl ClipSource {
+    /// `pcm` read as is from frame zero.
+    fn whole(pcm: Arc<PcmSource>) -> Self {
+        Self {
+            head: ChunkAudio {
+                pcm,
+                start: 0,
+                last: true,
+            },
+            tail: None,
+            scale: 1.0,
+        }
+    }
+
+    /// Stereo frame `index`, if it is loaded.
+    #[inline]
+    fn frame(&self, index: usize) -> Option<(f32, f32)> {
+        let chunk = if index < self.head.end() {
+            &self.head
+        } else {
+            self.tail.as_ref()?
+        };
+        let index = index.checked_sub(chunk.start)?;
+        (index < chunk.pcm.frame_count()).then(|| chunk.pcm.frame(index))
+    }
+}
+
+impl SourceCache {
+    /// Chunk `index` of `path` stretched by `factor`, decoding or
+    /// stretching it unless deferred.
+    fn chunk(&mut self, path: &str, factor: f64, index: usize) -> Option<ChunkAudio> {
+        let key = (factor.to_bits(), index);
+        if let Some(chunk) = self.chunks.get_mut(path).and_then(|c| c.get_mut(&key)) {
+            chunk.read = true;
+            return Some(chunk.audio.clone());
+        }
+        if self.deferred || self.failed.contains(path) {
+            return None;
+        }
+        let chunk = if factor == 1.0 {
+            self.decode(path, index)?
+        } else {
+            self.stretch(path, factor, index)?
+        };
+        let audio = chunk.audio.clone();
+        self.chunks
+            .entry(path.to_string())
+            .or_default()
+            .insert(key, chunk);
+        Some(audio)
+    }
+
+    /// Decode chunk `index` of `path`.
+    fn decode(&mut self, path: &str, index: usize) -> Option<Chunk> {
+        match self.loader.load_range(path, chunk_range(index)) {
+            Ok(pcm) if pcm.channels > 0 => Some(Chunk {
+                audio: ChunkAudio {
+                    start: index * chunk_frames(pcm.sample_rate),
+                    last: pcm.frame_count() < chunk_frames(pcm.sample_rate),
+                    pcm,
+                },
+                read: true,
+                next: None,
+            }),
+            Ok(_) => {
+                warn!("Audio source has no channels: {}", path);
+                self.failed.insert(path.to_string());
+                None
+            }
+            Err(e) => {
+                warn!("Audio source unavailable: {}", e);
+                self.failed.insert(path.to_string());
+                None
+            }
+        }
+    }
+
+    /// Stretch chunk `index` of `path` by `factor`, carrying on the
+    /// stretch of the chunk before when it is loaded.
+    fn stretch(&mut self, path: &str, factor: f64, index: usize) -> Option<Chunk> {
+        let source = self.chunk(path, 1.0, index)?;
+        let pcm = &source.pcm;
+        let previous = index
+            .checked_sub(1)
+            .and_then(|before| self.chunks.get(path)?.get(&(factor.to_bits(), before)))
+            .and_then(|chunk| chunk.next.clone());
+        let mut stretcher = previous.map_or_else(
+            || {
+                let channels = pcm.channels as usize;
+                Stretcher::starting_at(channels, pcm.sample_rate, factor, source.start)
+            },
+            |stretcher| *stretcher,
+        );
+        let start = stretcher.position();
+        let mut samples = stretcher.push(&pcm.samples);
+        let next = if source.last {
+            samples.extend(stretcher.finish());
+            None
+        } else {
+            Some(Box::new(stretcher))
+        };
+        Some(Chunk {
+            audio: ChunkAudio {
+                pcm: Arc::new(PcmSource::new(pcm.sample_rate, pcm.channels, samples)),
+                start,
+                last: source.last,
+            },
+            read: true,
+            next,
+        })
+    }
+
+    /// Load what `clip` reads from output sample `from` to `to` into the
+    /// clip.
+    fn load_span(&mut self, clip: &Clip, from: i64, to: i64) {
+        // Steps reading at most half a chunk reach every chunk.
+        let (tempo, _) = clip
+            .audio
+            .stretch
+            .ratios(clip.speed, clip.audio.pitch_semitones);
+        let half_chunk = CHUNK_SECS as f64 * self.sample_rate as f64 / 2.0;
+        let step = (half_chunk / tempo.abs().max(1e-3)).max(BLOCK_FRAMES as f64) as i64;
+        for at in (from..to).step_by(step as usize) {
+            self.clip_source(clip, at, step.min(to - at) as usize);
+        }
+    }
+
+    /// Clear the read marks of all chunks.
+    fn unmark(&mut self) {
+        for chunk in self.chunks.values_mut().flat_map(HashMap::values_mut) {
+            chunk.read = false;
+        }
+    }
+
+    /// Drop the chunks not read since the last eviction (or `unmark`).
+    fn evict(&mut self) {
+        for chunks in self.chunks.values_mut() {
+            chunks.retain(|_, chunk| std::mem::take(&mut chunk.read));
+        }
+        self.chunks.retain(|_, chunks| !chunks.is_empty());
+    }
+
+    /// The audio `clip` reads over `frames` output samples from `offset`
+    /// into the clip, loading and stretching source chunks or synthesizing
+    /// its tone on first use (`None` while deferred and not prepared).
+    fn clip_source(&mut self, clip: &Clip, offset: i64, frames: usize) -> Option<ClipSource> {
+        if let Some(generator) = &clip.generator {
+            let Generator::Bars(bars) = generator else {
+                return None;
+            };
+            return self.tone(clip, bars).map(ClipSource::whole);
+        }
+        let path = &clip.source.path;
+        let (tempo, _) = clip
+            .audio
+            .stretch
+            .ratios(clip.speed, clip.audio.pitch_semitones);
+        let factor = stretch_factor(clip);
+
+        // Source seconds the block reads. A stretched chunk may start a
+        // little before its source chunk's stretched position.
+        let from =
+            clip.source_in.to_seconds_f64() + offset as f64 * tempo / self.sample_rate as f64;
+        let to = from + frames as f64 * tempo / self.sample_rate as f64;
+        let lag = if factor == 1.0 { 0.0 } else { STRETCH_LAG_SECS };
+        let index = chunk_index(from - lag);
+        let head = self.chunk(path, factor, index)?;
+        // The frame after the last one read is interpolated towards.
+        let last_read = (to * head.pcm.sample_rate as f64 * factor) as usize + 1;
+        let tail = if head.last || last_read < head.end() {
+            None
+        } else {
+            self.continuation(path, factor, index, &head)
+        };
+        Some(ClipSource {
+            head,
+            tail,
+            scale: factor,
+        })
+    }
+
+    /// Chunk `index + 1` of `path` stretched by `factor`, made again if it
+    /// does not carry on from `head` (both were started afresh).
+    fn continuation(
+        &mut self,
+        path: &str,
+        factor: f64,
+        index: usize,
+        head: &ChunkAudio,
+    ) -> Option<ChunkAudio> {
+        let next = self.chunk(path, factor, index + 1)?;
+        if next.start == head.end() || self.deferred {
+            return Some(next);
+        }
+        self.chunks
+            .get_mut(path)?
+            .remove(&(factor.to_bits(), index + 1));
+        self.chunk(path, factor, index + 1)
+    }
+
+    /// The tone of a bars clip, long enough to play the clip's range and
+    /// a margin past it. A tone plays at the clip's tempo; pitch settings
+    /// do not apply.
+    fn tone(&mut self, clip: &Clip, bars: &BarsGenerator) -> Option<Arc<PcmSource>> {
+        let (tempo, _) = clip
+            .audio
+            .stretch
+            .ratios(clip.speed, clip.audio.pitch_semitones);
+        let seconds = clip.source_in.to_seconds_f64()
+            + clip.duration.to_seconds_f64() * tempo.max(0.0)
+            + TONE_MARGIN_SECS;
+        let frames = (seconds * self.sample_rate as f64).ceil() as usize;
+        if let Some(tone) = self.tones.get(&clip.id) {
+            if tone.bars == *bars && tone.pcm.frame_count() >= frames {
+                return Some(Arc::clone(&tone.pcm));
+            }
+        }
+        if self.deferred {
+            return None;
+        }
+        let pcm = Arc::new(bars_tone(bars, self.sample_rate, frames));
+        self.tones.insert(
+            clip.id,
+            Tone {
+                bars: bars.clone(),
+                pcm: Arc::clone(&pcm),
+            },
+        );
+        Some(pcm)
+    }
+}
+
+/// `frames` of a bars generator's stereo sine tone at `sample_rate`,
+/// following its frequency and level keyframes without phase jumps.
+fn bars_tone(bars: &BarsGenerator, sample_rate: u32, frames: usize) -> PcmSource {
+    let at =
+        |track: &KeyframeTrack, n: usize| track.evaluate(sample_to_time(n as i64, sample_rate));
+    let animated = bars.tone_frequency.is_animated() || bars.tone_level.is_animated();
+    let mut frequency = at(&bars.tone_frequency, 0);
+    let mut gain = db_to_gain(at(&bars.tone_level, 0) as f32);
+    let mut phase = 0.0f64;
+    let mut samples = Vec::with_capacity(frames * 2);
+    for n in 0..frames {
+        if animated && n > 0 {
+            frequency = at(&bars.tone_frequency, n);
+            gain = db_to_gain(at(&bars.tone_level, n) as f32);
+        }
+        let sample = phase.sin() as f32 * gain;
+        samples.extend_from_slice(&[sample, sample]);
+        phase = (phase + TAU * frequency / sample_rate as f64) % TAU;
+    }
+    PcmSource::new(sample_rate, 2, samples)
+}
+
+/// Renders a sequence's audio tracks through a `Mixer`.
+pub struct TimelineRenderer {
+    sample_rate: u32,
+    cache: SourceCache,
+    /// Interleaved stereo scratch buffer per audio track.
+    track_buffers: Vec<Vec<f32>>,
+}
+
+impl TimelineRenderer {
+    /// Create a renderer producing output at `sample_rate`.
+    pub fn new(sample_rate: u32, loader: Arc<dyn SourceLoader>) -> Self {
+        Self {
+            sample_rate,
+            cache: SourceCache {
+                loader,
+                chunks: HashMap::new(),
+                failed: HashSet::new(),
+                sample_rate,
+                tones: HashMap::new(),
+                deferred: false,
+            },
+            track_buffers: Vec::new(),
+        }
+    }
+
+    /// Defer loading: sources, stretched audio and tones not handed over
+    /// with [`adopt`](Self::adopt) (or loaded by `prepare`) render as
+    /// silence. The real-time render thread never decodes.
+    pub fn set_deferred(&mut self, deferred: bool) {
+        self.cache.deferred = deferred;
+    }
+
+    /// A renderer sharing this one's loader and loaded audio, for running
+    /// [`prepare`](Self::prepare) away from the render thread.
+    pub fn preparer(&self) -> TimelineRenderer {
+        let cache = &self.cache;
+        Self {
+            sample_rate: self.sample_rate,
+            cache: SourceCache {
+                loader: Arc::clone(&cache.loader),
+                chunks: cache.chunks.clone(),
+                failed: cache.failed.clone(),
+                sample_rate: cache.sample_rate,
+                tones: cache.tones.clone(),
+                deferred: false,
+            },
+            track_buffers: Vec::new(),
+        }
+    }
+
+    /// Take over the audio `preparer` loaded, and drop the chunks it
+    /// dropped. Ignored when the loader was replaced since the preparer was
+    /// made.
+    pub fn adopt(&mut self, preparer: TimelineRenderer) {
+        let same_loader = std::ptr::eq(
+            Arc::as_ptr(&self.cache.loader) as *const (),
+            Arc::as_ptr(&preparer.cache.loader) as *const (),
+        );
+        if same_loader {
+            let cache = preparer.cache;
+            self.cache.chunks = cache.chunks;
+            self.cache.failed = cache.failed;
+            self.cache.tones.extend(cache.tones);
+        }
+    }
+
+    /// Output sample rate.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// The loader used to decode clip sources.
+    pub fn loader(&self) -> Arc<dyn SourceLoader> {
+        Arc::clone(&self.cache.loader)
+    }
+
+    /// Replace the source loader (drops all cached sources).
+    pub fn set_loader(&mut self, loader: Arc<dyn SourceLoader>) {
+        self.cache.loader = loader;
+        self.cache.chunks.clear();
+        self.cache.failed.clear();
+    }
+
+    /// Load the audio the sequence plays over `range`: the source chunks,
+    /// stretched chunks and tones its clips read there, so playback does
+    /// not stall on them. Chunks read outside the range are dropped. Loads
+    /// even when deferred.
+    pub fn prepare(&mut self, sequence: &Sequence, range: TimeRange) {
+        self.release_unused(sequence);
+        let deferred = std::mem::replace(&mut self.cache.deferred, false);
+        self.cache.unmark();
+        let sample_rate = self.sample_rate;
+        let start = time_to_sample(range.start, sample_rate);
+        let end = time_to_sample(range.end(), sample_rate);
+        for track in &sequence.audio_tracks {
+            clip_spans(
+                track,
+                sample_rate,
+                start,
+                end,
+                |clip, clip_start, from, to, _| {
+                    self.cache
+                        .load_span(clip, from - clip_start, to - clip_start);
+                },
+            );
+        }
+        self.cache.evict();
+        self.cache.deferred = deferred;
+    }
+
+    /// Drop tones, and chunks of sources or stretch ratios, no clip of
+    /// `sequence` plays any more (after source, speed, pitch or generator
+    /// changes).
+    pub fn release_unused(&mut self, sequence: &Sequence) {
+        let cache = &mut self.cache;
+        cache.tones.retain(|id, tone| {
+            audio_clips(sequence).any(|clip| {
+                clip.id == *id
+                    && matches!(&clip.generator, Some(Generator::Bars(bars)) if *bars == tone.bars)
+            })
+        });
+        let used: HashSet<(&str, u64)> = audio_clips(sequence)
+            .flat_map(|clip| {
+                let path = clip.source.path.as_str();
+                [
+                    (path, 1f64.to_bits()),
+                    (path, stretch_factor(clip).to_bits()),
+                ]
+            })
+            .collect();
+        for (path, chunks) in &mut cache.chunks {
+            chunks.retain(|&(factor, _), _| used.contains(&(path.as_str(), factor)));
+        }
+        cache.chunks.retain(|_, chunks| !chunks.is_empty());
+    }
+
+    /// Render `frame_count` frames starting at output sample `start` into
+    /// `output` (interleaved in the mixer's layout, at least `frame_count`
+    /// frames).
+    ///
+    /// Audio track `i` is mixed through mixer channel `i`; missing mixer
+    /// channels are added, and channel and master inserts follow the
+    /// settings saved on the sequence (chains handed over by an
+    /// `InsertSender` are used as they are). Tracks render in stereo; the
+    /// mixer pans them into its layout.
+    pub fn render_block(
+        &mut self,
+        sequence: &Sequence,
+        mixer: &mut Mixer,
+        start: i64,
+        frame_count: usize,
+        output: &mut [f32],
+    ) {
+        let tracks = &sequence.audio_tracks;
+        while mixer.channel_count() < tracks.len() {
+            mixer.add_channel();
+        }
+        mixer.receive_inserts();
+        if self.track_buffers.len() < tracks.len() {
+            self.track_buffers.resize_with(tracks.len(), Vec::new);
+        }
+
+        let len = frame_count * 2;
+        for (index, (track, buffer)) in tracks.iter().zip(&mut self.track_buffers).enumerate() {
+            mixer.set_automation(index, &track.automation);
+            mixer.set_inserts(index, &track.inserts);
+            if buffer.len() < len {
+                buffer.resize(len, 0.0);
+            }
+            render_track(
+                track,
+                &mut self.cache,
+                self.sample_rate,
+                start,
+                &mut buffer[..len],
+            );
+        }
+
+        mixer.set_master_inserts(&sequence.master_inserts);
+        mixer.set_master_gain_db(sequence.master_gain_db);
+        mixer.set_position(start);
+        mixer.mix_into(&self.track_buffers[..tracks.len()], frame_count, output);
+    }
+
+    /// Render a time range of the sequence offline, returning samples
+    /// interleaved in the mixer's layout. Used by export and tests; no
+    /// device is involved.
+    pub fn render_range(
+        &mut self,
+        sequence: &Sequence,
+        mixer: &mut Mixer,
+        range: TimeRange,
+    ) -> Vec<f32> {
+        let frames = (time_to_sample(range.end(), self.sample_rate)
+            - time_to_sample(range.start, self.sample_rate))
+        .max(0) as usize;
+        let mut output = Vec::with_capacity(frames * mixer.layout().channel_count());
+        self.render_range_with(sequence, mixer, range, |block| {
+            output.extend_from_slice(block)
+        });
+        output
+    }
+
+    /// Render a time range offline, handing each block of interleaved
+    /// samples to `on_block` instead of collecting them. Source chunks are
+    /// dropped once no block reads them.
+    pub fn render_range_with(
+        &mut self,
+        sequence: &Sequence,
+        mixer: &mut Mixer,
+        range: TimeRange,
+        mut on_block: impl FnMut(&[f32]),
+    ) {
+        self.render_range_with_mixer(sequence, mixer, range, |block, _| on_block(block));
+    }
+
+    /// Like [`render_range_with`](Self::render_range_with), also passing
+    /// the mixer so per-bus output can be read after each block.
+    pub fn render_range_with_mixer(
+        &mut self,
+        sequence: &Sequence,
+        mixer: &mut Mixer,
+        range: TimeRange,
+        mut on_block: impl FnMut(&[f32], &Mixer),
+    ) {
+        let start = time_to_sample(range.start, self.sample_rate);
+        let end = time_to_sample(range.end(), self.sample_rate);
+        let total = (end - start).max(0) as usize;
+
+        let channels = mixer.layout().channel_count();
+        let mut block = vec![0.0; BLOCK_FRAMES * channels];
+        let mut done = 0;
+        while done < total {
+            let frames = BLOCK_FRAMES.min(total - done);
+            let out = &mut block[..frames * channels];
+            self.render_block(sequence, mixer, start + done as i64, frames, out);
+            self.cache.evict();
+            on_block(out, mixer);
+            done += frames;
+        }
+    }
+
+    /// Render a clip on its own from start to end (with gain, fades and
+    /// automation, without track or mixer processing).
+    pub fn render_clip(&mut self, clip: &Clip) -> Vec<f32> {
+        let frames = time_to_sample(clip.duration, self.sample_rate).max(0) as usize;
+        let mut output = vec![0.0; frames * 2];
+        if clip.enabled {
+            // Block by block, so automation ramps like in playback.
+            for (i, block) in output.chunks_mut(BLOCK_FRAMES * 2).enumerate() {
+                let offset = (i * BLOCK_FRAMES) as i64;
+                if let Some(source) = self.cache.clip_source(clip, offset, block.len() / 2) {
+                    render_clip(clip, &source, self.sample_rate, offset, None, block);
+                }
+                self.cache.evict();
+            }
+        }
+        output
+    }
+}
+
+/// Every clip on the sequence's audio tracks.
+fn audio_clips(sequence: &Sequence) -> impl Iterator<Item = &Clip> {
+    sequence
+        .audio_tracks
+        .iter()
+        .flat_map(|track| &track.items)
+        .filter_map(|item| match item {
+            TrackItem::Clip(clip) => Some(&**clip),
+            _ => None,
+        })
+}
+
+/// Render one track's clips overlapping `[start, start + output.len() / 2)`.
+fn render_track(
+    track: &Track,
+    cache: &mut SourceCache,
+    sample_rate: u32,
+    start: i64,
+    output: &mut [f32],
+) {
+    output.fill(0.0);
+    if track.muted {
+        return;
+    }
+
+    let end = start + (output.len() / 2) as i64;
+    clip_spans(
+        track,
+        sample_rate,
+        start,
+        end,
+        |clip, clip_start, from, to, crossfade| {
+            let offset = from - clip_start;
+            if let Some(source) = cache.clip_source(clip, offset, (to - from) as usize) {
+                let out = &mut output[((from - start) * 2) as usize..((to - start) * 2) as usize];
+                render_clip(clip, &source, sample_rate, offset, crossfade, out);
+            }
+        },
+    );
+}
+
+/// Call `visit` for each enabled clip of `track` playing within output
+/// samples `[start, end)`, with the sample the clip starts at, the part
+/// `[from, to)` of the range it plays and its crossfade through a
+/// transition.
+fn clip_spans(
+    track: &Track,
+    sample_rate: u32,
+    start: i64,
+    end: i64,
+    mut visit: impl FnMut(&Clip, i64, i64, i64, Option<Crossfade>),
+) {
+    let mut mix =
+        |clip: &Clip, clip_start: i64, from: i64, to: i64, crossfade: Option<Crossfade>| {
+            let from = from.max(start);
+            let to = to.min(end);
+            if clip.enabled && from < to {
+                visit(clip, clip_start, from, to, crossfade);
+            }
+        };
+
+    let mut item_start = RationalTime::ZERO;
+    for (index, item) in track.items.iter().enumerate() {
+        let item_from = time_to_sample(item_start, sample_rate);
+        if item_from >= end {
+            break;
+        }
+        let item_end = item_start + item.duration();
+        let item_to = time_to_sample(item_end, sample_rate);
+        match item {
+            TrackItem::Clip(clip) => mix(clip, item_from, item_from, item_to, None),
+            TrackItem::Transition {
+                transition_name, ..
+            } if item_to > item_from => {
+                let curve = FadeCurve::for_transition(transition_name);
+                let length = item_to - item_from;
+                if let Some(TrackItem::Clip(prev)) = index.checked_sub(1).map(|i| &track.items[i]) {
+                    let prev_start = time_to_sample(item_start - prev.duration, sample_rate);
+                    let crossfade = Crossfade {
+                        curve,
+                        fade_in: false,
+                        start: item_from - prev_start,
+                        length,
+                    };
+                    mix(prev, prev_start, item_from, item_to, Some(crossfade));
+                }
+                if let Some(TrackItem::Clip(next)) = track.items.get(index + 1) {
+                    let crossfade = Crossfade {
+                        curve,
+                        fade_in: true,
+                        start: -length,
+                        length,
+                    };
+                    mix(next, item_to, item_from, item_to, Some(crossfade));
+                }
+            }
+            _ => {}
+        }
+        item_start = item_end;
+    }
+}
+
+/// Gain ramp of a clip playing through a transition.
+#[derive(Clone, Copy)]
+struct Crossfade {
+    curve: FadeCurve,
+    /// True for the incoming clip.
+    fade_in: bool,
+    /// Transition start, in samples relative to the clip start.
+    start: i64,
+    /// Transition length in samples.
+    length: i64,
+}
+
+impl Crossfade {
+    #[inline]
+    fn gain(&self, offset: i64) -> f32 {
+        let t = (offset - self.start) as f32 / self.length as f32;
+        if self.fade_in {
+            self.curve.gain(t)
+        } else {
+            self.curve.gain(1.0 - t)
+        }
+    }
+}
+
+/// Gain of a clip fade at `pos` samples into the fade (0 = silent end).
+#[inline]
+fn fade_gain(fade: &Fade, length: i64, pos: i64) -> f32 {
+    if pos < length {
+        fade.curve.gain(pos as f32 / length as f32)
+    } else {
+        1.0
+    }
+}
+
+/// Resample a clip's source into `output`, starting `offset` output
+/// samples into the clip (negative or past the clip end while in a
+/// transition). The clip's tempo sets the step through the source; a
+/// stretched source is read at the pitch ratio instead.
+///
+/// Clip gain and volume automation are evaluated at both ends of the block
+/// and ramped linearly in between; fades and crossfades are per sample.
+fn render_clip(
+    clip: &Clip,
+    source: &ClipSource,
+    sample_rate: u32,
+    offset: i64,
+    crossfade: Option<Crossfade>,
+    output: &mut [f32],
+) {
+    let (tempo, _) = clip
+        .audio
+        .stretch
+        .ratios(clip.speed, clip.audio.pitch_semitones);
+    let source_rate = source.head.pcm.sample_rate as f64;
+    let step = tempo * source_rate / sample_rate as f64 * source.scale;
+    let base = clip.source_in.to_seconds_f64() * source_rate * source.scale;
+
+    let audio = &clip.audio;
+    let block_frames = output.len() / 2;
+    let level = |at: i64| {
+        let automation = audio.volume.as_ref().map_or(0.0, |track| {
+            track.evaluate(sample_to_time(at, sample_rate)) as f32
+        });
+        db_to_gain(audio.gain_db + automation)
+    };
+    let level_start = level(offset);
+    let level_step = (level(offset + block_frames as i64) - level_start) / block_frames as f32;
+
+    let length = time_to_sample(clip.duration, sample_rate);
+    let fade_in = time_to_sample(audio.fade_in.duration, sample_rate);
+    let fade_out = time_to_sample(audio.fade_out.duration, sample_rate);
+
+    for (n, out) in output.chunks_exact_mut(2).enumerate() {
+        let local = offset + n as i64;
+        let pos = base + local as f64 * step;
+        if pos < 0.0 {
+            continue;
+        }
+        let index = pos.floor() as usize;
+        let Some((l0, r0)) = source.frame(index) else {
+            continue;
+        };
+
+        let mut gain = level_start + level_step * n as f32;
+        let within = local.clamp(0, length);
+        if fade_in > 0 {
+            gain *= fade_gain(&audio.fade_in, fade_in, within);
+        }
+        if fade_out > 0 {
+            gain *= fade_gain(&audio.fade_out, fade_out, length - within);
+        }
+        if let Some(crossfade) = &crossfade {
+            gain *= crossfade.gain(local);
+        }
+
+        let frac = (pos - index as f64) as f32;
+        let (l1, r1) = source.frame(index + 1).unwrap_or((l0, r0));
+        out[0] += (l0 + (l1 - l0) * frac) * gain;
+        out[1] += (r0 + (r1 - r0) * frac) * gain;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proedit_core::{EasingCurve, FrameRate, KeyframeTrack};
+    use proedit_timeline::{ClipRef, Fade, SolidGenerator};
+
+    const RATE: u32 = 48000;
+
+    /// Mixer with unity gain on both sides so outputs equal track sums.
+    fn unity_mixer() -> Mixer {
+        let mut mixer = Mixer::new(1, 4096);
+        mixer.channel_mut(0).unwrap().volume = std::f32::consts::SQRT_2;
+        mixer
+    }
+
+    fn renderer(loader: MemorySourceLoader) -> TimelineRenderer {
+        TimelineRenderer::new(RATE, Arc::new(loader))
+    }
+
+    fn sequence_with(items: Vec<TrackItem>) -> Sequence {
+        let mut seq = Sequence::default();
+        seq.audio_tracks[0].items = items;
+        seq
+    }
+
+    fn clip(path: &str, seconds: i64) -> Clip {
+        Clip::new(path, ClipRef::new(path, RationalTime::new(seconds, 1)))
+    }
+
+    fn constant(value: f32, seconds: usize) -> PcmSource {
+        PcmSource::new(RATE, 2, vec![value; RATE as usize * seconds * 2])
+    }
+
+    fn assert_close(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len());
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() <= e.abs() * 1e-5, "{a} != {e}");
+        }
+    }
+
+    fn render(seq: &Sequence, r: &mut TimelineRenderer, start: i64, frames: usize) -> Vec<f32> {
+        let mut out = vec![0.0; frames * 2];
+        r.render_block(seq, &mut unity_mixer(), start, frames, &mut out);
+        out
+    }
+
+    #[test]
+    fn test_time_sample_conversion() {
+        // 24 fps at 48 kHz is exactly 2000 samples per frame.
+        let t = RationalTime::from_frames(10, FrameRate::FPS_24);
+        assert_eq!(time_to_sample(t, RATE), 20_000);
+        // 29.97 rounds to the nearest sample.
+        let t = RationalTime::from_frames(1, FrameRate::FPS_29_97);
+        assert_eq!(time_to_sample(t, RATE), 1602);
+        assert_eq!(sample_to_time(24_000, RATE), RationalTime::new(1, 2));
+    }
+
+    #[test]
+    fn test_gap_then_clip() {
+        let loader = MemorySourceLoader::new();
+        loader.insert("a.wav", constant(0.5, 1));
+        let seq = sequence_with(vec![
+            TrackItem::Gap {
+                duration: RationalTime::new(1, 100),
+            },
+            TrackItem::from(clip("a.wav", 1)),
+        ]);
+        let mut r = renderer(loader);
+
+        // 480 samples of gap, then the clip.
+        let out = render(&seq, &mut r, 400, 160);
+        assert!(out[..160].iter().all(|s| s.abs() < 1e-6));
+        assert!(out[160..].iter().all(|s| (s - 0.5).abs() < 1e-5));
+    }
+
+    #[test]
+    fn test_disabled_clip_and_muted_track_are_silent() {
+        let loader = MemorySourceLoader::new();
+        loader.insert("a.wav", constant(0.5, 1));
+        let mut c = clip("a.wav", 1);
+        c.enabled = false;
+        let mut seq = sequence_with(vec![TrackItem::from(c)]);
+        let mut r = renderer(loader);
+        assert!(render(&seq, &mut r, 0, 256).iter().all(|s| *s == 0.0));
+
+        seq.audio_tracks[0].items = vec![TrackItem::from(clip("a.wav", 1))];
+        seq.audio_tracks[0].muted = true;
+        assert!(render(&seq, &mut r, 0, 256).iter().all(|s| *s == 0.0));
+    }
+
+    #[test]
+    fn test_source_in_speed_and_resampling() {
+        // Source at half the output rate, value = frame index.
+        let ramp: Vec<f32> = (0..24000).map(|i| i as f32).collect();
+        let loader = MemorySourceLoader::new();
+        loader.insert("ramp.wav", PcmSource::new(RATE / 2, 1, ramp));
+
+        let mut c = clip("ramp.wav", 1);
+        c.source_in = RationalTime::new(1, 10); // source frame 2400
+        let mut seq = sequence_with(vec![TrackItem::from(c.clone())]);
+        let mut r = renderer(loader);
+
+        // Each output sample advances half a source frame.
+        let out = render(&seq, &mut r, 0, 4);
+        let left: Vec<f32> = out.iter().step_by(2).copied().collect();
+        assert_close(&left, &[2400.0, 2400.5, 2401.0, 2401.5]);
+
+        // At 2x speed, one source frame per output sample.
+        c.speed = 2.0;
+        seq.audio_tracks[0].items = vec![TrackItem::from(c)];
+        let out = render(&seq, &mut r, 10, 2);
+        assert_close(&out, &[2410.0, 2410.0, 2411.0, 2411.0]);
+    }
+
+    #[test]
+    fn test_sources_load_in_chunks_around_the_play_range() {
+        /// Serves ranges of a ramp (value = frame index), recording them.
+        struct RangeLoader {
+            ramp: PcmSource,
+            loaded: parking_lot::Mutex<Vec<RationalTime>>,
+        }
+
+        impl SourceLoader for RangeLoader {
+            fn load(&self, path: &str) -> Result<Arc<PcmSource>> {
+                panic!("decoded all of {path:?}");
+            }
+
+            fn load_range(&self, _: &str, range: TimeRange) -> Result<Arc<PcmSource>> {
+                self.loaded.lock().push(range.start);
+                Ok(Arc::new(self.ramp.slice(range)))
+            }
+        }
+
+        let loader = Arc::new(RangeLoader {
+            ramp: PcmSource::new(RATE, 1, (0..RATE * 40).map(|i| i as f32).collect()),
+            loaded: Default::default(),
+        });
+        let mut c = clip("long.wav", 40);
+        c.source_in = RationalTime::new(25, 1);
+        c.duration = RationalTime::new(10, 1);
+        let seq = sequence_with(vec![TrackItem::from(c)]);
+        let mut r = TimelineRenderer::new(RATE, loader.clone());
+
+        // The block around source time 30 s reads across two chunks.
+        let start = 5 * RATE as i64 - 256;
+        let out = render(&seq, &mut r, start, 512);
+        let left: Vec<f32> = out.iter().step_by(2).copied().collect();
+        let expected: Vec<f32> = (0..512).map(|n| (30 * RATE - 256 + n) as f32).collect();
+        assert_close(&left, &expected);
+        let seconds = |s: i64| RationalTime::new(s, 1);
+        assert_eq!(*loader.loaded.lock(), [seconds(20), seconds(30)]);
+
+        // Preparing a later range keeps only the chunk it reads.
+        r.prepare(&seq, TimeRange::new(seconds(6), seconds(2)));
+        let mut kept: Vec<usize> = r.cache.chunks["long.wav"]
+            .keys()
+            .map(|&(_, index)| index)
+            .collect();
+        kept.sort_unstable();
+        assert_eq!(kept, [3]);
+
+        // Offline rendering drops chunks it has moved past.
+        loader.loaded.lock().clear();
+        r.render_range(&seq, &mut unity_mixer(), seq.time_range());
+        assert_eq!(*loader.loaded.lock(), [seconds(20), seconds(30)]);
+        assert_eq!(r.cache.chunks["long.wav"].len(), 1);
+    }
+
+    #[test]
+    fn test_stretch_modes() {
+        use proedit_timeline::StretchMode;
+
+        let sine: Vec<f32> = (0..RATE as usize * 2)
+            .map(|i| (std::f32::consts::TAU * 440.0 * i as f32 / RATE as f32).sin() * 0.5)
+            .collect();
+        let loader = MemorySourceLoader::new();
+        loader.insert("sine.wav", PcmSource::new(RATE, 1, sine));
+        let mut r = renderer(loader);
+        let frequency = |out: &[f32]| {
+            let left: Vec<f32> = out.iter().step_by(2).copied().collect();
+            let crossings = left
+                .windows(2)
+                .filter(|w| w[0] < 0.0 && w[1] >= 0.0)
+                .count();
+            crossings as f32 * RATE as f32 / left.len() as f32
+        };
+
+        // Half a second at double speed.
+        let mut c = clip("sine.wav", 2);
+        c.duration = RationalTime::new(1, 2);
+        c.speed = 2.0;
+        for (mode, semitones, expected, stretched) in [
+            (StretchMode::Varispeed, 12.0, 880.0, false),
+            (StretchMode::PreservePitch, -12.0, 440.0, true),
+            (StretchMode::PitchOnly, -12.0, 220.0, true),
+            // An octave up at double speed needs no stretch.
+            (StretchMode::PitchOnly, 12.0, 880.0, false),
+        ] {
+            c.audio.stretch = mode;
+            c.audio.pitch_semitones = semitones;
+            let seq = sequence_with(vec![TrackItem::from(c.clone())]);
+            r.prepare(&seq, seq.time_range());
+            let out = render(&seq, &mut r, 4800, 9600);
+            let f = frequency(&out);
+            assert!((f - expected).abs() < expected * 0.03, "{mode:?}: {f} Hz");
+            assert!(out.iter().all(|s| s.abs() < 0.6));
+            let chunks = &r.cache.chunks["sine.wav"];
+            let one = 1f64.to_bits();
+            assert_eq!(
+                chunks.keys().any(|&(factor, _)| factor != one),
+                stretched,
+                "{mode:?}"
+            );
+        }
+
+        // Offline rendering of the clip on its own agrees.
+        let clip_out = r.render_clip(&c);
+        assert_eq!(clip_out.len(), RATE as usize);
+        let seq = sequence_with(vec![TrackItem::from(c)]);
+        assert_close(&clip_out[9600..28800], &render(&seq, &mut r, 4800, 9600));
+    }
+
+    #[test]
+    fn test_stretched_chunks_are_seamless_and_shared_across_trims() {
+        use proedit_timeline::StretchMode;
+
+        let sine: Vec<f32> = (0..RATE as usize * 25)
+            .map(|i| (TAU * 440.0 * i as f64 / RATE as f64).sin() as f32 * 0.5)
+            .collect();
+        let loader = MemorySourceLoader::new();
+        loader.insert("sine.wav", PcmSource::new(RATE, 1, sine));
+        let mut r = renderer(loader);
+        let mut c = clip("sine.wav", 8);
+        c.source_in = RationalTime::new(8, 1);
+        c.speed = 0.8;
+        c.audio.stretch = StretchMode::PreservePitch;
+        let seq = sequence_with(vec![TrackItem::from(c.clone())]);
+
+        // Source time 10 s, where the first two chunks meet, plays 2.5 s
+        // into the clip; block by block it plays on without a seam.
+        let range = TimeRange::new(RationalTime::new(24, 10), RationalTime::new(2, 10));
+        let out = r.render_range(&seq, &mut unity_mixer(), range);
+        let left: Vec<f32> = out.iter().step_by(2).copied().collect();
+        assert!(left.windows(2).all(|w| (w[1] - w[0]).abs() < 0.05));
+        for window in left.chunks(480) {
+            let rms = (window.iter().map(|s| s * s).sum::<f32>() / 480.0).sqrt();
+            assert!((rms - 0.3536).abs() < 0.03, "{rms}");
+        }
+
+        // A trim reads the same stretched chunks.
+        let key = (stretch_factor(&c).to_bits(), 1);
+        let stretched = Arc::clone(&r.cache.chunks["sine.wav"][&key].audio.pcm);
+        c.source_in = RationalTime::new(9, 1);
+        c.duration = RationalTime::new(7, 1);
+        let seq = sequence_with(vec![TrackItem::from(c)]);
+        render(&seq, &mut r, 60_000, 512);
+        assert!(Arc::ptr_eq(
+            &stretched,
+            &r.cache.chunks["sine.wav"][&key].audio.pcm
+        ));
+    }
+
+    #[test]
+    fn test_deferred_renderer_plays_prepared_audio_only() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let loads = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&loads);
+        let loader = move |_: &str| {
+            counter.fetch_add(1, Ordering::Relaxed);
+            Ok(Arc::new(constant(0.5, 1)))
+        };
+        let mut r = TimelineRenderer::new(RATE, Arc::new(loader));
+        r.set_deferred(true);
+        let seq = sequence_with(vec![TrackItem::from(clip("a.wav", 1))]);
+
+        // Nothing is decoded on the render path.
+        assert!(render(&seq, &mut r, 0, 64).iter().all(|s| *s == 0.0));
+        assert_eq!(loads.load(Ordering::Relaxed), 0);
+
+        let mut preparer = r.preparer();
+        preparer.prepare(&seq, seq.time_range());
+        r.adopt(preparer);
+        assert_eq!(loads.load(Ordering::Relaxed), 1);
+        assert!(render(&seq, &mut r, 0, 64)
+            .iter()
+            .all(|s| (s - 0.5).abs() < 1e-5));
+
+        // A preparer made before the loader changed is ignored.
+        let preparer = r.preparer();
+        r.set_loader(Arc::new(MemorySourceLoader::new()));
+        r.adopt(preparer);
+        assert!(render(&seq, &mut r, 0, 64).iter().all(|s| *s == 0.0));
+    }
+
+    #[test]
+    fn test_missing_source_is_silent() {
+        let seq = sequence_with(vec![TrackItem::from(clip("missing.wav", 1))]);
+        let mut r = renderer(MemorySourceLoader::new());
+        assert!(render(&seq, &mut r, 0, 64).iter().all(|s| *s == 0.0));
+    }
+
+    #[test]
+    fn test_bars_clip_plays_reference_tone() {
+        // No source is loaded for generator clips.
+        let loader = |path: &str| -> Result<Arc<PcmSource>> {
+            panic!("loaded {path:?}");
+        };
+        let mut r = TimelineRenderer::new(RATE, Arc::new(loader));
+        let mut bars = Clip::generated(
+            "Bars",
+            Generator::Bars(BarsGenerator::default()),
+            RationalTime::new(1, 1),
+        );
+        bars.source_in = RationalTime::new(1, 2);
+        let seq = sequence_with(vec![TrackItem::from(bars.clone())]);
+        r.prepare(&seq, seq.time_range());
+
+        let out = render(&seq, &mut r, 0, 4800);
+        let peak = out.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+        assert!((peak - 0.1).abs() < 1e-3, "peak {peak}");
+        let crossings = out
+            .iter()
+            .step_by(2)
+            .collect::<Vec<_>>()
+            .windows(2)
+            .filter(|w| *w[0] < 0.0 && *w[1] >= 0.0)
+            .count();
+        assert!((99..=101).contains(&crossings), "{crossings} cycles");
+        // Phase continues from the source in point.
+        let phase = TAU * 1000.0 * 0.5;
+        assert!((out[0] - 0.1 * phase.sin() as f32).abs() < 1e-4);
+
+        // The level is keyframeable; other generators are silent.
+        let mut fading = BarsGenerator::default();
+        fading
+            .tone_level
+            .set(RationalTime::new(1, 1), -80.0, EasingCurve::Linear);
+        let mut faded = bars.clone();
+        faded.generator = Some(Generator::Bars(fading));
+        let seq = sequence_with(vec![TrackItem::from(faded)]);
+        let out = render(&seq, &mut r, 40_000, 4800);
+        assert!(out.iter().all(|s| s.abs() < 0.01));
+        assert_eq!(r.cache.tones.len(), 1);
+        bars.generator = Some(Generator::Solid(SolidGenerator::new([1.0; 4])));
+        let seq = sequence_with(vec![TrackItem::from(bars)]);
+        assert!(render(&seq, &mut r, 0, 256).iter().all(|s| *s == 0.0));
+        r.release_unused(&seq);
+        assert!(r.cache.tones.is_empty());
+    }
+
+    #[test]
+    fn test_render_range_matches_blocks() {
+        let ramp: Vec<f32> = (0..48000).map(|i| i as f32 / 48000.0).collect();
+        let loader = MemorySourceLoader::new();
+        loader.insert("ramp.wav", PcmSource::new(RATE, 1, ramp));
+        let seq = sequence_with(vec![TrackItem::from(clip("ramp.wav", 1))]);
+        let mut r = renderer(loader);
+
+        let full = r.render_range(
+            &seq,
+            &mut unity_mixer(),
+            TimeRange::new(RationalTime::ZERO, RationalTime::new(1, 2)),
+        );
+        assert_eq!(full.len(), 48000);
+
+        // Starting at frame 5 (24 fps) lines up with sample 10000 exactly.
+        let start = RationalTime::from_frames(5, FrameRate::FPS_24);
+        let part = r.render_range(
+            &seq,
+            &mut unity_mixer(),
+            TimeRange::new(start, RationalTime::new(1, 10)),
+        );
+        assert_eq!(part.len(), 9600);
+        assert_eq!(&part[..], &full[20000..29600]);
+    }
+
+    #[test]
+    fn test_clip_gain_and_fades() {
+        let loader = MemorySourceLoader::new();
+        loader.insert("a.wav", constant(1.0, 1));
+        let mut c = clip("a.wav", 1);
+        c.audio.gain_db = -6.0;
+        c.audio.fade_in = Fade::new(RationalTime::new(1, 100), FadeCurve::Linear);
+        c.audio.fade_out = Fade::new(RationalTime::new(1, 100), FadeCurve::EqualPower);
+        let seq = sequence_with(vec![TrackItem::from(c)]);
+        let mut r = renderer(loader);
+
+        let gain = db_to_gain(-6.0);
+        // Halfway through the 480-sample linear fade-in.
+        let out = render(&seq, &mut r, 240, 1);
+        assert_close(&out, &[gain * 0.5, gain * 0.5]);
+        // Full level in the middle.
+        let out = render(&seq, &mut r, 24000, 1);
+        assert_close(&out, &[gain, gain]);
+        // Halfway through the equal-power fade-out.
+        let out = render(&seq, &mut r, 48000 - 240, 1);
+        let half = std::f32::consts::FRAC_1_SQRT_2 * gain;
+        assert_close(&out, &[half, half]);
+    }
+
+    #[test]
+    fn test_clip_volume_automation() {
+        let loader = MemorySourceLoader::new();
+        loader.insert("a.wav", constant(1.0, 1));
+        let mut c = clip("a.wav", 1);
+        let mut volume = KeyframeTrack::new("volume");
+        volume.set(RationalTime::ZERO, 0.0, EasingCurve::Hold);
+        volume.set(RationalTime::new(1, 2), -20.0, EasingCurve::Hold);
+        c.audio.volume = Some(volume);
+        let mut seq = sequence_with(vec![
+            TrackItem::Gap {
+                duration: RationalTime::new(1, 4),
+            },
+            TrackItem::from(c),
+        ]);
+        let mut r = renderer(loader);
+
+        // Automation is relative to the clip start, so it follows the clip.
+        assert_close(&render(&seq, &mut r, 12000 + 100, 1), &[1.0, 1.0]);
+        assert_close(&render(&seq, &mut r, 36000 + 100, 1), &[0.1, 0.1]);
+
+        seq.audio_tracks[0].items.remove(0);
+        assert_close(&render(&seq, &mut r, 24000 + 100, 1), &[0.1, 0.1]);
+    }
+
+    #[test]
+    fn test_transition_crossfades_neighbors() {
+        let loader = MemorySourceLoader::new();
+        loader.insert("a.wav", constant(1.0, 2));
+        loader.insert("b.wav", constant(-1.0, 2));
+        let mut a = clip("a.wav", 1);
+        a.source.source_duration = RationalTime::new(2, 1);
+        let mut b = clip("b.wav", 1);
+        b.source.source_duration = RationalTime::new(2, 1);
+        b.source_in = RationalTime::new(1, 2);
+        let seq = sequence_with(vec![
+            TrackItem::from(a),
+            TrackItem::Transition {
+                transition_name: "Constant Gain".into(),
+                duration: RationalTime::new(1, 10),
+                params: Default::default(),
+                easing: Default::default(),
+            },
+            TrackItem::from(b),
+        ]);
+        let mut r = renderer(loader);
+
+        // The transition spans samples 48000..52800: a fades out linearly
+        // while b fades in, so the midpoint cancels out.
+        assert_close(&render(&seq, &mut r, 47999, 1), &[1.0, 1.0]);
+        let quarter = render(&seq, &mut r, 48000 + 1200, 1);
+        assert_close(&quarter, &[0.5, 0.5]);
+        let mid = render(&seq, &mut r, 48000 + 2400, 1);
+        assert!(mid.iter().all(|s| s.abs() < 1e-5));
+        assert_close(&render(&seq, &mut r, 52800, 1), &[-1.0, -1.0]);
+    }
+}
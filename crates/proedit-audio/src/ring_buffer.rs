@@ -109,7 +109,8 @@ impl RingBuffer {
         count
     }
 
-    /// Clear the buffer.
+    /// Discard everything buffered. This moves the read position, so it
+    /// belongs to the consumer side.
     pub fn clear(&self) {
         self.read_pos
             .store(self.write_pos.load(Ordering::Acquire), Ordering::Release);
@@ -0,0 +1,500 @@
+//! Time-stretching and pitch-shifting.
+//!
+//! `time_stretch` changes duration without changing pitch using WSOLA
+//! (waveform-similarity overlap-add): 40 ms Hann grains are laid down at a
+//! fixed output hop, each read from near its nominal source position at
+//! the offset that best continues the previous grain. Detected onsets are
+//! played through 1:1 once they are due at their stretched position, so
+//! drum hits and consonants are neither doubled when stretching nor
+//! skipped when compressing; the stretch catches up in the steady
+//! material around them.
+//!
+//! `Stretcher` runs the same stretch over a source fed a window at a time.
+//!
+//! `pitch_shift` stretches by the pitch ratio and resamples back to the
+//! original length.
+
+use std::f32::consts::PI;
+
+/// Grain length in seconds (the output hop is half of it).
+const GRAIN_SECS: f64 = 0.04;
+/// How far a grain may move from its nominal source position, in seconds.
+const TOLERANCE_SECS: f64 = 0.01;
+/// Longest a `Stretcher`'s output lags behind the source fed to it, in
+/// source seconds.
+pub(crate) const STRETCH_LAG_SECS: f64 = 5.0 * GRAIN_SECS + 2.0 * TOLERANCE_SECS;
+/// Candidate offsets are first tried at this spacing, then refined.
+const SEARCH_STEP: i64 = 4;
+/// Samples skipped between products when comparing grains.
+const COMPARE_STRIDE: usize = 4;
+/// Energy jump (against the preceding blocks) that marks an onset.
+const ONSET_RATIO: f32 = 8.0;
+/// Mean-square level below which nothing counts as an onset (-50 dBFS).
+const ONSET_FLOOR: f32 = 1e-5;
+
+/// Stretch interleaved `samples` to `factor` times their duration,
+/// keeping the pitch. Returns `round(frames * factor)` frames.
+pub fn time_stretch(samples: &[f32], channels: usize, sample_rate: u32, factor: f64) -> Vec<f32> {
+    let channels = channels.max(1);
+    let frames = samples.len() / channels;
+    if frames == 0 || !factor.is_finite() || factor <= 0.0 {
+        return Vec::new();
+    }
+    if (factor - 1.0).abs() < 1e-9 {
+        return samples[..frames * channels].to_vec();
+    }
+    let mut stretcher = Stretcher::new(channels, sample_rate, factor);
+    let mut output = stretcher.push(samples);
+    output.extend(stretcher.finish());
+    output
+}
+
+/// WSOLA time-stretch fed its source a window at a time.
+///
+/// Grain placement, onsets and the overlap-add carry over from one window
+/// to the next, so a source stretched window by window comes out exactly
+/// as if stretched at once, while only a few grains of it are held.
+#[derive(Clone)]
+pub struct Stretcher {
+    channels: usize,
+    factor: f64,
+    hop: usize,
+    tolerance: i64,
+    window: Vec<f32>,
+    /// Onset detection block length.
+    block: usize,
+    /// Interleaved source frames from frame `first` on, and their mono mix.
+    samples: Vec<f32>,
+    mono: Vec<f32>,
+    first: i64,
+    /// Next onset block to measure, and the energy of the ones before it.
+    next_block: usize,
+    energy: Vec<f32>,
+    onsets: Vec<usize>,
+    /// Output frame of the next grain, and the previous grain's source
+    /// start.
+    at: usize,
+    prev: Option<i64>,
+    /// Overlap-added output and window sums from output frame `emitted` on.
+    output: Vec<f32>,
+    norm: Vec<f32>,
+    emitted: usize,
+}
+
+impl Stretcher {
+    /// Stretch by `factor` from the source's first frame.
+    pub fn new(channels: usize, sample_rate: u32, factor: f64) -> Self {
+        Self::starting_at(channels, sample_rate, factor, 0)
+    }
+
+    /// Stretch by `factor` from source frame `first`, placing it where it
+    /// falls in the stretch of the whole source.
+    pub fn starting_at(channels: usize, sample_rate: u32, factor: f64, first: usize) -> Self {
+        let hop = ((sample_rate as f64 * GRAIN_SECS / 2.0) as usize).max(16);
+        let size = 2 * hop;
+        let block = hop / 4;
+        let at = (first as f64 * factor).round() as usize;
+        Self {
+            channels: channels.max(1),
+            factor,
+            hop,
+            tolerance: ((sample_rate as f64 * TOLERANCE_SECS) as i64).max(1),
+            window: (0..size)
+                .map(|n| (PI * n as f32 / size as f32).sin().powi(2))
+                .collect(),
+            block,
+            samples: Vec::new(),
+            mono: Vec::new(),
+            first: first as i64,
+            next_block: first.div_ceil(block),
+            energy: Vec::new(),
+            onsets: Vec::new(),
+            at,
+            prev: None,
+            output: Vec::new(),
+            norm: Vec::new(),
+            emitted: at,
+        }
+    }
+
+    /// Output frame of the next frame `push` or `finish` returns.
+    pub fn position(&self) -> usize {
+        self.emitted
+    }
+
+    /// Feed the next interleaved source frames. Returns the stretched
+    /// frames that are complete; the rest follow with later calls.
+    pub fn push(&mut self, samples: &[f32]) -> Vec<f32> {
+        let channels = self.channels;
+        let samples = &samples[..samples.len() / channels * channels];
+        self.samples.extend_from_slice(samples);
+        self.mono.extend(
+            samples
+                .chunks_exact(channels)
+                .map(|frame| frame.iter().sum::<f32>() / channels as f32),
+        );
+        self.measure(false);
+        // Grains this far short of the measured source see the same source
+        // and onsets as they would with all of it.
+        let margin = 8 * self.hop as i64 + 2 * self.tolerance;
+        let measured = (self.next_block * self.block) as i64;
+        while self.nominal() + margin <= measured {
+            self.grain();
+        }
+        let complete = (self.end() as f64 * self.factor) as usize;
+        let output = self.emit(self.at.min(complete));
+        self.trim();
+        output
+    }
+
+    /// End the source. Returns the remaining stretched frames, for
+    /// `round(frames * factor)` in all.
+    pub fn finish(mut self) -> Vec<f32> {
+        self.measure(true);
+        let end = (self.end() as f64 * self.factor).round() as usize;
+        while self.at < end {
+            self.grain();
+        }
+        self.emit(end)
+    }
+
+    /// Source frame after the last one fed.
+    fn end(&self) -> i64 {
+        self.first + self.mono.len() as i64
+    }
+
+    /// Source frame the next grain nominally starts at.
+    fn nominal(&self) -> i64 {
+        (self.at as f64 / self.factor).round() as i64
+    }
+
+    /// Measure the onset blocks fed so far (and the last, partial block
+    /// once the source has ended): blocks whose energy jumps well above
+    /// the blocks just before them are onsets.
+    fn measure(&mut self, ended: bool) {
+        loop {
+            let from = self.next_block * self.block;
+            let lo = (from as i64 - self.first) as usize;
+            let len = self.block.min(self.mono.len().saturating_sub(lo));
+            if len == 0 || (len < self.block && !ended) {
+                break;
+            }
+            let e = self.mono[lo..lo + len].iter().map(|s| s * s).sum::<f32>() / len as f32;
+            if !self.energy.is_empty() {
+                let mean = self.energy.iter().sum::<f32>() / self.energy.len() as f32;
+                if e > ONSET_FLOOR && e > ONSET_RATIO * mean.max(ONSET_FLOOR / ONSET_RATIO) {
+                    // Only the first block of a rising edge.
+                    if self.onsets.last() != Some(&(from - self.block)) {
+                        self.onsets.push(from);
+                    }
+                }
+            }
+            if self.energy.len() == 4 {
+                self.energy.remove(0);
+            }
+            self.energy.push(e);
+            self.next_block += 1;
+        }
+    }
+
+    /// Lay down the next grain.
+    fn grain(&mut self) {
+        let hop = self.hop as i64;
+        let size = 2 * hop;
+        let factor = self.factor;
+        let tolerance = self.tolerance;
+        let nominal = self.nominal();
+        let onsets = &self.onsets;
+        let next_onset = |from: i64| {
+            let i = onsets.partition_point(|&o| (o as i64) < from);
+            onsets.get(i).map(|&o| o as i64)
+        };
+        let last_onset_before = |at: i64| {
+            let i = onsets.partition_point(|&o| (o as i64) < at);
+            i.checked_sub(1).map_or(-1, |i| onsets[i] as i64)
+        };
+
+        let start = match self.prev {
+            None => nominal,
+            Some(prev) => {
+                let natural = prev + hop;
+                let mut lo = (nominal - tolerance).max(last_onset_before(natural) + 1);
+                let mut hi = nominal + tolerance;
+                match next_onset(natural) {
+                    // Play through an onset once it is due at its stretched
+                    // position, and never jump over one.
+                    Some(c)
+                        if c < natural + size
+                            && (factor < 1.0
+                                || (self.at as i64 + c - natural) as f64
+                                    >= c as f64 * factor - hop as f64) =>
+                    {
+                        natural
+                    }
+                    Some(c) if factor < 1.0 && c < nominal + tolerance + size => natural,
+                    next => {
+                        // Stretching: keep grains clear of an onset that is
+                        // not due yet, repeating the material before it.
+                        if let Some(c) = next.filter(|_| factor > 1.0) {
+                            hi = hi.min(c - size);
+                            if lo > hi {
+                                lo = (hi - 2 * tolerance).max(last_onset_before(natural) + 1);
+                            }
+                        }
+                        if lo > hi {
+                            // Catching up after an onset.
+                            natural
+                        } else {
+                            best_match(&self.mono, self.first, natural, lo, hi, self.hop)
+                        }
+                    }
+                }
+            }
+        };
+
+        let channels = self.channels;
+        let base = self.at - self.emitted;
+        if self.norm.len() < base + self.window.len() {
+            self.norm.resize(base + self.window.len(), 0.0);
+            self.output.resize(self.norm.len() * channels, 0.0);
+        }
+        for (n, &w) in self.window.iter().enumerate() {
+            let o = base + n;
+            self.norm[o] += w;
+            let src = start + n as i64 - self.first;
+            if (0..self.mono.len() as i64).contains(&src) {
+                let src = src as usize * channels;
+                for c in 0..channels {
+                    self.output[o * channels + c] += w * self.samples[src + c];
+                }
+            }
+        }
+        self.prev = Some(start);
+        self.at += self.hop;
+    }
+
+    /// Hand out the output up to frame `upto`, normalized by the window
+    /// sums.
+    fn emit(&mut self, upto: usize) -> Vec<f32> {
+        let frames = upto.saturating_sub(self.emitted);
+        let channels = self.channels;
+        if self.norm.len() < frames {
+            self.norm.resize(frames, 0.0);
+            self.output.resize(frames * channels, 0.0);
+        }
+        let mut output: Vec<f32> = self.output.drain(..frames * channels).collect();
+        for (frame, sum) in output
+            .chunks_exact_mut(channels)
+            .zip(self.norm.drain(..frames))
+        {
+            if sum > 1e-9 {
+                for s in frame {
+                    *s /= sum;
+                }
+            }
+        }
+        self.emitted += frames;
+        output
+    }
+
+    /// Drop the source frames no later grain or measurement reads.
+    fn trim(&mut self) {
+        let hop = self.hop as i64;
+        let nominal = self.nominal();
+        let natural = self.prev.map_or(nominal, |prev| prev + hop);
+        let keep = (nominal - self.tolerance)
+            .min(natural - 4 * hop - 2 * self.tolerance)
+            .min((self.next_block * self.block) as i64);
+        let drop = (keep - self.first).clamp(0, self.mono.len() as i64) as usize;
+        if drop > 0 {
+            self.mono.drain(..drop);
+            self.samples.drain(..drop * self.channels);
+            self.first += drop as i64;
+        }
+    }
+}
+
+/// Shift the pitch of interleaved `samples` by `semitones`, keeping the
+/// duration.
+pub fn pitch_shift(samples: &[f32], channels: usize, sample_rate: u32, semitones: f32) -> Vec<f32> {
+    let channels = channels.max(1);
+    let ratio = 2f64.powf(semitones as f64 / 12.0);
+    let stretched = time_stretch(samples, channels, sample_rate, ratio);
+    let frames = samples.len() / channels;
+    let stretched_frames = stretched.len() / channels;
+    if stretched_frames == 0 {
+        return vec![0.0; frames * channels];
+    }
+
+    let mut output = vec![0.0; frames * channels];
+    for (i, frame) in output.chunks_exact_mut(channels).enumerate() {
+        let pos = i as f64 * ratio;
+        let index = (pos as usize).min(stretched_frames - 1);
+        let next = (index + 1).min(stretched_frames - 1);
+        let frac = (pos - index as f64).clamp(0.0, 1.0) as f32;
+        for (c, out) in frame.iter_mut().enumerate() {
+            let a = stretched[index * channels + c];
+            let b = stretched[next * channels + c];
+            *out = a + (b - a) * frac;
+        }
+    }
+    output
+}
+
+/// Start in `[lo, hi]` whose first `len` samples best match the natural
+/// continuation at `natural`: a coarse pass every `SEARCH_STEP` samples,
+/// then a fine pass around the best. `mono` starts at source frame `first`.
+fn best_match(mono: &[f32], first: i64, natural: i64, lo: i64, hi: i64, len: usize) -> i64 {
+    let at = |index: i64| {
+        usize::try_from(index - first)
+            .ok()
+            .and_then(|i| mono.get(i))
+    };
+    let score = |candidate: i64| {
+        let (mut dot, mut energy) = (0.0_f32, 0.0_f32);
+        for n in (0..len as i64).step_by(COMPARE_STRIDE) {
+            let (Some(&t), Some(&c)) = (at(natural + n), at(candidate + n)) else {
+                continue;
+            };
+            dot += t * c;
+            energy += c * c;
+        }
+        if energy > 0.0 {
+            dot / energy.sqrt()
+        } else {
+            0.0
+        }
+    };
+    let pick = |candidates: &mut dyn Iterator<Item = i64>| {
+        candidates
+            .map(|c| (c, score(c)))
+            .fold(
+                (lo, f32::MIN),
+                |best, (c, s)| if s > best.1 { (c, s) } else { best },
+            )
+            .0
+    };
+    let coarse = pick(&mut (lo..=hi).step_by(SEARCH_STEP as usize));
+    pick(&mut ((coarse - SEARCH_STEP + 1).max(lo)..=(coarse + SEARCH_STEP - 1).min(hi)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 16000;
+
+    fn sine(freq: f32, frames: usize, amplitude: f32) -> Vec<f32> {
+        (0..frames)
+            .map(|i| (2.0 * PI * freq * i as f32 / RATE as f32).sin() * amplitude)
+            .collect()
+    }
+
+    /// Frequency from upward zero crossings, skipping the edges.
+    fn frequency(mono: &[f32]) -> f32 {
+        let body = &mono[mono.len() / 10..mono.len() * 9 / 10];
+        let crossings: Vec<usize> = body
+            .windows(2)
+            .enumerate()
+            .filter(|(_, w)| w[0] < 0.0 && w[1] >= 0.0)
+            .map(|(i, _)| i)
+            .collect();
+        let cycles = crossings.len() - 1;
+        let span = crossings[cycles] - crossings[0];
+        cycles as f32 * RATE as f32 / span as f32
+    }
+
+    fn rms(samples: &[f32]) -> f32 {
+        (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn test_stretch_keeps_pitch_and_level() {
+        let input = sine(440.0, RATE as usize, 0.5);
+        for factor in [1.5, 0.75] {
+            let output = time_stretch(&input, 1, RATE, factor);
+            assert_eq!(output.len(), (RATE as f64 * factor).round() as usize);
+            let f = frequency(&output);
+            assert!((f - 440.0).abs() < 440.0 * 0.02, "{factor}: {f} Hz");
+            let level = rms(&output[output.len() / 10..output.len() * 9 / 10]);
+            assert!((level - rms(&input)).abs() < 0.05, "{factor}: {level}");
+        }
+    }
+
+    #[test]
+    fn test_pitch_shift_keeps_length() {
+        // Stereo, both channels shifted alike.
+        let mono = sine(300.0, RATE as usize, 0.5);
+        let input: Vec<f32> = mono.iter().flat_map(|&s| [s, s]).collect();
+        let output = pitch_shift(&input, 2, RATE, 12.0);
+        assert_eq!(output.len(), input.len());
+        let left: Vec<f32> = output.iter().step_by(2).copied().collect();
+        let f = frequency(&left);
+        assert!((f - 600.0).abs() < 600.0 * 0.02, "{f} Hz");
+        assert!(output.chunks(2).all(|f| f[0] == f[1]));
+    }
+
+    #[test]
+    fn test_transients_are_not_doubled_or_skipped() {
+        // Clicks every 250 ms over a quiet tone.
+        let mut input = sine(200.0, RATE as usize * 2, 0.01);
+        let clicks: Vec<usize> = (1..8).map(|i| i * RATE as usize / 4).collect();
+        for &c in &clicks {
+            for s in &mut input[c..c + 24] {
+                *s = 0.9;
+            }
+        }
+        let find = |signal: &[f32]| {
+            let mut found: Vec<usize> = Vec::new();
+            for (i, s) in signal.iter().enumerate() {
+                if *s > 0.5 && found.last().is_none_or(|&l| i > l + 200) {
+                    found.push(i);
+                }
+            }
+            found
+        };
+        assert_eq!(find(&input), clicks);
+
+        for factor in [2.0, 0.6] {
+            let output = time_stretch(&input, 1, RATE, factor);
+            let found = find(&output);
+            assert_eq!(found.len(), clicks.len(), "{factor}: {found:?}");
+            // Each click lands within a grain of its stretched position.
+            for (&f, &c) in found.iter().zip(&clicks) {
+                let expected = c as f64 * factor;
+                assert!(
+                    (f as f64 - expected).abs() < RATE as f64 * GRAIN_SECS,
+                    "{factor}: {found:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_stretching_in_windows_matches_stretching_at_once() {
+        // Stereo tone with clicks, so onsets span the window edges.
+        let mut mono = sine(330.0, RATE as usize * 2, 0.1);
+        for c in (1..8).map(|i| i * RATE as usize / 4 - 5) {
+            mono[c..c + 24].fill(0.9);
+        }
+        let input: Vec<f32> = mono.iter().flat_map(|&s| [s, -s]).collect();
+        for factor in [1.6, 0.7] {
+            let whole = time_stretch(&input, 2, RATE, factor);
+            let mut stretcher = Stretcher::new(2, RATE, factor);
+            let mut windowed = Vec::new();
+            for window in input.chunks(2 * 3001) {
+                windowed.extend(stretcher.push(window));
+            }
+            windowed.extend(stretcher.finish());
+            assert_eq!(windowed, whole, "{factor}");
+        }
+    }
+
+    #[test]
+    fn test_unity_and_empty() {
+        let input = sine(440.0, 1000, 0.5);
+        assert_eq!(time_stretch(&input, 1, RATE, 1.0), input);
+        assert!(time_stretch(&[], 2, RATE, 1.5).is_empty());
+        assert!(time_stretch(&input, 1, RATE, 0.0).is_empty());
+    }
+}
@@ -0,0 +1,104 @@
+//! Minimal streaming writer for 32-bit float WAV files.
+
+use proedit_core::Result;
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// KSDATAFORMAT_SUBTYPE_IEEE_FLOAT.
+const IEEE_FLOAT_GUID: [u8; 16] = [
+    0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71,
+];
+
+/// Writes interleaved f32 samples, patching the chunk sizes on `finish`.
+///
+/// More than two channels use WAVE_FORMAT_EXTENSIBLE so the speaker
+/// assignment (`channel_mask`) travels with the file.
+pub(crate) struct WavWriter {
+    file: BufWriter<File>,
+    header_bytes: u32,
+    data_bytes: u32,
+}
+
+impl WavWriter {
+    pub(crate) fn create(
+        path: &Path,
+        sample_rate: u32,
+        channels: u16,
+        channel_mask: u32,
+    ) -> Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        let extensible = channels > 2;
+        let fmt_bytes: u32 = if extensible { 40 } else { 16 };
+        let block_align = channels as u32 * 4;
+        file.write_all(b"RIFF")?;
+        file.write_all(&(20 + fmt_bytes).to_le_bytes())?;
+        file.write_all(b"WAVEfmt ")?;
+        file.write_all(&fmt_bytes.to_le_bytes())?;
+        let format: u16 = if extensible { 0xFFFE } else { 3 }; // IEEE float
+        file.write_all(&format.to_le_bytes())?;
+        file.write_all(&channels.to_le_bytes())?;
+        file.write_all(&sample_rate.to_le_bytes())?;
+        file.write_all(&(sample_rate * block_align).to_le_bytes())?;
+        file.write_all(&(block_align as u16).to_le_bytes())?;
+        file.write_all(&32u16.to_le_bytes())?;
+        if extensible {
+            file.write_all(&22u16.to_le_bytes())?;
+            file.write_all(&32u16.to_le_bytes())?;
+            file.write_all(&channel_mask.to_le_bytes())?;
+            file.write_all(&IEEE_FLOAT_GUID)?;
+        }
+        file.write_all(b"data")?;
+        file.write_all(&0u32.to_le_bytes())?;
+        Ok(Self {
+            file,
+            header_bytes: 28 + fmt_bytes,
+            data_bytes: 0,
+        })
+    }
+
+    pub(crate) fn write(&mut self, samples: &[f32]) -> Result<()> {
+        for sample in samples {
+            self.file.write_all(&sample.to_le_bytes())?;
+        }
+        self.data_bytes = self.data_bytes.saturating_add(samples.len() as u32 * 4);
+        Ok(())
+    }
+
+    /// Patch the chunk sizes and flush.
+    pub(crate) fn finish(mut self) -> Result<()> {
+        self.file.seek(SeekFrom::Start(4))?;
+        self.file
+            .write_all(&(self.data_bytes + self.header_bytes - 8).to_le_bytes())?;
+        self.file
+            .seek(SeekFrom::Start(self.header_bytes as u64 - 4))?;
+        self.file.write_all(&self.data_bytes.to_le_bytes())?;
+        self.file.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extensible_header() {
+        let path = std::env::temp_dir().join(format!("proedit-wav-{}.wav", std::process::id()));
+        let mut writer = WavWriter::create(&path, 48000, 6, 0x3F).unwrap();
+        writer.write(&[0.5; 12]).unwrap();
+        writer.finish().unwrap();
+
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let u16_at = |i: usize| u16::from_le_bytes([bytes[i], bytes[i + 1]]);
+        let u32_at = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+        assert_eq!(bytes.len(), 68 + 48);
+        assert_eq!(u32_at(4), bytes.len() as u32 - 8);
+        assert_eq!(u16_at(20), 0xFFFE);
+        assert_eq!(u16_at(22), 6);
+        assert_eq!(u32_at(40), 0x3F);
+        assert_eq!(&bytes[60..64], b"data");
+        assert_eq!(u32_at(64), 48);
+    }
+}
@@ -1,6 +1,8 @@
 //! Color space definitions and RGB↔XYZ transforms.
 #![allow(clippy::excessive_precision)]
 
+use crate::error::ColorError;
+use crate::transfer::TransferFunction;
 use serde::{Deserialize, Serialize};
 
 /// Supported color spaces.
@@ -75,6 +77,18 @@ impl ColorSpace {
         matches!(self, Self::LinearSRGB | Self::ACEScg)
     }
 
+    /// Transfer function used to encode values in this space.
+    pub fn transfer_function(&self) -> TransferFunction {
+        match self {
+            Self::SRGB => TransferFunction::SRGB,
+            Self::Rec709 => TransferFunction::Rec709,
+            Self::Rec2020 => TransferFunction::Rec709, // Rec2020 uses similar OETF
+            Self::DciP3 => TransferFunction::Gamma(2.6),
+            Self::ACEScct => TransferFunction::Linear, // ACEScct has its own log, simplified
+            Self::ACEScg | Self::LinearSRGB => TransferFunction::Linear,
+        }
+    }
+
     /// Display name.
     pub fn name(&self) -> &str {
         match self {
@@ -98,6 +112,25 @@ impl ColorSpace {
     }
 }
 
+impl TryFrom<proedit_core::ColorSpace> for ColorSpace {
+    type Error = ColorError;
+
+    fn try_from(space: proedit_core::ColorSpace) -> Result<Self, ColorError> {
+        use proedit_core::ColorSpace as Core;
+        match space {
+            Core::Srgb => Ok(Self::SRGB),
+            Core::LinearSrgb => Ok(Self::LinearSRGB),
+            Core::Rec709 => Ok(Self::Rec709),
+            Core::Rec2020 => Ok(Self::Rec2020),
+            Core::DciP3 => Ok(Self::DciP3),
+            Core::AcesCg => Ok(Self::ACEScg),
+            Core::DisplayP3 | Core::Aces2065 => {
+                Err(ColorError::UnsupportedSpace(format!("{space:?}")))
+            }
+        }
+    }
+}
+
 /// Apply a 3x3 matrix to an RGB triplet.
 fn mat3_mul(m: &[[f32; 3]; 3], v: [f32; 3]) -> [f32; 3] {
     [
@@ -153,6 +186,20 @@ mod tests {
         assert_eq!(ColorSpace::ACEScg.name(), "ACEScg");
     }
 
+    #[test]
+    fn test_from_core_space() {
+        use proedit_core::ColorSpace as Core;
+        assert_eq!(
+            ColorSpace::try_from(Core::AcesCg).unwrap(),
+            ColorSpace::ACEScg
+        );
+        assert_eq!(
+            ColorSpace::try_from(Core::LinearSrgb).unwrap(),
+            ColorSpace::LinearSRGB
+        );
+        assert!(ColorSpace::try_from(Core::Aces2065).is_err());
+    }
+
     #[test]
     fn test_is_linear() {
         assert!(ColorSpace::LinearSRGB.is_linear());
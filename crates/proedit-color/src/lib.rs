@@ -7,6 +7,7 @@ pub mod lut;
 pub mod pipeline;
 pub mod tonemapping;
 pub mod transfer;
+pub mod working;
 
 pub use color_space::{convert_3x3, ColorSpace};
 pub use error::ColorError;
@@ -15,3 +16,4 @@ pub use lut::{Lut1D, Lut3D};
 pub use pipeline::{ColorOp, ColorPipeline};
 pub use tonemapping::ToneMapOperator;
 pub use transfer::TransferFunction;
+pub use working::WorkingSpace;
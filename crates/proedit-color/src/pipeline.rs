@@ -39,9 +39,9 @@ impl ColorPipeline {
         // Input → working (via XYZ)
         if self.input_space != self.working_space {
             if !self.input_space.is_linear() {
-                self.ops.push(ColorOp::TransferToLinear(transfer_for_space(
-                    &self.input_space,
-                )));
+                self.ops.push(ColorOp::TransferToLinear(
+                    self.input_space.transfer_function(),
+                ));
             }
             // Input RGB → XYZ
             self.ops
@@ -60,10 +60,9 @@ impl ColorPipeline {
                 self.output_space.from_xyz_matrix(),
             ));
             if !self.output_space.is_linear() {
-                self.ops
-                    .push(ColorOp::TransferFromLinear(transfer_for_space(
-                        &self.output_space,
-                    )));
+                self.ops.push(ColorOp::TransferFromLinear(
+                    self.output_space.transfer_function(),
+                ));
             }
         }
     }
@@ -106,17 +105,6 @@ fn mat3_mul(m: &[[f32; 3]; 3], v: [f32; 3]) -> [f32; 3] {
     ]
 }
 
-fn transfer_for_space(space: &ColorSpace) -> TransferFunction {
-    match space {
-        ColorSpace::SRGB => TransferFunction::SRGB,
-        ColorSpace::Rec709 => TransferFunction::Rec709,
-        ColorSpace::Rec2020 => TransferFunction::Rec709, // Rec2020 uses similar OETF
-        ColorSpace::DciP3 => TransferFunction::Gamma(2.6),
-        ColorSpace::ACEScct => TransferFunction::Linear, // ACEScct has its own log, simplified
-        _ => TransferFunction::Linear,
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
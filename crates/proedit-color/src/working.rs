@@ -0,0 +1,142 @@
+//! Working space for the render path.
+//!
+//! Sources are converted into a scene-linear working space in a float pixel
+//! format, composited and filtered there, and only encoded for a display
+//! or an export at the end. Blending, blurs and dissolves then act on light
+//! rather than on display code values, and values above 1.0 survive until
+//! the output transform.
+//!
+//! The working space contributes its primaries only: pixels are always
+//! stored linear, whatever the space's own transfer function is.
+
+use crate::color_space::{convert_3x3, ColorSpace};
+use proedit_core::{FrameBuffer, PixelFormat, ProEditError, Result};
+use rayon::prelude::*;
+
+/// A linear working space and the float format frames are held in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WorkingSpace {
+    space: ColorSpace,
+    format: PixelFormat,
+}
+
+impl WorkingSpace {
+    /// Create a working space with `space`'s primaries, storing frames as
+    /// `format` (`Rgba16F` or `Rgba32F`).
+    pub fn new(space: ColorSpace, format: PixelFormat) -> Result<Self> {
+        if !matches!(format, PixelFormat::Rgba16F | PixelFormat::Rgba32F) {
+            return Err(ProEditError::UnsupportedFormat(format!(
+                "Working frames must be Rgba16F or Rgba32F, got {format:?}"
+            )));
+        }
+        Ok(Self { space, format })
+    }
+
+    /// The working primaries.
+    pub fn space(&self) -> ColorSpace {
+        self.space
+    }
+
+    /// The pixel format of working frames.
+    pub fn format(&self) -> PixelFormat {
+        self.format
+    }
+
+    /// Convert a straight-alpha colour encoded in `source` to working space.
+    pub fn color_to_working(&self, rgba: [f32; 4], source: ColorSpace) -> [f32; 4] {
+        let tf = source.transfer_function();
+        let linear = [0, 1, 2].map(|c| tf.to_linear(rgba[c]));
+        let [r, g, b] = convert_3x3(linear, &source, &self.space);
+        [r, g, b, rgba[3]]
+    }
+
+    /// Encode a straight-alpha working-space colour for `output`.
+    pub fn color_from_working(&self, rgba: [f32; 4], output: ColorSpace) -> [f32; 4] {
+        let tf = output.transfer_function();
+        let rgb = convert_3x3([rgba[0], rgba[1], rgba[2]], &self.space, &output);
+        let [r, g, b] = rgb.map(|v| tf.from_linear(v));
+        [r, g, b, rgba[3]]
+    }
+
+    /// Convert an RGBA `frame` encoded in `source` to a working frame.
+    pub fn to_working(&self, frame: &FrameBuffer, source: ColorSpace) -> Result<FrameBuffer> {
+        let mut pixels = frame.read_rgba_f32()?;
+        pixels
+            .par_iter_mut()
+            .for_each(|px| *px = self.color_to_working(*px, source));
+        FrameBuffer::from_rgba_f32(frame.width, frame.height, self.format, &pixels)
+    }
+
+    /// Encode a working `frame` for `output` as an RGBA frame of `format`.
+    /// 8-bit output is clamped; float output keeps out-of-range values.
+    pub fn to_output(
+        &self,
+        frame: &FrameBuffer,
+        output: ColorSpace,
+        format: PixelFormat,
+    ) -> Result<FrameBuffer> {
+        let mut pixels = frame.read_rgba_f32()?;
+        pixels
+            .par_iter_mut()
+            .for_each(|px| *px = self.color_from_working(*px, output));
+        FrameBuffer::from_rgba_f32(frame.width, frame.height, format, &pixels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(format: PixelFormat, rgba: [f32; 4]) -> FrameBuffer {
+        FrameBuffer::from_rgba_f32(4, 2, format, &[rgba; 8]).unwrap()
+    }
+
+    #[test]
+    fn test_rejects_non_float_format() {
+        assert!(WorkingSpace::new(ColorSpace::LinearSRGB, PixelFormat::Rgba8).is_err());
+        assert!(WorkingSpace::new(ColorSpace::ACEScg, PixelFormat::Rgba16F).is_ok());
+    }
+
+    #[test]
+    fn test_srgb_round_trip() {
+        let source = FrameBuffer::test_pattern(16, 2);
+        for space in [
+            ColorSpace::LinearSRGB,
+            ColorSpace::ACEScg,
+            ColorSpace::Rec2020,
+        ] {
+            let working = WorkingSpace::new(space, PixelFormat::Rgba32F).unwrap();
+            let linear = working.to_working(&source, ColorSpace::SRGB).unwrap();
+            assert_eq!(linear.format, PixelFormat::Rgba32F);
+            let back = working
+                .to_output(&linear, ColorSpace::SRGB, PixelFormat::Rgba8)
+                .unwrap();
+            assert_eq!(back.primary_plane().data, source.primary_plane().data);
+        }
+    }
+
+    #[test]
+    fn test_working_values_are_linear() {
+        let working = WorkingSpace::new(ColorSpace::LinearSRGB, PixelFormat::Rgba16F).unwrap();
+        let grey = solid(PixelFormat::Rgba8, [0.5, 0.5, 0.5, 1.0]);
+        let linear = working.to_working(&grey, ColorSpace::SRGB).unwrap();
+        let px = linear.read_rgba_f32().unwrap()[0];
+        // sRGB code value 128 is about 21.6% of the light of white.
+        assert!((px[0] - 0.2158).abs() < 1e-3, "{px:?}");
+        assert_eq!(px[3], 1.0);
+    }
+
+    #[test]
+    fn test_float_output_keeps_highlights() {
+        let working = WorkingSpace::new(ColorSpace::LinearSRGB, PixelFormat::Rgba32F).unwrap();
+        let hot = solid(PixelFormat::Rgba32F, [4.0, 1.0, 0.0, 1.0]);
+        let linear_out = working
+            .to_output(&hot, ColorSpace::LinearSRGB, PixelFormat::Rgba16F)
+            .unwrap();
+        assert_eq!(linear_out.read_rgba_f32().unwrap()[0], [4.0, 1.0, 0.0, 1.0]);
+        let display = working
+            .to_output(&hot, ColorSpace::SRGB, PixelFormat::Rgba8)
+            .unwrap();
+        assert_eq!(&display.primary_plane().row(0)[..4], &[255, 255, 0, 255]);
+    }
+}
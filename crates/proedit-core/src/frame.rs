@@ -2,6 +2,8 @@
 //!
 //! Designed for efficient memory usage on systems with limited RAM.
 
+use crate::error::{ProEditError, Result};
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use smallvec::SmallVec;
 use std::sync::Arc;
@@ -45,6 +47,16 @@ impl PixelFormat {
         }
     }
 
+    /// Whether this is a packed RGBA format.
+    pub fn is_rgba(self) -> bool {
+        matches!(self, Self::Rgba8 | Self::Rgba16F | Self::Rgba32F)
+    }
+
+    /// Whether samples are floating point (and may exceed 0..1).
+    pub fn is_float(self) -> bool {
+        matches!(self, Self::Rgba16F | Self::Rgba32F | Self::Gray16F)
+    }
+
     /// Number of planes for this format.
     pub fn plane_count(self) -> usize {
         match self {
@@ -256,6 +268,162 @@ impl FrameBuffer {
 
         frame
     }
+
+    /// Create an RGBA frame from `pixels` (see [`FrameBuffer::write_rgba_f32`]).
+    pub fn from_rgba_f32(
+        width: u32,
+        height: u32,
+        format: PixelFormat,
+        pixels: &[[f32; 4]],
+    ) -> Result<Self> {
+        let mut frame = Self::new(width, height, format);
+        frame.write_rgba_f32(pixels)?;
+        Ok(frame)
+    }
+
+    /// Read the pixels of an RGBA frame as `f32`, row by row.
+    ///
+    /// 8-bit samples map to 0..1; float samples are returned as stored.
+    pub fn read_rgba_f32(&self) -> Result<Vec<[f32; 4]>> {
+        self.check_rgba()?;
+        let width = self.width as usize;
+        let mut pixels = vec![[0.0; 4]; width * self.height as usize];
+        if width == 0 {
+            return Ok(pixels);
+        }
+        let format = self.format;
+        let plane = self.primary_plane();
+        let bpp = format.bytes_per_pixel();
+        pixels
+            .par_chunks_mut(width)
+            .zip(plane.data.par_chunks(plane.stride))
+            .for_each(|(row, src)| {
+                for (px, bytes) in row.iter_mut().zip(src.chunks_exact(bpp)) {
+                    *px = decode_rgba(format, bytes);
+                }
+            });
+        Ok(pixels)
+    }
+
+    /// Write `pixels` (as read by [`FrameBuffer::read_rgba_f32`]) into this
+    /// RGBA frame. 8-bit frames are clamped and rounded.
+    pub fn write_rgba_f32(&mut self, pixels: &[[f32; 4]]) -> Result<()> {
+        self.check_rgba()?;
+        let width = self.width as usize;
+        if pixels.len() != width * self.height as usize {
+            return Err(ProEditError::InvalidParameter(format!(
+                "{} pixels for a {}x{} frame",
+                pixels.len(),
+                self.width,
+                self.height
+            )));
+        }
+        if width == 0 {
+            return Ok(());
+        }
+        let format = self.format;
+        let bpp = format.bytes_per_pixel();
+        let plane = self.primary_plane_mut();
+        let stride = plane.stride;
+        plane
+            .data
+            .par_chunks_mut(stride)
+            .zip(pixels.par_chunks(width))
+            .for_each(|(dst, row)| {
+                for (bytes, px) in dst.chunks_exact_mut(bpp).zip(row) {
+                    encode_rgba(format, *px, bytes);
+                }
+            });
+        Ok(())
+    }
+
+    fn check_rgba(&self) -> Result<()> {
+        if self.format.is_rgba() {
+            Ok(())
+        } else {
+            Err(ProEditError::UnsupportedFormat(format!(
+                "Expected an RGBA frame, got {:?}",
+                self.format
+            )))
+        }
+    }
+}
+
+fn decode_rgba(format: PixelFormat, bytes: &[u8]) -> [f32; 4] {
+    match format {
+        PixelFormat::Rgba8 => [0, 1, 2, 3].map(|c| bytes[c] as f32 / 255.0),
+        PixelFormat::Rgba16F => {
+            [0, 1, 2, 3].map(|c| f16_to_f32(u16::from_le_bytes([bytes[c * 2], bytes[c * 2 + 1]])))
+        }
+        _ => [0, 1, 2, 3].map(|c| f32::from_le_bytes(bytes[c * 4..c * 4 + 4].try_into().unwrap())),
+    }
+}
+
+fn encode_rgba(format: PixelFormat, px: [f32; 4], bytes: &mut [u8]) {
+    match format {
+        PixelFormat::Rgba8 => {
+            for (b, v) in bytes.iter_mut().zip(px) {
+                *b = (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+            }
+        }
+        PixelFormat::Rgba16F => {
+            for (b, v) in bytes.chunks_exact_mut(2).zip(px) {
+                b.copy_from_slice(&f32_to_f16(v).to_le_bytes());
+            }
+        }
+        _ => {
+            for (b, v) in bytes.chunks_exact_mut(4).zip(px) {
+                b.copy_from_slice(&v.to_le_bytes());
+            }
+        }
+    }
+}
+
+/// Convert an IEEE 754 half (as bits) to `f32`.
+pub fn f16_to_f32(half: u16) -> f32 {
+    let sign = if half & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exp = ((half >> 10) & 0x1f) as u32;
+    let mant = (half & 0x3ff) as u32;
+    match exp {
+        0 => sign * mant as f32 * (2.0f32).powi(-24),
+        0x1f if mant == 0 => sign * f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => f32::from_bits(((half as u32 & 0x8000) << 16) | ((exp + 112) << 23) | (mant << 13)),
+    }
+}
+
+/// Convert `f32` to IEEE 754 half bits, rounding to nearest even.
+pub fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exp = ((bits >> 23) & 0xff) as i32;
+    let mant = bits & 0x7f_ffff;
+    if exp == 0xff {
+        return sign | 0x7c00 | if mant != 0 { 0x200 } else { 0 };
+    }
+    let e = exp - 127 + 15;
+    if e >= 0x1f {
+        return sign | 0x7c00;
+    }
+    // Drop `shift` mantissa bits with round-half-to-even; a carry into the
+    // exponent is still the right answer.
+    let (kept, shift) = if e <= 0 {
+        if e < -10 {
+            return sign;
+        }
+        (mant | 0x80_0000, (14 - e) as u32)
+    } else {
+        (((e as u32) << 23) | mant, 13)
+    };
+    let half = kept >> shift;
+    let rem = kept & ((1 << shift) - 1);
+    let halfway = 1 << (shift - 1);
+    let rounded = if rem > halfway || (rem == halfway && half & 1 == 1) {
+        half + 1
+    } else {
+        half
+    };
+    sign | rounded as u16
 }
 
 /// Arc-wrapped frame buffer for shared ownership.
@@ -291,4 +459,39 @@ mod tests {
         let row = frame.primary_plane().row(0);
         assert_eq!(row[0..4], [255, 255, 255, 255]);
     }
+
+    #[test]
+    fn test_half_float_conversion() {
+        for v in [0.0, 1.0, -2.5, 0.5, 65504.0, 6.1035156e-5, 5.9604645e-8] {
+            assert_eq!(f16_to_f32(f32_to_f16(v)), v, "{v}");
+        }
+        assert_eq!(f32_to_f16(1.0), 0x3c00);
+        assert_eq!(f32_to_f16(1e6), 0x7c00);
+        // 1 + 2^-11 is halfway between 1 and the next half; ties go even.
+        assert_eq!(f32_to_f16(1.0 + 2f32.powi(-11)), 0x3c00);
+        assert_eq!(f32_to_f16(1.0 + 3.0 * 2f32.powi(-11)), 0x3c02);
+        assert!(f16_to_f32(0x7e00).is_nan());
+    }
+
+    #[test]
+    fn test_rgba_f32_round_trip() {
+        let source = FrameBuffer::test_pattern(24, 3);
+        let pixels = source.read_rgba_f32().unwrap();
+        assert_eq!(pixels[0], [1.0; 4]);
+        assert_eq!(pixels[23], [0.0, 0.0, 0.0, 1.0]);
+
+        let mut hdr = pixels.clone();
+        hdr[5] = [4.0, 0.25, -0.5, 1.0];
+        for format in [PixelFormat::Rgba16F, PixelFormat::Rgba32F] {
+            let frame = FrameBuffer::from_rgba_f32(24, 3, format, &hdr).unwrap();
+            assert_eq!(frame.read_rgba_f32().unwrap(), hdr);
+        }
+        let eight = FrameBuffer::from_rgba_f32(24, 3, PixelFormat::Rgba8, &pixels).unwrap();
+        assert_eq!(eight.primary_plane().data, source.primary_plane().data);
+
+        let yuv = FrameBuffer::new(4, 4, PixelFormat::Yuv420P);
+        assert!(yuv.read_rgba_f32().is_err());
+        let mut frame = FrameBuffer::new(4, 4, PixelFormat::Rgba8);
+        assert!(frame.write_rgba_f32(&[[0.0; 4]; 3]).is_err());
+    }
 }
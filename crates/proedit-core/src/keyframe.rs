@@ -98,6 +98,24 @@ pub enum EasingCurve {
     Bezier(CubicBezier),
 }
 
+impl EasingCurve {
+    /// Map linear progress `t` (0 to 1) through the curve.
+    pub fn apply(self, t: f64) -> f64 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Self::Hold => {
+                if t >= 1.0 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Self::Linear => t,
+            Self::Bezier(bezier) => bezier.evaluate(t),
+        }
+    }
+}
+
 // ── Keyframe ────────────────────────────────────────────────────
 
 /// A single keyframe at a point in time.
@@ -137,7 +155,7 @@ impl Keyframe {
 ///
 /// Keyframes are kept sorted by time. Interpolation between keyframes
 /// uses the easing curve of the earlier keyframe.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct KeyframeTrack {
     /// Human-readable parameter name.
     pub name: String,
@@ -225,15 +243,7 @@ impl KeyframeTrack {
         }
 
         let t = ((time.to_seconds_f64() - t_start) / span).clamp(0.0, 1.0);
-
-        match a.easing {
-            EasingCurve::Hold => a.value,
-            EasingCurve::Linear => a.value + (b.value - a.value) * t,
-            EasingCurve::Bezier(bezier) => {
-                let eased_t = bezier.evaluate(t);
-                a.value + (b.value - a.value) * eased_t
-            }
-        }
+        a.value + (b.value - a.value) * a.easing.apply(t)
     }
 
     /// Get all keyframes (read-only).
@@ -348,6 +358,16 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_easing_apply() {
+        assert_eq!(EasingCurve::Linear.apply(0.25), 0.25);
+        assert_eq!(EasingCurve::Linear.apply(1.5), 1.0);
+        assert_eq!(EasingCurve::Hold.apply(0.99), 0.0);
+        assert_eq!(EasingCurve::Hold.apply(1.0), 1.0);
+        let ease_in = EasingCurve::Bezier(CubicBezier::EASE_IN);
+        assert!(ease_in.apply(0.25) < 0.25);
+    }
+
     #[test]
     fn test_keyframe_track_clamp_edges() {
         let mut track = KeyframeTrack::new("test");
@@ -5,12 +5,14 @@
 //! - Color and color space management
 //! - Frame buffers and pixel formats
 //! - Geometric primitives
+//! - Effect parameter values
 
 pub mod color;
 pub mod error;
 pub mod frame;
 pub mod geometry;
 pub mod keyframe;
+pub mod param;
 pub mod time;
 
 pub use color::{Color, ColorConfig, ColorSpace, TransferFunction};
@@ -18,6 +20,7 @@ pub use error::{ProEditError, Result};
 pub use frame::{FrameBuffer, FrameId, FramePlane, PixelFormat, SharedFrameBuffer};
 pub use geometry::{Rect, Transform2D, Vec2};
 pub use keyframe::{CubicBezier, EasingCurve, Keyframe, KeyframeTrack};
+pub use param::{ParamValue, ParamValues};
 pub use time::{FrameRate, RationalTime, TimeRange};
 
 /// Memory budget constants for 8GB M1 Mac
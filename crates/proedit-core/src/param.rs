@@ -0,0 +1,66 @@
+//! Effect parameter values.
+//!
+//! Shared by the effects library, which describes and reads parameters,
+//! and the timeline, which stores the values chosen for each effect.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Effect parameter types.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ParamValue {
+    Float(f32),
+    Int(i32),
+    Bool(bool),
+    Color([f32; 4]),
+    Vec2([f32; 2]),
+    /// Name of one of a choice parameter's options.
+    Choice(String),
+}
+
+impl ParamValue {
+    /// Numeric value as `f32`.
+    pub fn as_f32(&self) -> Option<f32> {
+        match *self {
+            Self::Float(v) => Some(v),
+            Self::Int(v) => Some(v as f32),
+            _ => None,
+        }
+    }
+
+    /// Integer value (floats are rounded).
+    pub fn as_i32(&self) -> Option<i32> {
+        match *self {
+            Self::Int(v) => Some(v),
+            Self::Float(v) => Some(v.round() as i32),
+            _ => None,
+        }
+    }
+
+    /// RGBA colour value.
+    pub fn as_color(&self) -> Option<[f32; 4]> {
+        match *self {
+            Self::Color(c) => Some(c),
+            _ => None,
+        }
+    }
+
+    /// 2D vector value.
+    pub fn as_vec2(&self) -> Option<[f32; 2]> {
+        match *self {
+            Self::Vec2(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Option name of a choice value.
+    pub fn as_choice(&self) -> Option<&str> {
+        match self {
+            Self::Choice(name) => Some(name),
+            _ => None,
+        }
+    }
+}
+
+/// Collection of parameter values.
+pub type ParamValues = HashMap<String, ParamValue>;
@@ -0,0 +1,124 @@
+//! Separable Gaussian blur.
+
+use crate::cpu::Rgba;
+use rayon::prelude::*;
+
+/// Gaussian blur processor.
+pub struct GaussianBlurProcessor;
+
+impl GaussianBlurProcessor {
+    /// Normalized 1D kernel reaching `radius` pixels each side.
+    pub fn kernel(radius: f32, sigma: f32) -> Vec<f32> {
+        let r = radius.max(0.0).ceil() as i32;
+        let sigma2 = 2.0 * sigma.max(0.01).powi(2);
+        let mut kernel: Vec<f32> = (-r..=r).map(|d| (-(d * d) as f32 / sigma2).exp()).collect();
+        let sum: f32 = kernel.iter().sum();
+        for k in &mut kernel {
+            *k /= sum;
+        }
+        kernel
+    }
+
+    /// Blur `pixels` (w*h, straight alpha) in place, clamping at the edges.
+    ///
+    /// Colour is blurred premultiplied so transparent pixels don't bleed
+    /// their colour into the result.
+    pub fn apply(pixels: &mut [Rgba], w: u32, h: u32, radius: f32, sigma: f32) {
+        let (w, h) = (w as usize, h as usize);
+        if radius < 0.5 || w == 0 || h == 0 {
+            return;
+        }
+        let kernel = Self::kernel(radius, sigma);
+        let r = (kernel.len() / 2) as isize;
+
+        pixels.par_iter_mut().for_each(|[red, green, blue, a]| {
+            *red *= *a;
+            *green *= *a;
+            *blue *= *a;
+        });
+
+        // Horizontal pass
+        let src = pixels.to_vec();
+        pixels
+            .par_chunks_mut(w)
+            .zip(src.par_chunks(w))
+            .for_each(|(row, src)| {
+                for (x, out) in row.iter_mut().enumerate() {
+                    let mut acc = [0.0; 4];
+                    for (k, &weight) in kernel.iter().enumerate() {
+                        let sx = (x as isize + k as isize - r).clamp(0, w as isize - 1);
+                        for (a, s) in acc.iter_mut().zip(src[sx as usize]) {
+                            *a += s * weight;
+                        }
+                    }
+                    *out = acc;
+                }
+            });
+
+        // Vertical pass
+        let src = pixels.to_vec();
+        pixels.par_chunks_mut(w).enumerate().for_each(|(y, row)| {
+            for (x, out) in row.iter_mut().enumerate() {
+                let mut acc = [0.0; 4];
+                for (k, &weight) in kernel.iter().enumerate() {
+                    let sy = (y as isize + k as isize - r).clamp(0, h as isize - 1);
+                    for (a, s) in acc.iter_mut().zip(src[sy as usize * w + x]) {
+                        *a += s * weight;
+                    }
+                }
+                *out = acc;
+            }
+        });
+
+        pixels.par_iter_mut().for_each(|[red, green, blue, a]| {
+            if *a > 1e-6 {
+                *red /= *a;
+                *green /= *a;
+                *blue /= *a;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kernel_is_normalized_and_symmetric() {
+        let kernel = GaussianBlurProcessor::kernel(5.0, 1.5);
+        assert_eq!(kernel.len(), 11);
+        assert!((kernel.iter().sum::<f32>() - 1.0).abs() < 1e-5);
+        for i in 0..5 {
+            assert!((kernel[i] - kernel[10 - i]).abs() < 1e-7);
+        }
+        assert!(kernel[5] > kernel[4]);
+    }
+
+    #[test]
+    fn test_impulse_spreads_symmetrically() {
+        let (w, h) = (9u32, 9u32);
+        let mut pixels = vec![[0.0, 0.0, 0.0, 1.0]; 81];
+        pixels[40] = [1.0, 1.0, 1.0, 1.0];
+        GaussianBlurProcessor::apply(&mut pixels, w, h, 3.0, 1.0);
+
+        let total: f32 = pixels.iter().map(|p| p[0]).sum();
+        assert!((total - 1.0).abs() < 1e-4, "energy {total}");
+        assert!(pixels[40][0] < 0.5);
+        assert!((pixels[39][0] - pixels[41][0]).abs() < 1e-6);
+        assert!((pixels[31][0] - pixels[49][0]).abs() < 1e-6);
+        assert!((pixels[39][0] - pixels[31][0]).abs() < 1e-6);
+        assert!(pixels.iter().all(|p| (p[3] - 1.0).abs() < 1e-5));
+    }
+
+    #[test]
+    fn test_transparent_colour_does_not_bleed() {
+        // Opaque white next to transparent red.
+        let mut pixels = vec![[1.0, 1.0, 1.0, 1.0], [1.0, 0.0, 0.0, 0.0]];
+        GaussianBlurProcessor::apply(&mut pixels, 2, 1, 1.0, 1.0);
+        for p in &pixels {
+            assert!((p[1] - 1.0).abs() < 1e-5, "{p:?}");
+        }
+        assert!(pixels[1][3] > 0.0 && pixels[1][3] < 1.0);
+    }
+}
@@ -64,7 +64,7 @@ pub struct ChromaKeyProcessor;
 
 impl ChromaKeyProcessor {
     /// Convert RGB pixel to YCbCr.
-    fn rgb_to_ycbcr(r: f32, g: f32, b: f32) -> [f32; 3] {
+    pub fn rgb_to_ycbcr(r: f32, g: f32, b: f32) -> [f32; 3] {
         let y = 0.299 * r + 0.587 * g + 0.114 * b;
         let cb = -0.168_736 * r - 0.331_264 * g + 0.5 * b;
         let cr = 0.5 * r - 0.418_688 * g - 0.081_312 * b;
@@ -0,0 +1,53 @@
+//! Pixel access for the CPU effect paths.
+//!
+//! Effects work on tightly packed, straight-alpha RGBA `f32` pixels (0..1
+//! for 8-bit frames, unbounded scene-linear values for float frames); these
+//! helpers move them in and out of `FrameBuffer`s, honouring row strides.
+//! `Rgba8`, `Rgba16F` and `Rgba32F` frames are supported.
+
+use proedit_core::{FrameBuffer, Result};
+
+/// One RGBA pixel.
+pub type Rgba = [f32; 4];
+
+/// Read a frame's pixels, row by row.
+pub fn read_rgba(frame: &FrameBuffer) -> Result<Vec<Rgba>> {
+    frame.read_rgba_f32()
+}
+
+/// Write `pixels` (as read by `read_rgba`) into `frame`. 8-bit frames are
+/// clamped and rounded.
+pub fn write_rgba(frame: &mut FrameBuffer, pixels: &[Rgba]) -> Result<()> {
+    frame.write_rgba_f32(pixels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proedit_core::PixelFormat;
+
+    #[test]
+    fn test_round_trip_8bit_and_float() {
+        let source = FrameBuffer::test_pattern(24, 3);
+        let pixels = read_rgba(&source).unwrap();
+        assert_eq!(pixels.len(), 72);
+        assert_eq!(pixels[0], [1.0, 1.0, 1.0, 1.0]);
+        assert_eq!(pixels[23], [0.0, 0.0, 0.0, 1.0]);
+
+        let mut float = FrameBuffer::new(24, 3, PixelFormat::Rgba32F);
+        write_rgba(&mut float, &pixels).unwrap();
+        assert_eq!(read_rgba(&float).unwrap(), pixels);
+
+        let mut eight = FrameBuffer::new(24, 3, PixelFormat::Rgba8);
+        write_rgba(&mut eight, &pixels).unwrap();
+        assert_eq!(eight.primary_plane().data, source.primary_plane().data);
+    }
+
+    #[test]
+    fn test_rejects_planar_and_wrong_size() {
+        let yuv = FrameBuffer::new(4, 4, PixelFormat::Yuv420P);
+        assert!(read_rgba(&yuv).is_err());
+        let mut frame = FrameBuffer::new(4, 4, PixelFormat::Rgba8);
+        assert!(write_rgba(&mut frame, &[[0.0; 4]; 3]).is_err());
+    }
+}
@@ -0,0 +1,103 @@
+//! Procedural film grain.
+//!
+//! Grain is value noise on a lattice of `size`-pixel cells, hashed from
+//! the cell coordinates and a seed so a given seed always produces the
+//! same pattern. It is strongest in the midtones, as with real film.
+
+use crate::cpu::Rgba;
+use rayon::prelude::*;
+
+/// Film grain processor.
+pub struct FilmGrainProcessor;
+
+impl FilmGrainProcessor {
+    /// Noise in -1..1 for lattice point (`x`, `y`).
+    fn lattice(x: i64, y: i64, seed: u32) -> f32 {
+        let mut h = (x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+            ^ (y as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+            ^ (seed as u64).wrapping_mul(0x1656_67B1_9E37_79F9);
+        h ^= h >> 33;
+        h = h.wrapping_mul(0xFF51_AFD7_ED55_8CCD);
+        h ^= h >> 33;
+        (h >> 40) as f32 / (1u64 << 23) as f32 - 1.0
+    }
+
+    /// Noise in -1..1 at pixel (`x`, `y`), bilinear between lattice points.
+    pub fn noise(x: u32, y: u32, size: f32, seed: u32) -> f32 {
+        let size = size.max(0.01);
+        let fx = x as f32 / size;
+        let fy = y as f32 / size;
+        let (x0, y0) = (fx.floor(), fy.floor());
+        let (tx, ty) = (fx - x0, fy - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        let top = Self::lattice(x0, y0, seed) * (1.0 - tx) + Self::lattice(x0 + 1, y0, seed) * tx;
+        let bottom =
+            Self::lattice(x0, y0 + 1, seed) * (1.0 - tx) + Self::lattice(x0 + 1, y0 + 1, seed) * tx;
+        top * (1.0 - ty) + bottom * ty
+    }
+
+    /// Add grain of `intensity` (0..1) to `pixels` (w*h) in place.
+    pub fn apply(pixels: &mut [Rgba], w: u32, intensity: f32, size: f32, seed: u32) {
+        if intensity <= 0.0 || w == 0 {
+            return;
+        }
+        pixels
+            .par_chunks_mut(w as usize)
+            .enumerate()
+            .for_each(|(y, row)| {
+                for (x, px) in row.iter_mut().enumerate() {
+                    let luma = (0.2126 * px[0] + 0.7152 * px[1] + 0.0722 * px[2]).clamp(0.0, 1.0);
+                    let response = 0.25 + 3.0 * luma * (1.0 - luma);
+                    let grain =
+                        Self::noise(x as u32, y as u32, size, seed) * intensity * 0.25 * response;
+                    for c in &mut px[..3] {
+                        *c += grain;
+                    }
+                }
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grain_is_seeded_and_zero_mean() {
+        let grey = vec![[0.5, 0.5, 0.5, 1.0]; 64 * 64];
+        let mut a = grey.clone();
+        let mut b = grey.clone();
+        let mut c = grey.clone();
+        FilmGrainProcessor::apply(&mut a, 64, 0.5, 1.0, 7);
+        FilmGrainProcessor::apply(&mut b, 64, 0.5, 1.0, 7);
+        FilmGrainProcessor::apply(&mut c, 64, 0.5, 1.0, 8);
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+
+        let mean = a.iter().map(|p| p[0]).sum::<f32>() / a.len() as f32;
+        assert!((mean - 0.5).abs() < 0.01, "mean {mean}");
+        assert!(a.iter().any(|p| (p[0] - 0.5).abs() > 0.05));
+        assert!(a.iter().all(|p| p[3] == 1.0 && p[0] == p[1]));
+    }
+
+    #[test]
+    fn test_zero_intensity_is_identity() {
+        let mut pixels = vec![[0.25, 0.5, 0.75, 1.0]; 16];
+        FilmGrainProcessor::apply(&mut pixels, 4, 0.0, 2.0, 1);
+        assert!(pixels.iter().all(|p| *p == [0.25, 0.5, 0.75, 1.0]));
+    }
+
+    #[test]
+    fn test_larger_grain_is_smoother() {
+        let step = |size: f32| {
+            (0..63)
+                .map(|x| {
+                    (FilmGrainProcessor::noise(x, 0, size, 3)
+                        - FilmGrainProcessor::noise(x + 1, 0, size, 3))
+                    .abs()
+                })
+                .sum::<f32>()
+        };
+        assert!(step(4.0) < step(1.0) * 0.5);
+    }
+}
@@ -1,29 +1,38 @@
 //! ProEdit Effects - GPU effects library and CPU processing pipelines
 //!
 //! Provides video effects, transitions, chroma keying, masking,
-//! optical flow, motion blur, and frame interpolation.
+//! optical flow, motion blur, frame interpolation, and resampling.
+//!
+//! Every `VideoEffect` renders on the CPU (the reference implementation,
+//! used for golden-image tests and when no GPU adapter is usable); effects
+//! with a shader also expose a `GpuEffect`.
 
+pub mod blur;
 pub mod chroma_key;
+pub mod cpu;
+pub mod film_grain;
 pub mod frame_interp;
 pub mod mask;
 pub mod motion_blur;
 pub mod optical_flow;
+pub mod resample;
 pub mod transition;
 pub mod transitions;
-
-use proedit_core::Result;
+pub mod vignette;
+
+use blur::GaussianBlurProcessor;
+use chroma_key::{ChromaKeyParams, ChromaKeyProcessor};
+use cpu::{read_rgba, write_rgba};
+use film_grain::FilmGrainProcessor;
+use motion_blur::RSMBParams;
+use proedit_core::{FrameBuffer, ProEditError, Result, TransferFunction, Transform2D, Vec2};
 use proedit_gpu::GpuTexture;
+use rayon::prelude::*;
+use resample::{EdgeMode, ResampleFilter, Resampler};
 use serde::{Deserialize, Serialize};
+use vignette::VignetteProcessor;
 
-/// Effect parameter types.
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum ParamValue {
-    Float(f32),
-    Int(i32),
-    Bool(bool),
-    Color([f32; 4]),
-    Vec2([f32; 2]),
-}
+pub use proedit_core::{ParamValue, ParamValues};
 
 /// Effect parameter descriptor.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,12 +42,151 @@ pub struct ParamDescriptor {
     pub default: ParamValue,
     pub min: Option<ParamValue>,
     pub max: Option<ParamValue>,
+    /// Option names of a choice parameter, whose values are
+    /// `ParamValue::Choice`.
+    #[serde(default)]
+    pub choices: Vec<String>,
 }
 
-/// Collection of parameter values.
-pub type ParamValues = std::collections::HashMap<String, ParamValue>;
+/// A descriptor with an optional `(min, max)` range and no choices.
+fn descriptor(
+    name: &str,
+    display_name: &str,
+    default: ParamValue,
+    range: Option<(ParamValue, ParamValue)>,
+) -> ParamDescriptor {
+    let (min, max) = range.unzip();
+    ParamDescriptor {
+        name: name.into(),
+        display_name: display_name.into(),
+        default,
+        min,
+        max,
+        choices: Vec::new(),
+    }
+}
+
+fn float_descriptor(
+    name: &str,
+    display_name: &str,
+    default: f32,
+    min: f32,
+    max: f32,
+) -> ParamDescriptor {
+    descriptor(
+        name,
+        display_name,
+        ParamValue::Float(default),
+        Some((ParamValue::Float(min), ParamValue::Float(max))),
+    )
+}
+
+fn int_descriptor(
+    name: &str,
+    display_name: &str,
+    default: i32,
+    min: i32,
+    max: i32,
+) -> ParamDescriptor {
+    descriptor(
+        name,
+        display_name,
+        ParamValue::Int(default),
+        Some((ParamValue::Int(min), ParamValue::Int(max))),
+    )
+}
+
+fn bool_descriptor(name: &str, display_name: &str, default: bool) -> ParamDescriptor {
+    descriptor(name, display_name, ParamValue::Bool(default), None)
+}
+
+fn color_descriptor(name: &str, display_name: &str, default: [f32; 4]) -> ParamDescriptor {
+    descriptor(name, display_name, ParamValue::Color(default), None)
+}
+
+fn vec2_descriptor(name: &str, display_name: &str, default: [f32; 2]) -> ParamDescriptor {
+    descriptor(name, display_name, ParamValue::Vec2(default), None)
+}
+
+/// A parameter that picks one of `choices` by name.
+fn choice_descriptor(
+    name: &str,
+    display_name: &str,
+    default: &str,
+    choices: &[&str],
+) -> ParamDescriptor {
+    ParamDescriptor {
+        choices: choices.iter().map(|c| c.to_string()).collect(),
+        ..descriptor(name, display_name, ParamValue::Choice(default.into()), None)
+    }
+}
+
+/// The value of `name`: from `values` if set, else the descriptor default.
+fn param<'a>(
+    descriptors: &'a [ParamDescriptor],
+    values: &'a ParamValues,
+    name: &str,
+) -> Option<&'a ParamValue> {
+    values.get(name).or_else(|| {
+        descriptors
+            .iter()
+            .find(|d| d.name == name)
+            .map(|d| &d.default)
+    })
+}
+
+fn float_param(descriptors: &[ParamDescriptor], values: &ParamValues, name: &str) -> f32 {
+    param(descriptors, values, name)
+        .and_then(ParamValue::as_f32)
+        .unwrap_or(0.0)
+}
+
+fn color_param(descriptors: &[ParamDescriptor], values: &ParamValues, name: &str) -> [f32; 4] {
+    param(descriptors, values, name)
+        .and_then(ParamValue::as_color)
+        .unwrap_or([0.0, 0.0, 0.0, 1.0])
+}
+
+fn int_param(descriptors: &[ParamDescriptor], values: &ParamValues, name: &str) -> i32 {
+    param(descriptors, values, name)
+        .and_then(ParamValue::as_i32)
+        .unwrap_or(0)
+}
+
+fn vec2_param(descriptors: &[ParamDescriptor], values: &ParamValues, name: &str) -> [f32; 2] {
+    param(descriptors, values, name)
+        .and_then(ParamValue::as_vec2)
+        .unwrap_or([0.0, 0.0])
+}
+
+/// The option chosen for `name`, falling back to the default when the
+/// value is not one of the descriptor's choices.
+fn choice_param<'a>(
+    descriptors: &'a [ParamDescriptor],
+    values: &'a ParamValues,
+    name: &str,
+) -> &'a str {
+    let Some(descriptor) = descriptors.iter().find(|d| d.name == name) else {
+        return "";
+    };
+    values
+        .get(name)
+        .and_then(ParamValue::as_choice)
+        .filter(|choice| descriptor.choices.iter().any(|c| c == choice))
+        .or_else(|| descriptor.default.as_choice())
+        .unwrap_or("")
+}
+
+fn bool_param(descriptors: &[ParamDescriptor], values: &ParamValues, name: &str) -> bool {
+    matches!(
+        param(descriptors, values, name),
+        Some(ParamValue::Bool(true))
+    )
+}
 
 /// Trait for video effects.
+///
+/// The CPU path is required and is the reference the GPU path is held to.
 pub trait VideoEffect: Send + Sync {
     /// Get the effect name.
     fn name(&self) -> &str;
@@ -46,6 +194,31 @@ pub trait VideoEffect: Send + Sync {
     /// Get parameter descriptors.
     fn params(&self) -> &[ParamDescriptor];
 
+    /// Render the effect on the CPU. `input` and `output` must have the
+    /// same size and be `Rgba8`, `Rgba16F` or `Rgba32F` (formats may differ).
+    /// Parameters missing from `params` take their defaults.
+    fn render_cpu(
+        &self,
+        input: &FrameBuffer,
+        output: &mut FrameBuffer,
+        params: &ParamValues,
+    ) -> Result<()>;
+
+    /// The GPU implementation, if the effect has one.
+    fn gpu(&self) -> Option<&dyn GpuEffect> {
+        None
+    }
+
+    /// Render on the CPU into a new frame in `input`'s format.
+    fn apply_cpu(&self, input: &FrameBuffer, params: &ParamValues) -> Result<FrameBuffer> {
+        let mut output = FrameBuffer::new(input.width, input.height, input.format);
+        self.render_cpu(input, &mut output, params)?;
+        Ok(output)
+    }
+}
+
+/// GPU implementation of a video effect.
+pub trait GpuEffect: Send + Sync {
     /// Render the effect.
     fn render(
         &self,
@@ -57,6 +230,16 @@ pub trait VideoEffect: Send + Sync {
     ) -> Result<()>;
 }
 
+fn check_sizes(input: &FrameBuffer, output: &FrameBuffer) -> Result<()> {
+    if (input.width, input.height) != (output.width, output.height) {
+        return Err(ProEditError::InvalidParameter(format!(
+            "Frame is {}x{}, expected {}x{}",
+            output.width, output.height, input.width, input.height
+        )));
+    }
+    Ok(())
+}
+
 /// Built-in effects registry.
 pub struct EffectsRegistry {
     effects: Vec<Box<dyn VideoEffect>>,
@@ -71,6 +254,7 @@ impl EffectsRegistry {
                 Box::new(GaussianBlurEffect::new()),
                 Box::new(FilmGrainEffect::new()),
                 Box::new(VignetteEffect::new()),
+                Box::new(TransformEffect::new()),
             ],
         }
     }
@@ -96,14 +280,13 @@ impl Default for EffectsRegistry {
 }
 
 // ---------------------------------------------------------------------------
-// Built-in effect adapters (stub GPU render; real shaders added later)
+// Built-in effect adapters (CPU paths; GPU shaders added later)
 // ---------------------------------------------------------------------------
 
 /// Chroma key (green/blue screen) effect adapter.
 ///
-/// Wraps the CPU-based `ChromaKeyProcessor` as a `VideoEffect`.
-/// The `render` method is currently a stub — the GPU shader pipeline
-/// will be connected in a future pass.
+/// Wraps the CPU-based `ChromaKeyProcessor` as a `VideoEffect`: keyed
+/// pixels become transparent and spill is removed from the rest.
 pub struct ChromaKeyEffect {
     params: Vec<ParamDescriptor>,
 }
@@ -118,34 +301,10 @@ impl ChromaKeyEffect {
     pub fn new() -> Self {
         Self {
             params: vec![
-                ParamDescriptor {
-                    name: "key_color".into(),
-                    display_name: "Key Color".into(),
-                    default: ParamValue::Color([0.0, 1.0, 0.0, 1.0]),
-                    min: None,
-                    max: None,
-                },
-                ParamDescriptor {
-                    name: "tolerance".into(),
-                    display_name: "Tolerance".into(),
-                    default: ParamValue::Float(0.35),
-                    min: Some(ParamValue::Float(0.0)),
-                    max: Some(ParamValue::Float(1.0)),
-                },
-                ParamDescriptor {
-                    name: "softness".into(),
-                    display_name: "Softness".into(),
-                    default: ParamValue::Float(0.1),
-                    min: Some(ParamValue::Float(0.0)),
-                    max: Some(ParamValue::Float(1.0)),
-                },
-                ParamDescriptor {
-                    name: "spill_suppression".into(),
-                    display_name: "Spill Suppression".into(),
-                    default: ParamValue::Float(0.6),
-                    min: Some(ParamValue::Float(0.0)),
-                    max: Some(ParamValue::Float(1.0)),
-                },
+                color_descriptor("key_color", "Key Color", [0.0, 1.0, 0.0, 1.0]),
+                float_descriptor("tolerance", "Tolerance", 0.35, 0.0, 1.0),
+                float_descriptor("softness", "Softness", 0.1, 0.0, 1.0),
+                float_descriptor("spill_suppression", "Spill Suppression", 0.6, 0.0, 1.0),
             ],
         }
     }
@@ -160,20 +319,67 @@ impl VideoEffect for ChromaKeyEffect {
         &self.params
     }
 
-    fn render(
+    fn render_cpu(
         &self,
-        _device: &wgpu::Device,
-        _queue: &wgpu::Queue,
-        _input: &GpuTexture,
-        _output: &GpuTexture,
-        _params: &ParamValues,
+        input: &FrameBuffer,
+        output: &mut FrameBuffer,
+        values: &ParamValues,
     ) -> Result<()> {
-        // Stub — GPU chroma key shader will be wired here.
-        Ok(())
+        check_sizes(input, output)?;
+        let key = color_param(&self.params, values, "key_color");
+        let params = ChromaKeyParams {
+            key_color: ChromaKeyProcessor::rgb_to_ycbcr(key[0], key[1], key[2]),
+            tolerance: float_param(&self.params, values, "tolerance"),
+            softness: float_param(&self.params, values, "softness"),
+            spill_suppression: float_param(&self.params, values, "spill_suppression"),
+            ..ChromaKeyParams::default()
+        };
+        let (w, h) = (input.width, input.height);
+        let mut pixels = read_rgba(input)?;
+        // The key is pulled on display-referred 8-bit values. Float frames
+        // are scene-linear, so their colour is sRGB-encoded first.
+        let transfer = if input.format.is_float() {
+            TransferFunction::Srgb
+        } else {
+            TransferFunction::Linear
+        };
+        let quantize = |v: f32| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+        let fg: Vec<u8> = pixels
+            .iter()
+            .flat_map(|&[r, g, b, a]| {
+                [
+                    quantize(transfer.apply(r)),
+                    quantize(transfer.apply(g)),
+                    quantize(transfer.apply(b)),
+                    quantize(a),
+                ]
+            })
+            .collect();
+
+        let mut matte = ChromaKeyProcessor::extract_matte(&fg, w, h, &params);
+        ChromaKeyProcessor::clip_black_white(&mut matte, 0.0, 1.0);
+        ChromaKeyProcessor::erode_dilate(&mut matte, w, h, params.edge_thin);
+        ChromaKeyProcessor::blur_matte(&mut matte, w, h, params.edge_feather);
+        let mut despilled = fg.clone();
+        ChromaKeyProcessor::despill(&mut despilled, &matte, w, h, &params);
+
+        // Apply the despill as a correction, back in the frame's own
+        // encoding, so float input keeps its precision.
+        pixels.par_iter_mut().enumerate().for_each(|(i, px)| {
+            for c in 0..3 {
+                let (after, before) = (despilled[i * 4 + c], fg[i * 4 + c]);
+                if after != before {
+                    px[c] += transfer.invert(after as f32 / 255.0)
+                        - transfer.invert(before as f32 / 255.0);
+                }
+            }
+            px[3] *= matte[i];
+        });
+        write_rgba(output, &pixels)
     }
 }
 
-/// Gaussian blur effect (stub).
+/// Gaussian blur effect.
 pub struct GaussianBlurEffect {
     params: Vec<ParamDescriptor>,
 }
@@ -188,20 +394,8 @@ impl GaussianBlurEffect {
     pub fn new() -> Self {
         Self {
             params: vec![
-                ParamDescriptor {
-                    name: "radius".into(),
-                    display_name: "Radius".into(),
-                    default: ParamValue::Float(5.0),
-                    min: Some(ParamValue::Float(0.0)),
-                    max: Some(ParamValue::Float(100.0)),
-                },
-                ParamDescriptor {
-                    name: "sigma".into(),
-                    display_name: "Sigma".into(),
-                    default: ParamValue::Float(1.5),
-                    min: Some(ParamValue::Float(0.1)),
-                    max: Some(ParamValue::Float(50.0)),
-                },
+                float_descriptor("radius", "Radius", 5.0, 0.0, 100.0),
+                float_descriptor("sigma", "Sigma", 1.5, 0.1, 50.0),
             ],
         }
     }
@@ -216,20 +410,26 @@ impl VideoEffect for GaussianBlurEffect {
         &self.params
     }
 
-    fn render(
+    fn render_cpu(
         &self,
-        _device: &wgpu::Device,
-        _queue: &wgpu::Queue,
-        _input: &GpuTexture,
-        _output: &GpuTexture,
-        _params: &ParamValues,
+        input: &FrameBuffer,
+        output: &mut FrameBuffer,
+        values: &ParamValues,
     ) -> Result<()> {
-        // Stub — GPU blur shader will be wired here.
-        Ok(())
+        check_sizes(input, output)?;
+        let mut pixels = read_rgba(input)?;
+        GaussianBlurProcessor::apply(
+            &mut pixels,
+            input.width,
+            input.height,
+            float_param(&self.params, values, "radius"),
+            float_param(&self.params, values, "sigma"),
+        );
+        write_rgba(output, &pixels)
     }
 }
 
-/// Film grain effect (stub).
+/// Film grain effect.
 pub struct FilmGrainEffect {
     params: Vec<ParamDescriptor>,
 }
@@ -244,27 +444,9 @@ impl FilmGrainEffect {
     pub fn new() -> Self {
         Self {
             params: vec![
-                ParamDescriptor {
-                    name: "intensity".into(),
-                    display_name: "Intensity".into(),
-                    default: ParamValue::Float(0.3),
-                    min: Some(ParamValue::Float(0.0)),
-                    max: Some(ParamValue::Float(1.0)),
-                },
-                ParamDescriptor {
-                    name: "size".into(),
-                    display_name: "Grain Size".into(),
-                    default: ParamValue::Float(1.0),
-                    min: Some(ParamValue::Float(0.5)),
-                    max: Some(ParamValue::Float(5.0)),
-                },
-                ParamDescriptor {
-                    name: "seed".into(),
-                    display_name: "Random Seed".into(),
-                    default: ParamValue::Int(0),
-                    min: Some(ParamValue::Int(0)),
-                    max: Some(ParamValue::Int(i32::MAX)),
-                },
+                float_descriptor("intensity", "Intensity", 0.3, 0.0, 1.0),
+                float_descriptor("size", "Grain Size", 1.0, 0.5, 5.0),
+                int_descriptor("seed", "Random Seed", 0, 0, i32::MAX),
             ],
         }
     }
@@ -279,20 +461,29 @@ impl VideoEffect for FilmGrainEffect {
         &self.params
     }
 
-    fn render(
+    fn render_cpu(
         &self,
-        _device: &wgpu::Device,
-        _queue: &wgpu::Queue,
-        _input: &GpuTexture,
-        _output: &GpuTexture,
-        _params: &ParamValues,
+        input: &FrameBuffer,
+        output: &mut FrameBuffer,
+        values: &ParamValues,
     ) -> Result<()> {
-        // Stub — GPU grain shader will be wired here.
-        Ok(())
+        check_sizes(input, output)?;
+        let seed = param(&self.params, values, "seed")
+            .and_then(ParamValue::as_i32)
+            .unwrap_or(0);
+        let mut pixels = read_rgba(input)?;
+        FilmGrainProcessor::apply(
+            &mut pixels,
+            input.width,
+            float_param(&self.params, values, "intensity"),
+            float_param(&self.params, values, "size"),
+            seed as u32,
+        );
+        write_rgba(output, &pixels)
     }
 }
 
-/// Vignette effect (stub).
+/// Vignette effect.
 pub struct VignetteEffect {
     params: Vec<ParamDescriptor>,
 }
@@ -307,34 +498,10 @@ impl VignetteEffect {
     pub fn new() -> Self {
         Self {
             params: vec![
-                ParamDescriptor {
-                    name: "intensity".into(),
-                    display_name: "Intensity".into(),
-                    default: ParamValue::Float(0.5),
-                    min: Some(ParamValue::Float(0.0)),
-                    max: Some(ParamValue::Float(1.0)),
-                },
-                ParamDescriptor {
-                    name: "radius".into(),
-                    display_name: "Radius".into(),
-                    default: ParamValue::Float(0.75),
-                    min: Some(ParamValue::Float(0.0)),
-                    max: Some(ParamValue::Float(2.0)),
-                },
-                ParamDescriptor {
-                    name: "softness".into(),
-                    display_name: "Softness".into(),
-                    default: ParamValue::Float(0.5),
-                    min: Some(ParamValue::Float(0.0)),
-                    max: Some(ParamValue::Float(1.0)),
-                },
-                ParamDescriptor {
-                    name: "color".into(),
-                    display_name: "Color".into(),
-                    default: ParamValue::Color([0.0, 0.0, 0.0, 1.0]),
-                    min: None,
-                    max: None,
-                },
+                float_descriptor("intensity", "Intensity", 0.5, 0.0, 1.0),
+                float_descriptor("radius", "Radius", 0.75, 0.0, 2.0),
+                float_descriptor("softness", "Softness", 0.5, 0.0, 1.0),
+                color_descriptor("color", "Color", [0.0, 0.0, 0.0, 1.0]),
             ],
         }
     }
@@ -349,16 +516,114 @@ impl VideoEffect for VignetteEffect {
         &self.params
     }
 
-    fn render(
+    fn render_cpu(
         &self,
-        _device: &wgpu::Device,
-        _queue: &wgpu::Queue,
-        _input: &GpuTexture,
-        _output: &GpuTexture,
-        _params: &ParamValues,
+        input: &FrameBuffer,
+        output: &mut FrameBuffer,
+        values: &ParamValues,
     ) -> Result<()> {
-        // Stub — GPU vignette shader will be wired here.
-        Ok(())
+        check_sizes(input, output)?;
+        let mut pixels = read_rgba(input)?;
+        VignetteProcessor::apply(
+            &mut pixels,
+            input.width,
+            input.height,
+            float_param(&self.params, values, "intensity"),
+            float_param(&self.params, values, "radius"),
+            float_param(&self.params, values, "softness"),
+            color_param(&self.params, values, "color"),
+        );
+        write_rgba(output, &pixels)
+    }
+}
+
+/// Position, scale and rotation about the frame centre, resampled with a
+/// chosen filter and edge mode.
+///
+/// `velocity`, `spin` and `zoom` are the change in position, rotation and
+/// scale per frame of an animated transform (e.g. the difference between
+/// neighbouring frames' keyframed values). With a shutter angle above zero,
+/// the frame is motion blurred along that motion.
+pub struct TransformEffect {
+    params: Vec<ParamDescriptor>,
+}
+
+impl Default for TransformEffect {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TransformEffect {
+    pub fn new() -> Self {
+        let filters = ResampleFilter::ALL.map(ResampleFilter::name);
+        let edges = EdgeMode::ALL.map(EdgeMode::name);
+        Self {
+            params: vec![
+                vec2_descriptor("position", "Position", [0.0, 0.0]),
+                float_descriptor("scale", "Scale", 1.0, 0.01, 20.0),
+                float_descriptor("rotation", "Rotation", 0.0, -3600.0, 3600.0),
+                choice_descriptor(
+                    "filter",
+                    "Filter",
+                    ResampleFilter::default().name(),
+                    &filters,
+                ),
+                choice_descriptor("edge", "Edge Mode", EdgeMode::default().name(), &edges),
+                float_descriptor("shutter_angle", "Shutter Angle", 0.0, 0.0, 720.0),
+                int_descriptor("samples", "Motion Samples", 8, 1, 64),
+                vec2_descriptor("velocity", "Velocity", [0.0, 0.0]),
+                float_descriptor("spin", "Spin", 0.0, -3600.0, 3600.0),
+                float_descriptor("zoom", "Zoom", 0.0, -20.0, 20.0),
+            ],
+        }
+    }
+}
+
+impl VideoEffect for TransformEffect {
+    fn name(&self) -> &str {
+        "Transform"
+    }
+
+    fn params(&self) -> &[ParamDescriptor] {
+        &self.params
+    }
+
+    fn render_cpu(
+        &self,
+        input: &FrameBuffer,
+        output: &mut FrameBuffer,
+        values: &ParamValues,
+    ) -> Result<()> {
+        check_sizes(input, output)?;
+        let filter = choice_param(&self.params, values, "filter");
+        let edge = choice_param(&self.params, values, "edge");
+        let resampler = Resampler::new(
+            ResampleFilter::from_name(filter).unwrap_or_default(),
+            EdgeMode::from_name(edge).unwrap_or_default(),
+        );
+        let shutter = RSMBParams {
+            shutter_angle: float_param(&self.params, values, "shutter_angle"),
+            samples: int_param(&self.params, values, "samples").max(1) as u32,
+        };
+        let position = Vec2::from(vec2_param(&self.params, values, "position"));
+        let velocity = Vec2::from(vec2_param(&self.params, values, "velocity"));
+        let scale = float_param(&self.params, values, "scale");
+        let zoom = float_param(&self.params, values, "zoom");
+        let rotation = float_param(&self.params, values, "rotation");
+        let spin = float_param(&self.params, values, "spin");
+
+        let centre = Vec2::new(input.width as f32, input.height as f32) * 0.5;
+        let transform_at = |t: f32| {
+            let offset = centre + position + velocity * t;
+            Transform2D::translate(offset.x, offset.y)
+                .then(Transform2D::rotate((rotation + spin * t).to_radians()))
+                .then(Transform2D::scale_uniform(scale + zoom * t))
+                .then(Transform2D::translate(-centre.x, -centre.y))
+        };
+        let pixels =
+            resampler.transform_pixels(input, input.width, input.height, &shutter, transform_at)?;
+        write_rgba(output, &pixels)
     }
 }
 
@@ -369,6 +634,7 @@ impl VideoEffect for VignetteEffect {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proedit_core::PixelFormat;
 
     #[test]
     fn registry_contains_builtin_effects() {
@@ -390,6 +656,10 @@ mod tests {
             names.contains(&"Vignette"),
             "Registry should contain Vignette, got: {names:?}"
         );
+        assert!(
+            names.contains(&"Transform"),
+            "Registry should contain Transform, got: {names:?}"
+        );
     }
 
     #[test]
@@ -439,13 +709,173 @@ mod tests {
         assert!(param_names.contains(&"color"));
     }
 
+    fn solid(format: PixelFormat, rgba: [f32; 4]) -> FrameBuffer {
+        let mut frame = FrameBuffer::new(16, 8, format);
+        write_rgba(&mut frame, &[rgba; 128]).unwrap();
+        frame
+    }
+
+    #[test]
+    fn chroma_key_cpu_keys_green_and_keeps_red() {
+        let effect = ChromaKeyEffect::new();
+        let params = ParamValues::new();
+        let green = effect
+            .apply_cpu(&solid(PixelFormat::Rgba8, [0.0, 1.0, 0.0, 1.0]), &params)
+            .unwrap();
+        assert!(read_rgba(&green).unwrap().iter().all(|p| p[3] == 0.0));
+
+        let red = effect
+            .apply_cpu(&solid(PixelFormat::Rgba32F, [1.0, 0.0, 0.0, 1.0]), &params)
+            .unwrap();
+        assert!(read_rgba(&red)
+            .unwrap()
+            .iter()
+            .all(|p| *p == [1.0, 0.0, 0.0, 1.0]));
+    }
+
+    #[test]
+    fn chroma_key_cpu_uses_key_color_param() {
+        let effect = ChromaKeyEffect::new();
+        let mut params = ParamValues::new();
+        params.insert("key_color".into(), ParamValue::Color([0.0, 0.0, 1.0, 1.0]));
+        let blue = effect
+            .apply_cpu(&solid(PixelFormat::Rgba8, [0.0, 0.0, 1.0, 1.0]), &params)
+            .unwrap();
+        assert!(read_rgba(&blue).unwrap().iter().all(|p| p[3] == 0.0));
+    }
+
+    #[test]
+    fn chroma_key_cpu_keys_float_frames_on_display_values() {
+        // The same colour as an 8-bit (display) and a float (linear) frame
+        // gets the same matte.
+        let effect = ChromaKeyEffect::new();
+        let params = ParamValues::new();
+        let display = [0.5, 0.75, 0.5];
+        let linear = display.map(|v| TransferFunction::Srgb.invert(v));
+        let eight = effect
+            .apply_cpu(
+                &solid(
+                    PixelFormat::Rgba8,
+                    [display[0], display[1], display[2], 1.0],
+                ),
+                &params,
+            )
+            .unwrap();
+        let float = effect
+            .apply_cpu(
+                &solid(PixelFormat::Rgba32F, [linear[0], linear[1], linear[2], 1.0]),
+                &params,
+            )
+            .unwrap();
+        let eight_alpha = read_rgba(&eight).unwrap()[0][3];
+        let float_alpha = read_rgba(&float).unwrap()[0][3];
+        assert!(eight_alpha > 0.0 && eight_alpha < 1.0, "{eight_alpha}");
+        assert!((eight_alpha - float_alpha).abs() < 2.0 / 255.0);
+    }
+
+    #[test]
+    fn uniform_frame_survives_blur_and_zero_strength_effects() {
+        let input = solid(PixelFormat::Rgba32F, [0.2, 0.4, 0.6, 1.0]);
+        let expected = read_rgba(&input).unwrap();
+        let mut zero = ParamValues::new();
+        zero.insert("intensity".into(), ParamValue::Float(0.0));
+
+        let blurred = GaussianBlurEffect::new()
+            .apply_cpu(&input, &ParamValues::new())
+            .unwrap();
+        for (a, b) in read_rgba(&blurred).unwrap().iter().zip(&expected) {
+            for c in 0..4 {
+                assert!((a[c] - b[c]).abs() < 1e-5);
+            }
+        }
+        for effect in [
+            Box::new(FilmGrainEffect::new()) as Box<dyn VideoEffect>,
+            Box::new(VignetteEffect::new()),
+        ] {
+            let out = effect.apply_cpu(&input, &zero).unwrap();
+            assert_eq!(read_rgba(&out).unwrap(), expected, "{}", effect.name());
+        }
+    }
+
+    #[test]
+    fn default_effects_change_the_frame() {
+        let input = solid(PixelFormat::Rgba8, [0.5, 0.5, 0.5, 1.0]);
+        let registry = EffectsRegistry::new();
+        for name in ["Film Grain", "Vignette"] {
+            let effect = registry.find(name).unwrap();
+            let out = effect.apply_cpu(&input, &ParamValues::new()).unwrap();
+            assert_ne!(
+                out.primary_plane().data,
+                input.primary_plane().data,
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn transform_effect_defaults_to_identity_and_scales_about_the_centre() {
+        let effect = TransformEffect::new();
+        let input = FrameBuffer::test_pattern(32, 16);
+        let same = effect.apply_cpu(&input, &ParamValues::new()).unwrap();
+        assert_eq!(same.primary_plane().data, input.primary_plane().data);
+
+        // Doubling the bars pushes the outer ones off the frame; the edges
+        // show the bars that were a quarter of the way in.
+        let mut params = ParamValues::new();
+        params.insert("scale".into(), ParamValue::Float(2.0));
+        params.insert("filter".into(), ParamValue::Choice("Bilinear".into()));
+        let zoomed = read_rgba(&effect.apply_cpu(&input, &params).unwrap()).unwrap();
+        assert_eq!(zoomed[1], [0.0, 1.0, 1.0, 1.0]);
+        assert_eq!(zoomed[30], [1.0, 0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn transform_effect_modes_are_chosen_by_name() {
+        let effect = TransformEffect::new();
+        let filter = effect.params().iter().find(|p| p.name == "filter").unwrap();
+        assert_eq!(
+            filter.choices,
+            ["Bilinear", "Bicubic", "Lanczos 3", "Area Average"]
+        );
+        assert_eq!(filter.default, ParamValue::Choice("Bicubic".into()));
+
+        // Names that are not options fall back to the default.
+        let mut params = ParamValues::new();
+        params.insert("edge".into(), ParamValue::Choice("Mirror".into()));
+        params.insert("filter".into(), ParamValue::Choice("Nearest".into()));
+        assert_eq!(choice_param(effect.params(), &params, "edge"), "Mirror");
+        assert_eq!(choice_param(effect.params(), &params, "filter"), "Bicubic");
+    }
+
+    #[test]
+    fn cpu_render_checks_sizes_and_formats() {
+        let effect = VignetteEffect::new();
+        let input = solid(PixelFormat::Rgba8, [1.0; 4]);
+        let mut small = FrameBuffer::new(4, 4, PixelFormat::Rgba8);
+        assert!(effect
+            .render_cpu(&input, &mut small, &ParamValues::new())
+            .is_err());
+        let yuv = FrameBuffer::new(16, 8, PixelFormat::Yuv420P);
+        assert!(effect.apply_cpu(&yuv, &ParamValues::new()).is_err());
+    }
+
+    #[test]
+    fn builtin_effects_run_without_gpu() {
+        let registry = EffectsRegistry::new();
+        let input = FrameBuffer::test_pattern(32, 16);
+        for effect in registry.effects() {
+            assert!(effect.gpu().is_none());
+            effect.apply_cpu(&input, &ParamValues::new()).unwrap();
+        }
+    }
+
     #[test]
     fn registry_has_correct_count() {
         let registry = EffectsRegistry::new();
         assert_eq!(
             registry.effects().len(),
-            4,
-            "Should have exactly 4 built-in effects"
+            5,
+            "Should have exactly 5 built-in effects"
         );
     }
 }
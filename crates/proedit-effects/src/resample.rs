@@ -0,0 +1,566 @@
+//! Quality resampling for scaling and transforming frames.
+//!
+//! Filters run on premultiplied pixels, so transparent areas do not bleed
+//! dark fringes into the picture. When an image shrinks, the kernel widens
+//! by the scale factor so that every source pixel an output pixel covers
+//! contributes, which avoids aliasing. Pixel centres sit at `n + 0.5`, as
+//! in the CPU backend.
+
+use crate::cpu::{read_rgba, Rgba};
+use crate::motion_blur::RSMBParams;
+use glam::{Mat3, Vec2};
+use proedit_core::{FrameBuffer, ProEditError, Result, Transform2D};
+use proedit_gpu::{premultiply, unpremultiply};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+/// The reconstruction filter used when resampling.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ResampleFilter {
+    /// Triangle filter over the 2x2 neighbourhood.
+    Bilinear,
+    /// Catmull-Rom cubic over 4x4: sharper than bilinear, slight ringing.
+    #[default]
+    Bicubic,
+    /// Windowed sinc over 6x6: sharpest, for final renders.
+    Lanczos3,
+    /// Box filter weighted by coverage: each output pixel is the mean of
+    /// the source area it covers. Best for large downscales.
+    AreaAverage,
+}
+
+impl ResampleFilter {
+    /// Every filter, in the order the transform effect offers them.
+    pub const ALL: [Self; 4] = [
+        Self::Bilinear,
+        Self::Bicubic,
+        Self::Lanczos3,
+        Self::AreaAverage,
+    ];
+
+    /// Display name, also the transform effect's `filter` value.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Bilinear => "Bilinear",
+            Self::Bicubic => "Bicubic",
+            Self::Lanczos3 => "Lanczos 3",
+            Self::AreaAverage => "Area Average",
+        }
+    }
+
+    /// The filter called `name`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|f| f.name() == name)
+    }
+
+    /// Kernel radius in source pixels at 1:1 scale.
+    fn support(self) -> f32 {
+        match self {
+            Self::Bilinear => 1.0,
+            Self::Bicubic => 2.0,
+            Self::Lanczos3 => 3.0,
+            Self::AreaAverage => 0.5,
+        }
+    }
+
+    /// Kernel weight at distance `x` from the sample point.
+    fn kernel(self, x: f32) -> f32 {
+        let x = x.abs();
+        match self {
+            Self::Bilinear => (1.0 - x).max(0.0),
+            Self::Bicubic => {
+                if x < 1.0 {
+                    (1.5 * x - 2.5) * x * x + 1.0
+                } else if x < 2.0 {
+                    ((-0.5 * x + 2.5) * x - 4.0) * x + 2.0
+                } else {
+                    0.0
+                }
+            }
+            Self::Lanczos3 => {
+                if x < 1e-6 {
+                    1.0
+                } else if x < 3.0 {
+                    let px = PI * x;
+                    3.0 * px.sin() * (px / 3.0).sin() / (px * px)
+                } else {
+                    0.0
+                }
+            }
+            Self::AreaAverage => {
+                if x <= 0.5 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+
+    /// Weights along one axis for an output pixel centred at `centre`
+    /// (source pixel units) covering `scale` source pixels. Fills
+    /// `weights`, which sum to 1, and returns the source index of the
+    /// first one.
+    fn taps(self, centre: f32, scale: f32, weights: &mut Vec<f32>) -> i64 {
+        let scale = scale.max(1.0);
+        let radius = self.support() * scale;
+        let first = (centre - radius).floor() as i64;
+        let last = (centre + radius).ceil() as i64;
+        weights.clear();
+        for i in first..last {
+            let w = match self {
+                // Coverage of source pixel `i` (spanning `i..i + 1`).
+                Self::AreaAverage => {
+                    let lo = (i as f32).max(centre - radius);
+                    let hi = ((i + 1) as f32).min(centre + radius);
+                    (hi - lo).max(0.0)
+                }
+                _ => self.kernel((i as f32 + 0.5 - centre) / scale),
+            };
+            weights.push(w);
+        }
+        let sum: f32 = weights.iter().sum();
+        if sum.abs() > 1e-6 {
+            for w in weights.iter_mut() {
+                *w /= sum;
+            }
+        }
+        first
+    }
+}
+
+/// What sampling outside the source image returns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum EdgeMode {
+    /// Transparent black.
+    #[default]
+    Transparent,
+    /// The nearest edge pixel.
+    Clamp,
+    /// The image tiled.
+    Repeat,
+    /// The image tiled, every other copy flipped, so tiles meet seamlessly.
+    Mirror,
+}
+
+impl EdgeMode {
+    /// Every edge mode, in the order the transform effect offers them.
+    pub const ALL: [Self; 4] = [Self::Transparent, Self::Clamp, Self::Repeat, Self::Mirror];
+
+    /// Display name, also the transform effect's `edge` value.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Transparent => "Transparent",
+            Self::Clamp => "Clamp",
+            Self::Repeat => "Repeat",
+            Self::Mirror => "Mirror",
+        }
+    }
+
+    /// The edge mode called `name`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|e| e.name() == name)
+    }
+
+    /// The source index read for index `i` of an axis `n` pixels long, or
+    /// `None` for transparent.
+    fn index(self, i: i64, n: usize) -> Option<usize> {
+        let n_i = n as i64;
+        if n == 0 {
+            return None;
+        }
+        if (0..n_i).contains(&i) {
+            return Some(i as usize);
+        }
+        match self {
+            Self::Transparent => None,
+            Self::Clamp => Some(i.clamp(0, n_i - 1) as usize),
+            Self::Repeat => Some(i.rem_euclid(n_i) as usize),
+            Self::Mirror => {
+                let m = i.rem_euclid(2 * n_i);
+                Some(if m < n_i { m } else { 2 * n_i - 1 - m } as usize)
+            }
+        }
+    }
+}
+
+/// Resamples frames with a chosen filter and edge mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Resampler {
+    pub filter: ResampleFilter,
+    pub edge: EdgeMode,
+}
+
+impl Resampler {
+    pub fn new(filter: ResampleFilter, edge: EdgeMode) -> Self {
+        Self { filter, edge }
+    }
+
+    /// Scale `input` to `width` x `height`, in `input`'s format.
+    ///
+    /// The output covers exactly the input. Kernel overhang at the borders
+    /// therefore reads clamped pixels when the edge mode is `Transparent`;
+    /// otherwise the borders would fade.
+    pub fn resize(&self, input: &FrameBuffer, width: u32, height: u32) -> Result<FrameBuffer> {
+        check_size(width, height)?;
+        let (iw, ih) = (input.width as usize, input.height as usize);
+        let texels = premultiplied(input)?;
+        let edge = match self.edge {
+            EdgeMode::Transparent => EdgeMode::Clamp,
+            edge => edge,
+        };
+        let (w, h) = (width as usize, height as usize);
+
+        // Separable: rows first, into a `w` x `ih` intermediate.
+        let columns = self.axis_taps(iw, w);
+        let mut wide = vec![[0.0; 4]; w * ih];
+        wide.par_chunks_mut(w).enumerate().for_each(|(y, row)| {
+            let source = &texels[y * iw..(y + 1) * iw];
+            for (px, (first, weights)) in row.iter_mut().zip(&columns) {
+                *px = weighted(weights, *first, |i| edge.index(i, iw).map(|x| source[x]));
+            }
+        });
+
+        let rows = self.axis_taps(ih, h);
+        let mut pixels = vec![[0.0; 4]; w * h];
+        pixels
+            .par_chunks_mut(w)
+            .zip(&rows)
+            .for_each(|(row, (first, weights))| {
+                for (x, px) in row.iter_mut().enumerate() {
+                    let acc = weighted(weights, *first, |i| {
+                        edge.index(i, ih).map(|y| wide[y * w + x])
+                    });
+                    *px = unpremultiply(acc);
+                }
+            });
+        FrameBuffer::from_rgba_f32(width, height, input.format, &pixels)
+    }
+
+    /// Taps for each of `to` output pixels along an axis `from` pixels long.
+    fn axis_taps(&self, from: usize, to: usize) -> Vec<(i64, Vec<f32>)> {
+        let scale = from as f32 / to as f32;
+        (0..to)
+            .map(|i| {
+                let mut weights = Vec::new();
+                let first = self
+                    .filter
+                    .taps((i as f32 + 0.5) * scale, scale, &mut weights);
+                (first, weights)
+            })
+            .collect()
+    }
+
+    /// Render `input` through `transform` (input pixel coordinates to
+    /// output pixel coordinates) into a `width` x `height` frame in
+    /// `input`'s format.
+    pub fn transform(
+        &self,
+        input: &FrameBuffer,
+        transform: Transform2D,
+        width: u32,
+        height: u32,
+    ) -> Result<FrameBuffer> {
+        let pixels = self.transform_pixels(input, width, height, &STILL, |_| transform)?;
+        FrameBuffer::from_rgba_f32(width, height, input.format, &pixels)
+    }
+
+    /// Like [`Resampler::transform`], for an animated transform.
+    /// `transform_at(t)` gives the transform `t` frames from the current
+    /// one. The result averages `shutter.samples` sub-frame positions,
+    /// spread evenly over the time the shutter is open. The shutter opens
+    /// for `shutter.shutter_angle / 360` of a frame, centred on the frame.
+    pub fn transform_motion_blur(
+        &self,
+        input: &FrameBuffer,
+        width: u32,
+        height: u32,
+        shutter: &RSMBParams,
+        transform_at: impl Fn(f32) -> Transform2D + Sync,
+    ) -> Result<FrameBuffer> {
+        let pixels = self.transform_pixels(input, width, height, shutter, transform_at)?;
+        FrameBuffer::from_rgba_f32(width, height, input.format, &pixels)
+    }
+
+    /// Straight-alpha pixels of a motion-blurred transform.
+    pub(crate) fn transform_pixels(
+        &self,
+        input: &FrameBuffer,
+        width: u32,
+        height: u32,
+        shutter: &RSMBParams,
+        transform_at: impl Fn(f32) -> Transform2D + Sync,
+    ) -> Result<Vec<Rgba>> {
+        check_size(width, height)?;
+        let texels = premultiplied(input)?;
+        let source = Source {
+            texels: &texels,
+            width: input.width as usize,
+            height: input.height as usize,
+        };
+        let samples = shutter.samples.max(1);
+        let open = shutter.shutter_angle.max(0.0) / 360.0;
+        let inverses = (0..samples)
+            .map(|s| {
+                let t = ((s as f32 + 0.5) / samples as f32 - 0.5) * open;
+                let inverse = transform_at(t).to_mat3().inverse();
+                if inverse.is_finite() {
+                    Ok(inverse)
+                } else {
+                    Err(ProEditError::InvalidParameter(
+                        "Transform matrix is not invertible".into(),
+                    ))
+                }
+            })
+            .collect::<Result<Vec<Mat3>>>()?;
+
+        let w = width as usize;
+        let mut pixels = vec![[0.0; 4]; w * height as usize];
+        pixels.par_chunks_mut(w).enumerate().for_each(|(y, row)| {
+            let (mut wx, mut wy) = (Vec::new(), Vec::new());
+            for (x, px) in row.iter_mut().enumerate() {
+                let centre = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+                let mut acc = [0.0; 4];
+                for inverse in &inverses {
+                    let sample = self.sample(&source, inverse, centre, &mut wx, &mut wy);
+                    for (a, s) in acc.iter_mut().zip(sample) {
+                        *a += s;
+                    }
+                }
+                *px = unpremultiply(acc.map(|a| a / inverses.len() as f32));
+            }
+        });
+        Ok(pixels)
+    }
+
+    /// The premultiplied value of output pixel `centre` under `inverse`
+    /// (output to source).
+    fn sample(
+        &self,
+        source: &Source,
+        inverse: &Mat3,
+        centre: Vec2,
+        wx: &mut Vec<f32>,
+        wy: &mut Vec<f32>,
+    ) -> Rgba {
+        let p = inverse.transform_point2(centre);
+        // Source pixels crossed per output pixel along each source axis.
+        let scale_x = Vec2::new(inverse.x_axis.x, inverse.y_axis.x).length();
+        let scale_y = Vec2::new(inverse.x_axis.y, inverse.y_axis.y).length();
+        let fx = self.filter.taps(p.x, scale_x, wx);
+        let fy = self.filter.taps(p.y, scale_y, wy);
+        let mut acc = [0.0; 4];
+        for (j, &w_y) in wy.iter().enumerate() {
+            if w_y == 0.0 {
+                continue;
+            }
+            let Some(y) = self.edge.index(fy + j as i64, source.height) else {
+                continue;
+            };
+            let row = &source.texels[y * source.width..(y + 1) * source.width];
+            let px = weighted(wx, fx, |i| self.edge.index(i, source.width).map(|x| row[x]));
+            for (a, p) in acc.iter_mut().zip(px) {
+                *a += p * w_y;
+            }
+        }
+        acc
+    }
+}
+
+/// A single sample at the frame time.
+const STILL: RSMBParams = RSMBParams {
+    shutter_angle: 0.0,
+    samples: 1,
+};
+
+/// Premultiplied source pixels.
+struct Source<'a> {
+    texels: &'a [Rgba],
+    width: usize,
+    height: usize,
+}
+
+fn check_size(width: u32, height: u32) -> Result<()> {
+    if width == 0 || height == 0 {
+        return Err(ProEditError::InvalidParameter(format!(
+            "Cannot resample to {width}x{height}"
+        )));
+    }
+    Ok(())
+}
+
+fn premultiplied(frame: &FrameBuffer) -> Result<Vec<Rgba>> {
+    Ok(read_rgba(frame)?.into_iter().map(premultiply).collect())
+}
+
+/// Sum of `texel(first + i) * weights[i]`; `None` texels are transparent.
+fn weighted(weights: &[f32], first: i64, texel: impl Fn(i64) -> Option<Rgba>) -> Rgba {
+    let mut acc = [0.0; 4];
+    for (i, &w) in weights.iter().enumerate() {
+        if w == 0.0 {
+            continue;
+        }
+        if let Some(t) = texel(first + i as i64) {
+            for (a, t) in acc.iter_mut().zip(t) {
+                *a += t * w;
+            }
+        }
+    }
+    acc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::write_rgba;
+    use proedit_core::PixelFormat;
+
+    fn frame(width: u32, height: u32, pixel: impl Fn(u32, u32) -> Rgba) -> FrameBuffer {
+        let pixels: Vec<Rgba> = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| pixel(x, y))
+            .collect();
+        let mut frame = FrameBuffer::new(width, height, PixelFormat::Rgba32F);
+        write_rgba(&mut frame, &pixels).unwrap();
+        frame
+    }
+
+    fn assert_close(a: &[Rgba], b: &[Rgba], tolerance: f32) {
+        assert_eq!(a.len(), b.len());
+        for (i, (a, b)) in a.iter().zip(b).enumerate() {
+            for c in 0..4 {
+                assert!(
+                    (a[c] - b[c]).abs() <= tolerance,
+                    "pixel {i}: {a:?} vs {b:?}"
+                );
+            }
+        }
+    }
+
+    fn gray(v: f32) -> Rgba {
+        [v, v, v, 1.0]
+    }
+
+    #[test]
+    fn test_identity_keeps_every_pixel() {
+        let input = frame(13, 7, |x, y| {
+            [x as f32 / 13.0, y as f32 / 7.0, ((x + y) % 2) as f32, 1.0]
+        });
+        let expected = read_rgba(&input).unwrap();
+        for filter in ResampleFilter::ALL {
+            let resampler = Resampler::new(filter, EdgeMode::Transparent);
+            let moved = resampler
+                .transform(&input, Transform2D::IDENTITY, 13, 7)
+                .unwrap();
+            assert_close(&read_rgba(&moved).unwrap(), &expected, 1e-4);
+            let resized = resampler.resize(&input, 13, 7).unwrap();
+            assert_close(&read_rgba(&resized).unwrap(), &expected, 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_downscale_averages_fine_detail() {
+        // A one-pixel checkerboard would alias to black or white if the
+        // kernels did not widen.
+        let input = frame(32, 32, |x, y| gray(((x + y) % 2) as f32));
+        for filter in ResampleFilter::ALL {
+            let resampler = Resampler::new(filter, EdgeMode::Clamp);
+            let expected = vec![gray(0.5); 64];
+            let resized = resampler.resize(&input, 8, 8).unwrap();
+            assert_close(&read_rgba(&resized).unwrap(), &expected, 0.02);
+            let scaled = resampler
+                .transform(&input, Transform2D::scale_uniform(0.25), 8, 8)
+                .unwrap();
+            assert_close(&read_rgba(&scaled).unwrap(), &expected, 0.02);
+        }
+    }
+
+    #[test]
+    fn test_edge_modes() {
+        let input = frame(4, 1, |x, _| gray(0.1 * (x + 1) as f32));
+        let shift = Transform2D::translate(2.0, 0.0);
+        let cases = [
+            (EdgeMode::Transparent, [[0.0; 4], [0.0; 4]]),
+            (EdgeMode::Clamp, [gray(0.1), gray(0.1)]),
+            (EdgeMode::Repeat, [gray(0.3), gray(0.4)]),
+            (EdgeMode::Mirror, [gray(0.2), gray(0.1)]),
+        ];
+        for (edge, left) in cases {
+            let resampler = Resampler::new(ResampleFilter::Bilinear, edge);
+            let out = read_rgba(&resampler.transform(&input, shift, 4, 1).unwrap()).unwrap();
+            assert_close(&out[..2], &left, 1e-5);
+            assert_close(&out[2..], &[gray(0.1), gray(0.2)], 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_transparent_surround_does_not_darken_edges() {
+        let input = frame(8, 8, |x, y| {
+            if (2..6).contains(&x) && (2..6).contains(&y) {
+                [1.0, 0.0, 0.0, 1.0]
+            } else {
+                [0.0; 4]
+            }
+        });
+        let resampler = Resampler::new(ResampleFilter::Lanczos3, EdgeMode::Transparent);
+        let scaled = resampler
+            .transform(&input, Transform2D::scale_uniform(0.75), 8, 8)
+            .unwrap();
+        let partial: Vec<Rgba> = read_rgba(&scaled)
+            .unwrap()
+            .into_iter()
+            .filter(|p| p[3] > 0.01 && p[3] < 0.99)
+            .collect();
+        assert!(!partial.is_empty());
+        for p in partial {
+            assert!((p[0] - 1.0).abs() < 1e-3 && p[1].abs() < 1e-3, "{p:?}");
+        }
+    }
+
+    #[test]
+    fn test_motion_blur_smears_along_the_motion() {
+        // A two-pixel white bar on black, moving 16 pixels per frame.
+        let input = frame(32, 1, |x, _| gray(if x == 8 || x == 9 { 1.0 } else { 0.0 }));
+        let resampler = Resampler::new(ResampleFilter::Bilinear, EdgeMode::Clamp);
+        let moving = |t: f32| Transform2D::translate(16.0 * t, 0.0);
+
+        let closed = RSMBParams {
+            shutter_angle: 0.0,
+            samples: 8,
+        };
+        let sharp = resampler
+            .transform_motion_blur(&input, 32, 1, &closed, moving)
+            .unwrap();
+        assert_close(
+            &read_rgba(&sharp).unwrap(),
+            &read_rgba(&input).unwrap(),
+            1e-5,
+        );
+
+        // A 180 degree shutter is open for half a frame: 8 pixels of travel.
+        let open = RSMBParams {
+            shutter_angle: 180.0,
+            samples: 8,
+        };
+        let blurred = resampler
+            .transform_motion_blur(&input, 32, 1, &open, moving)
+            .unwrap();
+        let row: Vec<f32> = read_rgba(&blurred).unwrap().iter().map(|p| p[0]).collect();
+        let total: f32 = row.iter().sum();
+        assert!((total - 2.0).abs() < 1e-3, "light is kept: {total}");
+        assert!(row.iter().all(|&v| v < 0.5), "{row:?}");
+        assert!(row[5] > 0.1 && row[12] > 0.1, "{row:?}");
+        assert!(row[2] == 0.0 && row[16] == 0.0, "{row:?}");
+    }
+
+    #[test]
+    fn test_rejects_empty_output_and_singular_transform() {
+        let input = frame(4, 4, |_, _| gray(1.0));
+        let resampler = Resampler::default();
+        assert!(resampler.resize(&input, 0, 4).is_err());
+        assert!(resampler
+            .transform(&input, Transform2D::scale(0.0, 1.0), 4, 4)
+            .is_err());
+    }
+}
@@ -1,6 +1,17 @@
 //! Transition system for video editing.
+//!
+//! Transitions describe their user parameters with `ParamDescriptor`s like
+//! effects do; a `TransitionParams` instance holds the values, the
+//! duration and the easing that maps elapsed time to progress.
+//!
+//! Like effects, transitions blend straight-alpha RGBA `f32` pixels (see
+//! `cpu`). In the compositor these hold linear light in a float working
+//! format, so a dissolve mixes light rather than sRGB code values and
+//! values above 1.0 come through unclipped.
 
-use proedit_core::{EasingCurve, FrameRate, RationalTime};
+use crate::cpu::{read_rgba, write_rgba, Rgba};
+use crate::{check_sizes, ParamDescriptor};
+use proedit_core::{EasingCurve, FrameBuffer, FrameRate, ParamValues, RationalTime, Result};
 use serde::{Deserialize, Serialize};
 
 /// Trait for video transitions between two clips.
@@ -8,10 +19,67 @@ pub trait Transition: Send + Sync {
     /// Get the transition name.
     fn name(&self) -> &str;
 
+    /// Get parameter descriptors.
+    fn params(&self) -> &[ParamDescriptor] {
+        &[]
+    }
+
     /// Render the transition between frame A and frame B.
     /// Progress goes from 0.0 (pure A) to 1.0 (pure B).
-    /// Input frames are RGBA u8.
-    fn render(&self, a: &[u8], b: &[u8], w: u32, h: u32, progress: f32) -> Vec<u8>;
+    /// Input pixels are `w`×`h`, row by row, as read by `read_rgba`;
+    /// parameters missing from `params` take their defaults.
+    fn render(
+        &self,
+        a: &[Rgba],
+        b: &[Rgba],
+        w: u32,
+        h: u32,
+        progress: f32,
+        params: &ParamValues,
+    ) -> Vec<Rgba>;
+
+    /// Render the transition between two frames into `output`. All three
+    /// must have the same size and be `Rgba8`, `Rgba16F` or `Rgba32F`
+    /// (formats may differ).
+    fn render_cpu(
+        &self,
+        a: &FrameBuffer,
+        b: &FrameBuffer,
+        output: &mut FrameBuffer,
+        progress: f32,
+        params: &ParamValues,
+    ) -> Result<()> {
+        check_sizes(a, b)?;
+        check_sizes(a, output)?;
+        let (from, to) = (read_rgba(a)?, read_rgba(b)?);
+        let pixels = self.render(&from, &to, a.width, a.height, progress, params);
+        write_rgba(output, &pixels)
+    }
+
+    /// Render on the CPU into a new frame in `a`'s format.
+    fn apply_cpu(
+        &self,
+        a: &FrameBuffer,
+        b: &FrameBuffer,
+        progress: f32,
+        params: &ParamValues,
+    ) -> Result<FrameBuffer> {
+        let mut output = FrameBuffer::new(a.width, a.height, a.format);
+        self.render_cpu(a, b, &mut output, progress, params)?;
+        Ok(output)
+    }
+
+    /// Render `elapsed` into a transition instance, eased by its curve.
+    fn render_at(
+        &self,
+        a: &FrameBuffer,
+        b: &FrameBuffer,
+        elapsed: RationalTime,
+        instance: &TransitionParams,
+    ) -> Result<FrameBuffer> {
+        let progress = instance.progress(elapsed);
+        self.apply_cpu(a, b, progress, &instance.values)
+    }
 }
 
 /// Parameters for a transition instance.
@@ -19,6 +87,9 @@ pub trait Transition: Send + Sync {
 pub struct TransitionParams {
     pub duration: RationalTime,
     pub easing: EasingCurve,
+    /// Values of the transition's parameters, by name
+    #[serde(default)]
+    pub values: ParamValues,
 }
 
 impl Default for TransitionParams {
@@ -26,7 +97,19 @@ impl Default for TransitionParams {
         Self {
             duration: RationalTime::from_frames(24, FrameRate::FPS_24),
             easing: EasingCurve::Linear,
+            values: ParamValues::new(),
+        }
+    }
+}
+
+impl TransitionParams {
+    /// Eased progress `elapsed` into the transition, from 0.0 to 1.0.
+    pub fn progress(&self, elapsed: RationalTime) -> f32 {
+        let duration = self.duration.to_seconds_f64();
+        if duration <= 0.0 {
+            return 1.0;
         }
+        self.easing.apply(elapsed.to_seconds_f64() / duration) as f32
     }
 }
 
@@ -48,6 +131,12 @@ impl TransitionRegistry {
         reg.register(Box::new(super::transitions::Wipe::default()));
         reg.register(Box::new(super::transitions::Push::default()));
         reg.register(Box::new(super::transitions::Iris::default()));
+        reg.register(Box::new(super::transitions::Slide::default()));
+        reg.register(Box::new(super::transitions::Zoom::new()));
+        reg.register(Box::new(super::transitions::DirectionalBlur::new()));
+        reg.register(Box::new(super::transitions::LumaWipe::default()));
+        reg.register(Box::new(super::transitions::FilmBurn::new()));
+        reg.register(Box::new(super::transitions::Glitch::new()));
         reg
     }
 
@@ -79,6 +168,7 @@ impl Default for TransitionRegistry {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proedit_core::PixelFormat;
 
     #[test]
     fn test_registry_has_builtins() {
@@ -90,6 +180,66 @@ mod tests {
         assert!(names.contains(&"Wipe"));
         assert!(names.contains(&"Push"));
         assert!(names.contains(&"Iris"));
+        for name in [
+            "Slide",
+            "Zoom",
+            "Directional Blur",
+            "Luma Wipe",
+            "Film Burn",
+            "Glitch",
+        ] {
+            assert!(names.contains(&name), "{name}");
+        }
+    }
+
+    #[test]
+    fn test_params_ease_progress() {
+        let mut params = TransitionParams::default();
+        let half = RationalTime::from_frames(12, FrameRate::FPS_24);
+        assert_eq!(params.progress(half), 0.5);
+        assert_eq!(params.progress(RationalTime::new(5, 1)), 1.0);
+        params.easing = EasingCurve::Bezier(proedit_core::CubicBezier::EASE_IN);
+        assert!(params.progress(half) < 0.5);
+
+        // Values reach the transition; unset ones take their defaults.
+        let reg = TransitionRegistry::new();
+        let wipe = reg.find("Wipe").unwrap();
+        assert!(wipe.params().iter().any(|d| d.name == "softness"));
+        let a = FrameBuffer::from_rgba_f32(4, 1, PixelFormat::Rgba8, &[[0.0; 4]; 4]).unwrap();
+        let b = FrameBuffer::from_rgba_f32(4, 1, PixelFormat::Rgba8, &[[1.0; 4]; 4]).unwrap();
+        params.easing = EasingCurve::Linear;
+        let hard = wipe.render_at(&a, &b, half, &params).unwrap();
+        assert_eq!(
+            hard.primary_plane().row(0)[..16],
+            [[255; 8], [0; 8]].concat()
+        );
+        params
+            .values
+            .insert("softness".into(), proedit_core::ParamValue::Float(1.0));
+        let soft = wipe.render_at(&a, &b, half, &params).unwrap();
+        let soft = soft.read_rgba_f32().unwrap();
+        assert!(soft[0][3] < 1.0 && soft[3][3] > 0.0);
+    }
+
+    #[test]
+    fn test_float_frames_blend_linear_light() {
+        let reg = TransitionRegistry::new();
+        let dissolve = reg.find("Cross Dissolve").unwrap();
+        // A highlight well above 1.0 fades out rather than clipping first.
+        let a = FrameBuffer::from_rgba_f32(2, 1, PixelFormat::Rgba16F, &[[4.0, 0.5, 0.0, 1.0]; 2])
+            .unwrap();
+        let b = FrameBuffer::from_rgba_f32(2, 1, PixelFormat::Rgba16F, &[[0.0, 0.5, 1.0, 1.0]; 2])
+            .unwrap();
+        let mid = dissolve
+            .apply_cpu(&a, &b, 0.5, &ParamValues::new())
+            .unwrap();
+        assert_eq!(mid.format, PixelFormat::Rgba16F);
+        assert_eq!(mid.read_rgba_f32().unwrap()[0], [2.0, 0.5, 0.5, 1.0]);
+
+        let small = FrameBuffer::new(1, 1, PixelFormat::Rgba16F);
+        assert!(dissolve
+            .apply_cpu(&a, &small, 0.5, &ParamValues::new())
+            .is_err());
     }
 
     #[test]
@@ -1,4 +1,7 @@
+use super::mix;
+use crate::cpu::Rgba;
 use crate::transition::Transition;
+use proedit_core::ParamValues;
 
 pub struct CrossDissolve;
 
@@ -7,15 +10,20 @@ impl Transition for CrossDissolve {
         "Cross Dissolve"
     }
 
-    fn render(&self, a: &[u8], b: &[u8], w: u32, h: u32, progress: f32) -> Vec<u8> {
-        let size = (w * h * 4) as usize;
-        let mut out = vec![0u8; size];
+    fn render(
+        &self,
+        a: &[Rgba],
+        b: &[Rgba],
+        w: u32,
+        h: u32,
+        progress: f32,
+        _params: &ParamValues,
+    ) -> Vec<Rgba> {
         let p = progress.clamp(0.0, 1.0);
-        let ip = 1.0 - p;
-
-        for ((out_px, a_px), b_px) in out.iter_mut().zip(a.iter()).zip(b.iter()) {
-            *out_px = (*a_px as f32 * ip + *b_px as f32 * p) as u8;
-        }
-        out
+        a.iter()
+            .zip(b)
+            .take((w * h) as usize)
+            .map(|(a_px, b_px)| mix(*a_px, *b_px, p))
+            .collect()
     }
 }
@@ -1,4 +1,7 @@
+use super::mix;
+use crate::cpu::Rgba;
 use crate::transition::Transition;
+use proedit_core::ParamValues;
 
 pub struct DipToWhite;
 
@@ -7,34 +10,28 @@ impl Transition for DipToWhite {
         "Dip to White"
     }
 
-    fn render(&self, a: &[u8], b: &[u8], w: u32, h: u32, progress: f32) -> Vec<u8> {
-        let size = (w * h * 4) as usize;
-        let mut out = vec![0u8; size];
+    fn render(
+        &self,
+        a: &[Rgba],
+        b: &[Rgba],
+        w: u32,
+        h: u32,
+        progress: f32,
+        _params: &ParamValues,
+    ) -> Vec<Rgba> {
         let p = progress.clamp(0.0, 1.0);
-
-        if p < 0.5 {
-            // Fade A to white
-            let fade = p * 2.0;
-            for (i, (out_px, a_px)) in out.iter_mut().zip(a.iter()).enumerate() {
-                *out_px = if i % 4 == 3 {
-                    255
-                } else {
-                    let src = *a_px as f32;
-                    (src + (255.0 - src) * fade) as u8
-                };
-            }
+        // Diffuse white; brighter highlights come down to it too.
+        let white = [1.0; 4];
+        // Fade A to white, then white to B.
+        let (frame, fade) = if p < 0.5 {
+            (a, p * 2.0)
         } else {
-            // Fade white to B
-            let fade = (p - 0.5) * 2.0;
-            for (i, (out_px, b_px)) in out.iter_mut().zip(b.iter()).enumerate() {
-                *out_px = if i % 4 == 3 {
-                    255
-                } else {
-                    let dst = *b_px as f32;
-                    (255.0 + (dst - 255.0) * fade) as u8
-                };
-            }
-        }
-        out
+            (b, 2.0 - p * 2.0)
+        };
+        frame
+            .iter()
+            .take((w * h) as usize)
+            .map(|px| mix([px[0], px[1], px[2], 1.0], white, fade))
+            .collect()
     }
 }
@@ -0,0 +1,99 @@
+use super::{mix, sample, smoothstep};
+use crate::cpu::Rgba;
+use crate::transition::Transition;
+use crate::{float_descriptor, float_param, ParamDescriptor};
+use proedit_core::ParamValues;
+use std::f32::consts::PI;
+
+/// Most taps taken along the blur per pixel.
+const MAX_TAPS: usize = 48;
+
+/// Both frames are smeared along a direction, most strongly in the middle
+/// of the transition, where A cross-fades to B.
+pub struct DirectionalBlur {
+    params: Vec<ParamDescriptor>,
+}
+
+impl Default for DirectionalBlur {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DirectionalBlur {
+    pub fn new() -> Self {
+        Self {
+            params: vec![
+                float_descriptor("angle", "Angle", 0.0, -180.0, 180.0),
+                float_descriptor("length", "Length", 0.15, 0.0, 1.0),
+            ],
+        }
+    }
+}
+
+/// Average of `frame` along a line through `(x, y)`.
+fn smear(frame: &[Rgba], w: u32, h: u32, x: f32, y: f32, step: [f32; 2], taps: usize) -> Rgba {
+    let mut sum = [0.0; 4];
+    for i in 0..taps {
+        let t = i as f32 - (taps - 1) as f32 * 0.5;
+        let px = sample(frame, w, h, x + step[0] * t, y + step[1] * t);
+        for c in 0..4 {
+            sum[c] += px[c];
+        }
+    }
+    sum.map(|c| c / taps as f32)
+}
+
+impl Transition for DirectionalBlur {
+    fn name(&self) -> &str {
+        "Directional Blur"
+    }
+
+    fn params(&self) -> &[ParamDescriptor] {
+        &self.params
+    }
+
+    fn render(
+        &self,
+        a: &[Rgba],
+        b: &[Rgba],
+        w: u32,
+        h: u32,
+        progress: f32,
+        params: &ParamValues,
+    ) -> Vec<Rgba> {
+        let mut out = vec![[0.0; 4]; (w * h) as usize];
+        let p = progress.clamp(0.0, 1.0);
+        let angle = float_param(&self.params, params, "angle").to_radians();
+        let length = float_param(&self.params, params, "length").max(0.0);
+        // Blur length in pixels, peaking halfway.
+        let extent = length * w as f32 * (PI * p).sin();
+        let taps = (extent.ceil() as usize).clamp(1, MAX_TAPS);
+        let spacing = if taps > 1 {
+            extent / (taps - 1) as f32
+        } else {
+            0.0
+        };
+        let step = [angle.cos() * spacing, angle.sin() * spacing];
+        let fade = smoothstep(0.3, 0.7, p);
+
+        for y in 0..h {
+            for x in 0..w {
+                let idx = (y * w + x) as usize;
+                let (cx, cy) = (x as f32 + 0.5, y as f32 + 0.5);
+                let from_a = if fade < 1.0 {
+                    smear(a, w, h, cx, cy, step, taps)
+                } else {
+                    [0.0; 4]
+                };
+                let from_b = if fade > 0.0 {
+                    smear(b, w, h, cx, cy, step, taps)
+                } else {
+                    [0.0; 4]
+                };
+                out[idx] = mix(from_a, from_b, fade);
+            }
+        }
+        out
+    }
+}
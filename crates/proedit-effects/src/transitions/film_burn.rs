@@ -0,0 +1,91 @@
+use super::{mix, pixel, smoothstep};
+use crate::cpu::Rgba;
+use crate::transition::Transition;
+use crate::{color_descriptor, color_param, float_descriptor, float_param, ParamDescriptor};
+use proedit_core::ParamValues;
+use std::f32::consts::PI;
+
+/// A light leak flares across the frame, burning out to its colour in the
+/// middle of the transition while A cross-fades to B underneath.
+pub struct FilmBurn {
+    params: Vec<ParamDescriptor>,
+}
+
+impl Default for FilmBurn {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FilmBurn {
+    pub fn new() -> Self {
+        Self {
+            params: vec![
+                color_descriptor("color", "Color", [1.0, 0.45, 0.1, 1.0]),
+                float_descriptor("intensity", "Intensity", 1.0, 0.0, 2.0),
+                float_descriptor("size", "Size", 0.6, 0.05, 2.0),
+            ],
+        }
+    }
+}
+
+impl Transition for FilmBurn {
+    fn name(&self) -> &str {
+        "Film Burn"
+    }
+
+    fn params(&self) -> &[ParamDescriptor] {
+        &self.params
+    }
+
+    fn render(
+        &self,
+        a: &[Rgba],
+        b: &[Rgba],
+        w: u32,
+        h: u32,
+        progress: f32,
+        params: &ParamValues,
+    ) -> Vec<Rgba> {
+        let mut out = vec![[0.0; 4]; (w * h) as usize];
+        let p = progress.clamp(0.0, 1.0);
+        let color = color_param(&self.params, params, "color");
+        let intensity = float_param(&self.params, params, "intensity").max(0.0);
+        let radius = float_param(&self.params, params, "size").max(1e-3) * w.max(h) as f32;
+
+        // The flare's strength rises and falls once; it drifts left to
+        // right and a little up and down as it goes.
+        let strength = if p > 0.0 && p < 1.0 {
+            intensity * (PI * p).sin()
+        } else {
+            0.0
+        };
+        let centre = [
+            w as f32 * (1.5 * p - 0.25),
+            h as f32 * (0.4 + 0.2 * (2.0 * PI * p).sin()),
+        ];
+        // The whole frame flashes near the cut.
+        let flash = strength * (PI * p).sin().powi(6) * 0.6;
+        let fade = smoothstep(0.4, 0.6, p);
+
+        for y in 0..h {
+            for x in 0..w {
+                let idx = (y * w + x) as usize;
+                let (xi, yi) = (x as i64, y as i64);
+                let base = mix(pixel(a, w, h, xi, yi), pixel(b, w, h, xi, yi), fade);
+                // Stretched sideways, like light through the gate.
+                let dx = (x as f32 + 0.5 - centre[0]) / (radius * 1.5);
+                let dy = (y as f32 + 0.5 - centre[1]) / radius;
+                let leak = (strength * (-(dx * dx + dy * dy)).exp() + flash).min(1.0);
+                // Screen the leak's colour over the picture; highlights
+                // already above 1.0 are left as they are.
+                let mut px = base;
+                for c in 0..3 {
+                    px[c] = base[c] + leak * color[c] * (1.0 - base[c].min(1.0));
+                }
+                out[idx] = px;
+            }
+        }
+        out
+    }
+}
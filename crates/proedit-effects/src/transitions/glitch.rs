@@ -0,0 +1,104 @@
+use super::pixel;
+use crate::cpu::Rgba;
+use crate::transition::Transition;
+use crate::{float_descriptor, float_param, int_descriptor, int_param, ParamDescriptor};
+use proedit_core::ParamValues;
+use std::f32::consts::PI;
+
+/// Times the displaced bands change over the transition.
+const STEPS: f32 = 12.0;
+
+/// Share of bands displaced at each step.
+const DISPLACED: f32 = 0.4;
+
+/// A digital glitch: bands of rows jump sideways and the red and blue
+/// channels split apart, strongest at the middle, where A cuts to B.
+pub struct Glitch {
+    params: Vec<ParamDescriptor>,
+}
+
+impl Default for Glitch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Glitch {
+    pub fn new() -> Self {
+        Self {
+            params: vec![
+                float_descriptor("amount", "Amount", 0.08, 0.0, 0.5),
+                float_descriptor("block_size", "Block Size", 0.08, 0.01, 0.5),
+                int_descriptor("seed", "Seed", 0, 0, i32::MAX),
+            ],
+        }
+    }
+}
+
+/// A well-mixed hash of a band, step and seed, as 0 to 1.
+fn random(band: u32, step: u32, seed: u32, salt: u32) -> f32 {
+    let mut h = band.wrapping_mul(0x8da6_b343)
+        ^ step.wrapping_mul(0xd816_3841)
+        ^ seed.wrapping_mul(0x9e37_79b9)
+        ^ salt.wrapping_mul(0xcb1a_b31f);
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x7feb_352d);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x846c_a68b);
+    h ^= h >> 16;
+    h as f32 / u32::MAX as f32
+}
+
+impl Transition for Glitch {
+    fn name(&self) -> &str {
+        "Glitch"
+    }
+
+    fn params(&self) -> &[ParamDescriptor] {
+        &self.params
+    }
+
+    fn render(
+        &self,
+        a: &[Rgba],
+        b: &[Rgba],
+        w: u32,
+        h: u32,
+        progress: f32,
+        params: &ParamValues,
+    ) -> Vec<Rgba> {
+        let mut out = vec![[0.0; 4]; (w * h) as usize];
+        let p = progress.clamp(0.0, 1.0);
+        let amount = float_param(&self.params, params, "amount").max(0.0);
+        let block = (float_param(&self.params, params, "block_size") * h as f32).max(1.0);
+        let seed = int_param(&self.params, params, "seed") as u32;
+
+        let frame = if p < 0.5 { a } else { b };
+        // Largest shift in pixels.
+        let strength = if p > 0.0 && p < 1.0 {
+            amount * w as f32 * (PI * p).sin()
+        } else {
+            0.0
+        };
+        let split = (strength * 0.25).round() as i64;
+        let step = (p * STEPS) as u32;
+
+        for y in 0..h {
+            let band = (y as f32 / block) as u32;
+            let shift = if random(band, step, seed, 0) < DISPLACED {
+                ((random(band, step, seed, 1) * 2.0 - 1.0) * strength).round() as i64
+            } else {
+                0
+            };
+            for x in 0..w {
+                let idx = (y * w + x) as usize;
+                let (sx, sy) = (x as i64 + shift, y as i64);
+                let centre = pixel(frame, w, h, sx, sy);
+                let red = pixel(frame, w, h, sx + split, sy)[0];
+                let blue = pixel(frame, w, h, sx - split, sy)[2];
+                out[idx] = [red, centre[1], blue, centre[3]];
+            }
+        }
+        out
+    }
+}
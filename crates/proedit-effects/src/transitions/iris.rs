@@ -1,4 +1,8 @@
+use super::{edge_descriptors, pixel, WipeEdge};
+use crate::cpu::Rgba;
 use crate::transition::Transition;
+use crate::ParamDescriptor;
+use proedit_core::ParamValues;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
@@ -9,36 +13,58 @@ pub enum IrisShape {
     Diamond,
 }
 
+/// A shape opening from the centre to reveal B, with optional softness
+/// and border.
 pub struct Iris {
     pub shape: IrisShape,
+    params: Vec<ParamDescriptor>,
 }
 
-impl Default for Iris {
-    fn default() -> Self {
+impl Iris {
+    pub fn new(shape: IrisShape) -> Self {
         Self {
-            shape: IrisShape::Circle,
+            shape,
+            params: edge_descriptors(),
         }
     }
 }
 
+impl Default for Iris {
+    fn default() -> Self {
+        Self::new(IrisShape::Circle)
+    }
+}
+
 impl Transition for Iris {
     fn name(&self) -> &str {
         "Iris"
     }
 
-    fn render(&self, a: &[u8], b: &[u8], w: u32, h: u32, progress: f32) -> Vec<u8> {
-        let size = (w * h * 4) as usize;
-        let mut out = vec![0u8; size];
+    fn params(&self) -> &[ParamDescriptor] {
+        &self.params
+    }
+
+    fn render(
+        &self,
+        a: &[Rgba],
+        b: &[Rgba],
+        w: u32,
+        h: u32,
+        progress: f32,
+        params: &ParamValues,
+    ) -> Vec<Rgba> {
+        let mut out = vec![[0.0; 4]; (w * h) as usize];
         let p = progress.clamp(0.0, 1.0);
+        let edge = WipeEdge::new(&self.params, params);
         let cx = w as f32 * 0.5;
         let cy = h as f32 * 0.5;
         let max_radius = (cx * cx + cy * cy).sqrt();
 
         for y in 0..h {
             for x in 0..w {
-                let idx = ((y * w + x) * 4) as usize;
-                let fx = x as f32 - cx;
-                let fy = y as f32 - cy;
+                let idx = (y * w + x) as usize;
+                let fx = x as f32 + 0.5 - cx;
+                let fy = y as f32 + 0.5 - cy;
 
                 let dist = match self.shape {
                     IrisShape::Circle => (fx * fx + fy * fy).sqrt() / max_radius,
@@ -46,14 +72,9 @@ impl Transition for Iris {
                     IrisShape::Diamond => (fx.abs() / cx + fy.abs() / cy) * 0.5,
                 };
 
-                let use_b = dist < p;
-                for c in 0..4 {
-                    out[idx + c] = if use_b {
-                        b.get(idx + c).copied().unwrap_or(0)
-                    } else {
-                        a.get(idx + c).copied().unwrap_or(0)
-                    };
-                }
+                let (x, y) = (x as i64, y as i64);
+                let px = edge.blend(pixel(a, w, h, x, y), pixel(b, w, h, x, y), dist, p);
+                out[idx] = px;
             }
         }
         out
@@ -0,0 +1,118 @@
+use super::{edge_descriptors, pixel, WipeEdge};
+use crate::cpu::Rgba;
+use crate::transition::Transition;
+use crate::{bool_descriptor, bool_param, ParamDescriptor};
+use proedit_core::ParamValues;
+
+/// A grayscale image driving a luma wipe.
+#[derive(Debug, Clone)]
+pub struct LumaMatte {
+    pub width: u32,
+    pub height: u32,
+    /// Luma per pixel, 0-255
+    pub luma: Vec<u8>,
+}
+
+impl LumaMatte {
+    /// A matte from the Rec. 709 luma of an RGBA u8 image.
+    pub fn from_rgba(width: u32, height: u32, rgba: &[u8]) -> Self {
+        let luma = rgba
+            .chunks_exact(4)
+            .take((width * height) as usize)
+            .map(|p| {
+                (0.2126 * p[0] as f32 + 0.7152 * p[1] as f32 + 0.0722 * p[2] as f32).round() as u8
+            })
+            .collect();
+        Self {
+            width,
+            height,
+            luma,
+        }
+    }
+
+    /// Luma (0 to 1) at `(u, v)` in 0-1 frame coordinates, bilinearly
+    /// filtered so a small matte scales smoothly to any frame.
+    fn at(&self, u: f32, v: f32) -> f32 {
+        if self.width == 0 || self.height == 0 {
+            return 0.0;
+        }
+        let x = u * self.width as f32 - 0.5;
+        let y = v * self.height as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let value = |x: f32, y: f32| {
+            let x = (x as i64).clamp(0, self.width as i64 - 1) as usize;
+            let y = (y as i64).clamp(0, self.height as i64 - 1) as usize;
+            self.luma
+                .get(y * self.width as usize + x)
+                .copied()
+                .unwrap_or(0) as f32
+                / 255.0
+        };
+        let top = value(x0, y0) + (value(x0 + 1.0, y0) - value(x0, y0)) * fx;
+        let bottom = value(x0, y0 + 1.0) + (value(x0 + 1.0, y0 + 1.0) - value(x0, y0 + 1.0)) * fx;
+        top + (bottom - top) * fy
+    }
+}
+
+/// B is revealed through A in order of a matte's luma, darkest first
+/// (brightest first when inverted). Without a matte it wipes left to
+/// right.
+pub struct LumaWipe {
+    pub matte: Option<LumaMatte>,
+    params: Vec<ParamDescriptor>,
+}
+
+impl Default for LumaWipe {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl LumaWipe {
+    pub fn new(matte: Option<LumaMatte>) -> Self {
+        let mut params = edge_descriptors();
+        params.push(bool_descriptor("invert", "Invert", false));
+        Self { matte, params }
+    }
+}
+
+impl Transition for LumaWipe {
+    fn name(&self) -> &str {
+        "Luma Wipe"
+    }
+
+    fn params(&self) -> &[ParamDescriptor] {
+        &self.params
+    }
+
+    fn render(
+        &self,
+        a: &[Rgba],
+        b: &[Rgba],
+        w: u32,
+        h: u32,
+        progress: f32,
+        params: &ParamValues,
+    ) -> Vec<Rgba> {
+        let mut out = vec![[0.0; 4]; (w * h) as usize];
+        let p = progress.clamp(0.0, 1.0);
+        let edge = WipeEdge::new(&self.params, params);
+        let invert = bool_param(&self.params, params, "invert");
+
+        for y in 0..h {
+            for x in 0..w {
+                let idx = (y * w + x) as usize;
+                let u = (x as f32 + 0.5) / w as f32;
+                let v = (y as f32 + 0.5) / h as f32;
+                let luma = self.matte.as_ref().map_or(u, |matte| matte.at(u, v));
+                // Just under 1, so the brightest pixels are passed too.
+                let position = if invert { 1.0 - luma } else { luma } * 0.999;
+                let (x, y) = (x as i64, y as i64);
+                let px = edge.blend(pixel(a, w, h, x, y), pixel(b, w, h, x, y), position, p);
+                out[idx] = px;
+            }
+        }
+        out
+    }
+}
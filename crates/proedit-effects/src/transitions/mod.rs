@@ -3,13 +3,309 @@
 mod cross_dissolve;
 mod dip_to_black;
 mod dip_to_white;
+mod directional_blur;
+mod film_burn;
+mod glitch;
 mod iris;
+mod luma_wipe;
 mod push;
+mod slide;
 mod wipe;
+mod zoom;
 
 pub use cross_dissolve::CrossDissolve;
 pub use dip_to_black::DipToBlack;
 pub use dip_to_white::DipToWhite;
+pub use directional_blur::DirectionalBlur;
+pub use film_burn::FilmBurn;
+pub use glitch::Glitch;
 pub use iris::{Iris, IrisShape};
+pub use luma_wipe::{LumaMatte, LumaWipe};
 pub use push::{Push, PushDirection};
+pub use slide::{Slide, SlideDirection};
 pub use wipe::{Wipe, WipeDirection};
+pub use zoom::Zoom;
+
+use crate::cpu::Rgba;
+use crate::{color_descriptor, color_param, float_descriptor, float_param, ParamDescriptor};
+use proedit_core::ParamValues;
+use proedit_gpu::{premultiply, unpremultiply};
+
+/// Softness and border parameters shared by the wipes.
+fn edge_descriptors() -> Vec<ParamDescriptor> {
+    vec![
+        float_descriptor("softness", "Softness", 0.0, 0.0, 1.0),
+        float_descriptor("border_width", "Border Width", 0.0, 0.0, 0.2),
+        color_descriptor("border_color", "Border Color", [1.0, 1.0, 1.0, 1.0]),
+    ]
+}
+
+/// The moving edge of a wipe. Each pixel has a position from 0 to 1 along
+/// the wipe; the edge passes it as progress goes from 0 to 1.
+struct WipeEdge {
+    /// Width of the blend between A and B, in position units
+    softness: f32,
+    /// Width of the border on the edge, in position units
+    border_width: f32,
+    border_color: Rgba,
+}
+
+impl WipeEdge {
+    fn new(descriptors: &[ParamDescriptor], values: &ParamValues) -> Self {
+        Self {
+            softness: float_param(descriptors, values, "softness").max(0.0),
+            border_width: float_param(descriptors, values, "border_width").max(0.0),
+            border_color: color_param(descriptors, values, "border_color"),
+        }
+    }
+
+    /// The pixel at `position` when the wipe is at `progress`. The edge
+    /// travels `1 + softness`, so the blend is fully off screen at both
+    /// ends.
+    fn blend(&self, a: Rgba, b: Rgba, position: f32, progress: f32) -> Rgba {
+        let edge = progress * (1.0 + self.softness);
+        let t = if self.softness > 0.0 {
+            ((edge - position) / self.softness).clamp(0.0, 1.0)
+        } else if position < edge {
+            1.0
+        } else {
+            0.0
+        };
+        let px = mix(a, b, t);
+        let centre = edge - self.softness * 0.5;
+        let on_border = self.border_width > 0.0
+            && progress > 0.0
+            && progress < 1.0
+            && (position - centre).abs() < self.border_width * 0.5;
+        if on_border {
+            mix(px, self.border_color, self.border_color[3])
+        } else {
+            px
+        }
+    }
+}
+
+/// The pixel at `(x, y)`, with coordinates clamped to the frame
+/// (transparent if the frame is short).
+fn pixel(frame: &[Rgba], w: u32, h: u32, x: i64, y: i64) -> Rgba {
+    let x = x.clamp(0, w as i64 - 1) as usize;
+    let y = y.clamp(0, h as i64 - 1) as usize;
+    frame.get(y * w as usize + x).copied().unwrap_or([0.0; 4])
+}
+
+/// Bilinear sample at pixel coordinates `(x, y)` (pixel centres at
+/// `n + 0.5`), clamped at the frame edges. Texels are filtered
+/// premultiplied so transparent neighbours don't bleed dark fringes.
+fn sample(frame: &[Rgba], w: u32, h: u32, x: f32, y: f32) -> Rgba {
+    let (x, y) = (x - 0.5, y - 0.5);
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let (x0, y0) = (x0 as i64, y0 as i64);
+    let texel = |x, y| premultiply(pixel(frame, w, h, x, y));
+    let top = lerp(texel(x0, y0), texel(x0 + 1, y0), fx);
+    let bottom = lerp(texel(x0, y0 + 1), texel(x0 + 1, y0 + 1), fx);
+    unpremultiply(lerp(top, bottom, fy))
+}
+
+/// `a` at 0, `b` at 1; exact at both ends. Interpolates premultiplied, so
+/// fading from a transparent pixel keeps the opaque side's colour.
+fn mix(a: Rgba, b: Rgba, t: f32) -> Rgba {
+    if t <= 0.0 {
+        return a;
+    }
+    if t >= 1.0 {
+        return b;
+    }
+    unpremultiply(lerp(premultiply(a), premultiply(b), t))
+}
+
+fn lerp(a: Rgba, b: Rgba, t: f32) -> Rgba {
+    [0, 1, 2, 3].map(|c| a[c] * (1.0 - t) + b[c] * t)
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transition::{Transition, TransitionRegistry};
+    use proedit_core::ParamValue;
+    use proedit_test_support::golden_path;
+
+    const W: u32 = 64;
+    const H: u32 = 36;
+
+    fn to_float(rgba: &[u8]) -> Vec<Rgba> {
+        rgba.chunks_exact(4)
+            .map(|p| [p[0], p[1], p[2], p[3]].map(|c| c as f32 / 255.0))
+            .collect()
+    }
+
+    /// Warm diagonal stripes.
+    fn frame_a() -> Vec<Rgba> {
+        let mut frame = Vec::with_capacity((W * H * 4) as usize);
+        for y in 0..H {
+            for x in 0..W {
+                let stripe = ((x + y) / 6) % 2 == 0;
+                let v = (x * 255 / W) as u8;
+                let px = if stripe {
+                    [230, 120, v / 2, 255]
+                } else {
+                    [120, 40, 30, 255]
+                };
+                frame.extend_from_slice(&px);
+            }
+        }
+        to_float(&frame)
+    }
+
+    /// Cool checkerboard with a vertical ramp.
+    fn frame_b() -> Vec<Rgba> {
+        let mut frame = Vec::with_capacity((W * H * 4) as usize);
+        for y in 0..H {
+            for x in 0..W {
+                let check = (x / 8 + y / 8) % 2 == 0;
+                let v = (y * 255 / H) as u8;
+                let px = if check {
+                    [20, v, 200, 255]
+                } else {
+                    [200, 230, 240, 255]
+                };
+                frame.extend_from_slice(&px);
+            }
+        }
+        to_float(&frame)
+    }
+
+    /// A radial matte, dark in the middle.
+    fn radial_matte() -> LumaMatte {
+        let (mw, mh) = (32, 18);
+        let mut rgba = Vec::new();
+        for y in 0..mh {
+            for x in 0..mw {
+                let dx = (x as f32 + 0.5) / mw as f32 - 0.5;
+                let dy = (y as f32 + 0.5) / mh as f32 - 0.5;
+                let v = ((dx * dx + dy * dy).sqrt() * 2.0 * 255.0).min(255.0) as u8;
+                rgba.extend_from_slice(&[v, v, v, 255]);
+            }
+        }
+        LumaMatte::from_rgba(mw, mh, &rgba)
+    }
+
+    const PROGRESS: [f32; 3] = [0.25, 0.5, 0.75];
+
+    /// Compare renders at each of `PROGRESS`, stacked top to bottom,
+    /// against `tests/golden/transitions/<name>.png` (see
+    /// `proedit_test_support::assert_golden`). Channels may differ by 2 for
+    /// float rounding.
+    fn assert_golden(name: &str, transition: &dyn Transition, params: &ParamValues) {
+        let (a, b) = (frame_a(), frame_b());
+        let mut strip = Vec::new();
+        for progress in PROGRESS {
+            let pixels = transition.render(&a, &b, W, H, progress, params);
+            strip.extend(
+                pixels
+                    .iter()
+                    .flat_map(|px| px.map(|v| (v.clamp(0.0, 1.0) * 255.0).round() as u8)),
+            );
+        }
+        let path = golden_path(env!("CARGO_MANIFEST_DIR"), &format!("transitions/{name}"));
+        let height = H * PROGRESS.len() as u32;
+        proedit_test_support::assert_golden(&path, W, height, &strip, 2);
+    }
+
+    fn values(pairs: &[(&str, ParamValue)]) -> ParamValues {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_every_transition_starts_at_a_and_ends_at_b() {
+        let (a, b) = (frame_a(), frame_b());
+        let registry = TransitionRegistry::new();
+        for name in registry.names() {
+            let transition = registry.find(name).unwrap();
+            let params = ParamValues::new();
+            assert_eq!(transition.render(&a, &b, W, H, 0.0, &params), a, "{name}");
+            assert_eq!(transition.render(&a, &b, W, H, 1.0, &params), b, "{name}");
+        }
+    }
+
+    #[test]
+    fn test_dissolve_from_transparent_keeps_colour() {
+        let clear = vec![[0.0; 4]; (W * H) as usize];
+        let red = vec![[1.0, 0.0, 0.0, 1.0]; (W * H) as usize];
+        let params = ParamValues::new();
+        let pixels = CrossDissolve.render(&clear, &red, W, H, 0.5, &params);
+        for px in pixels {
+            assert!((px[0] - 1.0).abs() < 1e-6, "{px:?}");
+            assert!((px[3] - 0.5).abs() < 1e-6, "{px:?}");
+        }
+    }
+
+    #[test]
+    fn test_sample_has_no_dark_fringe_at_transparent_edge() {
+        // Opaque white on the left half, transparent black on the right.
+        let frame: Vec<Rgba> = (0..W * H)
+            .map(|i| if i % W < W / 2 { [1.0; 4] } else { [0.0; 4] })
+            .collect();
+        let px = sample(&frame, W, H, W as f32 / 2.0, 4.5);
+        assert!((px[0] - 1.0).abs() < 1e-6, "{px:?}");
+        assert!((px[3] - 0.5).abs() < 1e-6, "{px:?}");
+    }
+
+    #[test]
+    fn test_golden_soft_bordered_wipe() {
+        let params = values(&[
+            ("softness", ParamValue::Float(0.2)),
+            ("border_width", ParamValue::Float(0.05)),
+            ("border_color", ParamValue::Color([1.0, 1.0, 0.0, 1.0])),
+        ]);
+        assert_golden("wipe_soft_border", &Wipe::default(), &params);
+    }
+
+    #[test]
+    fn test_golden_slide() {
+        assert_golden(
+            "slide_up",
+            &Slide::new(SlideDirection::Up),
+            &ParamValues::new(),
+        );
+    }
+
+    #[test]
+    fn test_golden_zoom() {
+        assert_golden("zoom", &Zoom::new(), &ParamValues::new());
+    }
+
+    #[test]
+    fn test_golden_directional_blur() {
+        let params = values(&[("angle", ParamValue::Float(30.0))]);
+        assert_golden("directional_blur", &DirectionalBlur::new(), &params);
+    }
+
+    #[test]
+    fn test_golden_luma_wipe() {
+        let params = values(&[("softness", ParamValue::Float(0.1))]);
+        let wipe = LumaWipe::new(Some(radial_matte()));
+        assert_golden("luma_wipe", &wipe, &params);
+        let inverted = values(&[("invert", ParamValue::Bool(true))]);
+        assert_golden("luma_wipe_inverted", &wipe, &inverted);
+    }
+
+    #[test]
+    fn test_golden_film_burn() {
+        assert_golden("film_burn", &FilmBurn::new(), &ParamValues::new());
+    }
+
+    #[test]
+    fn test_golden_glitch() {
+        let params = values(&[("seed", ParamValue::Int(7))]);
+        assert_golden("glitch", &Glitch::new(), &params);
+    }
+}
@@ -1,4 +1,6 @@
+use crate::cpu::Rgba;
 use crate::transition::Transition;
+use proedit_core::ParamValues;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
@@ -27,16 +29,23 @@ impl Transition for Push {
         "Push"
     }
 
-    fn render(&self, a: &[u8], b: &[u8], w: u32, h: u32, progress: f32) -> Vec<u8> {
-        let size = (w * h * 4) as usize;
-        let mut out = vec![0u8; size];
+    fn render(
+        &self,
+        a: &[Rgba],
+        b: &[Rgba],
+        w: u32,
+        h: u32,
+        progress: f32,
+        _params: &ParamValues,
+    ) -> Vec<Rgba> {
+        let mut out = vec![[0.0; 4]; (w * h) as usize];
         let p = progress.clamp(0.0, 1.0);
         let w_i = w as i32;
         let h_i = h as i32;
 
         for y in 0..h_i {
             for x in 0..w_i {
-                let idx = ((y * w_i + x) * 4) as usize;
+                let idx = (y * w_i + x) as usize;
                 let (src_x_a, src_y_a, src_x_b, src_y_b) = match self.direction {
                     PushDirection::Left => {
                         let offset = (w as f32 * p) as i32;
@@ -58,15 +67,11 @@ impl Transition for Push {
 
                 // Try to sample from A, then from B
                 if src_x_a >= 0 && src_x_a < w_i && src_y_a >= 0 && src_y_a < h_i {
-                    let src_idx = ((src_y_a * w_i + src_x_a) * 4) as usize;
-                    for c in 0..4 {
-                        out[idx + c] = a.get(src_idx + c).copied().unwrap_or(0);
-                    }
+                    let src_idx = (src_y_a * w_i + src_x_a) as usize;
+                    out[idx] = a.get(src_idx).copied().unwrap_or([0.0; 4]);
                 } else if src_x_b >= 0 && src_x_b < w_i && src_y_b >= 0 && src_y_b < h_i {
-                    let src_idx = ((src_y_b * w_i + src_x_b) * 4) as usize;
-                    for c in 0..4 {
-                        out[idx + c] = b.get(src_idx + c).copied().unwrap_or(0);
-                    }
+                    let src_idx = (src_y_b * w_i + src_x_b) as usize;
+                    out[idx] = b.get(src_idx).copied().unwrap_or([0.0; 4]);
                 }
             }
         }
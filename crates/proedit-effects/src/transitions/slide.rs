@@ -0,0 +1,74 @@
+use crate::cpu::Rgba;
+use crate::transition::Transition;
+use proedit_core::ParamValues;
+use serde::{Deserialize, Serialize};
+
+/// The direction B moves in as it slides over A.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum SlideDirection {
+    #[default]
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+/// B slides in over A, which stays in place (unlike `Push`).
+pub struct Slide {
+    pub direction: SlideDirection,
+}
+
+impl Slide {
+    pub fn new(direction: SlideDirection) -> Self {
+        Self { direction }
+    }
+}
+
+impl Default for Slide {
+    fn default() -> Self {
+        Self::new(SlideDirection::Left)
+    }
+}
+
+impl Transition for Slide {
+    fn name(&self) -> &str {
+        "Slide"
+    }
+
+    fn render(
+        &self,
+        a: &[Rgba],
+        b: &[Rgba],
+        w: u32,
+        h: u32,
+        progress: f32,
+        _params: &ParamValues,
+    ) -> Vec<Rgba> {
+        let mut out = vec![[0.0; 4]; (w * h) as usize];
+        let p = progress.clamp(0.0, 1.0);
+        let w_i = w as i32;
+        let h_i = h as i32;
+        // How far B is still from its place.
+        let dx = (w as f32 * (1.0 - p)).round() as i32;
+        let dy = (h as f32 * (1.0 - p)).round() as i32;
+
+        for y in 0..h_i {
+            for x in 0..w_i {
+                let idx = (y * w_i + x) as usize;
+                let (src_x, src_y) = match self.direction {
+                    SlideDirection::Left => (x - dx, y),
+                    SlideDirection::Right => (x + dx, y),
+                    SlideDirection::Up => (x, y - dy),
+                    SlideDirection::Down => (x, y + dy),
+                };
+                let (frame, src_idx) = if src_x >= 0 && src_x < w_i && src_y >= 0 && src_y < h_i {
+                    (b, (src_y * w_i + src_x) as usize)
+                } else {
+                    (a, idx)
+                };
+                out[idx] = frame.get(src_idx).copied().unwrap_or([0.0; 4]);
+            }
+        }
+        out
+    }
+}
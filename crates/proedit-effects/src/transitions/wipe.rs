@@ -1,4 +1,8 @@
+use super::{edge_descriptors, pixel, WipeEdge};
+use crate::cpu::Rgba;
 use crate::transition::Transition;
+use crate::ParamDescriptor;
+use proedit_core::ParamValues;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
@@ -10,46 +14,64 @@ pub enum WipeDirection {
     Down,
 }
 
+/// A straight edge sweeping B over A, with optional softness and border.
 pub struct Wipe {
     pub direction: WipeDirection,
+    params: Vec<ParamDescriptor>,
 }
 
-impl Default for Wipe {
-    fn default() -> Self {
+impl Wipe {
+    pub fn new(direction: WipeDirection) -> Self {
         Self {
-            direction: WipeDirection::Left,
+            direction,
+            params: edge_descriptors(),
         }
     }
 }
 
+impl Default for Wipe {
+    fn default() -> Self {
+        Self::new(WipeDirection::Left)
+    }
+}
+
 impl Transition for Wipe {
     fn name(&self) -> &str {
         "Wipe"
     }
 
-    fn render(&self, a: &[u8], b: &[u8], w: u32, h: u32, progress: f32) -> Vec<u8> {
-        let size = (w * h * 4) as usize;
-        let mut out = vec![0u8; size];
+    fn params(&self) -> &[ParamDescriptor] {
+        &self.params
+    }
+
+    fn render(
+        &self,
+        a: &[Rgba],
+        b: &[Rgba],
+        w: u32,
+        h: u32,
+        progress: f32,
+        params: &ParamValues,
+    ) -> Vec<Rgba> {
+        let mut out = vec![[0.0; 4]; (w * h) as usize];
         let p = progress.clamp(0.0, 1.0);
+        let edge = WipeEdge::new(&self.params, params);
 
         for y in 0..h {
             for x in 0..w {
-                let idx = ((y * w + x) * 4) as usize;
-                let threshold = match self.direction {
-                    WipeDirection::Left => x as f32 / w as f32,
-                    WipeDirection::Right => 1.0 - x as f32 / w as f32,
-                    WipeDirection::Up => y as f32 / h as f32,
-                    WipeDirection::Down => 1.0 - y as f32 / h as f32,
+                let idx = (y * w + x) as usize;
+                // Pixel centres, so every pixel is passed before the end.
+                let u = (x as f32 + 0.5) / w as f32;
+                let v = (y as f32 + 0.5) / h as f32;
+                let position = match self.direction {
+                    WipeDirection::Left => u,
+                    WipeDirection::Right => 1.0 - u,
+                    WipeDirection::Up => v,
+                    WipeDirection::Down => 1.0 - v,
                 };
-
-                let use_b = threshold < p;
-                for c in 0..4 {
-                    out[idx + c] = if use_b {
-                        b.get(idx + c).copied().unwrap_or(0)
-                    } else {
-                        a.get(idx + c).copied().unwrap_or(0)
-                    };
-                }
+                let (x, y) = (x as i64, y as i64);
+                let px = edge.blend(pixel(a, w, h, x, y), pixel(b, w, h, x, y), position, p);
+                out[idx] = px;
             }
         }
         out
@@ -0,0 +1,66 @@
+use super::{mix, sample, smoothstep};
+use crate::cpu::Rgba;
+use crate::transition::Transition;
+use crate::{float_descriptor, float_param, ParamDescriptor};
+use proedit_core::ParamValues;
+
+/// A zooms in towards the centre while B, zoomed in by the same amount,
+/// settles back to full frame; the two cross-fade in the middle.
+pub struct Zoom {
+    params: Vec<ParamDescriptor>,
+}
+
+impl Default for Zoom {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Zoom {
+    pub fn new() -> Self {
+        Self {
+            params: vec![float_descriptor("scale", "Scale", 2.0, 1.0, 8.0)],
+        }
+    }
+}
+
+impl Transition for Zoom {
+    fn name(&self) -> &str {
+        "Zoom"
+    }
+
+    fn params(&self) -> &[ParamDescriptor] {
+        &self.params
+    }
+
+    fn render(
+        &self,
+        a: &[Rgba],
+        b: &[Rgba],
+        w: u32,
+        h: u32,
+        progress: f32,
+        params: &ParamValues,
+    ) -> Vec<Rgba> {
+        let mut out = vec![[0.0; 4]; (w * h) as usize];
+        let p = progress.clamp(0.0, 1.0);
+        let scale = float_param(&self.params, params, "scale").max(1.0);
+        let zoom_a = 1.0 + (scale - 1.0) * p;
+        let zoom_b = scale - (scale - 1.0) * p;
+        let fade = smoothstep(0.25, 0.75, p);
+        let cx = w as f32 * 0.5;
+        let cy = h as f32 * 0.5;
+
+        for y in 0..h {
+            for x in 0..w {
+                let idx = (y * w + x) as usize;
+                let fx = x as f32 + 0.5 - cx;
+                let fy = y as f32 + 0.5 - cy;
+                let from_a = sample(a, w, h, cx + fx / zoom_a, cy + fy / zoom_a);
+                let from_b = sample(b, w, h, cx + fx / zoom_b, cy + fy / zoom_b);
+                out[idx] = mix(from_a, from_b, fade);
+            }
+        }
+        out
+    }
+}
@@ -0,0 +1,89 @@
+//! Vignette: darkens (or tints) the frame towards its corners.
+
+use crate::cpu::Rgba;
+use rayon::prelude::*;
+
+/// Vignette processor.
+pub struct VignetteProcessor;
+
+impl VignetteProcessor {
+    /// Vignette amount (0..1) at pixel (`x`, `y`).
+    ///
+    /// Distance is measured from the centre so that the corners are at
+    /// 1.0. Nothing changes inside `radius * (1 - softness)`, and the
+    /// full amount is reached at `radius`.
+    pub fn mask(x: u32, y: u32, w: u32, h: u32, radius: f32, softness: f32) -> f32 {
+        let dx = (x as f32 + 0.5) / w as f32 - 0.5;
+        let dy = (y as f32 + 0.5) / h as f32 - 0.5;
+        let d = (dx * dx + dy * dy).sqrt() * std::f32::consts::SQRT_2;
+        let outer = radius.max(0.0);
+        let inner = outer * (1.0 - softness.clamp(0.0, 1.0));
+        if d <= inner {
+            0.0
+        } else if d >= outer {
+            1.0
+        } else {
+            let t = (d - inner) / (outer - inner);
+            t * t * (3.0 - 2.0 * t)
+        }
+    }
+
+    /// Blend `pixels` (w*h) towards `color` by `intensity` outside the
+    /// radius. The colour's alpha scales the effect.
+    pub fn apply(
+        pixels: &mut [Rgba],
+        w: u32,
+        h: u32,
+        intensity: f32,
+        radius: f32,
+        softness: f32,
+        color: Rgba,
+    ) {
+        if intensity <= 0.0 || w == 0 {
+            return;
+        }
+        pixels
+            .par_chunks_mut(w as usize)
+            .enumerate()
+            .for_each(|(y, row)| {
+                for (x, px) in row.iter_mut().enumerate() {
+                    let amount = Self::mask(x as u32, y as u32, w, h, radius, softness)
+                        * intensity
+                        * color[3];
+                    for (c, &target) in px[..3].iter_mut().zip(&color[..3]) {
+                        *c += (target - *c) * amount;
+                    }
+                }
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_centre_untouched_corners_darkened() {
+        let (w, h) = (32u32, 18u32);
+        let mut pixels = vec![[1.0, 1.0, 1.0, 1.0]; (w * h) as usize];
+        VignetteProcessor::apply(&mut pixels, w, h, 0.5, 0.75, 0.5, [0.0, 0.0, 0.0, 1.0]);
+
+        let at = |x: u32, y: u32| pixels[(y * w + x) as usize];
+        assert_eq!(at(16, 9), [1.0, 1.0, 1.0, 1.0]);
+        assert!((at(0, 0)[0] - 0.5).abs() < 1e-3, "{:?}", at(0, 0));
+        assert_eq!(at(0, 0), at(w - 1, h - 1));
+        assert!(at(4, 9)[0] > at(0, 0)[0]);
+        assert!(pixels.iter().all(|p| p[3] == 1.0));
+    }
+
+    #[test]
+    fn test_mask_is_monotonic() {
+        let mut last = 0.0;
+        for x in 16..32 {
+            let m = VignetteProcessor::mask(x, 16, 32, 32, 0.8, 0.6);
+            assert!(m >= last);
+            last = m;
+        }
+        assert_eq!(VignetteProcessor::mask(0, 0, 32, 32, 0.5, 0.0), 1.0);
+    }
+}
@@ -0,0 +1,163 @@
+//! Countdown leaders.
+//!
+//! Drawn like an academy leader: a wedge sweeps clockwise from twelve
+//! o'clock once a second over two rings and a crosshair, with the number
+//! of seconds left in the middle.
+
+use proedit_core::{FrameBuffer, KeyframeTrack, ProEditError, RationalTime, Result};
+use proedit_text::{pixmap_to_frame, TextRenderer};
+use proedit_timeline::{CountdownFrame, TextGenerator};
+use std::f32::consts::TAU;
+use tiny_skia::{Color, FillRule, Paint, PathBuilder, Pixmap, PixmapPaint, Stroke, Transform};
+
+/// Segments of a full circle when drawing the sweep.
+const SWEEP_SEGMENTS: usize = 120;
+
+/// Height of the numbers, in pixels of a 1080-line frame.
+const NUMBER_SIZE: f32 = 560.0;
+
+/// A countdown frame (see `CountdownGenerator`); the numbers are set with
+/// `text`'s default sans-serif face.
+pub fn countdown(
+    text: &TextRenderer,
+    width: u32,
+    height: u32,
+    params: &CountdownFrame,
+) -> Result<FrameBuffer> {
+    let mut pixmap = Pixmap::new(width, height).ok_or_else(|| {
+        ProEditError::InvalidParameter(format!("Invalid countdown size {width}x{height}"))
+    })?;
+    pixmap.fill(color(params.background));
+    let Some(number) = params.number else {
+        return Ok(pixmap_to_frame(&pixmap));
+    };
+
+    let (w, h) = (width as f32, height as f32);
+    let (cx, cy) = (w / 2.0, h / 2.0);
+    if params.sweep > 0.0 {
+        // Long enough to reach the corners.
+        let radius = w.hypot(h);
+        let mut wedge = PathBuilder::new();
+        wedge.move_to(cx, cy);
+        let steps = ((SWEEP_SEGMENTS as f32 * params.sweep).ceil() as usize).max(1);
+        for i in 0..=steps {
+            let angle = TAU * params.sweep * i as f32 / steps as f32;
+            wedge.line_to(cx + radius * angle.sin(), cy - radius * angle.cos());
+        }
+        wedge.close();
+        if let Some(path) = wedge.finish() {
+            let paint = paint(params.sweep_color);
+            pixmap.fill_path(
+                &path,
+                &paint,
+                FillRule::Winding,
+                Transform::identity(),
+                None,
+            );
+        }
+    }
+
+    let line = paint(params.foreground);
+    let mut crosshair = PathBuilder::new();
+    crosshair.move_to(0.0, cy);
+    crosshair.line_to(w, cy);
+    crosshair.move_to(cx, 0.0);
+    crosshair.line_to(cx, h);
+    let mut rings = PathBuilder::new();
+    rings.push_circle(cx, cy, 0.42 * h);
+    rings.push_circle(cx, cy, 0.36 * h);
+    for (path, width) in [(crosshair, h / 270.0), (rings, h / 90.0)] {
+        if let Some(path) = path.finish() {
+            let stroke = Stroke {
+                width,
+                ..Stroke::default()
+            };
+            pixmap.stroke_path(&path, &line, &stroke, Transform::identity(), None);
+        }
+    }
+
+    let mut digits = TextGenerator::new(number.to_string(), NUMBER_SIZE);
+    digits.fill = params.foreground;
+    digits.position_y = KeyframeTrack::constant("position_y", 0.5);
+    let digits = text.render_pixmap(&digits, RationalTime::ZERO, width, height)?;
+    pixmap.draw_pixmap(
+        0,
+        0,
+        digits.as_ref(),
+        &PixmapPaint::default(),
+        Transform::identity(),
+        None,
+    );
+    Ok(pixmap_to_frame(&pixmap))
+}
+
+fn color(rgba: [f32; 4]) -> Color {
+    let [r, g, b, a] = rgba.map(|c| c.clamp(0.0, 1.0));
+    Color::from_rgba(r, g, b, a).unwrap_or(Color::BLACK)
+}
+
+fn paint(rgba: [f32; 4]) -> Paint<'static> {
+    let mut paint = Paint::default();
+    paint.set_color(color(rgba));
+    paint.anti_alias = true;
+    paint
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proedit_text::FontLibrary;
+    use proedit_timeline::CountdownGenerator;
+
+    fn renderer() -> TextRenderer {
+        let mut fonts = FontLibrary::new();
+        fonts
+            .load_file(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/../proedit-text/tests/fonts/DejaVuSans.ttf"
+            ))
+            .unwrap();
+        TextRenderer::new(fonts)
+    }
+
+    fn rgb(frame: &FrameBuffer, x: u32, y: u32) -> [u8; 3] {
+        let row = frame.primary_plane().row(y);
+        let i = x as usize * 4;
+        [row[i], row[i + 1], row[i + 2]]
+    }
+
+    #[test]
+    fn test_sweep_covers_elapsed_fraction() {
+        let leader = CountdownGenerator::new(5);
+        let frame = countdown(&renderer(), 320, 180, &leader.at(RationalTime::new(13, 4))).unwrap();
+        // A quarter into "2": the top right quadrant is swept, the rest not.
+        let background = rgb(&frame, 20, 20);
+        assert!(background[0].abs_diff(115) <= 1);
+        assert!(rgb(&frame, 300, 20)[0].abs_diff(179) <= 1);
+        assert_eq!(rgb(&frame, 300, 160), background);
+    }
+
+    #[test]
+    fn test_numbers_change_each_second() {
+        let renderer = renderer();
+        let leader = CountdownGenerator::new(3);
+        let frame_at = |seconds: i64| {
+            countdown(
+                &renderer,
+                160,
+                90,
+                &leader.at(RationalTime::new(seconds, 1)),
+            )
+            .unwrap()
+        };
+        let (three, two) = (frame_at(0), frame_at(1));
+        assert_ne!(three.planes[0].data, two.planes[0].data);
+        // The crosshair darkens the background.
+        assert!(rgb(&two, 5, 45)[0] < rgb(&two, 5, 30)[0]);
+
+        // After the count, only the background is left.
+        let done = frame_at(3);
+        let background = &done.planes[0].data[..4];
+        assert!(done.planes[0].data.chunks(4).all(|p| p == background));
+    }
+}
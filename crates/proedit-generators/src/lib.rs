@@ -0,0 +1,17 @@
+//! ProEdit Generators - Procedural clip pictures
+//!
+//! Synthesizes the frames of generator clips, which have no media:
+//! - `pattern`: Solid colours, linear and radial gradients, SMPTE bars
+//! - `noise`: Fractal gradient noise
+//! - `countdown`: Countdown leaders
+//! - `GeneratorRenderer`: Renders any `Generator`, titles included
+//!
+//! Frames are sRGB-encoded with straight alpha, like decoded media, so they
+//! enter the working space the same way.
+
+pub mod countdown;
+pub mod noise;
+pub mod pattern;
+pub mod renderer;
+
+pub use renderer::GeneratorRenderer;
@@ -0,0 +1,181 @@
+//! Fractal gradient noise.
+//!
+//! Improved Perlin noise in three dimensions, with lattice gradients picked
+//! by hashing the cell and the seed, so no permutation table is needed and
+//! any seed gives a different pattern. Octaves are summed as fractional
+//! Brownian motion.
+
+use crate::pattern::mix;
+use proedit_core::{FrameBuffer, PixelFormat, Result};
+use proedit_timeline::NoiseFrame;
+use rayon::prelude::*;
+
+/// Gradient noise seldom leaves ±0.7; stretch it to fill the range.
+const NORMALIZE: f32 = 1.4;
+
+/// A frame of fractal noise (see `NoiseGenerator`).
+pub fn noise(width: u32, height: u32, seed: u32, params: &NoiseFrame) -> Result<FrameBuffer> {
+    let cell = params.scale * height as f32;
+    let mut pixels = vec![[0.0; 4]; width as usize * height as usize];
+    pixels
+        .par_chunks_mut(width.max(1) as usize)
+        .enumerate()
+        .for_each(|(y, row)| {
+            let v = (y as f32 + 0.5) / cell + params.offset[1];
+            for (x, pixel) in row.iter_mut().enumerate() {
+                let u = (x as f32 + 0.5) / cell + params.offset[0];
+                let n = fractal_noise(
+                    [u, v, params.evolution],
+                    params.octaves,
+                    params.persistence,
+                    seed,
+                );
+                let t = 0.5 + 0.5 * n * NORMALIZE * params.contrast;
+                *pixel = mix(params.low_color, params.high_color, t.clamp(0.0, 1.0));
+            }
+        });
+    FrameBuffer::from_rgba_f32(width, height, PixelFormat::Rgba32F, &pixels)
+}
+
+/// Fractal noise at `p`, about -1 to 1: `octaves` layers, each at twice
+/// the frequency and `persistence` times the amplitude of the last. A
+/// fractional octave count fades the last layer in.
+pub fn fractal_noise(p: [f32; 3], octaves: f32, persistence: f32, seed: u32) -> f32 {
+    let mut sum = 0.0;
+    let mut total = 0.0;
+    let mut amplitude = 1.0;
+    let mut frequency = 1.0;
+    let layers = octaves.ceil().max(1.0) as u32;
+    for octave in 0..layers {
+        let weight = amplitude * (octaves - octave as f32).min(1.0);
+        let q = p.map(|c| c * frequency);
+        sum += weight * gradient_noise(q, seed.wrapping_add(octave));
+        total += weight;
+        amplitude *= persistence;
+        frequency *= 2.0;
+    }
+    if total > 0.0 {
+        sum / total
+    } else {
+        0.0
+    }
+}
+
+/// Improved Perlin noise at `p`, zero on every lattice point.
+pub fn gradient_noise(p: [f32; 3], seed: u32) -> f32 {
+    let cell = p.map(|c| c.floor());
+    let f = [p[0] - cell[0], p[1] - cell[1], p[2] - cell[2]];
+    let [i, j, k] = cell.map(|c| c as i32);
+    let [u, v, w] = f.map(fade);
+    let corner = |dx: i32, dy: i32, dz: i32| {
+        let hash = hash(i + dx, j + dy, k + dz, seed);
+        grad(hash, f[0] - dx as f32, f[1] - dy as f32, f[2] - dz as f32)
+    };
+    let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+    lerp(
+        lerp(
+            lerp(corner(0, 0, 0), corner(1, 0, 0), u),
+            lerp(corner(0, 1, 0), corner(1, 1, 0), u),
+            v,
+        ),
+        lerp(
+            lerp(corner(0, 0, 1), corner(1, 0, 1), u),
+            lerp(corner(0, 1, 1), corner(1, 1, 1), u),
+            v,
+        ),
+        w,
+    )
+}
+
+/// Quintic ease, so the noise has continuous second derivatives.
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn hash(x: i32, y: i32, z: i32, seed: u32) -> u32 {
+    let mut h = (x as u32).wrapping_mul(0x8da6_b343)
+        ^ (y as u32).wrapping_mul(0xd816_3841)
+        ^ (z as u32).wrapping_mul(0xcb1a_b31f)
+        ^ seed.wrapping_mul(0x9e37_79b9);
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x7feb_352d);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x846c_a68b);
+    h ^ (h >> 16)
+}
+
+/// Dot product with one of Perlin's twelve edge gradients.
+fn grad(hash: u32, x: f32, y: f32, z: f32) -> f32 {
+    match hash % 12 {
+        0 => x + y,
+        1 => -x + y,
+        2 => x - y,
+        3 => -x - y,
+        4 => x + z,
+        5 => -x + z,
+        6 => x - z,
+        7 => -x - z,
+        8 => y + z,
+        9 => -y + z,
+        10 => y - z,
+        _ => -y - z,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proedit_core::RationalTime;
+    use proedit_timeline::NoiseGenerator;
+
+    #[test]
+    fn test_gradient_noise_is_smooth_and_zero_on_lattice() {
+        assert_eq!(gradient_noise([3.0, -2.0, 7.0], 1), 0.0);
+        let mut max = 0.0f32;
+        for i in 0..1000 {
+            let p = [i as f32 * 0.0137, i as f32 * 0.0291, 0.5];
+            let a = gradient_noise(p, 9);
+            let b = gradient_noise([p[0] + 1e-3, p[1], p[2]], 9);
+            assert!((a - b).abs() < 5e-3);
+            max = max.max(a.abs());
+        }
+        assert!(max > 0.3 && max <= 1.0, "max {max}");
+    }
+
+    #[test]
+    fn test_fractal_noise_depends_on_seed_and_octaves() {
+        let p = [1.3, 2.7, 0.4];
+        assert_eq!(fractal_noise(p, 4.0, 0.5, 7), fractal_noise(p, 4.0, 0.5, 7));
+        assert_ne!(fractal_noise(p, 4.0, 0.5, 7), fractal_noise(p, 4.0, 0.5, 8));
+        assert_eq!(fractal_noise(p, 1.0, 0.5, 7), gradient_noise(p, 7));
+        // Half an octave is between one and two.
+        let one = fractal_noise(p, 1.0, 0.5, 7);
+        let two = fractal_noise(p, 2.0, 0.5, 7);
+        let half = fractal_noise(p, 1.5, 0.5, 7);
+        assert!((half - one) * (two - one) >= 0.0);
+        assert!((half - one).abs() <= (two - one).abs() + 1e-6);
+    }
+
+    #[test]
+    fn test_noise_frame_spans_colours() {
+        let params = NoiseGenerator::new(3).at(RationalTime::ZERO);
+        let frame = noise(64, 48, 3, &params).unwrap();
+        let pixels = frame.read_rgba_f32().unwrap();
+        let (lo, hi) = pixels
+            .iter()
+            .fold((1.0f32, 0.0f32), |(lo, hi), p| (lo.min(p[0]), hi.max(p[0])));
+        assert!(lo < 0.3 && hi > 0.7, "range {lo}..{hi}");
+        assert!(pixels.iter().all(|p| p[0] == p[1] && p[3] == 1.0));
+
+        let flat = NoiseFrame {
+            contrast: 0.0,
+            ..params
+        };
+        let frame = noise(8, 8, 3, &flat).unwrap();
+        assert!(frame
+            .read_rgba_f32()
+            .unwrap()
+            .iter()
+            .all(|p| (p[0] - 0.5).abs() < 1e-6));
+    }
+}
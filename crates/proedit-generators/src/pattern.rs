@@ -0,0 +1,194 @@
+//! Solid colours, gradients and colour bars.
+
+use proedit_core::{FrameBuffer, PixelFormat, Result};
+use proedit_timeline::{GradientFrame, GradientKind};
+use rayon::prelude::*;
+
+/// SMPTE EG 1 bars as 8-bit sRGB: the seven 75% bars.
+const TOP_BARS: [[u8; 3]; 7] = [
+    [192, 192, 192],
+    [192, 192, 0],
+    [0, 192, 192],
+    [0, 192, 0],
+    [192, 0, 192],
+    [192, 0, 0],
+    [0, 0, 192],
+];
+
+/// The reversed-order castellations under the bars.
+const MIDDLE_BARS: [[u8; 3]; 7] = [
+    [0, 0, 192],
+    [19, 19, 19],
+    [192, 0, 192],
+    [19, 19, 19],
+    [0, 192, 192],
+    [19, 19, 19],
+    [192, 192, 192],
+];
+
+/// -I, 100% white, +Q and black, each 5/4 of a bar wide.
+const BOTTOM_BLOCKS: [[u8; 3]; 4] = [[0, 33, 76], [255, 255, 255], [50, 0, 106], [19, 19, 19]];
+
+/// The PLUGE: below black, black and above black, a third of a bar each.
+const PLUGE: [[u8; 3]; 3] = [[9, 9, 9], [19, 19, 19], [29, 29, 29]];
+
+/// Black under the last bar.
+const BLACK: [u8; 3] = [19, 19, 19];
+
+/// A frame of one colour.
+pub fn solid(width: u32, height: u32, rgba: [f32; 4]) -> Result<FrameBuffer> {
+    let pixels = vec![rgba; width as usize * height as usize];
+    FrameBuffer::from_rgba_f32(width, height, PixelFormat::Rgba32F, &pixels)
+}
+
+/// A linear or radial gradient (see `GradientGenerator`).
+pub fn gradient(
+    width: u32,
+    height: u32,
+    kind: GradientKind,
+    params: &GradientFrame,
+) -> Result<FrameBuffer> {
+    let center = [
+        params.center[0] * width as f32,
+        params.center[1] * height as f32,
+    ];
+    let extent = (params.extent * width as f32).max(1e-3);
+    let (sin, cos) = params.angle.to_radians().sin_cos();
+    let mut pixels = vec![[0.0; 4]; width as usize * height as usize];
+    pixels
+        .par_chunks_mut(width.max(1) as usize)
+        .enumerate()
+        .for_each(|(y, row)| {
+            let dy = y as f32 + 0.5 - center[1];
+            for (x, pixel) in row.iter_mut().enumerate() {
+                let dx = x as f32 + 0.5 - center[0];
+                let t = match kind {
+                    // Clockwise on screen, since y points down.
+                    GradientKind::Linear => (dx * cos + dy * sin) / extent + 0.5,
+                    GradientKind::Radial => (dx * dx + dy * dy).sqrt() / extent,
+                };
+                *pixel = mix(params.start_color, params.end_color, t.clamp(0.0, 1.0));
+            }
+        });
+    FrameBuffer::from_rgba_f32(width, height, PixelFormat::Rgba32F, &pixels)
+}
+
+/// SMPTE EG 1 colour bars: the 75% bars over two thirds of the height,
+/// castellations for a twelfth, and -I, white, +Q and the PLUGE below.
+pub fn bars(width: u32, height: u32) -> Result<FrameBuffer> {
+    let top = height as f32 * 2.0 / 3.0;
+    let middle = height as f32 * 3.0 / 4.0;
+    let mut frame = FrameBuffer::new(width, height, PixelFormat::Rgba8);
+    let plane = frame.primary_plane_mut();
+    for y in 0..height {
+        let row = plane.row_mut(y);
+        let centre_y = y as f32 + 0.5;
+        for x in 0..width as usize {
+            // Position in bar widths.
+            let u = (x as f32 + 0.5) * 7.0 / width as f32;
+            let bar = (u as usize).min(6);
+            let rgb = if centre_y < top {
+                TOP_BARS[bar]
+            } else if centre_y < middle {
+                MIDDLE_BARS[bar]
+            } else if u < 5.0 {
+                BOTTOM_BLOCKS[((u / 1.25) as usize).min(3)]
+            } else if u < 6.0 {
+                PLUGE[(((u - 5.0) * 3.0) as usize).min(2)]
+            } else {
+                BLACK
+            };
+            row[x * 4..x * 4 + 4].copy_from_slice(&[rgb[0], rgb[1], rgb[2], 255]);
+        }
+    }
+    Ok(frame)
+}
+
+/// Blend two straight-alpha colours, premultiplied so a fade to
+/// transparent does not darken.
+pub(crate) fn mix(a: [f32; 4], b: [f32; 4], t: f32) -> [f32; 4] {
+    let alpha = a[3] + (b[3] - a[3]) * t;
+    if alpha <= 0.0 {
+        return [0.0; 4];
+    }
+    let channel = |c: usize| (a[c] * a[3] + (b[c] * b[3] - a[c] * a[3]) * t) / alpha;
+    [channel(0), channel(1), channel(2), alpha]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixel(frame: &FrameBuffer, x: u32, y: u32) -> [f32; 4] {
+        frame.read_rgba_f32().unwrap()[(y * frame.width + x) as usize]
+    }
+
+    #[test]
+    fn test_linear_gradient_follows_angle() {
+        let params = GradientFrame {
+            start_color: [0.0, 0.0, 0.0, 1.0],
+            end_color: [1.0, 1.0, 1.0, 1.0],
+            center: [0.5, 0.5],
+            angle: 0.0,
+            extent: 1.0,
+        };
+        let frame = gradient(100, 10, GradientKind::Linear, &params).unwrap();
+        assert!((pixel(&frame, 0, 5)[0] - 0.005).abs() < 1e-5);
+        assert!((pixel(&frame, 99, 5)[0] - 0.995).abs() < 1e-5);
+        assert_eq!(pixel(&frame, 30, 0), pixel(&frame, 30, 9));
+
+        // Pointing down, the ramp runs top to bottom, holding past the ends.
+        let down = GradientFrame {
+            angle: 90.0,
+            extent: 0.05,
+            ..params
+        };
+        let frame = gradient(100, 10, GradientKind::Linear, &down).unwrap();
+        assert_eq!(pixel(&frame, 50, 0)[0], 0.0);
+        assert_eq!(pixel(&frame, 50, 9)[0], 1.0);
+        assert_eq!(pixel(&frame, 0, 8), pixel(&frame, 99, 8));
+    }
+
+    #[test]
+    fn test_radial_gradient_fades_to_transparent() {
+        let params = GradientFrame {
+            start_color: [1.0, 0.5, 0.0, 1.0],
+            end_color: [1.0, 0.5, 0.0, 0.0],
+            center: [0.5, 0.5],
+            angle: 0.0,
+            extent: 0.5,
+        };
+        let frame = gradient(64, 64, GradientKind::Radial, &params).unwrap();
+        let centre = pixel(&frame, 32, 32);
+        assert!(centre[3] > 0.95);
+        let edge = pixel(&frame, 56, 32);
+        assert!(edge[3] > 0.0 && edge[3] < 0.5);
+        // Colour is kept while the alpha fades.
+        assert!((edge[1] - 0.5).abs() < 1e-5);
+        assert_eq!(pixel(&frame, 0, 0), [0.0; 4]);
+    }
+
+    #[test]
+    fn test_bars_layout() {
+        let frame = bars(140, 120).unwrap();
+        let rgb = |x, y| {
+            let [r, g, b, a] = pixel(&frame, x, y).map(|v| (v * 255.0).round() as u8);
+            assert_eq!(a, 255);
+            [r, g, b]
+        };
+        for (i, expected) in TOP_BARS.iter().enumerate() {
+            assert_eq!(rgb(i as u32 * 20 + 10, 40), *expected);
+        }
+        assert_eq!(rgb(10, 85), [0, 0, 192]);
+        assert_eq!(rgb(30, 85), BLACK);
+        // -I, white, +Q, black in 25-pixel blocks, then the PLUGE.
+        assert_eq!(rgb(12, 110), [0, 33, 76]);
+        assert_eq!(rgb(37, 110), [255, 255, 255]);
+        assert_eq!(rgb(62, 110), [50, 0, 106]);
+        assert_eq!(rgb(87, 110), BLACK);
+        assert_eq!(rgb(103, 110), PLUGE[0]);
+        assert_eq!(rgb(110, 110), PLUGE[1]);
+        assert_eq!(rgb(117, 110), PLUGE[2]);
+        assert_eq!(rgb(130, 110), BLACK);
+    }
+}
@@ -0,0 +1,108 @@
+//! Rendering any generator.
+
+use crate::countdown::countdown;
+use crate::noise::noise;
+use crate::pattern::{bars, gradient, solid};
+use proedit_core::{FrameBuffer, ProEditError, RationalTime, Result};
+use proedit_text::{FontLibrary, TextRenderer};
+use proedit_timeline::Generator;
+
+/// Renders generator clips' pictures.
+pub struct GeneratorRenderer {
+    text: TextRenderer,
+}
+
+impl GeneratorRenderer {
+    /// A renderer setting titles and countdown numbers in `fonts`.
+    pub fn new(fonts: FontLibrary) -> Self {
+        Self {
+            text: TextRenderer::new(fonts),
+        }
+    }
+
+    pub fn text(&self) -> &TextRenderer {
+        &self.text
+    }
+
+    pub fn text_mut(&mut self) -> &mut TextRenderer {
+        &mut self.text
+    }
+
+    /// Render `generator` at source time `time` into a `width`×`height`
+    /// RGBA frame (sRGB, straight alpha).
+    pub fn render(
+        &self,
+        generator: &Generator,
+        time: RationalTime,
+        width: u32,
+        height: u32,
+    ) -> Result<FrameBuffer> {
+        if width == 0 || height == 0 {
+            return Err(ProEditError::InvalidParameter(format!(
+                "Invalid generator frame size {width}x{height}"
+            )));
+        }
+        match generator {
+            Generator::Text(title) => self.text.render(title, time, width, height),
+            Generator::Solid(matte) => solid(width, height, matte.color.at(time)),
+            Generator::Gradient(ramp) => gradient(width, height, ramp.kind, &ramp.at(time)),
+            Generator::Noise(fractal) => noise(width, height, fractal.seed, &fractal.at(time)),
+            Generator::Bars(_) => bars(width, height),
+            Generator::Countdown(leader) => countdown(&self.text, width, height, &leader.at(time)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proedit_core::EasingCurve;
+    use proedit_timeline::{
+        BarsGenerator, CountdownGenerator, GradientGenerator, GradientKind, NoiseGenerator,
+        SolidGenerator,
+    };
+
+    #[test]
+    fn test_every_generator_fills_the_frame() {
+        let renderer = GeneratorRenderer::new(FontLibrary::new());
+        let mut solid = SolidGenerator::new([0.0, 0.0, 0.0, 1.0]);
+        solid.color.set(
+            RationalTime::ZERO,
+            [0.0, 0.0, 0.0, 1.0],
+            EasingCurve::Linear,
+        );
+        solid.color.set(
+            RationalTime::new(1, 1),
+            [1.0, 0.0, 0.0, 1.0],
+            EasingCurve::Linear,
+        );
+        let frame = renderer
+            .render(&Generator::Solid(solid), RationalTime::new(1, 2), 4, 4)
+            .unwrap();
+        assert_eq!(frame.read_rgba_f32().unwrap()[0], [0.5, 0.0, 0.0, 1.0]);
+
+        for generator in [
+            Generator::Gradient(GradientGenerator::new(
+                GradientKind::Radial,
+                [1.0; 4],
+                [0.0, 0.0, 0.0, 1.0],
+            )),
+            Generator::Noise(NoiseGenerator::new(1)),
+            Generator::Bars(BarsGenerator::default()),
+        ] {
+            let frame = renderer
+                .render(&generator, RationalTime::ZERO, 32, 18)
+                .unwrap();
+            assert_eq!((frame.width, frame.height), (32, 18));
+            assert!(frame.read_rgba_f32().unwrap().iter().all(|p| p[3] == 1.0));
+        }
+
+        let bars = Generator::Bars(BarsGenerator::default());
+        assert!(renderer.render(&bars, RationalTime::ZERO, 0, 18).is_err());
+        // Countdown numbers need a font.
+        let leader = Generator::Countdown(CountdownGenerator::new(3));
+        assert!(renderer
+            .render(&leader, RationalTime::ZERO, 32, 18)
+            .is_err());
+    }
+}
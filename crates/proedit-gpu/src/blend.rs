@@ -2,7 +2,12 @@
 //!
 //! Lists all supported blend modes. The actual GPU implementation
 //! uses these enums to select the correct blend operation in the
-//! compositing shader.
+//! compositing shader; [`BlendMode::blend`] is the CPU reference.
+//!
+//! Blending works on premultiplied linear-light RGBA in `f32`. Separable
+//! and non-separable modes follow the W3C Compositing and Blending formulas
+//! (`co = cs·(1 − αb) + cb·(1 − αs) + αs·αb·B(Cb, Cs)`), with Rec. 709
+//! luma weights for the HSL modes since the inputs are linear.
 
 use serde::{Deserialize, Serialize};
 
@@ -54,6 +59,9 @@ pub enum BlendMode {
     Silhouette = 27,
 }
 
+/// Premultiplied linear RGBA.
+pub type Rgba = [f32; 4];
+
 impl BlendMode {
     /// All blend modes in display order.
     pub const ALL: [BlendMode; 28] = [
@@ -139,4 +147,416 @@ impl BlendMode {
             Self::Add | Self::Stencil | Self::Silhouette => "Video",
         }
     }
+
+    /// The mode with discriminant `value`, as stored in `NodeOp::Composite`.
+    pub fn from_u32(value: u32) -> Option<Self> {
+        Self::ALL.get(value as usize).copied()
+    }
+
+    /// Blend `source` over `backdrop` at `opacity`.
+    ///
+    /// Both pixels are premultiplied. `noise` is only used by Dissolve: the
+    /// source pixel is drawn fully opaque where `noise` (0..1) falls below
+    /// its coverage, so pass [`dissolve_noise`] for the pixel position.
+    pub fn blend(self, backdrop: Rgba, source: Rgba, opacity: f32, noise: f32) -> Rgba {
+        let source = source.map(|v| v * opacity.clamp(0.0, 1.0));
+        let (ab, a_s) = (backdrop[3], source[3]);
+        match self {
+            Self::Dissolve => {
+                if noise < a_s {
+                    let [r, g, b] = unpremultiply(source);
+                    [r, g, b, 1.0]
+                } else {
+                    backdrop
+                }
+            }
+            // The layer only contributes its alpha, as a matte for what's below.
+            Self::Stencil => backdrop.map(|v| v * a_s),
+            Self::Silhouette => backdrop.map(|v| v * (1.0 - a_s)),
+            _ => {
+                let mixed = self.mix(unpremultiply(backdrop), unpremultiply(source));
+                let mut out = [0.0, 0.0, 0.0, a_s + ab * (1.0 - a_s)];
+                for c in 0..3 {
+                    out[c] =
+                        source[c] * (1.0 - ab) + backdrop[c] * (1.0 - a_s) + a_s * ab * mixed[c];
+                }
+                out
+            }
+        }
+    }
+
+    /// The blend function `B(Cb, Cs)` on straight colour.
+    fn mix(self, cb: [f32; 3], cs: [f32; 3]) -> [f32; 3] {
+        match self {
+            Self::Hue => set_lum(set_sat(cs, sat(cb)), lum(cb)),
+            Self::Saturation => set_lum(set_sat(cb, sat(cs)), lum(cb)),
+            Self::Color => set_lum(cs, lum(cb)),
+            Self::Luminosity => set_lum(cb, lum(cs)),
+            _ => [0, 1, 2].map(|c| self.separable(cb[c], cs[c])),
+        }
+    }
+
+    /// Per-channel blend function for the separable modes.
+    fn separable(self, b: f32, s: f32) -> f32 {
+        match self {
+            Self::Darken => b.min(s),
+            Self::Multiply => b * s,
+            Self::ColorBurn => color_burn(b, s),
+            Self::LinearBurn => (b + s - 1.0).max(0.0),
+            Self::Lighten => b.max(s),
+            Self::Screen => screen(b, s),
+            Self::ColorDodge => color_dodge(b, s),
+            Self::LinearDodge => (b + s).min(1.0),
+            Self::Overlay => hard_light(s, b),
+            Self::SoftLight => soft_light(b, s),
+            Self::HardLight => hard_light(b, s),
+            Self::VividLight => {
+                if s <= 0.5 {
+                    color_burn(b, 2.0 * s)
+                } else {
+                    color_dodge(b, 2.0 * s - 1.0)
+                }
+            }
+            Self::LinearLight => (b + 2.0 * s - 1.0).clamp(0.0, 1.0),
+            Self::PinLight => {
+                if s <= 0.5 {
+                    b.min(2.0 * s)
+                } else {
+                    b.max(2.0 * s - 1.0)
+                }
+            }
+            Self::HardMix => {
+                if b + s >= 1.0 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Self::Difference => (b - s).abs(),
+            Self::Exclusion => b + s - 2.0 * b * s,
+            Self::Subtract => (b - s).max(0.0),
+            Self::Divide => {
+                if s <= 0.0 {
+                    if b > 0.0 {
+                        1.0
+                    } else {
+                        0.0
+                    }
+                } else {
+                    (b / s).min(1.0)
+                }
+            }
+            // Unclamped, so HDR highlights add up.
+            Self::Add => b + s,
+            _ => s,
+        }
+    }
+}
+
+/// Deterministic noise in 0..1 for Dissolve at pixel (`x`, `y`).
+///
+/// A 32-bit integer hash, so the compositing shader produces exactly the
+/// same pattern.
+pub fn dissolve_noise(x: u32, y: u32, seed: u32) -> f32 {
+    let mut h =
+        x.wrapping_mul(0x8DA6_B343) ^ y.wrapping_mul(0xD816_3841) ^ seed.wrapping_mul(0xCB1A_B31F);
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x7FEB_352D);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x846C_A68B);
+    h ^= h >> 16;
+    (h >> 8) as f32 / (1u32 << 24) as f32
+}
+
+fn unpremultiply(px: Rgba) -> [f32; 3] {
+    if px[3] > 0.0 {
+        [px[0] / px[3], px[1] / px[3], px[2] / px[3]]
+    } else {
+        [0.0; 3]
+    }
+}
+
+fn screen(b: f32, s: f32) -> f32 {
+    b + s - b * s
+}
+
+fn hard_light(b: f32, s: f32) -> f32 {
+    if s <= 0.5 {
+        b * 2.0 * s
+    } else {
+        screen(b, 2.0 * s - 1.0)
+    }
+}
+
+fn color_burn(b: f32, s: f32) -> f32 {
+    if b >= 1.0 {
+        1.0
+    } else if s <= 0.0 {
+        0.0
+    } else {
+        1.0 - ((1.0 - b) / s).min(1.0)
+    }
+}
+
+fn color_dodge(b: f32, s: f32) -> f32 {
+    if b <= 0.0 {
+        0.0
+    } else if s >= 1.0 {
+        1.0
+    } else {
+        (b / (1.0 - s)).min(1.0)
+    }
+}
+
+fn soft_light(b: f32, s: f32) -> f32 {
+    if s <= 0.5 {
+        b - (1.0 - 2.0 * s) * b * (1.0 - b)
+    } else {
+        let d = if b <= 0.25 {
+            ((16.0 * b - 12.0) * b + 4.0) * b
+        } else {
+            b.max(0.0).sqrt()
+        };
+        b + (2.0 * s - 1.0) * (d - b)
+    }
+}
+
+fn lum(c: [f32; 3]) -> f32 {
+    0.2126 * c[0] + 0.7152 * c[1] + 0.0722 * c[2]
+}
+
+fn clip_color(c: [f32; 3]) -> [f32; 3] {
+    let l = lum(c);
+    let n = c[0].min(c[1]).min(c[2]);
+    let x = c[0].max(c[1]).max(c[2]);
+    let mut out = c;
+    if n < 0.0 {
+        out = out.map(|v| l + (v - l) * l / (l - n));
+    }
+    if x > 1.0 {
+        out = out.map(|v| l + (v - l) * (1.0 - l) / (x - l));
+    }
+    out
+}
+
+fn set_lum(c: [f32; 3], l: f32) -> [f32; 3] {
+    let d = l - lum(c);
+    clip_color(c.map(|v| v + d))
+}
+
+fn sat(c: [f32; 3]) -> f32 {
+    c[0].max(c[1]).max(c[2]) - c[0].min(c[1]).min(c[2])
+}
+
+/// Rescale `c` so its spread is `s`, keeping the channel order: the
+/// largest channel becomes `s` and the smallest 0.
+fn set_sat(c: [f32; 3], s: f32) -> [f32; 3] {
+    let lo = c[0].min(c[1]).min(c[2]);
+    let hi = c[0].max(c[1]).max(c[2]);
+    if hi > lo {
+        c.map(|v| (v - lo) * s / (hi - lo))
+    } else {
+        [0.0; 3]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn opaque(mode: BlendMode, b: [f32; 3], s: [f32; 3]) -> [f32; 3] {
+        let out = mode.blend([b[0], b[1], b[2], 1.0], [s[0], s[1], s[2], 1.0], 1.0, 0.0);
+        assert!((out[3] - 1.0).abs() < 1e-6);
+        [out[0], out[1], out[2]]
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-5
+    }
+
+    #[test]
+    fn test_from_u32_matches_discriminants() {
+        for mode in BlendMode::ALL {
+            assert_eq!(BlendMode::from_u32(mode as u32), Some(mode));
+        }
+        assert_eq!(BlendMode::from_u32(28), None);
+    }
+
+    #[test]
+    fn test_separable_modes_match_reference_values() {
+        use BlendMode::*;
+        // (mode, backdrop, source, expected), covering both sides of
+        // every branch in the W3C definitions.
+        let cases = [
+            (Normal, 0.6, 0.3, 0.3),
+            (Darken, 0.6, 0.3, 0.3),
+            (Darken, 0.2, 0.8, 0.2),
+            (Multiply, 0.6, 0.3, 0.18),
+            (Multiply, 0.5, 0.5, 0.25),
+            (ColorBurn, 0.8, 0.5, 0.6),
+            (ColorBurn, 0.6, 0.3, 0.0),
+            (ColorBurn, 1.0, 0.0, 1.0),
+            (ColorBurn, 0.5, 0.0, 0.0),
+            (LinearBurn, 0.6, 0.3, 0.0),
+            (LinearBurn, 0.8, 0.5, 0.3),
+            (Lighten, 0.6, 0.3, 0.6),
+            (Lighten, 0.2, 0.8, 0.8),
+            (Screen, 0.6, 0.3, 0.72),
+            (Screen, 0.5, 0.5, 0.75),
+            (ColorDodge, 0.2, 0.5, 0.4),
+            (ColorDodge, 0.6, 0.3, 0.6 / 0.7),
+            (ColorDodge, 0.6, 0.5, 1.0),
+            (ColorDodge, 0.0, 1.0, 0.0),
+            (ColorDodge, 0.3, 1.0, 1.0),
+            (LinearDodge, 0.6, 0.3, 0.9),
+            (LinearDodge, 0.6, 0.5, 1.0),
+            (Overlay, 0.2, 0.8, 0.32),
+            (Overlay, 0.6, 0.3, 0.44),
+            (SoftLight, 0.6, 0.3, 0.504),
+            (SoftLight, 0.2, 0.8, 0.3488),
+            (SoftLight, 0.64, 0.8, 0.736),
+            (HardLight, 0.6, 0.3, 0.36),
+            (HardLight, 0.2, 0.8, 0.68),
+            (VividLight, 0.6, 0.3, 1.0 / 3.0),
+            (VividLight, 0.2, 0.8, 0.5),
+            (LinearLight, 0.6, 0.3, 0.2),
+            (LinearLight, 0.2, 0.8, 0.8),
+            (LinearLight, 0.8, 0.8, 1.0),
+            (PinLight, 0.6, 0.3, 0.6),
+            (PinLight, 0.8, 0.2, 0.4),
+            (PinLight, 0.2, 0.8, 0.6),
+            (HardMix, 0.6, 0.3, 0.0),
+            (HardMix, 0.3, 0.8, 1.0),
+            (Difference, 0.6, 0.3, 0.3),
+            (Difference, 0.2, 0.8, 0.6),
+            (Exclusion, 0.6, 0.3, 0.54),
+            (Subtract, 0.6, 0.3, 0.3),
+            (Subtract, 0.2, 0.8, 0.0),
+            (Divide, 0.3, 0.6, 0.5),
+            (Divide, 0.6, 0.3, 1.0),
+            (Divide, 0.5, 0.0, 1.0),
+            (Divide, 0.0, 0.0, 0.0),
+            (Add, 0.6, 0.8, 1.4),
+        ];
+        for (mode, b, s, expected) in cases {
+            let out = opaque(mode, [b; 3], [s; 3]);
+            assert!(
+                out.iter().all(|&v| close(v, expected)),
+                "{mode:?}({b}, {s}) = {out:?}, expected {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_premultiplied_alpha_compositing() {
+        // Cb = 0.6 at 50%, Cs = 0.3 at 50%: 0.15·0.5 + 0.3·0.5 + 0.25·0.18.
+        let out =
+            BlendMode::Multiply.blend([0.3, 0.3, 0.3, 0.5], [0.15, 0.15, 0.15, 0.5], 1.0, 0.0);
+        assert!(close(out[0], 0.27), "{out:?}");
+        assert!(close(out[3], 0.75));
+
+        // Opacity scales the source's coverage.
+        let out = BlendMode::Normal.blend([0.0, 0.0, 0.0, 1.0], [1.0; 4], 0.5, 0.0);
+        assert_eq!(out, [0.5, 0.5, 0.5, 1.0]);
+
+        let red = [1.0, 0.0, 0.0, 1.0];
+        let clear = [0.0; 4];
+        for mode in BlendMode::ALL {
+            let over_nothing = mode.blend(clear, red, 1.0, 0.0);
+            let nothing_over = mode.blend(red, clear, 1.0, 0.0);
+            match mode {
+                BlendMode::Stencil => {
+                    assert_eq!(over_nothing, clear);
+                    assert_eq!(nothing_over, clear);
+                }
+                BlendMode::Silhouette => {
+                    assert_eq!(over_nothing, clear);
+                    assert_eq!(nothing_over, red);
+                }
+                _ => {
+                    assert_eq!(over_nothing, red, "{mode:?}");
+                    assert_eq!(nothing_over, red, "{mode:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_stencil_and_silhouette_use_source_alpha() {
+        let backdrop = [0.4, 0.2, 0.8, 1.0];
+        let matte = [0.9, 0.9, 0.9, 0.25];
+        let stencil = BlendMode::Stencil.blend(backdrop, matte, 1.0, 0.0);
+        let silhouette = BlendMode::Silhouette.blend(backdrop, matte, 1.0, 0.0);
+        assert_eq!(stencil, [0.1, 0.05, 0.2, 0.25]);
+        for (out, expected) in silhouette.into_iter().zip([0.3, 0.15, 0.6, 0.75]) {
+            assert!(close(out, expected), "{silhouette:?}");
+        }
+    }
+
+    #[test]
+    fn test_hsl_modes() {
+        let cb = [0.5, 0.25, 0.25];
+        let cs = [0.1, 0.3, 0.2];
+
+        let out = opaque(BlendMode::Luminosity, cb, [0.5; 3]);
+        let d = 0.5 - lum(cb);
+        for c in 0..3 {
+            assert!(close(out[c], cb[c] + d));
+        }
+
+        // Color keeps the backdrop's luminance with the source's hue and
+        // saturation.
+        let out = opaque(BlendMode::Color, cb, cs);
+        assert!(close(lum(out), lum(cb)));
+        assert!(close(sat(out), sat(cs)));
+        assert!(out[1] > out[2] && out[2] > out[0]);
+
+        // Hue takes only the source's channel order.
+        let out = opaque(BlendMode::Hue, cb, cs);
+        assert!(close(lum(out), lum(cb)));
+        assert!(close(sat(out), sat(cb)));
+        assert!(out[1] > out[2] && out[2] > out[0]);
+
+        // Saturation takes only the source's saturation.
+        let out = opaque(BlendMode::Saturation, cb, cs);
+        assert!(close(lum(out), lum(cb)));
+        assert!(close(sat(out), sat(cs)));
+        assert!(out[0] > out[1] && close(out[1], out[2]));
+        let grey = opaque(BlendMode::Saturation, cb, [0.7; 3]);
+        assert!(grey.iter().all(|&v| close(v, lum(cb))));
+
+        // Out-of-gamut results are pulled back to the luminance axis.
+        let out = opaque(BlendMode::Luminosity, [1.0, 0.0, 0.0], [0.9; 3]);
+        assert!(
+            out.iter().all(|&v| (0.0..=1.0 + 1e-6).contains(&v)),
+            "{out:?}"
+        );
+        assert!(close(lum(out), 0.9));
+    }
+
+    #[test]
+    fn test_dissolve_is_seeded_and_matches_coverage() {
+        let backdrop = [0.0, 0.0, 0.0, 1.0];
+        let source = [0.5, 0.25, 0.0, 0.5];
+        let render = |seed: u32| -> Vec<Rgba> {
+            (0..64 * 64)
+                .map(|i| {
+                    let noise = dissolve_noise(i % 64, i / 64, seed);
+                    BlendMode::Dissolve.blend(backdrop, source, 0.5, noise)
+                })
+                .collect()
+        };
+        let a = render(1);
+        assert_eq!(a, render(1));
+        assert_ne!(a, render(2));
+
+        // Source pixels come through opaque, at a quarter of positions.
+        let drawn = a.iter().filter(|p| **p == [1.0, 0.5, 0.0, 1.0]).count();
+        assert_eq!(
+            drawn + a.iter().filter(|p| **p == backdrop).count(),
+            a.len()
+        );
+        let fraction = drawn as f32 / a.len() as f32;
+        assert!((fraction - 0.25).abs() < 0.03, "{fraction}");
+    }
 }
@@ -39,19 +39,61 @@ impl GpuContext {
 
         info!("Using GPU adapter: {:?}", adapter.get_info());
 
+        Self::with_adapter(
+            instance,
+            adapter,
+            wgpu::Limits {
+                // Reasonable limits for 8GB M1
+                max_texture_dimension_2d: 8192,
+                max_buffer_size: 512 * 1024 * 1024, // 512MB max buffer
+                max_storage_buffer_binding_size: 256 * 1024 * 1024,
+                ..wgpu::Limits::default()
+            },
+        )
+        .await
+    }
+
+    /// Create a context on a software adapter, for offscreen rendering on
+    /// machines without a GPU (e.g. CI).
+    ///
+    /// Looks for a fallback adapter on Vulkan (lavapipe) and GL (llvmpipe
+    /// through EGL); `WGPU_BACKEND` overrides the backends searched. The
+    /// device gets whatever limits the adapter has.
+    pub async fn new_headless() -> Result<Self> {
+        let backends = wgpu::util::backend_bits_from_env()
+            .unwrap_or(wgpu::Backends::VULKAN | wgpu::Backends::GL);
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends,
+            ..Default::default()
+        });
+
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::LowPower,
+                compatible_surface: None,
+                force_fallback_adapter: true,
+            })
+            .await
+            .ok_or_else(|| ProEditError::Gpu("No software GPU adapter found".to_string()))?;
+
+        info!("Using headless adapter: {:?}", adapter.get_info());
+
+        let limits = adapter.limits();
+        Self::with_adapter(instance, adapter, limits).await
+    }
+
+    async fn with_adapter(
+        instance: wgpu::Instance,
+        adapter: wgpu::Adapter,
+        required_limits: wgpu::Limits,
+    ) -> Result<Self> {
         // Request device with limits suitable for video editing
         let (device, queue) = adapter
             .request_device(
                 &wgpu::DeviceDescriptor {
                     label: Some("ProEdit Device"),
                     required_features: wgpu::Features::empty(),
-                    required_limits: wgpu::Limits {
-                        // Reasonable limits for 8GB M1
-                        max_texture_dimension_2d: 8192,
-                        max_buffer_size: 512 * 1024 * 1024, // 512MB max buffer
-                        max_storage_buffer_binding_size: 256 * 1024 * 1024,
-                        ..wgpu::Limits::default()
-                    },
+                    required_limits,
                     memory_hints: wgpu::MemoryHints::Performance,
                 },
                 None,
@@ -72,6 +114,11 @@ impl GpuContext {
         pollster::block_on(Self::new())
     }
 
+    /// Create a headless context (blocking version).
+    pub fn new_headless_blocking() -> Result<Self> {
+        pollster::block_on(Self::new_headless())
+    }
+
     /// Get adapter info.
     pub fn adapter_info(&self) -> wgpu::AdapterInfo {
         self.adapter.get_info()
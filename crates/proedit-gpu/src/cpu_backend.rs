@@ -0,0 +1,485 @@
+//! CPU render backend.
+//!
+//! Runs render graphs on straight-alpha RGBA frames in system memory, for
+//! machines without a usable GPU adapter and as the reference the GPU
+//! backend is tested against. Sources are registered frames and effects
+//! and transitions are registered closures, so callers can plug in
+//! `proedit-effects`.
+//!
+//! All frames share one pixel format, normally a float working format
+//! holding linear light (see `proedit_color::WorkingSpace`); composites
+//! go through [`BlendMode::blend`] on the values as stored.
+
+use crate::blend::{dissolve_noise, BlendMode};
+use crate::executor::RenderBackend;
+use glam::{Mat3, Vec2};
+use proedit_core::{FrameBuffer, PixelFormat, ProEditError, Result};
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// An effect implementation: renders a new frame from its input.
+pub type CpuEffectFn = Box<dyn Fn(&FrameBuffer) -> Result<FrameBuffer> + Send + Sync>;
+
+/// A transition implementation: blends two frames at a progress.
+pub type CpuTransitionFn =
+    Box<dyn Fn(&FrameBuffer, &FrameBuffer, f32) -> Result<FrameBuffer> + Send + Sync>;
+
+/// Renders graph nodes on the CPU.
+pub struct CpuBackend {
+    format: PixelFormat,
+    sources: HashMap<u64, Arc<FrameBuffer>>,
+    effects: HashMap<String, CpuEffectFn>,
+    transitions: HashMap<String, CpuTransitionFn>,
+    dissolve_seed: u32,
+}
+
+impl Default for CpuBackend {
+    fn default() -> Self {
+        Self::with_format(PixelFormat::Rgba8)
+    }
+}
+
+impl CpuBackend {
+    /// Create an `Rgba8` backend with no sources or effects.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a backend whose frames are all `format` (an RGBA format).
+    pub fn with_format(format: PixelFormat) -> Self {
+        Self {
+            format,
+            sources: HashMap::new(),
+            effects: HashMap::new(),
+            transitions: HashMap::new(),
+            dissolve_seed: 0,
+        }
+    }
+
+    /// The pixel format of every frame this backend handles.
+    pub fn format(&self) -> PixelFormat {
+        self.format
+    }
+
+    /// Provide the frame for `Source { frame_id }` nodes.
+    pub fn set_source(&mut self, frame_id: u64, frame: impl Into<Arc<FrameBuffer>>) {
+        self.sources.insert(frame_id, frame.into());
+    }
+
+    /// Forget source frame `frame_id`.
+    pub fn remove_source(&mut self, frame_id: u64) {
+        self.sources.remove(&frame_id);
+    }
+
+    /// Forget all source frames.
+    pub fn clear_sources(&mut self) {
+        self.sources.clear();
+    }
+
+    /// Register the implementation of effect `name`.
+    pub fn register_effect(
+        &mut self,
+        name: impl Into<String>,
+        effect: impl Fn(&FrameBuffer) -> Result<FrameBuffer> + Send + Sync + 'static,
+    ) {
+        self.effects.insert(name.into(), Box::new(effect));
+    }
+
+    /// Forget the implementation of effect `name`.
+    pub fn unregister_effect(&mut self, name: &str) {
+        self.effects.remove(name);
+    }
+
+    /// Register the implementation of transition `name`.
+    pub fn register_transition(
+        &mut self,
+        name: impl Into<String>,
+        transition: impl Fn(&FrameBuffer, &FrameBuffer, f32) -> Result<FrameBuffer>
+            + Send
+            + Sync
+            + 'static,
+    ) {
+        self.transitions.insert(name.into(), Box::new(transition));
+    }
+
+    /// Seed the Dissolve pattern. Cached composites keep the old pattern
+    /// until the executor's cache is cleared.
+    pub fn set_dissolve_seed(&mut self, seed: u32) {
+        self.dissolve_seed = seed;
+    }
+}
+
+fn check(frame: &FrameBuffer, format: PixelFormat, size: (u32, u32)) -> Result<()> {
+    if frame.format != format {
+        return Err(ProEditError::UnsupportedFormat(format!(
+            "CPU backend needs {format:?} frames, got {:?}",
+            frame.format
+        )));
+    }
+    if (frame.width, frame.height) != size {
+        return Err(ProEditError::InvalidParameter(format!(
+            "Frame is {}x{}, node expects {}x{}",
+            frame.width, frame.height, size.0, size.1
+        )));
+    }
+    Ok(())
+}
+
+/// Straight-alpha RGBA to premultiplied.
+#[inline]
+pub fn premultiply([r, g, b, a]: [f32; 4]) -> [f32; 4] {
+    [r * a, g * a, b * a, a]
+}
+
+/// Premultiplied RGBA back to straight alpha. Filter lobes can push alpha
+/// past 1, so it is clamped; fully transparent pixels come back as zero.
+#[inline]
+pub fn unpremultiply([r, g, b, a]: [f32; 4]) -> [f32; 4] {
+    if a > 1e-6 {
+        [r / a, g / a, b / a, a.min(1.0)]
+    } else {
+        [0.0; 4]
+    }
+}
+
+impl RenderBackend for CpuBackend {
+    type Image = Arc<FrameBuffer>;
+
+    fn source(&mut self, frame_id: u64, size: (u32, u32)) -> Result<Self::Image> {
+        let frame = self
+            .sources
+            .get(&frame_id)
+            .ok_or_else(|| ProEditError::NotFound(format!("Source frame {frame_id}")))?;
+        check(frame, self.format, size)?;
+        Ok(Arc::clone(frame))
+    }
+
+    fn effect(&mut self, name: &str, input: &Self::Image, size: (u32, u32)) -> Result<Self::Image> {
+        let effect = self
+            .effects
+            .get(name)
+            .ok_or_else(|| ProEditError::NotFound(format!("Effect {name}")))?;
+        let output = effect(input)?;
+        check(&output, self.format, size)?;
+        Ok(Arc::new(output))
+    }
+
+    fn transition(
+        &mut self,
+        name: &str,
+        a: &Self::Image,
+        b: &Self::Image,
+        progress: f32,
+        size: (u32, u32),
+    ) -> Result<Self::Image> {
+        let transition = self
+            .transitions
+            .get(name)
+            .ok_or_else(|| ProEditError::NotFound(format!("Transition {name}")))?;
+        check(a, self.format, size)?;
+        check(b, self.format, size)?;
+        let output = transition(a, b, progress)?;
+        check(&output, self.format, size)?;
+        Ok(Arc::new(output))
+    }
+
+    /// Blend `layer` over `base` with the mode and opacity, premultiplied.
+    fn composite(
+        &mut self,
+        base: &Self::Image,
+        layer: &Self::Image,
+        blend_mode: u32,
+        opacity: f32,
+        size: (u32, u32),
+    ) -> Result<Self::Image> {
+        check(base, self.format, size)?;
+        check(layer, self.format, size)?;
+        let mode = BlendMode::from_u32(blend_mode).ok_or_else(|| {
+            ProEditError::InvalidParameter(format!("Unknown blend mode {blend_mode}"))
+        })?;
+        let seed = self.dissolve_seed;
+        let width = size.0 as usize;
+        let mut pixels = base.read_rgba_f32()?;
+        let layer = layer.read_rgba_f32()?;
+        pixels
+            .par_chunks_mut(width.max(1))
+            .zip(layer.par_chunks(width.max(1)))
+            .enumerate()
+            .for_each(|(y, (dst, src))| {
+                for (x, (d, s)) in dst.iter_mut().zip(src).enumerate() {
+                    let noise = dissolve_noise(x as u32, y as u32, seed);
+                    *d =
+                        unpremultiply(mode.blend(premultiply(*d), premultiply(*s), opacity, noise));
+                }
+            });
+        let output = FrameBuffer::from_rgba_f32(size.0, size.1, self.format, &pixels)?;
+        Ok(Arc::new(output))
+    }
+
+    /// Bilinear resampling through the inverse matrix; pixels that map
+    /// outside the input are transparent.
+    fn transform(
+        &mut self,
+        input: &Self::Image,
+        matrix: &[f32; 9],
+        size: (u32, u32),
+    ) -> Result<Self::Image> {
+        let inverse = Mat3::from_cols_array(matrix).inverse();
+        if !inverse.is_finite() {
+            return Err(ProEditError::InvalidParameter(
+                "Transform matrix is not invertible".into(),
+            ));
+        }
+        check(input, self.format, (input.width, input.height))?;
+        let (iw, ih) = (input.width as i64, input.height as i64);
+        let texels: Vec<[f32; 4]> = input
+            .read_rgba_f32()?
+            .into_iter()
+            .map(premultiply)
+            .collect();
+        // Premultiplied texel, transparent outside the frame.
+        let texel = |x: i64, y: i64| -> [f32; 4] {
+            if x < 0 || y < 0 || x >= iw || y >= ih {
+                return [0.0; 4];
+            }
+            texels[(y * iw + x) as usize]
+        };
+
+        let width = size.0 as usize;
+        let mut pixels = vec![[0.0; 4]; width * size.1 as usize];
+        pixels
+            .par_chunks_mut(width.max(1))
+            .enumerate()
+            .for_each(|(y, row)| {
+                for (x, px) in row.iter_mut().enumerate() {
+                    let p = inverse.transform_point2(Vec2::new(x as f32 + 0.5, y as f32 + 0.5))
+                        - Vec2::splat(0.5);
+                    let (x0, y0) = (p.x.floor(), p.y.floor());
+                    let (tx, ty) = (p.x - x0, p.y - y0);
+                    let (x0, y0) = (x0 as i64, y0 as i64);
+                    let mut acc = [0.0; 4];
+                    for (dx, dy, w) in [
+                        (0, 0, (1.0 - tx) * (1.0 - ty)),
+                        (1, 0, tx * (1.0 - ty)),
+                        (0, 1, (1.0 - tx) * ty),
+                        (1, 1, tx * ty),
+                    ] {
+                        for (a, t) in acc.iter_mut().zip(texel(x0 + dx, y0 + dy)) {
+                            *a += t * w;
+                        }
+                    }
+                    *px = unpremultiply(acc);
+                }
+            });
+        Ok(Arc::new(FrameBuffer::from_rgba_f32(
+            size.0,
+            size.1,
+            self.format,
+            &pixels,
+        )?))
+    }
+
+    fn download(&mut self, image: &Self::Image) -> Result<Vec<u8>> {
+        Ok(image.primary_plane().data.clone())
+    }
+
+    fn upload(&mut self, data: &[u8], size: (u32, u32)) -> Result<Self::Image> {
+        let mut frame = FrameBuffer::new(size.0, size.1, self.format);
+        let plane = frame.primary_plane_mut();
+        if plane.data.len() != data.len() {
+            return Err(ProEditError::InvalidParameter(
+                "Cached frame has the wrong size".into(),
+            ));
+        }
+        plane.data.copy_from_slice(data);
+        Ok(Arc::new(frame))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::GraphExecutor;
+    use crate::render_graph::{NodeOp, RenderGraph};
+
+    fn solid(w: u32, h: u32, rgba: [u8; 4]) -> FrameBuffer {
+        let mut frame = FrameBuffer::new(w, h, PixelFormat::Rgba8);
+        for y in 0..h {
+            for px in frame.primary_plane_mut().row_mut(y).chunks_exact_mut(4) {
+                px.copy_from_slice(&rgba);
+            }
+        }
+        frame
+    }
+
+    fn pixel(frame: &FrameBuffer, x: u32, y: u32) -> [u8; 4] {
+        let i = x as usize * 4;
+        frame.primary_plane().row(y)[i..i + 4].try_into().unwrap()
+    }
+
+    #[test]
+    fn test_composite_and_effect() {
+        let mut backend = CpuBackend::new();
+        backend.set_source(1, solid(4, 2, [255, 0, 0, 255]));
+        backend.set_source(2, solid(4, 2, [0, 0, 255, 255]));
+        backend.register_effect("invert", |frame: &FrameBuffer| {
+            let mut out = frame.clone();
+            for y in 0..out.height {
+                for px in out.primary_plane_mut().row_mut(y).chunks_exact_mut(4) {
+                    for c in &mut px[..3] {
+                        *c = 255 - *c;
+                    }
+                }
+            }
+            Ok(out)
+        });
+
+        let mut graph = RenderGraph::new();
+        let size = (4, 2);
+        let red = graph.add_node(NodeOp::Source { frame_id: 1 }, vec![], size);
+        let blue = graph.add_node(NodeOp::Source { frame_id: 2 }, vec![], size);
+        let comp = graph.add_node(
+            NodeOp::Composite {
+                blend_mode: 0,
+                opacity: 0.5,
+            },
+            vec![red, blue],
+            size,
+        );
+        let inverted = graph.add_node(
+            NodeOp::Effect {
+                effect_name: "invert".into(),
+            },
+            vec![comp],
+            size,
+        );
+        let out = graph.add_node(NodeOp::Output, vec![inverted], size);
+
+        let mut exec = GraphExecutor::new(backend, 1 << 20);
+        let frame = exec.execute(&graph, out).unwrap();
+        assert_eq!(pixel(&frame, 3, 1), [127, 255, 127, 255]);
+
+        // Cached result matches a fresh render.
+        let again = exec.execute(&graph, out).unwrap();
+        assert_eq!(exec.stats().cache_hits, 1);
+        assert_eq!(again.primary_plane().data, frame.primary_plane().data);
+    }
+
+    #[test]
+    fn test_transition_blends_its_inputs() {
+        let mut backend = CpuBackend::with_format(PixelFormat::Rgba32F);
+        let frame = |value: f32| {
+            FrameBuffer::from_rgba_f32(2, 2, PixelFormat::Rgba32F, &[[value, 0.0, 0.0, 1.0]; 4])
+                .unwrap()
+        };
+        backend.set_source(1, frame(3.0));
+        backend.set_source(2, frame(1.0));
+        backend.register_transition("mix", |a: &FrameBuffer, b: &FrameBuffer, t: f32| {
+            let (a, b) = (a.read_rgba_f32()?, b.read_rgba_f32()?);
+            let pixels: Vec<_> = a
+                .iter()
+                .zip(&b)
+                .map(|(a, b)| [0, 1, 2, 3].map(|c| a[c] * (1.0 - t) + b[c] * t))
+                .collect();
+            FrameBuffer::from_rgba_f32(2, 2, PixelFormat::Rgba32F, &pixels)
+        });
+
+        let mut graph = RenderGraph::new();
+        let size = (2, 2);
+        let a = graph.add_node(NodeOp::Source { frame_id: 1 }, vec![], size);
+        let b = graph.add_node(NodeOp::Source { frame_id: 2 }, vec![], size);
+        let mix = graph.add_node(
+            NodeOp::Transition {
+                transition_name: "mix".into(),
+                progress: 0.25,
+            },
+            vec![a, b],
+            size,
+        );
+        let out = graph.add_node(NodeOp::Output, vec![mix], size);
+        let mut exec = GraphExecutor::new(backend, 1 << 20);
+        let frame = exec.execute(&graph, out).unwrap();
+        assert_eq!(frame.read_rgba_f32().unwrap()[0], [2.5, 0.0, 0.0, 1.0]);
+
+        let input = exec.backend_mut().source(1, size).unwrap();
+        assert!(exec
+            .backend_mut()
+            .transition("nope", &input, &input, 0.5, size)
+            .is_err());
+    }
+
+    #[test]
+    fn test_composite_selects_blend_mode() {
+        let mut backend = CpuBackend::new();
+        let base = Arc::new(solid(2, 2, [153, 51, 255, 255]));
+        let layer = Arc::new(solid(2, 2, [102, 255, 0, 255]));
+        // Multiply: 0.6·0.4, 0.2·1.0, 1.0·0.0.
+        let multiply = BlendMode::Multiply as u32;
+        let out = backend
+            .composite(&base, &layer, multiply, 1.0, (2, 2))
+            .unwrap();
+        assert_eq!(pixel(&out, 1, 1), [61, 51, 0, 255]);
+        // Screen at half opacity: halfway to 0.76, 1.0, 1.0.
+        let screen = BlendMode::Screen as u32;
+        let out = backend
+            .composite(&base, &layer, screen, 0.5, (2, 2))
+            .unwrap();
+        assert_eq!(pixel(&out, 0, 0), [173, 153, 255, 255]);
+        assert!(backend.composite(&base, &layer, 99, 1.0, (2, 2)).is_err());
+    }
+
+    #[test]
+    fn test_float_frames_keep_highlights() {
+        let mut backend = CpuBackend::with_format(PixelFormat::Rgba16F);
+        let grey = |v: f32| {
+            Arc::new(
+                FrameBuffer::from_rgba_f32(2, 2, PixelFormat::Rgba16F, &[[v, v, v, 1.0]; 4])
+                    .unwrap(),
+            )
+        };
+        let add = BlendMode::Add as u32;
+        let out = backend
+            .composite(&grey(0.75), &grey(1.5), add, 1.0, (2, 2))
+            .unwrap();
+        assert_eq!(out.format, PixelFormat::Rgba16F);
+        assert_eq!(out.read_rgba_f32().unwrap()[3], [2.25, 2.25, 2.25, 1.0]);
+
+        // Cached images come back in the backend's format.
+        let bytes = backend.download(&out).unwrap();
+        let restored = backend.upload(&bytes, (2, 2)).unwrap();
+        assert_eq!(
+            restored.read_rgba_f32().unwrap(),
+            out.read_rgba_f32().unwrap()
+        );
+
+        let eight = Arc::new(solid(2, 2, [0; 4]));
+        assert!(backend.composite(&eight, &eight, 0, 1.0, (2, 2)).is_err());
+    }
+
+    #[test]
+    fn test_transform_translates_with_transparent_edges() {
+        let mut frame = solid(4, 4, [0, 0, 0, 0]);
+        frame.primary_plane_mut().row_mut(1)[4..8].copy_from_slice(&[10, 20, 30, 255]);
+        let mut backend = CpuBackend::new();
+        let input = Arc::new(frame);
+        let shift = Mat3::from_translation(Vec2::new(2.0, 1.0)).to_cols_array();
+        let moved = backend.transform(&input, &shift, (4, 4)).unwrap();
+        assert_eq!(pixel(&moved, 3, 2), [10, 20, 30, 255]);
+        assert_eq!(pixel(&moved, 1, 1), [0, 0, 0, 0]);
+        assert_eq!(pixel(&moved, 0, 0), [0, 0, 0, 0]);
+
+        let singular = [0.0; 9];
+        assert!(backend.transform(&input, &singular, (4, 4)).is_err());
+    }
+
+    #[test]
+    fn test_missing_source_and_effect() {
+        let mut backend = CpuBackend::new();
+        assert!(backend.source(7, (2, 2)).is_err());
+        backend.set_source(7, solid(2, 2, [0; 4]));
+        assert!(backend.source(7, (4, 4)).is_err());
+        let input = backend.source(7, (2, 2)).unwrap();
+        assert!(backend.effect("nope", &input, (2, 2)).is_err());
+    }
+}
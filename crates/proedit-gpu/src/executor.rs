@@ -0,0 +1,483 @@
+//! Render graph execution with node caching.
+//!
+//! `GraphExecutor` runs a `RenderGraph` on a `RenderBackend`. Each node is
+//! keyed by a hash of its operation, output size and the keys of its
+//! inputs, so a node whose whole subgraph is unchanged since an earlier
+//! frame is read back from the `FrameCache` instead of being rendered —
+//! and nothing below it is evaluated at all. Intermediate images are
+//! dropped as soon as their last consumer has run.
+//!
+//! Source nodes are keyed by `frame_id` alone, so ids must identify the
+//! content (e.g. media plus frame number), as with `FrameCache`. Likewise
+//! effect nodes are keyed by `effect_name` alone: a name must stand for one
+//! effect with fixed parameters, so callers put a hash of the parameters in
+//! the name and register a new name when they change. Transition nodes
+//! follow the same rule, with their progress as part of the node.
+
+use crate::render_graph::{FrameCache, NodeId, NodeOp, RenderGraph, RenderNode};
+use proedit_core::{ProEditError, Result};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+/// The operations a graph needs from a renderer.
+pub trait RenderBackend {
+    /// An image produced by a node (cheap to clone).
+    type Image: Clone;
+
+    /// Load source frame `frame_id` at `size`.
+    fn source(&mut self, frame_id: u64, size: (u32, u32)) -> Result<Self::Image>;
+
+    /// Apply effect `name` to `input`.
+    fn effect(&mut self, name: &str, input: &Self::Image, size: (u32, u32)) -> Result<Self::Image>;
+
+    /// Blend from `a` to `b` through transition `name` at `progress`
+    /// (0 is all `a`, 1 all `b`).
+    fn transition(
+        &mut self,
+        name: &str,
+        a: &Self::Image,
+        b: &Self::Image,
+        progress: f32,
+        size: (u32, u32),
+    ) -> Result<Self::Image>;
+
+    /// Composite `layer` over `base`.
+    fn composite(
+        &mut self,
+        base: &Self::Image,
+        layer: &Self::Image,
+        blend_mode: u32,
+        opacity: f32,
+        size: (u32, u32),
+    ) -> Result<Self::Image>;
+
+    /// Transform `input` by `matrix` (column-major 3x3, mapping input
+    /// pixel coordinates to output pixel coordinates).
+    fn transform(
+        &mut self,
+        input: &Self::Image,
+        matrix: &[f32; 9],
+        size: (u32, u32),
+    ) -> Result<Self::Image>;
+
+    /// Copy an image to CPU memory for the frame cache.
+    fn download(&mut self, image: &Self::Image) -> Result<Vec<u8>>;
+
+    /// Recreate an image from bytes returned by `download`.
+    fn upload(&mut self, data: &[u8], size: (u32, u32)) -> Result<Self::Image>;
+}
+
+/// What the last `execute` did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ExecStats {
+    /// Nodes rendered.
+    pub nodes_run: usize,
+    /// Nodes read back from the cache.
+    pub cache_hits: usize,
+    /// Most images alive at once (inputs plus the result being made).
+    pub peak_live: usize,
+}
+
+/// Runs render graphs on a backend, reusing unchanged subgraphs.
+pub struct GraphExecutor<B: RenderBackend> {
+    backend: B,
+    cache: FrameCache,
+    stats: ExecStats,
+}
+
+impl<B: RenderBackend> GraphExecutor<B> {
+    /// Create an executor caching up to `cache_bytes` of node output.
+    pub fn new(backend: B, cache_bytes: usize) -> Self {
+        Self {
+            backend,
+            cache: FrameCache::new(cache_bytes),
+            stats: ExecStats::default(),
+        }
+    }
+
+    /// The backend.
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    /// The backend, mutably (e.g. to update sources).
+    pub fn backend_mut(&mut self) -> &mut B {
+        &mut self.backend
+    }
+
+    /// Statistics of the last `execute`.
+    pub fn stats(&self) -> ExecStats {
+        self.stats
+    }
+
+    /// Forget all cached node output.
+    pub fn clear_cache(&mut self) {
+        self.cache.clear();
+    }
+
+    /// Render `output` and whatever it depends on.
+    pub fn execute(&mut self, graph: &RenderGraph, output: NodeId) -> Result<B::Image> {
+        self.stats = ExecStats::default();
+        let order = graph
+            .topological_sort()
+            .ok_or_else(|| ProEditError::InvalidParameter("Render graph has a cycle".into()))?;
+        let node = |id: NodeId| {
+            graph
+                .node(id)
+                .ok_or_else(|| ProEditError::NotFound(format!("Render node {}", id.0)))
+        };
+        node(output)?;
+
+        let mut keys = HashMap::with_capacity(order.len());
+        for &id in &order {
+            let key = node_key(node(id)?, &keys);
+            keys.insert(id, key);
+        }
+
+        // Walk down from the output, stopping at cached nodes.
+        let mut needed: HashMap<NodeId, bool> = HashMap::new();
+        let mut stack = vec![output];
+        while let Some(id) = stack.pop() {
+            if needed.contains_key(&id) {
+                continue;
+            }
+            let n = node(id)?;
+            let cached = is_cacheable(&n.op) && self.cache.contains(keys[&id]);
+            needed.insert(id, cached);
+            if !cached {
+                stack.extend(&n.inputs);
+            }
+        }
+
+        // Consumers still to run per image; the output is held for the caller.
+        let mut remaining: HashMap<NodeId, usize> = HashMap::from([(output, 1)]);
+        for (&id, &cached) in &needed {
+            if !cached {
+                for &input in &node(id)?.inputs {
+                    *remaining.entry(input).or_default() += 1;
+                }
+            }
+        }
+
+        let mut live: HashMap<NodeId, B::Image> = HashMap::new();
+        for id in order {
+            let Some(&cached) = needed.get(&id) else {
+                continue;
+            };
+            let n = node(id)?;
+            let key = keys[&id];
+            let image = if cached {
+                let (data, _, _) = self
+                    .cache
+                    .get(key)
+                    .ok_or_else(|| ProEditError::Internal("Cached node vanished".into()))?;
+                self.stats.cache_hits += 1;
+                self.stats.peak_live = self.stats.peak_live.max(live.len() + 1);
+                self.backend.upload(data, n.output_size)?
+            } else {
+                let image = self.run(n, &live)?;
+                self.stats.nodes_run += 1;
+                self.stats.peak_live = self.stats.peak_live.max(live.len() + 1);
+                if is_cacheable(&n.op) {
+                    let data = self.backend.download(&image)?;
+                    let (w, h) = n.output_size;
+                    self.cache.insert(key, data, w, h);
+                }
+                for input in &n.inputs {
+                    let count = remaining.get_mut(input).expect("input counted");
+                    *count -= 1;
+                    if *count == 0 {
+                        live.remove(input);
+                    }
+                }
+                image
+            };
+            live.insert(id, image);
+        }
+        live.remove(&output)
+            .ok_or_else(|| ProEditError::Internal("Output was not rendered".into()))
+    }
+
+    fn run(&mut self, node: &RenderNode, live: &HashMap<NodeId, B::Image>) -> Result<B::Image> {
+        let input = |i: usize| {
+            node.inputs
+                .get(i)
+                .and_then(|id| live.get(id))
+                .ok_or_else(|| {
+                    ProEditError::InvalidParameter(format!(
+                        "Render node {} is missing input {i}",
+                        node.id.0
+                    ))
+                })
+        };
+        let size = node.output_size;
+        match &node.op {
+            NodeOp::Source { frame_id } => self.backend.source(*frame_id, size),
+            NodeOp::Effect { effect_name } => self.backend.effect(effect_name, input(0)?, size),
+            NodeOp::Composite {
+                blend_mode,
+                opacity,
+            } => {
+                // Layers after the first go over it in order.
+                let mut image = input(0)?.clone();
+                for i in 1..node.inputs.len() {
+                    image =
+                        self.backend
+                            .composite(&image, input(i)?, *blend_mode, *opacity, size)?;
+                }
+                Ok(image)
+            }
+            NodeOp::Transition {
+                transition_name,
+                progress,
+            } => self
+                .backend
+                .transition(transition_name, input(0)?, input(1)?, *progress, size),
+            NodeOp::Transform { matrix } => self.backend.transform(input(0)?, matrix, size),
+            NodeOp::Output => input(0).cloned(),
+        }
+    }
+}
+
+/// Sources are the backend's to cache, and an output is its input.
+fn is_cacheable(op: &NodeOp) -> bool {
+    !matches!(op, NodeOp::Source { .. } | NodeOp::Output)
+}
+
+/// Hash of a node's operation, size and input keys.
+fn node_key(node: &RenderNode, keys: &HashMap<NodeId, u64>) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    match &node.op {
+        NodeOp::Source { frame_id } => (0u8, frame_id).hash(&mut hasher),
+        // The name covers the effect's parameters (see the module docs).
+        NodeOp::Effect { effect_name } => (1u8, effect_name).hash(&mut hasher),
+        NodeOp::Composite {
+            blend_mode,
+            opacity,
+        } => (2u8, blend_mode, opacity.to_bits()).hash(&mut hasher),
+        NodeOp::Transform { matrix } => (3u8, matrix.map(f32::to_bits)).hash(&mut hasher),
+        NodeOp::Output => 4u8.hash(&mut hasher),
+        NodeOp::Transition {
+            transition_name,
+            progress,
+        } => (5u8, transition_name, progress.to_bits()).hash(&mut hasher),
+    }
+    node.output_size.hash(&mut hasher);
+    for input in &node.inputs {
+        keys.get(input).hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Images are strings spelling out the operations applied.
+    #[derive(Default)]
+    struct Trace {
+        calls: Vec<String>,
+    }
+
+    impl RenderBackend for Trace {
+        type Image = String;
+
+        fn source(&mut self, frame_id: u64, _: (u32, u32)) -> Result<String> {
+            self.calls.push(format!("source {frame_id}"));
+            Ok(format!("s{frame_id}"))
+        }
+
+        fn effect(&mut self, name: &str, input: &String, _: (u32, u32)) -> Result<String> {
+            self.calls.push(format!("effect {name}"));
+            Ok(format!("{name}({input})"))
+        }
+
+        fn transition(
+            &mut self,
+            name: &str,
+            a: &String,
+            b: &String,
+            progress: f32,
+            _: (u32, u32),
+        ) -> Result<String> {
+            self.calls.push(format!("transition {name}"));
+            Ok(format!("{name}({a} to {b} at {progress})"))
+        }
+
+        fn composite(
+            &mut self,
+            base: &String,
+            layer: &String,
+            _: u32,
+            _: f32,
+            _: (u32, u32),
+        ) -> Result<String> {
+            self.calls.push("composite".into());
+            Ok(format!("{layer} over {base}"))
+        }
+
+        fn transform(&mut self, input: &String, _: &[f32; 9], _: (u32, u32)) -> Result<String> {
+            self.calls.push("transform".into());
+            Ok(format!("t({input})"))
+        }
+
+        fn download(&mut self, image: &String) -> Result<Vec<u8>> {
+            Ok(image.clone().into_bytes())
+        }
+
+        fn upload(&mut self, data: &[u8], _: (u32, u32)) -> Result<String> {
+            Ok(String::from_utf8(data.to_vec()).unwrap())
+        }
+    }
+
+    const SIZE: (u32, u32) = (4, 4);
+
+    /// blur(s{bg}) under t(s{fg}), then output.
+    fn two_layers(bg: u64, fg: u64) -> (RenderGraph, NodeId) {
+        let mut graph = RenderGraph::new();
+        let a = graph.add_node(NodeOp::Source { frame_id: bg }, vec![], SIZE);
+        let blur = graph.add_node(
+            NodeOp::Effect {
+                effect_name: "blur".into(),
+            },
+            vec![a],
+            SIZE,
+        );
+        let b = graph.add_node(NodeOp::Source { frame_id: fg }, vec![], SIZE);
+        let moved = graph.add_node(
+            NodeOp::Transform {
+                matrix: [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 2.0, 0.0, 1.0],
+            },
+            vec![b],
+            SIZE,
+        );
+        let comp = graph.add_node(
+            NodeOp::Composite {
+                blend_mode: 0,
+                opacity: 1.0,
+            },
+            vec![blur, moved],
+            SIZE,
+        );
+        let out = graph.add_node(NodeOp::Output, vec![comp], SIZE);
+        (graph, out)
+    }
+
+    #[test]
+    fn test_executes_in_dependency_order() {
+        let mut exec = GraphExecutor::new(Trace::default(), 1 << 20);
+        let (graph, out) = two_layers(1, 2);
+        assert_eq!(exec.execute(&graph, out).unwrap(), "t(s2) over blur(s1)");
+        assert_eq!(exec.stats().nodes_run, 6);
+        assert_eq!(exec.stats().cache_hits, 0);
+    }
+
+    #[test]
+    fn test_unchanged_subgraphs_come_from_cache() {
+        let mut exec = GraphExecutor::new(Trace::default(), 1 << 20);
+        let (graph, out) = two_layers(1, 2);
+        exec.execute(&graph, out).unwrap();
+
+        // Same graph: only the composite is read back.
+        exec.backend_mut().calls.clear();
+        assert_eq!(exec.execute(&graph, out).unwrap(), "t(s2) over blur(s1)");
+        assert!(exec.backend().calls.is_empty());
+        assert_eq!(exec.stats().cache_hits, 1);
+
+        // New foreground: the blurred background is reused.
+        exec.backend_mut().calls.clear();
+        let (graph, out) = two_layers(1, 3);
+        assert_eq!(exec.execute(&graph, out).unwrap(), "t(s3) over blur(s1)");
+        assert_eq!(exec.backend().calls, ["source 3", "transform", "composite"]);
+        assert_eq!(exec.stats().cache_hits, 1);
+
+        exec.clear_cache();
+        exec.backend_mut().calls.clear();
+        exec.execute(&graph, out).unwrap();
+        assert_eq!(exec.backend().calls.len(), 5);
+    }
+
+    #[test]
+    fn test_transition_progress_is_part_of_the_key() {
+        let graph_at = |progress: f32| {
+            let mut graph = RenderGraph::new();
+            let a = graph.add_node(NodeOp::Source { frame_id: 1 }, vec![], SIZE);
+            let b = graph.add_node(NodeOp::Source { frame_id: 2 }, vec![], SIZE);
+            let mix = graph.add_node(
+                NodeOp::Transition {
+                    transition_name: "Wipe".into(),
+                    progress,
+                },
+                vec![a, b],
+                SIZE,
+            );
+            let out = graph.add_node(NodeOp::Output, vec![mix], SIZE);
+            (graph, out)
+        };
+        let mut exec = GraphExecutor::new(Trace::default(), 1 << 20);
+        let (graph, out) = graph_at(0.25);
+        assert_eq!(exec.execute(&graph, out).unwrap(), "Wipe(s1 to s2 at 0.25)");
+        exec.execute(&graph, out).unwrap();
+        assert_eq!(exec.stats().cache_hits, 1);
+
+        let (graph, out) = graph_at(0.5);
+        assert_eq!(exec.execute(&graph, out).unwrap(), "Wipe(s1 to s2 at 0.5)");
+        assert_eq!(exec.stats().cache_hits, 0);
+    }
+
+    #[test]
+    fn test_intermediates_released_after_last_consumer() {
+        // A long chain never holds more than an input and its result.
+        let mut graph = RenderGraph::new();
+        let mut last = graph.add_node(NodeOp::Source { frame_id: 0 }, vec![], SIZE);
+        for i in 0..10 {
+            last = graph.add_node(
+                NodeOp::Effect {
+                    effect_name: format!("e{i}"),
+                },
+                vec![last],
+                SIZE,
+            );
+        }
+        let out = graph.add_node(NodeOp::Output, vec![last], SIZE);
+        let mut exec = GraphExecutor::new(Trace::default(), 1 << 20);
+        exec.execute(&graph, out).unwrap();
+        assert_eq!(exec.stats().peak_live, 2);
+
+        // A shared input lives until both consumers have run.
+        let mut graph = RenderGraph::new();
+        let src = graph.add_node(NodeOp::Source { frame_id: 0 }, vec![], SIZE);
+        let fx = |name: &str| NodeOp::Effect {
+            effect_name: name.into(),
+        };
+        let left = graph.add_node(fx("a"), vec![src], SIZE);
+        let right = graph.add_node(fx("b"), vec![src], SIZE);
+        let comp = graph.add_node(
+            NodeOp::Composite {
+                blend_mode: 0,
+                opacity: 0.5,
+            },
+            vec![left, right],
+            SIZE,
+        );
+        let out = graph.add_node(NodeOp::Output, vec![comp], SIZE);
+        assert_eq!(exec.execute(&graph, out).unwrap(), "b(s0) over a(s0)");
+        assert_eq!(exec.stats().peak_live, 3);
+    }
+
+    #[test]
+    fn test_rejects_bad_graphs() {
+        let mut exec = GraphExecutor::new(Trace::default(), 1 << 20);
+        let mut graph = RenderGraph::new();
+        let fx = graph.add_node(
+            NodeOp::Effect {
+                effect_name: "blur".into(),
+            },
+            vec![],
+            SIZE,
+        );
+        assert!(exec.execute(&graph, NodeId(42)).is_err());
+        assert!(exec.execute(&graph, fx).is_err());
+    }
+}
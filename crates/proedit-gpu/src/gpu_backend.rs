@@ -0,0 +1,551 @@
+//! GPU render backend and headless compositor.
+//!
+//! Runs render graphs on `Rgba16Float` textures holding straight-alpha
+//! linear RGBA, the GPU counterpart of [`CpuBackend`](crate::CpuBackend)
+//! with the same blend and resampling maths (`shaders/compositor.wgsl`).
+//! [`HeadlessCompositor`] renders a graph offscreen and reads the result
+//! back, which together with [`GpuContext::new_headless`] lets GPU-vs-CPU
+//! parity tests run on a software adapter.
+//!
+//! # Parity with the CPU reference
+//!
+//! Results are compared in linear working values with [`parity_error`] and
+//! should stay within [`PARITY_TOLERANCE`]. Both backends store half
+//! floats, but the GPU rounds intermediate values differently and its
+//! `sqrt`/division may be a few ulps off, so an exact match isn't expected.
+//! Dissolve patterns use an integer hash and match exactly.
+
+use crate::blend::BlendMode;
+use crate::context::GpuContext;
+use crate::executor::{GraphExecutor, RenderBackend};
+use crate::render_graph::{NodeId, RenderGraph};
+use crate::texture::GpuTexture;
+use bytemuck::{Pod, Zeroable};
+use glam::Mat3;
+use proedit_core::{FrameBuffer, PixelFormat, ProEditError, Result};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Largest difference allowed between GPU and CPU renders of the same
+/// graph, per channel, in linear working values. Values above 1.0 are
+/// compared relative to their magnitude.
+///
+/// Four half-float ulps at 1.0: enough for rounding differences in the
+/// blend formulas and bilinear weights, small enough to catch a wrong
+/// formula or an off-by-half-pixel sample.
+pub const PARITY_TOLERANCE: f32 = 4.0 / 1024.0;
+
+/// Working texture format.
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// A GPU effect implementation: renders `input` into `output`.
+pub type GpuEffectFn =
+    Box<dyn Fn(&wgpu::Device, &wgpu::Queue, &GpuTexture, &GpuTexture) -> Result<()> + Send + Sync>;
+
+/// A GPU transition implementation: renders `a` to `b` at a progress into
+/// `output`.
+pub type GpuTransitionFn = Box<
+    dyn Fn(&wgpu::Device, &wgpu::Queue, &GpuTexture, &GpuTexture, f32, &GpuTexture) -> Result<()>
+        + Send
+        + Sync,
+>;
+
+/// Shader parameters, laid out as `Params` in `compositor.wgsl`.
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct Params {
+    mode: u32,
+    seed: u32,
+    opacity: f32,
+    _pad: f32,
+    inverse: [[f32; 4]; 3],
+}
+
+/// Renders graph nodes with wgpu.
+pub struct GpuBackend {
+    device: Arc<wgpu::Device>,
+    queue: Arc<wgpu::Queue>,
+    layout: wgpu::BindGroupLayout,
+    composite: wgpu::RenderPipeline,
+    transform: wgpu::RenderPipeline,
+    sources: HashMap<u64, Arc<GpuTexture>>,
+    effects: HashMap<String, GpuEffectFn>,
+    transitions: HashMap<String, GpuTransitionFn>,
+    dissolve_seed: u32,
+}
+
+impl GpuBackend {
+    /// Create a backend on `context`'s device.
+    pub fn new(context: &GpuContext) -> Result<Self> {
+        let usage = context
+            .adapter
+            .get_texture_format_features(FORMAT)
+            .allowed_usages;
+        if !usage.contains(wgpu::TextureUsages::RENDER_ATTACHMENT) {
+            return Err(ProEditError::Gpu(
+                "Adapter can't render to half-float textures".to_string(),
+            ));
+        }
+        let device = Arc::clone(&context.device);
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Compositor Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/compositor.wgsl").into()),
+        });
+
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Compositor Bind Group Layout"),
+            entries: &[
+                texture_entry(0),
+                texture_entry(1),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Compositor Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = |label, entry_point| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some(entry_point),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: FORMAT,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            })
+        };
+        let composite = pipeline("Composite Pipeline", "fs_composite");
+        let transform = pipeline("Transform Pipeline", "fs_transform");
+
+        Ok(Self {
+            queue: Arc::clone(&context.queue),
+            device,
+            layout,
+            composite,
+            transform,
+            sources: HashMap::new(),
+            effects: HashMap::new(),
+            transitions: HashMap::new(),
+            dissolve_seed: 0,
+        })
+    }
+
+    /// Upload the frame for `Source { frame_id }` nodes. RGBA frames of any
+    /// depth are accepted and stored as half floats.
+    pub fn set_source(&mut self, frame_id: u64, frame: &FrameBuffer) -> Result<()> {
+        let texture = self.upload_frame(frame)?;
+        self.sources.insert(frame_id, texture);
+        Ok(())
+    }
+
+    /// Forget source frame `frame_id`.
+    pub fn remove_source(&mut self, frame_id: u64) {
+        self.sources.remove(&frame_id);
+    }
+
+    /// Forget all source frames.
+    pub fn clear_sources(&mut self) {
+        self.sources.clear();
+    }
+
+    /// Register the GPU implementation of effect `name`.
+    pub fn register_effect(
+        &mut self,
+        name: impl Into<String>,
+        effect: impl Fn(&wgpu::Device, &wgpu::Queue, &GpuTexture, &GpuTexture) -> Result<()>
+            + Send
+            + Sync
+            + 'static,
+    ) {
+        self.effects.insert(name.into(), Box::new(effect));
+    }
+
+    /// Register a CPU implementation of effect `name`, run by reading the
+    /// input back and uploading the result. For effects without a shader.
+    pub fn register_cpu_effect(
+        &mut self,
+        name: impl Into<String>,
+        effect: impl Fn(&FrameBuffer) -> Result<FrameBuffer> + Send + Sync + 'static,
+    ) {
+        self.register_effect(name, move |device, queue, input, output| {
+            let frame = input.download_frame(device, queue)?;
+            let result = effect(&frame)?;
+            let mut half = FrameBuffer::new(result.width, result.height, PixelFormat::Rgba16F);
+            half.write_rgba_f32(&result.read_rgba_f32()?)?;
+            output.upload_frame(queue, &half)
+        });
+    }
+
+    /// Register the GPU implementation of transition `name`.
+    pub fn register_transition(
+        &mut self,
+        name: impl Into<String>,
+        transition: impl Fn(
+                &wgpu::Device,
+                &wgpu::Queue,
+                &GpuTexture,
+                &GpuTexture,
+                f32,
+                &GpuTexture,
+            ) -> Result<()>
+            + Send
+            + Sync
+            + 'static,
+    ) {
+        self.transitions.insert(name.into(), Box::new(transition));
+    }
+
+    /// Register a CPU implementation of transition `name`, run by reading
+    /// both inputs back and uploading the result.
+    pub fn register_cpu_transition(
+        &mut self,
+        name: impl Into<String>,
+        transition: impl Fn(&FrameBuffer, &FrameBuffer, f32) -> Result<FrameBuffer>
+            + Send
+            + Sync
+            + 'static,
+    ) {
+        self.register_transition(name, move |device, queue, a, b, progress, output| {
+            let a = a.download_frame(device, queue)?;
+            let b = b.download_frame(device, queue)?;
+            let result = transition(&a, &b, progress)?;
+            let mut half = FrameBuffer::new(result.width, result.height, PixelFormat::Rgba16F);
+            half.write_rgba_f32(&result.read_rgba_f32()?)?;
+            output.upload_frame(queue, &half)
+        });
+    }
+
+    /// Seed the Dissolve pattern. Cached composites keep the old pattern
+    /// until the executor's cache is cleared.
+    pub fn set_dissolve_seed(&mut self, seed: u32) {
+        self.dissolve_seed = seed;
+    }
+
+    /// Read `image` back as an `Rgba16F` frame.
+    pub fn read_back(&self, image: &GpuTexture) -> Result<FrameBuffer> {
+        image.download_frame(&self.device, &self.queue)
+    }
+
+    fn target(&self, size: (u32, u32)) -> GpuTexture {
+        GpuTexture::new(
+            &self.device,
+            size.0,
+            size.1,
+            FORMAT,
+            wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::COPY_DST,
+            Some("Compositor Target"),
+        )
+    }
+
+    fn upload_frame(&self, frame: &FrameBuffer) -> Result<Arc<GpuTexture>> {
+        let texture = self.target((frame.width, frame.height));
+        if frame.format == PixelFormat::Rgba16F {
+            texture.upload_frame(&self.queue, frame)?;
+        } else {
+            let half = FrameBuffer::from_rgba_f32(
+                frame.width,
+                frame.height,
+                PixelFormat::Rgba16F,
+                &frame.read_rgba_f32()?,
+            )?;
+            texture.upload_frame(&self.queue, &half)?;
+        }
+        Ok(Arc::new(texture))
+    }
+
+    /// Draw `pipeline` into a new target of `size`.
+    fn draw(
+        &self,
+        pipeline: &wgpu::RenderPipeline,
+        base: &GpuTexture,
+        layer: &GpuTexture,
+        params: Params,
+        size: (u32, u32),
+    ) -> Arc<GpuTexture> {
+        let target = self.target(size);
+        let uniforms = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Compositor Params"),
+            size: std::mem::size_of::<Params>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        self.queue
+            .write_buffer(&uniforms, 0, bytemuck::bytes_of(&params));
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Compositor Bind Group"),
+            layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&base.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&layer.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: uniforms.as_entire_binding(),
+                },
+            ],
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Compositor Encoder"),
+            });
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Compositor Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &target.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            pass.set_pipeline(pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+        self.queue.submit(Some(encoder.finish()));
+        Arc::new(target)
+    }
+}
+
+fn check(texture: &GpuTexture, size: (u32, u32)) -> Result<()> {
+    if (texture.width, texture.height) != size {
+        return Err(ProEditError::InvalidParameter(format!(
+            "Texture is {}x{}, node expects {}x{}",
+            texture.width, texture.height, size.0, size.1
+        )));
+    }
+    Ok(())
+}
+
+impl RenderBackend for GpuBackend {
+    type Image = Arc<GpuTexture>;
+
+    fn source(&mut self, frame_id: u64, size: (u32, u32)) -> Result<Self::Image> {
+        let texture = self
+            .sources
+            .get(&frame_id)
+            .ok_or_else(|| ProEditError::NotFound(format!("Source frame {frame_id}")))?;
+        check(texture, size)?;
+        Ok(Arc::clone(texture))
+    }
+
+    fn effect(&mut self, name: &str, input: &Self::Image, size: (u32, u32)) -> Result<Self::Image> {
+        let effect = self
+            .effects
+            .get(name)
+            .ok_or_else(|| ProEditError::NotFound(format!("Effect {name}")))?;
+        let output = self.target(size);
+        effect(&self.device, &self.queue, input, &output)?;
+        Ok(Arc::new(output))
+    }
+
+    fn transition(
+        &mut self,
+        name: &str,
+        a: &Self::Image,
+        b: &Self::Image,
+        progress: f32,
+        size: (u32, u32),
+    ) -> Result<Self::Image> {
+        check(a, size)?;
+        check(b, size)?;
+        let transition = self
+            .transitions
+            .get(name)
+            .ok_or_else(|| ProEditError::NotFound(format!("Transition {name}")))?;
+        let output = self.target(size);
+        transition(&self.device, &self.queue, a, b, progress, &output)?;
+        Ok(Arc::new(output))
+    }
+
+    fn composite(
+        &mut self,
+        base: &Self::Image,
+        layer: &Self::Image,
+        blend_mode: u32,
+        opacity: f32,
+        size: (u32, u32),
+    ) -> Result<Self::Image> {
+        check(base, size)?;
+        check(layer, size)?;
+        if BlendMode::from_u32(blend_mode).is_none() {
+            return Err(ProEditError::InvalidParameter(format!(
+                "Unknown blend mode {blend_mode}"
+            )));
+        }
+        let params = Params {
+            mode: blend_mode,
+            seed: self.dissolve_seed,
+            opacity,
+            _pad: 0.0,
+            inverse: [[0.0; 4]; 3],
+        };
+        Ok(self.draw(&self.composite, base, layer, params, size))
+    }
+
+    fn transform(
+        &mut self,
+        input: &Self::Image,
+        matrix: &[f32; 9],
+        size: (u32, u32),
+    ) -> Result<Self::Image> {
+        let inverse = Mat3::from_cols_array(matrix).inverse();
+        if !inverse.is_finite() {
+            return Err(ProEditError::InvalidParameter(
+                "Transform matrix is not invertible".into(),
+            ));
+        }
+        let params = Params {
+            mode: 0,
+            seed: 0,
+            opacity: 1.0,
+            _pad: 0.0,
+            inverse: [inverse.x_axis, inverse.y_axis, inverse.z_axis].map(|c| c.extend(0.0).into()),
+        };
+        Ok(self.draw(&self.transform, input, input, params, size))
+    }
+
+    fn download(&mut self, image: &Self::Image) -> Result<Vec<u8>> {
+        Ok(self.read_back(image)?.primary_plane().data.clone())
+    }
+
+    fn upload(&mut self, data: &[u8], size: (u32, u32)) -> Result<Self::Image> {
+        let mut frame = FrameBuffer::new(size.0, size.1, PixelFormat::Rgba16F);
+        let plane = frame.primary_plane_mut();
+        if plane.data.len() != data.len() {
+            return Err(ProEditError::InvalidParameter(
+                "Cached frame has the wrong size".into(),
+            ));
+        }
+        plane.data.copy_from_slice(data);
+        self.upload_frame(&frame)
+    }
+}
+
+/// Renders render graphs offscreen on the GPU and reads them back.
+pub struct HeadlessCompositor {
+    executor: GraphExecutor<GpuBackend>,
+}
+
+impl HeadlessCompositor {
+    /// Create a compositor on `context` with a node cache of `cache_bytes`.
+    pub fn new(context: &GpuContext, cache_bytes: usize) -> Result<Self> {
+        Ok(Self {
+            executor: GraphExecutor::new(GpuBackend::new(context)?, cache_bytes),
+        })
+    }
+
+    /// The backend, e.g. to set sources or register effects.
+    pub fn backend_mut(&mut self) -> &mut GpuBackend {
+        self.executor.backend_mut()
+    }
+
+    /// The executor, e.g. to read its statistics.
+    pub fn executor_mut(&mut self) -> &mut GraphExecutor<GpuBackend> {
+        &mut self.executor
+    }
+
+    /// Render `graph` up to `output` into an offscreen texture.
+    pub fn render(&mut self, graph: &RenderGraph, output: NodeId) -> Result<Arc<GpuTexture>> {
+        self.executor.execute(graph, output)
+    }
+
+    /// Render `graph` up to `output` and read it back as an `Rgba16F` frame.
+    pub fn render_to_frame(&mut self, graph: &RenderGraph, output: NodeId) -> Result<FrameBuffer> {
+        let texture = self.render(graph, output)?;
+        self.executor.backend().read_back(&texture)
+    }
+}
+
+/// Largest per-channel difference between two RGBA frames of the same
+/// size, relative to the magnitude for values above 1.0. Compare against
+/// [`PARITY_TOLERANCE`].
+pub fn parity_error(a: &FrameBuffer, b: &FrameBuffer) -> Result<f32> {
+    if (a.width, a.height) != (b.width, b.height) {
+        return Err(ProEditError::InvalidParameter(format!(
+            "Can't compare {}x{} with {}x{}",
+            a.width, a.height, b.width, b.height
+        )));
+    }
+    let (a, b) = (a.read_rgba_f32()?, b.read_rgba_f32()?);
+    Ok(a.iter()
+        .zip(&b)
+        .flat_map(|(p, q)| p.iter().zip(q))
+        .map(|(x, y)| (x - y).abs() / x.abs().max(y.abs()).max(1.0))
+        .fold(0.0, f32::max))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parity_error() {
+        let a =
+            FrameBuffer::from_rgba_f32(2, 1, PixelFormat::Rgba32F, &[[0.5; 4], [4.0; 4]]).unwrap();
+        let b = FrameBuffer::from_rgba_f32(2, 1, PixelFormat::Rgba16F, &[[0.501; 4], [4.01; 4]])
+            .unwrap();
+        let error = parity_error(&a, &b).unwrap();
+        assert!(error > 0.0 && error < PARITY_TOLERANCE, "{error}");
+        let c = FrameBuffer::from_rgba_f32(1, 1, PixelFormat::Rgba32F, &[[0.5; 4]]).unwrap();
+        assert!(parity_error(&a, &c).is_err());
+    }
+
+    #[test]
+    fn test_params_match_shader_layout() {
+        // mode, seed, opacity, pad, then three vec4 columns.
+        assert_eq!(std::mem::size_of::<Params>(), 64);
+    }
+}
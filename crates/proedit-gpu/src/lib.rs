@@ -4,6 +4,9 @@
 
 pub mod blend;
 pub mod context;
+pub mod cpu_backend;
+pub mod executor;
+pub mod gpu_backend;
 pub mod pipeline;
 pub mod render_graph;
 pub mod texture;
@@ -11,6 +14,11 @@ pub mod texture_pool;
 
 pub use blend::BlendMode;
 pub use context::GpuContext;
+pub use cpu_backend::{premultiply, unpremultiply, CpuBackend, CpuEffectFn, CpuTransitionFn};
+pub use executor::{ExecStats, GraphExecutor, RenderBackend};
+pub use gpu_backend::{
+    parity_error, GpuBackend, GpuEffectFn, GpuTransitionFn, HeadlessCompositor, PARITY_TOLERANCE,
+};
 pub use pipeline::BlitPipeline;
 pub use render_graph::{FrameCache, NodeId, NodeOp, RenderGraph, RenderNode};
 pub use texture::GpuTexture;
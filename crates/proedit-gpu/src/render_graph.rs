@@ -19,6 +19,11 @@ pub enum NodeOp {
     Effect { effect_name: String },
     /// Composite two layers using a blend mode.
     Composite { blend_mode: u32, opacity: f32 },
+    /// Blend from the first input to the second through a transition.
+    Transition {
+        transition_name: String,
+        progress: f32,
+    },
     /// Transform (translate/rotate/scale) the input.
     Transform { matrix: [f32; 9] },
     /// Final output target.
@@ -0,0 +1,274 @@
+// Compositing shader - GPU counterpart of CpuBackend.
+//
+// Textures hold straight-alpha linear RGBA. Blending follows blend.rs
+// exactly (premultiplied W3C formulas, Rec. 709 luma for the HSL modes).
+
+struct Params {
+    mode: u32,
+    seed: u32,
+    opacity: f32,
+    _pad: f32,
+    // Inverse transform (output pixel -> input pixel), one column per vec4.
+    inv0: vec4<f32>,
+    inv1: vec4<f32>,
+    inv2: vec4<f32>,
+};
+
+@group(0) @binding(0)
+var base_tex: texture_2d<f32>;
+@group(0) @binding(1)
+var layer_tex: texture_2d<f32>;
+@group(0) @binding(2)
+var<uniform> params: Params;
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+    // One triangle covering the whole target.
+    let x = f32(i32(vertex_index & 1u) * 4 - 1);
+    let y = f32(i32(vertex_index >> 1u) * 4 - 1);
+    return vec4<f32>(x, y, 0.0, 1.0);
+}
+
+const DISSOLVE: u32 = 1u;
+const DARKEN: u32 = 2u;
+const MULTIPLY: u32 = 3u;
+const COLOR_BURN: u32 = 4u;
+const LINEAR_BURN: u32 = 5u;
+const LIGHTEN: u32 = 6u;
+const SCREEN: u32 = 7u;
+const COLOR_DODGE: u32 = 8u;
+const LINEAR_DODGE: u32 = 9u;
+const OVERLAY: u32 = 10u;
+const SOFT_LIGHT: u32 = 11u;
+const HARD_LIGHT: u32 = 12u;
+const VIVID_LIGHT: u32 = 13u;
+const LINEAR_LIGHT: u32 = 14u;
+const PIN_LIGHT: u32 = 15u;
+const HARD_MIX: u32 = 16u;
+const DIFFERENCE: u32 = 17u;
+const EXCLUSION: u32 = 18u;
+const SUBTRACT: u32 = 19u;
+const DIVIDE: u32 = 20u;
+const HUE: u32 = 21u;
+const SATURATION: u32 = 22u;
+const COLOR: u32 = 23u;
+const LUMINOSITY: u32 = 24u;
+const ADD: u32 = 25u;
+const STENCIL: u32 = 26u;
+const SILHOUETTE: u32 = 27u;
+
+fn dissolve_noise(x: u32, y: u32, seed: u32) -> f32 {
+    var h = (x * 0x8da6b343u) ^ (y * 0xd8163841u) ^ (seed * 0xcb1ab31fu);
+    h ^= h >> 16u;
+    h *= 0x7feb352du;
+    h ^= h >> 15u;
+    h *= 0x846ca68bu;
+    h ^= h >> 16u;
+    return f32(h >> 8u) / 16777216.0;
+}
+
+fn premultiply(c: vec4<f32>) -> vec4<f32> {
+    return vec4<f32>(c.rgb * c.a, c.a);
+}
+
+fn unpremultiply_rgb(c: vec4<f32>) -> vec3<f32> {
+    if (c.a > 0.0) {
+        return c.rgb / c.a;
+    }
+    return vec3<f32>(0.0);
+}
+
+fn to_straight(c: vec4<f32>) -> vec4<f32> {
+    if (c.a > 1e-6) {
+        return vec4<f32>(c.rgb / c.a, c.a);
+    }
+    return vec4<f32>(0.0);
+}
+
+fn screen(b: f32, s: f32) -> f32 {
+    return b + s - b * s;
+}
+
+fn hard_light(b: f32, s: f32) -> f32 {
+    if (s <= 0.5) {
+        return b * 2.0 * s;
+    }
+    return screen(b, 2.0 * s - 1.0);
+}
+
+fn color_burn(b: f32, s: f32) -> f32 {
+    if (b >= 1.0) {
+        return 1.0;
+    }
+    if (s <= 0.0) {
+        return 0.0;
+    }
+    return 1.0 - min((1.0 - b) / s, 1.0);
+}
+
+fn color_dodge(b: f32, s: f32) -> f32 {
+    if (b <= 0.0) {
+        return 0.0;
+    }
+    if (s >= 1.0) {
+        return 1.0;
+    }
+    return min(b / (1.0 - s), 1.0);
+}
+
+fn soft_light(b: f32, s: f32) -> f32 {
+    if (s <= 0.5) {
+        return b - (1.0 - 2.0 * s) * b * (1.0 - b);
+    }
+    var d = sqrt(max(b, 0.0));
+    if (b <= 0.25) {
+        d = ((16.0 * b - 12.0) * b + 4.0) * b;
+    }
+    return b + (2.0 * s - 1.0) * (d - b);
+}
+
+fn separable(mode: u32, b: f32, s: f32) -> f32 {
+    switch mode {
+        case DARKEN: { return min(b, s); }
+        case MULTIPLY: { return b * s; }
+        case COLOR_BURN: { return color_burn(b, s); }
+        case LINEAR_BURN: { return max(b + s - 1.0, 0.0); }
+        case LIGHTEN: { return max(b, s); }
+        case SCREEN: { return screen(b, s); }
+        case COLOR_DODGE: { return color_dodge(b, s); }
+        case LINEAR_DODGE: { return min(b + s, 1.0); }
+        case OVERLAY: { return hard_light(s, b); }
+        case SOFT_LIGHT: { return soft_light(b, s); }
+        case HARD_LIGHT: { return hard_light(b, s); }
+        case VIVID_LIGHT: {
+            if (s <= 0.5) {
+                return color_burn(b, 2.0 * s);
+            }
+            return color_dodge(b, 2.0 * s - 1.0);
+        }
+        case LINEAR_LIGHT: { return clamp(b + 2.0 * s - 1.0, 0.0, 1.0); }
+        case PIN_LIGHT: {
+            if (s <= 0.5) {
+                return min(b, 2.0 * s);
+            }
+            return max(b, 2.0 * s - 1.0);
+        }
+        case HARD_MIX: { return select(0.0, 1.0, b + s >= 1.0); }
+        case DIFFERENCE: { return abs(b - s); }
+        case EXCLUSION: { return b + s - 2.0 * b * s; }
+        case SUBTRACT: { return max(b - s, 0.0); }
+        case DIVIDE: {
+            if (s <= 0.0) {
+                return select(0.0, 1.0, b > 0.0);
+            }
+            return min(b / s, 1.0);
+        }
+        case ADD: { return b + s; }
+        default: { return s; }
+    }
+}
+
+fn lum(c: vec3<f32>) -> f32 {
+    return 0.2126 * c.r + 0.7152 * c.g + 0.0722 * c.b;
+}
+
+fn clip_color(c_in: vec3<f32>) -> vec3<f32> {
+    var c = c_in;
+    let l = lum(c);
+    let n = min(c.r, min(c.g, c.b));
+    let x = max(c.r, max(c.g, c.b));
+    if (n < 0.0) {
+        c = l + (c - l) * l / (l - n);
+    }
+    if (x > 1.0) {
+        c = l + (c - l) * (1.0 - l) / (x - l);
+    }
+    return c;
+}
+
+fn set_lum(c: vec3<f32>, l: f32) -> vec3<f32> {
+    return clip_color(c + (l - lum(c)));
+}
+
+fn sat(c: vec3<f32>) -> f32 {
+    return max(c.r, max(c.g, c.b)) - min(c.r, min(c.g, c.b));
+}
+
+fn set_sat(c: vec3<f32>, s: f32) -> vec3<f32> {
+    let lo = min(c.r, min(c.g, c.b));
+    let hi = max(c.r, max(c.g, c.b));
+    if (hi > lo) {
+        return (c - lo) * s / (hi - lo);
+    }
+    return vec3<f32>(0.0);
+}
+
+fn mix_colors(mode: u32, cb: vec3<f32>, cs: vec3<f32>) -> vec3<f32> {
+    switch mode {
+        case HUE: { return set_lum(set_sat(cs, sat(cb)), lum(cb)); }
+        case SATURATION: { return set_lum(set_sat(cb, sat(cs)), lum(cb)); }
+        case COLOR: { return set_lum(cs, lum(cb)); }
+        case LUMINOSITY: { return set_lum(cb, lum(cs)); }
+        default: {
+            return vec3<f32>(
+                separable(mode, cb.r, cs.r),
+                separable(mode, cb.g, cs.g),
+                separable(mode, cb.b, cs.b),
+            );
+        }
+    }
+}
+
+// Premultiplied in, premultiplied out; see BlendMode::blend.
+fn blend(mode: u32, backdrop: vec4<f32>, source_in: vec4<f32>, opacity: f32, noise: f32) -> vec4<f32> {
+    let source = source_in * clamp(opacity, 0.0, 1.0);
+    let ba = backdrop.a;
+    let sa = source.a;
+    switch mode {
+        case DISSOLVE: {
+            if (noise < sa) {
+                return vec4<f32>(unpremultiply_rgb(source), 1.0);
+            }
+            return backdrop;
+        }
+        case STENCIL: { return backdrop * sa; }
+        case SILHOUETTE: { return backdrop * (1.0 - sa); }
+        default: {
+            let mixed = mix_colors(mode, unpremultiply_rgb(backdrop), unpremultiply_rgb(source));
+            let rgb = source.rgb * (1.0 - ba) + backdrop.rgb * (1.0 - sa) + sa * ba * mixed;
+            return vec4<f32>(rgb, sa + ba * (1.0 - sa));
+        }
+    }
+}
+
+@fragment
+fn fs_composite(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let p = vec2<u32>(position.xy);
+    let base = textureLoad(base_tex, p, 0);
+    let layer = textureLoad(layer_tex, p, 0);
+    let noise = dissolve_noise(p.x, p.y, params.seed);
+    return to_straight(blend(params.mode, premultiply(base), premultiply(layer), params.opacity, noise));
+}
+
+// Premultiplied texel of the input, transparent outside it.
+fn texel(x: i32, y: i32) -> vec4<f32> {
+    let size = vec2<i32>(textureDimensions(base_tex));
+    if (x < 0 || y < 0 || x >= size.x || y >= size.y) {
+        return vec4<f32>(0.0);
+    }
+    return premultiply(textureLoad(base_tex, vec2<i32>(x, y), 0));
+}
+
+@fragment
+fn fs_transform(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let p = params.inv0.xy * position.x + params.inv1.xy * position.y + params.inv2.xy - vec2<f32>(0.5);
+    let p0 = floor(p);
+    let t = p - p0;
+    let x0 = i32(p0.x);
+    let y0 = i32(p0.y);
+    let acc = texel(x0, y0) * ((1.0 - t.x) * (1.0 - t.y))
+        + texel(x0 + 1, y0) * (t.x * (1.0 - t.y))
+        + texel(x0, y0 + 1) * ((1.0 - t.x) * t.y)
+        + texel(x0 + 1, y0 + 1) * (t.x * t.y);
+    return to_straight(acc);
+}
@@ -75,12 +75,36 @@ impl GpuTexture {
         )
     }
 
-    /// Upload a FrameBuffer to this texture.
+    /// Texture format holding frames of `format`, if one exists.
+    pub fn format_for(format: PixelFormat) -> Option<wgpu::TextureFormat> {
+        match format {
+            PixelFormat::Rgba8 => Some(wgpu::TextureFormat::Rgba8Unorm),
+            PixelFormat::Rgba16F => Some(wgpu::TextureFormat::Rgba16Float),
+            PixelFormat::Rgba32F => Some(wgpu::TextureFormat::Rgba32Float),
+            _ => None,
+        }
+    }
+
+    /// Pixel format of frames this texture uploads from and reads back to.
+    pub fn frame_format(&self) -> Option<PixelFormat> {
+        match self.format {
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => {
+                Some(PixelFormat::Rgba8)
+            }
+            wgpu::TextureFormat::Rgba16Float => Some(PixelFormat::Rgba16F),
+            wgpu::TextureFormat::Rgba32Float => Some(PixelFormat::Rgba32F),
+            _ => None,
+        }
+    }
+
+    /// Upload a FrameBuffer to this texture. The frame's format must match
+    /// the texture's (RGBA8, RGBA16F or RGBA32F).
     pub fn upload_frame(&self, queue: &wgpu::Queue, frame: &FrameBuffer) -> Result<()> {
-        if frame.format != PixelFormat::Rgba8 {
-            return Err(ProEditError::Gpu(
-                "Only RGBA8 format supported for upload".to_string(),
-            ));
+        if self.frame_format() != Some(frame.format) {
+            return Err(ProEditError::Gpu(format!(
+                "Can't upload a {:?} frame to a {:?} texture",
+                frame.format, self.format
+            )));
         }
 
         if frame.width != self.width || frame.height != self.height {
@@ -115,6 +139,77 @@ impl GpuTexture {
         Ok(())
     }
 
+    /// Copy this texture back into a FrameBuffer, waiting for the GPU.
+    /// The texture needs `COPY_SRC` usage.
+    pub fn download_frame(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Result<FrameBuffer> {
+        let format = self.frame_format().ok_or_else(|| {
+            ProEditError::Gpu(format!("Can't read back a {:?} texture", self.format))
+        })?;
+        let mut frame = FrameBuffer::new(self.width, self.height, format);
+        let row_bytes = self.width as usize * format.bytes_per_pixel();
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as usize;
+        let padded = row_bytes.div_ceil(align) * align;
+        if row_bytes == 0 || self.height == 0 {
+            return Ok(frame);
+        }
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Readback Buffer"),
+            size: (padded * self.height as usize) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Readback Encoder"),
+        });
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded as u32),
+                    rows_per_image: Some(self.height),
+                },
+            },
+            wgpu::Extent3d {
+                width: self.width,
+                height: self.height,
+                depth_or_array_layers: 1,
+            },
+        );
+        queue.submit(Some(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        let (tx, rx) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = tx.send(result);
+        });
+        device.poll(wgpu::Maintain::Wait);
+        rx.recv()
+            .map_err(|_| ProEditError::Gpu("Readback was abandoned".to_string()))?
+            .map_err(|e| ProEditError::Gpu(format!("Failed to map readback buffer: {e}")))?;
+
+        {
+            let data = slice.get_mapped_range();
+            let plane = frame.primary_plane_mut();
+            for (y, src) in data.chunks(padded).enumerate() {
+                plane.row_mut(y as u32)[..row_bytes].copy_from_slice(&src[..row_bytes]);
+            }
+        }
+        buffer.unmap();
+        Ok(frame)
+    }
+
     /// Memory usage estimate in bytes.
     pub fn memory_size(&self) -> usize {
         let bytes_per_pixel = match self.format {
@@ -0,0 +1,862 @@
+//! Project archiving ("collect files") for finished projects.
+//!
+//! Walks every clip in a `Project` (and its alternate takes; generator
+//! clips have no media), gathers the referenced media into a
+//! destination folder, and rewrites the project to point at the collected
+//! copies. Sources can be copied whole or trimmed through FFmpeg to the
+//! ranges actually used on the timeline (plus handles).
+//!
+//! Collected media is referenced relative to the archive root, so the
+//! archive can be moved or opened on another machine. Relative paths in the
+//! input project are found through [`Project::media_path`].
+//!
+//! Missing media never aborts the run — it is reported in the manifest and
+//! the affected clips keep their original references.
+
+use proedit_core::{ProEditError, RationalTime, Result, TimeRange};
+use proedit_timeline::{Clip, ClipRef, Project, ProjectFile, TrackItem};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// File name of the manifest written into the archive root.
+pub const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+// ── Options ─────────────────────────────────────────────────────
+
+/// How trimmed sources are produced by FFmpeg.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TrimMethod {
+    /// Copy packets without re-encoding. Fast, but cuts snap to keyframes.
+    StreamCopy,
+    /// Re-encode with the container's default encoders. Frame-accurate.
+    Reencode,
+}
+
+/// What to do with each referenced source.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum CollectMode {
+    /// Copy every source file in full.
+    CopyAll,
+    /// Trim each source to the used ranges, padded by `handles` on both sides.
+    Trim {
+        handles: RationalTime,
+        method: TrimMethod,
+    },
+}
+
+/// Configuration for a collect run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollectOptions {
+    /// Archive root. Created if it doesn't exist.
+    pub destination: PathBuf,
+    /// Sub-folder (relative to `destination`) receiving the media.
+    pub media_dir: String,
+    /// Copy or trim.
+    pub mode: CollectMode,
+    /// Also write the rewritten project file into the archive root.
+    pub write_project: bool,
+}
+
+impl CollectOptions {
+    /// Copy all media in full into `destination/Media`.
+    pub fn new(destination: impl Into<PathBuf>) -> Self {
+        Self {
+            destination: destination.into(),
+            media_dir: "Media".to_string(),
+            mode: CollectMode::CopyAll,
+            write_project: true,
+        }
+    }
+
+    /// Trim sources to their used ranges plus `handles`.
+    pub fn with_trim(mut self, handles: RationalTime, method: TrimMethod) -> Self {
+        self.mode = CollectMode::Trim { handles, method };
+        self
+    }
+
+    /// Absolute (or caller-relative) media folder.
+    pub fn media_path(&self) -> PathBuf {
+        self.destination.join(&self.media_dir)
+    }
+}
+
+// ── Manifest ────────────────────────────────────────────────────
+
+/// One file written into the archive.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// Path the project referenced before collecting.
+    pub original_path: String,
+    /// Path of the collected file, relative to the archive root.
+    pub collected_path: String,
+    /// Size of the collected file in bytes.
+    pub size_bytes: u64,
+    /// Lowercase hex SHA-256 of the collected file.
+    pub sha256: String,
+    /// Source range covered by this file (None = the whole source).
+    pub source_range: Option<TimeRange>,
+}
+
+/// A source that could not be collected.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MissingMedia {
+    /// Path referenced by the project.
+    pub path: String,
+    /// Clips referencing this path.
+    pub clip_ids: Vec<Uuid>,
+    /// Why it could not be collected.
+    pub reason: String,
+}
+
+/// Summary of a collect run.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CollectManifest {
+    /// Project name at the time of collection.
+    pub project_name: String,
+    /// Every file written into the archive.
+    pub entries: Vec<ManifestEntry>,
+    /// Sources that were skipped.
+    pub missing: Vec<MissingMedia>,
+    /// Non-fatal problems (e.g. a trim that fell back to a full copy).
+    pub warnings: Vec<String>,
+}
+
+impl CollectManifest {
+    /// Total bytes of collected media.
+    pub fn total_bytes(&self) -> u64 {
+        self.entries.iter().map(|e| e.size_bytes).sum()
+    }
+
+    /// True when every referenced source was collected.
+    pub fn is_complete(&self) -> bool {
+        self.missing.is_empty()
+    }
+
+    /// Serialize to pretty JSON.
+    pub fn to_json(&self) -> Result<Vec<u8>> {
+        serde_json::to_vec_pretty(self).map_err(|e| {
+            ProEditError::Serialization(format!("Failed to serialize manifest: {}", e))
+        })
+    }
+
+    /// Write the manifest to a file.
+    pub fn save_to_file(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, self.to_json()?)?;
+        Ok(())
+    }
+}
+
+/// Result of `collect_project`.
+#[derive(Debug, Clone)]
+pub struct CollectResult {
+    /// Copy of the input project pointing at the collected media, by paths
+    /// relative to the archive root.
+    pub project: Project,
+    /// Files written and problems found.
+    pub manifest: CollectManifest,
+}
+
+// ── Collect ─────────────────────────────────────────────────────
+
+/// Per-source usage gathered from the timeline.
+struct SourceUsage {
+    source_duration: RationalTime,
+    clip_ids: Vec<Uuid>,
+    ranges: Vec<TimeRange>,
+}
+
+/// Where a source ended up.
+enum Collected {
+    /// Whole file copied to this path.
+    Full(String),
+    /// Trimmed into one file per merged range.
+    Trimmed(Vec<(TimeRange, String)>),
+}
+
+/// Collect all media referenced by `project` into `options.destination`.
+///
+/// Only I/O errors on the destination itself (creating folders, writing the
+/// manifest or project file) are returned as errors; per-source failures are
+/// recorded in the manifest.
+pub fn collect_project(project: &Project, options: &CollectOptions) -> Result<CollectResult> {
+    let media_path = options.media_path();
+    std::fs::create_dir_all(&media_path)?;
+
+    let mut manifest = CollectManifest {
+        project_name: project.name.clone(),
+        ..Default::default()
+    };
+
+    let usage = gather_usage(project);
+    let mut used_names = HashSet::new();
+    let mut collected: BTreeMap<String, Collected> = BTreeMap::new();
+
+    for (path, usage) in &usage {
+        let src = &project.media_path(path);
+        if !src.is_file() {
+            warn!("Missing media: {}", path);
+            manifest.missing.push(MissingMedia {
+                path: path.clone(),
+                clip_ids: usage.clip_ids.clone(),
+                reason: "file not found".to_string(),
+            });
+            continue;
+        }
+
+        let result = match options.mode {
+            CollectMode::CopyAll => copy_source(src, path, options, &mut used_names, &mut manifest),
+            CollectMode::Trim { handles, method } => {
+                let ranges = merge_ranges(&usage.ranges, handles, usage.source_duration);
+                match trim_source(
+                    src,
+                    path,
+                    &ranges,
+                    method,
+                    options,
+                    &mut used_names,
+                    &mut manifest,
+                ) {
+                    Ok(files) => Ok(Collected::Trimmed(files)),
+                    Err(e) => {
+                        manifest
+                            .warnings
+                            .push(format!("Trim failed for {path}, copied in full: {e}"));
+                        copy_source(src, path, options, &mut used_names, &mut manifest)
+                    }
+                }
+            }
+        };
+
+        match result {
+            Ok(c) => {
+                collected.insert(path.clone(), c);
+            }
+            Err(e) => manifest.missing.push(MissingMedia {
+                path: path.clone(),
+                clip_ids: usage.clip_ids.clone(),
+                reason: e.to_string(),
+            }),
+        }
+    }
+
+    let mut rewritten = project.clone();
+    rewritten.media_root = Some(options.destination.clone());
+    for sequence in &mut rewritten.sequences {
+        for track in sequence
+            .video_tracks
+            .iter_mut()
+            .chain(sequence.audio_tracks.iter_mut())
+        {
+            for item in &mut track.items {
+                if let TrackItem::Clip(clip) = item {
+                    relink_clip(clip, &collected);
+                }
+            }
+        }
+    }
+
+    manifest.save_to_file(&options.destination.join(MANIFEST_FILE_NAME))?;
+    if options.write_project {
+        let file_name = format!("{}.pep", sanitize_file_stem(&rewritten.name));
+        ProjectFile::new(rewritten.clone()).save_to_file(&options.destination.join(file_name))?;
+    }
+
+    info!(
+        "Collected {} files ({} bytes), {} missing",
+        manifest.entries.len(),
+        manifest.total_bytes(),
+        manifest.missing.len()
+    );
+
+    Ok(CollectResult {
+        project: rewritten,
+        manifest,
+    })
+}
+
+/// Group every clip and alternate take in the project by source path.
+fn gather_usage(project: &Project) -> BTreeMap<String, SourceUsage> {
+    let mut usage: BTreeMap<String, SourceUsage> = BTreeMap::new();
+    let clips = project.sequences.iter().flat_map(|s| {
+        s.video_tracks
+            .iter()
+            .chain(s.audio_tracks.iter())
+            .flat_map(|t| t.items.iter())
+    });
+    for item in clips {
+        let TrackItem::Clip(clip) = item else {
+            continue;
+        };
+        for (source, used) in clip_sources(clip) {
+            let entry = usage
+                .entry(source.path.clone())
+                .or_insert_with(|| SourceUsage {
+                    source_duration: source.source_duration,
+                    clip_ids: Vec::new(),
+                    ranges: Vec::new(),
+                });
+            entry.source_duration = entry.source_duration.max(source.source_duration);
+            if !entry.clip_ids.contains(&clip.id) {
+                entry.clip_ids.push(clip.id);
+            }
+            entry.ranges.push(used);
+        }
+    }
+    usage
+}
+
+/// The media `clip` references with the range of each it plays: its
+/// source, then its alternate takes. Generator clips reference none.
+fn clip_sources(clip: &Clip) -> Vec<(&ClipRef, TimeRange)> {
+    if clip.generator.is_some() {
+        return Vec::new();
+    }
+    let played = clip.played_source_range();
+    std::iter::once((&clip.source, played))
+        .chain(clip.takes.iter().map(|take| {
+            (
+                &take.source,
+                TimeRange::new(take.source_in, played.duration),
+            )
+        }))
+        .collect()
+}
+
+/// Pad each range by `handles`, clamp to the source, and merge overlaps.
+///
+/// Returned ranges are sorted and non-overlapping.
+pub fn merge_ranges(
+    ranges: &[TimeRange],
+    handles: RationalTime,
+    source_duration: RationalTime,
+) -> Vec<TimeRange> {
+    let mut padded: Vec<TimeRange> = ranges
+        .iter()
+        .map(|r| {
+            let start = (r.start - handles).max(RationalTime::ZERO);
+            let mut end = r.end() + handles;
+            if source_duration > RationalTime::ZERO {
+                end = end.min(source_duration);
+            }
+            TimeRange::from_start_end(start, end.max(start))
+        })
+        .collect();
+    padded.sort_by_key(|r| r.start);
+
+    let mut merged: Vec<TimeRange> = Vec::with_capacity(padded.len());
+    for r in padded {
+        match merged.last_mut() {
+            Some(last) if r.start <= last.end() => {
+                *last = TimeRange::from_start_end(last.start, last.end().max(r.end()));
+            }
+            _ => merged.push(r),
+        }
+    }
+    merged
+}
+
+fn copy_source(
+    src: &Path,
+    original: &str,
+    options: &CollectOptions,
+    used_names: &mut HashSet<String>,
+    manifest: &mut CollectManifest,
+) -> Result<Collected> {
+    let name = unique_file_name(src, None, used_names);
+    let relative = format!("{}/{}", options.media_dir, name);
+    let dst = options.destination.join(&relative);
+    std::fs::copy(src, &dst)?;
+    manifest
+        .entries
+        .push(manifest_entry(original, &dst, relative.clone(), None)?);
+    Ok(Collected::Full(relative))
+}
+
+/// Cut each of `ranges` out of `src` into its own file. On failure the
+/// parts already written are removed and their names released, so the
+/// full copy that replaces them starts from a clean slate.
+fn trim_source(
+    src: &Path,
+    original: &str,
+    ranges: &[TimeRange],
+    method: TrimMethod,
+    options: &CollectOptions,
+    used_names: &mut HashSet<String>,
+    manifest: &mut CollectManifest,
+) -> Result<Vec<(TimeRange, String)>> {
+    let mut files = Vec::with_capacity(ranges.len());
+    let mut entries = Vec::with_capacity(ranges.len());
+    let mut written = Vec::with_capacity(ranges.len());
+    for (i, range) in ranges.iter().enumerate() {
+        let name = unique_file_name(src, Some(i), used_names);
+        let relative = format!("{}/{}", options.media_dir, name);
+        let dst = options.destination.join(&relative);
+        written.push((name, dst.clone()));
+
+        let part = trim_part(src, &dst, *range, method).and_then(|range| {
+            let entry = manifest_entry(original, &dst, relative.clone(), Some(range))?;
+            Ok((range, entry))
+        });
+        match part {
+            Ok((range, entry)) => {
+                entries.push(entry);
+                files.push((range, relative));
+            }
+            Err(e) => {
+                for (name, dst) in written {
+                    let _ = std::fs::remove_file(dst);
+                    used_names.remove(&name);
+                }
+                return Err(e);
+            }
+        }
+    }
+    manifest.entries.extend(entries);
+    Ok(files)
+}
+
+/// Cut `range` out of `src` into `dst` with FFmpeg, returning the range
+/// the file actually covers.
+fn trim_part(src: &Path, dst: &Path, range: TimeRange, method: TrimMethod) -> Result<TimeRange> {
+    // A stream copy can only start on a keyframe; start the cut there,
+    // so the file begins exactly where its range says.
+    let range = match method {
+        TrimMethod::StreamCopy => {
+            let start = keyframe_at_or_before(src, range.start)?;
+            TimeRange::from_start_end(start, range.end())
+        }
+        TrimMethod::Reencode => range,
+    };
+    let args = trim_args(src, dst, range, method);
+    let status = Command::new("ffmpeg")
+        .args(&args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map_err(|e| ProEditError::Encoder(format!("Failed to spawn ffmpeg: {e}")))?;
+    if !status.success() {
+        return Err(ProEditError::Encoder(format!(
+            "ffmpeg exited with status: {}",
+            status
+        )));
+    }
+    Ok(range)
+}
+
+/// Build the FFmpeg arguments for trimming `range` out of `src`.
+pub fn trim_args(src: &Path, dst: &Path, range: TimeRange, method: TrimMethod) -> Vec<String> {
+    let mut args = vec![
+        "-y".to_string(),
+        "-ss".to_string(),
+        format!("{:.6}", range.start.to_seconds_f64()),
+        "-i".to_string(),
+        src.to_string_lossy().into_owned(),
+        "-t".to_string(),
+        format!("{:.6}", range.duration.to_seconds_f64()),
+        "-map".to_string(),
+        "0".to_string(),
+    ];
+    if method == TrimMethod::StreamCopy {
+        args.extend_from_slice(&["-c".to_string(), "copy".to_string()]);
+    }
+    args.push(dst.to_string_lossy().into_owned());
+    args
+}
+
+/// The time of the last video keyframe at or before `time` in `src`, where
+/// a stream copy seeking to `time` really starts. Sources without video
+/// start where asked.
+fn keyframe_at_or_before(src: &Path, time: RationalTime) -> Result<RationalTime> {
+    let seconds = time.to_seconds_f64();
+    let output = Command::new("ffprobe")
+        .args([
+            "-v",
+            "error",
+            "-select_streams",
+            "v:0",
+            "-show_entries",
+            "packet=pts_time,flags",
+            "-of",
+            "csv=print_section=0",
+            "-read_intervals",
+            &format!("{seconds:.6}%+2"),
+        ])
+        .arg(src)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .map_err(|e| ProEditError::Encoder(format!("Failed to spawn ffprobe: {e}")))?;
+    if !output.status.success() {
+        return Err(ProEditError::Encoder(format!(
+            "ffprobe exited with status: {}",
+            output.status
+        )));
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(last_keyframe(&stdout, seconds)
+        .map(|k| RationalTime::from_seconds_f64(k).min(time))
+        .unwrap_or(time))
+}
+
+/// The last keyframe at or before `seconds` in ffprobe's
+/// `pts_time,flags` packet listing.
+fn last_keyframe(packets: &str, seconds: f64) -> Option<f64> {
+    packets
+        .lines()
+        .filter_map(|line| {
+            let (pts, flags) = line.trim().split_once(',')?;
+            let pts: f64 = pts.parse().ok()?;
+            (flags.starts_with('K') && pts <= seconds + 1e-6).then_some(pts)
+        })
+        .fold(None, |last: Option<f64>, pts| {
+            Some(last.map_or(pts, |l| l.max(pts)))
+        })
+}
+
+/// Point `clip`'s source and alternate takes at their collected files
+/// (relative to the archive root), shifting in points for trimmed media.
+fn relink_clip(clip: &mut Clip, collected: &BTreeMap<String, Collected>) {
+    if clip.generator.is_some() {
+        return;
+    }
+    let duration = clip.played_source_range().duration;
+    let takes = clip
+        .takes
+        .iter_mut()
+        .map(|take| (&mut take.source, &mut take.source_in));
+    for (source, source_in) in std::iter::once((&mut clip.source, &mut clip.source_in)).chain(takes)
+    {
+        if let Some(c) = collected.get(&source.path) {
+            relink_source(source, source_in, duration, c);
+        }
+    }
+}
+
+/// Point `source`, played for `duration` from `source_in`, at its collected
+/// file.
+fn relink_source(
+    source: &mut ClipRef,
+    source_in: &mut RationalTime,
+    duration: RationalTime,
+    collected: &Collected,
+) {
+    match collected {
+        Collected::Full(relative) => {
+            source.path = relative.clone();
+        }
+        Collected::Trimmed(files) => {
+            let used = TimeRange::new(*source_in, duration);
+            let found = files
+                .iter()
+                .find(|(r, _)| r.start <= used.start && used.end() <= r.end());
+            if let Some((range, relative)) = found {
+                source.path = relative.clone();
+                source.source_duration = range.duration;
+                *source_in = *source_in - range.start;
+            }
+        }
+    }
+}
+
+fn manifest_entry(
+    original: &str,
+    collected: &Path,
+    relative: String,
+    source_range: Option<TimeRange>,
+) -> Result<ManifestEntry> {
+    let size_bytes = std::fs::metadata(collected)?.len();
+    Ok(ManifestEntry {
+        original_path: original.to_string(),
+        collected_path: relative,
+        size_bytes,
+        sha256: sha256_file(collected)?,
+        source_range,
+    })
+}
+
+/// Lowercase hex SHA-256 of a file's contents.
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+/// Pick a destination file name that doesn't collide with earlier ones.
+fn unique_file_name(src: &Path, part: Option<usize>, used: &mut HashSet<String>) -> String {
+    let stem = src
+        .file_stem()
+        .map(|s| sanitize_file_stem(&s.to_string_lossy()))
+        .unwrap_or_else(|| "media".to_string());
+    let ext = src
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    let base = match part {
+        Some(i) => format!("{stem}_part{i}"),
+        None => stem,
+    };
+
+    let mut candidate = format!("{base}{ext}");
+    let mut n = 1;
+    while used.contains(&candidate) {
+        candidate = format!("{base}_{n}{ext}");
+        n += 1;
+    }
+    used.insert(candidate.clone());
+    candidate
+}
+
+fn sanitize_file_stem(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, '-' | '_' | '.' | ' ') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if cleaned.trim().is_empty() {
+        "untitled".to_string()
+    } else {
+        cleaned
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proedit_timeline::{BarsGenerator, Generator, Sequence};
+
+    fn secs(s: i64) -> RationalTime {
+        RationalTime::new(s, 1)
+    }
+
+    #[test]
+    fn test_merge_ranges_pads_clamps_and_merges() {
+        let ranges = vec![
+            TimeRange::new(secs(10), secs(2)),
+            TimeRange::new(secs(1), secs(2)),
+            TimeRange::new(secs(13), secs(1)),
+        ];
+        let merged = merge_ranges(&ranges, secs(1), secs(14));
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0], TimeRange::from_start_end(secs(0), secs(4)));
+        assert_eq!(merged[1], TimeRange::from_start_end(secs(9), secs(14)));
+    }
+
+    #[test]
+    fn test_trim_args_stream_copy() {
+        let range = TimeRange::new(secs(2), secs(3));
+        let args = trim_args(
+            Path::new("in.mp4"),
+            Path::new("out.mp4"),
+            range,
+            TrimMethod::StreamCopy,
+        );
+        assert!(args.contains(&"copy".to_string()));
+        assert!(args.contains(&"2.000000".to_string()));
+        assert!(args.contains(&"3.000000".to_string()));
+
+        let args = trim_args(
+            Path::new("in.mp4"),
+            Path::new("out.mp4"),
+            range,
+            TrimMethod::Reencode,
+        );
+        assert!(!args.contains(&"copy".to_string()));
+    }
+
+    #[test]
+    fn test_collect_copies_and_reports_missing() {
+        let src_dir = tempfile::tempdir().unwrap();
+        let dst_dir = tempfile::tempdir().unwrap();
+        let media = src_dir.path().join("shot.mov");
+        std::fs::write(&media, b"hello").unwrap();
+
+        let mut project = Project::new("Archive Me");
+        let mut seq = Sequence::default();
+        let present = Clip::new(
+            "shot",
+            ClipRef::new(media.to_string_lossy(), RationalTime::new(10, 1)),
+        );
+        let missing = Clip::new("gone", ClipRef::new("/nonexistent/gone.mov", secs(5)));
+        let missing_id = missing.id;
+        seq.video_tracks[0].append_clip(present);
+        seq.video_tracks[0].append_clip(missing);
+        project.add_sequence(seq);
+
+        let options = CollectOptions::new(dst_dir.path());
+        let result = collect_project(&project, &options).unwrap();
+
+        assert_eq!(result.manifest.entries.len(), 1);
+        let entry = &result.manifest.entries[0];
+        assert_eq!(entry.collected_path, "Media/shot.mov");
+        assert_eq!(entry.size_bytes, 5);
+        assert_eq!(
+            entry.sha256,
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+
+        assert_eq!(result.manifest.missing.len(), 1);
+        assert_eq!(result.manifest.missing[0].clip_ids, vec![missing_id]);
+        assert!(!result.manifest.is_complete());
+
+        let track = &result.project.sequences[0].video_tracks[0];
+        assert_eq!(track.clip_at(0).unwrap().source.path, "Media/shot.mov");
+        assert_eq!(
+            track.clip_at(1).unwrap().source.path,
+            "/nonexistent/gone.mov"
+        );
+
+        assert!(dst_dir.path().join(MANIFEST_FILE_NAME).is_file());
+
+        // The archive opens from wherever it is moved to.
+        let moved = tempfile::tempdir().unwrap();
+        let archive = moved.path().join("archive");
+        std::fs::rename(dst_dir.path(), &archive).unwrap();
+        let reopened = ProjectFile::load_from_file(&archive.join("Archive Me.pep"))
+            .unwrap()
+            .project;
+        let path = &reopened.sequences[0].video_tracks[0]
+            .clip_at(0)
+            .unwrap()
+            .source
+            .path;
+        assert_eq!(path, "Media/shot.mov");
+        let media = reopened.media_path(path);
+        assert_eq!(media, archive.join("Media/shot.mov"));
+        assert_eq!(std::fs::read(media).unwrap(), b"hello");
+    }
+
+    #[test]
+    fn test_failed_trim_leaves_only_the_full_copy() {
+        let src_dir = tempfile::tempdir().unwrap();
+        let dst_dir = tempfile::tempdir().unwrap();
+        // Not decodable, so every trim fails.
+        std::fs::write(src_dir.path().join("shot.mov"), b"hello").unwrap();
+
+        let mut project = Project::new("Trimmed");
+        project.media_root = Some(src_dir.path().to_path_buf());
+        let mut seq = Sequence::default();
+        let mut first = Clip::new("a", ClipRef::new("shot.mov", secs(60)));
+        first.duration = secs(2);
+        let mut second = first.clone();
+        second.id = Uuid::new_v4();
+        second.source_in = secs(40);
+        seq.video_tracks[0].append_clip(first);
+        seq.video_tracks[0].append_clip(second);
+        project.add_sequence(seq);
+
+        let options = CollectOptions::new(dst_dir.path()).with_trim(secs(1), TrimMethod::Reencode);
+        let result = collect_project(&project, &options).unwrap();
+
+        assert_eq!(result.manifest.warnings.len(), 1);
+        assert_eq!(result.manifest.entries.len(), 1);
+        let entry = &result.manifest.entries[0];
+        assert_eq!(entry.original_path, "shot.mov");
+        assert_eq!(entry.collected_path, "Media/shot.mov");
+        let files: Vec<_> = std::fs::read_dir(options.media_path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        assert_eq!(files, ["shot.mov"]);
+    }
+
+    #[test]
+    fn test_used_range_honours_speed() {
+        let mut project = Project::new("Fast");
+        let mut seq = Sequence::default();
+        let mut clip = Clip::new("fast", ClipRef::new("a.mov", secs(60)));
+        clip.source_in = secs(10);
+        clip.duration = secs(5);
+        clip.speed = 2.0;
+        seq.video_tracks[0].append_clip(clip);
+        project.add_sequence(seq);
+
+        let usage = gather_usage(&project);
+        assert_eq!(
+            usage["a.mov"].ranges,
+            vec![TimeRange::new(secs(10), secs(10))]
+        );
+    }
+
+    #[test]
+    fn test_usage_covers_takes_and_skips_generators() {
+        let mut project = Project::new("Takes");
+        let mut seq = Sequence::default();
+        let mut clip = Clip::new("vo", ClipRef::new("take1.wav", secs(30)));
+        clip.source_in = secs(2);
+        clip.duration = secs(4);
+        clip.push_take(ClipRef::new("take2.wav", secs(30)), secs(1));
+        let bars = Clip::generated("bars", Generator::Bars(BarsGenerator::default()), secs(5));
+        seq.audio_tracks[0].append_clip(clip);
+        seq.video_tracks[0].append_clip(bars);
+        project.add_sequence(seq);
+
+        let usage = gather_usage(&project);
+        assert_eq!(usage.len(), 2);
+        assert_eq!(
+            usage["take1.wav"].ranges,
+            vec![TimeRange::new(secs(2), secs(4))]
+        );
+        assert_eq!(
+            usage["take2.wav"].ranges,
+            vec![TimeRange::new(secs(1), secs(4))]
+        );
+    }
+
+    #[test]
+    fn test_last_keyframe_before_time() {
+        let packets = "0.000000,K__\n0.040000,___\n2.002000,K__\n2.042000,___\n4.004000,K_\n";
+        assert_eq!(last_keyframe(packets, 3.0), Some(2.002));
+        assert_eq!(last_keyframe(packets, 2.002), Some(2.002));
+        assert_eq!(last_keyframe(packets, 1.0), Some(0.0));
+        assert_eq!(last_keyframe("", 1.0), None);
+    }
+
+    #[test]
+    fn test_relink_trimmed_shifts_source_in() {
+        let mut clip = Clip::new("c", ClipRef::new("a.mov", secs(60)));
+        clip.source_in = secs(20);
+        clip.duration = secs(5);
+        let files = vec![(
+            TimeRange::from_start_end(secs(18), secs(27)),
+            "Media/a_part0.mov".to_string(),
+        )];
+        let collected = BTreeMap::from([("a.mov".to_string(), Collected::Trimmed(files))]);
+        relink_clip(&mut clip, &collected);
+        assert_eq!(clip.source_in, secs(2));
+        assert_eq!(clip.source.source_duration, secs(9));
+        assert_eq!(clip.source.path, "Media/a_part0.mov");
+    }
+
+    #[test]
+    fn test_unique_file_names() {
+        let mut used = HashSet::new();
+        let a = unique_file_name(Path::new("/x/clip.mp4"), None, &mut used);
+        let b = unique_file_name(Path::new("/y/clip.mp4"), None, &mut used);
+        assert_eq!(a, "clip.mp4");
+        assert_eq!(b, "clip_1.mp4");
+    }
+}
@@ -1,7 +1,8 @@
-//! Video decoder using FFmpeg via ffmpeg-sidecar.
+//! Video and audio decoding using FFmpeg.
 
-use proedit_core::{FrameBuffer, FrameRate, ProEditError, Result};
+use proedit_core::{FrameBuffer, FrameRate, ProEditError, Result, TimeRange};
 use std::path::Path;
+use std::process::{Command, Stdio};
 use tracing::info;
 
 /// A decoded video frame with metadata.
@@ -121,3 +122,77 @@ impl VideoDecoder {
         self.seek_to_frame(frame)
     }
 }
+
+/// Decode the audio of a media file to interleaved f32 PCM.
+///
+/// FFmpeg converts the first audio stream to `sample_rate` and `channels`,
+/// so the result can be mixed without further conversion.
+pub fn decode_audio<P: AsRef<Path>>(path: P, sample_rate: u32, channels: u16) -> Result<Vec<f32>> {
+    let path = path.as_ref();
+    info!("Decoding audio: {}", path.display());
+    run_audio_decode(path, sample_rate, channels, None)
+}
+
+/// Decode `range` of a media file's audio, converted like [`decode_audio`].
+///
+/// FFmpeg seeks to the range start instead of decoding from the top. The
+/// result holds the range's frames at `sample_rate`, fewer where the media
+/// ends first.
+pub fn decode_audio_range<P: AsRef<Path>>(
+    path: P,
+    sample_rate: u32,
+    channels: u16,
+    range: TimeRange,
+) -> Result<Vec<f32>> {
+    let path = path.as_ref();
+    let mut samples = run_audio_decode(path, sample_rate, channels, Some(range))?;
+    let frames = (range.duration.to_seconds_f64() * sample_rate as f64).round() as usize;
+    samples.truncate(frames * channels as usize);
+    Ok(samples)
+}
+
+fn run_audio_decode(
+    path: &Path,
+    sample_rate: u32,
+    channels: u16,
+    range: Option<TimeRange>,
+) -> Result<Vec<f32>> {
+    let mut command = Command::new("ffmpeg");
+    command.arg("-v").arg("error");
+    if let Some(range) = range {
+        command
+            .arg("-ss")
+            .arg(range.start.to_seconds_f64().max(0.0).to_string());
+    }
+    command.arg("-i").arg(path);
+    if let Some(range) = range {
+        command
+            .arg("-t")
+            .arg(range.duration.to_seconds_f64().max(0.0).to_string());
+    }
+    let output = command
+        .args(["-vn", "-f", "f32le", "-acodec", "pcm_f32le", "-ac"])
+        .arg(channels.to_string())
+        .arg("-ar")
+        .arg(sample_rate.to_string())
+        .arg("pipe:1")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .output()
+        .map_err(|e| ProEditError::Decoder(format!("Failed to spawn ffmpeg: {e}")))?;
+
+    if !output.status.success() {
+        return Err(ProEditError::Decoder(format!(
+            "ffmpeg failed to decode {}: {}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(output
+        .stdout
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect())
+}
@@ -5,7 +5,7 @@
 
 use proedit_core::{FrameRate, RationalTime};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
@@ -93,6 +93,13 @@ pub struct ExportFormat {
     pub audio_bitrate: u32,
     /// Audio sample rate.
     pub audio_sample_rate: u32,
+    /// Audio channels (2 = stereo, 6 = 5.1, 8 = 7.1).
+    #[serde(default = "default_audio_channels")]
+    pub audio_channels: u16,
+}
+
+fn default_audio_channels() -> u16 {
+    2
 }
 
 impl ExportFormat {
@@ -109,6 +116,7 @@ impl ExportFormat {
             video_bitrate: None,
             audio_bitrate: 192,
             audio_sample_rate: 48000,
+            audio_channels: 2,
         }
     }
 
@@ -125,6 +133,7 @@ impl ExportFormat {
             video_bitrate: None,
             audio_bitrate: 256,
             audio_sample_rate: 48000,
+            audio_channels: 2,
         }
     }
 
@@ -141,6 +150,7 @@ impl ExportFormat {
             video_bitrate: None,
             audio_bitrate: 1536,
             audio_sample_rate: 48000,
+            audio_channels: 2,
         }
     }
 
@@ -157,6 +167,7 @@ impl ExportFormat {
             video_bitrate: None,
             audio_bitrate: 128,
             audio_sample_rate: 48000,
+            audio_channels: 2,
         }
     }
 }
@@ -186,6 +197,98 @@ impl ExportProgress {
     }
 }
 
+/// Loudness delivery spec for an export's audio.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LoudnessTarget {
+    /// Spec name shown in reports.
+    pub name: String,
+    /// Target integrated loudness (LUFS).
+    pub integrated: f64,
+    /// Allowed deviation from the target (LU).
+    pub tolerance: f64,
+    /// Maximum true peak (dBTP).
+    pub max_true_peak: f64,
+}
+
+impl LoudnessTarget {
+    /// EBU R128 broadcast: -23 LUFS ±0.5 LU, -1 dBTP.
+    pub fn ebu_r128() -> Self {
+        Self {
+            name: "EBU R128".into(),
+            integrated: -23.0,
+            tolerance: 0.5,
+            max_true_peak: -1.0,
+        }
+    }
+
+    /// Streaming platforms: -14 LUFS ±1 LU, -1 dBTP.
+    pub fn streaming() -> Self {
+        Self {
+            name: "Streaming".into(),
+            integrated: -14.0,
+            tolerance: 1.0,
+            max_true_peak: -1.0,
+        }
+    }
+}
+
+/// Measured loudness of an export checked against its target.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComplianceReport {
+    pub target: LoudnessTarget,
+    /// Integrated loudness (LUFS).
+    pub integrated: f64,
+    /// Loudness range (LU).
+    pub loudness_range: f64,
+    /// Maximum true peak (dBTP).
+    pub true_peak: f64,
+}
+
+impl ComplianceReport {
+    /// Whether the integrated loudness is within tolerance of the target.
+    pub fn loudness_ok(&self) -> bool {
+        (self.integrated - self.target.integrated).abs() <= self.target.tolerance
+    }
+
+    /// Whether the true peak is at or below the maximum.
+    pub fn true_peak_ok(&self) -> bool {
+        self.true_peak <= self.target.max_true_peak
+    }
+
+    /// Whether the export meets the spec.
+    pub fn passed(&self) -> bool {
+        self.loudness_ok() && self.true_peak_ok()
+    }
+}
+
+impl std::fmt::Display for ComplianceReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mark = |ok: bool| if ok { "ok" } else { "FAIL" };
+        writeln!(
+            f,
+            "{} loudness: {}",
+            self.target.name,
+            if self.passed() { "PASS" } else { "FAIL" }
+        )?;
+        writeln!(
+            f,
+            "  Integrated: {:.1} LUFS (target {:.1} \u{00B1}{:.1}) {}",
+            self.integrated,
+            self.target.integrated,
+            self.target.tolerance,
+            mark(self.loudness_ok())
+        )?;
+        writeln!(
+            f,
+            "  True peak:  {:.1} dBTP (max {:.1}) {}",
+            self.true_peak,
+            self.target.max_true_peak,
+            mark(self.true_peak_ok())
+        )?;
+        write!(f, "  Range:      {:.1} LU", self.loudness_range)
+    }
+}
+
 /// An export job configuration.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExportJob {
@@ -195,6 +298,15 @@ pub struct ExportJob {
     pub format: ExportFormat,
     /// Time range to export (None = entire sequence).
     pub range: Option<(RationalTime, RationalTime)>,
+    /// Loudness spec to check the audio against (None = no report).
+    #[serde(default)]
+    pub loudness_target: Option<LoudnessTarget>,
+    /// Rendered mix (WAV) to mux as the audio stream (None = no audio).
+    #[serde(default)]
+    pub audio_input: Option<PathBuf>,
+    /// Also write one WAV per submix bus next to the output.
+    #[serde(default)]
+    pub stems: bool,
 }
 
 impl ExportJob {
@@ -204,6 +316,9 @@ impl ExportJob {
             output_path: output_path.into(),
             format,
             range: None,
+            loudness_target: None,
+            audio_input: None,
+            stems: false,
         }
     }
 
@@ -213,6 +328,54 @@ impl ExportJob {
         self
     }
 
+    /// Check the audio against a loudness spec.
+    pub fn with_loudness_target(mut self, target: LoudnessTarget) -> Self {
+        self.loudness_target = Some(target);
+        self
+    }
+
+    /// Mux a rendered mix as the audio stream.
+    pub fn with_audio_input(mut self, path: impl Into<PathBuf>) -> Self {
+        self.audio_input = Some(path.into());
+        self
+    }
+
+    /// Also write per-bus stems.
+    pub fn with_stems(mut self, stems: bool) -> Self {
+        self.stems = stems;
+        self
+    }
+
+    /// Where the rendered mix goes before muxing: the output path with a
+    /// `.mix.wav` extension.
+    pub fn mix_path(&self) -> PathBuf {
+        self.output_path.with_extension("mix.wav")
+    }
+
+    /// Where the stem of bus `name` goes: `<output stem>.<name>.wav` next
+    /// to the output, with the name reduced to file-safe characters.
+    pub fn stem_path(&self, name: &str) -> PathBuf {
+        let safe: String = name
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' {
+                    c.to_ascii_lowercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let stem = self
+            .output_path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        self.output_path
+            .parent()
+            .unwrap_or(Path::new(""))
+            .join(format!("{stem}.{safe}.wav"))
+    }
+
     /// Compute total frames for this job.
     pub fn total_frames(&self, sequence_duration: RationalTime) -> u64 {
         let duration = if let Some((start, end)) = self.range {
@@ -245,6 +408,10 @@ impl ExportJob {
             "pipe:0".into(),
         ]);
 
+        if let Some(audio) = &self.audio_input {
+            args.extend_from_slice(&["-i".into(), audio.to_string_lossy().into_owned()]);
+        }
+
         // Video codec
         args.extend_from_slice(&[
             "-c:v".into(),
@@ -269,6 +436,20 @@ impl ExportJob {
         // Pixel format for output
         args.extend_from_slice(&["-pix_fmt".into(), "yuv420p".into()]);
 
+        // Audio codec and channel count
+        if self.audio_input.is_some() {
+            args.extend_from_slice(&[
+                "-c:a".into(),
+                self.format.audio_codec.ffmpeg_encoder().into(),
+                "-b:a".into(),
+                format!("{}k", self.format.audio_bitrate),
+                "-ar".into(),
+                self.format.audio_sample_rate.to_string(),
+                "-ac".into(),
+                self.format.audio_channels.to_string(),
+            ]);
+        }
+
         // Output
         args.push(self.output_path.to_string_lossy().into_owned());
 
@@ -437,6 +618,27 @@ mod tests {
         assert!(args.contains(&"-c:v".to_string()));
         assert!(args.contains(&"libx264".to_string()));
         assert!(args.contains(&"-crf".to_string()));
+        assert!(!args.contains(&"-c:a".to_string()));
+    }
+
+    #[test]
+    fn test_multichannel_audio_and_stem_paths() {
+        let mut format = ExportFormat::prores_422();
+        format.audio_channels = 6;
+        let job = ExportJob::new("/tmp/docs/cut 3.mov", format).with_stems(true);
+        let job = job.clone().with_audio_input(job.mix_path());
+        assert_eq!(job.mix_path(), PathBuf::from("/tmp/docs/cut 3.mix.wav"));
+        assert_eq!(
+            job.stem_path("Dialogue (DX)"),
+            PathBuf::from("/tmp/docs/cut 3.dialogue__dx_.wav")
+        );
+
+        let args = job.ffmpeg_args();
+        let after = |flag: &str| &args[args.iter().position(|a| a == flag).unwrap() + 1];
+        assert_eq!(args.iter().filter(|a| *a == "-i").count(), 2);
+        assert_eq!(after("-c:a"), "pcm_s16le");
+        assert_eq!(after("-ac"), "6");
+        assert_eq!(args.last().unwrap(), "/tmp/docs/cut 3.mov");
     }
 
     #[test]
@@ -450,6 +652,23 @@ mod tests {
         assert!((progress.fraction() - 0.25).abs() < 0.001);
     }
 
+    #[test]
+    fn test_compliance_report() {
+        let mut report = ComplianceReport {
+            target: LoudnessTarget::ebu_r128(),
+            integrated: -23.3,
+            loudness_range: 6.0,
+            true_peak: -1.5,
+        };
+        assert!(report.passed());
+        assert!(report.to_string().starts_with("EBU R128 loudness: PASS"));
+
+        report.true_peak = -0.2;
+        assert!(report.loudness_ok());
+        assert!(!report.passed());
+        assert!(report.to_string().contains("-0.2 dBTP (max -1.0) FAIL"));
+    }
+
     #[test]
     fn test_cancel_handle() {
         let cancel = ExportCancel::new();
@@ -5,13 +5,19 @@
 //! - Audio decoding
 //! - Media file probing
 //! - Encoding and muxing
+//! - Collecting project media into an archive
 
+pub mod collect;
 pub mod decoder;
 pub mod export;
 pub mod probe;
 
-pub use decoder::{VideoDecoder, VideoFrame};
-pub use export::{ExportCancel, ExportFormat, ExportJob, ExportProgress, VideoCodec};
+pub use collect::{collect_project, CollectManifest, CollectMode, CollectOptions, TrimMethod};
+pub use decoder::{decode_audio, decode_audio_range, VideoDecoder, VideoFrame};
+pub use export::{
+    ComplianceReport, ExportCancel, ExportFormat, ExportJob, ExportProgress, LoudnessTarget,
+    VideoCodec,
+};
 pub use probe::MediaProbe;
 
 /// Initialize FFmpeg (call once at startup).
@@ -0,0 +1,61 @@
+//! Shared test helpers for ProEdit Studio.
+//!
+//! Used as a dev-dependency by crates with golden image tests, so the
+//! comparison and the `PROEDIT_BLESS` workflow behave the same everywhere.
+
+use std::path::{Path, PathBuf};
+use tiny_skia::{IntSize, Pixmap};
+
+/// `tests/golden/<name>.png` under `manifest_dir` (pass
+/// `env!("CARGO_MANIFEST_DIR")`). `name` may contain `/`.
+pub fn golden_path(manifest_dir: &str, name: &str) -> PathBuf {
+    Path::new(manifest_dir)
+        .join("tests")
+        .join("golden")
+        .join(name)
+        .with_extension("png")
+}
+
+/// Compare `width`×`height` RGBA8 pixels against the golden at `path`, or
+/// rewrite it when `PROEDIT_BLESS` is set. Channels may differ by
+/// `tolerance`.
+///
+/// Panics if the golden is missing, has another size, or differs by more.
+pub fn assert_golden(path: &Path, width: u32, height: u32, rgba: &[u8], tolerance: u8) {
+    if std::env::var_os("PROEDIT_BLESS").is_some() {
+        let size = IntSize::from_wh(width, height).expect("golden size");
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+        Pixmap::from_vec(rgba.to_vec(), size)
+            .expect("RGBA8 pixels for the golden's size")
+            .save_png(path)
+            .unwrap();
+        return;
+    }
+    let golden = Pixmap::load_png(path).unwrap_or_else(|e| {
+        panic!(
+            "missing golden {}: {e} (run with PROEDIT_BLESS=1)",
+            path.display()
+        )
+    });
+    assert_eq!(
+        (golden.width(), golden.height()),
+        (width, height),
+        "{} has another size",
+        path.display()
+    );
+    assert_eq!(golden.data().len(), rgba.len());
+    let worst = golden
+        .data()
+        .iter()
+        .zip(rgba)
+        .map(|(a, b)| a.abs_diff(*b))
+        .max()
+        .unwrap_or(0);
+    assert!(
+        worst <= tolerance,
+        "{} differs from its golden by {worst}",
+        path.display()
+    );
+}
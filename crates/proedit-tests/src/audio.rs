@@ -99,3 +99,303 @@ fn waveform_pixel_count_matches_duration() {
     let waveform = Waveform::compute(&samples, spp, sample_rate as u32);
     assert_eq!(waveform.data.len(), 1000);
 }
+
+/// Sequence with one audio clip whose left channel is a ramp, so every
+/// sample identifies its position in the source.
+fn ramp_sequence(loader: &proedit_audio::MemorySourceLoader) -> proedit_timeline::Sequence {
+    use proedit_audio::PcmSource;
+    use proedit_core::RationalTime;
+    use proedit_timeline::{Clip, ClipRef, Sequence};
+
+    let ramp: Vec<f32> = (0..48000 * 2).map(|i| (i / 2) as f32 / 48000.0).collect();
+    loader.insert("ramp.wav", PcmSource::new(48000, 2, ramp));
+
+    let mut seq = Sequence::default();
+    seq.audio_tracks[0].append_clip(Clip::new(
+        "ramp",
+        ClipRef::new("ramp.wav", RationalTime::new(1, 1)),
+    ));
+    seq
+}
+
+#[test]
+fn audio_engine_render_thread_matches_offline_render_after_seek() {
+    use proedit_audio::{MemorySourceLoader, Mixer, TimelineRenderer};
+    use proedit_core::{FrameRate, RationalTime, TimeRange};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    let loader = Arc::new(MemorySourceLoader::new());
+    let seq = ramp_sequence(&loader);
+
+    let mut engine = AudioEngine::new().unwrap();
+    engine.set_source_loader(loader.clone());
+    engine.set_sequence(seq.clone());
+    assert!(engine.wait_prepared(Duration::from_secs(5)));
+    let seek = RationalTime::from_frames(12, FrameRate::FPS_24);
+    engine.seek(seek);
+    assert_eq!(engine.position(), seek);
+
+    engine.play();
+    let output = engine.output_buffer();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while output.available_read() < 2048 && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    let mut live = vec![0.0f32; 2048];
+    assert_eq!(output.read(&mut live), 2048);
+    engine.stop();
+
+    // The first live sample is exactly frame 12 (sample 24000).
+    let mut renderer = TimelineRenderer::new(48000, loader);
+    let offline = renderer.render_range(
+        &seq,
+        &mut Mixer::new(3, 16),
+        TimeRange::new(seek, RationalTime::new(1024, 48000)),
+    );
+    assert_eq!(live, offline);
+
+    // Stopping rewinds to the last sample actually read.
+    assert_eq!(engine.position(), seek + RationalTime::new(1024, 48000));
+}
+
+#[test]
+fn audio_engine_waits_for_sources_before_playing() {
+    use proedit_audio::{MemorySourceLoader, PcmSource, SourceLoader};
+    use proedit_core::Result;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    /// Blocks loads until released, and records the loading thread.
+    struct GatedLoader {
+        inner: MemorySourceLoader,
+        open: AtomicBool,
+    }
+
+    impl SourceLoader for GatedLoader {
+        fn load(&self, path: &str) -> Result<Arc<PcmSource>> {
+            assert_ne!(std::thread::current().name(), Some("audio-render"));
+            while !self.open.load(Ordering::Acquire) {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            self.inner.load(path)
+        }
+    }
+
+    let loader = Arc::new(GatedLoader {
+        inner: MemorySourceLoader::new(),
+        open: AtomicBool::new(false),
+    });
+    let seq = ramp_sequence(&loader.inner);
+    let mut engine = AudioEngine::new().unwrap();
+    engine.set_source_loader(loader.clone());
+    engine.set_sequence(seq);
+
+    // Nothing is rendered, and the playhead holds, while the source loads.
+    engine.play();
+    let output = engine.output_buffer();
+    std::thread::sleep(Duration::from_millis(50));
+    assert_eq!(output.available_read(), 0);
+    assert_eq!(engine.position(), proedit_core::RationalTime::ZERO);
+    assert!(!engine.is_prepared());
+
+    // Once it has loaded, the first block heard is the start of the clip.
+    loader.open.store(true, Ordering::Release);
+    assert!(engine.wait_prepared(Duration::from_secs(5)));
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while output.available_read() < 2048 && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    let mut block = vec![1.0f32; 2048];
+    assert_eq!(output.read(&mut block), 2048);
+    engine.stop();
+    assert_eq!(&block[..2], &[0.0, 0.0]);
+    assert!(block.windows(4).all(|w| w[2] > w[0]));
+}
+
+#[test]
+fn audio_engine_without_sequence_renders_nothing() {
+    let mut engine = AudioEngine::new().unwrap();
+    engine.play();
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    assert_eq!(engine.output_buffer().available_read(), 0);
+    assert_eq!(engine.position(), proedit_core::RationalTime::ZERO);
+}
+
+/// Sequence with one 10 s, 1 kHz stereo sine clip at `dbfs` peak.
+fn tone_sequence(
+    loader: &proedit_audio::MemorySourceLoader,
+    dbfs: f32,
+) -> proedit_timeline::Sequence {
+    use proedit_audio::PcmSource;
+    use proedit_core::RationalTime;
+    use proedit_timeline::{Clip, ClipRef, Sequence};
+
+    let amplitude = 10f32.powf(dbfs / 20.0);
+    let tone: Vec<f32> = (0..48000 * 10)
+        .flat_map(|n| {
+            let s = amplitude * (2.0 * std::f32::consts::PI * 1000.0 * n as f32 / 48000.0).sin();
+            [s, s]
+        })
+        .collect();
+    loader.insert("tone.wav", PcmSource::new(48000, 2, tone));
+
+    let mut seq = Sequence::default();
+    seq.audio_tracks[0].append_clip(Clip::new(
+        "tone",
+        ClipRef::new("tone.wav", RationalTime::new(10, 1)),
+    ));
+    seq
+}
+
+#[test]
+fn normalize_clip_and_master_to_loudness_target() {
+    use proedit_audio::{
+        measure_clip, measure_range, normalize_clip, normalize_master, MemorySourceLoader, Mixer,
+        TimelineRenderer,
+    };
+    use std::sync::Arc;
+
+    let loader = Arc::new(MemorySourceLoader::new());
+    let mut seq = tone_sequence(&loader, -30.0);
+    let mut renderer = TimelineRenderer::new(48000, loader);
+
+    let edit = normalize_clip(&mut renderer, &seq.audio_tracks[0], 0, -23.0).unwrap();
+    seq.execute(edit);
+    let clip = seq.audio_tracks[0].clip_at(0).unwrap();
+    assert!((clip.audio.gain_db - 7.0).abs() < 0.1);
+    assert!((measure_clip(&mut renderer, clip).integrated + 23.0).abs() < 0.1);
+    assert!(seq.undo());
+    assert_eq!(seq.audio_tracks[0].clip_at(0).unwrap().audio.gain_db, 0.0);
+    assert!(seq.redo());
+
+    // The mix is 3 dB down at center pan; the master makes it up.
+    let mut mixer = Mixer::new(1, 4096);
+    let range = seq.time_range();
+    let edit = normalize_master(&mut renderer, &seq, &mut mixer, range, -14.0);
+    seq.execute(edit);
+    assert!((seq.master_gain_db - 12.0).abs() < 0.2);
+    let report = measure_range(&mut renderer, &seq, &mut mixer, range);
+    assert!((report.integrated + 14.0).abs() < 0.1);
+    assert!(report.true_peak < -10.0);
+}
+
+#[test]
+fn mixer_meter_matches_offline_measurement() {
+    use proedit_audio::{
+        measure_range, LoudnessMeter, MemorySourceLoader, Mixer, TimelineRenderer,
+    };
+    use std::sync::Arc;
+
+    let loader = Arc::new(MemorySourceLoader::new());
+    let seq = tone_sequence(&loader, -20.0);
+    let mut renderer = TimelineRenderer::new(48000, loader);
+
+    let mut mixer = Mixer::new(1, 4096);
+    mixer.meter = Some(LoudnessMeter::new(48000));
+    let offline = measure_range(
+        &mut renderer,
+        &seq,
+        &mut mixer.offline_copy(),
+        seq.time_range(),
+    );
+    renderer.render_range(&seq, &mut mixer, seq.time_range());
+    let live = mixer.meter.as_ref().unwrap().report();
+    assert_eq!(live, offline);
+}
+
+#[test]
+fn audio_engine_measures_sequence_loudness() {
+    use proedit_audio::MemorySourceLoader;
+    use std::sync::Arc;
+
+    let loader = Arc::new(MemorySourceLoader::new());
+    let seq = tone_sequence(&loader, -20.0);
+    let engine = AudioEngine::default();
+    assert!(engine.measure_loudness(None).is_none());
+
+    engine.set_source_loader(loader);
+    engine.set_sequence(seq);
+    let report = engine.measure_loudness(None).unwrap();
+    // -20 dBFS sine, 3 dB pan law.
+    assert!((report.integrated + 23.0).abs() < 0.1);
+}
+
+#[test]
+fn audio_engine_plays_through_null_output() {
+    use proedit_audio::{MemorySourceLoader, NullOutput};
+    use proedit_core::RationalTime;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    let loader = Arc::new(MemorySourceLoader::new());
+    let seq = ramp_sequence(&loader);
+    let mut engine = AudioEngine::new().unwrap();
+    engine.set_source_loader(loader);
+    engine.set_sequence(seq);
+    engine
+        .open_output(Box::new(NullOutput::new().with_format(44100, 1)))
+        .unwrap();
+    assert_eq!(engine.output().unwrap().format(), Some((44100, 1)));
+
+    engine.play();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while engine.position() < RationalTime::new(1, 5) && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    engine.stop();
+
+    let stats = engine.output_stats().unwrap();
+    assert!(stats.frames_played > 0);
+    assert_eq!(stats.latency, Duration::from_millis(10));
+    // The audible time trails the playhead by the output latency.
+    let position = engine.position();
+    assert!(position >= RationalTime::new(1, 5));
+    assert_eq!(
+        engine.audible_position(),
+        position - RationalTime::new(1, 100)
+    );
+    engine.close_output().unwrap();
+    assert!(engine.output().is_none());
+}
+
+#[test]
+fn audio_engine_exports_surround_mix_and_stems() {
+    use proedit_audio::{Bus, ChannelLayout, MemorySourceLoader, Route};
+    use std::sync::Arc;
+
+    let loader = Arc::new(MemorySourceLoader::new());
+    let seq = tone_sequence(&loader, -20.0);
+    let engine = AudioEngine::default();
+    engine.set_source_loader(loader);
+    engine.set_sequence(seq);
+    {
+        let mut mixer = engine.mixer.lock();
+        let dialogue = mixer.add_bus(Bus::new("Dialogue"));
+        mixer.add_bus(Bus::new("Music"));
+        mixer.channel_mut(0).unwrap().output = Route::Bus(dialogue);
+    }
+
+    let dir = std::env::temp_dir().join(format!("proedit-stems-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let mix = dir.join("mix.wav");
+    engine
+        .export_mix(&mix, None, ChannelLayout::Surround71)
+        .unwrap();
+    let stems = engine
+        .export_stems(None, ChannelLayout::Surround51, |bus| {
+            dir.join(format!("{}.wav", bus.name))
+        })
+        .unwrap();
+
+    // 10 s of 32-bit float: 8 channels in the mix, 6 in each stem.
+    let data_bytes = |path: &std::path::Path| std::fs::metadata(path).unwrap().len() - 68;
+    assert_eq!(data_bytes(&mix), 480_000 * 8 * 4);
+    assert_eq!(stems, vec![dir.join("Dialogue.wav"), dir.join("Music.wav")]);
+    assert!(stems.iter().all(|s| data_bytes(s) == 480_000 * 6 * 4));
+
+    // Playback keeps its stereo layout.
+    assert_eq!(engine.mixer.lock().layout(), ChannelLayout::Stereo);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
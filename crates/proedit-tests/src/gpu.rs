@@ -1,9 +1,16 @@
 //! Integration tests for the GPU subsystem.
 //!
-//! Exercises CPU-side logic only — no actual GPU required.
+//! The parity tests render on a software adapter (see
+//! `GpuContext::new_headless`) and are skipped when there is none, unless
+//! `PROEDIT_REQUIRE_GPU` is set.
 
-use proedit_core::{FrameRate, RationalTime};
-use proedit_gpu::{BlendMode, FrameCache, NodeOp, RenderGraph};
+use proedit_color::{ColorSpace, WorkingSpace};
+use proedit_core::{FrameBuffer, FrameRate, PixelFormat, RationalTime};
+use proedit_effects::{EffectsRegistry, ParamValues, VideoEffect, VignetteEffect};
+use proedit_gpu::{
+    parity_error, BlendMode, CpuBackend, FrameCache, GpuContext, GraphExecutor, HeadlessCompositor,
+    NodeId, NodeOp, RenderGraph, PARITY_TOLERANCE,
+};
 
 #[test]
 fn render_graph_models_three_layer_composite() {
@@ -104,3 +111,243 @@ fn timecode_as_frame_id_for_cache() {
     assert_eq!(frame, parsed_frame);
     assert!(cache.get(parsed_frame).is_some());
 }
+
+#[test]
+fn executor_runs_registered_cpu_effects() {
+    let mut backend = CpuBackend::new();
+    backend.set_source(1, FrameBuffer::test_pattern(64, 32));
+    let registry = EffectsRegistry::new();
+    for effect in registry.effects() {
+        let name = effect.name().to_string();
+        backend.register_effect(name.clone(), move |frame: &FrameBuffer| {
+            EffectsRegistry::new()
+                .find(&name)
+                .unwrap()
+                .apply_cpu(frame, &ParamValues::new())
+        });
+    }
+
+    let size = (64, 32);
+    let mut graph = RenderGraph::new();
+    let src = graph.add_node(NodeOp::Source { frame_id: 1 }, vec![], size);
+    let fx = graph.add_node(
+        NodeOp::Effect {
+            effect_name: "Vignette".into(),
+        },
+        vec![src],
+        size,
+    );
+    let out = graph.add_node(NodeOp::Output, vec![fx], size);
+
+    let mut exec = GraphExecutor::new(backend, 64 * 1024 * 1024);
+    let frame = exec.execute(&graph, out).unwrap();
+    let expected = VignetteEffect::new()
+        .apply_cpu(&FrameBuffer::test_pattern(64, 32), &ParamValues::new())
+        .unwrap();
+    assert_eq!(frame.primary_plane().data, expected.primary_plane().data);
+
+    exec.execute(&graph, out).unwrap();
+    assert_eq!(exec.stats().cache_hits, 1);
+    assert_eq!(exec.stats().nodes_run, 1);
+}
+
+#[test]
+fn half_dissolve_in_linear_working_space_is_physically_correct() {
+    let working = WorkingSpace::new(ColorSpace::LinearSRGB, PixelFormat::Rgba32F).unwrap();
+    let size = (8, 4);
+    let solid = |v: u8| {
+        let frame = FrameBuffer::from_rgba_f32(
+            8,
+            4,
+            PixelFormat::Rgba8,
+            &[[v as f32 / 255.0, v as f32 / 255.0, v as f32 / 255.0, 1.0]; 32],
+        )
+        .unwrap();
+        working.to_working(&frame, ColorSpace::SRGB).unwrap()
+    };
+    let mut backend = CpuBackend::with_format(working.format());
+    backend.set_source(1, solid(0));
+    backend.set_source(2, solid(255));
+
+    let mut graph = RenderGraph::new();
+    let black = graph.add_node(NodeOp::Source { frame_id: 1 }, vec![], size);
+    let white = graph.add_node(NodeOp::Source { frame_id: 2 }, vec![], size);
+    let mix = graph.add_node(
+        NodeOp::Composite {
+            blend_mode: BlendMode::Normal as u32,
+            opacity: 0.5,
+        },
+        vec![black, white],
+        size,
+    );
+    let out = graph.add_node(NodeOp::Output, vec![mix], size);
+    let mut exec = GraphExecutor::new(backend, 1 << 20);
+    let frame = exec.execute(&graph, out).unwrap();
+
+    // Half of white's light, which displays as sRGB 188 rather than the
+    // 128 a display-referred mix gives.
+    let px = frame.read_rgba_f32().unwrap()[0];
+    assert!(px[..3].iter().all(|v| (v - 0.5).abs() < 1e-5), "{px:?}");
+    let display = working
+        .to_output(&frame, ColorSpace::SRGB, PixelFormat::Rgba8)
+        .unwrap();
+    assert_eq!(&display.primary_plane().row(0)[..4], &[188, 188, 188, 255]);
+}
+
+fn headless_compositor() -> Option<HeadlessCompositor> {
+    let compositor = GpuContext::new_headless_blocking()
+        .and_then(|context| HeadlessCompositor::new(&context, 64 * 1024 * 1024));
+    match compositor {
+        Ok(compositor) => Some(compositor),
+        Err(e) if std::env::var_os("PROEDIT_REQUIRE_GPU").is_none() => {
+            tracing::warn!("Skipping GPU parity test: {}", e);
+            None
+        }
+        Err(e) => panic!("no headless GPU: {e}"),
+    }
+}
+
+/// Two half-float layers with varied colour and coverage, some of it HDR.
+fn parity_layers() -> (FrameBuffer, FrameBuffer) {
+    let (w, h) = (32, 16);
+    let mut base = Vec::new();
+    let mut layer = Vec::new();
+    for y in 0..h {
+        for x in 0..w {
+            let (u, v) = (x as f32 / w as f32, y as f32 / h as f32);
+            base.push([u, v, 0.5, 0.4 + 0.6 * u]);
+            layer.push([1.0 - v, ((x * y) % 7) as f32 / 7.0, 1.5 * u, v]);
+        }
+    }
+    let frame = |pixels: &[[f32; 4]]| {
+        FrameBuffer::from_rgba_f32(w, h, PixelFormat::Rgba16F, pixels).unwrap()
+    };
+    (frame(&base), frame(&layer))
+}
+
+/// Render `graph` on the CPU reference backend and on the headless GPU.
+fn render_both(
+    gpu: &mut HeadlessCompositor,
+    sources: &[(u64, &FrameBuffer)],
+    graph: &RenderGraph,
+    out: NodeId,
+) -> (FrameBuffer, FrameBuffer) {
+    let mut cpu = CpuBackend::with_format(PixelFormat::Rgba16F);
+    for (id, frame) in sources {
+        cpu.set_source(*id, (*frame).clone());
+        gpu.backend_mut().set_source(*id, frame).unwrap();
+    }
+    let reference = GraphExecutor::new(cpu, 0).execute(graph, out).unwrap();
+    let rendered = gpu.render_to_frame(graph, out).unwrap();
+    (FrameBuffer::clone(&reference), rendered)
+}
+
+#[test]
+fn headless_gpu_blend_modes_match_cpu_reference() {
+    let Some(mut gpu) = headless_compositor() else {
+        return;
+    };
+    let (base, layer) = parity_layers();
+    let size = (base.width, base.height);
+    for mode in BlendMode::ALL {
+        let mut graph = RenderGraph::new();
+        let b = graph.add_node(NodeOp::Source { frame_id: 1 }, vec![], size);
+        let l = graph.add_node(NodeOp::Source { frame_id: 2 }, vec![], size);
+        let comp = graph.add_node(
+            NodeOp::Composite {
+                blend_mode: mode as u32,
+                opacity: 0.75,
+            },
+            vec![b, l],
+            size,
+        );
+        let out = graph.add_node(NodeOp::Output, vec![comp], size);
+        let (reference, rendered) = render_both(&mut gpu, &[(1, &base), (2, &layer)], &graph, out);
+        let error = parity_error(&reference, &rendered).unwrap();
+        assert!(error <= PARITY_TOLERANCE, "{mode:?}: error {error}");
+    }
+}
+
+#[test]
+fn headless_gpu_transform_and_effects_match_cpu_reference() {
+    let Some(mut gpu) = headless_compositor() else {
+        return;
+    };
+    let (base, layer) = parity_layers();
+    let size = (base.width, base.height);
+    let vignette =
+        |frame: &FrameBuffer| VignetteEffect::new().apply_cpu(frame, &ParamValues::new());
+    gpu.backend_mut().register_cpu_effect("Vignette", vignette);
+
+    // Rotate by 0.3 rad and scale by 1.4 about the centre, then shift by a
+    // fraction of a pixel. Column-major.
+    let (sin, cos) = (0.3f32.sin() * 1.4, 0.3f32.cos() * 1.4);
+    let tx = 16.3 - (cos * 16.0 - sin * 8.0);
+    let ty = 8.6 - (sin * 16.0 + cos * 8.0);
+    let matrix = [cos, sin, 0.0, -sin, cos, 0.0, tx, ty, 1.0];
+    let mut graph = RenderGraph::new();
+    let b = graph.add_node(NodeOp::Source { frame_id: 1 }, vec![], size);
+    let l = graph.add_node(NodeOp::Source { frame_id: 2 }, vec![], size);
+    let moved = graph.add_node(NodeOp::Transform { matrix }, vec![l], size);
+    let comp = graph.add_node(
+        NodeOp::Composite {
+            blend_mode: BlendMode::Normal as u32,
+            opacity: 1.0,
+        },
+        vec![b, moved],
+        size,
+    );
+    let fx = graph.add_node(
+        NodeOp::Effect {
+            effect_name: "Vignette".into(),
+        },
+        vec![comp],
+        size,
+    );
+    let out = graph.add_node(NodeOp::Output, vec![fx], size);
+
+    let mut cpu = CpuBackend::with_format(PixelFormat::Rgba16F);
+    cpu.register_effect("Vignette", vignette);
+    cpu.set_source(1, base.clone());
+    cpu.set_source(2, layer.clone());
+    let reference = GraphExecutor::new(cpu, 0).execute(&graph, out).unwrap();
+    gpu.backend_mut().set_source(1, &base).unwrap();
+    gpu.backend_mut().set_source(2, &layer).unwrap();
+    let rendered = gpu.render_to_frame(&graph, out).unwrap();
+    let error = parity_error(&reference, &rendered).unwrap();
+    assert!(error <= PARITY_TOLERANCE, "error {error}");
+
+    // The second render comes from the node cache.
+    let again = gpu.render_to_frame(&graph, out).unwrap();
+    assert_eq!(gpu.executor_mut().stats().cache_hits, 1);
+    assert_eq!(again.primary_plane().data, rendered.primary_plane().data);
+}
+
+#[test]
+fn headless_gpu_dissolve_pattern_matches_cpu_exactly() {
+    let Some(mut gpu) = headless_compositor() else {
+        return;
+    };
+    let size = (32, 16);
+    let solid = |v: f32| {
+        FrameBuffer::from_rgba_f32(32, 16, PixelFormat::Rgba16F, &[[v, v, v, 1.0]; 512]).unwrap()
+    };
+    let (black, white) = (solid(0.0), solid(1.0));
+    let mut graph = RenderGraph::new();
+    let b = graph.add_node(NodeOp::Source { frame_id: 1 }, vec![], size);
+    let w = graph.add_node(NodeOp::Source { frame_id: 2 }, vec![], size);
+    let comp = graph.add_node(
+        NodeOp::Composite {
+            blend_mode: BlendMode::Dissolve as u32,
+            opacity: 0.5,
+        },
+        vec![b, w],
+        size,
+    );
+    let out = graph.add_node(NodeOp::Output, vec![comp], size);
+    let (reference, rendered) = render_both(&mut gpu, &[(1, &black), (2, &white)], &graph, out);
+    assert_eq!(
+        reference.primary_plane().data,
+        rendered.primary_plane().data
+    );
+}
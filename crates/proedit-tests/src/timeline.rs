@@ -1,12 +1,14 @@
 //! Integration tests for the timeline subsystem.
 //!
 //! Exercises cross-crate interactions between proedit-core,
-//! proedit-timeline, and proedit-media.
+//! proedit-timeline, proedit-media and proedit-text.
 
-use proedit_core::{EasingCurve, FrameRate, KeyframeTrack, RationalTime};
+use proedit_core::{EasingCurve, FrameRate, KeyframeTrack, ParamValue, RationalTime};
 use proedit_media::export::{ExportFormat, ExportJob};
+use proedit_text::{FontLibrary, TextRenderer};
 use proedit_timeline::{
-    Clip, ClipRef, EditCommand, Project, ProjectFile, Sequence, Track, UndoStack,
+    AdjustmentLayer, Clip, ClipRef, EditCommand, EffectInstance, Generator, Project, ProjectFile,
+    Sequence, TextGenerator, Track, TrackItem, UndoStack,
 };
 
 // ── Helpers ────────────────────────────────────────────────────
@@ -203,6 +205,109 @@ fn edited_project_serializes_correctly() {
     assert_eq!(seq.video_tracks[1].clip_count(), 1);
 }
 
+#[test]
+fn adjustment_layers_and_track_effects_serialize() {
+    let mut project = build_project();
+
+    let seq = project.active_sequence_mut().unwrap();
+    seq.video_tracks[0].effects = vec![EffectInstance::new("Film Grain")];
+    let mut grade = AdjustmentLayer::new("Grade", RationalTime::new(20, 1));
+    grade
+        .effects
+        .push(EffectInstance::new("Vignette").with_param("intensity", ParamValue::Float(0.8)));
+    let mut v2 = Track::new_video("V2");
+    v2.append_gap(RationalTime::new(5, 1));
+    v2.items.push(TrackItem::Adjustment(grade.clone()));
+    seq.video_tracks.push(v2);
+
+    let json = ProjectFile::new(project).to_json().unwrap();
+    let loaded = ProjectFile::from_json(&json).unwrap();
+
+    let seq = loaded.project.active_sequence().unwrap();
+    assert_eq!(seq.video_tracks[0].effects[0].name, "Film Grain");
+    assert!(matches!(&seq.video_tracks[1].items[1], TrackItem::Adjustment(l) if *l == grade));
+    assert_eq!(seq.video_tracks[1].clip_count(), 0);
+    assert_eq!(seq.duration(), RationalTime::new(45, 1));
+}
+
+#[test]
+fn overwrite_splits_adjustment_layers() {
+    let mut track = Track::new_video("V2");
+    let mut grade = AdjustmentLayer::new("Grade", RationalTime::new(10, 1));
+    grade.effects.push(EffectInstance::new("Vignette"));
+    track.items.push(TrackItem::Adjustment(grade.clone()));
+
+    track.overwrite(RationalTime::new(4, 1), clip("Insert", 2));
+    assert_eq!(track.items.len(), 3);
+    let (TrackItem::Adjustment(left), TrackItem::Adjustment(right)) =
+        (&track.items[0], &track.items[2])
+    else {
+        panic!("adjustment layer was not split: {:?}", track.items);
+    };
+    assert_eq!(left.id, grade.id);
+    assert_ne!(right.id, grade.id);
+    assert_eq!(left.duration, RationalTime::new(4, 1));
+    assert_eq!(right.duration, RationalTime::new(4, 1));
+    assert_eq!(right.effects, grade.effects);
+}
+
+#[test]
+fn split_title_keeps_its_animation() {
+    let mut title = TextGenerator::new("Chapter One", 96.0);
+    title.opacity = KeyframeTrack::new("opacity");
+    title
+        .opacity
+        .set(RationalTime::ZERO, 0.0, EasingCurve::Linear);
+    title
+        .opacity
+        .set(RationalTime::new(2, 1), 1.0, EasingCurve::Linear);
+    let mut track = Track::new_video("V2");
+    track.append_clip(Clip::generated(
+        "Title",
+        Generator::Text(title),
+        RationalTime::new(4, 1),
+    ));
+
+    let mut project = build_project();
+    project
+        .active_sequence_mut()
+        .unwrap()
+        .video_tracks
+        .push(track);
+    let json = ProjectFile::new(project).to_json().unwrap();
+    let loaded = ProjectFile::from_json(&json).unwrap();
+    let mut track = loaded.project.active_sequence().unwrap().video_tracks[1].clone();
+
+    track.overwrite(RationalTime::new(1, 1), clip("Insert", 0));
+    let (TrackItem::Clip(head), TrackItem::Clip(tail)) = (&track.items[0], &track.items[2]) else {
+        panic!("title was not split: {:?}", track.items);
+    };
+    let Some(Generator::Text(text)) = &tail.generator else {
+        panic!("tail lost its generator");
+    };
+    assert!(head.is_generated());
+
+    let mut fonts = FontLibrary::new();
+    fonts
+        .load_file(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../proedit-text/tests/fonts/DejaVuSans.ttf"
+        ))
+        .unwrap();
+    let renderer = TextRenderer::new(fonts);
+    // Half a second into the tail is 1.5s into the title: 75% opaque.
+    let frame = renderer
+        .render(text, tail.source_in + RationalTime::new(1, 2), 640, 360)
+        .unwrap();
+    let alpha = frame
+        .read_rgba_f32()
+        .unwrap()
+        .iter()
+        .map(|p| p[3])
+        .fold(0.0, f32::max);
+    assert!((alpha - 0.75).abs() < 0.01, "peak alpha {alpha}");
+}
+
 // ── Export pipeline integration ────────────────────────────────
 
 #[test]
@@ -296,6 +401,74 @@ fn find_clip_by_uuid() {
     assert_eq!(found.name, "Target");
 }
 
+#[test]
+fn overwrite_splits_trims_and_extends() {
+    let mut track = Track::new_audio("A1");
+    track.append_clip(clip("Bed", 10));
+    track.append_clip(clip("Tail", 5));
+
+    // Inside one clip: split around the new clip.
+    let idx = track.overwrite(RationalTime::new(2, 1), clip("VO", 3));
+    assert_eq!(idx, 1);
+    assert_eq!(track.items.len(), 4);
+    assert_eq!(track.duration(), RationalTime::new(15, 1));
+    let right = track.clip_at(2).unwrap();
+    assert_eq!(right.name, "Bed");
+    assert_eq!(right.source_in, RationalTime::new(5, 1));
+    assert_eq!(right.duration, RationalTime::new(5, 1));
+
+    // Across a boundary: trim both neighbours.
+    let idx = track.overwrite(RationalTime::new(8, 1), clip("VO 2", 4));
+    assert_eq!(track.item_start_time(idx), RationalTime::new(8, 1));
+    assert_eq!(
+        track.clip_at(idx + 1).unwrap().duration,
+        RationalTime::new(3, 1)
+    );
+    assert_eq!(track.duration(), RationalTime::new(15, 1));
+
+    // Past the end: a gap fills the space before it.
+    let idx = track.overwrite(RationalTime::new(20, 1), clip("VO 3", 1));
+    assert!(matches!(track.items[idx - 1], TrackItem::Gap { .. }));
+    assert_eq!(track.duration(), RationalTime::new(21, 1));
+}
+
+#[test]
+fn overwrite_into_speed_changed_clip_keeps_media_in_place() {
+    let mut track = Track::new_video("V1");
+    let mut fast = clip("Fast", 20);
+    fast.speed = 2.0;
+    fast.duration = RationalTime::new(10, 1);
+    track.append_clip(fast);
+
+    // 4 s of timeline at 2x is 8 s of source, plus the 2 s overwritten.
+    track.overwrite(RationalTime::new(2, 1), clip("Insert", 2));
+    let tail = track.clip_at(2).unwrap();
+    assert_eq!(tail.source_in, RationalTime::new(8, 1));
+    assert_eq!(tail.duration, RationalTime::new(6, 1));
+    assert_eq!(tail.played_source_range().end(), RationalTime::new(20, 1));
+}
+
+#[test]
+fn takes_stack_and_switch() {
+    let mut c = clip("VO", 5);
+    c.source_in = RationalTime::new(1, 1);
+    c.push_take(
+        ClipRef::new("vo2.wav", RationalTime::new(5, 1)),
+        RationalTime::ZERO,
+    );
+    assert_eq!(c.source.path, "vo2.wav");
+    assert_eq!(c.source_in, RationalTime::ZERO);
+    assert_eq!(c.takes.len(), 1);
+
+    // Each take keeps its own in point.
+    assert!(c.select_take(0));
+    assert_eq!(c.source.path, "media/test.mp4");
+    assert_eq!(c.source_in, RationalTime::new(1, 1));
+    assert_eq!(c.takes[0].source.path, "vo2.wav");
+    assert_eq!(c.takes[0].source_in, RationalTime::ZERO);
+    assert!(!c.select_take(3));
+}
+
 // ── Keyframe + timeline timing ─────────────────────────────────
 
 #[test]
@@ -0,0 +1,243 @@
+//! Font loading and selection.
+//!
+//! Fonts come from files, directories or the system's font directories.
+//! A `FontSpec` resolves to the closest face available, and characters
+//! that face can't show fall back to the first face that can.
+
+use fontdb::{Database, Family, Query, Source, Style, Weight};
+use proedit_core::{ProEditError, Result};
+use proedit_timeline::FontSpec;
+use rustybuzz::ttf_parser;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+pub use fontdb::ID as FontId;
+
+/// The bytes of a loaded face.
+#[derive(Clone)]
+pub(crate) struct FaceData {
+    pub bytes: Arc<Vec<u8>>,
+    pub index: u32,
+}
+
+impl FaceData {
+    /// Parse the face for shaping and outlines.
+    pub fn face(&self) -> Option<rustybuzz::Face<'_>> {
+        rustybuzz::Face::from_slice(&self.bytes, self.index)
+    }
+}
+
+/// A set of font faces to lay text out with.
+#[derive(Default)]
+pub struct FontLibrary {
+    db: Database,
+    /// Face bytes read so far.
+    data: Mutex<HashMap<FontId, FaceData>>,
+    /// Fallback face found for each character looked up.
+    fallback: Mutex<HashMap<char, Option<FontId>>>,
+}
+
+impl FontLibrary {
+    /// An empty library.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A library with the system's fonts loaded.
+    pub fn with_system_fonts() -> Self {
+        let mut library = Self::new();
+        library.load_system_fonts();
+        library
+    }
+
+    /// Number of faces loaded.
+    pub fn len(&self) -> usize {
+        self.db.len()
+    }
+
+    /// Whether no faces are loaded.
+    pub fn is_empty(&self) -> bool {
+        self.db.is_empty()
+    }
+
+    /// Load every face in a font file (`ttf`, `otf`, `ttc` or `otc`).
+    /// Returns the number of faces added.
+    pub fn load_file(&mut self, path: impl AsRef<Path>) -> Result<usize> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path)?;
+        let added = self.load_data(bytes)?;
+        Ok(added)
+    }
+
+    /// Load every face in in-memory font data. Returns the number of faces
+    /// added.
+    pub fn load_data(&mut self, bytes: Vec<u8>) -> Result<usize> {
+        let ids = self.db.load_font_source(Source::Binary(Arc::new(bytes)));
+        if ids.is_empty() {
+            return Err(ProEditError::UnsupportedFormat(
+                "Not a font file, or it has no usable faces".into(),
+            ));
+        }
+        self.fallback_changed();
+        Ok(ids.len())
+    }
+
+    /// Load the fonts in a directory and its subdirectories. Returns the
+    /// number of faces added.
+    pub fn load_dir(&mut self, path: impl AsRef<Path>) -> usize {
+        let before = self.db.len();
+        self.db.load_fonts_dir(path);
+        self.fallback_changed();
+        self.db.len() - before
+    }
+
+    /// Load the fonts installed on the system. Returns the number of faces
+    /// added.
+    pub fn load_system_fonts(&mut self) -> usize {
+        let before = self.db.len();
+        self.db.load_system_fonts();
+        self.fallback_changed();
+        self.db.len() - before
+    }
+
+    /// Family names of the loaded faces, sorted and without duplicates.
+    pub fn families(&self) -> Vec<String> {
+        let mut families: Vec<String> = self
+            .db
+            .faces()
+            .filter_map(|face| face.families.first().map(|(name, _)| name.clone()))
+            .collect();
+        families.sort();
+        families.dedup();
+        families
+    }
+
+    /// The face that best matches `spec`: the requested family if loaded,
+    /// else the default sans-serif, else the loaded face closest in weight
+    /// and style.
+    pub fn resolve(&self, spec: &FontSpec) -> Result<FontId> {
+        let style = if spec.italic {
+            Style::Italic
+        } else {
+            Style::Normal
+        };
+        let query = |families: &[Family]| {
+            self.db.query(&Query {
+                families,
+                weight: Weight(spec.weight),
+                style,
+                ..Query::default()
+            })
+        };
+        let requested = (!spec.family.is_empty())
+            .then(|| query(&[Family::Name(&spec.family)]))
+            .flatten();
+        requested
+            .or_else(|| query(&[Family::SansSerif]))
+            .or_else(|| {
+                self.db
+                    .faces()
+                    .min_by_key(|face| {
+                        (
+                            (face.style != style) as u16,
+                            face.weight.0.abs_diff(spec.weight),
+                        )
+                    })
+                    .map(|face| face.id)
+            })
+            .ok_or_else(|| ProEditError::NotFound("No fonts are loaded".into()))
+    }
+
+    /// A face other than `primary` that has a glyph for `c`, if any.
+    pub fn fallback_for(&self, c: char, primary: FontId) -> Option<FontId> {
+        let mut cache = self.fallback.lock().unwrap_or_else(|e| e.into_inner());
+        *cache.entry(c).or_insert_with(|| {
+            self.db
+                .faces()
+                .filter(|face| face.id != primary)
+                .find(|face| {
+                    self.face_data(face.id)
+                        .and_then(|data| {
+                            ttf_parser::Face::parse(&data.bytes, data.index)
+                                .ok()
+                                .and_then(|f| f.glyph_index(c))
+                        })
+                        .is_some()
+                })
+                .map(|face| face.id)
+        })
+    }
+
+    /// The bytes of face `id`, read on first use.
+    pub(crate) fn face_data(&self, id: FontId) -> Option<FaceData> {
+        let mut data = self.data.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(face) = data.get(&id) {
+            return Some(face.clone());
+        }
+        let face = self.db.with_face_data(id, |bytes, index| FaceData {
+            bytes: Arc::new(bytes.to_vec()),
+            index,
+        })?;
+        data.insert(id, face.clone());
+        Some(face)
+    }
+
+    fn fallback_changed(&mut self) {
+        self.fallback
+            .get_mut()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// A library holding only the bundled test font.
+    pub(crate) fn test_library() -> FontLibrary {
+        let mut library = FontLibrary::new();
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fonts/DejaVuSans.ttf");
+        library.load_file(path).unwrap();
+        library
+    }
+
+    #[test]
+    fn test_load_file() {
+        let library = test_library();
+        assert_eq!(library.len(), 1);
+        assert_eq!(library.families(), ["DejaVu Sans"]);
+        assert!(FontLibrary::new().load_file("/no/such/font.ttf").is_err());
+        assert!(FontLibrary::new().load_data(vec![0; 64]).is_err());
+    }
+
+    #[test]
+    fn test_resolve_falls_back_to_closest_face() {
+        let library = test_library();
+        let named = library
+            .resolve(&FontSpec {
+                family: "DejaVu Sans".into(),
+                ..FontSpec::default()
+            })
+            .unwrap();
+        let missing = library
+            .resolve(&FontSpec {
+                family: "No Such Family".into(),
+                weight: 700,
+                italic: true,
+            })
+            .unwrap();
+        assert_eq!(named, missing);
+        assert!(FontLibrary::new().resolve(&FontSpec::default()).is_err());
+    }
+
+    #[test]
+    fn test_fallback_needs_a_covering_face() {
+        let library = test_library();
+        let primary = library.resolve(&FontSpec::default()).unwrap();
+        // The only face is the primary, and it has no CJK glyphs anyway.
+        assert_eq!(library.fallback_for('語', primary), None);
+        assert_eq!(library.fallback_for('A', primary), None);
+    }
+}
@@ -0,0 +1,503 @@
+//! Text shaping and line layout.
+//!
+//! Each line is split into bidi runs in visual order, each run into
+//! segments by the font that covers them, and each segment is shaped with
+//! rustybuzz. Lines wrap greedily at Unicode line break opportunities, so
+//! CJK text wraps between ideographs and other scripts at spaces.
+
+use crate::font::{FontId, FontLibrary};
+use proedit_core::{ProEditError, Result};
+use proedit_timeline::{FontSpec, TextAlign};
+use rustybuzz::{Direction, UnicodeBuffer};
+use std::ops::Range;
+use unicode_bidi::BidiInfo;
+use unicode_linebreak::{linebreaks, BreakOpportunity};
+
+/// How to lay text out.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LayoutOptions {
+    /// Font size in pixels (the em size)
+    pub size: f32,
+    /// Extra space between characters, in thousandths of an em
+    pub tracking: f32,
+    /// Distance between baselines, as a multiple of the size
+    pub leading: f32,
+    /// Line alignment within the block
+    pub align: TextAlign,
+    /// Wrap lines wider than this many pixels
+    pub max_width: Option<f32>,
+}
+
+impl LayoutOptions {
+    /// Unwrapped, centred text at `size` with default spacing.
+    pub fn new(size: f32) -> Self {
+        Self {
+            size,
+            tracking: 0.0,
+            leading: 1.2,
+            align: TextAlign::Center,
+            max_width: None,
+        }
+    }
+}
+
+/// A glyph placed in the text block.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PositionedGlyph {
+    /// Face the glyph comes from
+    pub font: FontId,
+    /// Glyph index in that face
+    pub glyph: u16,
+    /// Pen position in pixels from the block's left edge
+    pub x: f32,
+    /// Baseline position in pixels from the block's top edge
+    pub y: f32,
+    /// Pixels per font unit
+    pub scale: f32,
+}
+
+/// A laid-out line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayoutLine {
+    /// Byte range of the line in the source text, trailing spaces excluded
+    pub text: Range<usize>,
+    /// Range of the line's glyphs in [`TextLayout::glyphs`]
+    pub glyphs: Range<usize>,
+    /// Left edge in pixels from the block's left edge
+    pub x: f32,
+    /// Width in pixels
+    pub width: f32,
+    /// Baseline in pixels from the block's top edge
+    pub baseline: f32,
+}
+
+/// Shaped, positioned text.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TextLayout {
+    /// Glyphs in visual order, line by line
+    pub glyphs: Vec<PositionedGlyph>,
+    pub lines: Vec<LayoutLine>,
+    /// Width of the widest line
+    pub width: f32,
+    /// Height from the first line's ascent to the last line's descent
+    pub height: f32,
+}
+
+/// A glyph of a shaped line, before the line is placed.
+struct ShapedGlyph {
+    font: FontId,
+    glyph: u16,
+    x: f32,
+    y_offset: f32,
+    scale: f32,
+}
+
+/// A shaped line, left edge at zero.
+struct ShapedLine {
+    glyphs: Vec<ShapedGlyph>,
+    width: f32,
+}
+
+/// Shape and lay out `text` in `font`. Lines break at `\n` and, with
+/// `max_width` set, wherever the next word would overflow.
+pub fn layout(
+    fonts: &FontLibrary,
+    text: &str,
+    font: &FontSpec,
+    options: &LayoutOptions,
+) -> Result<TextLayout> {
+    let primary = fonts.resolve(font)?;
+    let (ascent, descent) = {
+        let data = fonts
+            .face_data(primary)
+            .ok_or_else(|| ProEditError::NotFound("Font data is unavailable".into()))?;
+        let face = data
+            .face()
+            .ok_or_else(|| ProEditError::UnsupportedFormat("Unreadable font face".into()))?;
+        let scale = options.size / face.units_per_em() as f32;
+        (
+            face.ascender() as f32 * scale,
+            -(face.descender() as f32) * scale,
+        )
+    };
+    let shaper = Shaper {
+        fonts,
+        primary,
+        size: options.size,
+        tracking: options.tracking * options.size / 1000.0,
+    };
+
+    let mut shaped = Vec::new();
+    let mut start = 0;
+    for raw in text.split('\n') {
+        let paragraph = raw.strip_suffix('\r').unwrap_or(raw);
+        for range in shaper.wrap(paragraph, options.max_width) {
+            let range = start + range.start..start + range.end;
+            let line = shaper.shape(&text[range.clone()]);
+            shaped.push((range, line));
+        }
+        start += raw.len() + 1;
+    }
+
+    let width = shaped
+        .iter()
+        .map(|(_, line)| line.width)
+        .fold(0.0, f32::max);
+    let line_height = options.size * options.leading;
+    let mut layout = TextLayout {
+        width,
+        height: ascent + descent + line_height * (shaped.len() - 1) as f32,
+        ..TextLayout::default()
+    };
+    for (i, (text, line)) in shaped.into_iter().enumerate() {
+        let x = match options.align {
+            TextAlign::Left => 0.0,
+            TextAlign::Center => (width - line.width) / 2.0,
+            TextAlign::Right => width - line.width,
+        };
+        let baseline = ascent + line_height * i as f32;
+        let first = layout.glyphs.len();
+        layout
+            .glyphs
+            .extend(line.glyphs.into_iter().map(|g| PositionedGlyph {
+                font: g.font,
+                glyph: g.glyph,
+                x: x + g.x,
+                y: baseline - g.y_offset,
+                scale: g.scale,
+            }));
+        layout.lines.push(LayoutLine {
+            text,
+            glyphs: first..layout.glyphs.len(),
+            x,
+            width: line.width,
+            baseline,
+        });
+    }
+    Ok(layout)
+}
+
+struct Shaper<'a> {
+    fonts: &'a FontLibrary,
+    primary: FontId,
+    size: f32,
+    /// Tracking in pixels
+    tracking: f32,
+}
+
+impl Shaper<'_> {
+    /// Split a paragraph into line ranges no wider than `max_width`, except
+    /// where a single unbreakable piece is wider.
+    fn wrap(&self, paragraph: &str, max_width: Option<f32>) -> Vec<Range<usize>> {
+        let trimmed =
+            |range: Range<usize>| range.start..range.start + paragraph[range].trim_end().len();
+        let Some(max_width) = max_width else {
+            return vec![trimmed(0..paragraph.len())];
+        };
+        let fits = |range: &Range<usize>| self.shape(&paragraph[range.clone()]).width <= max_width;
+
+        let mut lines = Vec::new();
+        let mut start = 0;
+        let mut fitted: Option<usize> = None;
+        for (end, opportunity) in linebreaks(paragraph) {
+            let mut line = trimmed(start..end);
+            if !fits(&line) {
+                if let Some(last) = fitted.take() {
+                    lines.push(trimmed(start..last));
+                    start = last;
+                    line = trimmed(start..end);
+                }
+            }
+            if fits(&line) {
+                fitted = Some(end);
+            } else {
+                // Wider than a line on its own: let it overflow.
+                lines.push(line);
+                start = end;
+            }
+            if opportunity == BreakOpportunity::Mandatory {
+                if let Some(last) = fitted.take() {
+                    lines.push(trimmed(start..last));
+                    start = last;
+                }
+            }
+        }
+        if lines.is_empty() {
+            lines.push(0..0);
+        }
+        lines
+    }
+
+    /// Shape one line into glyphs in visual order.
+    fn shape(&self, line: &str) -> ShapedLine {
+        let mut shaped = ShapedLine {
+            glyphs: Vec::new(),
+            width: 0.0,
+        };
+        if line.is_empty() {
+            return shaped;
+        }
+        let bidi = BidiInfo::new(line, None);
+        for paragraph in &bidi.paragraphs {
+            let (levels, runs) = bidi.visual_runs(paragraph, paragraph.range.clone());
+            for run in runs {
+                let rtl = levels[run.start].is_rtl();
+                let mut segments = self.font_segments(line, run);
+                if rtl {
+                    segments.reverse();
+                }
+                for (font, range) in segments {
+                    self.shape_segment(&mut shaped, font, &line[range], rtl);
+                }
+            }
+        }
+        // No tracking after the last character.
+        if !shaped.glyphs.is_empty() {
+            shaped.width = (shaped.width - self.tracking).max(0.0);
+        }
+        shaped
+    }
+
+    /// Split `range` of `line` into pieces that one face can show: the
+    /// primary face where it has the glyphs, a fallback face elsewhere.
+    fn font_segments(&self, line: &str, range: Range<usize>) -> Vec<(FontId, Range<usize>)> {
+        let primary = self.fonts.face_data(self.primary);
+        let primary = primary
+            .as_ref()
+            .and_then(|data| rustybuzz::ttf_parser::Face::parse(&data.bytes, data.index).ok());
+        let mut segments: Vec<(FontId, Range<usize>)> = Vec::new();
+        for (i, c) in line[range.clone()].char_indices() {
+            let i = range.start + i;
+            let covered = primary
+                .as_ref()
+                .is_some_and(|face| face.glyph_index(c).is_some());
+            let font = match segments.last() {
+                // Spaces and marks stay with the text around them.
+                Some((font, _)) if c.is_whitespace() || is_mark(c) => *font,
+                _ if covered => self.primary,
+                _ => self
+                    .fonts
+                    .fallback_for(c, self.primary)
+                    .unwrap_or(self.primary),
+            };
+            match segments.last_mut() {
+                Some((last, segment)) if *last == font => segment.end = i + c.len_utf8(),
+                _ => segments.push((font, i..i + c.len_utf8())),
+            }
+        }
+        segments
+    }
+
+    fn shape_segment(&self, shaped: &mut ShapedLine, font: FontId, text: &str, rtl: bool) {
+        let Some(data) = self.fonts.face_data(font) else {
+            return;
+        };
+        let Some(face) = data.face() else {
+            return;
+        };
+        let scale = self.size / face.units_per_em() as f32;
+        let mut buffer = UnicodeBuffer::new();
+        buffer.push_str(text);
+        buffer.set_direction(if rtl {
+            Direction::RightToLeft
+        } else {
+            Direction::LeftToRight
+        });
+        buffer.guess_segment_properties();
+        let output = rustybuzz::shape(&face, &[], buffer);
+        let infos = output.glyph_infos();
+        for (i, (info, position)) in infos.iter().zip(output.glyph_positions()).enumerate() {
+            shaped.glyphs.push(ShapedGlyph {
+                font,
+                glyph: info.glyph_id as u16,
+                x: shaped.width + position.x_offset as f32 * scale,
+                y_offset: position.y_offset as f32 * scale,
+                scale,
+            });
+            shaped.width += position.x_advance as f32 * scale;
+            // Track between clusters, not between a letter and its marks.
+            let cluster_ends = infos
+                .get(i + 1)
+                .map_or(true, |next| next.cluster != info.cluster);
+            if cluster_ends {
+                shaped.width += self.tracking;
+            }
+        }
+    }
+}
+
+/// Whether `c` is a combining mark, which must be shaped with its base.
+fn is_mark(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x0300..=0x036F
+            | 0x0591..=0x05C7
+            | 0x0610..=0x061A
+            | 0x064B..=0x065F
+            | 0x0670
+            | 0x06D6..=0x06ED
+            | 0x1AB0..=0x1AFF
+            | 0x1DC0..=0x1DFF
+            | 0x200C..=0x200D
+            | 0x20D0..=0x20FF
+            | 0xFE00..=0xFE0F
+            | 0xFE20..=0xFE2F
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::font::tests::test_library;
+    use rustybuzz::ttf_parser;
+
+    fn lay_out(text: &str, options: LayoutOptions) -> TextLayout {
+        layout(&test_library(), text, &FontSpec::default(), &options).unwrap()
+    }
+
+    /// Glyph ids of `text` looked up one character at a time, without
+    /// shaping.
+    fn cmap_glyphs(text: &str) -> Vec<u16> {
+        let bytes = std::fs::read(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fonts/DejaVuSans.ttf"
+        ))
+        .unwrap();
+        let face = ttf_parser::Face::parse(&bytes, 0).unwrap();
+        text.chars()
+            .map(|c| face.glyph_index(c).unwrap().0)
+            .collect()
+    }
+
+    fn glyph_ids(layout: &TextLayout) -> Vec<u16> {
+        layout.glyphs.iter().map(|g| g.glyph).collect()
+    }
+
+    #[test]
+    fn test_tracking_widens_lines() {
+        let plain = lay_out("Hello", LayoutOptions::new(40.0));
+        let tracked = lay_out(
+            "Hello",
+            LayoutOptions {
+                tracking: 100.0,
+                ..LayoutOptions::new(40.0)
+            },
+        );
+        // Four gaps of a tenth of an em each.
+        assert!((tracked.width - plain.width - 16.0).abs() < 1e-3);
+        assert_eq!(glyph_ids(&plain), cmap_glyphs("Hello"));
+        assert!(tracked.glyphs[4].x - plain.glyphs[4].x > 15.9);
+    }
+
+    #[test]
+    fn test_lines_align_and_space_by_leading() {
+        for (align, expected) in [
+            (TextAlign::Left, 0.0),
+            (TextAlign::Center, 0.5),
+            (TextAlign::Right, 1.0),
+        ] {
+            let layout = lay_out(
+                "A much wider line\nshort",
+                LayoutOptions {
+                    align,
+                    leading: 1.5,
+                    ..LayoutOptions::new(20.0)
+                },
+            );
+            assert_eq!(layout.lines.len(), 2);
+            let [first, second] = &layout.lines[..] else {
+                unreachable!()
+            };
+            assert_eq!(first.x, 0.0);
+            assert_eq!(first.width, layout.width);
+            let slack = layout.width - second.width;
+            assert!((second.x - slack * expected).abs() < 1e-3);
+            assert!((second.baseline - first.baseline - 30.0).abs() < 1e-3);
+            assert_eq!(layout.glyphs[second.glyphs.start].x, second.x);
+        }
+    }
+
+    #[test]
+    fn test_wrap_breaks_between_words() {
+        let text = "The quick brown fox jumps over the lazy dog";
+        let layout = lay_out(
+            text,
+            LayoutOptions {
+                max_width: Some(120.0),
+                ..LayoutOptions::new(20.0)
+            },
+        );
+        assert!(layout.lines.len() > 2);
+        assert!(layout.width <= 120.0);
+        let words: Vec<&str> = layout
+            .lines
+            .iter()
+            .flat_map(|line| text[line.text.clone()].split(' '))
+            .collect();
+        assert_eq!(words.join(" "), text);
+        for line in &layout.lines {
+            assert!(!text[line.text.clone()].ends_with(' '));
+        }
+    }
+
+    #[test]
+    fn test_cjk_wraps_between_ideographs() {
+        // The test font has no CJK glyphs, but every character still takes
+        // up a box and may break anywhere.
+        let text = "日本語のテキストを折り返す";
+        let layout = lay_out(
+            text,
+            LayoutOptions {
+                max_width: Some(80.0),
+                ..LayoutOptions::new(20.0)
+            },
+        );
+        assert!(layout.lines.len() > 1);
+        let rejoined: String = layout
+            .lines
+            .iter()
+            .map(|line| &text[line.text.clone()])
+            .collect();
+        assert_eq!(rejoined, text);
+    }
+
+    #[test]
+    fn test_hebrew_runs_right_to_left() {
+        let layout = lay_out("שלום", LayoutOptions::new(20.0));
+        let reversed: String = "שלום".chars().rev().collect();
+        assert_eq!(glyph_ids(&layout), cmap_glyphs(&reversed));
+        assert!(layout.glyphs.windows(2).all(|w| w[0].x < w[1].x));
+    }
+
+    #[test]
+    fn test_arabic_letters_join() {
+        // Final, medial and initial forms replace the isolated letters.
+        let text = "بيت";
+        let layout = lay_out(text, LayoutOptions::new(20.0));
+        let isolated = cmap_glyphs(&text.chars().rev().collect::<String>());
+        let ids = glyph_ids(&layout);
+        assert_eq!(ids.len(), 3);
+        assert!(ids.iter().zip(&isolated).all(|(a, b)| a != b));
+
+        // Lam and alef combine into one ligature.
+        let layout = lay_out("سلام", LayoutOptions::new(20.0));
+        assert_eq!(layout.glyphs.len(), 3);
+    }
+
+    #[test]
+    fn test_mixed_direction_text_orders_runs() {
+        let layout = lay_out("abc שלום def", LayoutOptions::new(20.0));
+        let ids = glyph_ids(&layout);
+        let expected: Vec<u16> = [
+            cmap_glyphs("abc "),
+            cmap_glyphs(&"שלום".chars().rev().collect::<String>()),
+            cmap_glyphs(" def"),
+        ]
+        .concat();
+        assert_eq!(ids, expected);
+
+        // Right-to-left paragraphs put the first run on the right.
+        let layout = lay_out("שלום abc", LayoutOptions::new(20.0));
+        assert_eq!(&glyph_ids(&layout)[..3], &cmap_glyphs("abc")[..]);
+    }
+}
@@ -0,0 +1,17 @@
+//! ProEdit Text - Titles and text layers
+//!
+//! Renders `TextGenerator` clips into frames:
+//! - `FontLibrary`: Fonts from files, directories and the system, with
+//!   closest-match selection and per-character fallback
+//! - `layout`: Bidi-aware shaping (rustybuzz), line wrapping, alignment,
+//!   tracking and leading
+//! - `TextRenderer`: Anti-aliased fill, stroke and drop shadow into a
+//!   straight-alpha sRGB `FrameBuffer`
+
+pub mod font;
+pub mod layout;
+pub mod render;
+
+pub use font::{FontId, FontLibrary};
+pub use layout::{layout, LayoutLine, LayoutOptions, PositionedGlyph, TextLayout};
+pub use render::{pixmap_to_frame, TextRenderer};
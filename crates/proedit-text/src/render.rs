@@ -0,0 +1,415 @@
+//! Rasterizing text layers.
+//!
+//! Glyph outlines are filled with tiny-skia, anti-aliased, in sRGB. The
+//! shadow is drawn first (fill and stroke in the shadow colour, blurred),
+//! then the stroke, then the fill over the stroke's inner half.
+
+use crate::font::FontLibrary;
+use crate::layout::{layout, LayoutOptions, TextLayout};
+use proedit_core::{FrameBuffer, PixelFormat, ProEditError, RationalTime, Result};
+use proedit_timeline::{TextAlign, TextGenerator};
+use rustybuzz::ttf_parser::{self, GlyphId, OutlineBuilder};
+use tiny_skia::{
+    Color, FillRule, LineJoin, Paint, Path, PathBuilder, Pixmap, PixmapPaint, Stroke, Transform,
+};
+
+/// Lengths in a `TextGenerator` are in pixels of a frame this tall.
+const REFERENCE_HEIGHT: f32 = 1080.0;
+
+/// Renders text layers with a set of fonts.
+pub struct TextRenderer {
+    fonts: FontLibrary,
+}
+
+impl TextRenderer {
+    pub fn new(fonts: FontLibrary) -> Self {
+        Self { fonts }
+    }
+
+    pub fn fonts(&self) -> &FontLibrary {
+        &self.fonts
+    }
+
+    pub fn fonts_mut(&mut self) -> &mut FontLibrary {
+        &mut self.fonts
+    }
+
+    /// Render `text` at source time `time` into a transparent
+    /// `width`×`height` Rgba8 frame (sRGB, straight alpha).
+    pub fn render(
+        &self,
+        text: &TextGenerator,
+        time: RationalTime,
+        width: u32,
+        height: u32,
+    ) -> Result<FrameBuffer> {
+        let pixmap = self.render_pixmap(text, time, width, height)?;
+        Ok(pixmap_to_frame(&pixmap))
+    }
+
+    /// Render into a premultiplied pixmap, e.g. to draw over other
+    /// graphics before converting with [`pixmap_to_frame`].
+    pub fn render_pixmap(
+        &self,
+        text: &TextGenerator,
+        time: RationalTime,
+        width: u32,
+        height: u32,
+    ) -> Result<Pixmap> {
+        let mut pixmap = Pixmap::new(width, height).ok_or_else(|| {
+            ProEditError::InvalidParameter(format!("Invalid text frame size {width}x{height}"))
+        })?;
+        let frame = text.at(time);
+        let scale = height as f32 / REFERENCE_HEIGHT;
+        if text.text.trim().is_empty() || frame.size <= 0.0 || frame.opacity <= 0.0 {
+            return Ok(pixmap);
+        }
+
+        let options = LayoutOptions {
+            size: frame.size * scale,
+            tracking: frame.tracking,
+            leading: frame.leading,
+            align: text.align,
+            max_width: text.wrap_width.map(|w| w * width as f32),
+        };
+        let laid_out = layout(&self.fonts, &text.text, &text.font, &options)?;
+        let Some(path) = self.outlines(&laid_out) else {
+            return Ok(pixmap);
+        };
+
+        // Anchor at the requested position, rotate about it, then place the
+        // block relative to the anchor.
+        let block_x = match text.align {
+            TextAlign::Left => 0.0,
+            TextAlign::Center => -laid_out.width / 2.0,
+            TextAlign::Right => -laid_out.width,
+        };
+        let transform = Transform::from_translate(
+            frame.position[0] * width as f32,
+            frame.position[1] * height as f32,
+        )
+        .pre_rotate(frame.rotation)
+        .pre_translate(block_x, -laid_out.height / 2.0);
+
+        let stroke = text.stroke.filter(|s| s.width > 0.0).map(|s| {
+            let stroke = Stroke {
+                // Centred on the outline; the fill covers the inner half.
+                width: 2.0 * s.width * scale,
+                line_join: LineJoin::Round,
+                ..Stroke::default()
+            };
+            (stroke, s.color)
+        });
+
+        if let Some(shadow) = &text.shadow {
+            let mut layer = Pixmap::new(width, height).expect("size checked above");
+            let offset =
+                transform.post_translate(shadow.offset[0] * scale, shadow.offset[1] * scale);
+            let paint = paint(shadow.color);
+            layer.fill_path(&path, &paint, FillRule::Winding, offset, None);
+            if let Some((stroke, _)) = &stroke {
+                layer.stroke_path(&path, &paint, stroke, offset, None);
+            }
+            box_blur(&mut layer, shadow.blur * scale);
+            pixmap.draw_pixmap(
+                0,
+                0,
+                layer.as_ref(),
+                &PixmapPaint::default(),
+                Transform::identity(),
+                None,
+            );
+        }
+        if let Some((stroke, color)) = &stroke {
+            pixmap.stroke_path(&path, &paint(*color), stroke, transform, None);
+        }
+        pixmap.fill_path(&path, &paint(text.fill), FillRule::Winding, transform, None);
+
+        if frame.opacity < 1.0 {
+            for value in pixmap.data_mut() {
+                *value = (*value as f32 * frame.opacity).round() as u8;
+            }
+        }
+        Ok(pixmap)
+    }
+
+    /// All glyph outlines of a layout as one path, in block pixels.
+    fn outlines(&self, layout: &TextLayout) -> Option<Path> {
+        let mut builder = PathBuilder::new();
+        let mut fonts: Vec<_> = layout.glyphs.iter().map(|g| g.font).collect();
+        fonts.sort();
+        fonts.dedup();
+        for font in fonts {
+            let Some(data) = self.fonts.face_data(font) else {
+                continue;
+            };
+            let Ok(face) = ttf_parser::Face::parse(&data.bytes, data.index) else {
+                continue;
+            };
+            for glyph in layout.glyphs.iter().filter(|g| g.font == font) {
+                let mut outline = GlyphOutline {
+                    builder: &mut builder,
+                    x: glyph.x,
+                    y: glyph.y,
+                    scale: glyph.scale,
+                };
+                face.outline_glyph(GlyphId(glyph.glyph), &mut outline);
+            }
+        }
+        builder.finish()
+    }
+}
+
+/// Convert a premultiplied pixmap to an Rgba8 frame with straight alpha.
+pub fn pixmap_to_frame(pixmap: &Pixmap) -> FrameBuffer {
+    let (width, height) = (pixmap.width(), pixmap.height());
+    let mut frame = FrameBuffer::new(width, height, PixelFormat::Rgba8);
+    let plane = frame.primary_plane_mut();
+    for (y, row) in pixmap.pixels().chunks(width as usize).enumerate() {
+        let dst = plane.row_mut(y as u32);
+        for (x, pixel) in row.iter().enumerate() {
+            let c = pixel.demultiply();
+            dst[x * 4..x * 4 + 4].copy_from_slice(&[c.red(), c.green(), c.blue(), c.alpha()]);
+        }
+    }
+    frame
+}
+
+/// Feeds a glyph outline in font units (y up) into a path in pixels
+/// (y down) at a pen position.
+struct GlyphOutline<'a> {
+    builder: &'a mut PathBuilder,
+    x: f32,
+    y: f32,
+    scale: f32,
+}
+
+impl GlyphOutline<'_> {
+    fn point(&self, x: f32, y: f32) -> (f32, f32) {
+        (self.x + x * self.scale, self.y - y * self.scale)
+    }
+}
+
+impl OutlineBuilder for GlyphOutline<'_> {
+    fn move_to(&mut self, x: f32, y: f32) {
+        let (x, y) = self.point(x, y);
+        self.builder.move_to(x, y);
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        let (x, y) = self.point(x, y);
+        self.builder.line_to(x, y);
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        let (x1, y1) = self.point(x1, y1);
+        let (x, y) = self.point(x, y);
+        self.builder.quad_to(x1, y1, x, y);
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        let (x1, y1) = self.point(x1, y1);
+        let (x2, y2) = self.point(x2, y2);
+        let (x, y) = self.point(x, y);
+        self.builder.cubic_to(x1, y1, x2, y2, x, y);
+    }
+
+    fn close(&mut self) {
+        self.builder.close();
+    }
+}
+
+fn paint(color: [f32; 4]) -> Paint<'static> {
+    let [r, g, b, a] = color.map(|c| c.clamp(0.0, 1.0));
+    let mut paint = Paint::default();
+    paint.set_color(Color::from_rgba(r, g, b, a).unwrap_or(Color::BLACK));
+    paint.anti_alias = true;
+    paint
+}
+
+/// Approximate a Gaussian blur of about `radius` pixels with three box
+/// blurs, on premultiplied data.
+fn box_blur(pixmap: &mut Pixmap, radius: f32) {
+    let r = (radius / 3.0).round() as usize;
+    if r == 0 {
+        return;
+    }
+    let (w, h) = (pixmap.width() as usize, pixmap.height() as usize);
+    let data = pixmap.data_mut();
+    let mut line = Vec::new();
+    for _ in 0..3 {
+        for y in 0..h {
+            blur_line(data, y * w * 4, 4, w, r, &mut line);
+        }
+        for x in 0..w {
+            blur_line(data, x * 4, w * 4, h, r, &mut line);
+        }
+    }
+}
+
+/// Box-blur `len` pixels starting at byte `start`, `step` bytes apart,
+/// treating pixels outside as transparent.
+fn blur_line(
+    data: &mut [u8],
+    start: usize,
+    step: usize,
+    len: usize,
+    r: usize,
+    line: &mut Vec<[u8; 4]>,
+) {
+    line.clear();
+    line.extend((0..len).map(|i| {
+        let p = start + i * step;
+        [data[p], data[p + 1], data[p + 2], data[p + 3]]
+    }));
+    let window = (2 * r + 1) as u32;
+    let mut sum = [0u32; 4];
+    for pixel in line.iter().take(r) {
+        for c in 0..4 {
+            sum[c] += pixel[c] as u32;
+        }
+    }
+    for i in 0..len {
+        if let Some(entering) = line.get(i + r) {
+            for c in 0..4 {
+                sum[c] += entering[c] as u32;
+            }
+        }
+        let p = start + i * step;
+        for c in 0..4 {
+            data[p + c] = ((sum[c] + window / 2) / window) as u8;
+        }
+        if i >= r {
+            for c in 0..4 {
+                sum[c] -= line[i - r][c] as u32;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::font::tests::test_library;
+    use proedit_core::{EasingCurve, KeyframeTrack};
+    use proedit_test_support::golden_path;
+    use proedit_timeline::{TextShadow, TextStroke};
+
+    const WIDTH: u32 = 320;
+    const HEIGHT: u32 = 180;
+
+    fn renderer() -> TextRenderer {
+        TextRenderer::new(test_library())
+    }
+
+    /// Compare against `tests/golden/<name>.png` (see
+    /// `proedit_test_support::assert_golden`). Channels may differ by 3 to
+    /// allow for rasterizer rounding.
+    fn assert_golden(name: &str, pixmap: &Pixmap) {
+        let path = golden_path(env!("CARGO_MANIFEST_DIR"), name);
+        proedit_test_support::assert_golden(
+            &path,
+            pixmap.width(),
+            pixmap.height(),
+            pixmap.data(),
+            3,
+        );
+    }
+
+    fn title(text: &str) -> TextGenerator {
+        let mut title = TextGenerator::new(text, 160.0);
+        // Dark, so the goldens are legible in an image viewer.
+        title.fill = [0.1, 0.2, 0.5, 1.0];
+        title.position_y = KeyframeTrack::constant("position_y", 0.5);
+        title
+    }
+
+    #[test]
+    fn test_golden_two_line_title() {
+        let pixmap = renderer()
+            .render_pixmap(&title("Hello,\nworld"), RationalTime::ZERO, WIDTH, HEIGHT)
+            .unwrap();
+        assert_golden("two_line_title", &pixmap);
+    }
+
+    #[test]
+    fn test_golden_stroke_shadow_tracking() {
+        let mut styled = title("Styled");
+        styled.align = TextAlign::Left;
+        styled.position_x = KeyframeTrack::constant("position_x", 0.1);
+        styled.tracking = KeyframeTrack::constant("tracking", 150.0);
+        styled.fill = [1.0, 0.8, 0.2, 1.0];
+        styled.stroke = Some(TextStroke {
+            color: [0.1, 0.1, 0.4, 1.0],
+            width: 8.0,
+        });
+        styled.shadow = Some(TextShadow {
+            color: [0.0, 0.0, 0.0, 0.7],
+            offset: [12.0, 12.0],
+            blur: 18.0,
+        });
+        let pixmap = renderer()
+            .render_pixmap(&styled, RationalTime::ZERO, WIDTH, HEIGHT)
+            .unwrap();
+        assert_golden("stroke_shadow_tracking", &pixmap);
+    }
+
+    #[test]
+    fn test_golden_right_to_left() {
+        let pixmap = renderer()
+            .render_pixmap(&title("שלום سلام"), RationalTime::ZERO, WIDTH, HEIGHT)
+            .unwrap();
+        assert_golden("right_to_left", &pixmap);
+    }
+
+    #[test]
+    fn test_golden_animated_rotation_and_opacity() {
+        let mut spinning = title("Spin");
+        spinning.rotation = KeyframeTrack::new("rotation");
+        spinning
+            .rotation
+            .set(RationalTime::ZERO, 0.0, EasingCurve::Linear);
+        spinning
+            .rotation
+            .set(RationalTime::new(1, 1), 90.0, EasingCurve::Linear);
+        spinning.opacity = KeyframeTrack::new("opacity");
+        spinning
+            .opacity
+            .set(RationalTime::ZERO, 1.0, EasingCurve::Linear);
+        spinning
+            .opacity
+            .set(RationalTime::new(1, 1), 0.0, EasingCurve::Linear);
+        let pixmap = renderer()
+            .render_pixmap(&spinning, RationalTime::new(1, 3), WIDTH, HEIGHT)
+            .unwrap();
+        assert_golden("rotation_and_opacity", &pixmap);
+
+        let faded = renderer()
+            .render_pixmap(&spinning, RationalTime::new(1, 1), WIDTH, HEIGHT)
+            .unwrap();
+        assert!(faded.data().iter().all(|&v| v == 0));
+    }
+
+    #[test]
+    fn test_render_into_straight_alpha_frame() {
+        let renderer = renderer();
+        let mut title = title("I");
+        title.fill = [1.0; 4];
+        let frame = renderer
+            .render(&title, RationalTime::ZERO, WIDTH, HEIGHT)
+            .unwrap();
+        assert_eq!(frame.format, PixelFormat::Rgba8);
+        let pixels = frame.read_rgba_f32().unwrap();
+        // Solid white in the middle of the stem, transparent in the corner.
+        let centre = pixels[(HEIGHT / 2 * WIDTH + WIDTH / 2) as usize];
+        assert_eq!(centre, [1.0; 4]);
+        assert_eq!(pixels[0], [0.0; 4]);
+        // Partly covered edge pixels keep their colour.
+        assert!(pixels
+            .iter()
+            .filter(|p| p[3] > 0.0 && p[3] < 1.0)
+            .all(|p| p[0] == 1.0));
+
+        assert!(renderer.render(&title, RationalTime::ZERO, 0, 10).is_err());
+    }
+}
@@ -1,6 +1,7 @@
 //! Clip types for the timeline.
 
-use proedit_core::{RationalTime, TimeRange};
+use crate::generator::Generator;
+use proedit_core::{KeyframeTrack, RationalTime, TimeRange};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
@@ -23,6 +24,166 @@ impl ClipRef {
     }
 }
 
+/// An alternate take of a clip: its media and the in point it plays from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlternateTake {
+    /// Take media
+    pub source: ClipRef,
+    /// Source in point
+    pub source_in: RationalTime,
+}
+
+/// Shape of a fade or crossfade.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum FadeCurve {
+    /// Gain changes linearly (constant gain crossfade).
+    Linear,
+    /// Quarter sine; keeps power constant across a crossfade.
+    #[default]
+    EqualPower,
+    /// Raised cosine; eases in and out.
+    SCurve,
+}
+
+impl FadeCurve {
+    /// Fade-in gain at normalized position `t` (0 = silent, 1 = full).
+    /// A fade-out uses `gain(1 - t)`.
+    pub fn gain(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Self::Linear => t,
+            Self::EqualPower => (t * std::f32::consts::FRAC_PI_2).sin(),
+            Self::SCurve => 0.5 - 0.5 * (t * std::f32::consts::PI).cos(),
+        }
+    }
+
+    /// Crossfade curve for an audio transition name.
+    pub fn for_transition(name: &str) -> Self {
+        if name.eq_ignore_ascii_case("Constant Gain") {
+            Self::Linear
+        } else {
+            Self::EqualPower
+        }
+    }
+}
+
+/// A fade at the start or end of a clip.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Fade {
+    /// Fade length (zero = no fade)
+    pub duration: RationalTime,
+    /// Fade shape
+    pub curve: FadeCurve,
+}
+
+impl Default for Fade {
+    fn default() -> Self {
+        Self {
+            duration: RationalTime::ZERO,
+            curve: FadeCurve::default(),
+        }
+    }
+}
+
+impl Fade {
+    /// Create a fade.
+    pub fn new(duration: RationalTime, curve: FadeCurve) -> Self {
+        Self { duration, curve }
+    }
+
+    /// Whether this fade has any length.
+    pub fn is_active(&self) -> bool {
+        self.duration > RationalTime::ZERO
+    }
+}
+
+/// How a clip's speed and pitch shift are applied to its audio.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum StretchMode {
+    /// Resample like tape: speed changes tempo and pitch together.
+    #[default]
+    Varispeed,
+    /// Time-stretch to the clip speed, keeping the original pitch.
+    PreservePitch,
+    /// Time-stretch to the clip speed, changing the pitch only by
+    /// `pitch_semitones`.
+    PitchOnly,
+}
+
+impl StretchMode {
+    /// All modes, in display order.
+    pub const ALL: [StretchMode; 3] = [
+        StretchMode::Varispeed,
+        StretchMode::PreservePitch,
+        StretchMode::PitchOnly,
+    ];
+
+    /// Display name for UI.
+    pub fn display_name(self) -> &'static str {
+        match self {
+            Self::Varispeed => "Varispeed",
+            Self::PreservePitch => "Preserve Pitch",
+            Self::PitchOnly => "Pitch Only",
+        }
+    }
+
+    /// Tempo and pitch ratios for a clip at `speed` shifted by
+    /// `semitones`.
+    pub fn ratios(self, speed: f64, semitones: f32) -> (f64, f64) {
+        let shift = 2f64.powf(semitones as f64 / 12.0);
+        match self {
+            Self::Varispeed => (speed, speed),
+            Self::PreservePitch => (speed, 1.0),
+            Self::PitchOnly => (speed, shift),
+        }
+    }
+}
+
+/// Audio properties of a clip.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ClipAudio {
+    /// Static gain in dB
+    #[serde(default)]
+    pub gain_db: f32,
+    /// Fade at the clip start
+    #[serde(default)]
+    pub fade_in: Fade,
+    /// Fade at the clip end
+    #[serde(default)]
+    pub fade_out: Fade,
+    /// Volume automation in dB, keyed on time relative to the clip start
+    #[serde(default)]
+    pub volume: Option<KeyframeTrack>,
+    /// How speed and pitch shift are applied
+    #[serde(default)]
+    pub stretch: StretchMode,
+    /// Pitch shift in semitones (used in pitch-only mode)
+    #[serde(default)]
+    pub pitch_semitones: f32,
+}
+
+impl ClipAudio {
+    /// Audio for the right half of a clip split `offset` after its start:
+    /// keeps gain and fade-out, drops the fade-in and shifts automation.
+    pub fn split_off(&self, offset: RationalTime) -> Self {
+        let volume = self.volume.as_ref().map(|track| {
+            let mut shifted = KeyframeTrack::new(track.name.clone());
+            for kf in track.keyframes() {
+                shifted.set(kf.time - offset, kf.value, kf.easing);
+            }
+            shifted
+        });
+        Self {
+            gain_db: self.gain_db,
+            fade_in: Fade::default(),
+            fade_out: self.fade_out,
+            volume,
+            stretch: self.stretch,
+            pitch_semitones: self.pitch_semitones,
+        }
+    }
+}
+
 /// A clip on the timeline.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Clip {
@@ -40,6 +201,16 @@ pub struct Clip {
     pub speed: f64,
     /// Is clip enabled
     pub enabled: bool,
+    /// Gain, fades and volume automation (audio tracks only)
+    #[serde(default)]
+    pub audio: ClipAudio,
+    /// Alternate takes recorded over the same range (`source` is the
+    /// active one)
+    #[serde(default)]
+    pub takes: Vec<AlternateTake>,
+    /// Synthesized content shown instead of `source`'s media
+    #[serde(default)]
+    pub generator: Option<Generator>,
 }
 
 impl Clip {
@@ -54,9 +225,30 @@ impl Clip {
             duration,
             speed: 1.0,
             enabled: true,
+            audio: ClipAudio::default(),
+            takes: Vec::new(),
+            generator: None,
         }
     }
 
+    /// Create a clip of `duration` showing `generator`. It has no media, so
+    /// its source has no path and an unknown length.
+    pub fn generated(
+        name: impl Into<String>,
+        generator: Generator,
+        duration: RationalTime,
+    ) -> Self {
+        let mut clip = Self::new(name, ClipRef::new("", RationalTime::ZERO));
+        clip.duration = duration;
+        clip.generator = Some(generator);
+        clip
+    }
+
+    /// Whether the clip's picture is generated rather than decoded.
+    pub fn is_generated(&self) -> bool {
+        self.generator.is_some()
+    }
+
     /// Get the source time range.
     pub fn source_range(&self) -> TimeRange {
         TimeRange::new(self.source_in, self.duration)
@@ -67,9 +259,25 @@ impl Clip {
         self.source_in + self.duration
     }
 
-    /// Trim the clip's in point.
+    /// The range of source media the clip plays: its duration scaled by
+    /// speed.
+    pub fn played_source_range(&self) -> TimeRange {
+        TimeRange::new(self.source_in, self.source_offset(self.duration))
+    }
+
+    /// Source media played in `offset` of timeline time at the clip's
+    /// speed. An invalid speed counts as 1.
+    pub fn source_offset(&self, offset: RationalTime) -> RationalTime {
+        if self.speed == 1.0 || !self.speed.is_finite() || self.speed <= 0.0 {
+            offset
+        } else {
+            RationalTime::from_seconds_f64(offset.to_seconds_f64() * self.speed)
+        }
+    }
+
+    /// Trim the clip's in point by `delta` of timeline time.
     pub fn trim_in(&mut self, delta: RationalTime) {
-        self.source_in = self.source_in + delta;
+        self.source_in = self.source_in + self.source_offset(delta);
         self.duration = self.duration - delta;
     }
 
@@ -77,4 +285,27 @@ impl Clip {
     pub fn trim_out(&mut self, delta: RationalTime) {
         self.duration = self.duration + delta;
     }
+
+    /// Make `source`, played from `source_in`, the active take, keeping
+    /// the current one as an alternate.
+    pub fn push_take(&mut self, source: ClipRef, source_in: RationalTime) {
+        let previous = AlternateTake {
+            source: std::mem::replace(&mut self.source, source),
+            source_in: std::mem::replace(&mut self.source_in, source_in),
+        };
+        self.takes.push(previous);
+    }
+
+    /// Swap the active take with alternate `index`, each keeping its own in
+    /// point. Returns false if there is no such alternate.
+    pub fn select_take(&mut self, index: usize) -> bool {
+        match self.takes.get_mut(index) {
+            Some(take) => {
+                std::mem::swap(&mut self.source, &mut take.source);
+                std::mem::swap(&mut self.source_in, &mut take.source_in);
+                true
+            }
+            None => false,
+        }
+    }
 }
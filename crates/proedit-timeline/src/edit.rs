@@ -4,10 +4,12 @@
 //! how to apply itself and produce its inverse for undo.
 
 use proedit_core::RationalTime;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 
-use crate::clip::Clip;
-use crate::track::{Track, TrackKind};
+use crate::clip::{Clip, Fade};
+use crate::track::{Track, TrackItem, TrackKind};
 
 // ── Trim types ──────────────────────────────────────────────────
 
@@ -27,7 +29,7 @@ pub enum TrimMode {
 // ── Edit commands ───────────────────────────────────────────────
 
 /// A reversible edit operation on the timeline.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum EditCommand {
     /// Insert a clip at position `index` on a track.
     InsertClip {
@@ -81,6 +83,10 @@ pub enum EditCommand {
         track_id: Uuid,
         clip_index: usize,
         offset: RationalTime,
+        /// The clip before the split, stored for undo — populated when the
+        /// command is executed.
+        #[serde(default)]
+        original: Option<Box<Clip>>,
     },
     /// Toggle clip enabled state.
     ToggleClipEnabled { track_id: Uuid, clip_index: usize },
@@ -106,6 +112,28 @@ pub enum EditCommand {
         /// Original index in the track list.
         index: Option<usize>,
     },
+    /// Replace a clip's properties (name, audio, speed, ...).
+    UpdateClip {
+        track_id: Uuid,
+        clip_index: usize,
+        before: Box<Clip>,
+        after: Box<Clip>,
+    },
+    /// Replace a track's items wholesale (free placement: overwrite,
+    /// lift, drag to an arbitrary position).
+    ReplaceItems {
+        track_id: Uuid,
+        before: Vec<TrackItem>,
+        after: Vec<TrackItem>,
+    },
+    /// Replace the mixer inserts of an audio track (`None` = the master).
+    SetInserts {
+        track_id: Option<Uuid>,
+        before: Vec<serde_json::Value>,
+        after: Vec<serde_json::Value>,
+    },
+    /// Change the master bus gain (dB).
+    SetMasterGain { before: f32, after: f32 },
     /// A batch of commands applied atomically.
     Batch(Vec<EditCommand>),
 }
@@ -134,7 +162,7 @@ impl EditCommand {
             } => {
                 if let Some(track) = find_track_mut(sequence, *track_id) {
                     if let Some(crate::track::TrackItem::Clip(clip)) = track.remove_item(*index) {
-                        *removed = Some(clip);
+                        *removed = Some(*clip);
                     }
                 }
             }
@@ -147,7 +175,7 @@ impl EditCommand {
                 let clip = find_track_mut(sequence, *src_track_id)
                     .and_then(|t| t.remove_item(*src_index))
                     .and_then(|item| match item {
-                        crate::track::TrackItem::Clip(c) => Some(c),
+                        crate::track::TrackItem::Clip(c) => Some(*c),
                         _ => None,
                     });
                 if let Some(clip) = clip {
@@ -227,30 +255,25 @@ impl EditCommand {
                 track_id,
                 clip_index,
                 offset,
+                original,
             } => {
                 if let Some(track) = find_track_mut(sequence, *track_id) {
-                    // Read data from original clip
-                    let split_data = track.clip_at(*clip_index).map(|clip| {
-                        (
-                            clip.name.clone(),
-                            clip.source.clone(),
-                            clip.source_in,
-                            clip.duration,
-                            clip.speed,
-                            clip.enabled,
-                        )
-                    });
-                    if let Some((name, source, source_in, _orig_dur, speed, enabled)) = split_data {
-                        // Shorten left clip to offset
-                        if let Some(clip) = track.clip_at_mut(*clip_index) {
-                            clip.duration = *offset;
+                    if let Some(clip) = track.clip_at_mut(*clip_index) {
+                        *original = Some(Box::new(clip.clone()));
+                        // Right half keeps the source, takes and generator
+                        let mut right = clip.clone();
+                        right.id = Uuid::new_v4();
+                        right.name = format!("{} (split)", clip.name);
+                        let shift = clip.source_offset(*offset);
+                        right.source_in = clip.source_in + shift;
+                        for take in &mut right.takes {
+                            take.source_in = take.source_in + shift;
                         }
-                        // Create right half
-                        let mut right = Clip::new(format!("{name} (split)"), source);
-                        right.source_in = source_in + *offset;
-                        right.duration = _orig_dur - *offset;
-                        right.speed = speed;
-                        right.enabled = enabled;
+                        right.duration = clip.duration - *offset;
+                        right.audio = clip.audio.split_off(*offset);
+                        // Shorten left clip to offset; its fade-out moves to the right half
+                        clip.duration = *offset;
+                        clip.audio.fade_out = Fade::default();
                         track.insert_clip(*clip_index + 1, right);
                     }
                 }
@@ -282,15 +305,19 @@ impl EditCommand {
                 name,
                 track_id,
             } => {
+                // Reuse a previously assigned ID (redo, replayed operations)
+                // so later commands referencing the track still resolve.
                 let new_track = match kind {
                     TrackKind::Video => {
-                        let t = Track::new_video(name.clone());
+                        let mut t = Track::new_video(name.clone());
+                        t.id = track_id.unwrap_or(t.id);
                         let id = t.id;
                         sequence.video_tracks.push(t);
                         id
                     }
                     TrackKind::Audio => {
-                        let t = Track::new_audio(name.clone());
+                        let mut t = Track::new_audio(name.clone());
+                        t.id = track_id.unwrap_or(t.id);
                         let id = t.id;
                         sequence.audio_tracks.push(t);
                         id
@@ -313,6 +340,36 @@ impl EditCommand {
                     *removed = Some(sequence.audio_tracks.remove(idx));
                 }
             }
+            Self::UpdateClip {
+                track_id,
+                clip_index,
+                after,
+                ..
+            } => {
+                if let Some(track) = find_track_mut(sequence, *track_id) {
+                    if let Some(clip) = track.clip_at_mut(*clip_index) {
+                        *clip = (**after).clone();
+                    }
+                }
+            }
+            Self::ReplaceItems {
+                track_id, after, ..
+            } => {
+                if let Some(track) = find_track_mut(sequence, *track_id) {
+                    track.items = after.clone();
+                }
+            }
+            Self::SetInserts {
+                track_id, after, ..
+            } => match track_id {
+                Some(id) => {
+                    if let Some(track) = find_track_mut(sequence, *id) {
+                        track.inserts = after.clone();
+                    }
+                }
+                None => sequence.master_inserts = after.clone(),
+            },
+            Self::SetMasterGain { after, .. } => sequence.master_gain_db = *after,
             Self::Batch(commands) => {
                 for cmd in commands {
                     cmd.apply(sequence);
@@ -322,8 +379,12 @@ impl EditCommand {
     }
 
     /// Produce the inverse command (for undo).
-    pub fn inverse(&self) -> Self {
-        match self {
+    ///
+    /// Returns `None` for a command that needs data recorded when it was
+    /// executed (a removed clip, a new track's ID) but was never applied,
+    /// e.g. one loaded from a stale history file.
+    pub fn inverse(&self) -> Option<Self> {
+        Some(match self {
             Self::InsertClip {
                 track_id,
                 index,
@@ -340,7 +401,7 @@ impl EditCommand {
             } => Self::InsertClip {
                 track_id: *track_id,
                 index: *index,
-                clip: removed.clone().expect("removed clip must be populated"),
+                clip: removed.clone()?,
             },
             Self::MoveClip {
                 src_track_id,
@@ -394,15 +455,28 @@ impl EditCommand {
             Self::SplitClip {
                 track_id,
                 clip_index,
-                ..
+                offset,
+                original,
             } => {
-                // Undo split = merge clip_index and clip_index+1
-                // Represented as removing the second clip and extending the first
-                Self::RemoveClip {
-                    track_id: *track_id,
-                    index: clip_index + 1,
-                    removed: None,
-                }
+                // Undo split = remove the right half and restore the left
+                // clip's duration and fade-out
+                let original = original.clone()?;
+                let mut left = original.clone();
+                left.duration = *offset;
+                left.audio.fade_out = Fade::default();
+                Self::Batch(vec![
+                    Self::RemoveClip {
+                        track_id: *track_id,
+                        index: clip_index + 1,
+                        removed: None,
+                    },
+                    Self::UpdateClip {
+                        track_id: *track_id,
+                        clip_index: *clip_index,
+                        before: left,
+                        after: original,
+                    },
+                ])
             }
             Self::ToggleClipEnabled {
                 track_id,
@@ -422,10 +496,8 @@ impl EditCommand {
                 old_speed: *new_speed,
                 new_speed: *old_speed,
             },
-            Self::AddTrack {
-                track_id, kind: _, ..
-            } => Self::RemoveTrack {
-                track_id: track_id.expect("track_id must be populated"),
+            Self::AddTrack { track_id, .. } => Self::RemoveTrack {
+                track_id: (*track_id)?,
                 removed: None,
                 index: None,
             },
@@ -438,10 +510,47 @@ impl EditCommand {
                 name: removed.as_ref().map(|t| t.name.clone()).unwrap_or_default(),
                 track_id: Some(*track_id),
             },
-            Self::Batch(commands) => {
-                Self::Batch(commands.iter().rev().map(|c| c.inverse()).collect())
-            }
-        }
+            Self::UpdateClip {
+                track_id,
+                clip_index,
+                before,
+                after,
+            } => Self::UpdateClip {
+                track_id: *track_id,
+                clip_index: *clip_index,
+                before: after.clone(),
+                after: before.clone(),
+            },
+            Self::ReplaceItems {
+                track_id,
+                before,
+                after,
+            } => Self::ReplaceItems {
+                track_id: *track_id,
+                before: after.clone(),
+                after: before.clone(),
+            },
+            Self::SetInserts {
+                track_id,
+                before,
+                after,
+            } => Self::SetInserts {
+                track_id: *track_id,
+                before: after.clone(),
+                after: before.clone(),
+            },
+            Self::SetMasterGain { before, after } => Self::SetMasterGain {
+                before: *after,
+                after: *before,
+            },
+            Self::Batch(commands) => Self::Batch(
+                commands
+                    .iter()
+                    .rev()
+                    .map(|c| c.inverse())
+                    .collect::<Option<_>>()?,
+            ),
+        })
     }
 }
 
@@ -456,52 +565,426 @@ fn find_track_mut(sequence: &mut crate::project::Sequence, track_id: Uuid) -> Op
         .find(|track| track.id == track_id)
 }
 
+// ── History commands ────────────────────────────────────────────
+
+/// A command that can be recorded in an [`UndoStack`].
+///
+/// Implemented by [`EditCommand`]; other editing models (e.g. UI-only state)
+/// can implement it to share the same history mechanism.
+pub trait HistoryCommand: Clone {
+    /// Produce the command that reverts this one, or `None` if it cannot
+    /// be reverted.
+    fn inverse(&self) -> Option<Self>;
+
+    /// Human-readable description shown in Edit menus and history panels.
+    fn label(&self) -> String;
+
+    /// Try to fold `next` into `self` so both become a single history entry.
+    ///
+    /// Returns true if `self` now represents both commands.
+    fn merge(&mut self, _next: &Self) -> bool {
+        false
+    }
+
+    /// Combine several commands into one atomic command.
+    fn batch(commands: Vec<Self>) -> Self;
+}
+
+impl HistoryCommand for EditCommand {
+    fn inverse(&self) -> Option<Self> {
+        EditCommand::inverse(self)
+    }
+
+    fn label(&self) -> String {
+        match self {
+            Self::InsertClip { clip, .. } => format!("Insert {}", clip.name),
+            Self::RemoveClip {
+                removed: Some(clip),
+                ..
+            } => format!("Delete {}", clip.name),
+            Self::RemoveClip { .. } => "Delete Clip".into(),
+            Self::MoveClip { .. } => "Move Clip".into(),
+            Self::RippleTrim { .. } => "Ripple Trim".into(),
+            Self::RollTrim { .. } => "Roll Trim".into(),
+            Self::Slip { .. } => "Slip Clip".into(),
+            Self::Slide { .. } => "Slide Clip".into(),
+            Self::SplitClip { .. } => "Split Clip".into(),
+            Self::ToggleClipEnabled { .. } => "Toggle Clip Enabled".into(),
+            Self::SetClipSpeed { .. } => "Change Clip Speed".into(),
+            Self::AddTrack { name, .. } => format!("Add Track {}", name),
+            Self::RemoveTrack {
+                removed: Some(track),
+                ..
+            } => format!("Delete Track {}", track.name),
+            Self::RemoveTrack { .. } => "Delete Track".into(),
+            Self::UpdateClip { after, .. } => format!("Edit {}", after.name),
+            Self::ReplaceItems { .. } => "Edit Track".into(),
+            Self::SetInserts { .. } => "Change Inserts".into(),
+            Self::SetMasterGain { .. } => "Change Master Gain".into(),
+            Self::Batch(commands) => match commands.as_slice() {
+                [single] => single.label(),
+                _ => format!("{} Edits", commands.len()),
+            },
+        }
+    }
+
+    fn merge(&mut self, next: &Self) -> bool {
+        match (self, next) {
+            (
+                Self::RippleTrim {
+                    track_id,
+                    clip_index,
+                    delta,
+                    trim_in,
+                },
+                Self::RippleTrim {
+                    track_id: next_track,
+                    clip_index: next_index,
+                    delta: next_delta,
+                    trim_in: next_trim_in,
+                },
+            ) if track_id == next_track && clip_index == next_index && trim_in == next_trim_in => {
+                *delta = *delta + *next_delta;
+                true
+            }
+            (
+                Self::RollTrim {
+                    track_id,
+                    clip_index,
+                    delta,
+                },
+                Self::RollTrim {
+                    track_id: next_track,
+                    clip_index: next_index,
+                    delta: next_delta,
+                },
+            )
+            | (
+                Self::Slip {
+                    track_id,
+                    clip_index,
+                    delta,
+                },
+                Self::Slip {
+                    track_id: next_track,
+                    clip_index: next_index,
+                    delta: next_delta,
+                },
+            )
+            | (
+                Self::Slide {
+                    track_id,
+                    clip_index,
+                    delta,
+                },
+                Self::Slide {
+                    track_id: next_track,
+                    clip_index: next_index,
+                    delta: next_delta,
+                },
+            ) if track_id == next_track && clip_index == next_index => {
+                *delta = *delta + *next_delta;
+                true
+            }
+            (
+                Self::SetClipSpeed {
+                    track_id,
+                    clip_index,
+                    new_speed,
+                    ..
+                },
+                Self::SetClipSpeed {
+                    track_id: next_track,
+                    clip_index: next_index,
+                    new_speed: next_speed,
+                    ..
+                },
+            ) if track_id == next_track && clip_index == next_index => {
+                *new_speed = *next_speed;
+                true
+            }
+            (
+                Self::MoveClip {
+                    dst_track_id,
+                    dst_index,
+                    ..
+                },
+                Self::MoveClip {
+                    src_track_id: next_src_track,
+                    src_index: next_src_index,
+                    dst_track_id: next_dst_track,
+                    dst_index: next_dst_index,
+                },
+            ) if dst_track_id == next_src_track && dst_index == next_src_index => {
+                // Moving the same clip again: keep the original source.
+                *dst_track_id = *next_dst_track;
+                *dst_index = *next_dst_index;
+                true
+            }
+            (
+                Self::UpdateClip {
+                    track_id,
+                    clip_index,
+                    after,
+                    ..
+                },
+                Self::UpdateClip {
+                    track_id: next_track,
+                    clip_index: next_index,
+                    after: next_after,
+                    ..
+                },
+            ) if track_id == next_track && clip_index == next_index => {
+                *after = next_after.clone();
+                true
+            }
+            (
+                Self::ReplaceItems {
+                    track_id, after, ..
+                },
+                Self::ReplaceItems {
+                    track_id: next_track,
+                    after: next_after,
+                    ..
+                },
+            ) if track_id == next_track => {
+                *after = next_after.clone();
+                true
+            }
+            (
+                Self::SetInserts {
+                    track_id, after, ..
+                },
+                Self::SetInserts {
+                    track_id: next_track,
+                    after: next_after,
+                    ..
+                },
+            ) if track_id == next_track => {
+                *after = next_after.clone();
+                true
+            }
+            (
+                Self::SetMasterGain { after, .. },
+                Self::SetMasterGain {
+                    after: next_after, ..
+                },
+            ) => {
+                *after = *next_after;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn batch(commands: Vec<Self>) -> Self {
+        Self::Batch(commands)
+    }
+}
+
 // ── Undo stack ──────────────────────────────────────────────────
 
+/// Default window within which consecutive same-kind commands merge.
+pub const DEFAULT_MERGE_WINDOW: Duration = Duration::from_millis(1000);
+
+/// A labeled entry in the undo history.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry<C> {
+    /// Human-readable description of the change.
+    pub label: String,
+    /// The command that was executed.
+    pub command: C,
+    /// When the entry was last pushed or merged into (not persisted).
+    #[serde(skip)]
+    touched: Option<Instant>,
+}
+
+/// An open group of commands that will be recorded as one entry.
+#[derive(Debug, Clone)]
+struct OpenGroup<C> {
+    label: String,
+    commands: Vec<C>,
+    depth: usize,
+}
+
 /// Undo/redo history stack.
-#[derive(Debug)]
-pub struct UndoStack {
+///
+/// Consecutive commands that [`HistoryCommand::merge`] into each other within
+/// the merge window become a single entry, and commands pushed between
+/// [`begin_group`](Self::begin_group) and [`end_group`](Self::end_group)
+/// are recorded as one batch.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound(serialize = "C: Serialize", deserialize = "C: Deserialize<'de>"))]
+pub struct UndoStack<C = EditCommand> {
     /// Commands that have been executed (most recent last).
-    undo: Vec<EditCommand>,
+    undo: Vec<HistoryEntry<C>>,
     /// Commands that have been undone (most recent last).
-    redo: Vec<EditCommand>,
+    redo: Vec<HistoryEntry<C>>,
     /// Maximum history depth.
     max_depth: usize,
+    /// Consecutive commands within this window may merge.
+    #[serde(skip, default = "default_merge_window")]
+    merge_window: Duration,
+    /// Prevents the next push from merging into the last entry.
+    #[serde(skip)]
+    sealed: bool,
+    /// Group currently being recorded.
+    #[serde(skip)]
+    group: Option<OpenGroup<C>>,
+}
+
+fn default_merge_window() -> Duration {
+    DEFAULT_MERGE_WINDOW
 }
 
-impl UndoStack {
+impl<C: HistoryCommand> UndoStack<C> {
     /// Create a new undo stack with the given maximum depth.
     pub fn new(max_depth: usize) -> Self {
         Self {
             undo: Vec::new(),
             redo: Vec::new(),
             max_depth,
+            merge_window: DEFAULT_MERGE_WINDOW,
+            sealed: false,
+            group: None,
         }
     }
 
+    /// Set the window within which consecutive commands merge.
+    /// A zero window disables merging.
+    pub fn set_merge_window(&mut self, window: Duration) {
+        self.merge_window = window;
+    }
+
     /// Push a command onto the undo stack after it has been executed.
     /// Clears the redo stack (new action invalidates redo history).
-    pub fn push(&mut self, command: EditCommand) {
+    pub fn push(&mut self, command: C) {
+        let label = command.label();
+        self.push_labeled(label, command);
+    }
+
+    /// Push a command with an explicit label.
+    pub fn push_labeled(&mut self, label: impl Into<String>, command: C) {
         self.redo.clear();
-        self.undo.push(command);
+
+        if let Some(group) = &mut self.group {
+            let merged = group
+                .commands
+                .last_mut()
+                .is_some_and(|last| last.merge(&command));
+            if !merged {
+                group.commands.push(command);
+            }
+            return;
+        }
+
+        let now = Instant::now();
+        if !self.sealed {
+            if let Some(last) = self.undo.last_mut() {
+                let recent = last
+                    .touched
+                    .is_some_and(|t| now.duration_since(t) <= self.merge_window);
+                if recent && !self.merge_window.is_zero() && last.command.merge(&command) {
+                    last.touched = Some(now);
+                    return;
+                }
+            }
+        }
+
+        self.sealed = false;
+        self.undo.push(HistoryEntry {
+            label: label.into(),
+            command,
+            touched: Some(now),
+        });
         if self.undo.len() > self.max_depth {
             self.undo.remove(0);
         }
     }
 
+    /// Stop the next push from merging into the current last entry
+    /// (e.g. when a drag gesture ends).
+    pub fn seal(&mut self) {
+        self.sealed = true;
+    }
+
+    /// Start recording a group. Everything pushed until the matching
+    /// [`end_group`](Self::end_group) becomes a single batch entry.
+    /// Groups may nest; inner groups are folded into the outermost one.
+    pub fn begin_group(&mut self, label: impl Into<String>) {
+        match &mut self.group {
+            Some(group) => group.depth += 1,
+            None => {
+                self.group = Some(OpenGroup {
+                    label: label.into(),
+                    commands: Vec::new(),
+                    depth: 1,
+                });
+            }
+        }
+    }
+
+    /// Finish the current group. Empty groups leave no entry.
+    pub fn end_group(&mut self) {
+        let Some(group) = &mut self.group else {
+            return;
+        };
+        group.depth -= 1;
+        if group.depth > 0 {
+            return;
+        }
+        let group = self.group.take().expect("group checked above");
+        if group.commands.is_empty() {
+            return;
+        }
+        self.seal();
+        self.push_labeled(group.label, C::batch(group.commands));
+        self.seal();
+    }
+
+    /// Check if a group is currently being recorded.
+    pub fn in_group(&self) -> bool {
+        self.group.is_some()
+    }
+
     /// Pop the most recent command for undo. Returns the inverse command.
-    pub fn undo(&mut self) -> Option<EditCommand> {
-        let cmd = self.undo.pop()?;
-        let inverse = cmd.inverse();
-        self.redo.push(cmd);
+    ///
+    /// Closes any open group first so it can be undone as a whole. An entry
+    /// that cannot be inverted is dropped together with everything older,
+    /// since those edits no longer lead to the current state.
+    pub fn undo(&mut self) -> Option<C> {
+        self.close_groups();
+        let entry = self.undo.pop()?;
+        let Some(inverse) = entry.command.inverse() else {
+            self.undo.clear();
+            return None;
+        };
+        self.redo.push(entry);
+        self.seal();
         Some(inverse)
     }
 
     /// Pop the most recent undone command for redo. Returns the original command.
-    pub fn redo(&mut self) -> Option<EditCommand> {
-        let cmd = self.redo.pop()?;
-        self.undo.push(cmd.clone());
-        Some(cmd)
+    pub fn redo(&mut self) -> Option<C> {
+        let entry = self.redo.pop()?;
+        let command = entry.command.clone();
+        self.undo.push(entry);
+        self.seal();
+        Some(command)
+    }
+
+    /// Drop undo entries that cannot be inverted, with everything older
+    /// (e.g. after loading a saved history). Returns the number of entries
+    /// removed.
+    pub fn drop_uninvertible(&mut self) -> usize {
+        match self
+            .undo
+            .iter()
+            .rposition(|e| e.command.inverse().is_none())
+        {
+            Some(last) => self.undo.drain(..=last).count(),
+            None => 0,
+        }
     }
 
     /// Check if undo is available.
@@ -514,10 +997,27 @@ impl UndoStack {
         !self.redo.is_empty()
     }
 
+    /// Label of the entry that would be undone next.
+    pub fn undo_label(&self) -> Option<&str> {
+        self.undo.last().map(|e| e.label.as_str())
+    }
+
+    /// Label of the entry that would be redone next.
+    pub fn redo_label(&self) -> Option<&str> {
+        self.redo.last().map(|e| e.label.as_str())
+    }
+
+    /// Executed entries, oldest first.
+    pub fn entries(&self) -> &[HistoryEntry<C>] {
+        &self.undo
+    }
+
     /// Clear all history.
     pub fn clear(&mut self) {
         self.undo.clear();
         self.redo.clear();
+        self.group = None;
+        self.sealed = false;
     }
 
     /// Number of undo steps available.
@@ -529,9 +1029,15 @@ impl UndoStack {
     pub fn redo_count(&self) -> usize {
         self.redo.len()
     }
+
+    fn close_groups(&mut self) {
+        while self.group.is_some() {
+            self.end_group();
+        }
+    }
 }
 
-impl Default for UndoStack {
+impl<C: HistoryCommand> Default for UndoStack<C> {
     fn default() -> Self {
         Self::new(200)
     }
@@ -620,7 +1126,7 @@ mod tests {
             delta: RationalTime::new(5, 1),
             trim_in: true,
         };
-        let inv = cmd.inverse();
+        let inv = cmd.inverse().unwrap();
         if let EditCommand::RippleTrim { delta, .. } = inv {
             assert_eq!(delta, RationalTime::new(-5, 1));
         } else {
@@ -777,6 +1283,7 @@ mod tests {
             track_id,
             clip_index: 0,
             offset: split_at,
+            original: None,
         };
         cmd.apply(&mut seq);
 
@@ -788,6 +1295,73 @@ mod tests {
         assert!(right.name.contains("split"));
     }
 
+    #[test]
+    fn test_split_undo_restores_clip() {
+        use crate::clip::FadeCurve;
+
+        let (mut seq, track_id) = make_sequence_with_track();
+        let mut clip = make_test_clip("original");
+        clip.audio.fade_out = Fade::new(RationalTime::new(2, 1), FadeCurve::SCurve);
+        seq.video_tracks[0].append_clip(clip);
+        let before = serde_json::to_value(&seq.video_tracks[0].items).unwrap();
+
+        seq.execute(EditCommand::SplitClip {
+            track_id,
+            clip_index: 0,
+            offset: RationalTime::new(4, 1),
+            original: None,
+        });
+        assert_eq!(seq.video_tracks[0].clip_count(), 2);
+
+        assert!(seq.undo());
+        let after = serde_json::to_value(&seq.video_tracks[0].items).unwrap();
+        assert_eq!(after, before);
+
+        assert!(seq.redo());
+        assert_eq!(seq.video_tracks[0].clip_count(), 2);
+        assert!(seq.undo());
+        assert_eq!(
+            serde_json::to_value(&seq.video_tracks[0].items).unwrap(),
+            before
+        );
+    }
+
+    #[test]
+    fn test_split_clip_carries_audio() {
+        use crate::clip::FadeCurve;
+        use proedit_core::EasingCurve;
+
+        let (mut seq, track_id) = make_sequence_with_track();
+        let mut clip = make_test_clip("original");
+        clip.audio.gain_db = -3.0;
+        clip.audio.fade_in = Fade::new(RationalTime::new(1, 1), FadeCurve::Linear);
+        clip.audio.fade_out = Fade::new(RationalTime::new(2, 1), FadeCurve::SCurve);
+        let mut volume = proedit_core::KeyframeTrack::new("volume");
+        volume.set(RationalTime::new(6, 1), -12.0, EasingCurve::Linear);
+        clip.audio.volume = Some(volume);
+        clip.audio.stretch = crate::StretchMode::PreservePitch;
+        seq.video_tracks[0].append_clip(clip);
+
+        EditCommand::SplitClip {
+            track_id,
+            clip_index: 0,
+            offset: RationalTime::new(4, 1),
+            original: None,
+        }
+        .apply(&mut seq);
+
+        let left = seq.video_tracks[0].clip_at(0).unwrap();
+        let right = seq.video_tracks[0].clip_at(1).unwrap();
+        assert!(left.audio.fade_in.is_active());
+        assert!(!left.audio.fade_out.is_active());
+        assert!(!right.audio.fade_in.is_active());
+        assert_eq!(right.audio.fade_out.curve, FadeCurve::SCurve);
+        assert_eq!(right.audio.gain_db, -3.0);
+        assert_eq!(right.audio.stretch, crate::StretchMode::PreservePitch);
+        let shifted = right.audio.volume.as_ref().unwrap();
+        assert_eq!(shifted.keyframes()[0].time, RationalTime::new(2, 1));
+    }
+
     #[test]
     fn test_apply_add_track() {
         let (mut seq, _) = make_sequence_with_track();
@@ -806,6 +1380,19 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_add_track_reuses_assigned_id() {
+        let (mut seq, _) = make_sequence_with_track();
+        let id = Uuid::new_v4();
+        let mut cmd = EditCommand::AddTrack {
+            kind: TrackKind::Audio,
+            name: "A2".into(),
+            track_id: Some(id),
+        };
+        cmd.apply(&mut seq);
+        assert_eq!(seq.audio_tracks.last().unwrap().id, id);
+    }
+
     #[test]
     fn test_apply_remove_track() {
         let (mut seq, track_id) = make_sequence_with_track();
@@ -845,6 +1432,94 @@ mod tests {
         assert_eq!(clip.duration, orig_dur); // Duration unchanged
     }
 
+    #[test]
+    fn test_update_clip_merges_and_undoes() {
+        let (mut seq, track_id) = make_sequence_with_track();
+        seq.video_tracks[0].append_clip(make_test_clip("clip"));
+        for speed in [1.5, 2.0] {
+            let before = seq.video_tracks[0].clip_at(0).unwrap().clone();
+            let mut after = before.clone();
+            after.speed = speed;
+            seq.execute(EditCommand::UpdateClip {
+                track_id,
+                clip_index: 0,
+                before: Box::new(before),
+                after: Box::new(after),
+            });
+        }
+        assert_eq!(seq.history.undo_count(), 1);
+        assert_eq!(seq.history.undo_label(), Some("Edit clip"));
+        assert_eq!(seq.video_tracks[0].clip_at(0).unwrap().speed, 2.0);
+
+        assert!(seq.undo());
+        assert_eq!(seq.video_tracks[0].clip_at(0).unwrap().speed, 1.0);
+    }
+
+    #[test]
+    fn test_replace_items_overwrite_and_undo() {
+        let (mut seq, track_id) = make_sequence_with_track();
+        seq.video_tracks[0].append_clip(make_test_clip("base"));
+        let before = seq.video_tracks[0].items.clone();
+        let mut placed = seq.video_tracks[0].clone();
+        let mut clip = make_test_clip("over");
+        clip.duration = RationalTime::new(2, 1);
+        placed.overwrite(RationalTime::new(4, 1), clip);
+
+        seq.execute(EditCommand::ReplaceItems {
+            track_id,
+            before,
+            after: placed.items,
+        });
+        assert_eq!(seq.video_tracks[0].clip_count(), 3);
+        assert_eq!(seq.video_tracks[0].duration(), RationalTime::new(10, 1));
+
+        assert!(seq.undo());
+        assert_eq!(seq.video_tracks[0].clip_count(), 1);
+        assert!(seq.redo());
+        assert_eq!(seq.video_tracks[0].clip_at(1).unwrap().name, "over");
+    }
+
+    #[test]
+    fn test_set_inserts_merges_and_undoes() {
+        let mut seq = crate::project::Sequence::default();
+        let track_id = seq.audio_tracks[0].id;
+        let eq = |gain: f64| serde_json::json!({ "effect": { "Eq": { "gain": gain } } });
+        for gain in [-3.0, -6.0] {
+            seq.execute(EditCommand::SetInserts {
+                track_id: Some(track_id),
+                before: seq.audio_tracks[0].inserts.clone(),
+                after: vec![eq(gain)],
+            });
+        }
+        seq.execute(EditCommand::SetInserts {
+            track_id: None,
+            before: Vec::new(),
+            after: vec![eq(-1.0)],
+        });
+        assert_eq!(seq.history.undo_count(), 2);
+        assert_eq!(seq.history.undo_label(), Some("Change Inserts"));
+        assert_eq!(seq.master_inserts, vec![eq(-1.0)]);
+
+        assert!(seq.undo());
+        assert!(seq.master_inserts.is_empty());
+        assert_eq!(seq.audio_tracks[0].inserts, vec![eq(-6.0)]);
+        assert!(seq.undo());
+        assert!(seq.audio_tracks[0].inserts.is_empty());
+    }
+
+    #[test]
+    fn test_set_master_gain_merges_and_undoes() {
+        let mut seq = crate::project::Sequence::default();
+        for (before, after) in [(0.0, -2.0), (-2.0, -4.5)] {
+            seq.execute(EditCommand::SetMasterGain { before, after });
+        }
+        assert_eq!(seq.history.undo_count(), 1);
+        assert_eq!(seq.history.undo_label(), Some("Change Master Gain"));
+        assert_eq!(seq.master_gain_db, -4.5);
+        assert!(seq.undo());
+        assert_eq!(seq.master_gain_db, 0.0);
+    }
+
     #[test]
     fn test_apply_batch() {
         let (mut seq, track_id) = make_sequence_with_track();
@@ -882,7 +1557,7 @@ mod tests {
         assert_eq!(seq.video_tracks[0].clip_count(), 1);
 
         // Apply inverse (remove)
-        let mut inv = cmd.inverse();
+        let mut inv = cmd.inverse().unwrap();
         inv.apply(&mut seq);
         assert_eq!(seq.video_tracks[0].clip_count(), 0);
     }
@@ -901,7 +1576,7 @@ mod tests {
                 new_speed: 2.0,
             },
         ]);
-        let inv = cmd.inverse();
+        let inv = cmd.inverse().unwrap();
         if let EditCommand::Batch(cmds) = inv {
             assert_eq!(cmds.len(), 2);
             // First in inverse = last in original (reversed)
@@ -913,4 +1588,167 @@ mod tests {
             panic!("expected Batch inverse");
         }
     }
+
+    fn nudge(delta: i64) -> EditCommand {
+        EditCommand::Slip {
+            track_id: Uuid::nil(),
+            clip_index: 0,
+            delta: RationalTime::new(delta, 24),
+        }
+    }
+
+    #[test]
+    fn test_consecutive_commands_merge() {
+        let mut stack = UndoStack::new(100);
+        stack.push(nudge(1));
+        stack.push(nudge(1));
+        stack.push(nudge(2));
+        assert_eq!(stack.undo_count(), 1);
+        assert_eq!(stack.undo_label(), Some("Slip Clip"));
+
+        let inverse = stack.undo().unwrap();
+        assert!(
+            matches!(inverse, EditCommand::Slip { delta, .. } if delta == RationalTime::new(-4, 24))
+        );
+    }
+
+    #[test]
+    fn test_merge_respects_seal_and_window() {
+        let mut stack = UndoStack::new(100);
+        stack.push(nudge(1));
+        stack.seal();
+        stack.push(nudge(1));
+        assert_eq!(stack.undo_count(), 2);
+
+        let mut stack = UndoStack::new(100);
+        stack.set_merge_window(std::time::Duration::ZERO);
+        stack.push(nudge(1));
+        stack.push(nudge(1));
+        assert_eq!(stack.undo_count(), 2);
+    }
+
+    #[test]
+    fn test_speed_changes_merge_keep_original() {
+        let mut stack = UndoStack::new(100);
+        for (old, new) in [(1.0, 1.5), (1.5, 2.0), (2.0, 3.0)] {
+            stack.push(EditCommand::SetClipSpeed {
+                track_id: Uuid::nil(),
+                clip_index: 0,
+                old_speed: old,
+                new_speed: new,
+            });
+        }
+        assert_eq!(stack.undo_count(), 1);
+        let inverse = stack.undo().unwrap();
+        assert!(matches!(
+            inverse,
+            EditCommand::SetClipSpeed { old_speed, new_speed, .. }
+                if old_speed == 3.0 && new_speed == 1.0
+        ));
+    }
+
+    #[test]
+    fn test_group_produces_single_batch() {
+        let mut stack = UndoStack::new(100);
+        stack.begin_group("Lift and Insert");
+        stack.push(EditCommand::ToggleClipEnabled {
+            track_id: Uuid::nil(),
+            clip_index: 0,
+        });
+        stack.begin_group("Inner");
+        stack.push(EditCommand::InsertClip {
+            track_id: Uuid::nil(),
+            index: 1,
+            clip: make_test_clip("b"),
+        });
+        stack.end_group();
+        assert!(stack.in_group());
+        stack.end_group();
+
+        assert!(!stack.in_group());
+        assert_eq!(stack.undo_count(), 1);
+        assert_eq!(stack.undo_label(), Some("Lift and Insert"));
+        assert!(matches!(
+            &stack.entries()[0].command,
+            EditCommand::Batch(cmds) if cmds.len() == 2
+        ));
+
+        stack.begin_group("Nothing");
+        stack.end_group();
+        assert_eq!(stack.undo_count(), 1);
+    }
+
+    #[test]
+    fn test_grouped_edits_undo_together() {
+        let mut seq = crate::project::Sequence::default();
+        let track_id = seq.video_tracks[0].id;
+        seq.history.begin_group("Add Two");
+        for name in ["a", "b"] {
+            seq.execute(EditCommand::InsertClip {
+                track_id,
+                index: 0,
+                clip: make_test_clip(name),
+            });
+        }
+        seq.history.end_group();
+        assert_eq!(seq.video_tracks[0].clip_count(), 2);
+
+        assert!(seq.undo());
+        assert_eq!(seq.video_tracks[0].clip_count(), 0);
+        assert_eq!(seq.history.redo_label(), Some("Add Two"));
+        assert!(seq.redo());
+        assert_eq!(seq.video_tracks[0].clip_count(), 2);
+    }
+
+    #[test]
+    fn test_labels() {
+        let cmd = EditCommand::InsertClip {
+            track_id: Uuid::nil(),
+            index: 0,
+            clip: make_test_clip("Interview"),
+        };
+        assert_eq!(cmd.label(), "Insert Interview");
+        let mut stack = UndoStack::new(10);
+        stack.push_labeled("Paste", cmd);
+        assert_eq!(stack.undo_label(), Some("Paste"));
+    }
+
+    #[test]
+    fn test_unapplied_commands_are_dropped_not_inverted() {
+        let mut stack: UndoStack = UndoStack::new(10);
+        stack.push(nudge(1));
+        stack.push(EditCommand::RemoveClip {
+            track_id: Uuid::nil(),
+            index: 0,
+            removed: None,
+        });
+        stack.seal();
+        stack.push(nudge(2));
+
+        let mut loaded = stack.clone();
+        assert_eq!(loaded.drop_uninvertible(), 2);
+        assert_eq!(loaded.undo_count(), 1);
+
+        assert!(stack.undo().is_some());
+        assert!(stack.undo().is_none());
+        assert!(!stack.can_undo());
+    }
+
+    #[test]
+    fn test_history_serde_roundtrip() {
+        let mut stack = UndoStack::new(50);
+        stack.push(EditCommand::InsertClip {
+            track_id: Uuid::nil(),
+            index: 0,
+            clip: make_test_clip("a"),
+        });
+        stack.push(nudge(3));
+        stack.undo();
+
+        let json = serde_json::to_string(&stack).unwrap();
+        let loaded: UndoStack = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.undo_count(), 1);
+        assert_eq!(loaded.redo_count(), 1);
+        assert_eq!(loaded.redo_label(), Some("Slip Clip"));
+    }
 }
@@ -0,0 +1,72 @@
+//! Video effect stacks on tracks and adjustment layers.
+
+use proedit_core::{ParamValue, ParamValues, RationalTime};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// One effect in a stack, naming an effect in the effects registry.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EffectInstance {
+    /// Registry name of the effect (e.g. "Gaussian Blur")
+    pub name: String,
+    /// Parameter values; missing ones take the effect's defaults
+    #[serde(default)]
+    pub params: ParamValues,
+    /// Disabled effects stay in the stack but are not rendered
+    pub enabled: bool,
+}
+
+impl EffectInstance {
+    /// An enabled instance of effect `name` with default parameters.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            params: ParamValues::new(),
+            enabled: true,
+        }
+    }
+
+    /// Set parameter `name` to `value`.
+    pub fn with_param(mut self, name: impl Into<String>, value: ParamValue) -> Self {
+        self.params.insert(name.into(), value);
+        self
+    }
+}
+
+/// A timeline item with an effect stack but no source.
+///
+/// For its duration, its effects apply to the composite of every track
+/// below its own.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AdjustmentLayer {
+    /// Unique item ID
+    pub id: Uuid,
+    /// Name (displayed in UI)
+    pub name: String,
+    /// Duration on timeline
+    pub duration: RationalTime,
+    /// Is the layer enabled
+    pub enabled: bool,
+    /// Effects, applied first to last
+    pub effects: Vec<EffectInstance>,
+}
+
+impl AdjustmentLayer {
+    /// Create an empty adjustment layer.
+    pub fn new(name: impl Into<String>, duration: RationalTime) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            name: name.into(),
+            duration,
+            enabled: true,
+            effects: Vec::new(),
+        }
+    }
+
+    /// Effects that render, in order.
+    pub fn active_effects(&self) -> impl Iterator<Item = &EffectInstance> {
+        self.effects
+            .iter()
+            .filter(move |effect| self.enabled && effect.enabled)
+    }
+}
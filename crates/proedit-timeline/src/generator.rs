@@ -0,0 +1,592 @@
+//! Generated clip content: pictures synthesized instead of decoded.
+//!
+//! Every numeric parameter is a `KeyframeTrack` (colours are one track per
+//! channel), evaluated on the clip's source time like text properties.
+
+use proedit_core::{EasingCurve, KeyframeTrack, RationalTime};
+use serde::{Deserialize, Serialize};
+
+/// Procedural content a clip shows instead of media.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Generator {
+    /// A title or text layer
+    Text(TextGenerator),
+    /// A single colour filling the frame
+    Solid(SolidGenerator),
+    /// A linear or radial blend between two colours
+    Gradient(GradientGenerator),
+    /// Fractal noise between two colours
+    Noise(NoiseGenerator),
+    /// SMPTE colour bars; on an audio track, a reference tone
+    Bars(BarsGenerator),
+    /// A countdown leader
+    Countdown(CountdownGenerator),
+}
+
+impl Generator {
+    /// Display name of the generator type.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Text(_) => "Text",
+            Self::Solid(_) => "Solid Color",
+            Self::Gradient(_) => "Gradient",
+            Self::Noise(_) => "Fractal Noise",
+            Self::Bars(_) => "Bars and Tone",
+            Self::Countdown(_) => "Countdown",
+        }
+    }
+
+    /// Whether the picture changes over time, so each frame must be
+    /// generated anew.
+    pub fn is_animated(&self) -> bool {
+        match self {
+            Self::Text(text) => text.is_animated(),
+            Self::Solid(solid) => solid.color.is_animated(),
+            Self::Gradient(gradient) => gradient.is_animated(),
+            Self::Noise(noise) => noise.is_animated(),
+            // The tone may be animated, but the picture is not.
+            Self::Bars(_) => false,
+            Self::Countdown(_) => true,
+        }
+    }
+}
+
+/// An animatable colour: sRGB, straight alpha, one track per channel.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ColorTrack {
+    pub r: KeyframeTrack,
+    pub g: KeyframeTrack,
+    pub b: KeyframeTrack,
+    pub a: KeyframeTrack,
+}
+
+impl ColorTrack {
+    /// A colour that does not change; the channel tracks are named
+    /// `<name>.r` and so on.
+    pub fn constant(name: &str, rgba: [f32; 4]) -> Self {
+        let track = |channel: &str, value: f32| {
+            KeyframeTrack::constant(format!("{name}.{channel}"), value as f64)
+        };
+        Self {
+            r: track("r", rgba[0]),
+            g: track("g", rgba[1]),
+            b: track("b", rgba[2]),
+            a: track("a", rgba[3]),
+        }
+    }
+
+    /// Set a keyframe on all four channels.
+    pub fn set(&mut self, time: RationalTime, rgba: [f32; 4], easing: EasingCurve) {
+        for (track, value) in [&mut self.r, &mut self.g, &mut self.b, &mut self.a]
+            .into_iter()
+            .zip(rgba)
+        {
+            track.set(time, value as f64, easing);
+        }
+    }
+
+    /// The colour at `time`, clamped to 0..1.
+    pub fn at(&self, time: RationalTime) -> [f32; 4] {
+        [&self.r, &self.g, &self.b, &self.a]
+            .map(|track| track.evaluate(time).clamp(0.0, 1.0) as f32)
+    }
+
+    /// Whether any channel changes over time.
+    pub fn is_animated(&self) -> bool {
+        [&self.r, &self.g, &self.b, &self.a]
+            .iter()
+            .any(|track| track.is_animated())
+    }
+}
+
+/// A single colour filling the frame.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SolidGenerator {
+    pub color: ColorTrack,
+}
+
+impl SolidGenerator {
+    pub fn new(rgba: [f32; 4]) -> Self {
+        Self {
+            color: ColorTrack::constant("color", rgba),
+        }
+    }
+}
+
+/// Shape of a gradient.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum GradientKind {
+    /// Bands perpendicular to `angle`
+    #[default]
+    Linear,
+    /// Rings around the centre
+    Radial,
+}
+
+/// A blend from `start_color` to `end_color`, interpolated in sRGB like
+/// design tools do.
+///
+/// A linear gradient runs along `angle` through the centre, `extent` long;
+/// a radial one from the centre out to radius `extent`. Colours hold beyond
+/// either end.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GradientGenerator {
+    pub kind: GradientKind,
+    /// Colour at the start (linear) or centre (radial)
+    pub start_color: ColorTrack,
+    /// Colour at the end (linear) or edge (radial)
+    pub end_color: ColorTrack,
+    /// Horizontal centre (0 = left, 1 = right edge of the frame)
+    pub center_x: KeyframeTrack,
+    /// Vertical centre (0 = top, 1 = bottom)
+    pub center_y: KeyframeTrack,
+    /// Direction of a linear gradient, in degrees clockwise from
+    /// left-to-right
+    pub angle: KeyframeTrack,
+    /// Length of a linear gradient or radius of a radial one, as a fraction
+    /// of the frame width
+    pub extent: KeyframeTrack,
+}
+
+/// A gradient's parameters at one instant.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GradientFrame {
+    pub start_color: [f32; 4],
+    pub end_color: [f32; 4],
+    pub center: [f32; 2],
+    pub angle: f32,
+    pub extent: f32,
+}
+
+impl GradientGenerator {
+    /// A gradient across the whole frame.
+    pub fn new(kind: GradientKind, start: [f32; 4], end: [f32; 4]) -> Self {
+        Self {
+            kind,
+            start_color: ColorTrack::constant("start_color", start),
+            end_color: ColorTrack::constant("end_color", end),
+            center_x: KeyframeTrack::constant("center_x", 0.5),
+            center_y: KeyframeTrack::constant("center_y", 0.5),
+            angle: KeyframeTrack::constant("angle", 0.0),
+            extent: KeyframeTrack::constant(
+                "extent",
+                match kind {
+                    GradientKind::Linear => 1.0,
+                    GradientKind::Radial => 0.5,
+                },
+            ),
+        }
+    }
+
+    /// Evaluate the parameters at source time `time`.
+    pub fn at(&self, time: RationalTime) -> GradientFrame {
+        let value = |track: &KeyframeTrack| track.evaluate(time) as f32;
+        GradientFrame {
+            start_color: self.start_color.at(time),
+            end_color: self.end_color.at(time),
+            center: [value(&self.center_x), value(&self.center_y)],
+            angle: value(&self.angle),
+            extent: value(&self.extent).max(0.0),
+        }
+    }
+
+    /// Whether any parameter changes over time.
+    pub fn is_animated(&self) -> bool {
+        self.start_color.is_animated()
+            || self.end_color.is_animated()
+            || [&self.center_x, &self.center_y, &self.angle, &self.extent]
+                .iter()
+                .any(|track| track.is_animated())
+    }
+}
+
+/// Fractal (fBm) gradient noise, mapped from `low_color` to `high_color`.
+///
+/// Animating `evolution` moves through the noise's third dimension, so the
+/// pattern churns in place; animating the offset scrolls it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NoiseGenerator {
+    /// Selects a different, equally random pattern
+    pub seed: u32,
+    /// Size of the largest features, as a fraction of the frame height
+    pub scale: KeyframeTrack,
+    /// Number of octaves of detail, 1 to 10; fractions fade the last
+    /// octave in
+    pub octaves: KeyframeTrack,
+    /// Amplitude of each octave relative to the one before, 0 to 1
+    pub persistence: KeyframeTrack,
+    /// Contrast around mid grey; 1 leaves the noise as is
+    pub contrast: KeyframeTrack,
+    /// Position in the noise's third dimension
+    pub evolution: KeyframeTrack,
+    /// Horizontal scroll, in units of `scale`
+    pub offset_x: KeyframeTrack,
+    /// Vertical scroll, in units of `scale`
+    pub offset_y: KeyframeTrack,
+    pub low_color: ColorTrack,
+    pub high_color: ColorTrack,
+}
+
+/// Noise parameters at one instant.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NoiseFrame {
+    pub scale: f32,
+    pub octaves: f32,
+    pub persistence: f32,
+    pub contrast: f32,
+    pub evolution: f32,
+    pub offset: [f32; 2],
+    pub low_color: [f32; 4],
+    pub high_color: [f32; 4],
+}
+
+impl NoiseGenerator {
+    /// Black-to-white noise with features a quarter of the frame high.
+    pub fn new(seed: u32) -> Self {
+        Self {
+            seed,
+            scale: KeyframeTrack::constant("scale", 0.25),
+            octaves: KeyframeTrack::constant("octaves", 5.0),
+            persistence: KeyframeTrack::constant("persistence", 0.5),
+            contrast: KeyframeTrack::constant("contrast", 1.0),
+            evolution: KeyframeTrack::constant("evolution", 0.0),
+            offset_x: KeyframeTrack::constant("offset_x", 0.0),
+            offset_y: KeyframeTrack::constant("offset_y", 0.0),
+            low_color: ColorTrack::constant("low_color", [0.0, 0.0, 0.0, 1.0]),
+            high_color: ColorTrack::constant("high_color", [1.0, 1.0, 1.0, 1.0]),
+        }
+    }
+
+    /// Evaluate the parameters at source time `time`.
+    pub fn at(&self, time: RationalTime) -> NoiseFrame {
+        let value = |track: &KeyframeTrack| track.evaluate(time) as f32;
+        NoiseFrame {
+            scale: value(&self.scale).max(1e-4),
+            octaves: value(&self.octaves).clamp(1.0, 10.0),
+            persistence: value(&self.persistence).clamp(0.0, 1.0),
+            contrast: value(&self.contrast).max(0.0),
+            evolution: value(&self.evolution),
+            offset: [value(&self.offset_x), value(&self.offset_y)],
+            low_color: self.low_color.at(time),
+            high_color: self.high_color.at(time),
+        }
+    }
+
+    /// Whether any parameter changes over time.
+    pub fn is_animated(&self) -> bool {
+        self.low_color.is_animated()
+            || self.high_color.is_animated()
+            || [
+                &self.scale,
+                &self.octaves,
+                &self.persistence,
+                &self.contrast,
+                &self.evolution,
+                &self.offset_x,
+                &self.offset_y,
+            ]
+            .iter()
+            .any(|track| track.is_animated())
+    }
+}
+
+/// SMPTE EG 1 colour bars, with a sine reference tone when the clip is on
+/// an audio track.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BarsGenerator {
+    /// Tone frequency in Hz
+    pub tone_frequency: KeyframeTrack,
+    /// Tone level in dBFS
+    pub tone_level: KeyframeTrack,
+}
+
+impl Default for BarsGenerator {
+    /// 1 kHz at -20 dBFS, the usual alignment tone.
+    fn default() -> Self {
+        Self {
+            tone_frequency: KeyframeTrack::constant("tone_frequency", 1000.0),
+            tone_level: KeyframeTrack::constant("tone_level", -20.0),
+        }
+    }
+}
+
+/// A countdown leader: a number per second counting down from `from` to
+/// 1, with a sweep going round once a second over rings and a crosshair.
+/// After 1 it shows the background only.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CountdownGenerator {
+    /// The first number shown
+    pub from: u32,
+    pub background: ColorTrack,
+    /// Colour of the rings, crosshair and numbers
+    pub foreground: ColorTrack,
+    /// Colour of the area the sweep has passed this second
+    pub sweep: ColorTrack,
+}
+
+/// The state of a countdown at one instant.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CountdownFrame {
+    /// Number shown, or `None` once the count is over
+    pub number: Option<u32>,
+    /// Fraction of the current second elapsed, 0 to 1
+    pub sweep: f32,
+    pub background: [f32; 4],
+    pub foreground: [f32; 4],
+    pub sweep_color: [f32; 4],
+}
+
+impl CountdownGenerator {
+    /// A grey leader counting down from `from`.
+    pub fn new(from: u32) -> Self {
+        Self {
+            from,
+            background: ColorTrack::constant("background", [0.45, 0.45, 0.45, 1.0]),
+            foreground: ColorTrack::constant("foreground", [0.05, 0.05, 0.05, 1.0]),
+            sweep: ColorTrack::constant("sweep", [0.7, 0.7, 0.7, 1.0]),
+        }
+    }
+
+    /// The countdown at source time `time`.
+    pub fn at(&self, time: RationalTime) -> CountdownFrame {
+        let seconds = time.to_seconds_f64().max(0.0);
+        let elapsed = seconds.floor();
+        let number = (elapsed < self.from as f64).then(|| self.from - elapsed as u32);
+        CountdownFrame {
+            number,
+            sweep: (seconds - elapsed) as f32,
+            background: self.background.at(time),
+            foreground: self.foreground.at(time),
+            sweep_color: self.sweep.at(time),
+        }
+    }
+}
+
+/// Horizontal alignment of text lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum TextAlign {
+    Left,
+    #[default]
+    Center,
+    Right,
+}
+
+impl TextAlign {
+    /// Parse an alignment name ("left", "center", "right"), as stored in
+    /// style preferences.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "left" => Some(Self::Left),
+            "center" | "centre" => Some(Self::Center),
+            "right" => Some(Self::Right),
+            _ => None,
+        }
+    }
+}
+
+/// The font a text layer asks for. Missing fonts fall back to the closest
+/// available face, and characters a face lacks to any face that has them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FontSpec {
+    /// Family name (e.g. "DejaVu Sans"); empty for the default sans-serif
+    pub family: String,
+    /// Weight, 100 (thin) to 900 (black); 400 is regular
+    pub weight: u16,
+    /// Italic or oblique style
+    pub italic: bool,
+}
+
+impl Default for FontSpec {
+    fn default() -> Self {
+        Self {
+            family: String::new(),
+            weight: 400,
+            italic: false,
+        }
+    }
+}
+
+/// An outline around the glyphs, drawn under the fill.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TextStroke {
+    /// sRGB colour, straight alpha
+    pub color: [f32; 4],
+    /// Width in pixels of a 1080-line frame
+    pub width: f32,
+}
+
+/// A drop shadow under the text and its stroke.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TextShadow {
+    /// sRGB colour, straight alpha
+    pub color: [f32; 4],
+    /// Offset in pixels of a 1080-line frame (positive is right and down)
+    pub offset: [f32; 2],
+    /// Blur radius in pixels of a 1080-line frame
+    pub blur: f32,
+}
+
+/// A text layer: styled, laid-out text with animatable properties.
+///
+/// Lengths are in pixels of a 1080-line frame and scale with the output
+/// height. Keyframes are on the clip's source time (`source_in` plus the
+/// time into the clip), so splits and trims leave the animation in place.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TextGenerator {
+    /// The text; `\n` starts a new line
+    pub text: String,
+    /// Requested font
+    pub font: FontSpec,
+    /// Line alignment within the text block
+    pub align: TextAlign,
+    /// Wrap lines longer than this fraction of the frame width
+    pub wrap_width: Option<f32>,
+    /// Fill colour (sRGB, straight alpha)
+    pub fill: [f32; 4],
+    /// Optional outline
+    pub stroke: Option<TextStroke>,
+    /// Optional drop shadow
+    pub shadow: Option<TextShadow>,
+    /// Font size
+    pub size: KeyframeTrack,
+    /// Extra space between characters, in thousandths of an em
+    pub tracking: KeyframeTrack,
+    /// Distance between baselines, as a multiple of the font size
+    pub leading: KeyframeTrack,
+    /// Horizontal position of the block's anchor (0 = left, 1 = right
+    /// edge of the frame); the anchor is the block's centre, or its left or
+    /// right edge for left or right aligned text
+    pub position_x: KeyframeTrack,
+    /// Vertical position of the block's centre (0 = top, 1 = bottom)
+    pub position_y: KeyframeTrack,
+    /// Rotation about the anchor, in degrees clockwise
+    pub rotation: KeyframeTrack,
+    /// Opacity, 0 to 1
+    pub opacity: KeyframeTrack,
+}
+
+/// A text layer's animatable properties at one instant.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextFrame {
+    pub size: f32,
+    pub tracking: f32,
+    pub leading: f32,
+    pub position: [f32; 2],
+    pub rotation: f32,
+    pub opacity: f32,
+}
+
+impl TextGenerator {
+    /// White centred text at `size`, in the lower third of the frame.
+    pub fn new(text: impl Into<String>, size: f32) -> Self {
+        Self {
+            text: text.into(),
+            font: FontSpec::default(),
+            align: TextAlign::Center,
+            wrap_width: None,
+            fill: [1.0, 1.0, 1.0, 1.0],
+            stroke: None,
+            shadow: None,
+            size: KeyframeTrack::constant("size", size as f64),
+            tracking: KeyframeTrack::constant("tracking", 0.0),
+            leading: KeyframeTrack::constant("leading", 1.2),
+            position_x: KeyframeTrack::constant("position_x", 0.5),
+            position_y: KeyframeTrack::constant("position_y", 0.85),
+            rotation: KeyframeTrack::constant("rotation", 0.0),
+            opacity: KeyframeTrack::constant("opacity", 1.0),
+        }
+    }
+
+    /// Evaluate the animatable properties at source time `time`.
+    pub fn at(&self, time: RationalTime) -> TextFrame {
+        let value = |track: &KeyframeTrack| track.evaluate(time) as f32;
+        TextFrame {
+            size: value(&self.size).max(0.0),
+            tracking: value(&self.tracking),
+            leading: value(&self.leading),
+            position: [value(&self.position_x), value(&self.position_y)],
+            rotation: value(&self.rotation),
+            opacity: value(&self.opacity).clamp(0.0, 1.0),
+        }
+    }
+
+    /// Whether any property changes over time.
+    pub fn is_animated(&self) -> bool {
+        [
+            &self.size,
+            &self.tracking,
+            &self.leading,
+            &self.position_x,
+            &self.position_y,
+            &self.rotation,
+            &self.opacity,
+        ]
+        .iter()
+        .any(|track| track.is_animated())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_properties_animate() {
+        let mut title = TextGenerator::new("Hello", 48.0);
+        assert!(!title.is_animated());
+        title.opacity = KeyframeTrack::new("opacity");
+        title
+            .opacity
+            .set(RationalTime::ZERO, 0.0, EasingCurve::Linear);
+        title
+            .opacity
+            .set(RationalTime::new(1, 1), 1.0, EasingCurve::Linear);
+        assert!(title.is_animated());
+
+        let half = title.at(RationalTime::new(1, 2));
+        assert!((half.opacity - 0.5).abs() < 1e-6);
+        assert_eq!(half.size, 48.0);
+        assert_eq!(half.position, [0.5, 0.85]);
+        assert_eq!(title.at(RationalTime::new(5, 1)).opacity, 1.0);
+    }
+
+    #[test]
+    fn test_align_from_name() {
+        assert_eq!(TextAlign::from_name("Center"), Some(TextAlign::Center));
+        assert_eq!(TextAlign::from_name("right"), Some(TextAlign::Right));
+        assert_eq!(TextAlign::from_name("justify"), None);
+    }
+
+    #[test]
+    fn test_color_track_animates_per_channel() {
+        let mut color = ColorTrack::constant("color", [1.0, 0.0, 0.0, 1.0]);
+        assert!(!color.is_animated());
+        color.set(
+            RationalTime::ZERO,
+            [1.0, 0.0, 0.0, 1.0],
+            EasingCurve::Linear,
+        );
+        color.set(
+            RationalTime::new(2, 1),
+            [0.0, 0.0, 1.0, 0.5],
+            EasingCurve::Linear,
+        );
+        assert!(color.is_animated());
+        assert_eq!(color.at(RationalTime::new(1, 1)), [0.5, 0.0, 0.5, 0.75]);
+        assert!(Generator::Solid(SolidGenerator { color }).is_animated());
+        assert!(!Generator::Bars(BarsGenerator::default()).is_animated());
+    }
+
+    #[test]
+    fn test_countdown_counts_seconds() {
+        let countdown = CountdownGenerator::new(8);
+        let start = countdown.at(RationalTime::ZERO);
+        assert_eq!(start.number, Some(8));
+        assert_eq!(start.sweep, 0.0);
+        let later = countdown.at(RationalTime::new(27, 4));
+        assert_eq!(later.number, Some(2));
+        assert_eq!(later.sweep, 0.75);
+        assert_eq!(countdown.at(RationalTime::new(7, 1)).number, Some(1));
+        assert_eq!(countdown.at(RationalTime::new(8, 1)).number, None);
+    }
+}
@@ -1,19 +1,35 @@
 //! ProEdit Timeline - Timeline data model
 //!
 //! Implements the timeline structure for video editing:
-//! - Projects containing sequences
-//! - Tracks containing clips
-//! - Edit operations with undo/redo
+//! - Projects containing multiple sequences with per-sequence settings
+//! - Tracks containing clips and adjustment layers, with video effect stacks
+//! - Generated clips (titles, mattes, gradients, noise, bars and tone,
+//!   countdowns) that need no media
+//! - Edit operations with undo/redo, merging, grouping and persistent history
 //! - Professional trim modes (ripple, roll, slip, slide)
+//! - Validation and repair of loaded timelines
+//! - Append-only operation logs that can be merged between editors
 
 pub mod clip;
 pub mod edit;
+pub mod effect;
+pub mod generator;
+pub mod oplog;
 pub mod project;
 pub mod serialization;
 pub mod track;
+pub mod validate;
 
-pub use clip::{Clip, ClipRef};
-pub use edit::{EditCommand, TrimMode, UndoStack};
-pub use project::{Project, Sequence};
-pub use serialization::{ProjectFile, RecentProjects};
-pub use track::{Track, TrackItem, TrackKind};
+pub use clip::{AlternateTake, Clip, ClipAudio, ClipRef, Fade, FadeCurve, StretchMode};
+pub use edit::{EditCommand, HistoryCommand, HistoryEntry, TrimMode, UndoStack};
+pub use effect::{AdjustmentLayer, EffectInstance};
+pub use generator::{
+    BarsGenerator, ColorTrack, CountdownFrame, CountdownGenerator, FontSpec, Generator,
+    GradientFrame, GradientGenerator, GradientKind, NoiseFrame, NoiseGenerator, SolidGenerator,
+    TextAlign, TextFrame, TextGenerator, TextShadow, TextStroke,
+};
+pub use oplog::{Anchor, Conflict, ConflictReason, MergeResult, Operation, OperationLog};
+pub use project::{resolve_media_path, ConformMode, Project, Sequence, SequenceSettings};
+pub use serialization::{HistoryFile, ProjectFile, RecentProjects};
+pub use track::{Track, TrackAutomation, TrackItem, TrackKind};
+pub use validate::{Diagnostic, Issue, Severity};
@@ -0,0 +1,1103 @@
+//! Append-only operation log for merging concurrent edits.
+//!
+//! Every `EditCommand` is recorded together with its author, a timestamp and
+//! stable `Uuid` anchors for the clips and tracks it touches. Because
+//! commands address clips by index, the anchors let an operation be
+//! re-resolved after other edits have shifted the track contents, which is
+//! what makes rebasing one editor's log onto another's possible.
+//!
+//! Logs are plain JSON Lines files: one operation per line, only ever
+//! appended to, so two editors can exchange them without a server.
+
+use proedit_core::{ProEditError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+use crate::edit::EditCommand;
+use crate::project::{Project, Sequence};
+use crate::track::{Track, TrackItem};
+
+/// A stable reference captured when an operation is recorded.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Anchor {
+    /// The command addresses this clip by index.
+    Clip { track_id: Uuid, clip_id: Uuid },
+    /// The command inserts an item `skip` items after this clip
+    /// (`None` = counted from the start of the track).
+    InsertAfter {
+        track_id: Uuid,
+        clip_id: Option<Uuid>,
+        skip: usize,
+    },
+    /// The command addressed an index holding no clip when recorded. There
+    /// is nothing to follow, so it never resolves and replaying the
+    /// operation fails rather than hitting whatever is there now.
+    Index { track_id: Uuid, index: usize },
+    /// The command addresses a whole track.
+    Track { track_id: Uuid },
+}
+
+impl Anchor {
+    fn track_id(&self) -> Uuid {
+        match self {
+            Anchor::Clip { track_id, .. }
+            | Anchor::InsertAfter { track_id, .. }
+            | Anchor::Index { track_id, .. }
+            | Anchor::Track { track_id } => *track_id,
+        }
+    }
+}
+
+/// Anchors for one non-batch command inside an operation.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StepRefs {
+    /// References resolved before the command is applied.
+    pub anchors: Vec<Anchor>,
+    /// ID of a clip the command created (e.g. the right half of a split),
+    /// pinned so that replays produce the same ID.
+    pub created_clip: Option<Uuid>,
+}
+
+/// One recorded edit.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Operation {
+    /// Unique operation ID.
+    pub id: Uuid,
+    /// Who made the edit.
+    pub author: String,
+    /// When the edit was made (unix milliseconds).
+    pub timestamp: u64,
+    /// Sequence the edit applies to.
+    pub sequence_id: Uuid,
+    /// The command as applied by its author.
+    pub command: EditCommand,
+    /// Anchors for each non-batch command, in application order.
+    pub steps: Vec<StepRefs>,
+}
+
+impl Operation {
+    /// Clips this operation modifies (insertion points are not included).
+    pub fn touched_clips(&self) -> HashSet<Uuid> {
+        let mut clips: HashSet<Uuid> = self
+            .steps
+            .iter()
+            .flat_map(|s| &s.anchors)
+            .filter_map(|a| match a {
+                Anchor::Clip { clip_id, .. } => Some(*clip_id),
+                _ => None,
+            })
+            .collect();
+        clips.extend(self.steps.iter().filter_map(|s| s.created_clip));
+        clips
+    }
+
+    /// Tracks this operation reads or modifies.
+    pub fn touched_tracks(&self) -> HashSet<Uuid> {
+        self.steps
+            .iter()
+            .flat_map(|s| &s.anchors)
+            .map(Anchor::track_id)
+            .collect()
+    }
+
+    /// Tracks this operation deletes.
+    pub fn removed_tracks(&self) -> HashSet<Uuid> {
+        let mut removed = HashSet::new();
+        for_each_step(&self.command, &mut |command| {
+            if let EditCommand::RemoveTrack { track_id, .. } = command {
+                removed.insert(*track_id);
+            }
+        });
+        removed
+    }
+
+    /// Tracks whose items this operation reads or modifies (setting a
+    /// track's inserts leaves its items alone).
+    pub fn item_tracks(&self) -> HashSet<Uuid> {
+        let mut sets_inserts = Vec::new();
+        for_each_step(&self.command, &mut |command| {
+            sets_inserts.push(matches!(command, EditCommand::SetInserts { .. }));
+        });
+        self.steps
+            .iter()
+            .zip(sets_inserts)
+            .filter(|(_, inserts)| !inserts)
+            .flat_map(|(step, _)| &step.anchors)
+            .map(Anchor::track_id)
+            .collect()
+    }
+
+    /// Tracks whose items this operation replaces as a whole. Replaying it
+    /// overwrites every other edit made to them meanwhile.
+    pub fn replaced_tracks(&self) -> HashSet<Uuid> {
+        let mut replaced = HashSet::new();
+        for_each_step(&self.command, &mut |command| {
+            if let EditCommand::ReplaceItems { track_id, .. } = command {
+                replaced.insert(*track_id);
+            }
+        });
+        replaced
+    }
+
+    /// Tracks whose inserts this operation sets (`None` = the master's).
+    pub fn insert_targets(&self) -> HashSet<Option<Uuid>> {
+        let mut targets = HashSet::new();
+        for_each_step(&self.command, &mut |command| {
+            if let EditCommand::SetInserts { track_id, .. } = command {
+                targets.insert(*track_id);
+            }
+        });
+        targets
+    }
+}
+
+/// Call `f` with every non-batch command in `command`.
+fn for_each_step(command: &EditCommand, f: &mut impl FnMut(&EditCommand)) {
+    match command {
+        EditCommand::Batch(commands) => {
+            for c in commands {
+                for_each_step(c, f);
+            }
+        }
+        _ => f(command),
+    }
+}
+
+/// Why an operation could not be merged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConflictReason {
+    /// Both sides edited the same clip.
+    SameClip(Uuid),
+    /// One side deleted a track the other side edited.
+    TrackRemoved(Uuid),
+    /// One side replaced the items of a track the other side edited.
+    TrackReplaced(Uuid),
+    /// Both sides changed the inserts of the same track (`None` = the
+    /// master's).
+    SameInserts(Option<Uuid>),
+    /// Something the operation refers to no longer exists after rebasing.
+    MissingTarget,
+}
+
+/// An operation from the rebased log that was left out of the merge.
+#[derive(Debug, Clone)]
+pub struct Conflict {
+    /// Our operation that was not applied.
+    pub ours: Operation,
+    /// Their operation it collided with, if a single one is responsible.
+    pub theirs: Option<Operation>,
+    /// What went wrong.
+    pub reason: ConflictReason,
+}
+
+/// Result of rebasing one log onto another.
+#[derive(Debug)]
+pub struct MergeResult {
+    /// Their operations followed by our rebased, non-conflicting operations.
+    pub log: OperationLog,
+    /// The base project with the merged log applied.
+    pub project: Project,
+    /// Our operations that could not be applied.
+    pub conflicts: Vec<Conflict>,
+}
+
+/// Append-only log of edit operations.
+#[derive(Debug, Clone, Default)]
+pub struct OperationLog {
+    operations: Vec<Operation>,
+}
+
+impl OperationLog {
+    /// Create an empty log.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Recorded operations, oldest first.
+    pub fn operations(&self) -> &[Operation] {
+        &self.operations
+    }
+
+    /// Number of operations.
+    pub fn len(&self) -> usize {
+        self.operations.len()
+    }
+
+    /// Check if the log is empty.
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+
+    /// Apply a command to a sequence and append it to the log.
+    ///
+    /// This does not touch the sequence's undo history; undoing an edit is
+    /// recorded as a new operation.
+    pub fn record(
+        &mut self,
+        author: impl Into<String>,
+        sequence: &mut Sequence,
+        mut command: EditCommand,
+    ) -> &Operation {
+        let mut steps = Vec::new();
+        record_steps(&mut command, sequence, &mut steps);
+        self.operations.push(Operation {
+            id: Uuid::new_v4(),
+            author: author.into(),
+            timestamp: now_millis(),
+            sequence_id: sequence.id,
+            command,
+            steps,
+        });
+        self.operations.last().expect("just pushed")
+    }
+
+    /// Append an already-recorded operation (e.g. one read from another log).
+    pub fn push(&mut self, operation: Operation) {
+        self.operations.push(operation);
+    }
+
+    /// Apply every operation in the log to a project.
+    pub fn replay(&self, project: &mut Project) -> Result<()> {
+        for op in &self.operations {
+            apply_operation(op, project).ok_or_else(|| {
+                ProEditError::Timeline(format!("Operation {} could not be applied", op.id))
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Rebase this log ("ours") onto `theirs`.
+    ///
+    /// Both logs must start from the same `base` project. Operations up to
+    /// the first divergence are shared; our operations after that point are
+    /// re-resolved on top of all of theirs. Operations that touch a clip
+    /// their new operations also touched, a track either side removed or
+    /// replaced whole, or inserts both sides changed are reported as
+    /// conflicts instead of being applied.
+    pub fn rebase_onto(&self, theirs: &OperationLog, base: &Project) -> Result<MergeResult> {
+        let shared = self
+            .operations
+            .iter()
+            .zip(&theirs.operations)
+            .take_while(|(a, b)| a.id == b.id)
+            .count();
+        let their_ids: HashSet<Uuid> = theirs.operations.iter().map(|op| op.id).collect();
+        let their_new = &theirs.operations[shared..];
+
+        let mut project = base.clone();
+        theirs.replay(&mut project)?;
+
+        let mut log = theirs.clone();
+        let mut conflicts = Vec::new();
+        for ours in &self.operations[shared..] {
+            if their_ids.contains(&ours.id) {
+                continue;
+            }
+            if let Some((theirs, reason)) = find_conflict(ours, their_new) {
+                conflicts.push(Conflict {
+                    ours: ours.clone(),
+                    theirs: Some(theirs.clone()),
+                    reason,
+                });
+                continue;
+            }
+            match apply_operation(ours, &mut project) {
+                Some(rebased) => log.operations.push(rebased),
+                None => conflicts.push(Conflict {
+                    ours: ours.clone(),
+                    theirs: None,
+                    reason: ConflictReason::MissingTarget,
+                }),
+            }
+        }
+
+        Ok(MergeResult {
+            log,
+            project,
+            conflicts,
+        })
+    }
+
+    /// Append a single operation to a log file.
+    pub fn append_to_file(path: &Path, operation: &Operation) -> Result<()> {
+        let mut line = serde_json::to_string(operation).map_err(|e| {
+            ProEditError::Serialization(format!("Failed to serialize operation: {}", e))
+        })?;
+        line.push('\n');
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        file.write_all(line.as_bytes())?;
+        Ok(())
+    }
+
+    /// Write the whole log to a file, replacing its contents.
+    pub fn save_to_file(&self, path: &Path) -> Result<()> {
+        let mut data = String::new();
+        for op in &self.operations {
+            data.push_str(&serde_json::to_string(op).map_err(|e| {
+                ProEditError::Serialization(format!("Failed to serialize operation: {}", e))
+            })?);
+            data.push('\n');
+        }
+        std::fs::write(path, data)?;
+        Ok(())
+    }
+
+    /// Read a log file written by [`save_to_file`](Self::save_to_file) or
+    /// [`append_to_file`](Self::append_to_file).
+    pub fn load_from_file(path: &Path) -> Result<Self> {
+        let data = std::fs::read_to_string(path)?;
+        let operations = data
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(n, line)| {
+                serde_json::from_str(line).map_err(|e| {
+                    ProEditError::Serialization(format!(
+                        "Invalid operation on line {}: {}",
+                        n + 1,
+                        e
+                    ))
+                })
+            })
+            .collect::<Result<Vec<Operation>>>()?;
+        Ok(Self { operations })
+    }
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn find_conflict<'a>(
+    ours: &Operation,
+    their_new: &'a [Operation],
+) -> Option<(&'a Operation, ConflictReason)> {
+    let our_clips = ours.touched_clips();
+    let our_tracks = ours.touched_tracks();
+    let our_removed = ours.removed_tracks();
+    let our_replaced = ours.replaced_tracks();
+    let our_items = ours.item_tracks();
+    let our_inserts = ours.insert_targets();
+
+    their_new
+        .iter()
+        .filter(|theirs| theirs.sequence_id == ours.sequence_id)
+        .find_map(|theirs| {
+            if let Some(clip) = theirs.touched_clips().intersection(&our_clips).next() {
+                return Some((theirs, ConflictReason::SameClip(*clip)));
+            }
+            let their_tracks = theirs.touched_tracks();
+            let their_removed = theirs.removed_tracks();
+            if let Some(track) = their_removed.intersection(&our_tracks).next() {
+                return Some((theirs, ConflictReason::TrackRemoved(*track)));
+            }
+            if let Some(track) = our_removed.intersection(&their_tracks).next() {
+                return Some((theirs, ConflictReason::TrackRemoved(*track)));
+            }
+            let their_replaced = theirs.replaced_tracks();
+            if let Some(track) = their_replaced.intersection(&our_items).next() {
+                return Some((theirs, ConflictReason::TrackReplaced(*track)));
+            }
+            if let Some(track) = our_replaced.intersection(&theirs.item_tracks()).next() {
+                return Some((theirs, ConflictReason::TrackReplaced(*track)));
+            }
+            theirs
+                .insert_targets()
+                .intersection(&our_inserts)
+                .next()
+                .map(|track| (theirs, ConflictReason::SameInserts(*track)))
+        })
+}
+
+// ── Recording ────────────────────────────────────────────────────
+
+fn find_track(sequence: &Sequence, track_id: Uuid) -> Option<&Track> {
+    sequence
+        .video_tracks
+        .iter()
+        .chain(sequence.audio_tracks.iter())
+        .find(|t| t.id == track_id)
+}
+
+fn find_track_mut(sequence: &mut Sequence, track_id: Uuid) -> Option<&mut Track> {
+    sequence
+        .video_tracks
+        .iter_mut()
+        .chain(sequence.audio_tracks.iter_mut())
+        .find(|t| t.id == track_id)
+}
+
+fn clip_anchor(sequence: &Sequence, track_id: Uuid, index: usize) -> Anchor {
+    match find_track(sequence, track_id).and_then(|t| t.clip_at(index)) {
+        Some(clip) => Anchor::Clip {
+            track_id,
+            clip_id: clip.id,
+        },
+        None => Anchor::Index { track_id, index },
+    }
+}
+
+fn insert_anchor(track_id: Uuid, items: &[TrackItem], index: usize) -> Anchor {
+    let index = index.min(items.len());
+    let previous_clip = items[..index]
+        .iter()
+        .enumerate()
+        .rev()
+        .find_map(|(i, item)| match item {
+            TrackItem::Clip(clip) => Some((i, clip.id)),
+            _ => None,
+        });
+    match previous_clip {
+        Some((i, clip_id)) => Anchor::InsertAfter {
+            track_id,
+            clip_id: Some(clip_id),
+            skip: index - i - 1,
+        },
+        None => Anchor::InsertAfter {
+            track_id,
+            clip_id: None,
+            skip: index,
+        },
+    }
+}
+
+fn step_anchors(command: &EditCommand, sequence: &Sequence) -> Vec<Anchor> {
+    match command {
+        EditCommand::InsertClip {
+            track_id, index, ..
+        } => {
+            let items = find_track(sequence, *track_id)
+                .map(|t| t.items.as_slice())
+                .unwrap_or_default();
+            vec![insert_anchor(*track_id, items, *index)]
+        }
+        EditCommand::MoveClip {
+            src_track_id,
+            src_index,
+            dst_track_id,
+            dst_index,
+        } => {
+            let source = clip_anchor(sequence, *src_track_id, *src_index);
+            let mut items = find_track(sequence, *dst_track_id)
+                .map(|t| t.items.clone())
+                .unwrap_or_default();
+            if src_track_id == dst_track_id && *src_index < items.len() {
+                items.remove(*src_index);
+            }
+            vec![source, insert_anchor(*dst_track_id, &items, *dst_index)]
+        }
+        EditCommand::RollTrim {
+            track_id,
+            clip_index,
+            ..
+        } => {
+            let mut anchors = vec![clip_anchor(sequence, *track_id, *clip_index)];
+            if let Some(next) =
+                find_track(sequence, *track_id).and_then(|t| t.clip_at(clip_index + 1))
+            {
+                anchors.push(Anchor::Clip {
+                    track_id: *track_id,
+                    clip_id: next.id,
+                });
+            }
+            anchors
+        }
+        EditCommand::RemoveClip {
+            track_id, index, ..
+        } => vec![clip_anchor(sequence, *track_id, *index)],
+        EditCommand::RippleTrim {
+            track_id,
+            clip_index,
+            ..
+        }
+        | EditCommand::Slip {
+            track_id,
+            clip_index,
+            ..
+        }
+        | EditCommand::Slide {
+            track_id,
+            clip_index,
+            ..
+        }
+        | EditCommand::SplitClip {
+            track_id,
+            clip_index,
+            ..
+        }
+        | EditCommand::ToggleClipEnabled {
+            track_id,
+            clip_index,
+        }
+        | EditCommand::SetClipSpeed {
+            track_id,
+            clip_index,
+            ..
+        }
+        | EditCommand::UpdateClip {
+            track_id,
+            clip_index,
+            ..
+        } => vec![clip_anchor(sequence, *track_id, *clip_index)],
+        EditCommand::RemoveTrack { track_id, .. }
+        | EditCommand::ReplaceItems { track_id, .. }
+        | EditCommand::SetInserts {
+            track_id: Some(track_id),
+            ..
+        } => {
+            vec![Anchor::Track {
+                track_id: *track_id,
+            }]
+        }
+        EditCommand::AddTrack { .. }
+        | EditCommand::SetInserts { track_id: None, .. }
+        | EditCommand::SetMasterGain { .. }
+        | EditCommand::Batch(_) => Vec::new(),
+    }
+}
+
+fn created_clip(command: &EditCommand, sequence: &Sequence) -> Option<Uuid> {
+    match command {
+        EditCommand::SplitClip {
+            track_id,
+            clip_index,
+            ..
+        } => find_track(sequence, *track_id)
+            .and_then(|t| t.clip_at(clip_index + 1))
+            .map(|c| c.id),
+        _ => None,
+    }
+}
+
+fn record_steps(command: &mut EditCommand, sequence: &mut Sequence, steps: &mut Vec<StepRefs>) {
+    if let EditCommand::Batch(commands) = command {
+        for c in commands {
+            record_steps(c, sequence, steps);
+        }
+        return;
+    }
+    let anchors = step_anchors(command, sequence);
+    command.apply(sequence);
+    steps.push(StepRefs {
+        anchors,
+        created_clip: created_clip(command, sequence),
+    });
+}
+
+// ── Replaying ────────────────────────────────────────────────────
+
+fn clip_index(sequence: &Sequence, anchor: &Anchor) -> Option<usize> {
+    match anchor {
+        Anchor::Clip { track_id, clip_id } => find_track(sequence, *track_id)?
+            .find_clip(*clip_id)
+            .map(|(i, _)| i),
+        _ => None,
+    }
+}
+
+fn insert_index(
+    sequence: &Sequence,
+    anchor: &Anchor,
+    removed_from: Option<usize>,
+) -> Option<usize> {
+    let Anchor::InsertAfter {
+        track_id,
+        clip_id,
+        skip,
+    } = anchor
+    else {
+        return None;
+    };
+    let track = find_track(sequence, *track_id)?;
+    let start = match clip_id {
+        Some(id) => {
+            let (i, _) = track.find_clip(*id)?;
+            // Account for the moved clip leaving the same track first.
+            match removed_from {
+                Some(removed) if removed < i => i,
+                _ => i + 1,
+            }
+        }
+        None => 0,
+    };
+    Some(start + skip)
+}
+
+/// Re-resolve a command's indices from its anchors against `sequence`.
+fn resolve_step(
+    command: &EditCommand,
+    refs: &StepRefs,
+    sequence: &Sequence,
+) -> Option<EditCommand> {
+    let mut resolved = command.clone();
+    let anchors = &refs.anchors;
+    match &mut resolved {
+        EditCommand::InsertClip {
+            track_id, index, ..
+        } => {
+            *index = insert_index(sequence, anchors.first()?, None)?;
+            *track_id = anchors.first()?.track_id();
+        }
+        EditCommand::MoveClip {
+            src_track_id,
+            src_index,
+            dst_track_id,
+            dst_index,
+        } => {
+            *src_index = clip_index(sequence, anchors.first()?)?;
+            let same_track = src_track_id == dst_track_id;
+            let removed_from = same_track.then_some(*src_index);
+            *dst_index = insert_index(sequence, anchors.get(1)?, removed_from)?;
+        }
+        EditCommand::RemoveClip { index, .. } => {
+            *index = clip_index(sequence, anchors.first()?)?;
+        }
+        EditCommand::RippleTrim { clip_index: i, .. }
+        | EditCommand::RollTrim { clip_index: i, .. }
+        | EditCommand::Slip { clip_index: i, .. }
+        | EditCommand::Slide { clip_index: i, .. }
+        | EditCommand::SplitClip { clip_index: i, .. }
+        | EditCommand::ToggleClipEnabled { clip_index: i, .. }
+        | EditCommand::SetClipSpeed { clip_index: i, .. }
+        | EditCommand::UpdateClip { clip_index: i, .. } => {
+            *i = clip_index(sequence, anchors.first()?)?;
+        }
+        EditCommand::RemoveTrack { track_id, .. }
+        | EditCommand::ReplaceItems { track_id, .. }
+        | EditCommand::SetInserts {
+            track_id: Some(track_id),
+            ..
+        } => {
+            find_track(sequence, *track_id)?;
+        }
+        EditCommand::AddTrack { .. }
+        | EditCommand::SetInserts { track_id: None, .. }
+        | EditCommand::SetMasterGain { .. }
+        | EditCommand::Batch(_) => {}
+    }
+    Some(resolved)
+}
+
+/// Resolve and apply a (possibly batched) command step by step.
+fn replay_steps<'a>(
+    command: &EditCommand,
+    steps: &mut impl Iterator<Item = &'a StepRefs>,
+    sequence: &mut Sequence,
+) -> Option<EditCommand> {
+    if let EditCommand::Batch(commands) = command {
+        let resolved = commands
+            .iter()
+            .map(|c| replay_steps(c, steps, sequence))
+            .collect::<Option<Vec<_>>>()?;
+        return Some(EditCommand::Batch(resolved));
+    }
+
+    let refs = steps.next()?;
+    let mut resolved = resolve_step(command, refs, sequence)?;
+    resolved.apply(sequence);
+
+    if let (
+        Some(id),
+        EditCommand::SplitClip {
+            track_id,
+            clip_index,
+            ..
+        },
+    ) = (refs.created_clip, &resolved)
+    {
+        if let Some(clip) =
+            find_track_mut(sequence, *track_id).and_then(|t| t.clip_at_mut(clip_index + 1))
+        {
+            clip.id = id;
+        }
+    }
+    Some(resolved)
+}
+
+/// Apply an operation to its sequence in `project`.
+///
+/// Returns the operation with indices re-resolved against the project, or
+/// `None` (leaving the project untouched) if it cannot be applied.
+fn apply_operation(op: &Operation, project: &mut Project) -> Option<Operation> {
+    let sequence = project.sequence_mut(op.sequence_id)?;
+    let mut scratch = sequence.clone();
+    let command = replay_steps(&op.command, &mut op.steps.iter(), &mut scratch)?;
+    *sequence = scratch;
+    Some(Operation {
+        command,
+        ..op.clone()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clip::{Clip, ClipRef};
+    use proedit_core::RationalTime;
+
+    fn clip(name: &str) -> Clip {
+        Clip::new(
+            name,
+            ClipRef::new(format!("{name}.mp4"), RationalTime::new(10, 1)),
+        )
+    }
+
+    /// Base project with one sequence holding clips a, b, c on V1.
+    fn base() -> (Project, Uuid, Uuid) {
+        let mut project = Project::new("Shared");
+        let mut seq = Sequence::default();
+        for name in ["a", "b", "c"] {
+            seq.video_tracks[0].append_clip(clip(name));
+        }
+        let ids = (seq.id, seq.video_tracks[0].id);
+        project.add_sequence(seq);
+        (project, ids.0, ids.1)
+    }
+
+    fn names(project: &Project, seq: Uuid) -> Vec<String> {
+        project.sequence(seq).unwrap().video_tracks[0]
+            .items
+            .iter()
+            .filter_map(|i| match i {
+                TrackItem::Clip(c) => Some(c.name.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_record_and_replay() {
+        let (base, seq_id, track) = base();
+        let mut working = base.clone();
+        let mut log = OperationLog::new();
+        let seq = working.sequence_mut(seq_id).unwrap();
+        log.record(
+            "alice",
+            seq,
+            EditCommand::SplitClip {
+                track_id: track,
+                clip_index: 0,
+                offset: RationalTime::new(4, 1),
+                original: None,
+            },
+        );
+        log.record(
+            "alice",
+            seq,
+            EditCommand::ToggleClipEnabled {
+                track_id: track,
+                clip_index: 1,
+            },
+        );
+        assert_eq!(log.len(), 2);
+        assert_eq!(log.operations()[0].author, "alice");
+
+        let mut replayed = base.clone();
+        log.replay(&mut replayed).unwrap();
+        let expected = &working.sequence(seq_id).unwrap().video_tracks[0];
+        let actual = &replayed.sequence(seq_id).unwrap().video_tracks[0];
+        // Split halves get the same IDs on replay, so later ops resolve.
+        assert_eq!(
+            actual.clip_at(1).unwrap().id,
+            expected.clip_at(1).unwrap().id
+        );
+        assert!(!actual.clip_at(1).unwrap().enabled);
+    }
+
+    #[test]
+    fn test_index_anchor_does_not_resolve() {
+        let (base, seq_id, track) = base();
+        let mut working = base.clone();
+        let mut log = OperationLog::new();
+        // Nothing at index 5 when recorded: a no-op for its author.
+        let op = log.record(
+            "alice",
+            working.sequence_mut(seq_id).unwrap(),
+            EditCommand::ToggleClipEnabled {
+                track_id: track,
+                clip_index: 5,
+            },
+        );
+        assert_eq!(
+            op.steps[0].anchors,
+            vec![Anchor::Index {
+                track_id: track,
+                index: 5
+            }]
+        );
+
+        // Replaying over a track that now has a clip there must not touch it.
+        let mut longer = base.clone();
+        let v1 = &mut longer.sequence_mut(seq_id).unwrap().video_tracks[0];
+        for name in ["d", "e", "f"] {
+            v1.append_clip(clip(name));
+        }
+        assert!(log.replay(&mut longer).is_err());
+        let v1 = &longer.sequence(seq_id).unwrap().video_tracks[0];
+        assert!(v1.clip_at(5).unwrap().enabled);
+    }
+
+    #[test]
+    fn test_rebase_shifts_indices() {
+        let (base, seq_id, track) = base();
+
+        // They insert a clip at the start of the track.
+        let mut theirs_project = base.clone();
+        let mut theirs = OperationLog::new();
+        theirs.record(
+            "bob",
+            theirs_project.sequence_mut(seq_id).unwrap(),
+            EditCommand::InsertClip {
+                track_id: track,
+                index: 0,
+                clip: clip("intro"),
+            },
+        );
+
+        // We disable "c" (index 2) and insert after "b".
+        let mut ours_project = base.clone();
+        let mut ours = OperationLog::new();
+        let seq = ours_project.sequence_mut(seq_id).unwrap();
+        ours.record(
+            "alice",
+            seq,
+            EditCommand::ToggleClipEnabled {
+                track_id: track,
+                clip_index: 2,
+            },
+        );
+        ours.record(
+            "alice",
+            seq,
+            EditCommand::InsertClip {
+                track_id: track,
+                index: 2,
+                clip: clip("insert"),
+            },
+        );
+
+        let merged = ours.rebase_onto(&theirs, &base).unwrap();
+        assert!(merged.conflicts.is_empty());
+        assert_eq!(merged.log.len(), 3);
+        assert_eq!(
+            names(&merged.project, seq_id),
+            ["intro", "a", "b", "insert", "c"]
+        );
+        let track = &merged.project.sequence(seq_id).unwrap().video_tracks[0];
+        assert!(!track.clip_at(4).unwrap().enabled);
+
+        // The merged log replays to the same state.
+        let mut replayed = base.clone();
+        merged.log.replay(&mut replayed).unwrap();
+        assert_eq!(names(&replayed, seq_id), names(&merged.project, seq_id));
+    }
+
+    #[test]
+    fn test_same_clip_edits_conflict() {
+        let (base, seq_id, track) = base();
+        let clip_b = base.sequence(seq_id).unwrap().video_tracks[0]
+            .clip_at(1)
+            .unwrap()
+            .id;
+
+        let mut theirs_project = base.clone();
+        let mut theirs = OperationLog::new();
+        theirs.record(
+            "bob",
+            theirs_project.sequence_mut(seq_id).unwrap(),
+            EditCommand::RemoveClip {
+                track_id: track,
+                index: 1,
+                removed: None,
+            },
+        );
+
+        let mut ours_project = base.clone();
+        let mut ours = OperationLog::new();
+        let seq = ours_project.sequence_mut(seq_id).unwrap();
+        ours.record(
+            "alice",
+            seq,
+            EditCommand::Slip {
+                track_id: track,
+                clip_index: 1,
+                delta: RationalTime::new(1, 1),
+            },
+        );
+        ours.record(
+            "alice",
+            seq,
+            EditCommand::ToggleClipEnabled {
+                track_id: track,
+                clip_index: 0,
+            },
+        );
+
+        let merged = ours.rebase_onto(&theirs, &base).unwrap();
+        assert_eq!(merged.conflicts.len(), 1);
+        let conflict = &merged.conflicts[0];
+        assert_eq!(conflict.reason, ConflictReason::SameClip(clip_b));
+        assert_eq!(conflict.theirs.as_ref().unwrap().author, "bob");
+        // The non-conflicting edit still went through.
+        assert_eq!(merged.log.len(), 2);
+        assert_eq!(names(&merged.project, seq_id), ["a", "c"]);
+    }
+
+    #[test]
+    fn test_track_removal_conflicts() {
+        let (base, seq_id, track) = base();
+        let mut theirs_project = base.clone();
+        let mut theirs = OperationLog::new();
+        theirs.record(
+            "bob",
+            theirs_project.sequence_mut(seq_id).unwrap(),
+            EditCommand::RemoveTrack {
+                track_id: track,
+                removed: None,
+                index: None,
+            },
+        );
+
+        let mut ours_project = base.clone();
+        let mut ours = OperationLog::new();
+        ours.record(
+            "alice",
+            ours_project.sequence_mut(seq_id).unwrap(),
+            EditCommand::InsertClip {
+                track_id: track,
+                index: 0,
+                clip: clip("x"),
+            },
+        );
+
+        let merged = ours.rebase_onto(&theirs, &base).unwrap();
+        assert_eq!(
+            merged.conflicts[0].reason,
+            ConflictReason::TrackRemoved(track)
+        );
+    }
+
+    #[test]
+    fn test_track_replacements_conflict() {
+        let (base, seq_id, track) = base();
+        let other = base.sequence(seq_id).unwrap().audio_tracks[0].id;
+        let replace = |project: &Project, track_id: Uuid, edit: &dyn Fn(&mut Track)| {
+            let before = find_track(project.sequence(seq_id).unwrap(), track_id)
+                .unwrap()
+                .clone();
+            let mut after = before.clone();
+            edit(&mut after);
+            EditCommand::ReplaceItems {
+                track_id,
+                before: before.items,
+                after: after.items,
+            }
+        };
+
+        // They drop "a"; we append "d" to the same track, then edit another.
+        let mut theirs_project = base.clone();
+        let mut theirs = OperationLog::new();
+        let command = replace(&theirs_project, track, &|t| {
+            t.items.remove(0);
+        });
+        theirs.record("bob", theirs_project.sequence_mut(seq_id).unwrap(), command);
+
+        let mut ours_project = base.clone();
+        let mut ours = OperationLog::new();
+        let command = replace(&ours_project, track, &|t| t.append_clip(clip("d")));
+        ours.record("alice", ours_project.sequence_mut(seq_id).unwrap(), command);
+        let command = replace(&ours_project, other, &|t| t.append_clip(clip("e")));
+        ours.record("alice", ours_project.sequence_mut(seq_id).unwrap(), command);
+
+        let merged = ours.rebase_onto(&theirs, &base).unwrap();
+        assert_eq!(merged.conflicts.len(), 1);
+        assert_eq!(
+            merged.conflicts[0].reason,
+            ConflictReason::TrackReplaced(track)
+        );
+        // Their edit survives; our edit to the other track still applies.
+        assert_eq!(names(&merged.project, seq_id), ["b", "c"]);
+        let merged_seq = merged.project.sequence(seq_id).unwrap();
+        assert_eq!(merged_seq.audio_tracks[0].clip_count(), 1);
+    }
+
+    #[test]
+    fn test_different_sequences_merge_cleanly() {
+        let (mut base, seq_id, track) = base();
+        let other_id = base.create_sequence("Other", Default::default());
+        let other_track = base.sequence(other_id).unwrap().video_tracks[0].id;
+
+        let mut theirs_project = base.clone();
+        let mut theirs = OperationLog::new();
+        theirs.record(
+            "bob",
+            theirs_project.sequence_mut(other_id).unwrap(),
+            EditCommand::InsertClip {
+                track_id: other_track,
+                index: 0,
+                clip: clip("b-roll"),
+            },
+        );
+
+        let mut ours_project = base.clone();
+        let mut ours = OperationLog::new();
+        ours.record(
+            "alice",
+            ours_project.sequence_mut(seq_id).unwrap(),
+            EditCommand::RemoveClip {
+                track_id: track,
+                index: 0,
+                removed: None,
+            },
+        );
+
+        let merged = ours.rebase_onto(&theirs, &base).unwrap();
+        assert!(merged.conflicts.is_empty());
+        assert_eq!(names(&merged.project, seq_id), ["b", "c"]);
+        assert_eq!(names(&merged.project, other_id), ["b-roll"]);
+
+        // Rebasing again after the merge changes nothing.
+        let again = merged.log.rebase_onto(&merged.log, &base).unwrap();
+        assert_eq!(again.log.len(), 2);
+    }
+
+    #[test]
+    fn test_log_file_roundtrip() {
+        let (mut base, seq_id, track) = base();
+        let dir = std::env::temp_dir().join(format!("proedit-oplog-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("alice.oplog");
+
+        let mut log = OperationLog::new();
+        let seq = base.sequence_mut(seq_id).unwrap();
+        for i in 0..2 {
+            let op = log.record(
+                "alice",
+                seq,
+                EditCommand::ToggleClipEnabled {
+                    track_id: track,
+                    clip_index: i,
+                },
+            );
+            OperationLog::append_to_file(&path, op).unwrap();
+        }
+
+        let loaded = OperationLog::load_from_file(&path).unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded.operations()[1].id, log.operations()[1].id);
+
+        std::fs::write(&path, "not json\n").unwrap();
+        assert!(OperationLog::load_from_file(&path).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
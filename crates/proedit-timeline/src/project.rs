@@ -1,10 +1,12 @@
 //! Project and sequence types.
 
-use proedit_core::{FrameRate, RationalTime, TimeRange};
+use proedit_core::{ColorSpace, FrameRate, ProEditError, RationalTime, Result, TimeRange};
 use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 use uuid::Uuid;
 
-use crate::track::Track;
+use crate::edit::{EditCommand, UndoStack};
+use crate::track::{Track, TrackItem};
 
 /// A project containing media references and sequences.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -17,6 +19,13 @@ pub struct Project {
     pub frame_rate: FrameRate,
     /// Sequences in this project
     pub sequences: Vec<Sequence>,
+    /// Sequence currently open for editing (falls back to the first sequence)
+    #[serde(default)]
+    pub active_sequence_id: Option<Uuid>,
+    /// Folder relative media paths are resolved against: that of the file
+    /// the project was loaded from. Not saved, so the project stays portable.
+    #[serde(skip)]
+    pub media_root: Option<PathBuf>,
 }
 
 impl Project {
@@ -27,6 +36,8 @@ impl Project {
             name: name.into(),
             frame_rate: FrameRate::FPS_24,
             sequences: Vec::new(),
+            active_sequence_id: None,
+            media_root: None,
         }
     }
 
@@ -35,14 +46,132 @@ impl Project {
         self.sequences.push(sequence);
     }
 
-    /// Get the active sequence (first one for now).
+    /// Get the active sequence.
+    ///
+    /// Falls back to the first sequence when no (valid) active ID is set.
     pub fn active_sequence(&self) -> Option<&Sequence> {
-        self.sequences.first()
+        let index = self.active_index()?;
+        self.sequences.get(index)
     }
 
     /// Get the active sequence mutably.
     pub fn active_sequence_mut(&mut self) -> Option<&mut Sequence> {
-        self.sequences.first_mut()
+        let index = self.active_index()?;
+        self.sequences.get_mut(index)
+    }
+
+    /// Make the sequence with the given ID the active one.
+    pub fn set_active_sequence(&mut self, id: Uuid) -> Result<()> {
+        self.index_of(id)?;
+        self.active_sequence_id = Some(id);
+        Ok(())
+    }
+
+    /// Get a sequence by ID.
+    pub fn sequence(&self, id: Uuid) -> Option<&Sequence> {
+        self.sequences.iter().find(|s| s.id == id)
+    }
+
+    /// Get a sequence mutably by ID.
+    pub fn sequence_mut(&mut self, id: Uuid) -> Option<&mut Sequence> {
+        self.sequences.iter_mut().find(|s| s.id == id)
+    }
+
+    /// Create a new sequence with the given settings and make it active.
+    pub fn create_sequence(&mut self, name: impl Into<String>, settings: SequenceSettings) -> Uuid {
+        let mut sequence =
+            Sequence::new(name, settings.width, settings.height, settings.frame_rate);
+        sequence.sample_rate = settings.sample_rate;
+        sequence.working_space = settings.working_space;
+        let id = sequence.id;
+        self.sequences.push(sequence);
+        self.active_sequence_id = Some(id);
+        id
+    }
+
+    /// Duplicate a sequence, inserting the copy right after the original.
+    ///
+    /// The copy gets fresh sequence, track and clip IDs and an empty history.
+    /// Returns the new sequence's ID.
+    pub fn duplicate_sequence(&mut self, id: Uuid) -> Result<Uuid> {
+        let index = self.index_of(id)?;
+        let mut copy = self.sequences[index].clone();
+        copy.id = Uuid::new_v4();
+        copy.name = self.unique_sequence_name(&format!("{} Copy", copy.name));
+        copy.history = UndoStack::default();
+        for track in copy
+            .video_tracks
+            .iter_mut()
+            .chain(copy.audio_tracks.iter_mut())
+        {
+            track.id = Uuid::new_v4();
+            for item in &mut track.items {
+                if let TrackItem::Clip(clip) = item {
+                    clip.id = Uuid::new_v4();
+                }
+            }
+        }
+        let new_id = copy.id;
+        self.sequences.insert(index + 1, copy);
+        Ok(new_id)
+    }
+
+    /// Rename a sequence.
+    pub fn rename_sequence(&mut self, id: Uuid, name: impl Into<String>) -> Result<()> {
+        let index = self.index_of(id)?;
+        self.sequences[index].name = name.into();
+        Ok(())
+    }
+
+    /// Delete a sequence and return it.
+    ///
+    /// If the deleted sequence was active, the neighbouring sequence becomes active.
+    pub fn delete_sequence(&mut self, id: Uuid) -> Result<Sequence> {
+        let was_active = self.active_sequence().map(|s| s.id) == Some(id);
+        let index = self.index_of(id)?;
+        let removed = self.sequences.remove(index);
+        if was_active {
+            self.active_sequence_id = self
+                .sequences
+                .get(index.min(self.sequences.len().saturating_sub(1)))
+                .map(|s| s.id);
+        }
+        Ok(removed)
+    }
+
+    /// Where the clip source at `path` is on disk. Relative paths (as in a
+    /// collected archive) stay relative in the project and are resolved
+    /// against `media_root` when the media is opened.
+    pub fn media_path(&self, path: &str) -> PathBuf {
+        resolve_media_path(self.media_root.as_deref(), path)
+    }
+
+    fn active_index(&self) -> Option<usize> {
+        if self.sequences.is_empty() {
+            return None;
+        }
+        let found = self
+            .active_sequence_id
+            .and_then(|id| self.sequences.iter().position(|s| s.id == id));
+        Some(found.unwrap_or(0))
+    }
+
+    fn index_of(&self, id: Uuid) -> Result<usize> {
+        self.sequences
+            .iter()
+            .position(|s| s.id == id)
+            .ok_or_else(|| ProEditError::NotFound(format!("Sequence {}", id)))
+    }
+
+    fn unique_sequence_name(&self, base: &str) -> String {
+        let taken = |name: &str| self.sequences.iter().any(|s| s.name == name);
+        if !taken(base) {
+            return base.to_string();
+        }
+        (2..)
+            .map(|n| format!("{} {}", base, n))
+            .find(|name| !taken(name))
+            .unwrap_or_else(|| base.to_string())
     }
 }
 
@@ -52,6 +181,53 @@ impl Default for Project {
     }
 }
 
+/// How clip timing is conformed when a sequence's frame rate changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ConformMode {
+    /// Keep wall-clock timing and snap every edit point to the new frame grid.
+    #[default]
+    SnapToFrames,
+    /// Keep every item's frame count, so items get shorter or longer in time.
+    PreserveFrameCount,
+}
+
+/// Sequence-level format settings.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SequenceSettings {
+    /// Resolution width
+    pub width: u32,
+    /// Resolution height
+    pub height: u32,
+    /// Frame rate
+    pub frame_rate: FrameRate,
+    /// Audio sample rate in Hz
+    pub sample_rate: u32,
+    /// Working color space for compositing
+    pub working_space: ColorSpace,
+}
+
+impl Default for SequenceSettings {
+    fn default() -> Self {
+        Self {
+            width: 1920,
+            height: 1080,
+            frame_rate: FrameRate::FPS_24,
+            sample_rate: default_sample_rate(),
+            working_space: default_working_space(),
+        }
+    }
+}
+
+/// Where the clip source at `path` is on disk for a project whose media
+/// root is `root`: relative paths are joined to the root, absolute ones are
+/// kept.
+pub fn resolve_media_path(root: Option<&Path>, path: &str) -> PathBuf {
+    match root {
+        Some(root) if Path::new(path).is_relative() => root.join(path),
+        _ => PathBuf::from(path),
+    }
+}
+
 /// A sequence (timeline) containing tracks.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Sequence {
@@ -65,10 +241,40 @@ pub struct Sequence {
     pub width: u32,
     /// Resolution height
     pub height: u32,
+    /// Audio sample rate in Hz
+    #[serde(default = "default_sample_rate")]
+    pub sample_rate: u32,
+    /// Working color space for compositing
+    #[serde(default = "default_working_space")]
+    pub working_space: ColorSpace,
+    /// Playhead position
+    #[serde(default = "default_playhead")]
+    pub playhead: RationalTime,
     /// Video tracks
     pub video_tracks: Vec<Track>,
     /// Audio tracks
     pub audio_tracks: Vec<Track>,
+    /// Master bus insert effects, as saved by the audio engine
+    #[serde(default)]
+    pub master_inserts: Vec<serde_json::Value>,
+    /// Master bus gain in dB, applied with the master volume
+    #[serde(default)]
+    pub master_gain_db: f32,
+    /// Edit history for this sequence (not persisted)
+    #[serde(skip)]
+    pub history: UndoStack,
+}
+
+fn default_sample_rate() -> u32 {
+    48_000
+}
+
+fn default_working_space() -> ColorSpace {
+    ColorSpace::LinearSrgb
+}
+
+fn default_playhead() -> RationalTime {
+    RationalTime::ZERO
 }
 
 impl Sequence {
@@ -80,8 +286,14 @@ impl Sequence {
             frame_rate,
             width,
             height,
+            sample_rate: default_sample_rate(),
+            working_space: default_working_space(),
+            playhead: RationalTime::ZERO,
             video_tracks: vec![Track::new_video("V1")],
             audio_tracks: vec![Track::new_audio("A1")],
+            master_inserts: Vec::new(),
+            master_gain_db: 0.0,
+            history: UndoStack::default(),
         }
     }
 
@@ -112,6 +324,143 @@ impl Sequence {
     pub fn time_range(&self) -> TimeRange {
         TimeRange::new(RationalTime::ZERO, self.duration())
     }
+
+    /// Current format settings.
+    pub fn settings(&self) -> SequenceSettings {
+        SequenceSettings {
+            width: self.width,
+            height: self.height,
+            frame_rate: self.frame_rate,
+            sample_rate: self.sample_rate,
+            working_space: self.working_space,
+        }
+    }
+
+    /// Change the sequence settings.
+    ///
+    /// When the frame rate changes, clip timing and the playhead are conformed
+    /// to the new rate according to `conform`.
+    pub fn apply_settings(
+        &mut self,
+        settings: SequenceSettings,
+        conform: ConformMode,
+    ) -> Result<()> {
+        if settings.width == 0 || settings.height == 0 {
+            return Err(ProEditError::InvalidParameter(format!(
+                "Invalid sequence resolution {}x{}",
+                settings.width, settings.height
+            )));
+        }
+        if settings.frame_rate.numerator == 0 || settings.frame_rate.denominator == 0 {
+            return Err(ProEditError::InvalidParameter(
+                "Frame rate must be non-zero".into(),
+            ));
+        }
+        if settings.sample_rate == 0 {
+            return Err(ProEditError::InvalidParameter(
+                "Sample rate must be non-zero".into(),
+            ));
+        }
+
+        let old_rate = self.frame_rate;
+        if settings.frame_rate != old_rate {
+            self.conform_frame_rate(old_rate, settings.frame_rate, conform);
+        }
+
+        self.width = settings.width;
+        self.height = settings.height;
+        self.frame_rate = settings.frame_rate;
+        self.sample_rate = settings.sample_rate;
+        self.working_space = settings.working_space;
+        Ok(())
+    }
+
+    /// Apply an edit to this sequence and record it in the sequence history.
+    pub fn execute(&mut self, mut command: EditCommand) {
+        command.apply(self);
+        self.history.push(command);
+    }
+
+    /// Like [`execute`](Self::execute), with an explicit history label.
+    pub fn execute_labeled(&mut self, label: impl Into<String>, mut command: EditCommand) {
+        command.apply(self);
+        self.history.push_labeled(label, command);
+    }
+
+    /// Undo the most recent edit. Returns false if there was nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        match self.history.undo() {
+            Some(mut inverse) => {
+                inverse.apply(self);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Redo the most recently undone edit. Returns false if there was nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        match self.history.redo() {
+            Some(mut command) => {
+                command.apply(self);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn conform_frame_rate(&mut self, from: FrameRate, to: FrameRate, mode: ConformMode) {
+        let conform = |time: RationalTime| match mode {
+            ConformMode::SnapToFrames => snap_to_frame(time, to),
+            ConformMode::PreserveFrameCount => {
+                RationalTime::from_frames(nearest_frame(time, from), to)
+            }
+        };
+
+        // Edit points (each item's end) are conformed rather than each
+        // duration, so rounding never accumulates down the track. `shift`
+        // is how far clips clamped to their source pulled later cuts in.
+        for track in self
+            .video_tracks
+            .iter_mut()
+            .chain(self.audio_tracks.iter_mut())
+        {
+            let mut end = RationalTime::ZERO;
+            let mut start = RationalTime::ZERO;
+            let mut shift = RationalTime::ZERO;
+            for item in &mut track.items {
+                end = end + item.duration();
+                let mut duration = conform(end) - shift - start;
+                match item {
+                    TrackItem::Clip(clip) => {
+                        // Source in-points are media time, not sequence time,
+                        // so only the timeline duration is conformed. Never
+                        // reach past the end of the source at the clip's speed.
+                        let available = clip.source.source_duration - clip.source_in;
+                        if available > RationalTime::ZERO && clip.speed > 0.0 {
+                            let seconds = available.to_seconds_f64() / clip.speed;
+                            let limit = RationalTime::from_frames(
+                                (seconds * to.to_fps_f64() + 1e-9).floor() as i64,
+                                to,
+                            );
+                            if duration > limit {
+                                shift = shift + (duration - limit);
+                                duration = limit;
+                            }
+                        }
+                        clip.duration = duration;
+                    }
+                    TrackItem::Adjustment(layer) => layer.duration = duration,
+                    TrackItem::Gap { duration: d } | TrackItem::Transition { duration: d, .. } => {
+                        *d = duration;
+                    }
+                }
+                start = start + duration;
+            }
+        }
+
+        self.playhead = conform(self.playhead);
+    }
 }
 
 impl Default for Sequence {
@@ -119,3 +468,229 @@ impl Default for Sequence {
         Self::new("Sequence 1", 1920, 1080, FrameRate::FPS_24)
     }
 }
+
+/// Round a time to the nearest frame index at `rate`.
+fn nearest_frame(time: RationalTime, rate: FrameRate) -> i64 {
+    let half_frame = rate.frame_duration() / 2;
+    if time < RationalTime::ZERO {
+        -(-time + half_frame).to_frames(rate)
+    } else {
+        (time + half_frame).to_frames(rate)
+    }
+}
+
+/// Snap a time to the nearest frame boundary at `rate`.
+fn snap_to_frame(time: RationalTime, rate: FrameRate) -> RationalTime {
+    RationalTime::from_frames(nearest_frame(time, rate), rate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clip::{Clip, ClipRef};
+
+    fn project_with_clip() -> (Project, Uuid) {
+        let mut project = Project::new("Test");
+        let mut seq = Sequence::default();
+        seq.video_tracks[0].append_clip(Clip::new(
+            "clip",
+            ClipRef::new("a.mp4", RationalTime::new(10, 1)),
+        ));
+        let id = seq.id;
+        project.add_sequence(seq);
+        (project, id)
+    }
+
+    #[test]
+    fn test_active_sequence_switching() {
+        let (mut project, first) = project_with_clip();
+        assert_eq!(project.active_sequence().unwrap().id, first);
+
+        let second = project.create_sequence("Second", SequenceSettings::default());
+        assert_eq!(project.active_sequence().unwrap().id, second);
+
+        project.set_active_sequence(first).unwrap();
+        assert_eq!(project.active_sequence().unwrap().id, first);
+        assert!(project.set_active_sequence(Uuid::new_v4()).is_err());
+    }
+
+    #[test]
+    fn test_duplicate_gets_fresh_ids() {
+        let (mut project, first) = project_with_clip();
+        let copy_id = project.duplicate_sequence(first).unwrap();
+        let again = project.duplicate_sequence(first).unwrap();
+
+        let original = project.sequence(first).unwrap();
+        let copy = project.sequence(copy_id).unwrap();
+        assert_eq!(copy.name, "Sequence 1 Copy");
+        assert_eq!(project.sequence(again).unwrap().name, "Sequence 1 Copy 2");
+        assert_ne!(copy.video_tracks[0].id, original.video_tracks[0].id);
+        assert_ne!(
+            copy.video_tracks[0].clip_at(0).unwrap().id,
+            original.video_tracks[0].clip_at(0).unwrap().id
+        );
+        assert_eq!(project.sequences[1].id, again);
+    }
+
+    #[test]
+    fn test_rename_and_delete() {
+        let (mut project, first) = project_with_clip();
+        let second = project.create_sequence("Second", SequenceSettings::default());
+        project.rename_sequence(first, "Main").unwrap();
+        assert_eq!(project.sequence(first).unwrap().name, "Main");
+
+        let removed = project.delete_sequence(second).unwrap();
+        assert_eq!(removed.name, "Second");
+        assert_eq!(project.active_sequence().unwrap().id, first);
+        assert!(project.delete_sequence(second).is_err());
+    }
+
+    #[test]
+    fn test_per_sequence_history_and_playhead() {
+        let (mut project, first) = project_with_clip();
+        let second = project.create_sequence("Second", SequenceSettings::default());
+
+        let seq = project.active_sequence_mut().unwrap();
+        let track_id = seq.video_tracks[0].id;
+        seq.execute(EditCommand::InsertClip {
+            track_id,
+            index: 0,
+            clip: Clip::new("b", ClipRef::new("b.mp4", RationalTime::new(2, 1))),
+        });
+        seq.playhead = RationalTime::new(1, 1);
+
+        project.set_active_sequence(first).unwrap();
+        let seq = project.active_sequence_mut().unwrap();
+        assert!(!seq.history.can_undo());
+        assert_eq!(seq.playhead, RationalTime::ZERO);
+
+        project.set_active_sequence(second).unwrap();
+        let seq = project.active_sequence_mut().unwrap();
+        assert_eq!(seq.playhead, RationalTime::new(1, 1));
+        assert!(seq.undo());
+        assert_eq!(seq.video_tracks[0].clip_count(), 0);
+        assert!(seq.redo());
+        assert_eq!(seq.video_tracks[0].clip_count(), 1);
+    }
+
+    #[test]
+    fn test_conform_snap_to_frames() {
+        let mut seq = Sequence::default();
+        let mut clip = Clip::new("c", ClipRef::new("c.mp4", RationalTime::new(10, 1)));
+        clip.source_in = RationalTime::new(1, 7);
+        clip.duration = RationalTime::from_frames(49, FrameRate::FPS_24);
+        seq.video_tracks[0].append_clip(clip);
+        seq.playhead = RationalTime::from_frames(13, FrameRate::FPS_24);
+
+        let settings = SequenceSettings {
+            frame_rate: FrameRate::FPS_25,
+            ..seq.settings()
+        };
+        seq.apply_settings(settings, ConformMode::SnapToFrames)
+            .unwrap();
+
+        // 49/24 s = 2.0417 s → 51.04 frames at 25 fps → 51 frames
+        let clip = seq.video_tracks[0].clip_at(0).unwrap();
+        assert_eq!(clip.source_in, RationalTime::new(1, 7));
+        assert_eq!(
+            clip.duration,
+            RationalTime::from_frames(51, FrameRate::FPS_25)
+        );
+        // 13/24 s = 0.5417 s → 13.54 frames → 14 frames
+        assert_eq!(
+            seq.playhead,
+            RationalTime::from_frames(14, FrameRate::FPS_25)
+        );
+        assert_eq!(seq.frame_rate, FrameRate::FPS_25);
+    }
+
+    #[test]
+    fn test_conform_preserve_frame_count() {
+        let mut seq = Sequence::default();
+        let mut clip = Clip::new("c", ClipRef::new("c.mp4", RationalTime::new(10, 1)));
+        clip.duration = RationalTime::from_frames(48, FrameRate::FPS_24);
+        seq.video_tracks[0].append_clip(clip);
+        seq.video_tracks[0].append_gap(RationalTime::from_frames(12, FrameRate::FPS_24));
+
+        let settings = SequenceSettings {
+            frame_rate: FrameRate::FPS_30,
+            sample_rate: 44_100,
+            ..seq.settings()
+        };
+        seq.apply_settings(settings, ConformMode::PreserveFrameCount)
+            .unwrap();
+
+        assert_eq!(seq.sample_rate, 44_100);
+        assert_eq!(
+            seq.video_tracks[0].duration(),
+            RationalTime::from_frames(60, FrameRate::FPS_30)
+        );
+    }
+
+    #[test]
+    fn test_conform_keeps_edit_points_on_the_grid() {
+        // Ten 1-frame-at-24 items at 30 fps: each alone rounds 1.25 frames
+        // to 1, which would lose a quarter frame per cut.
+        let mut seq = Sequence::default();
+        for i in 0..10 {
+            let mut clip = Clip::new(
+                format!("c{i}"),
+                ClipRef::new("c.mp4", RationalTime::new(100, 1)),
+            );
+            clip.duration = RationalTime::from_frames(1, FrameRate::FPS_24);
+            seq.video_tracks[0].append_clip(clip);
+        }
+        let settings = SequenceSettings {
+            frame_rate: FrameRate::FPS_30,
+            ..seq.settings()
+        };
+        seq.apply_settings(settings, ConformMode::SnapToFrames)
+            .unwrap();
+
+        let mut end = RationalTime::ZERO;
+        for (i, item) in seq.video_tracks[0].items.iter().enumerate() {
+            end = end + item.duration();
+            let original = RationalTime::from_frames(i as i64 + 1, FrameRate::FPS_24);
+            let error = (end - original).abs();
+            assert!(
+                error <= FrameRate::FPS_30.frame_duration() / 2,
+                "cut {i} drifted to {end}"
+            );
+        }
+        assert_eq!(end, RationalTime::from_frames(13, FrameRate::FPS_30));
+    }
+
+    #[test]
+    fn test_conform_clamps_to_source_at_clip_speed() {
+        // 2 s of source left at 2x plays for 1 s.
+        let mut seq = Sequence::default();
+        let mut clip = Clip::new("c", ClipRef::new("c.mp4", RationalTime::new(3, 1)));
+        clip.source_in = RationalTime::new(1, 1);
+        clip.speed = 2.0;
+        clip.duration = RationalTime::new(1, 1) + RationalTime::new(1, 100);
+        seq.video_tracks[0].append_clip(clip);
+        let settings = SequenceSettings {
+            frame_rate: FrameRate::FPS_25,
+            ..seq.settings()
+        };
+        seq.apply_settings(settings, ConformMode::SnapToFrames)
+            .unwrap();
+        assert_eq!(
+            seq.video_tracks[0].clip_at(0).unwrap().duration,
+            RationalTime::from_frames(25, FrameRate::FPS_25)
+        );
+    }
+
+    #[test]
+    fn test_invalid_settings_rejected() {
+        let mut seq = Sequence::default();
+        let settings = SequenceSettings {
+            width: 0,
+            ..seq.settings()
+        };
+        assert!(seq
+            .apply_settings(settings, ConformMode::default())
+            .is_err());
+        assert_eq!(seq.width, 1920);
+    }
+}
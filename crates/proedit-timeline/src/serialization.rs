@@ -2,13 +2,18 @@
 //!
 //! Uses JSON with a schema version field for forward-compatible persistence.
 
-use proedit_core::{ProEditError, Result};
+use proedit_core::{ProEditError, RationalTime, Result};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
 
-use crate::project::Project;
+use crate::edit::UndoStack;
+use crate::project::{Project, Sequence};
+use crate::validate::Diagnostic;
 
 /// Current schema version.
-pub const CURRENT_VERSION: u32 = 1;
+pub const CURRENT_VERSION: u32 = 3;
 
 /// Versioned project file wrapper.
 #[derive(Debug, Serialize, Deserialize)]
@@ -19,6 +24,9 @@ pub struct ProjectFile {
     pub project: Project,
     /// Application version that wrote this file.
     pub app_version: String,
+    /// Validation findings from loading (not persisted).
+    #[serde(skip)]
+    pub diagnostics: Vec<Diagnostic>,
 }
 
 impl ProjectFile {
@@ -28,6 +36,7 @@ impl ProjectFile {
             version: CURRENT_VERSION,
             project,
             app_version: env!("CARGO_PKG_VERSION").to_string(),
+            diagnostics: Vec::new(),
         }
     }
 
@@ -38,6 +47,9 @@ impl ProjectFile {
     }
 
     /// Deserialize from JSON bytes, applying migrations if needed.
+    ///
+    /// The loaded project is validated; findings are left in `diagnostics`
+    /// for the caller to surface (see `Project::repair` to fix them).
     pub fn from_json(data: &[u8]) -> Result<Self> {
         // First, try to read just the version
         let raw: serde_json::Value = serde_json::from_slice(data)
@@ -55,8 +67,10 @@ impl ProjectFile {
         // Apply migrations
         let migrated = migrate(raw, version)?;
 
-        serde_json::from_value(migrated)
-            .map_err(|e| ProEditError::Serialization(format!("Failed to parse project: {}", e)))
+        let mut file: Self = serde_json::from_value(migrated)
+            .map_err(|e| ProEditError::Serialization(format!("Failed to parse project: {}", e)))?;
+        file.diagnostics = file.project.validate();
+        Ok(file)
     }
 
     /// Save project to a file path.
@@ -66,10 +80,13 @@ impl ProjectFile {
         Ok(())
     }
 
-    /// Load project from a file path.
+    /// Load project from a file path. Relative media paths are resolved
+    /// against the file's folder (see [`Project::media_path`]).
     pub fn load_from_file(path: &std::path::Path) -> Result<Self> {
         let data = std::fs::read(path)?;
-        Self::from_json(&data)
+        let mut file = Self::from_json(&data)?;
+        file.project.media_root = path.parent().map(Path::to_path_buf);
+        Ok(file)
     }
 }
 
@@ -91,6 +108,42 @@ fn migrate(mut data: serde_json::Value, from_version: u32) -> Result<serde_json:
                 }
                 version = 1;
             }
+            1 => {
+                // v1 → v2: Persist the active sequence (previously always the first)
+                if let Some(project) = data.get_mut("project").and_then(|p| p.as_object_mut()) {
+                    let first_id = project
+                        .get("sequences")
+                        .and_then(|s| s.get(0))
+                        .and_then(|s| s.get("id"))
+                        .cloned()
+                        .unwrap_or(serde_json::Value::Null);
+                    project.entry("active_sequence_id").or_insert(first_id);
+                }
+                data["version"] = serde_json::json!(2);
+                version = 2;
+            }
+            2 => {
+                // v2 → v3: Alternate takes keep their own in point
+                if let Some(sequences) = data.pointer_mut("/project/sequences") {
+                    for_each_clip(sequences, |clip| {
+                        let Some(takes) = clip.get_mut("takes").and_then(|t| t.as_array_mut())
+                        else {
+                            return;
+                        };
+                        for take in takes.iter_mut() {
+                            if take.get("source").is_none() {
+                                let source = take.take();
+                                *take = serde_json::json!({
+                                    "source": source,
+                                    "source_in": RationalTime::ZERO,
+                                });
+                            }
+                        }
+                    });
+                }
+                data["version"] = serde_json::json!(3);
+                version = 3;
+            }
             _ => {
                 return Err(ProEditError::Serialization(format!(
                     "No migration path from version {}",
@@ -103,6 +156,143 @@ fn migrate(mut data: serde_json::Value, from_version: u32) -> Result<serde_json:
     Ok(data)
 }
 
+/// Call `f` on every serialized clip in a `sequences` array.
+fn for_each_clip(sequences: &mut serde_json::Value, mut f: impl FnMut(&mut serde_json::Value)) {
+    let Some(sequences) = sequences.as_array_mut() else {
+        return;
+    };
+    for sequence in sequences {
+        for kind in ["video_tracks", "audio_tracks"] {
+            let Some(tracks) = sequence.get_mut(kind).and_then(|t| t.as_array_mut()) else {
+                continue;
+            };
+            for track in tracks {
+                let Some(items) = track.get_mut("items").and_then(|i| i.as_array_mut()) else {
+                    continue;
+                };
+                for item in items {
+                    if let Some(clip) = item.get_mut("Clip") {
+                        f(clip);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Current history sidecar schema version.
+pub const HISTORY_VERSION: u32 = 2;
+
+/// Per-sequence undo history stored next to a project file, so undo
+/// survives closing and reopening the project.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HistoryFile {
+    /// Schema version.
+    pub version: u32,
+    /// ID of the project this history belongs to.
+    pub project_id: Uuid,
+    /// History of each sequence.
+    pub sequences: Vec<SequenceHistory>,
+}
+
+/// Saved history of one sequence.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SequenceHistory {
+    /// Sequence ID.
+    pub sequence_id: Uuid,
+    /// [`content_hash`] of the sequence when the history was saved.
+    #[serde(default)]
+    pub content_hash: String,
+    /// Undo/redo stack.
+    pub history: UndoStack,
+}
+
+impl HistoryFile {
+    /// Capture the history of every sequence in a project.
+    pub fn from_project(project: &Project) -> Self {
+        Self {
+            version: HISTORY_VERSION,
+            project_id: project.id,
+            sequences: project
+                .sequences
+                .iter()
+                .map(|s| SequenceHistory {
+                    sequence_id: s.id,
+                    content_hash: content_hash(s),
+                    history: s.history.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Sidecar path for a project file (`<project file>.history`).
+    pub fn sidecar_path(project_path: &Path) -> PathBuf {
+        let mut name = project_path.as_os_str().to_owned();
+        name.push(".history");
+        PathBuf::from(name)
+    }
+
+    /// Write the project's history next to its project file.
+    pub fn save_for_project(project: &Project, project_path: &Path) -> Result<()> {
+        let data = serde_json::to_vec(&Self::from_project(project)).map_err(|e| {
+            ProEditError::Serialization(format!("Failed to serialize history: {}", e))
+        })?;
+        std::fs::write(Self::sidecar_path(project_path), data)?;
+        Ok(())
+    }
+
+    /// Restore history from the sidecar next to `project_path`, if present.
+    ///
+    /// Returns the number of sequences whose history was restored. A
+    /// sequence whose content changed since the history was saved (edited
+    /// by another tool, repaired on load) keeps an empty history, and
+    /// entries that cannot be undone are dropped. A missing sidecar is not
+    /// an error; a sidecar written for a different project is.
+    pub fn load_into_project(project: &mut Project, project_path: &Path) -> Result<usize> {
+        let path = Self::sidecar_path(project_path);
+        if !path.exists() {
+            return Ok(0);
+        }
+        let data = std::fs::read(path)?;
+        let file: Self = serde_json::from_slice(&data)
+            .map_err(|e| ProEditError::Serialization(format!("Invalid history file: {}", e)))?;
+
+        if file.version > HISTORY_VERSION {
+            return Err(ProEditError::Serialization(format!(
+                "History file version {} is newer than supported version {}",
+                file.version, HISTORY_VERSION
+            )));
+        }
+        if file.project_id != project.id {
+            return Err(ProEditError::Serialization(
+                "History file belongs to a different project".into(),
+            ));
+        }
+
+        let mut restored = 0;
+        for saved in file.sequences {
+            if let Some(sequence) = project.sequence_mut(saved.sequence_id) {
+                if saved.content_hash != content_hash(sequence) {
+                    continue;
+                }
+                sequence.history = saved.history;
+                sequence.history.drop_uninvertible();
+                restored += 1;
+            }
+        }
+        Ok(restored)
+    }
+}
+
+/// Lowercase hex SHA-256 of a sequence's saved form (its history is not
+/// part of it). JSON objects serialize with sorted keys, so the hash does
+/// not depend on map iteration order.
+pub fn content_hash(sequence: &Sequence) -> String {
+    let value = serde_json::to_value(sequence).unwrap_or_default();
+    let digest = Sha256::digest(value.to_string().as_bytes());
+    digest.iter().map(|b| format!("{b:02x}")).collect()
+}
+
 /// Recent projects list.
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct RecentProjects {
@@ -185,6 +375,91 @@ mod tests {
         assert_eq!(loaded.project.name, "Old Project");
     }
 
+    #[test]
+    fn test_migration_v1_sets_active_sequence() {
+        let mut project = Project::new("V1 Project");
+        project.add_sequence(crate::project::Sequence::default());
+        project.add_sequence(crate::project::Sequence::default());
+        let first_id = project.sequences[0].id;
+
+        let mut value = serde_json::to_value(&project).unwrap();
+        value.as_object_mut().unwrap().remove("active_sequence_id");
+        let json = serde_json::json!({
+            "version": 1,
+            "project": value,
+            "app_version": "0.1.0",
+        });
+        let data = serde_json::to_vec(&json).unwrap();
+
+        let loaded = ProjectFile::from_json(&data).unwrap();
+        assert_eq!(loaded.project.active_sequence_id, Some(first_id));
+    }
+
+    #[test]
+    fn test_migration_v2_gives_takes_an_in_point() {
+        use crate::clip::{Clip, ClipRef};
+        use proedit_core::RationalTime;
+
+        let mut project = Project::new("V2 Project");
+        let mut seq = crate::project::Sequence::default();
+        seq.audio_tracks[0].append_clip(Clip::new(
+            "vo",
+            ClipRef::new("vo2.wav", RationalTime::new(5, 1)),
+        ));
+        project.add_sequence(seq);
+
+        let mut value = serde_json::to_value(&project).unwrap();
+        let clip = value
+            .pointer_mut("/sequences/0/audio_tracks/0/items/0/Clip")
+            .unwrap();
+        clip["takes"] = serde_json::json!([{
+            "path": "vo1.wav",
+            "source_duration": RationalTime::new(5, 1),
+        }]);
+        let json = serde_json::json!({
+            "version": 2,
+            "project": value,
+            "app_version": "0.1.0",
+        });
+        let data = serde_json::to_vec(&json).unwrap();
+
+        let loaded = ProjectFile::from_json(&data).unwrap();
+        let clip = loaded.project.sequences[0].audio_tracks[0]
+            .clip_at(0)
+            .unwrap();
+        assert_eq!(clip.takes[0].source.path, "vo1.wav");
+        assert_eq!(clip.takes[0].source_in, RationalTime::ZERO);
+    }
+
+    #[test]
+    fn test_active_sequence_roundtrip() {
+        let mut project = Project::new("Multi");
+        project.add_sequence(crate::project::Sequence::default());
+        let second = project.create_sequence("Second", Default::default());
+        project.active_sequence_mut().unwrap().playhead = proedit_core::RationalTime::new(3, 1);
+
+        let json = ProjectFile::new(project).to_json().unwrap();
+        let loaded = ProjectFile::from_json(&json).unwrap();
+        let seq = loaded.project.active_sequence().unwrap();
+        assert_eq!(seq.id, second);
+        assert_eq!(seq.playhead, proedit_core::RationalTime::new(3, 1));
+    }
+
+    #[test]
+    fn test_load_reports_diagnostics() {
+        let mut project = Project::new("Broken");
+        let mut seq = crate::project::Sequence::default();
+        seq.video_tracks[0].append_gap(proedit_core::RationalTime::new(-1, 1));
+        project.add_sequence(seq);
+
+        let json = ProjectFile::new(project).to_json().unwrap();
+        let mut loaded = ProjectFile::from_json(&json).unwrap();
+        assert_eq!(loaded.diagnostics.len(), 1);
+
+        assert_eq!(loaded.project.repair().len(), 1);
+        assert!(loaded.project.validate().is_empty());
+    }
+
     #[test]
     fn test_future_version_rejected() {
         let json = serde_json::json!({
@@ -197,6 +472,59 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_history_sidecar_roundtrip() {
+        use crate::clip::{Clip, ClipRef};
+        use crate::edit::EditCommand;
+
+        let dir = std::env::temp_dir().join(format!("proedit-history-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let project_path = dir.join("edit.pep");
+
+        let mut project = Project::new("History");
+        project.add_sequence(crate::project::Sequence::default());
+        let seq = project.active_sequence_mut().unwrap();
+        let track_id = seq.video_tracks[0].id;
+        seq.execute(EditCommand::InsertClip {
+            track_id,
+            index: 0,
+            clip: Clip::new(
+                "a",
+                ClipRef::new("a.mp4", proedit_core::RationalTime::new(5, 1)),
+            ),
+        });
+
+        ProjectFile::new(project.clone())
+            .save_to_file(&project_path)
+            .unwrap();
+        HistoryFile::save_for_project(&project, &project_path).unwrap();
+        assert!(dir.join("edit.pep.history").exists());
+
+        let mut loaded = ProjectFile::load_from_file(&project_path).unwrap().project;
+        assert!(!loaded.active_sequence().unwrap().history.can_undo());
+        assert_eq!(
+            HistoryFile::load_into_project(&mut loaded, &project_path).unwrap(),
+            1
+        );
+        let seq = loaded.active_sequence_mut().unwrap();
+        assert!(seq.undo());
+        assert_eq!(seq.video_tracks[0].clip_count(), 0);
+
+        let mut other = Project::new("Other");
+        assert!(HistoryFile::load_into_project(&mut other, &project_path).is_err());
+
+        // An edit made without updating the sidecar invalidates the history.
+        let mut edited = ProjectFile::load_from_file(&project_path).unwrap().project;
+        edited.active_sequence_mut().unwrap().video_tracks[0].muted = true;
+        assert_eq!(
+            HistoryFile::load_into_project(&mut edited, &project_path).unwrap(),
+            0
+        );
+        assert!(!edited.active_sequence().unwrap().history.can_undo());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
     #[test]
     fn test_recent_projects() {
         let mut recent = RecentProjects::new();
@@ -1,10 +1,11 @@
 //! Track types for the timeline.
 
-use proedit_core::RationalTime;
+use proedit_core::{EasingCurve, KeyframeTrack, ParamValues, RationalTime};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::clip::Clip;
+use crate::effect::{AdjustmentLayer, EffectInstance};
 
 /// Kind of track.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -13,30 +14,66 @@ pub enum TrackKind {
     Audio,
 }
 
-/// An item in a track (clip, gap, or transition).
+/// An item in a track (clip, adjustment layer, gap, or transition).
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum TrackItem {
-    Clip(Clip),
+    Clip(Box<Clip>),
+    Adjustment(AdjustmentLayer),
     Gap {
         duration: RationalTime,
     },
     Transition {
         transition_name: String,
         duration: RationalTime,
+        /// Values of the transition's parameters, by name; unset ones take
+        /// their defaults
+        #[serde(default)]
+        params: ParamValues,
+        /// Maps elapsed time to progress
+        #[serde(default)]
+        easing: EasingCurve,
     },
 }
 
+impl From<Clip> for TrackItem {
+    fn from(clip: Clip) -> Self {
+        TrackItem::Clip(Box::new(clip))
+    }
+}
+
 impl TrackItem {
     /// Get the duration of this item.
     pub fn duration(&self) -> RationalTime {
         match self {
             TrackItem::Clip(clip) => clip.duration,
+            TrackItem::Adjustment(layer) => layer.duration,
             TrackItem::Gap { duration } => *duration,
             TrackItem::Transition { duration, .. } => *duration,
         }
     }
 }
 
+/// Track-level mixer automation (audio tracks only).
+///
+/// Keyframes are on sequence time. Volume is in dB and scales the mixer
+/// channel's fader; pan (-1..1) replaces the channel's pan while present.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct TrackAutomation {
+    /// Volume automation in dB
+    #[serde(default)]
+    pub volume: Option<KeyframeTrack>,
+    /// Pan automation
+    #[serde(default)]
+    pub pan: Option<KeyframeTrack>,
+}
+
+impl TrackAutomation {
+    /// Whether no parameter is automated.
+    pub fn is_empty(&self) -> bool {
+        self.volume.is_none() && self.pan.is_none()
+    }
+}
+
 /// A track containing clips and gaps.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Track {
@@ -52,6 +89,16 @@ pub struct Track {
     pub muted: bool,
     /// Is track locked (prevent edits)
     pub locked: bool,
+    /// Volume and pan automation
+    #[serde(default)]
+    pub automation: TrackAutomation,
+    /// Effects applied to every clip on the track (video tracks only)
+    #[serde(default)]
+    pub effects: Vec<EffectInstance>,
+    /// Mixer insert effects in processing order, as saved by the audio
+    /// engine (audio tracks only)
+    #[serde(default)]
+    pub inserts: Vec<serde_json::Value>,
 }
 
 impl Track {
@@ -64,6 +111,9 @@ impl Track {
             items: Vec::new(),
             muted: false,
             locked: false,
+            automation: TrackAutomation::default(),
+            effects: Vec::new(),
+            inserts: Vec::new(),
         }
     }
 
@@ -76,6 +126,9 @@ impl Track {
             items: Vec::new(),
             muted: false,
             locked: false,
+            automation: TrackAutomation::default(),
+            effects: Vec::new(),
+            inserts: Vec::new(),
         }
     }
 
@@ -88,7 +141,7 @@ impl Track {
 
     /// Add a clip to the end of the track.
     pub fn append_clip(&mut self, clip: Clip) {
-        self.items.push(TrackItem::Clip(clip));
+        self.items.push(clip.into());
     }
 
     /// Add a gap to the end of the track.
@@ -99,7 +152,7 @@ impl Track {
     /// Insert a clip at the given index.
     pub fn insert_clip(&mut self, index: usize, clip: Clip) {
         let index = index.min(self.items.len());
-        self.items.insert(index, TrackItem::Clip(clip));
+        self.items.insert(index, clip.into());
     }
 
     /// Remove the item at the given index. Returns the removed item.
@@ -116,7 +169,7 @@ impl Track {
         self.items.iter().enumerate().find_map(|(i, item)| {
             if let TrackItem::Clip(clip) = item {
                 if clip.id == id {
-                    return Some((i, clip));
+                    return Some((i, &**clip));
                 }
             }
             None
@@ -128,7 +181,7 @@ impl Track {
         self.items.iter_mut().enumerate().find_map(|(i, item)| {
             if let TrackItem::Clip(item) = item {
                 if item.id == id {
-                    return Some((i, item));
+                    return Some((i, &mut **item));
                 }
             }
             None
@@ -203,10 +256,55 @@ impl Track {
             TrackItem::Transition {
                 transition_name: name.to_string(),
                 duration,
+                params: ParamValues::new(),
+                easing: EasingCurve::Linear,
             },
         );
     }
 
+    /// Place `clip` at `start`, replacing whatever the track has there.
+    ///
+    /// Clips straddling the edges are trimmed (or split, if the new clip
+    /// lands inside one), transitions touching the range become gaps, and
+    /// a gap is added if the track ends before `start`. Returns the index
+    /// of the new clip.
+    pub fn overwrite(&mut self, start: RationalTime, clip: Clip) -> usize {
+        let end = start + clip.duration;
+        let mut items = Vec::with_capacity(self.items.len() + 3);
+        let mut pos = RationalTime::ZERO;
+        let mut index = None;
+        for item in self.items.drain(..) {
+            let item_start = pos;
+            let item_end = pos + item.duration();
+            pos = item_end;
+            if item_end <= start {
+                items.push(item);
+                continue;
+            }
+            if item_start < start {
+                items.push(head(&item, start - item_start));
+            }
+            if index.is_none() {
+                index = Some(items.len());
+                items.push(clip.clone().into());
+            }
+            if item_end > end {
+                items.push(tail(&item, end.max(item_start) - item_start));
+            }
+        }
+        let index = index.unwrap_or_else(|| {
+            if pos < start {
+                items.push(TrackItem::Gap {
+                    duration: start - pos,
+                });
+            }
+            items.push(clip.into());
+            items.len() - 1
+        });
+        self.items = items;
+        index
+    }
+
     /// Number of clips (excluding gaps) in this track.
     pub fn clip_count(&self) -> usize {
         self.items
@@ -215,3 +313,48 @@ impl Track {
             .count()
     }
 }
+
+/// The first `length` of an item; a cut transition becomes a gap.
+fn head(item: &TrackItem, length: RationalTime) -> TrackItem {
+    match item {
+        TrackItem::Clip(clip) => {
+            let mut clip = clip.clone();
+            clip.duration = length;
+            clip.audio.fade_out = Default::default();
+            TrackItem::Clip(clip)
+        }
+        TrackItem::Adjustment(layer) => {
+            let mut layer = layer.clone();
+            layer.duration = length;
+            TrackItem::Adjustment(layer)
+        }
+        _ => TrackItem::Gap { duration: length },
+    }
+}
+
+/// An item from `offset` on; a cut transition becomes a gap.
+fn tail(item: &TrackItem, offset: RationalTime) -> TrackItem {
+    if offset.is_zero() {
+        return item.clone();
+    }
+    let remaining = item.duration() - offset;
+    match item {
+        TrackItem::Clip(clip) => {
+            let mut right = clip.clone();
+            right.id = Uuid::new_v4();
+            right.source_in = clip.source_in + clip.source_offset(offset);
+            right.duration = remaining;
+            right.audio = clip.audio.split_off(offset);
+            TrackItem::Clip(right)
+        }
+        TrackItem::Adjustment(layer) => {
+            let mut right = layer.clone();
+            right.id = Uuid::new_v4();
+            right.duration = remaining;
+            TrackItem::Adjustment(right)
+        }
+        _ => TrackItem::Gap {
+            duration: remaining,
+        },
+    }
+}
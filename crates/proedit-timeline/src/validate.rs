@@ -0,0 +1,490 @@
+//! Structural validation and repair of timeline data.
+//!
+//! Project files can be hand-edited or partially migrated, so loaded
+//! sequences are checked for values the rest of the editor assumes never
+//! happen (negative durations, source windows past the end of the media,
+//! dangling transitions).
+
+use proedit_core::RationalTime;
+use std::fmt;
+use uuid::Uuid;
+
+use crate::effect::AdjustmentLayer;
+use crate::project::{Project, Sequence};
+use crate::track::{Track, TrackItem};
+
+/// How serious a diagnostic is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Likely to misrender but safe to keep editing.
+    Warning,
+    /// Invalid data that breaks timeline math.
+    Error,
+}
+
+/// A specific problem found in a track.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Issue {
+    /// An item has a negative duration.
+    NegativeDuration { duration: RationalTime },
+    /// A clip's source in point is before the start of its media.
+    NegativeSourceIn { source_in: RationalTime },
+    /// The source a clip plays (`source_in + duration × speed`) runs past
+    /// the end of its media.
+    SourceOutOfRange {
+        source_out: RationalTime,
+        source_duration: RationalTime,
+    },
+    /// A clip's speed is zero, negative or not finite.
+    InvalidSpeed { speed: f64 },
+    /// A transition directly follows another transition.
+    AdjacentTransitions,
+    /// A transition is the first or last item of a track.
+    TransitionAtTrackEdge,
+}
+
+impl Issue {
+    /// Severity of this issue.
+    pub fn severity(&self) -> Severity {
+        match self {
+            Issue::NegativeDuration { .. } | Issue::InvalidSpeed { .. } => Severity::Error,
+            _ => Severity::Warning,
+        }
+    }
+}
+
+/// A validation finding tied to a specific track item.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    /// Sequence containing the item.
+    pub sequence_id: Uuid,
+    /// Track containing the item.
+    pub track_id: Uuid,
+    /// Index of the item in the track at the time of validation.
+    pub item_index: usize,
+    /// Clip ID, if the item is a clip.
+    pub clip_id: Option<Uuid>,
+    /// What is wrong.
+    pub issue: Issue,
+}
+
+impl Diagnostic {
+    /// Severity of this diagnostic.
+    pub fn severity(&self) -> Severity {
+        self.issue.severity()
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.clip_id {
+            Some(clip_id) => write!(f, "track {} clip {}: ", self.track_id, clip_id)?,
+            None => write!(f, "track {} item {}: ", self.track_id, self.item_index)?,
+        }
+        match &self.issue {
+            Issue::NegativeDuration { duration } => write!(f, "negative duration {}", duration),
+            Issue::NegativeSourceIn { source_in } => {
+                write!(f, "source in point {} is before the media start", source_in)
+            }
+            Issue::SourceOutOfRange {
+                source_out,
+                source_duration,
+            } => write!(
+                f,
+                "source out point {} is past the media duration {}",
+                source_out, source_duration
+            ),
+            Issue::InvalidSpeed { speed } => write!(f, "invalid speed {}", speed),
+            Issue::AdjacentTransitions => write!(f, "transition follows another transition"),
+            Issue::TransitionAtTrackEdge => write!(f, "transition at the edge of the track"),
+        }
+    }
+}
+
+impl Project {
+    /// Validate every sequence in the project.
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        self.sequences.iter().flat_map(|s| s.validate()).collect()
+    }
+
+    /// Repair every sequence in the project. Returns the issues that were fixed.
+    pub fn repair(&mut self) -> Vec<Diagnostic> {
+        self.sequences.iter_mut().flat_map(|s| s.repair()).collect()
+    }
+}
+
+impl Sequence {
+    /// Check all tracks for invalid items without modifying anything.
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        for track in self.video_tracks.iter().chain(self.audio_tracks.iter()) {
+            validate_track(self.id, track, &mut diagnostics);
+        }
+        diagnostics
+    }
+
+    /// Fix what can be fixed without guessing at user intent.
+    ///
+    /// Clamps source windows into the media, resets invalid speeds, drops
+    /// negative gaps and transitions, and removes dangling transitions.
+    /// Clips with negative durations are left untouched (they still show up
+    /// in [`validate`](Self::validate)). Returns the issues that were fixed.
+    pub fn repair(&mut self) -> Vec<Diagnostic> {
+        let sequence_id = self.id;
+        let mut fixed = Vec::new();
+        for track in self
+            .video_tracks
+            .iter_mut()
+            .chain(self.audio_tracks.iter_mut())
+        {
+            repair_track(sequence_id, track, &mut fixed);
+        }
+        fixed
+    }
+}
+
+fn validate_track(sequence_id: Uuid, track: &Track, out: &mut Vec<Diagnostic>) {
+    let last = track.items.len().saturating_sub(1);
+    let mut report = |item_index: usize, clip_id: Option<Uuid>, issue: Issue| {
+        out.push(Diagnostic {
+            sequence_id,
+            track_id: track.id,
+            item_index,
+            clip_id,
+            issue,
+        });
+    };
+
+    for (index, item) in track.items.iter().enumerate() {
+        let duration = item.duration();
+        let clip_id = match item {
+            TrackItem::Clip(clip) => Some(clip.id),
+            _ => None,
+        };
+        if duration < RationalTime::ZERO {
+            report(index, clip_id, Issue::NegativeDuration { duration });
+        }
+
+        match item {
+            TrackItem::Clip(clip) => {
+                if !clip.speed.is_finite() || clip.speed <= 0.0 {
+                    report(index, clip_id, Issue::InvalidSpeed { speed: clip.speed });
+                }
+                if clip.source_in < RationalTime::ZERO {
+                    report(
+                        index,
+                        clip_id,
+                        Issue::NegativeSourceIn {
+                            source_in: clip.source_in,
+                        },
+                    );
+                }
+                let source_duration = clip.source.source_duration;
+                let source_out = clip.played_source_range().end();
+                // A zero source duration means the media length is unknown.
+                if source_duration > RationalTime::ZERO && source_out > source_duration {
+                    report(
+                        index,
+                        clip_id,
+                        Issue::SourceOutOfRange {
+                            source_out,
+                            source_duration,
+                        },
+                    );
+                }
+            }
+            TrackItem::Transition { .. } => {
+                if index == 0 || index == last {
+                    report(index, None, Issue::TransitionAtTrackEdge);
+                }
+                if index > 0 && matches!(track.items[index - 1], TrackItem::Transition { .. }) {
+                    report(index, None, Issue::AdjacentTransitions);
+                }
+            }
+            TrackItem::Adjustment(_) | TrackItem::Gap { .. } => {}
+        }
+    }
+}
+
+fn repair_track(sequence_id: Uuid, track: &mut Track, fixed: &mut Vec<Diagnostic>) {
+    let track_id = track.id;
+    let mut report = |item_index: usize, clip_id: Option<Uuid>, issue: Issue| {
+        fixed.push(Diagnostic {
+            sequence_id,
+            track_id,
+            item_index,
+            clip_id,
+            issue,
+        });
+    };
+
+    // Per-item fixes. Kept items remember their original index for reporting.
+    let mut kept: Vec<(usize, TrackItem)> = Vec::with_capacity(track.items.len());
+    for (index, mut item) in track.items.drain(..).enumerate() {
+        match &mut item {
+            TrackItem::Gap { duration }
+            | TrackItem::Transition { duration, .. }
+            | TrackItem::Adjustment(AdjustmentLayer { duration, .. })
+                if *duration < RationalTime::ZERO =>
+            {
+                report(
+                    index,
+                    None,
+                    Issue::NegativeDuration {
+                        duration: *duration,
+                    },
+                );
+                continue;
+            }
+            TrackItem::Clip(clip) => {
+                if !clip.speed.is_finite() || clip.speed <= 0.0 {
+                    report(
+                        index,
+                        Some(clip.id),
+                        Issue::InvalidSpeed { speed: clip.speed },
+                    );
+                    clip.speed = 1.0;
+                }
+                if clip.source_in < RationalTime::ZERO {
+                    report(
+                        index,
+                        Some(clip.id),
+                        Issue::NegativeSourceIn {
+                            source_in: clip.source_in,
+                        },
+                    );
+                    clip.source_in = RationalTime::ZERO;
+                }
+                let source_duration = clip.source.source_duration;
+                let played = clip.played_source_range();
+                if source_duration > RationalTime::ZERO
+                    && clip.duration >= RationalTime::ZERO
+                    && played.end() > source_duration
+                {
+                    report(
+                        index,
+                        Some(clip.id),
+                        Issue::SourceOutOfRange {
+                            source_out: played.end(),
+                            source_duration,
+                        },
+                    );
+                    // Prefer slipping the source window back over shortening
+                    // the clip, so the rest of the track keeps its timing.
+                    if played.duration > source_duration {
+                        clip.duration =
+                            timeline_length(source_duration, clip.speed).min(clip.duration);
+                    }
+                    let played = clip.played_source_range().duration;
+                    clip.source_in = clip
+                        .source_in
+                        .min(source_duration - played)
+                        .max(RationalTime::ZERO);
+                }
+            }
+            _ => {}
+        }
+        kept.push((index, item));
+    }
+
+    // Structural fixes: collapse runs of transitions, then drop edge transitions.
+    let is_transition = |item: &TrackItem| matches!(item, TrackItem::Transition { .. });
+    let mut items: Vec<(usize, TrackItem)> = Vec::with_capacity(kept.len());
+    for (index, item) in kept {
+        if is_transition(&item) && items.last().is_some_and(|(_, prev)| is_transition(prev)) {
+            report(index, None, Issue::AdjacentTransitions);
+            continue;
+        }
+        items.push((index, item));
+    }
+    if items.first().is_some_and(|(_, item)| is_transition(item)) {
+        let (index, _) = items.remove(0);
+        report(index, None, Issue::TransitionAtTrackEdge);
+    }
+    if items.last().is_some_and(|(_, item)| is_transition(item)) {
+        let (index, _) = items.pop().expect("checked non-empty");
+        report(index, None, Issue::TransitionAtTrackEdge);
+    }
+
+    track.items = items.into_iter().map(|(_, item)| item).collect();
+}
+
+/// Longest timeline duration that plays at most `source` of media at
+/// `speed` (rounded down to a microsecond).
+fn timeline_length(source: RationalTime, speed: f64) -> RationalTime {
+    if speed == 1.0 {
+        return source;
+    }
+    let micros = (source.to_seconds_f64() / speed * 1e6).floor() as i64;
+    RationalTime::new(micros, 1_000_000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clip::{Clip, ClipRef};
+
+    fn secs(s: i64) -> RationalTime {
+        RationalTime::new(s, 1)
+    }
+
+    fn clip(source_len: i64, source_in: i64, duration: i64) -> Clip {
+        let mut clip = Clip::new("c", ClipRef::new("c.mp4", secs(source_len)));
+        clip.source_in = secs(source_in);
+        clip.duration = secs(duration);
+        clip
+    }
+
+    fn transition() -> TrackItem {
+        TrackItem::Transition {
+            transition_name: "Cross Dissolve".into(),
+            duration: secs(1),
+            params: Default::default(),
+            easing: Default::default(),
+        }
+    }
+
+    fn sequence_with(items: Vec<TrackItem>) -> Sequence {
+        let mut seq = Sequence::default();
+        seq.video_tracks[0].items = items;
+        seq
+    }
+
+    #[test]
+    fn test_valid_sequence_has_no_diagnostics() {
+        let seq = sequence_with(vec![
+            TrackItem::from(clip(10, 0, 5)),
+            transition(),
+            TrackItem::from(clip(10, 2, 8)),
+        ]);
+        assert!(seq.validate().is_empty());
+    }
+
+    #[test]
+    fn test_reports_clip_issues_with_ids() {
+        let mut bad_speed = clip(10, -1, 5);
+        bad_speed.speed = 0.0;
+        let bad_id = bad_speed.id;
+        let seq = sequence_with(vec![
+            TrackItem::from(clip(10, 0, -2)),
+            TrackItem::from(clip(10, 6, 5)),
+            TrackItem::from(bad_speed),
+        ]);
+        let diagnostics = seq.validate();
+        let track_id = seq.video_tracks[0].id;
+
+        assert!(diagnostics.iter().all(|d| d.track_id == track_id));
+        assert!(matches!(
+            diagnostics[0].issue,
+            Issue::NegativeDuration { .. }
+        ));
+        assert_eq!(diagnostics[0].severity(), Severity::Error);
+        assert_eq!(
+            diagnostics[1].issue,
+            Issue::SourceOutOfRange {
+                source_out: secs(11),
+                source_duration: secs(10),
+            }
+        );
+        let for_bad: Vec<_> = diagnostics
+            .iter()
+            .filter(|d| d.clip_id == Some(bad_id))
+            .map(|d| &d.issue)
+            .collect();
+        assert_eq!(for_bad.len(), 2);
+        assert!(diagnostics[1]
+            .to_string()
+            .contains("past the media duration"));
+    }
+
+    #[test]
+    fn test_reports_transition_issues() {
+        let seq = sequence_with(vec![
+            transition(),
+            TrackItem::from(clip(10, 0, 5)),
+            transition(),
+            transition(),
+            TrackItem::from(clip(10, 0, 5)),
+            transition(),
+        ]);
+        let issues: Vec<_> = seq
+            .validate()
+            .into_iter()
+            .map(|d| (d.item_index, d.issue))
+            .collect();
+        assert_eq!(
+            issues,
+            vec![
+                (0, Issue::TransitionAtTrackEdge),
+                (3, Issue::AdjacentTransitions),
+                (5, Issue::TransitionAtTrackEdge),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_repair_clamps_and_removes() {
+        let mut seq = sequence_with(vec![
+            transition(),
+            TrackItem::from(clip(10, 7, 5)),
+            TrackItem::Gap { duration: secs(-1) },
+            transition(),
+            transition(),
+            TrackItem::from(clip(4, -1, 6)),
+            transition(),
+        ]);
+        let fixed = seq.repair();
+        assert_eq!(fixed.len(), 7);
+        assert!(seq.validate().is_empty());
+
+        let track = &seq.video_tracks[0];
+        assert_eq!(track.items.len(), 3);
+        // Slipped back to fit, keeping its length
+        let first = track.clip_at(0).unwrap();
+        assert_eq!((first.source_in, first.duration), (secs(5), secs(5)));
+        assert!(matches!(track.items[1], TrackItem::Transition { .. }));
+        // Too long for its media: clamped to the whole source
+        let second = track.clip_at(2).unwrap();
+        assert_eq!((second.source_in, second.duration), (secs(0), secs(4)));
+    }
+
+    #[test]
+    fn test_source_range_accounts_for_speed() {
+        // 4 s at 2x plays 8 s of source: 3 + 8 runs past a 10 s file.
+        let mut fast = clip(10, 3, 4);
+        fast.speed = 2.0;
+        // 8 s at half speed plays 4 s: fits.
+        let mut slow = clip(10, 5, 8);
+        slow.speed = 0.5;
+        let mut seq = sequence_with(vec![TrackItem::from(fast), TrackItem::from(slow)]);
+        let diagnostics = seq.validate();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].issue,
+            Issue::SourceOutOfRange {
+                source_out: secs(11),
+                source_duration: secs(10),
+            }
+        );
+
+        assert_eq!(seq.repair().len(), 1);
+        assert!(seq.validate().is_empty());
+        let repaired = seq.video_tracks[0].clip_at(0).unwrap();
+        assert_eq!((repaired.source_in, repaired.duration), (secs(2), secs(4)));
+
+        // Too long at this speed even from the start: shortened to fit.
+        let mut long = clip(10, 0, 6);
+        long.speed = 2.0;
+        let mut seq = sequence_with(vec![TrackItem::from(long)]);
+        seq.repair();
+        assert!(seq.validate().is_empty());
+        assert_eq!(seq.video_tracks[0].clip_at(0).unwrap().duration, secs(5));
+    }
+
+    #[test]
+    fn test_repair_keeps_negative_clip_durations() {
+        let mut seq = sequence_with(vec![TrackItem::from(clip(10, 0, -3))]);
+        assert!(seq.repair().is_empty());
+        assert_eq!(seq.validate().len(), 1);
+    }
+}
@@ -4,29 +4,70 @@ use crate::theme::Theme;
 use crate::widgets;
 use egui::{self, Color32, Pos2, Rect, Rounding, Vec2};
 
+/// Master layouts, in `ChannelLayout::ALL` order.
+pub const MASTER_LAYOUTS: &[&str] = &["Stereo", "5.1", "7.1"];
+
+/// Loudness targets the master can be normalized to (label, LUFS).
+pub const NORMALIZE_TARGETS: &[(&str, f64)] = &[
+    ("-23 LUFS", -23.0),
+    ("-16 LUFS", -16.0),
+    ("-14 LUFS", -14.0),
+];
+
+const TRACK_LABELS: [&str; 3] = ["A1", "A2", "A3"];
+
 // ── State ──────────────────────────────────────────────────────
 
 pub struct AudioMixerState {
     pub master_volume: f32,
+    /// The active sequence's master gain in dB.
+    pub master_gain_db: f32,
+    /// Selected index into `NORMALIZE_TARGETS`.
+    pub normalize_index: usize,
     pub levels: [f32; 3], // A1, A2, A3
     pub loudness_metering: bool,
     pub limiter: bool,
+    /// Selected master layout index into `MASTER_LAYOUTS`.
+    pub layout_index: usize,
+    /// Submix bus names (also their stem names).
+    pub buses: Vec<String>,
+    /// Destination bus per track (`None` = master).
+    pub routes: [Option<usize>; 3],
 }
 
 impl Default for AudioMixerState {
     fn default() -> Self {
         Self {
             master_volume: 80.0,
+            master_gain_db: 0.0,
+            normalize_index: 0,
             levels: [0.0, 0.0, 0.0],
             loudness_metering: true,
             limiter: false,
+            layout_index: 0,
+            buses: vec!["Dialogue".into(), "Music".into(), "Effects".into()],
+            routes: [None; 3],
         }
     }
 }
 
+// ── Actions ────────────────────────────────────────────────────
+
+/// Routing changes the caller should apply to the mixer.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AudioMixerAction {
+    /// Master layout changed (index into `MASTER_LAYOUTS`).
+    SetLayout(usize),
+    /// Track routed to a bus (`None` = master).
+    SetRoute { track: usize, bus: Option<usize> },
+    /// Set the master gain so the mix measures `target` LUFS.
+    NormalizeMaster { target: f64 },
+}
+
 // ── Rendering ──────────────────────────────────────────────────
 
-pub fn show_audio_mixer(ctx: &egui::Context, state: &mut AudioMixerState) {
+pub fn show_audio_mixer(ctx: &egui::Context, state: &mut AudioMixerState) -> Vec<AudioMixerAction> {
+    let mut actions = Vec::new();
     egui::Area::new(egui::Id::new("audio_mixer_panel"))
         .order(egui::Order::Foreground)
         .anchor(egui::Align2::RIGHT_BOTTOM, Vec2::new(-20.0, -20.0))
@@ -66,15 +107,41 @@ pub fn show_audio_mixer(ctx: &egui::Context, state: &mut AudioMixerState) {
 
                     // Master slider
                     mixer_slider(ui, "Master", &mut state.master_volume, Theme::green());
+                    master_gain_row(ui, state, &mut actions);
 
                     // Level meters
-                    let labels = ["A1", "A2", "A3"];
-                    for (i, label) in labels.iter().enumerate() {
+                    for (i, label) in TRACK_LABELS.iter().enumerate() {
                         level_meter(ui, label, state.levels[i]);
                     }
 
                     ui.add_space(Theme::SPACE_XS);
 
+                    // Master layout
+                    ui.horizontal(|ui| {
+                        ui.spacing_mut().item_spacing = Vec2::new(6.0, 0.0);
+                        ui.label(
+                            egui::RichText::new("Output")
+                                .size(Theme::FONT_XS)
+                                .color(Theme::t3()),
+                        );
+                        let before = state.layout_index;
+                        egui::ComboBox::from_id_salt("mixer_layout_combo")
+                            .selected_text(MASTER_LAYOUTS[state.layout_index])
+                            .width(80.0)
+                            .show_ui(ui, |ui| {
+                                for (i, label) in MASTER_LAYOUTS.iter().enumerate() {
+                                    ui.selectable_value(&mut state.layout_index, i, *label);
+                                }
+                            });
+                        if state.layout_index != before {
+                            actions.push(AudioMixerAction::SetLayout(state.layout_index));
+                        }
+                    });
+
+                    routing_matrix(ui, state, &mut actions);
+
+                    ui.add_space(Theme::SPACE_XS);
+
                     // Toggles
                     ui.horizontal(|ui| {
                         ui.spacing_mut().item_spacing = Vec2::new(Theme::SPACE_SM, 0.0);
@@ -110,6 +177,119 @@ pub fn show_audio_mixer(ctx: &egui::Context, state: &mut AudioMixerState) {
                     });
                 });
         });
+    actions
+}
+
+/// Master gain readout with loudness normalization to a chosen target.
+fn master_gain_row(
+    ui: &mut egui::Ui,
+    state: &mut AudioMixerState,
+    actions: &mut Vec<AudioMixerAction>,
+) {
+    ui.horizontal(|ui| {
+        ui.spacing_mut().item_spacing = Vec2::new(6.0, 0.0);
+        ui.label(
+            egui::RichText::new(format!("Gain {:+.1} dB", state.master_gain_db))
+                .size(Theme::FONT_XS)
+                .color(Theme::t3()),
+        );
+        egui::ComboBox::from_id_salt("mixer_normalize_combo")
+            .selected_text(NORMALIZE_TARGETS[state.normalize_index].0)
+            .width(70.0)
+            .show_ui(ui, |ui| {
+                for (i, (label, _)) in NORMALIZE_TARGETS.iter().enumerate() {
+                    ui.selectable_value(&mut state.normalize_index, i, *label);
+                }
+            });
+        if ui
+            .small_button("Normalize")
+            .on_hover_text("Set the master gain so the mix measures the target loudness")
+            .clicked()
+        {
+            actions.push(AudioMixerAction::NormalizeMaster {
+                target: NORMALIZE_TARGETS[state.normalize_index].1,
+            });
+        }
+    });
+}
+
+/// Track-to-bus routing grid: one row per track, one column per
+/// destination (master first); each track feeds exactly one.
+fn routing_matrix(
+    ui: &mut egui::Ui,
+    state: &mut AudioMixerState,
+    actions: &mut Vec<AudioMixerAction>,
+) {
+    const CELL: f32 = 16.0;
+    let destinations: Vec<(Option<usize>, String)> = std::iter::once((None, "M".to_string()))
+        .chain(state.buses.iter().enumerate().map(|(i, name)| {
+            let initial = name.chars().next().unwrap_or('?').to_ascii_uppercase();
+            (Some(i), initial.to_string())
+        }))
+        .collect();
+
+    ui.label(
+        egui::RichText::new("ROUTING")
+            .size(Theme::FONT_XS)
+            .color(Theme::t3())
+            .strong(),
+    );
+
+    // Column headers
+    ui.horizontal(|ui| {
+        ui.spacing_mut().item_spacing = Vec2::new(2.0, 0.0);
+        ui.allocate_space(Vec2::new(20.0, CELL));
+        for ((_, label), name) in destinations
+            .iter()
+            .zip(std::iter::once("Master").chain(state.buses.iter().map(String::as_str)))
+        {
+            let (rect, resp) = ui.allocate_exact_size(Vec2::splat(CELL), egui::Sense::hover());
+            ui.painter().text(
+                rect.center(),
+                egui::Align2::CENTER_CENTER,
+                label,
+                egui::FontId::proportional(Theme::FONT_XS),
+                Theme::t3(),
+            );
+            resp.on_hover_text(name);
+        }
+    });
+
+    for (track, label) in TRACK_LABELS.iter().enumerate() {
+        ui.horizontal(|ui| {
+            ui.spacing_mut().item_spacing = Vec2::new(2.0, 0.0);
+            ui.allocate_ui(Vec2::new(20.0, CELL), |ui| {
+                ui.label(
+                    egui::RichText::new(*label)
+                        .size(Theme::FONT_XS)
+                        .color(Theme::t4()),
+                );
+            });
+            for (destination, _) in &destinations {
+                let (rect, resp) = ui.allocate_exact_size(Vec2::splat(CELL), egui::Sense::click());
+                let selected = state.routes[track] == *destination;
+                let cell = rect.shrink(2.0);
+                if selected {
+                    ui.painter()
+                        .rect_filled(cell, Rounding::same(3.0), Theme::green());
+                } else {
+                    let fill = if resp.hovered() {
+                        Theme::white_10()
+                    } else {
+                        Theme::white_04()
+                    };
+                    ui.painter().rect_filled(cell, Rounding::same(3.0), fill);
+                }
+                if resp.clicked() && !selected {
+                    state.routes[track] = *destination;
+                    actions.push(AudioMixerAction::SetRoute {
+                        track,
+                        bus: *destination,
+                    });
+                }
+            }
+        });
+    }
 }
 
 fn mixer_slider(ui: &mut egui::Ui, label: &str, value: &mut f32, accent: Color32) {
@@ -65,6 +65,18 @@ pub const COMMANDS: &[Command] = &[
         category: CommandCategory::Edit,
         icon: "\u{26A1}",
     },
+    Command {
+        name: "New Sequence",
+        shortcut: "\u{2014}",
+        category: CommandCategory::File,
+        icon: "+",
+    },
+    Command {
+        name: "Next Sequence",
+        shortcut: "\u{2014}",
+        category: CommandCategory::View,
+        icon: "\u{21C4}",
+    },
     Command {
         name: "Toggle Audio Mixer",
         shortcut: "\u{2318}M",
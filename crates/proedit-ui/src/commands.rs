@@ -368,6 +368,13 @@ impl CommandRegistry {
             shortcut: Some(Shortcut::new(Modifiers::NONE, "End")),
             contexts: &[Global],
         });
+        self.register(Command {
+            id: "transport.record_voice_over",
+            name: "Record Voice-Over",
+            category: "Transport",
+            shortcut: Some(Shortcut::new(Modifiers::SHIFT, "R")),
+            contexts: &[Timeline],
+        });
 
         // ── Timeline commands ────────────────────────
         self.register(Command {
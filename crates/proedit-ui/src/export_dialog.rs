@@ -1,8 +1,9 @@
 //! Export dialog — format selection, output path, progress bar, and cancel.
 
 use crate::theme::Theme;
+use crate::widgets;
 use egui::{self, Rounding, Stroke, Vec2};
-use proedit_media::export::ExportFormat;
+use proedit_media::export::{ComplianceReport, ExportFormat, LoudnessTarget};
 use std::path::PathBuf;
 
 // ── Format preset labels ────────────────────────────────────────
@@ -19,6 +20,18 @@ fn format_from_index(index: usize) -> ExportFormat {
     }
 }
 
+const LOUDNESS_TARGETS: &[&str] = &["None", "EBU R128 (-23 LUFS)", "Streaming (-14 LUFS)"];
+
+fn loudness_target_from_index(index: usize) -> Option<LoudnessTarget> {
+    match index {
+        1 => Some(LoudnessTarget::ebu_r128()),
+        2 => Some(LoudnessTarget::streaming()),
+        _ => None,
+    }
+}
+
+const AUDIO_CHANNELS: &[(&str, u16)] = &[("Stereo", 2), ("5.1", 6), ("7.1", 8)];
+
 // ── State ───────────────────────────────────────────────────────
 
 /// Persistent state for the export dialog.
@@ -34,6 +47,14 @@ pub struct ExportDialogState {
     pub progress: Option<f32>,
     /// Whether an export is currently running.
     pub exporting: bool,
+    /// Selected loudness target index (0 = none).
+    pub loudness_index: usize,
+    /// Loudness compliance of the last export, if a target was set.
+    pub compliance: Option<ComplianceReport>,
+    /// Selected audio channel layout index (0 = stereo).
+    pub channels_index: usize,
+    /// Whether to also write one WAV stem per submix bus.
+    pub stems: bool,
 }
 
 // ── Actions ─────────────────────────────────────────────────────
@@ -45,6 +66,9 @@ pub enum ExportDialogAction {
     StartExport {
         format: ExportFormat,
         output_path: PathBuf,
+        loudness_target: Option<LoudnessTarget>,
+        /// Also write per-bus stems.
+        stems: bool,
     },
     /// User clicked "Cancel" during an active export.
     Cancel,
@@ -120,6 +144,57 @@ pub fn show_export_dialog(
                 }
             });
 
+            ui.add_space(Theme::SPACE_XS);
+
+            // ── Loudness target ──────────────────────────
+            ui.label(
+                egui::RichText::new("LOUDNESS TARGET")
+                    .size(Theme::FONT_XS)
+                    .color(Theme::t3())
+                    .strong(),
+            );
+
+            egui::ComboBox::from_id_salt("export_loudness_combo")
+                .selected_text(LOUDNESS_TARGETS[state.loudness_index])
+                .width(ui.available_width())
+                .show_ui(ui, |ui| {
+                    for (i, label) in LOUDNESS_TARGETS.iter().enumerate() {
+                        ui.selectable_value(&mut state.loudness_index, i, *label);
+                    }
+                });
+
+            ui.add_space(Theme::SPACE_XS);
+
+            // ── Audio channels and stems ─────────────────
+            ui.label(
+                egui::RichText::new("AUDIO")
+                    .size(Theme::FONT_XS)
+                    .color(Theme::t3())
+                    .strong(),
+            );
+
+            ui.horizontal(|ui| {
+                ui.spacing_mut().item_spacing = Vec2::new(Theme::SPACE_SM, 0.0);
+                egui::ComboBox::from_id_salt("export_channels_combo")
+                    .selected_text(AUDIO_CHANNELS[state.channels_index].0)
+                    .width(120.0)
+                    .show_ui(ui, |ui| {
+                        for (i, (label, _)) in AUDIO_CHANNELS.iter().enumerate() {
+                            ui.selectable_value(&mut state.channels_index, i, *label);
+                        }
+                    });
+                if widgets::toggle_switch(ui, state.stems) {
+                    state.stems = !state.stems;
+                }
+                ui.label(egui::RichText::new("Bus stems").size(Theme::FONT_XS).color(
+                    if state.stems {
+                        Theme::t1()
+                    } else {
+                        Theme::t3()
+                    },
+                ));
+            });
+
             ui.add_space(Theme::SPACE_SM);
             Theme::draw_separator(ui);
             ui.add_space(Theme::SPACE_SM);
@@ -137,6 +212,22 @@ pub fn show_export_dialog(
                 ui.add_space(Theme::SPACE_SM);
             }
 
+            // ── Loudness compliance ──────────────────────
+            if let Some(report) = &state.compliance {
+                let color = if report.passed() {
+                    Theme::green()
+                } else {
+                    Theme::red()
+                };
+                ui.label(
+                    egui::RichText::new(report.to_string())
+                        .size(Theme::FONT_XS)
+                        .color(color)
+                        .family(egui::FontFamily::Monospace),
+                );
+                ui.add_space(Theme::SPACE_SM);
+            }
+
             // ── Format info ──────────────────────────────
             let fmt = format_from_index(state.format_index);
             ui.label(
@@ -179,11 +270,14 @@ pub fn show_export_dialog(
                     .rounding(Rounding::same(Theme::RADIUS))
                     .min_size(Vec2::new(100.0, 32.0));
                     if ui.add_enabled(can_export, export_btn).clicked() {
-                        let format = format_from_index(state.format_index);
+                        let mut format = format_from_index(state.format_index);
+                        format.audio_channels = AUDIO_CHANNELS[state.channels_index].1;
                         let output_path = PathBuf::from(&state.output_path);
                         actions.push(ExportDialogAction::StartExport {
                             format,
                             output_path,
+                            loudness_target: loudness_target_from_index(state.loudness_index),
+                            stems: state.stems,
                         });
                     }
                 }
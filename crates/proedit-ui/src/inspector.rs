@@ -3,6 +3,7 @@
 use crate::theme::Theme;
 use crate::widgets;
 use egui::{self, Color32, Rounding, Stroke, Vec2};
+use proedit_timeline::StretchMode;
 
 // ── Data ───────────────────────────────────────────────────────
 
@@ -28,6 +29,10 @@ pub struct InspectorClip {
     pub speed: f32,
     pub in_point: f32,
     pub out_point: f32,
+    /// How speed and pitch apply to the audio (audio clips)
+    pub stretch: StretchMode,
+    /// Pitch shift in semitones (audio clips)
+    pub pitch_semitones: f32,
     // Audio (only for audio clips)
     pub volume: f32,
     pub pan: f32,
@@ -39,6 +44,7 @@ pub enum ClipType {
     Video,
     Audio,
     Gfx,
+    Adjustment,
 }
 
 impl InspectorClip {
@@ -63,6 +69,8 @@ impl InspectorClip {
             speed: 100.0,
             in_point: 0.0,
             out_point,
+            stretch: StretchMode::default(),
+            pitch_semitones: 0.0,
             volume: 80.0,
             pan: 0.0,
             eq_enabled: false,
@@ -123,6 +131,8 @@ pub fn show_inspector(ui: &mut egui::Ui, state: &mut InspectorState) -> Vec<Insp
         clip.rotation,
         clip.opacity,
         clip.speed,
+        clip.stretch,
+        clip.pitch_semitones,
     );
 
     ui.spacing_mut().item_spacing = Vec2::new(0.0, 2.0);
@@ -211,6 +221,37 @@ pub fn show_inspector(ui: &mut egui::Ui, state: &mut InspectorState) -> Vec<Insp
                     0.0..=max_point,
                     Theme::accent(),
                 );
+                if clip.clip_type == ClipType::Audio {
+                    ui.horizontal(|ui| {
+                        ui.spacing_mut().item_spacing = Vec2::new(Theme::SPACE_SM, 0.0);
+                        ui.label(
+                            egui::RichText::new("Audio")
+                                .size(Theme::FONT_XS)
+                                .color(Theme::t3()),
+                        );
+                        egui::ComboBox::from_id_salt("inspector_stretch_combo")
+                            .selected_text(clip.stretch.display_name())
+                            .width(110.0)
+                            .show_ui(ui, |ui| {
+                                for mode in StretchMode::ALL {
+                                    ui.selectable_value(
+                                        &mut clip.stretch,
+                                        mode,
+                                        mode.display_name(),
+                                    );
+                                }
+                            });
+                    });
+                    if clip.stretch == StretchMode::PitchOnly {
+                        widgets::themed_slider(
+                            ui,
+                            "Pitch",
+                            &mut clip.pitch_semitones,
+                            -12.0..=12.0,
+                            Theme::accent(),
+                        );
+                    }
+                }
             });
 
             // ── Audio section (only for audio clips) ───────
@@ -312,6 +353,8 @@ pub fn show_inspector(ui: &mut egui::Ui, state: &mut InspectorState) -> Vec<Insp
         clip.rotation,
         clip.opacity,
         clip.speed,
+        clip.stretch,
+        clip.pitch_semitones,
     );
     if prev != curr {
         actions.push(InspectorAction::PropertyChanged {
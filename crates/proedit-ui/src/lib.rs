@@ -19,6 +19,7 @@ pub mod curve_editor;
 pub mod effects_panel;
 pub mod export_dialog;
 pub mod inspector;
+pub mod load_report;
 pub mod media_browser;
 pub mod snapping;
 pub mod theme;
@@ -29,7 +30,7 @@ pub mod viewer;
 pub mod widgets;
 
 // Re-exports for main app convenience
-pub use audio_mixer::{show_audio_mixer, AudioMixerState};
+pub use audio_mixer::{show_audio_mixer, AudioMixerAction, AudioMixerState, MASTER_LAYOUTS};
 pub use color_wheels::{show_color_wheels, ColorWheelsState};
 pub use command_palette::{show_command_palette, CommandPaletteState};
 pub use commands::CommandRegistry;
@@ -37,6 +38,7 @@ pub use curve_editor::{show_curve_editor, CurveEditorAction, CurveEditorState};
 pub use effects_panel::{show_effects_panel, EffectsPanelState};
 pub use export_dialog::{show_export_dialog, ExportDialogAction, ExportDialogState};
 pub use inspector::{show_inspector, InspectorAction, InspectorClip, InspectorState};
+pub use load_report::{show_load_report, LoadReportState};
 pub use media_browser::{show_media_browser, MediaBrowserAction, MediaBrowserState};
 pub use snapping::SnappingEngine;
 pub use theme::Theme;
@@ -0,0 +1,92 @@
+//! Load report — what validation found and repaired in an opened project.
+
+use crate::theme::Theme;
+use egui::{self, Vec2};
+use proedit_timeline::{Diagnostic, Severity};
+
+/// Persistent state for the load report window.
+#[derive(Default)]
+pub struct LoadReportState {
+    /// Whether the window is visible.
+    pub open: bool,
+    /// Name of the project the findings belong to.
+    pub project_name: String,
+    /// Problems fixed while loading.
+    pub repaired: Vec<Diagnostic>,
+    /// Problems left in the project.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl LoadReportState {
+    /// Show what was `repaired` and the remaining `diagnostics` for
+    /// `project_name`; stays closed if there are neither.
+    pub fn report(
+        &mut self,
+        project_name: impl Into<String>,
+        repaired: Vec<Diagnostic>,
+        diagnostics: Vec<Diagnostic>,
+    ) {
+        self.project_name = project_name.into();
+        self.open = !repaired.is_empty() || !diagnostics.is_empty();
+        self.repaired = repaired;
+        self.diagnostics = diagnostics;
+    }
+}
+
+/// Show the load report as a floating egui window.
+pub fn show_load_report(ctx: &egui::Context, state: &mut LoadReportState) {
+    if !state.open {
+        return;
+    }
+
+    let mut still_open = state.open;
+    let mut dismissed = false;
+    egui::Window::new("Project Problems")
+        .open(&mut still_open)
+        .resizable(false)
+        .collapsible(false)
+        .anchor(egui::Align2::CENTER_CENTER, Vec2::ZERO)
+        .frame(Theme::glass_frame())
+        .show(ctx, |ui| {
+            ui.set_width(420.0);
+            ui.spacing_mut().item_spacing = Vec2::new(0.0, Theme::SPACE_SM);
+            ui.label(
+                egui::RichText::new(format!(
+                    "{} had {} problem(s) that may misrender or break edits; {} were repaired.",
+                    state.project_name,
+                    state.repaired.len() + state.diagnostics.len(),
+                    state.repaired.len()
+                ))
+                .size(Theme::FONT_SM)
+                .color(Theme::t2()),
+            );
+
+            egui::ScrollArea::vertical()
+                .max_height(240.0)
+                .show(ui, |ui| {
+                    for diagnostic in &state.repaired {
+                        ui.label(
+                            egui::RichText::new(format!("Repaired: {diagnostic}"))
+                                .size(Theme::FONT_XS)
+                                .color(Theme::t2()),
+                        );
+                    }
+                    for diagnostic in &state.diagnostics {
+                        let color = match diagnostic.severity() {
+                            Severity::Error => Theme::red(),
+                            Severity::Warning => Theme::amber(),
+                        };
+                        ui.label(
+                            egui::RichText::new(diagnostic.to_string())
+                                .size(Theme::FONT_XS)
+                                .color(color),
+                        );
+                    }
+                });
+
+            if ui.button("OK").clicked() {
+                dismissed = true;
+            }
+        });
+    state.open = still_open && !dismissed;
+}
@@ -217,6 +217,9 @@ mod tests {
             dur: 30.0,
             track: 0,
             clip_type: crate::timeline::ClipKind::Video,
+            effects: Vec::new(),
+            generator: None,
+            transition: None,
         });
         let points = SnappingEngine::collect_snap_points(&state);
         // Playhead + 2 clip edges
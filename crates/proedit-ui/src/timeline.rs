@@ -5,6 +5,8 @@ use crate::theme::Theme;
 use crate::trim::{apply_trim, hit_test_trim_handle, trim_cursor, ClipDragState, TrimState};
 use crate::widgets;
 use egui::{self, Color32, Pos2, Rect, Rounding, Stroke, Vec2};
+use proedit_core::{EasingCurve, ParamValues};
+use proedit_timeline::{EffectInstance, Generator};
 use std::collections::HashMap;
 
 // ── Clip data ────────────────────────────────────────────────────
@@ -18,6 +20,24 @@ pub struct TimelineClip {
     pub dur: f32,     // duration in frames
     pub track: usize, // 0-5 (V3,V2,V1,A1,A2,A3)
     pub clip_type: ClipKind,
+    /// Video effects on the clip's picture; for an adjustment layer, on
+    /// everything beneath it
+    pub effects: Vec<EffectInstance>,
+    /// Synthesized picture of a generator clip, timed from the clip's start
+    pub generator: Option<Box<Generator>>,
+    /// Transition from the clip before it on the same lane
+    pub transition: Option<ClipTransition>,
+}
+
+/// A transition into a clip, played over the `dur` frames before its
+/// start; the outgoing clip ends where it begins.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClipTransition {
+    pub name: String,
+    pub dur: f32,
+    /// Values of the transition's parameters, by name
+    pub params: ParamValues,
+    pub easing: EasingCurve,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -25,6 +45,8 @@ pub enum ClipKind {
     Video,
     Audio,
     Gfx,
+    /// Effects only, applied to the tracks below
+    Adjustment,
 }
 
 const TRACK_NAMES: &[&str] = &["V3", "V2", "V1", "A1", "A2", "A3"];
@@ -66,6 +88,8 @@ pub struct TimelineState {
     pub snapping: SnappingEngine,
     pub track_locked: [bool; TRACK_COUNT],
     pub track_solo: [bool; TRACK_COUNT],
+    /// Video effects applied to every clip on each track
+    pub track_effects: [Vec<EffectInstance>; TRACK_COUNT],
     /// Cached waveform data per clip ID: Vec of [min, max] pairs for display.
     pub waveform_cache: HashMap<usize, Vec<[f32; 2]>>,
 }
@@ -92,6 +116,7 @@ impl Default for TimelineState {
             snapping: SnappingEngine::new(),
             track_locked: [false; TRACK_COUNT],
             track_solo: [false; TRACK_COUNT],
+            track_effects: Default::default(),
             waveform_cache: HashMap::new(),
         }
     }
@@ -110,11 +135,15 @@ pub enum TimelineAction {
     ZoomOut,
     TrimClip {
         clip_id: usize,
+        old_start: f32,
+        old_dur: f32,
         new_start: f32,
         new_dur: f32,
     },
     DragClip {
         clip_id: usize,
+        old_start: f32,
+        old_track: usize,
         new_start: f32,
         new_track: usize,
     },
@@ -605,6 +634,7 @@ pub fn show_timeline(ui: &mut egui::Ui, state: &mut TimelineState) -> Vec<Timeli
                                     state.drag_state = Some(ClipDragState {
                                         clip_id: clip.id,
                                         offset_frame: frame - clip.start,
+                                        original_start: clip.start,
                                         original_track: clip.track,
                                         snap_indicator: None,
                                     });
@@ -692,6 +722,8 @@ pub fn show_timeline(ui: &mut egui::Ui, state: &mut TimelineState) -> Vec<Timeli
                         if let Some(clip) = state.clips.iter().find(|c| c.id == trim.clip_id) {
                             actions.push(TimelineAction::TrimClip {
                                 clip_id: trim.clip_id,
+                                old_start: trim.original_clip_start,
+                                old_dur: trim.original_clip_dur,
                                 new_start: clip.start,
                                 new_dur: clip.dur,
                             });
@@ -701,6 +733,8 @@ pub fn show_timeline(ui: &mut egui::Ui, state: &mut TimelineState) -> Vec<Timeli
                         if let Some(clip) = state.clips.iter().find(|c| c.id == drag.clip_id) {
                             actions.push(TimelineAction::DragClip {
                                 clip_id: drag.clip_id,
+                                old_start: drag.original_start,
+                                old_track: drag.original_track,
                                 new_start: clip.start,
                                 new_track: clip.track,
                             });
@@ -116,6 +116,7 @@ pub fn trim_cursor(edge: TrimEdge) -> CursorIcon {
 pub struct ClipDragState {
     pub clip_id: usize,
     pub offset_frame: f32,
+    pub original_start: f32,
     pub original_track: usize,
     pub snap_indicator: Option<f32>,
 }
@@ -135,6 +136,9 @@ mod tests {
             dur: 50.0,
             track: 0,
             clip_type: ClipKind::Video,
+            effects: Vec::new(),
+            generator: None,
+            transition: None,
         }
     }
 